@@ -28,7 +28,7 @@ struct AppState {
 }
 
 impl AuthState<WrappedDatabase> for AppState {
-    fn auth_provider(&self) -> MutexGuard<AuthProvider<WrappedDatabase>> {
+    fn auth_provider(&self) -> MutexGuard<'_, AuthProvider<WrappedDatabase>> {
         self.auth.lock().unwrap()
     }
 }
@@ -178,7 +178,7 @@ fn signup(request: Request, state: Arc<AppState>) -> Response {
 }
 
 /// Sign out API endpoint handler.
-fn sign_out(_: Request, state: Arc<AppState>, uid: String) -> Response {
+fn sign_out(request: Request, state: Arc<AppState>, uid: String) -> Response {
     // Use the auth provider to invalidate the user's session.
     let mut provider = state.auth.lock().unwrap();
     provider.invalidate_user_session(uid);
@@ -186,7 +186,7 @@ fn sign_out(_: Request, state: Arc<AppState>, uid: String) -> Response {
     // Return a response which redirects the client to the homepage as well as resets the cookie.
     Response::empty(StatusCode::Found)
         .with_bytes("OK")
-        .with_header(HeaderType::Location, "/")
+        .with_header(HeaderType::Location, homepage_url(&request))
         .with_cookie(
             SetCookie::new("HumphreyToken", "deleted")
                 .with_path("/")
@@ -195,7 +195,7 @@ fn sign_out(_: Request, state: Arc<AppState>, uid: String) -> Response {
 }
 
 /// Delete account API endpoint handler.
-fn delete_account(_: Request, state: Arc<AppState>, uid: String) -> Response {
+fn delete_account(request: Request, state: Arc<AppState>, uid: String) -> Response {
     // Remove the user from the users section of the database.
     {
         let mut db = state.db.lock().unwrap();
@@ -209,7 +209,7 @@ fn delete_account(_: Request, state: Arc<AppState>, uid: String) -> Response {
     // Return a response which redirects the client to the homepage as well as resets the cookie.
     Response::empty(StatusCode::Found)
         .with_bytes("OK")
-        .with_header(HeaderType::Location, "/")
+        .with_header(HeaderType::Location, homepage_url(&request))
         .with_cookie(
             SetCookie::new("HumphreyToken", "deleted")
                 .with_path("/")
@@ -231,3 +231,11 @@ fn profile(_: Request, state: Arc<AppState>, uid: String) -> Response {
         .with_header(HeaderType::ContentType, "text/html")
         .with_bytes(html)
 }
+
+/// Builds the absolute URL of the homepage to redirect to, falling back to a relative URL if the
+///   request has no valid `Host` header.
+fn homepage_url(request: &Request) -> String {
+    request
+        .absolute_url("/", None, &[])
+        .unwrap_or_else(|| "/".into())
+}
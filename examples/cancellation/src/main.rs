@@ -0,0 +1,54 @@
+use humphrey::http::{Request, Response, StatusCode};
+use humphrey::App;
+
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let app: App<()> = App::new().with_stateless_route("/primes/*", primes_handler);
+    app.run("0.0.0.0:80")?;
+
+    Ok(())
+}
+
+/// Counts the primes below the number at the end of the path, giving up if the client disconnects,
+///   for example by closing the browser tab, before the count is finished.
+fn primes_handler(request: Request) -> Response {
+    let limit: u64 = match request.uri.trim_start_matches("/primes/").parse() {
+        Ok(limit) => limit,
+        Err(_) => return Response::new(StatusCode::BadRequest, "Invalid number"),
+    };
+
+    let cancellation = request.cancellation_token();
+    let start = Instant::now();
+    let mut count = 0;
+
+    for n in 2..limit {
+        if cancellation.is_cancelled() {
+            println!(
+                "Gave up counting primes below {} after {:?} at {}",
+                limit,
+                start.elapsed(),
+                n
+            );
+
+            return Response::empty(StatusCode::ServiceUnavailable);
+        }
+
+        if is_prime(n) {
+            count += 1;
+        }
+
+        // Slow the work down so that there is time to disconnect
+        std::thread::sleep(Duration::from_micros(1));
+    }
+
+    Response::new(
+        StatusCode::OK,
+        format!("There are {} primes below {}", count, limit),
+    )
+}
+
+fn is_prime(n: u64) -> bool {
+    (2..).take_while(|d| d * d <= n).all(|d| !n.is_multiple_of(d))
+}
@@ -22,8 +22,7 @@ enum AppPhase {
 }
 
 enum ClientMessageKind {
-  Register,
-  Chat
+  Chat = 1
 }
 
 enum ServerMessageKind {
@@ -71,24 +70,20 @@ export class App extends Component<{}, AppState> {
   connect(username: string) {
     this.setState({ phase: AppPhase.Connecting });
 
+    // The room is taken from the URL hash, e.g. `/#room1`, and defaults to "general"
+    let room = encodeURIComponent(window.location.hash.slice(1) || "general");
     let websocketAddr = (window.location.protocol === "https:" ? "wss" : "ws")
-      + `://${window.location.host}/ws`;
+      + `://${window.location.host}/ws/${room}?name=${encodeURIComponent(username)}`;
 
     this.ws = new WebSocket(websocketAddr);
-    this.ws.onmessage = e => this.recvId(e, username);
+    this.ws.onmessage = this.recvId;
   }
 
-  recvId(e: MessageEvent, username: string) {
+  recvId(e: MessageEvent) {
     let incomingMessage: ServerMessage = JSON.parse(e.data);
 
-    let message: ClientMessage = {
-      kind: ClientMessageKind.Register,
-      message: username
-    };
-
     this.setState({ id: incomingMessage.senderId, phase: AppPhase.Connected });
     this.ws!.onmessage = this.recvMessage;
-    this.ws!.send(JSON.stringify(message));
   }
 
   recvMessage(e: MessageEvent) {
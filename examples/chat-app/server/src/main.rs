@@ -10,15 +10,16 @@ use humphrey::App;
 use humphrey_json::error::ParseError;
 use humphrey_json::prelude::*;
 
-use humphrey_ws::async_app::{AsyncStream, AsyncWebsocketApp};
-use humphrey_ws::handler::async_websocket_handler;
+use humphrey_ws::async_app::{AsyncSender, AsyncStream, AsyncWebsocketApp};
+use humphrey_ws::context::ContextOptions;
+use humphrey_ws::handler::path_aware_async_websocket_handler;
 use humphrey_ws::message::Message;
 
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::atomic::AtomicUsize;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, OnceLock, RwLock};
 use std::thread::spawn;
 
 static NEXT_UID: AtomicUsize = AtomicUsize::new(1);
@@ -26,6 +27,7 @@ static NEXT_UID: AtomicUsize = AtomicUsize::new(1);
 #[derive(Default)]
 pub struct State {
     users: RwLock<HashMap<SocketAddr, User>>,
+    sender: OnceLock<AsyncSender>,
 }
 
 fn main() {
@@ -34,6 +36,12 @@ fn main() {
         .with_connect_handler(connect_handler)
         .with_disconnect_handler(disconnect_handler);
 
+    websocket_app
+        .get_state()
+        .sender
+        .set(websocket_app.sender())
+        .ok();
+
     let mut args = std::env::args();
 
     let client_dir: &'static str = Box::leak(Box::new(if let Some(path) = args.nth(1) {
@@ -51,9 +59,12 @@ fn main() {
 
     let humphrey_app: App<()> = App::new()
         .with_path_aware_route("/*", serve_dir(client_dir))
-        .with_websocket_route(
-            "/ws",
-            async_websocket_handler(websocket_app.connect_hook().unwrap()),
+        .with_path_aware_websocket_route(
+            "/ws/*",
+            path_aware_async_websocket_handler(
+                websocket_app.connect_hook().unwrap(),
+                ContextOptions::new().with_headers(false),
+            ),
         );
 
     spawn(move || humphrey_app.run("0.0.0.0:80").unwrap());
@@ -62,22 +73,51 @@ fn main() {
 }
 
 fn connect_handler(stream: AsyncStream, state: Arc<State>) {
+    // The room and name are taken from the URL the client connected to, e.g. `/ws/general?name=Bob`
+    let context = stream.context();
+    let room = context
+        .uri
+        .strip_prefix("/ws/")
+        .filter(|room| !room.is_empty())
+        .unwrap_or("general")
+        .to_string();
+    let name = context
+        .query_param("name")
+        .unwrap_or_else(|| "Anonymous".to_string());
+
     let user = User {
         id: NEXT_UID.fetch_add(1, std::sync::atomic::Ordering::SeqCst),
-        name: "".to_string(),
-        loaded: false,
+        name,
+        room,
     };
 
-    let message = ServerMessage {
+    let id_message = ServerMessage {
         kind: ServerMessageKind::Id,
         message: None,
         sender_id: user.id,
         sender_name: None,
     };
 
-    stream.send(Message::new(humphrey_json::to_string(&message)));
+    let join_message = ServerMessage {
+        kind: ServerMessageKind::Join,
+        message: None,
+        sender_id: user.id,
+        sender_name: Some(user.name.clone()),
+    };
+
+    state.set_user(stream.peer_addr(), user.clone());
 
-    state.set_user(stream.peer_addr(), user);
+    let participants_message = json!({
+        "kind": (ServerMessageKind::Participants),
+        "participants": (state.list_users(&user.room))
+    });
+
+    stream.send(Message::new(humphrey_json::to_string(&id_message)));
+    state.send_to_room(
+        &user.room,
+        Message::new(humphrey_json::to_string(&join_message)),
+    );
+    stream.send(Message::new(participants_message.serialize()));
 }
 
 fn disconnect_handler(stream: AsyncStream, state: Arc<State>) {
@@ -92,49 +132,25 @@ fn disconnect_handler(stream: AsyncStream, state: Arc<State>) {
         sender_name: Some(user.name),
     };
 
-    stream.broadcast(Message::new(humphrey_json::to_string(&message)));
+    state.send_to_room(&user.room, Message::new(humphrey_json::to_string(&message)));
 }
 
 fn message_handler(stream: AsyncStream, message: Message, state: Arc<State>) {
-    let mut user = state.get_user(stream.peer_addr()).unwrap();
+    let user = state.get_user(stream.peer_addr()).unwrap();
 
     let client_message: Result<ClientMessage, ParseError> =
         humphrey_json::from_str(&message.text().unwrap());
 
-    let client_message = if let Ok(client_message) = client_message {
-        client_message
-    } else {
-        return;
-    };
-
-    if user.loaded && client_message.kind == ClientMessageKind::Chat {
-        let message = ServerMessage {
-            kind: ServerMessageKind::Chat,
-            message: Some(client_message.message),
-            sender_id: user.id,
-            sender_name: Some(user.name),
-        };
-
-        stream.broadcast(Message::new(humphrey_json::to_string(&message)));
-    } else if client_message.kind == ClientMessageKind::Register {
-        user.name = client_message.message;
-        user.loaded = true;
-
-        state.set_user(stream.peer_addr(), user.clone());
-
-        let broadcast_message = ServerMessage {
-            kind: ServerMessageKind::Join,
-            message: None,
-            sender_id: user.id,
-            sender_name: Some(user.name),
-        };
-
-        let private_message = json!({
-            "kind": (ServerMessageKind::Participants),
-            "participants": (state.list_users())
-        });
-
-        stream.broadcast(Message::new(humphrey_json::to_string(&broadcast_message)));
-        stream.send(Message::new(private_message.serialize()));
-    };
+    if let Ok(client_message) = client_message {
+        if client_message.kind == ClientMessageKind::Chat {
+            let message = ServerMessage {
+                kind: ServerMessageKind::Chat,
+                message: Some(client_message.message),
+                sender_id: user.id,
+                sender_name: Some(user.name),
+            };
+
+            state.send_to_room(&user.room, Message::new(humphrey_json::to_string(&message)));
+        }
+    }
 }
@@ -21,8 +21,7 @@ pub struct ServerMessage {
 #[repr(u8)]
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum ClientMessageKind {
-    Register,
-    Chat,
+    Chat = 1,
 }
 
 #[repr(u8)]
@@ -38,7 +37,6 @@ pub enum ServerMessageKind {
 impl FromJson for ClientMessageKind {
     fn from_json(value: &Value) -> Result<Self, ParseError> {
         match value.as_number().map(|f| f as u8) {
-            Some(0) => Ok(Self::Register),
             Some(1) => Ok(Self::Chat),
             _ => Err(ParseError::TypeError),
         }
@@ -3,18 +3,21 @@ use std::sync::Arc;
 
 use crate::State;
 
+use humphrey_ws::Message;
+
 #[derive(Clone)]
 pub struct User {
     pub id: usize,
     pub name: String,
-    pub loaded: bool,
+    pub room: String,
 }
 
 pub trait UserManager {
     fn get_user(&self, addr: SocketAddr) -> Option<User>;
     fn set_user(&self, addr: SocketAddr, user: User);
     fn remove_user(&self, addr: SocketAddr);
-    fn list_users(&self) -> Vec<String>;
+    fn list_users(&self, room: &str) -> Vec<String>;
+    fn send_to_room(&self, room: &str, message: Message);
 }
 
 impl UserManager for Arc<State> {
@@ -30,12 +33,27 @@ impl UserManager for Arc<State> {
         self.users.write().unwrap().remove(&addr);
     }
 
-    fn list_users(&self) -> Vec<String> {
+    fn list_users(&self, room: &str) -> Vec<String> {
         self.users
             .read()
             .unwrap()
             .values()
+            .filter(|u| u.room == room)
             .map(|u| u.name.clone())
             .collect()
     }
+
+    fn send_to_room(&self, room: &str, message: Message) {
+        let sender = self.sender.get().unwrap();
+
+        for (addr, _) in self
+            .users
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, u)| u.room == room)
+        {
+            sender.send(*addr, message.clone());
+        }
+    }
 }
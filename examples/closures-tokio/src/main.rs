@@ -0,0 +1,93 @@
+use humphrey::http::{Request, Response, StatusCode};
+use humphrey::App;
+
+use std::error::Error;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Configuration for one type of resource, captured by the closures which serve it.
+struct Resource {
+    /// The name of the resource, used in responses.
+    name: &'static str,
+    /// The route matching every item of the resource.
+    route: &'static str,
+    /// The maximum number of items which can be listed at once.
+    page_size: usize,
+}
+
+const RESOURCES: [Resource; 3] = [
+    Resource {
+        name: "users",
+        route: "/users/*",
+        page_size: 10,
+    },
+    Resource {
+        name: "posts",
+        route: "/posts/*",
+        page_size: 25,
+    },
+    Resource {
+        name: "comments",
+        route: "/comments/*",
+        page_size: 100,
+    },
+];
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let blocked: Vec<IpAddr> = vec!["192.0.2.1".parse()?];
+    let site_name = String::from("Closures Example");
+    let requests = Arc::new(AtomicUsize::new(0));
+
+    // Every extension point accepts a `move` closure, so configuration can be captured directly
+    //   instead of being stored in the app's state
+    let mut app: App<()> = App::new()
+        .with_connection_condition(move |stream, _| {
+            stream
+                .peer_addr()
+                .map(|addr| !blocked.contains(&addr.ip()))
+                .unwrap_or(false)
+        })
+        .with_request_condition({
+            let requests = requests.clone();
+            move |_, _| {
+                requests.fetch_add(1, Ordering::SeqCst);
+                None
+            }
+        })
+        .with_error_handler(move |status_code: StatusCode| {
+            let code: u16 = status_code.into();
+            Response::new(status_code, format!("{} - {}", site_name, code))
+        })
+        .with_stateless_route("/stats", move |_| {
+            // The future must own what it uses, so read the count before returning it
+            let count = requests.load(Ordering::SeqCst);
+            async move { Response::new(StatusCode::OK, format!("{} requests served", count)) }
+        });
+
+    // Register the same pair of routes for each resource, each with its own configuration
+    for resource in RESOURCES {
+        app = app
+            .with_route(
+                &format!("/{}", resource.name),
+                move |_: Request, _: Arc<()>| async move {
+                    Response::new(
+                        StatusCode::OK,
+                        format!("Listing up to {} {}", resource.page_size, resource.name),
+                    )
+                },
+            )
+            .with_path_aware_route(
+                resource.route,
+                move |request: Request, _: Arc<()>, route: &'static str| async move {
+                    let id = request.uri.trim_start_matches(route.trim_end_matches('*'));
+                    Response::new(StatusCode::OK, format!("{} with ID {}", resource.name, id))
+                },
+            );
+    }
+
+    app.run("0.0.0.0:80").await?;
+
+    Ok(())
+}
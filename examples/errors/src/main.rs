@@ -0,0 +1,70 @@
+use humphrey::error::Error;
+use humphrey::http::headers::HeaderType;
+use humphrey::http::{Request, Response, StatusCode};
+use humphrey::App;
+
+use humphrey_auth::user::User;
+use humphrey_auth::AuthProvider;
+use humphrey_json::Value;
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+struct AppState {
+    auth: Mutex<AuthProvider<Vec<User>>>,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut auth = AuthProvider::default();
+    let uid = auth.create_user("password")?;
+    let token = auth.create_session(uid)?;
+
+    println!("Add a note with the token {}", token);
+
+    let app = App::new_with_config(
+        32,
+        AppState {
+            auth: Mutex::new(auth),
+        },
+    )
+    .with_route("/api/note", |request, state| {
+        add_note(request, state).unwrap_or_else(|error| {
+            // The error's message is suitable for logging, but the client only sees the status code.
+            eprintln!("Error: {}", error);
+            Response::from(error)
+        })
+    });
+
+    app.run("0.0.0.0:80")?;
+
+    Ok(())
+}
+
+/// Appends the note in the JSON body of the request to the authenticated user's notes file.
+///
+/// Every fallible step uses `?`, converting JSON, authentication and I/O errors into
+///   `humphrey::error::Error`.
+fn add_note(request: Request, state: Arc<AppState>) -> Result<Response, Error> {
+    let token = request
+        .headers
+        .get(HeaderType::Authorization)
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    let uid = state.auth.lock().unwrap().get_uid_by_token(token)?;
+
+    let body = String::from_utf8(request.content.unwrap_or_default()).unwrap_or_default();
+    let json = Value::parse(body)?;
+    let note = json
+        .get("note")
+        .and_then(|note| note.as_str())
+        .ok_or(humphrey_json::error::ParseError::MissingField)?;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(format!("{}.txt", uid))?;
+    writeln!(file, "{}", note)?;
+
+    Ok(Response::new(StatusCode::OK, "Note added"))
+}
@@ -0,0 +1,52 @@
+//! The functions which the WebAssembly client exports to JavaScript.
+//!
+//! Only the types-only build of Humphrey is used here, since there are no sockets or threads in
+//!   the browser.
+
+use crate::Todo;
+
+use humphrey::http::StatusCode;
+
+/// Allocates a buffer of the given length for JavaScript to write a response body into.
+#[no_mangle]
+pub extern "C" fn alloc(len: usize) -> *mut u8 {
+    let mut buf = Vec::with_capacity(len);
+    let ptr = buf.as_mut_ptr();
+    std::mem::forget(buf);
+    ptr
+}
+
+/// Frees a buffer allocated by `alloc`.
+///
+/// # Safety
+/// The buffer must have been allocated by `alloc` with the same length, and not freed already.
+#[no_mangle]
+pub unsafe extern "C" fn dealloc(ptr: *mut u8, len: usize) {
+    drop(Vec::from_raw_parts(ptr, 0, len));
+}
+
+/// Counts the tasks which are not done in the response to `GET /api/todos`, given its status code
+///   and a buffer containing its body.
+///
+/// Returns -1 if the request failed or the body is not a list of tasks.
+///
+/// # Safety
+/// The buffer must have been allocated by `alloc` with at least the given length, all of which has
+///   been written to.
+#[no_mangle]
+pub unsafe extern "C" fn remaining(status: u16, ptr: *const u8, len: usize) -> i32 {
+    if StatusCode::try_from(status) != Ok(StatusCode::OK) {
+        return -1;
+    }
+
+    let body = std::slice::from_raw_parts(ptr, len);
+    let todos: Vec<Todo> = match std::str::from_utf8(body)
+        .ok()
+        .and_then(|body| humphrey_json::from_str(body).ok())
+    {
+        Some(todos) => todos,
+        None => return -1,
+    };
+
+    todos.iter().filter(|todo| !todo.done).count() as i32
+}
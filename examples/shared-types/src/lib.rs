@@ -0,0 +1,21 @@
+//! The types shared by the server and the WebAssembly client.
+//!
+//! Build the client with `cargo build --lib --release --target wasm32-unknown-unknown`, and copy
+//!   `target/wasm32-unknown-unknown/release/shared_types.wasm` into the `static` directory.
+
+pub mod client;
+
+use humphrey_json::prelude::*;
+
+/// A task on the to-do list, as sent between the server and the client.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Todo {
+    pub title: String,
+    pub done: bool,
+}
+
+json_map! {
+    Todo,
+    title => "title",
+    done => "done"
+}
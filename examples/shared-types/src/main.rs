@@ -0,0 +1,54 @@
+use humphrey::handlers::serve_dir;
+use humphrey::http::method::Method;
+use humphrey::http::{Request, Response, StatusCode};
+use humphrey::App;
+
+use shared_types::Todo;
+
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+type AppState = Mutex<Vec<Todo>>;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let todos = vec![
+        Todo {
+            title: "Share types with the client".into(),
+            done: true,
+        },
+        Todo {
+            title: "Build the client for WebAssembly".into(),
+            done: false,
+        },
+    ];
+
+    let app: App<AppState> = App::new_with_config(32, Mutex::new(todos))
+        // The same `Todo` type is parsed and serialized here and in the client.
+        .with_route("/api/todos", api)
+        // Serve the page and the WebAssembly client from the static directory.
+        .with_path_aware_route("/*", serve_dir("./static"));
+
+    app.run("0.0.0.0:80")?;
+
+    Ok(())
+}
+
+/// Lists the tasks, or adds the task in the body of a `POST` request.
+fn api(request: Request, state: Arc<AppState>) -> Response {
+    let mut todos = state.lock().unwrap();
+
+    if request.method == Method::Post {
+        let todo: Todo = match request
+            .content
+            .and_then(|body| String::from_utf8(body).ok())
+            .and_then(|body| humphrey_json::from_str(body).ok())
+        {
+            Some(todo) => todo,
+            None => return Response::new(StatusCode::BadRequest, "Invalid task"),
+        };
+
+        todos.push(todo);
+    }
+
+    Response::json(&*todos)
+}
@@ -1,6 +1,6 @@
 use humphrey::http::{Request, Response, StatusCode};
+use humphrey::shutdown::ShutdownSignal;
 use humphrey::App;
-use tokio_util::sync::CancellationToken;
 
 use std::error::Error;
 use std::thread::{sleep, spawn};
@@ -8,19 +8,20 @@ use std::time::Duration;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    let cancel = CancellationToken::new();
+    let shutdown = ShutdownSignal::new();
     let app: App<()> = App::new()
-        .with_shutdown(cancel.clone())
+        .with_shutdown(shutdown.clone())
         .with_stateless_route("/", hello);
 
     // Shutdown the main app after 5 seconds
     spawn(move || {
         sleep(Duration::from_secs(5));
-        cancel.cancel();
+        shutdown.trigger();
     });
 
     // Returns after shutdown signal
-    app.run("0.0.0.0:8080").await?;
+    let summary = app.run("0.0.0.0:8080").await?;
+    println!("{}", summary);
 
     Ok(())
 }
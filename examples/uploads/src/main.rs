@@ -0,0 +1,106 @@
+use humphrey::http::multipart::{MultipartError, MultipartLimits, MultipartReader};
+use humphrey::http::request::RequestLimits;
+use humphrey::http::{Request, Response, StatusCode};
+use humphrey::App;
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const FORM: &str = r#"<form action="/upload" method="post" enctype="multipart/form-data">
+  <input type="text" name="description">
+  <input type="file" name="file">
+  <input type="submit">
+</form>"#;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let directory = std::env::temp_dir().join("humphrey-uploads");
+    std::fs::create_dir_all(&directory)?;
+
+    println!("Saving uploads to {}", directory.display());
+
+    let app: App<PathBuf> = App::new_with_config(32, directory)
+        .with_request_limits(RequestLimits {
+            max_body_size: Some(2 * 1024 * 1024 * 1024 + 64 * 1024),
+            ..Default::default()
+        })
+        .with_stateless_route("/", |_| Response::new(StatusCode::OK, FORM))
+        .with_route("/upload", upload_handler);
+
+    app.run("0.0.0.0:80")?;
+
+    Ok(())
+}
+
+/// Saves each file in the form to the upload directory, printing the progress of the upload, and
+///   responds with the text fields and the size of each file.
+fn upload_handler(request: Request, directory: Arc<PathBuf>) -> Response {
+    let limits = MultipartLimits {
+        max_field_size: Some(4 * 1024),
+        max_file_size: Some(2 * 1024 * 1024 * 1024),
+        max_total_size: None,
+    };
+
+    let mut multipart = match MultipartReader::from_request(&request, limits) {
+        Ok(multipart) => multipart,
+        Err(e) => return Response::new(StatusCode::BadRequest, e.to_string()),
+    };
+
+    let mut summary = String::new();
+
+    loop {
+        let mut part = match multipart.next_part() {
+            Ok(Some(part)) => part,
+            Ok(None) => break,
+            Err(e) => return Response::new(StatusCode::BadRequest, e.to_string()),
+        };
+
+        let result = match part.filename.clone() {
+            Some(filename) => save_file(&mut part, &directory, &filename)
+                .map(|size| summary.push_str(&format!("{}: {} bytes\n", filename, size))),
+            None => {
+                let mut value = String::new();
+                part.read_to_string(&mut value)
+                    .map(|_| summary.push_str(&format!("{}: {}\n", part.name, value)))
+            }
+        };
+
+        if let Err(e) = result {
+            // Limit errors are returned from `read` wrapped in an I/O error
+            return match e.get_ref().and_then(|e| e.downcast_ref::<MultipartError>()) {
+                Some(e) => Response::new(StatusCode::RequestEntityTooLarge, e.to_string()),
+                None => Response::new(StatusCode::InternalError, e.to_string()),
+            };
+        }
+    }
+
+    Response::new(StatusCode::OK, summary)
+}
+
+/// Copies the content of the part to a file, printing the progress every megabyte.
+fn save_file(part: &mut impl Read, directory: &Path, filename: &str) -> std::io::Result<usize> {
+    // Only the final component of the name is used, so files cannot be written elsewhere
+    let filename = Path::new(filename)
+        .file_name()
+        .ok_or(std::io::ErrorKind::InvalidInput)?;
+
+    let mut file = File::create(directory.join(filename))?;
+    let mut buf = [0; 64 * 1024];
+    let mut written = 0;
+
+    loop {
+        match part.read(&mut buf)? {
+            0 => return Ok(written),
+            n => {
+                file.write_all(&buf[..n])?;
+
+                if (written + n) / (1024 * 1024) > written / (1024 * 1024) {
+                    println!("{:?}: {} MB", filename, (written + n) / (1024 * 1024));
+                }
+
+                written += n;
+            }
+        }
+    }
+}
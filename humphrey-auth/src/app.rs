@@ -7,6 +7,7 @@ use humphrey::http::{Request, Response, StatusCode};
 use humphrey::App;
 
 use std::sync::{Arc, MutexGuard};
+use std::time::Duration;
 
 /// Represents a state which contains an `AuthProvider`.
 /// This must be implemented on the state in order to use authentication.
@@ -21,7 +22,7 @@ use std::sync::{Arc, MutexGuard};
 /// }
 ///
 /// impl AuthState<DatabaseWrapper> for MyState {
-///    fn auth_provider(&self) -> MutexGuard<AuthProvider<DatabaseWrapper>> {
+///    fn auth_provider(&self) -> MutexGuard<'_, AuthProvider<DatabaseWrapper>> {
 ///       self.auth_provider.lock().unwrap()
 ///   }
 /// }
@@ -31,7 +32,7 @@ where
     D: AuthDatabase,
 {
     /// Returns a `MutexGuard` to the `AuthProvider`.
-    fn auth_provider(&self) -> MutexGuard<AuthProvider<D>>;
+    fn auth_provider(&self) -> MutexGuard<'_, AuthProvider<D>>;
 }
 
 /// Represents a function able to handle an authenticated request.
@@ -60,6 +61,15 @@ where
     fn with_auth_route<T>(self, route: &str, handler: T) -> Self
     where
         T: AuthRequestHandler<S> + 'static;
+
+    /// Adds an authenticated route which additionally requires the user to have entered their
+    ///   password within the given duration, either when logging in or by reauthenticating.
+    ///
+    /// If the session is valid but too old, the response is a 401 with the `X-Reauthenticate`
+    ///   header set, so the client can prompt for the password rather than a full login.
+    fn with_auth_route_max_age<T>(self, route: &str, handler: T, max_age: Duration) -> Self
+    where
+        T: AuthRequestHandler<S> + 'static;
 }
 
 impl<S, D> AuthApp<S, D> for App<S>
@@ -83,8 +93,40 @@ where
             forbidden()
         })
     }
+
+    fn with_auth_route_max_age<T>(self, route: &str, handler: T, max_age: Duration) -> Self
+    where
+        T: AuthRequestHandler<S> + 'static,
+    {
+        self.with_route(route, move |request: Request, state: Arc<S>| {
+            if let Some(cookie) = request.get_cookie("HumphreyToken") {
+                let (uid, age) = {
+                    let provider = state.auth_provider();
+                    (
+                        provider.get_uid_by_token(&cookie.value),
+                        provider.session_age(&cookie.value),
+                    )
+                };
+
+                match (uid, age) {
+                    (Ok(uid), Ok(age)) if age <= max_age => {
+                        return (handler)(request, state, uid);
+                    }
+                    (Ok(_), Ok(_)) => return reauthentication_required(),
+                    _ => (),
+                }
+            }
+
+            forbidden()
+        })
+    }
 }
 
 fn forbidden() -> Response {
     Response::new(StatusCode::Unauthorized, "401 Unauthorized")
 }
+
+fn reauthentication_required() -> Response {
+    Response::new(StatusCode::Unauthorized, "401 Reauthentication Required")
+        .with_header("X-Reauthenticate", "true")
+}
@@ -1,10 +1,15 @@
 //! Contains database traits for connecting the authentication service with a database of
 //!   your choosing. These are automatically implemented for `Vec<User>` as an example.
+//!
+//! Databases with blocking drivers can be wrapped in a `PooledDatabase`, which checks out a
+//!   separate connection for every call so that a slow query does not hold up other requests.
 
 use crate::error::AuthError;
 use crate::session::Session;
 use crate::user::User;
 
+use std::sync::Arc;
+
 /// Represents a database which can be used to store auth information.
 /// Must be implemented for whatever database you are using.
 ///
@@ -26,6 +31,24 @@ pub trait AuthDatabase {
     fn add_user(&mut self, user: User) -> Result<(), AuthError>;
     /// Remove the user with the given UID from the database.
     fn remove_user(&mut self, uid: impl AsRef<str>) -> Result<(), AuthError>;
+
+    /// Returns the UIDs of up to `limit` users, skipping the first `offset`.
+    /// The order must be consistent between calls so that the results can be paginated.
+    ///
+    /// Databases which cannot list their users can leave this unimplemented, in which case it
+    ///   returns `AuthError::Unsupported`.
+    fn list_uids(&self, offset: usize, limit: usize) -> Result<Vec<String>, AuthError> {
+        let _ = (offset, limit);
+        Err(AuthError::Unsupported)
+    }
+
+    /// Returns the number of users in the database.
+    ///
+    /// Databases which cannot count their users can leave this unimplemented, in which case it
+    ///   returns `AuthError::Unsupported`.
+    fn count_users(&self) -> Result<usize, AuthError> {
+        Err(AuthError::Unsupported)
+    }
 }
 
 impl AuthDatabase for Vec<User> {
@@ -73,4 +96,137 @@ impl AuthDatabase for Vec<User> {
 
         Ok(())
     }
+
+    fn list_uids(&self, offset: usize, limit: usize) -> Result<Vec<String>, AuthError> {
+        Ok(self
+            .iter()
+            .skip(offset)
+            .take(limit)
+            .map(|user| user.uid.clone())
+            .collect())
+    }
+
+    fn count_users(&self) -> Result<usize, AuthError> {
+        Ok(self.len())
+    }
+}
+
+/// A database adapter which checks out a new connection for every call, for example from a
+///   connection pool, instead of sharing a single connection.
+///
+/// The connection type must implement `AuthDatabase`, and is dropped at the end of each call, which
+///   should return it to the pool. Cloning the adapter is cheap and shares the factory, so it can
+///   be used with `AuthProvider::clone_with_shared_config` to give each worker thread its own
+///   provider.
+///
+/// If a connection cannot be checked out, the getters return `None` and the other methods return
+///   `AuthError::DatabaseUnavailable`. The error can be inspected with `with_error_handler`.
+///
+/// ## Example
+/// ```
+/// let pool = Pool::new(manager)?;
+/// let database = PooledDatabase::new(move || pool.get().map(UserTable));
+/// let provider = AuthProvider::new(database);
+/// ```
+pub struct PooledDatabase<C, E> {
+    factory: Arc<Factory<C, E>>,
+    error_handler: Option<Arc<ErrorHandler<E>>>,
+}
+
+/// A function which checks out a connection.
+type Factory<C, E> = dyn Fn() -> Result<C, E> + Send + Sync;
+
+/// A function which is called with the error when a connection cannot be checked out.
+type ErrorHandler<E> = dyn Fn(&E) + Send + Sync;
+
+impl<C, E> PooledDatabase<C, E>
+where
+    C: AuthDatabase,
+{
+    /// Creates a new adapter which calls the given function to check out a connection.
+    pub fn new<F>(factory: F) -> Self
+    where
+        F: Fn() -> Result<C, E> + Send + Sync + 'static,
+    {
+        Self {
+            factory: Arc::new(factory),
+            error_handler: None,
+        }
+    }
+
+    /// Calls the given function with the error whenever a connection cannot be checked out, for
+    ///   example to log it.
+    pub fn with_error_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&E) + Send + Sync + 'static,
+    {
+        self.error_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Checks out a connection and calls the given function with it.
+    fn with_connection<T>(&self, f: impl FnOnce(C) -> T) -> Result<T, AuthError> {
+        match (self.factory)() {
+            Ok(connection) => Ok(f(connection)),
+            Err(error) => {
+                if let Some(handler) = &self.error_handler {
+                    handler(&error);
+                }
+
+                Err(AuthError::DatabaseUnavailable)
+            }
+        }
+    }
+}
+
+impl<C, E> Clone for PooledDatabase<C, E> {
+    fn clone(&self) -> Self {
+        Self {
+            factory: self.factory.clone(),
+            error_handler: self.error_handler.clone(),
+        }
+    }
+}
+
+impl<C, E> AuthDatabase for PooledDatabase<C, E>
+where
+    C: AuthDatabase,
+{
+    fn get_user_by_uid(&self, uid: impl AsRef<str>) -> Option<User> {
+        self.with_connection(|c| c.get_user_by_uid(uid))
+            .ok()
+            .flatten()
+    }
+
+    fn get_user_by_token(&self, token: impl AsRef<str>) -> Option<User> {
+        self.with_connection(|c| c.get_user_by_token(token))
+            .ok()
+            .flatten()
+    }
+
+    fn get_session_by_token(&self, token: impl AsRef<str>) -> Option<Session> {
+        self.with_connection(|c| c.get_session_by_token(token))
+            .ok()
+            .flatten()
+    }
+
+    fn update_user(&mut self, user: User) -> Result<(), AuthError> {
+        self.with_connection(|mut c| c.update_user(user))?
+    }
+
+    fn add_user(&mut self, user: User) -> Result<(), AuthError> {
+        self.with_connection(|mut c| c.add_user(user))?
+    }
+
+    fn remove_user(&mut self, uid: impl AsRef<str>) -> Result<(), AuthError> {
+        self.with_connection(|mut c| c.remove_user(uid))?
+    }
+
+    fn list_uids(&self, offset: usize, limit: usize) -> Result<Vec<String>, AuthError> {
+        self.with_connection(|c| c.list_uids(offset, limit))?
+    }
+
+    fn count_users(&self) -> Result<usize, AuthError> {
+        self.with_connection(|c| c.count_users())?
+    }
 }
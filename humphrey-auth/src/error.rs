@@ -3,6 +3,9 @@
 use std::error::Error;
 use std::fmt::Display;
 
+#[cfg(feature = "json")]
+use humphrey_json::error::ParseError;
+
 /// Represents an error with authentication.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AuthError {
@@ -16,6 +19,14 @@ pub enum AuthError {
     InvalidToken,
     /// A session for the given user already exists.
     SessionAlreadyExists,
+    /// The given user has been disabled.
+    UserDisabled,
+    /// The operation is not supported by the database.
+    Unsupported,
+    /// The given password is incorrect.
+    IncorrectPassword,
+    /// A connection to the database could not be established.
+    DatabaseUnavailable,
 }
 
 impl Display for AuthError {
@@ -26,8 +37,52 @@ impl Display for AuthError {
             AuthError::UserAlreadyExists => write!(f, "User already exists"),
             AuthError::InvalidToken => write!(f, "Invalid token"),
             AuthError::SessionAlreadyExists => write!(f, "Session already exists"),
+            AuthError::UserDisabled => write!(f, "User disabled"),
+            AuthError::Unsupported => write!(f, "Operation not supported"),
+            AuthError::IncorrectPassword => write!(f, "Incorrect password"),
+            AuthError::DatabaseUnavailable => write!(f, "Database unavailable"),
         }
     }
 }
 
 impl Error for AuthError {}
+
+/// Represents an error reading a stored user or session.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MigrationError {
+    /// The document was written by a newer version of the crate, whose format is not understood.
+    /// Contains the version of the document.
+    UnsupportedVersion(u64),
+    /// The document is not a valid user or session of its version.
+    InvalidDocument(ParseError),
+}
+
+#[cfg(feature = "json")]
+impl Display for MigrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MigrationError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported format version {}", version)
+            }
+            MigrationError::InvalidDocument(error) => write!(f, "Invalid document: {}", error),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl Error for MigrationError {}
+
+#[cfg(feature = "json")]
+impl From<ParseError> for MigrationError {
+    fn from(error: ParseError) -> Self {
+        MigrationError::InvalidDocument(error)
+    }
+}
+
+#[cfg(feature = "error")]
+impl From<AuthError> for humphrey::error::Error {
+    fn from(error: AuthError) -> Self {
+        humphrey::error::Error::Auth(Box::new(error))
+    }
+}
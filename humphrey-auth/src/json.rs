@@ -1,17 +1,175 @@
+//! Provides a stable, versioned JSON representation of users and sessions.
+//!
+//! Every user and session is written with a `version` field, so that databases which persisted
+//!   an older shape can still be read after the structs change. Documents from older versions are
+//!   upgraded one version at a time by `User::migrate` and `Session::migrate`, which `from_json`
+//!   uses, so existing databases are upgraded as they are read and written back.
+//!
+//! The versions of the format are as follows:
+//! - Version 0 has no `version` field, and was written before the format was versioned. Users may
+//!   be missing `disabled` and sessions may be missing `authenticated_at`, since these were added
+//!   before then.
+//! - Version 1 adds the `version` field, and every other field is always present.
+//!
+//! Fields which are not part of the format are ignored, so databases can store their own fields
+//!   alongside. Documents from newer versions are rejected instead of being read with a format which
+//!   may have changed, so that an older release never writes them back with fields missing.
+//!
+//! To change the format, increment the version, write the new fields in `to_json`, and add a
+//!   migration from the previous version which fills in any new fields.
+
+use crate::error::MigrationError;
 use crate::session::Session;
 use crate::user::User;
 
+use humphrey_json::error::ParseError;
 use humphrey_json::prelude::*;
+use humphrey_json::Value;
+
+impl User {
+    /// The version of the format written by `to_json`.
+    pub const FORMAT_VERSION: u64 = 1;
+
+    /// Reads a user from a JSON document written by any version of the format, upgrading it to
+    ///   the current version one step at a time.
+    ///
+    /// The user's session, if they have one, is migrated with `Session::migrate`.
+    pub fn migrate(mut value: Value) -> Result<User, MigrationError> {
+        let version = format_version(&value, Self::FORMAT_VERSION)?;
+
+        if version < 1 {
+            migrate_user_to_v1(&mut value);
+        }
+
+        let session = match field(&value, "session")? {
+            Value::Null => None,
+            session => Some(Session::migrate(session.clone())?),
+        };
+
+        Ok(Self {
+            uid: FromJson::from_json(field(&value, "uid")?)?,
+            session,
+            password_hash: FromJson::from_json(field(&value, "password_hash")?)?,
+            disabled: FromJson::from_json(field(&value, "disabled")?)?,
+        })
+    }
+}
+
+impl Session {
+    /// The version of the format written by `to_json`.
+    pub const FORMAT_VERSION: u64 = 1;
+
+    /// Reads a session from a JSON document written by any version of the format, upgrading it to
+    ///   the current version one step at a time.
+    pub fn migrate(mut value: Value) -> Result<Session, MigrationError> {
+        let version = format_version(&value, Self::FORMAT_VERSION)?;
+
+        if version < 1 {
+            migrate_session_to_v1(&mut value);
+        }
+
+        Ok(Self {
+            token: FromJson::from_json(field(&value, "token")?)?,
+            expiry: FromJson::from_json(field(&value, "expiry")?)?,
+            authenticated_at: FromJson::from_json(field(&value, "authenticated_at")?)?,
+        })
+    }
+}
+
+/// Upgrades a user from version 0 to version 1.
+fn migrate_user_to_v1(value: &mut Value) {
+    // Users serialized before accounts could be disabled have no `disabled` field
+    set_default(value, "disabled", Value::Bool(false));
+    set_default(value, "session", Value::Null);
+    set_version(value, 1);
+}
+
+/// Upgrades a session from version 0 to version 1.
+fn migrate_session_to_v1(value: &mut Value) {
+    // Sessions serialized before reauthentication was tracked are treated as never having been
+    //   authenticated, so routes with a maximum session age will ask for the password
+    set_default(value, "authenticated_at", Value::Number(0.0));
+    set_version(value, 1);
+}
+
+/// Returns the version of the given document, which is 0 if it has no version.
+fn format_version(value: &Value, current: u64) -> Result<u64, MigrationError> {
+    let version = match value.get("version") {
+        Some(version) => Option::<u64>::from_json(version)?.unwrap_or(0),
+        None => 0,
+    };
+
+    if version > current {
+        return Err(MigrationError::UnsupportedVersion(version));
+    }
+
+    Ok(version)
+}
+
+/// Returns the field with the given name, which must be present in the current version.
+fn field<'a>(value: &'a Value, name: &str) -> Result<&'a Value, ParseError> {
+    value.get(name).ok_or(ParseError::MissingField)
+}
+
+/// Sets the field with the given name to the given value if it is missing or null, adding it if
+///   it is missing.
+fn set_default(value: &mut Value, name: &str, default: Value) {
+    if let Some(field) = value.get_mut(name) {
+        if *field == Value::Null {
+            *field = default;
+        }
+    }
+}
+
+/// Sets the version of the given document.
+fn set_version(value: &mut Value, version: u64) {
+    if let Some(field) = value.get_mut("version") {
+        *field = version.to_json();
+    }
+}
+
+impl FromJson for User {
+    fn from_json(value: &Value) -> Result<Self, ParseError> {
+        User::migrate(value.clone()).map_err(ParseError::from)
+    }
+}
+
+impl IntoJson for User {
+    fn to_json(&self) -> Value {
+        json!({
+            "version": (User::FORMAT_VERSION),
+            "uid": (&self.uid),
+            "session": (&self.session),
+            "password_hash": (&self.password_hash),
+            "disabled": (self.disabled)
+        })
+    }
+}
+
+impl FromJson for Session {
+    fn from_json(value: &Value) -> Result<Self, ParseError> {
+        Session::migrate(value.clone()).map_err(ParseError::from)
+    }
+}
 
-json_map! {
-    User,
-    uid => "uid",
-    session => "session",
-    password_hash => "password_hash"
+impl IntoJson for Session {
+    fn to_json(&self) -> Value {
+        json!({
+            "version": (Session::FORMAT_VERSION),
+            "token": (&self.token),
+            "expiry": (self.expiry),
+            "authenticated_at": (self.authenticated_at)
+        })
+    }
 }
 
-json_map! {
-    Session,
-    token => "token",
-    expiry => "expiry"
+impl From<MigrationError> for ParseError {
+    fn from(error: MigrationError) -> Self {
+        match error {
+            MigrationError::UnsupportedVersion(_) => {
+                ParseError::ValidationError("version", error.to_string())
+            }
+            MigrationError::InvalidDocument(error) => error,
+        }
+    }
 }
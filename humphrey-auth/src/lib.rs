@@ -1,6 +1,6 @@
 //! Web applications commonly need a way of authenticating users. This crate provides an easy and secure way to do this, integrating with Humphrey using the `AuthApp` trait and allowing complete control over the database users are stored in. Humphrey Auth does not come with a database, but the `AuthDatabase` trait is implemented for `Vec<User>` to get started. For a production use, you should use a proper database and implement the `AuthDatabase` trait for it.
 //!
-//! If a JSON representation of users is useful for your database, you can enable the `json` feature which provides JSON serialization and deserialization for `User` and `Session` using the Humphrey JSON crate.
+//! If a JSON representation of users is useful for your database, you can enable the `json` feature which provides JSON serialization and deserialization for `User` and `Session` using the Humphrey JSON crate. The JSON representation is versioned, and users stored by older versions of the crate are upgraded as they are read with `User::migrate`.
 //!
 //! Learn more about Humphrey Auth [here](https://humphrey.whenderson.dev/auth/index.html).
 
@@ -27,12 +27,18 @@ use crate::error::AuthError;
 use crate::session::Session;
 use crate::user::User;
 
+use std::time::Duration;
+
 /// Represents an authentication provider.
 /// Contains a database of users and provides methods for managing authentication.
 ///
 /// If the database needs to be used from elsewhere in the program, it is advisable to
 ///   put the database behind an `Arc` and `Mutex`/`RwLock` and store a cloned reference
 ///   to the database in the users field of this struct.
+///
+/// The provider is usually stored behind a `Mutex`, so every call is serialised. If the database
+///   can be cloned, such as a `PooledDatabase`, this can be avoided by giving each worker thread its
+///   own provider with `clone_with_shared_config`.
 #[derive(Default)]
 pub struct AuthProvider<T>
 where
@@ -60,6 +66,22 @@ where
         self
     }
 
+    /// Creates another provider with the same configuration and a clone of the database handle.
+    ///
+    /// This allows the provider to be sharded, for example by storing one provider per worker thread
+    ///   in a `Vec<Mutex<AuthProvider<D>>>`, so that threads do not contend on a single mutex. Clones
+    ///   of the database handle must share the underlying storage, as clones of `PooledDatabase` do,
+    ///   otherwise each provider would see a different set of users.
+    pub fn clone_with_shared_config(&self) -> Self
+    where
+        T: Clone,
+    {
+        Self {
+            users: self.users.clone(),
+            config: self.config.clone(),
+        }
+    }
+
     /// Create a user with the given password. Returns the UID of the new user.
     pub fn create_user(&mut self, password: impl AsRef<str>) -> Result<String, AuthError> {
         let new_user = User::create(password, self.config.pepper.as_ref().map(|p| p.as_ref()))?;
@@ -74,13 +96,51 @@ where
     }
 
     /// Verifies that the given password matches the password of the user with the given UID.
+    /// Always returns false if the user has been disabled.
     pub fn verify(&self, uid: impl AsRef<str>, password: impl AsRef<str>) -> bool {
         self.users
             .get_user_by_uid(&uid)
+            .filter(|user| !user.disabled)
             .map(|user| user.verify(&password, self.config.pepper.as_ref().map(|p| p.as_ref())))
             .unwrap_or(false)
     }
 
+    /// Disables or re-enables the user with the given UID.
+    ///
+    /// A disabled user cannot log in, and any session they already have will be rejected until
+    ///   they are re-enabled, at which point it becomes usable again if it has not expired.
+    pub fn set_disabled(&mut self, uid: impl AsRef<str>, disabled: bool) -> Result<(), AuthError> {
+        let mut user = self
+            .users
+            .get_user_by_uid(uid)
+            .ok_or(AuthError::UserNotFound)?;
+
+        user.disabled = disabled;
+        self.users.update_user(user)
+    }
+
+    /// Returns true if the user with the given UID has been disabled.
+    pub fn is_disabled(&self, uid: impl AsRef<str>) -> Result<bool, AuthError> {
+        self.users
+            .get_user_by_uid(uid)
+            .map(|user| user.disabled)
+            .ok_or(AuthError::UserNotFound)
+    }
+
+    /// Returns the UIDs of up to `limit` users, skipping the first `offset`.
+    ///
+    /// Returns `AuthError::Unsupported` if the database does not support listing users.
+    pub fn list_uids(&self, offset: usize, limit: usize) -> Result<Vec<String>, AuthError> {
+        self.users.list_uids(offset, limit)
+    }
+
+    /// Returns the number of users.
+    ///
+    /// Returns `AuthError::Unsupported` if the database does not support counting users.
+    pub fn count_users(&self) -> Result<usize, AuthError> {
+        self.users.count_users()
+    }
+
     /// Removes the user with the given UID.
     pub fn remove_user(&mut self, uid: impl AsRef<str>) -> Result<(), AuthError> {
         self.users.remove_user(&uid)
@@ -95,6 +155,10 @@ where
             .get_user_by_uid(uid.as_ref())
             .ok_or(AuthError::UserNotFound)?;
 
+        if user.disabled {
+            return Err(AuthError::UserDisabled);
+        }
+
         if !user.session.map(|t| t.valid()).unwrap_or(false) {
             let token = Session::create_with_lifetime(self.config.default_lifetime);
             user.session = Some(token.clone());
@@ -119,6 +183,10 @@ where
             .get_user_by_uid(uid.as_ref())
             .ok_or(AuthError::UserNotFound)?;
 
+        if user.disabled {
+            return Err(AuthError::UserDisabled);
+        }
+
         if !user.session.map(|t| t.valid()).unwrap_or(false) {
             let session = Session::create_with_lifetime(lifetime);
             user.session = Some(session.clone());
@@ -138,6 +206,10 @@ where
             .get_user_by_token(token)
             .ok_or(AuthError::InvalidToken)?;
 
+        if user.disabled {
+            return Err(AuthError::UserDisabled);
+        }
+
         let mut session = user.session.unwrap();
         session.refresh(self.config.default_refresh_lifetime);
 
@@ -147,6 +219,81 @@ where
         Ok(())
     }
 
+    /// Replaces the token of the session with the given token, returning the new token.
+    ///
+    /// The expiry and authentication time of the session are preserved, and the old token stops
+    ///   working immediately. This should be called after logging in or elevating privileges to
+    ///   prevent session fixation.
+    pub fn rotate_session(&mut self, token: impl AsRef<str>) -> Result<String, AuthError> {
+        let mut user = self
+            .users
+            .get_user_by_token(token)
+            .ok_or(AuthError::InvalidToken)?;
+
+        if user.disabled {
+            return Err(AuthError::UserDisabled);
+        }
+
+        let mut session = user.session.take().unwrap();
+
+        if !session.valid() {
+            return Err(AuthError::InvalidToken);
+        }
+
+        session.rotate();
+        let new_token = session.token.clone();
+
+        user.session = Some(session);
+        self.users.update_user(user)?;
+
+        Ok(new_token)
+    }
+
+    /// Verifies the password of the user with the given UID, and if it is correct, marks their
+    ///   current session as recently authenticated.
+    pub fn reauthenticate(
+        &mut self,
+        uid: impl AsRef<str>,
+        password: impl AsRef<str>,
+    ) -> Result<(), AuthError> {
+        let mut user = self
+            .users
+            .get_user_by_uid(uid)
+            .ok_or(AuthError::UserNotFound)?;
+
+        if user.disabled {
+            return Err(AuthError::UserDisabled);
+        }
+
+        if !user.verify(password, self.config.pepper.as_ref().map(|p| p.as_ref())) {
+            return Err(AuthError::IncorrectPassword);
+        }
+
+        let mut session = user
+            .session
+            .take()
+            .filter(|session| session.valid())
+            .ok_or(AuthError::InvalidToken)?;
+
+        session.reauthenticate();
+
+        user.session = Some(session);
+        self.users.update_user(user)
+    }
+
+    /// Returns the time since the owner of the given token last authenticated with their password.
+    ///
+    /// This can be used to require a recent password entry before sensitive operations.
+    pub fn session_age(&self, token: impl AsRef<str>) -> Result<Duration, AuthError> {
+        self.users
+            .get_user_by_token(token)
+            .filter(|u| !u.disabled)
+            .and_then(|u| u.session)
+            .filter(|session| session.valid())
+            .map(|session| session.age())
+            .ok_or(AuthError::InvalidToken)
+    }
+
     /// Invalidates the given token, if it exists.
     pub fn invalidate_session(&mut self, token: impl AsRef<str>) {
         if let Some(mut user) = self.users.get_user_by_token(token) {
@@ -164,10 +311,11 @@ where
     }
 
     /// Gets the UID of the user with the given token.
+    /// Sessions belonging to disabled users are treated as invalid.
     pub fn get_uid_by_token(&self, token: impl AsRef<str>) -> Result<String, AuthError> {
         self.users
             .get_user_by_token(token)
-            .filter(|u| u.session.as_ref().unwrap().valid())
+            .filter(|u| !u.disabled && u.session.as_ref().unwrap().valid())
             .map(|user| user.uid)
             .ok_or(AuthError::InvalidToken)
     }
@@ -1,16 +1,21 @@
 //! Provides functionality for handling sessions and tokens.
 
-use std::time::UNIX_EPOCH;
-
+use humphrey::clock;
 use rand_core::{OsRng, RngCore};
 
-/// Represents a session, containing a token and an expiration time.
+use std::time::Duration;
+
+/// Represents a session, containing a token, an expiration time, and when the user last
+///   entered their password.
 #[derive(Default, Clone, PartialEq, Eq)]
 pub struct Session {
     /// The token string for this session.
     pub token: String,
     /// The UNIX timestamp at which this session will expire.
     pub expiry: u64,
+    /// The UNIX timestamp at which the user last authenticated with their password, either when
+    ///   the session was created or when they last reauthenticated.
+    pub authenticated_at: u64,
 }
 
 impl Session {
@@ -21,39 +26,68 @@ impl Session {
 
     /// Creates a token with the given lifetime (in seconds).
     pub fn create_with_lifetime(lifetime: u64) -> Self {
-        let token = {
-            let mut token: [u8; 32] = [0; 32];
-            OsRng.fill_bytes(&mut token);
-            token
-        };
-
-        let token_hex = token.iter().fold(String::with_capacity(64), |mut acc, &b| {
-            acc.push_str(&format!("{:02x}", b));
-            acc
-        });
-
-        let expiry = UNIX_EPOCH.elapsed().unwrap().as_secs() + lifetime;
+        let now = now();
 
         Self {
-            token: token_hex,
-            expiry,
+            token: generate_token(),
+            expiry: now + lifetime,
+            authenticated_at: now,
         }
     }
 
     /// Returns true if the token is valid.
     pub fn valid(&self) -> bool {
-        let now = UNIX_EPOCH.elapsed().unwrap().as_secs();
+        let now = now();
         now < self.expiry
     }
 
     /// Returns true if the token has expired.
     pub fn expired(&self) -> bool {
-        let now = UNIX_EPOCH.elapsed().unwrap().as_secs();
+        let now = now();
         self.expiry < now
     }
 
     /// Refreshes the token, setting it to expire the given number of seconds after the current time.
     pub fn refresh(&mut self, lifetime: u64) {
-        self.expiry = UNIX_EPOCH.elapsed().unwrap().as_secs() + lifetime;
+        self.expiry = now() + lifetime;
+    }
+
+    /// Replaces the token with a new random one, keeping the expiry and authentication time.
+    pub fn rotate(&mut self) {
+        self.token = generate_token();
+    }
+
+    /// Marks the session as having just been authenticated with the user's password.
+    pub fn reauthenticate(&mut self) {
+        self.authenticated_at = now();
     }
+
+    /// Returns the time elapsed since the user last authenticated with their password.
+    pub fn age(&self) -> Duration {
+        let now = now();
+        Duration::from_secs(now.saturating_sub(self.authenticated_at))
+    }
+}
+
+/// Returns the current UNIX timestamp according to the current thread's clock, which tests can
+///   override with `humphrey::clock::override_clock`.
+fn now() -> u64 {
+    clock::now_unix().as_secs()
+}
+
+/// Generates a random 256-bit token, encoded as hexadecimal.
+///
+/// The token is read from the operating system's cryptographically secure generator, so it is
+///   safe to use as a session identifier.
+fn generate_token() -> String {
+    let token = {
+        let mut token: [u8; 32] = [0; 32];
+        OsRng.fill_bytes(&mut token);
+        token
+    };
+
+    token.iter().fold(String::with_capacity(64), |mut acc, &b| {
+        acc.push_str(&format!("{:02x}", b));
+        acc
+    })
 }
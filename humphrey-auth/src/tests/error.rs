@@ -0,0 +1,23 @@
+use crate::error::AuthError;
+use crate::{AuthProvider, User};
+
+use humphrey::error::Error;
+use humphrey::http::StatusCode;
+
+#[test]
+fn test_auth_conversion() {
+    let mut auth: AuthProvider<Vec<User>> = AuthProvider::default();
+
+    let result: Result<String, Error> = (|| Ok(auth.create_session("missing")?))();
+    let error = result.unwrap_err();
+
+    assert!(matches!(error, Error::Auth(_)));
+    assert_eq!(error.status_code(), StatusCode::Unauthorized);
+    assert_eq!(error.to_string(), "Authentication error: User not found");
+
+    let source = std::error::Error::source(&error).unwrap();
+    assert_eq!(
+        source.downcast_ref::<AuthError>(),
+        Some(&AuthError::UserNotFound)
+    );
+}
@@ -0,0 +1,179 @@
+use crate::error::MigrationError;
+use crate::session::Session;
+use crate::user::User;
+
+use humphrey_json::error::ParseError;
+use humphrey_json::prelude::*;
+use humphrey_json::Value;
+
+const PASSWORD_HASH: &str = "$argon2id$v=19$m=4096,t=3,p=1$c2FsdHNhbHQ$aGFzaGhhc2hoYXNoaGFzaA";
+
+fn fixture(json: &str) -> Value {
+    humphrey_json::from_str(json).unwrap()
+}
+
+#[test]
+fn migrate_v0_test() {
+    // Users written before accounts could be disabled or sessions reauthenticated
+    let user = User::migrate(fixture(include_str!("testcases/user_v0.json"))).unwrap();
+    let session = user.session.unwrap();
+
+    assert_eq!(user.uid, "8f14e45f-ceea-467a-9575-6f1d2d7c5a3b");
+    assert_eq!(user.password_hash, PASSWORD_HASH);
+    assert!(!user.disabled);
+    assert_eq!(
+        session.token,
+        "4a7d1ed414474e4033ac29ccb8653d9b4a7d1ed414474e4033ac29ccb8653d9b"
+    );
+    assert_eq!(session.expiry, 1650000000);
+    assert_eq!(session.authenticated_at, 0);
+
+    // Users written after those fields were added, but before the format was versioned
+    let user = User::migrate(fixture(include_str!("testcases/user_v0_disabled.json"))).unwrap();
+    let session = user.session.unwrap();
+
+    assert_eq!(user.uid, "c9f0f895-fb98-4b91-8b3b-6f3c2d1e0a7f");
+    assert!(user.disabled);
+    assert_eq!(session.expiry, 1700000000);
+    assert_eq!(session.authenticated_at, 1699996400);
+}
+
+#[test]
+fn migrate_v1_test() {
+    // Fields which are not part of the format, such as `email`, are ignored
+    let user = User::migrate(fixture(include_str!("testcases/user_v1.json"))).unwrap();
+    let session = user.session.unwrap();
+
+    assert_eq!(user.uid, "45c48cce-2e2d-4fbd-b1d9-1b2f5e6a7c8d");
+    assert_eq!(user.password_hash, PASSWORD_HASH);
+    assert!(!user.disabled);
+    assert_eq!(
+        session.token,
+        "1679091c5a880faf6fb5e6087eb1b2dc1679091c5a880faf6fb5e6087eb1b2dc"
+    );
+    assert_eq!(session.expiry, 1750000000);
+    assert_eq!(session.authenticated_at, 1749996400);
+}
+
+#[test]
+fn round_trip_test() {
+    let user = User {
+        uid: "45c48cce-2e2d-4fbd-b1d9-1b2f5e6a7c8d".into(),
+        session: Some(Session::create()),
+        password_hash: PASSWORD_HASH.into(),
+        disabled: true,
+    };
+
+    let json = humphrey_json::to_string(&user);
+    let parsed: User = humphrey_json::from_str(json).unwrap();
+
+    assert_eq!(parsed.uid, user.uid);
+    assert!(parsed.session == user.session);
+    assert_eq!(parsed.password_hash, user.password_hash);
+    assert_eq!(parsed.disabled, user.disabled);
+
+    // Upgraded users are written back in the current format
+    let user = User::migrate(fixture(include_str!("testcases/user_v0.json"))).unwrap();
+    let json = user.to_json();
+
+    assert_eq!(json["version"], Value::Number(User::FORMAT_VERSION as f64));
+    assert_eq!(
+        json["session"]["version"],
+        Value::Number(Session::FORMAT_VERSION as f64)
+    );
+    assert_eq!(json["session"]["authenticated_at"], Value::Number(0.0));
+    assert_eq!(json["disabled"], Value::Bool(false));
+}
+
+#[test]
+fn current_format_test() {
+    // Guards against accidental changes to the format, which need a new version and a migration
+    let user = User {
+        uid: "45c48cce-2e2d-4fbd-b1d9-1b2f5e6a7c8d".into(),
+        session: Some(Session {
+            token: "1679091c5a880faf6fb5e6087eb1b2dc1679091c5a880faf6fb5e6087eb1b2dc".into(),
+            expiry: 1750000000,
+            authenticated_at: 1749996400,
+        }),
+        password_hash: PASSWORD_HASH.into(),
+        disabled: false,
+    };
+
+    let mut expected = fixture(include_str!("testcases/user_v1.json"));
+    if let Value::Object(fields) = &mut expected {
+        fields.retain(|(name, _)| name != "email");
+    }
+
+    assert_eq!(user.to_json(), expected);
+}
+
+#[test]
+fn unsupported_version_test() {
+    let user = fixture(r#"{"version": 2, "uid": "abc", "session": null, "password_hash": ""}"#);
+    assert!(matches!(
+        User::migrate(user.clone()),
+        Err(MigrationError::UnsupportedVersion(2))
+    ));
+    assert_eq!(
+        User::from_json(&user).err(),
+        Some(ParseError::ValidationError(
+            "version",
+            "Unsupported format version 2".into()
+        ))
+    );
+
+    // Users are rejected if their session is from a newer version
+    let user = fixture(
+        r#"{"version": 1, "uid": "abc", "session": {"version": 2}, "password_hash": "", "disabled": false}"#,
+    );
+    assert!(matches!(
+        User::migrate(user),
+        Err(MigrationError::UnsupportedVersion(2))
+    ));
+}
+
+#[test]
+fn invalid_document_test() {
+    // Fields which were always present must be present in every version
+    let user = fixture(r#"{"session": null, "password_hash": ""}"#);
+    assert_eq!(
+        User::migrate(user).err(),
+        Some(MigrationError::InvalidDocument(ParseError::MissingField))
+    );
+
+    // Versioned documents are not given defaults for missing fields
+    let session = fixture(r#"{"version": 1, "token": "abc", "expiry": 1}"#);
+    assert_eq!(
+        Session::migrate(session).err(),
+        Some(MigrationError::InvalidDocument(ParseError::MissingField))
+    );
+
+    let user = fixture(r#"{"uid": 1, "session": null, "password_hash": ""}"#);
+    assert_eq!(
+        User::migrate(user).err(),
+        Some(MigrationError::InvalidDocument(ParseError::TypeError))
+    );
+}
+
+#[test]
+fn database_test() {
+    // Databases of users are migrated one user at a time
+    let database = format!(
+        "[{}, {}, {}]",
+        include_str!("testcases/user_v0.json"),
+        include_str!("testcases/user_v0_disabled.json"),
+        include_str!("testcases/user_v1.json")
+    );
+
+    let users: Vec<User> = humphrey_json::from_str(database).unwrap();
+    let uids: Vec<&str> = users.iter().map(|user| user.uid.as_str()).collect();
+
+    assert_eq!(
+        uids,
+        [
+            "8f14e45f-ceea-467a-9575-6f1d2d7c5a3b",
+            "c9f0f895-fb98-4b91-8b3b-6f3c2d1e0a7f",
+            "45c48cce-2e2d-4fbd-b1d9-1b2f5e6a7c8d"
+        ]
+    );
+}
@@ -1,3 +1,4 @@
+use crate::error::AuthError;
 use crate::{AuthProvider, User};
 
 #[test]
@@ -14,3 +15,71 @@ fn integration_test() {
 
     assert!(!provider.exists(&uid));
 }
+
+#[test]
+fn disabled_user_test() {
+    let mut provider: AuthProvider<Vec<User>> = AuthProvider::default();
+
+    let uid = provider.create_user("hunter42").unwrap();
+    let token = provider.create_session(&uid).unwrap();
+
+    assert_eq!(provider.is_disabled(&uid), Ok(false));
+    assert_eq!(provider.get_uid_by_token(&token), Ok(uid.clone()));
+
+    provider.set_disabled(&uid, true).unwrap();
+
+    assert_eq!(provider.is_disabled(&uid), Ok(true));
+    assert!(!provider.verify(&uid, "hunter42"));
+    assert_eq!(
+        provider.get_uid_by_token(&token),
+        Err(AuthError::InvalidToken)
+    );
+    assert_eq!(
+        provider.refresh_session(&token),
+        Err(AuthError::UserDisabled)
+    );
+
+    provider.set_disabled(&uid, false).unwrap();
+
+    assert!(provider.verify(&uid, "hunter42"));
+    assert_eq!(provider.get_uid_by_token(&token), Ok(uid.clone()));
+
+    provider.invalidate_session(&token);
+    provider.set_disabled(&uid, true).unwrap();
+
+    assert_eq!(provider.create_session(&uid), Err(AuthError::UserDisabled));
+    assert_eq!(
+        provider.set_disabled("nonexistent", true),
+        Err(AuthError::UserNotFound)
+    );
+}
+
+#[test]
+fn list_users_test() {
+    let mut provider: AuthProvider<Vec<User>> = AuthProvider::default();
+
+    let uids: Vec<String> = (0..5)
+        .map(|i| provider.create_user(format!("password{}", i)).unwrap())
+        .collect();
+
+    assert_eq!(provider.count_users(), Ok(5));
+    assert_eq!(provider.list_uids(0, 2), Ok(uids[0..2].to_vec()));
+    assert_eq!(provider.list_uids(2, 10), Ok(uids[2..].to_vec()));
+    assert_eq!(provider.list_uids(5, 10), Ok(Vec::new()));
+}
+
+#[test]
+#[cfg(feature = "json")]
+fn user_without_disabled_field_test() {
+    use humphrey_json::prelude::*;
+
+    let user = User::from_json(&json!({
+        "uid": "abc",
+        "session": null,
+        "password_hash": "hash"
+    }))
+    .unwrap();
+
+    assert!(!user.disabled);
+    assert_eq!(user.to_json().get("disabled"), Some(&json!(false)));
+}
@@ -1,2 +1,7 @@
+#[cfg(feature = "error")]
+pub mod error;
+#[cfg(feature = "json")]
+pub mod json;
 pub mod main;
+pub mod pool;
 pub mod session;
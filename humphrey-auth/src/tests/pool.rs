@@ -0,0 +1,155 @@
+use crate::database::{AuthDatabase, PooledDatabase};
+use crate::error::AuthError;
+use crate::session::Session;
+use crate::{AuthProvider, User};
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::{sleep, spawn};
+use std::time::Duration;
+
+const LATENCY: Duration = Duration::from_millis(50);
+
+/// Records how many connections are checked out at once.
+#[derive(Default)]
+struct Stats {
+    checkouts: AtomicUsize,
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+}
+
+/// A connection to a fake database which takes `LATENCY` to answer every query.
+struct FakeConnection {
+    users: Arc<RwLock<Vec<User>>>,
+    stats: Arc<Stats>,
+}
+
+impl FakeConnection {
+    fn query<T>(&self, f: impl FnOnce(&mut Vec<User>) -> T) -> T {
+        let in_flight = self.stats.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.stats
+            .max_in_flight
+            .fetch_max(in_flight, Ordering::SeqCst);
+
+        sleep(LATENCY);
+        let result = f(&mut self.users.write().unwrap());
+
+        self.stats.in_flight.fetch_sub(1, Ordering::SeqCst);
+        result
+    }
+}
+
+impl AuthDatabase for FakeConnection {
+    fn get_user_by_uid(&self, uid: impl AsRef<str>) -> Option<User> {
+        self.query(|users| users.get_user_by_uid(uid))
+    }
+
+    fn get_user_by_token(&self, token: impl AsRef<str>) -> Option<User> {
+        self.query(|users| users.get_user_by_token(token))
+    }
+
+    fn get_session_by_token(&self, token: impl AsRef<str>) -> Option<Session> {
+        self.query(|users| users.get_session_by_token(token))
+    }
+
+    fn update_user(&mut self, user: User) -> Result<(), AuthError> {
+        self.query(|users| users.update_user(user))
+    }
+
+    fn add_user(&mut self, user: User) -> Result<(), AuthError> {
+        self.query(|users| users.add_user(user))
+    }
+
+    fn remove_user(&mut self, uid: impl AsRef<str>) -> Result<(), AuthError> {
+        self.query(|users| users.remove_user(uid))
+    }
+}
+
+fn pooled_database() -> (PooledDatabase<FakeConnection, AuthError>, Arc<Stats>) {
+    let users = Arc::new(RwLock::new(Vec::new()));
+    let stats = Arc::new(Stats::default());
+    let cloned_stats = stats.clone();
+
+    let database = PooledDatabase::new(move || {
+        cloned_stats.checkouts.fetch_add(1, Ordering::SeqCst);
+
+        Ok(FakeConnection {
+            users: users.clone(),
+            stats: cloned_stats.clone(),
+        })
+    });
+
+    (database, stats)
+}
+
+#[test]
+fn pooled_database_test() {
+    let (database, stats) = pooled_database();
+    let mut provider = AuthProvider::new(database);
+
+    let uid = provider.create_user("hunter42").unwrap();
+    let token = provider.create_session(&uid).unwrap();
+
+    assert!(provider.verify(&uid, "hunter42"));
+    assert_eq!(provider.get_uid_by_token(&token), Ok(uid.clone()));
+    assert_eq!(provider.count_users(), Err(AuthError::Unsupported));
+
+    // One connection for each call to the database, with `create_session` making two
+    assert_eq!(stats.checkouts.load(Ordering::SeqCst), 6);
+}
+
+#[test]
+fn pooled_database_unavailable_test() {
+    let errors = Arc::new(AtomicUsize::new(0));
+    let cloned_errors = errors.clone();
+
+    let database: PooledDatabase<Vec<User>, &str> = PooledDatabase::new(|| Err("pool exhausted"))
+        .with_error_handler(move |error| {
+            assert_eq!(*error, "pool exhausted");
+            cloned_errors.fetch_add(1, Ordering::SeqCst);
+        });
+    let mut provider = AuthProvider::new(database);
+
+    assert_eq!(
+        provider.create_user("hunter42"),
+        Err(AuthError::DatabaseUnavailable)
+    );
+    assert!(!provider.exists("uid"));
+    assert_eq!(errors.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn sharded_provider_stress_test() {
+    const THREADS: usize = 8;
+
+    let (database, stats) = pooled_database();
+    let mut provider = AuthProvider::new(database);
+    let uid = provider.create_user("hunter42").unwrap();
+
+    let shards: Arc<Vec<Mutex<AuthProvider<_>>>> = Arc::new(
+        (0..THREADS)
+            .map(|_| Mutex::new(provider.clone_with_shared_config()))
+            .collect(),
+    );
+
+    stats.max_in_flight.store(0, Ordering::SeqCst);
+
+    let threads: Vec<_> = (0..THREADS)
+        .map(|i| {
+            let shards = shards.clone();
+            let uid = uid.clone();
+
+            spawn(move || {
+                let provider = shards[i].lock().unwrap();
+                provider.verify(&uid, "hunter42")
+            })
+        })
+        .collect();
+
+    for thread in threads {
+        assert!(thread.join().unwrap());
+    }
+
+    // Serialised verifies would only ever hold one connection at a time
+    assert!(stats.max_in_flight.load(Ordering::SeqCst) > 1);
+}
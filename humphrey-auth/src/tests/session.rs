@@ -1,12 +1,17 @@
+use crate::database::AuthDatabase;
 use crate::error::AuthError;
+use crate::session::Session;
 use crate::{AuthProvider, User};
 
+use humphrey::clock::{override_clock, MockClock};
+
 use std::error::Error;
-use std::thread::sleep;
 use std::time::Duration;
 
 #[test]
 fn session_test() -> Result<(), Box<dyn Error>> {
+    let clock = MockClock::new();
+    let _guard = override_clock(clock.clone());
     let mut auth: AuthProvider<Vec<User>> = AuthProvider::default();
 
     let uid_1 = auth.create_user("password1")?;
@@ -28,7 +33,7 @@ fn session_test() -> Result<(), Box<dyn Error>> {
     assert_eq!(auth.get_uid_by_token(&token_2), err);
 
     // Wait for token 1 to expire
-    sleep(Duration::from_secs(1));
+    clock.advance(Duration::from_secs(1));
 
     // Both tokens are invalid
     assert_eq!(auth.get_uid_by_token(&token_1), err);
@@ -36,3 +41,103 @@ fn session_test() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[test]
+fn expiry_test() {
+    let clock = MockClock::new();
+    let _guard = override_clock(clock.clone());
+
+    let mut session = Session::create_with_lifetime(60);
+    assert!(session.valid());
+    assert!(!session.expired());
+
+    // The session is no longer valid at the moment it expires, but is only expired afterwards
+    clock.advance(Duration::from_secs(60));
+    assert!(!session.valid());
+    assert!(!session.expired());
+
+    clock.advance(Duration::from_secs(1));
+    assert!(session.expired());
+    assert_eq!(session.age(), Duration::from_secs(61));
+
+    // Refreshing extends the session from the current time without reauthenticating
+    session.refresh(60);
+    assert!(session.valid());
+    assert_eq!(session.age(), Duration::from_secs(61));
+
+    clock.advance(Duration::from_secs(59));
+    assert!(session.valid());
+
+    session.reauthenticate();
+    assert_eq!(session.age(), Duration::ZERO);
+}
+
+#[test]
+fn rotation_test() -> Result<(), Box<dyn Error>> {
+    let mut auth: AuthProvider<Vec<User>> = AuthProvider::default();
+
+    let uid = auth.create_user("password")?;
+    let token = auth.create_session(&uid)?;
+    let session = auth.users.get_session_by_token(&token).unwrap();
+
+    let new_token = auth.rotate_session(&token)?;
+    let new_session = auth.users.get_session_by_token(&new_token).unwrap();
+
+    // The old token no longer works, but the rest of the session is preserved
+    assert_ne!(token, new_token);
+    assert_eq!(auth.get_uid_by_token(&token), Err(AuthError::InvalidToken));
+    assert_eq!(auth.get_uid_by_token(&new_token), Ok(uid));
+    assert_eq!(new_session.expiry, session.expiry);
+    assert_eq!(new_session.authenticated_at, session.authenticated_at);
+
+    assert_eq!(auth.rotate_session(&token), Err(AuthError::InvalidToken));
+
+    Ok(())
+}
+
+#[test]
+fn reauthentication_test() -> Result<(), Box<dyn Error>> {
+    let clock = MockClock::new();
+    let _guard = override_clock(clock.clone());
+    let mut auth: AuthProvider<Vec<User>> = AuthProvider::default();
+
+    let uid = auth.create_user("password")?;
+    assert_eq!(
+        auth.reauthenticate(&uid, "password"),
+        Err(AuthError::InvalidToken)
+    );
+
+    let token = auth.create_session_with_lifetime(&uid, 7200)?;
+    assert_eq!(auth.session_age(&token)?, Duration::ZERO);
+
+    clock.advance(Duration::from_secs(3600));
+    assert_eq!(auth.session_age(&token)?, Duration::from_secs(3600));
+
+    assert_eq!(
+        auth.reauthenticate(&uid, "wrong"),
+        Err(AuthError::IncorrectPassword)
+    );
+    assert_eq!(auth.session_age(&token)?, Duration::from_secs(3600));
+
+    auth.reauthenticate(&uid, "password")?;
+    assert_eq!(auth.session_age(&token)?, Duration::ZERO);
+
+    auth.invalidate_session(&token);
+    assert_eq!(auth.session_age(&token), Err(AuthError::InvalidToken));
+
+    Ok(())
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn legacy_session_json_test() {
+    use crate::session::Session;
+
+    let session: Session = humphrey_json::from_str(r#"{"token": "abc", "expiry": 1}"#).unwrap();
+    assert_eq!(session.authenticated_at, 0);
+
+    let session = Session::create();
+    let json = humphrey_json::to_string(&session);
+    let parsed: Session = humphrey_json::from_str(json).unwrap();
+    assert!(parsed == session);
+}
@@ -18,6 +18,9 @@ pub struct User {
     pub session: Option<Session>,
     /// The Argon2 hashed password of the user.
     pub password_hash: String,
+    /// Whether the user has been disabled.
+    /// Disabled users cannot log in, and any existing session they have is treated as invalid.
+    pub disabled: bool,
 }
 
 impl User {
@@ -38,10 +41,12 @@ impl User {
             uid,
             session: None,
             password_hash,
+            disabled: false,
         })
     }
 
     /// Verifies that the given password matches the password of the user.
+    /// This does not check whether the user has been disabled.
     pub fn verify(&self, password: impl AsRef<str>, pepper: Option<&[u8]>) -> bool {
         let password = password.as_ref().as_bytes();
         let argon2 = create_argon2_instance(pepper);
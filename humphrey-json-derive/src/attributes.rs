@@ -0,0 +1,102 @@
+//! Provides functions for parsing the attributes which can be applied to fields.
+
+use syn::spanned::Spanned;
+use syn::{Attribute, Error, Field, Lit, Meta, NestedMeta, Path};
+
+/// Represents the options specified by the attributes of a single field.
+pub struct FieldAttributes {
+    /// The name of the field in the JSON representation.
+    pub name: String,
+    /// Whether the field should take its default value if it is missing.
+    pub default: bool,
+    /// The path to a function which validates the field after it has been parsed.
+    ///
+    /// Parsed from a string literal, so every token in the path carries the span of the literal.
+    pub validate: Option<Path>,
+    /// The path to a module containing `from_json` and `to_json` functions for the field.
+    pub with: Option<Path>,
+}
+
+impl FieldAttributes {
+    /// Parses the attributes of the given named field.
+    ///
+    /// Both the `#[rename = "..."]` attribute and the `#[json(...)]` attribute are supported, and any
+    ///   other attributes (such as doc comments) are ignored.
+    pub fn parse(field: &Field) -> Result<Self, Error> {
+        let mut attributes = Self {
+            name: field.ident.as_ref().unwrap().to_string(),
+            default: false,
+            validate: None,
+            with: None,
+        };
+
+        for attr in &field.attrs {
+            if attr.path.is_ident("rename") {
+                attributes.name = parse_rename(attr)?;
+            } else if attr.path.is_ident("json") {
+                attributes.parse_json(attr)?;
+            }
+        }
+
+        Ok(attributes)
+    }
+
+    /// Parses a `#[json(...)]` attribute, updating the options accordingly.
+    fn parse_json(&mut self, attr: &Attribute) -> Result<(), Error> {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => {
+                return Err(Error::new(
+                    meta.span(),
+                    "expected `#[json(...)]` with a list of options",
+                ))
+            }
+        };
+
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("default") => {
+                    self.default = true;
+                }
+                NestedMeta::Meta(Meta::NameValue(name_value)) => {
+                    let string = match &name_value.lit {
+                        Lit::Str(s) => s,
+                        lit => return Err(Error::new(lit.span(), "expected a string literal")),
+                    };
+
+                    if name_value.path.is_ident("rename") {
+                        self.name = string.value();
+                    } else if name_value.path.is_ident("validate") {
+                        self.validate = Some(string.parse()?);
+                    } else if name_value.path.is_ident("with") {
+                        self.with = Some(string.parse()?);
+                    } else {
+                        return Err(Error::new(
+                            name_value.path.span(),
+                            "unknown option, expected one of `rename`, `default`, `validate` or `with`",
+                        ));
+                    }
+                }
+                nested => {
+                    return Err(Error::new(
+                        nested.span(),
+                        "unknown option, expected one of `rename`, `default`, `validate` or `with`",
+                    ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses the name given by a `#[rename = "..."]` attribute.
+pub fn parse_rename(attr: &Attribute) -> Result<String, Error> {
+    match attr.parse_meta()? {
+        Meta::NameValue(name_value) => match name_value.lit {
+            Lit::Str(s) => Ok(s.value()),
+            lit => Err(Error::new(lit.span(), "expected a string literal")),
+        },
+        meta => Err(Error::new(meta.span(), "expected `#[rename = \"...\"]`")),
+    }
+}
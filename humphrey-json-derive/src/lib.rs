@@ -7,6 +7,7 @@
 
 extern crate proc_macro;
 
+mod attributes;
 mod enum_type;
 mod named_struct;
 mod tuple_struct;
@@ -19,7 +20,13 @@ use syn::{parse_macro_input, Data, DeriveInput, Error};
 /// Derives the `FromJson` trait for a type.
 ///
 /// This macro can be used on named structs, tuple structs, and enums. It is not currently supported for enums with data variants.
-#[proc_macro_derive(FromJson, attributes(rename))]
+///
+/// Fields of named structs can be configured with the `#[json(...)]` attribute, which accepts the following options:
+/// - `rename = "name"`: use a different name for the field in the JSON representation.
+/// - `default`: use the field type's default value if the field is missing.
+/// - `validate = "path::to::fn"`: run a `fn(&T) -> Result<(), String>` on the parsed value, failing with `ParseError::ValidationError` containing the field name and the message if it returns an error.
+/// - `with = "path::to::module"`: parse the field with the module's `fn from_json(&Value) -> Result<T, ParseError>` instead of the `FromJson` trait.
+#[proc_macro_derive(FromJson, attributes(rename, json))]
 pub fn derive_from_json(input: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(input as DeriveInput);
 
@@ -71,7 +78,10 @@ pub fn derive_from_json(input: TokenStream) -> TokenStream {
 /// Derives the `IntoJson` trait for a type.
 ///
 /// This macro can be used on named structs, tuple structs, and enums. It is not currently supported for enums with data variants.
-#[proc_macro_derive(IntoJson, attributes(rename))]
+///
+/// The `#[json(rename = "name")]` and `#[json(with = "path::to::module")]` field attributes are respected, the latter
+///   serializing the field with the module's `fn to_json(&T) -> Value` instead of the `IntoJson` trait.
+#[proc_macro_derive(IntoJson, attributes(rename, json))]
 pub fn derive_into_json(input: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(input as DeriveInput);
 
@@ -1,40 +1,35 @@
 //! Provides functions for deriving the traits on named structs.
 
+use crate::attributes::FieldAttributes;
+
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{DataStruct, DeriveInput, Field, Ident, Lit, Meta};
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{DataStruct, DeriveInput, Error, Field, Ident};
 
 /// Derives the `FromJson` trait for a named struct.
 pub fn from_json_named_struct(ast: DeriveInput, r#struct: &DataStruct) -> TokenStream {
-    let fields: Vec<Field> = r#struct.fields.iter().cloned().collect();
+    let fields: Vec<(Field, FieldAttributes)> = match parse_fields(r#struct) {
+        Ok(fields) => fields,
+        Err(e) => return e.to_compile_error().into(),
+    };
 
     let idents: Vec<Ident> = fields
         .iter()
-        .map(|field| field.ident.clone().unwrap())
+        .map(|(field, _)| field.ident.clone().unwrap())
         .collect();
 
-    let names: Vec<String> = fields
+    // Fields are parsed into prefixed local variables so they can't shadow the `value` argument.
+    let locals: Vec<Ident> = idents
         .iter()
-        .map(|field| {
-            if field.attrs.is_empty() {
-                field.ident.as_ref().unwrap().to_string()
-            } else {
-                let attr = field
-                    .attrs
-                    .iter()
-                    .find(|attr| attr.path.is_ident("rename"))
-                    .expect("Unknown attribute");
-                let meta = attr.parse_meta().unwrap();
-
-                match meta {
-                    Meta::NameValue(name_value) => match name_value.lit {
-                        Lit::Str(s) => s.value(),
-                        _ => panic!("Attribute format incorrect"),
-                    },
-                    _ => panic!("Attribute format incorrect"),
-                }
-            }
-        })
+        .map(|ident| format_ident!("field_{}", ident))
+        .collect();
+
+    let parsers: Vec<TokenStream2> = fields
+        .iter()
+        .zip(locals.iter())
+        .map(|((field, attrs), local)| field_parser(field, attrs, local))
         .collect();
 
     let name = &ast.ident;
@@ -43,9 +38,11 @@ pub fn from_json_named_struct(ast: DeriveInput, r#struct: &DataStruct) -> TokenS
     let tokens = quote! {
         impl #impl_generics ::humphrey_json::traits::FromJson for #name #ty_generics #where_clause {
             fn from_json(value: &::humphrey_json::Value) -> Result<Self, ::humphrey_json::error::ParseError> {
+                #(#parsers)*
+
                 Ok(Self {
                     #(
-                        #idents: ::humphrey_json::traits::FromJson::from_json(value.get(#names).unwrap_or(&::humphrey_json::Value::Null))?,
+                        #idents: #locals,
                     )*
                 })
             }
@@ -57,33 +54,29 @@ pub fn from_json_named_struct(ast: DeriveInput, r#struct: &DataStruct) -> TokenS
 
 /// Derives the `IntoJson` trait for a named struct.
 pub fn into_json_named_struct(ast: DeriveInput, r#struct: &DataStruct) -> TokenStream {
-    let fields: Vec<Field> = r#struct.fields.iter().cloned().collect();
+    let fields: Vec<(Field, FieldAttributes)> = match parse_fields(r#struct) {
+        Ok(fields) => fields,
+        Err(e) => return e.to_compile_error().into(),
+    };
 
-    let idents: Vec<Ident> = fields
-        .iter()
-        .map(|field| field.ident.clone().unwrap())
-        .collect();
+    let names: Vec<&String> = fields.iter().map(|(_, attrs)| &attrs.name).collect();
 
-    let names: Vec<String> = fields
+    let values: Vec<TokenStream2> = fields
         .iter()
-        .map(|field| {
-            if field.attrs.is_empty() {
-                field.ident.as_ref().unwrap().to_string()
-            } else {
-                let attr = field
-                    .attrs
-                    .iter()
-                    .find(|attr| attr.path.is_ident("rename"))
-                    .expect("Unknown attribute");
-                let meta = attr.parse_meta().unwrap();
-
-                match meta {
-                    Meta::NameValue(name_value) => match name_value.lit {
-                        Lit::Str(s) => s.value(),
-                        _ => panic!("Attribute format incorrect"),
-                    },
-                    _ => panic!("Attribute format incorrect"),
-                }
+        .map(|(field, attrs)| {
+            let ident = field.ident.as_ref().unwrap();
+            let ty = &field.ty;
+
+            match &attrs.with {
+                Some(with) => quote_spanned! {with.span()=>
+                    {
+                        let to_json: fn(&#ty) -> ::humphrey_json::Value = #with::to_json;
+                        to_json(&self.#ident)
+                    }
+                },
+                None => quote! {
+                    ::humphrey_json::traits::IntoJson::to_json(&self.#ident)
+                },
             }
         })
         .collect();
@@ -98,7 +91,7 @@ pub fn into_json_named_struct(ast: DeriveInput, r#struct: &DataStruct) -> TokenS
 
                 json!({
                     #(
-                        #names: (::humphrey_json::traits::IntoJson::to_json(&self.#idents)),
+                        #names: (#values),
                     )*
                 })
             }
@@ -107,3 +100,63 @@ pub fn into_json_named_struct(ast: DeriveInput, r#struct: &DataStruct) -> TokenS
 
     TokenStream::from(tokens)
 }
+
+/// Parses the attributes of every field of the struct.
+fn parse_fields(r#struct: &DataStruct) -> Result<Vec<(Field, FieldAttributes)>, Error> {
+    r#struct
+        .fields
+        .iter()
+        .map(|field| Ok((field.clone(), FieldAttributes::parse(field)?)))
+        .collect()
+}
+
+/// Generates the code to parse, default and validate a single field into the given local variable.
+///
+/// User-supplied functions are first assigned to a function pointer of the expected type, spanned
+///   to the attribute, so that a signature mismatch is reported at the attribute rather than
+///   somewhere in the generated code.
+fn field_parser(field: &Field, attrs: &FieldAttributes, local: &Ident) -> TokenStream2 {
+    let ty = &field.ty;
+    let name = &attrs.name;
+
+    let from_json = match &attrs.with {
+        Some(with) => quote_spanned! {with.span()=>
+            let from_json: fn(&::humphrey_json::Value) -> ::std::result::Result<#ty, ::humphrey_json::error::ParseError> = #with::from_json;
+        },
+        None => quote! {
+            let from_json = <#ty as ::humphrey_json::traits::FromJson>::from_json;
+        },
+    };
+
+    let parse = if attrs.default {
+        quote! {
+            match value.get(#name) {
+                Some(json) => from_json(json)?,
+                None => ::std::default::Default::default(),
+            }
+        }
+    } else {
+        quote! {
+            from_json(value.get(#name).unwrap_or(&::humphrey_json::Value::Null))?
+        }
+    };
+
+    let validate = attrs.validate.as_ref().map(|validate| {
+        quote_spanned! {validate.span()=>
+            let validate: fn(&#ty) -> ::std::result::Result<(), ::std::string::String> = #validate;
+
+            if let Err(message) = validate(&#local) {
+                return Err(::humphrey_json::error::ParseError::ValidationError(#name, message));
+            }
+        }
+    });
+
+    quote! {
+        let #local: #ty = {
+            #from_json
+            #parse
+        };
+
+        #validate
+    }
+}
@@ -4,7 +4,7 @@ use std::error::Error;
 use std::fmt::Display;
 
 /// Represents an error during parsing.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ParseError {
     /// An unknown error was encountered. This is likely a bug with Humphrey JSON.
     UnknownError,
@@ -22,10 +22,13 @@ pub enum ParseError {
     MissingField,
     /// The maximum recursion depth was exceeded.
     RecursionDepthExceeded,
+    /// A field was parsed successfully but failed validation. Contains the name of the field and the
+    ///   message returned by its validator.
+    ValidationError(&'static str, String),
 }
 
 /// Encapsulates a parse error and its location.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct TracebackError {
     pub(crate) line: usize,
     pub(crate) column: usize,
@@ -40,13 +43,31 @@ impl From<TracebackError> for ParseError {
 
 impl Display for TracebackError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "error at {}:{}: {:?}", self.line, self.column, self.kind)
+        write!(f, "{} at {}:{}", self.kind, self.line, self.column)
     }
 }
 
 impl Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            ParseError::UnknownError => write!(f, "JSON error: an unknown error occurred"),
+            ParseError::InvalidToken => write!(f, "JSON error: invalid token"),
+            ParseError::UnexpectedEOF => write!(f, "JSON error: unexpected end of input"),
+            ParseError::InvalidEscapeSequence => write!(f, "JSON error: invalid escape sequence"),
+            ParseError::TrailingComma => write!(f, "JSON error: trailing comma"),
+            ParseError::TypeError => write!(f, "JSON error: value has the wrong type"),
+            ParseError::MissingField => write!(f, "JSON error: missing field"),
+            ParseError::RecursionDepthExceeded => {
+                write!(f, "JSON error: maximum recursion depth exceeded")
+            }
+            ParseError::ValidationError(field, message) => {
+                write!(
+                    f,
+                    "JSON error: field `{}` failed validation: {}",
+                    field, message
+                )
+            }
+        }
     }
 }
 
@@ -7,6 +7,8 @@
 // Reference:
 // - [serde_json::macros](https://github.com/serde-rs/json/blob/94019a31c6036dc4ebb9afc44a214f950caf0d1f/src/macros.rs)
 
+use crate::Value;
+
 /// Create a JSON value from JSON-like syntax.
 ///
 /// ## Usage
@@ -28,6 +30,51 @@
 /// assert_eq!(json!(1234), Value::Number(1234.0));
 /// assert_eq!(json!("Hello, world!"), Value::String("Hello, world!".into()));
 /// ```
+///
+/// ## Interpolation
+/// Any expression whose type implements `IntoJson`, as well as any `Value`, can be used as a value.
+/// Expressions which start with `[` or `{` must be wrapped in parentheses, since they would
+///   otherwise be parsed as JSON, as must keys which are more than a single token.
+///
+/// ```
+/// let name = "William";
+/// let value = json!({
+///     "name": name,
+///     "length": name.len(),
+///     "first": ([1, 2, 3].first()),
+///     (format!("{}_id", name)): 1
+/// });
+/// ```
+///
+/// ## Spreading and Conditional Fields
+/// In an object, `..expr` merges the fields of another object into the object, where `expr` is a
+///   `Value` or implements `IntoJson`. Fields which appear later, whether they are written out or
+///   spread, replace earlier fields with the same key. Spreading `null` has no effect.
+///
+/// A field written as `key?: expr`, where `expr` is an `Option`, is omitted if it is `None`.
+///
+/// ```
+/// let defaults = json!({ "theme": "light", "language": "en" });
+/// let nickname: Option<&str> = None;
+///
+/// let value = json!({
+///     ..defaults,
+///     "theme": "dark",
+///     "nickname"?: nickname
+/// });
+///
+/// assert_eq!(value, json!({ "theme": "dark", "language": "en" }));
+/// ```
+///
+/// ## Panics
+/// Whether a spread value is an object is only known once it has been converted into a `Value`, so
+///   spreading a value which is neither an object nor `null`, such as an array or a string, panics.
+///
+/// ```should_panic
+/// let tags = json!(["a", "b"]);
+///
+/// json!({ ..tags });
+/// ```
 #[macro_export]
 macro_rules! json {
     () => {
@@ -42,9 +89,11 @@ macro_rules! json {
     ({}) => {
         $crate::Value::Object(Vec::new())
     };
-    ({ $($elems:tt)* }) => {
-        $crate::Value::Object($crate::json_object_internal!([] $($elems)*))
-    };
+    ({ $($elems:tt)* }) => {{
+        let mut object = $crate::Value::Object(Vec::new());
+        $crate::json_object_internal!(object $($elems)*);
+        object
+    }};
     ($v:expr) => {
         $crate::Value::from($v)
     };
@@ -94,46 +143,89 @@ macro_rules! json_array_internal {
 }
 
 /// Internal macro, do not use.
+///
+/// Sets the fields of the object value named by the first token, one at a time.
 #[macro_export]
 #[doc(hidden)]
 macro_rules! json_object_internal {
-    ([ $($elems:expr,)* ]) => {
-        vec![ $( $elems, )* ]
+    ($object:ident) => {};
+
+    // Comma.
+    ($object:ident , $($rest:tt)*) => {
+        $crate::json_object_internal!($object $($rest)*);
     };
 
-    ([ $($elems:expr),* ]) => {
-        vec![ $( $elems ),* ]
+    // Next entry is a spread object.
+    ($object:ident .. $value:expr , $($rest:tt)*) => {
+        $crate::macros::json_spread_internal(&mut $object, $crate::json!($value));
+        $crate::json_object_internal!($object $($rest)*);
+    };
+
+    // Last entry is a spread object.
+    ($object:ident .. $value:expr) => {
+        $crate::macros::json_spread_internal(&mut $object, $crate::json!($value));
+    };
+
+    // Next value is optional.
+    ($object:ident $key:tt ?: $value:expr , $($rest:tt)*) => {
+        if let Some(value) = $value {
+            $object[$key.to_string()] = $crate::json!(value);
+        }
+        $crate::json_object_internal!($object $($rest)*);
+    };
+
+    // Last value is optional.
+    ($object:ident $key:tt ?: $value:expr) => {
+        if let Some(value) = $value {
+            $object[$key.to_string()] = $crate::json!(value);
+        }
     };
 
     // Next value is `null`.
-    ([ $($elems:expr,)* ] $key:tt : null $($rest:tt)*) => {
-        $crate::json_object_internal!([ $($elems,)* ($key.to_string(), $crate::Value::Null), ] $($rest)*)
+    ($object:ident $key:tt : null $($rest:tt)*) => {
+        $object[$key.to_string()] = $crate::Value::Null;
+        $crate::json_object_internal!($object $($rest)*);
     };
 
     // Next value is an array.
-    ([ $($elems:expr,)* ] $key:tt : [ $($array:tt)* ] $($rest:tt)*) => {
-        $crate::json_object_internal!([ $($elems,)* ($key.to_string(), $crate::json!([ $($array)* ])), ] $($rest)*)
+    ($object:ident $key:tt : [ $($array:tt)* ] $($rest:tt)*) => {
+        $object[$key.to_string()] = $crate::json!([ $($array)* ]);
+        $crate::json_object_internal!($object $($rest)*);
     };
 
     // Next value is an object.
-    ([ $($elems:expr,)* ] $key:tt : { $($object:tt)* } $($rest:tt)*) => {
-        $crate::json_object_internal!([ $($elems,)* ($key.to_string(), $crate::json!({ $($object)* })), ] $($rest)*)
+    ($object:ident $key:tt : { $($inner:tt)* } $($rest:tt)*) => {
+        $object[$key.to_string()] = $crate::json!({ $($inner)* });
+        $crate::json_object_internal!($object $($rest)*);
     };
 
     // Next value is an expression.
-    ([ $($elems:expr,)* ] $key:tt : $value:expr, $($rest:tt)*) => {
-        $crate::json_object_internal!([ $($elems,)* ($key.to_string(), $crate::json!($value)), ] $($rest)*)
+    ($object:ident $key:tt : $value:expr , $($rest:tt)*) => {
+        $object[$key.to_string()] = $crate::json!($value);
+        $crate::json_object_internal!($object $($rest)*);
     };
 
     // Last value is an expression.
-    ([ $($elems:expr,)* ] $key:tt : $value:expr) => {
-        $crate::json_object_internal!([ $($elems,)* ($key.to_string(), $crate::json!($value)), ])
+    ($object:ident $key:tt : $value:expr) => {
+        $object[$key.to_string()] = $crate::json!($value);
     };
+}
 
-    // Comma.
-    ([ $($elems:expr,)* ] , $($rest:tt)*) => {
-        $crate::json_object_internal!([ $($elems,)* ] $($rest)*)
-    };
+/// Internal function used by the `json!` macro to merge the fields of `value` into `object`, do not
+///   use.
+///
+/// Panics if `value` is neither an object nor `null`.
+#[doc(hidden)]
+pub fn json_spread_internal(object: &mut Value, value: Value) {
+    match value {
+        Value::Object(fields) => {
+            for (key, value) in fields {
+                object[key] = value;
+            }
+        }
+        Value::Null => (),
+        _ => panic!("Only objects can be spread into a JSON object"),
+    }
 }
 
 /// Specifies a mapping between a Rust data structure and a JSON value.
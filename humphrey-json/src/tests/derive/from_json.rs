@@ -1,3 +1,4 @@
+use crate::error::ParseError;
 use crate::prelude::*;
 
 #[test]
@@ -211,3 +212,86 @@ fn renamed_enum_from_json() {
     assert!(Enum::from_json(&json!("VariantB")).is_err());
     assert!(Enum::from_json(&json!("VariantC")).is_err());
 }
+
+#[test]
+fn attributed_struct_from_json() {
+    mod validation {
+        pub fn positive(n: &i64) -> Result<(), String> {
+            match *n > 0 {
+                true => Ok(()),
+                false => Err("must be positive".into()),
+            }
+        }
+    }
+
+    mod hex {
+        use crate::error::ParseError;
+        use crate::Value;
+
+        pub fn from_json(value: &Value) -> Result<u32, ParseError> {
+            let string = value.as_str().ok_or(ParseError::TypeError)?;
+            u32::from_str_radix(string, 16).map_err(|_| ParseError::TypeError)
+        }
+    }
+
+    #[derive(FromJson, PartialEq, Debug)]
+    struct Test {
+        #[json(rename = "n", validate = "validation::positive")]
+        number: i64,
+        #[json(with = "hex")]
+        colour: u32,
+        #[json(default)]
+        tags: Vec<String>,
+        /// Doc comments on fields are ignored by the derive macro.
+        value: bool,
+    }
+
+    assert_eq!(
+        Test::from_json(&json!({
+            "n": 1,
+            "colour": "ff0000",
+            "value": true
+        }))
+        .unwrap(),
+        Test {
+            number: 1,
+            colour: 0xff0000,
+            tags: Vec::new(),
+            value: true
+        }
+    );
+
+    assert_eq!(
+        Test::from_json(&json!({
+            "n": 0,
+            "colour": "ff0000",
+            "value": true
+        })),
+        Err(ParseError::ValidationError("n", "must be positive".into()))
+    );
+
+    assert_eq!(
+        Test::from_json(&json!({
+            "n": 1,
+            "colour": "not hex",
+            "value": true
+        })),
+        Err(ParseError::TypeError)
+    );
+
+    assert_eq!(
+        Test::from_json(&json!({
+            "n": 1,
+            "colour": "ff0000",
+            "tags": null,
+            "value": true
+        })),
+        Err(ParseError::TypeError)
+    );
+}
+
+#[test]
+fn test_derive_compile_errors() {
+    let tests = trybuild::TestCases::new();
+    tests.compile_fail("src/tests/derive/ui/*.rs");
+}
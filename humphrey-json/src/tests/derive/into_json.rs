@@ -190,3 +190,34 @@ fn renamed_enum_into_json() {
     assert_eq!(Enum::VariantB.to_json(), json!("variant_b"));
     assert_eq!(Enum::VariantC.to_json(), json!("variant_c"));
 }
+
+#[test]
+fn attributed_struct_into_json() {
+    mod hex {
+        use crate::Value;
+
+        pub fn to_json(value: &u32) -> Value {
+            Value::String(format!("{:06x}", value))
+        }
+    }
+
+    #[derive(IntoJson)]
+    struct Test {
+        #[json(rename = "n")]
+        name: String,
+        #[json(with = "hex", default)]
+        colour: u32,
+    }
+
+    assert_eq!(
+        Test {
+            name: "red".to_string(),
+            colour: 0xff0000,
+        }
+        .to_json(),
+        json!({
+            "n": "red",
+            "colour": "ff0000"
+        })
+    );
+}
@@ -0,0 +1,16 @@
+use humphrey_json::prelude::*;
+
+fn positive(n: i64) -> Result<(), String> {
+    match n > 0 {
+        true => Ok(()),
+        false => Err("must be positive".into()),
+    }
+}
+
+#[derive(FromJson)]
+struct Settings {
+    #[json(validate = "positive")]
+    threads: i64,
+}
+
+fn main() {}
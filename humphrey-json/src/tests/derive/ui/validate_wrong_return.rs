@@ -0,0 +1,13 @@
+use humphrey_json::prelude::*;
+
+fn positive(n: &i64) -> bool {
+    *n > 0
+}
+
+#[derive(FromJson)]
+struct Settings {
+    #[json(validate = "positive")]
+    threads: i64,
+}
+
+fn main() {}
@@ -122,3 +122,117 @@ fn test_macro_complex_embedded_variables() {
 
     assert_eq!(value, expected_value);
 }
+
+#[test]
+fn test_macro_spread() {
+    let defaults = json!({
+        "theme": "light",
+        "language": "en",
+        "notifications": true
+    });
+
+    let value = json!({
+        "user": "William",
+        ..defaults.clone(),
+        "theme": "dark"
+    });
+
+    let expected_value = Value::Object(vec![
+        ("user".into(), Value::String("William".into())),
+        ("theme".into(), Value::String("dark".into())),
+        ("language".into(), Value::String("en".into())),
+        ("notifications".into(), Value::Bool(true)),
+    ]);
+
+    assert_eq!(value, expected_value);
+
+    // Spread fields replace earlier fields in place
+    let value = json!({
+        "theme": "dark",
+        "user": "William",
+        ..defaults
+    });
+
+    let expected_value = Value::Object(vec![
+        ("theme".into(), Value::String("light".into())),
+        ("user".into(), Value::String("William".into())),
+        ("language".into(), Value::String("en".into())),
+        ("notifications".into(), Value::Bool(true)),
+    ]);
+
+    assert_eq!(value, expected_value);
+}
+
+#[test]
+fn test_macro_nested_spread() {
+    let base = json!({ "a": 1, "b": 2 });
+    let nothing = Value::Null;
+
+    let value = json!({
+        ..json!({ ..base.clone(), "b": 3 }),
+        "inner": { ..base, "c": 4 },
+        ..nothing
+    });
+
+    let expected_value = Value::Object(vec![
+        ("a".into(), Value::Number(1.0)),
+        ("b".into(), Value::Number(3.0)),
+        (
+            "inner".into(),
+            Value::Object(vec![
+                ("a".into(), Value::Number(1.0)),
+                ("b".into(), Value::Number(2.0)),
+                ("c".into(), Value::Number(4.0)),
+            ]),
+        ),
+    ]);
+
+    assert_eq!(value, expected_value);
+}
+
+#[test]
+#[should_panic(expected = "Only objects can be spread into a JSON object")]
+fn test_macro_spread_non_object() {
+    let array = json!([1, 2, 3]);
+    json!({ ..array });
+}
+
+#[test]
+#[should_panic(expected = "Only objects can be spread into a JSON object")]
+fn test_macro_spread_string() {
+    json!({ "a": 1, .."not an object" });
+}
+
+#[test]
+fn test_macro_conditional_fields() {
+    let nickname: Option<&str> = None;
+    let age: Option<u8> = Some(21);
+
+    let value = json!({
+        "name": "William",
+        "nickname"?: nickname,
+        "age"?: age,
+        "email"?: Some(json!({ "verified": false }))
+    });
+
+    let expected_value = Value::Object(vec![
+        ("name".into(), Value::String("William".into())),
+        ("age".into(), Value::Number(21.0)),
+        (
+            "email".into(),
+            Value::Object(vec![("verified".into(), Value::Bool(false))]),
+        ),
+    ]);
+
+    assert_eq!(value, expected_value);
+
+    // A present optional field still replaces an earlier field
+    let value = json!({ "a": 1, "a"?: Some(2), "a"?: None::<u8> });
+    assert_eq!(value, Value::Object(vec![("a".into(), Value::Number(2.0))]));
+}
+
+#[test]
+fn test_macro_compile_errors() {
+    let tests = trybuild::TestCases::new();
+    tests.compile_fail("src/tests/ui/*.rs");
+}
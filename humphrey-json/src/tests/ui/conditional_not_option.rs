@@ -0,0 +1,7 @@
+use humphrey_json::json;
+
+fn main() {
+    let name = "William";
+
+    json!({ "name"?: name });
+}
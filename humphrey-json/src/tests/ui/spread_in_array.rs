@@ -0,0 +1,7 @@
+use humphrey_json::json;
+
+fn main() {
+    let values = json!([1, 2, 3]);
+
+    json!([0, ..values]);
+}
@@ -0,0 +1,7 @@
+use humphrey_json::json;
+
+struct Settings;
+
+fn main() {
+    json!({ ..Settings });
+}
@@ -1,19 +1,102 @@
 //! Provides the core configuration functionality.
 
+use crate::config::error::{ConfigError, ConfigErrors};
 use crate::config::extended_hashmap::ExtendedMap;
-use crate::config::tree::{parse_conf, ConfigNode};
-use crate::logger::LogLevel;
+use crate::config::tree::{parse_conf_recovering, ConfigKey, ConfigNode};
+use crate::logger::{LogFormat, LogLevel};
 use crate::proxy::{EqMutex, LoadBalancer};
 use crate::rand::Lcg;
 
+use humphrey::http::method::{Method, MethodOverride};
+use humphrey::http::url::host_matches;
+
 use std::collections::HashMap;
 use std::env::{args, var};
 use std::fs::File;
 use std::io::Read;
 use std::net::IpAddr;
 use std::path::Path;
+use std::str::FromStr;
 use std::time::Duration;
 
+/// The keys which may be specified in the `server` section, excluding hosts, routes and plugins.
+const SERVER_KEYS: &[&str] = &[
+    "server.address",
+    "server.port",
+    "server.threads",
+    "server.websocket",
+    "server.timeout",
+    "server.websocket_limits.global",
+    "server.websocket_limits.per_ip",
+    "server.blacklist.file",
+    "server.blacklist.mode",
+    "server.tls.cert_file",
+    "server.tls.key_file",
+    "server.tls.force",
+    "server.log.level",
+    "server.log.format",
+    "server.log.console",
+    "server.log.file",
+    "server.log.slow_request_threshold",
+    "server.log.body_limit",
+    "server.log.redact_headers",
+    "server.cache.size",
+    "server.cache.time",
+    "server.cache.max_file_size",
+    "server.cache.ceiling",
+    "server.normalize.merge_slashes",
+    "server.normalize.resolve_dots",
+    "server.normalize.lowercase",
+    "server.method_override.mode",
+    "server.method_override.field",
+    "server.mime_types.*",
+    "server.rewrite_path_prefix",
+    "server.upstream_host",
+    "server.rewrite_response_location",
+];
+
+/// The keys which configure how proxied requests and their responses are rewritten, which may be
+///   specified in a route section or, as defaults for its proxy routes, in a host or server section.
+const REWRITE_KEYS: &[&str] = &[
+    "rewrite_path_prefix",
+    "upstream_host",
+    "rewrite_response_location",
+];
+
+/// The keys which configure the retry policy of a proxy route.
+const RETRY_KEYS: &[&str] = &[
+    "retry.attempts",
+    "retry.delay",
+    "retry.methods",
+    "retry.unavailable_ttl",
+];
+
+/// The keys which configure how files are served by a file or directory route.
+const FILE_KEYS: &[&str] = &["nosniff", "untrusted_uploads"];
+
+/// The keys which may be specified in a route section.
+const ROUTE_KEYS: &[&str] = &[
+    "file",
+    "directory",
+    "proxy",
+    "load_balancer_mode",
+    "redirect",
+    "websocket",
+    "rewrite_path_prefix",
+    "upstream_host",
+    "rewrite_response_location",
+    "cache.enabled",
+    "cache.max_file_size",
+    "cache.priority",
+    "log_bodies",
+    "nosniff",
+    "untrusted_uploads",
+    "retry.attempts",
+    "retry.delay",
+    "retry.methods",
+    "retry.unavailable_ttl",
+];
+
 /// Represents the parsed and validated configuration.
 #[derive(Debug, PartialEq)]
 pub struct Config {
@@ -45,6 +128,17 @@ pub struct Config {
     pub blacklist: BlacklistConfig,
     /// The amount of time to wait between requests
     pub connection_timeout: Option<Duration>,
+    /// Limits on the number of concurrent WebSocket connections
+    pub websocket_limits: WebsocketLimitsConfig,
+    /// Request path normalisation configuration
+    pub normalization: NormalizationConfig,
+    /// Where `POST` requests can specify another method to be handled as, if enabled
+    pub method_override: Option<MethodOverride>,
+    /// Additional mappings from file extensions to MIME types, sorted by extension
+    pub mime_types: Vec<(String, String)>,
+    /// Every value in the `server` section outside of hosts, routes and plugins, by its full key such
+    ///   as `server.log.level`, as it was written apart from sizes, which are given in bytes
+    pub settings: HashMap<String, String>,
 }
 
 /// Represents the configuration for a specific host.
@@ -84,6 +178,64 @@ pub struct RouteConfig {
     pub load_balancer: Option<EqMutex<LoadBalancer>>,
     /// The WebSocket proxy target for WebSocket connections to this route
     pub websocket_proxy: Option<String>,
+    /// The caching rules for this route, merged with the global cache configuration
+    pub cache: RouteCacheConfig,
+    /// The rewrite rules for requests proxied by this route and their responses
+    pub rewrite: RewriteConfig,
+    /// The retry policy for requests proxied by this route
+    pub retry: RetryConfig,
+    /// Whether the bodies of requests to this route and their responses are logged at debug level
+    pub log_bodies: bool,
+    /// Whether files served by this route are sent with `X-Content-Type-Options: nosniff`
+    pub nosniff: bool,
+    /// Whether files served by this route may have been uploaded by untrusted users, in which case
+    ///   files which could run scripts or whose types are unknown are sent as downloads
+    pub untrusted_uploads: bool,
+}
+
+/// Represents the rules for rewriting proxied requests and their responses.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct RewriteConfig {
+    /// The path prefix to replace before proxying the request, and its replacement
+    pub path_prefix: Option<(String, String)>,
+    /// The `Host` header to send upstream instead of the client's
+    pub upstream_host: Option<String>,
+    /// Whether to rewrite `Location` headers which point to the upstream to point to the public origin
+    pub response_location: bool,
+}
+
+/// Represents the policy for retrying proxied requests when the upstream cannot be reached.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryConfig {
+    /// The maximum number of attempts, including the first, so `1` disables retries
+    pub attempts: usize,
+    /// How long to wait before each retry
+    pub delay: Duration,
+    /// The methods which may be retried after some of the request was sent
+    pub methods: Vec<Method>,
+    /// How long to consider a target unavailable after a connection to it fails, during which no
+    ///   connections to it are attempted, or `None` to always attempt to connect
+    pub unavailable_ttl: Option<Duration>,
+}
+
+/// Represents the caching rules for a specific route.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RouteCacheConfig {
+    /// Whether files served by this route are cached
+    pub enabled: bool,
+    /// The maximum size of a file to cache, in bytes, overriding the global setting
+    pub max_file_size: Option<usize>,
+    /// The eviction priority of files served by this route
+    pub priority: CachePriority,
+}
+
+/// Represents the eviction priority of a cached item.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CachePriority {
+    /// The item is evicted in the order it was cached
+    Normal,
+    /// The item is pinned, so it is only evicted to stay within the memory ceiling
+    High,
 }
 
 /// Represents configuration for the logger.
@@ -91,10 +243,26 @@ pub struct RouteConfig {
 pub struct LoggingConfig {
     /// The level of logging
     pub level: LogLevel,
+    /// The format in which messages are logged
+    pub format: LogFormat,
     /// Whether to log to the console
     pub console: bool,
     /// The path to the log file
     pub file: Option<String>,
+    /// How long a handler may take before the request is logged as slow, or `None` if slow
+    ///   requests are not logged
+    pub slow_request_threshold: Option<Duration>,
+    /// How bodies are logged for routes which opt in
+    pub bodies: BodyLogConfig,
+}
+
+/// Represents configuration for the logging of request and response bodies.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BodyLogConfig {
+    /// The maximum number of bytes of each body to log
+    pub limit: usize,
+    /// The headers whose values are replaced with `[redacted]` when logged
+    pub redact_headers: Vec<String>,
 }
 
 /// Represents configuration for the cache.
@@ -104,6 +272,10 @@ pub struct CacheConfig {
     pub size_limit: usize,
     /// The maximum time to cache an item for, in seconds
     pub time_limit: usize,
+    /// The maximum size of a file to cache, in bytes, unless overridden by the route
+    pub max_file_size: Option<usize>,
+    /// The hard memory ceiling of the cache, in bytes, which pinned items may use beyond the size limit
+    pub ceiling: usize,
 }
 
 /// Represents configuration for the blacklist.
@@ -115,6 +287,26 @@ pub struct BlacklistConfig {
     pub mode: BlacklistMode,
 }
 
+/// Represents limits on the number of WebSocket connections which can be open at once.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct WebsocketLimitsConfig {
+    /// The maximum number of WebSocket connections across all clients, if any
+    pub global: Option<usize>,
+    /// The maximum number of WebSocket connections from a single IP address, if any
+    pub per_ip: Option<usize>,
+}
+
+/// Represents configuration for the normalisation of request paths before routing.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct NormalizationConfig {
+    /// Whether to merge consecutive slashes into one
+    pub merge_slashes: bool,
+    /// Whether to resolve `.` and `..` segments, rejecting paths which escape the root
+    pub resolve_dots: bool,
+    /// Whether to convert the path to lowercase
+    pub lowercase: bool,
+}
+
 /// Represents configuration for TLS.
 #[cfg(feature = "tls")]
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -135,6 +327,8 @@ pub struct PluginConfig {
     pub name: String,
     /// The path to the shared library file.
     pub library: String,
+    /// The log level for messages from the plugin, overriding the server's log level.
+    pub log_level: Option<LogLevel>,
     /// The configuration for the plugin.
     pub config: HashMap<String, String>,
 }
@@ -184,8 +378,7 @@ impl Config {
         };
 
         if let Ok((filename, config_string)) = load_config_file(path) {
-            let tree = parse_conf(&config_string, &filename).map_err(|e| e.to_string())?;
-            let mut config = Self::from_tree(tree)?;
+            let mut config = Self::parse(&config_string, &filename).map_err(|e| e.to_string())?;
             config.source = source;
 
             Ok(config)
@@ -194,39 +387,110 @@ impl Config {
         }
     }
 
+    /// Parses and validates the configuration string, reporting every error found rather than
+    ///   stopping at the first.
+    pub fn parse(conf: &str, filename: &str) -> Result<Self, ConfigErrors> {
+        let mut errors = ConfigErrors::new();
+
+        // If the structure of the file could not be parsed, there is nothing to validate
+        let config = parse_conf_recovering(conf, filename, &mut errors)
+            .map(|tree| Self::validate(&tree, &mut errors));
+
+        match config {
+            Some(config) if errors.is_empty() => Ok(config),
+            _ => Err(errors),
+        }
+    }
+
     /// Parses the config from the config tree.
-    pub fn from_tree(tree: ConfigNode) -> Result<Self, &'static str> {
+    pub fn from_tree(tree: ConfigNode) -> Result<Self, ConfigErrors> {
+        let mut errors = ConfigErrors::new();
+        let config = Self::validate(&tree, &mut errors);
+
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Validates the config tree, recording every error encountered.
+    /// Invalid values are replaced with their defaults so that validation can continue.
+    fn validate(tree: &ConfigNode, errors: &mut ConfigErrors) -> Self {
         let mut hashmap: HashMap<String, ConfigNode> = HashMap::new();
         tree.flatten(&mut hashmap, &Vec::new());
 
+        let mut server = Validator {
+            map: &hashmap,
+            path: "",
+            location: tree.key(),
+            errors,
+        };
+
+        server.reject_unknown(SERVER_KEYS);
+
         // Get and validate the specified address, port and threads
         let address = hashmap.get_optional("server.address", "0.0.0.0".into());
-        let port: u16 = hashmap.get_optional_parsed("server.port", 80, "Invalid port")?;
-        let threads: usize =
-            hashmap.get_optional_parsed("server.threads", 32, "Invalid number of threads")?;
+        let port: u16 = server.parsed("server.port", 80, "Invalid port");
+        let threads: usize = server.parsed("server.threads", 32, "Invalid number of threads");
         let default_websocket_proxy = hashmap.get_owned("server.websocket");
-        let connection_timeout_seconds: u64 =
-            hashmap.get_optional_parsed("server.timeout", 0, "Invalid connection timeout")?;
-        let connection_timeout = if connection_timeout_seconds > 0 {
-            Some(Duration::from_secs(connection_timeout_seconds))
-        } else {
-            None
-        };
+        let connection_timeout = server
+            .duration(
+                "server.timeout",
+                Duration::from_secs(1),
+                "Invalid connection timeout",
+            )
+            .filter(|timeout| !timeout.is_zero());
 
         if threads < 1 {
-            return Err("You cannot specify less than 1 thread");
+            server.report("server.threads", "You cannot specify less than 1 thread");
         }
 
+        // Get and validate the WebSocket connection limits
+        let websocket_limits = {
+            let global = server.optional_parsed(
+                "server.websocket_limits.global",
+                "Invalid global WebSocket connection limit",
+            );
+            let per_ip = server.optional_parsed(
+                "server.websocket_limits.per_ip",
+                "Invalid per-IP WebSocket connection limit",
+            );
+
+            if global == Some(0) {
+                server.report(
+                    "server.websocket_limits.global",
+                    "The global WebSocket connection limit must be at least 1",
+                );
+            }
+
+            if per_ip == Some(0) {
+                server.report(
+                    "server.websocket_limits.per_ip",
+                    "The per-IP WebSocket connection limit must be at least 1",
+                );
+            }
+
+            WebsocketLimitsConfig { global, per_ip }
+        };
+
         // Get and validate the blacklist file and mode
         let blacklist = {
-            let blacklist_strings: Vec<String> =
-                load_list_file(hashmap.get_owned("server.blacklist.file"))?;
+            let blacklist_strings: Vec<String> = server
+                .check(
+                    "server.blacklist.file",
+                    load_list_file(hashmap.get_owned("server.blacklist.file")),
+                )
+                .unwrap_or_default();
             let mut blacklist: Vec<IpAddr> = Vec::with_capacity(blacklist_strings.len());
 
             for ip in blacklist_strings {
-                blacklist.push(
-                    ip.parse::<IpAddr>()
-                        .map_err(|_| "Could not parse IP address in blacklist file")?,
+                blacklist.extend(
+                    server.check(
+                        "server.blacklist.file",
+                        ip.parse::<IpAddr>()
+                            .map_err(|_| "Could not parse IP address in blacklist file"),
+                    ),
                 );
             }
 
@@ -234,7 +498,10 @@ impl Config {
             let blacklist_mode = match blacklist_mode.as_ref() {
                 "block" => BlacklistMode::Block,
                 "forbidden" => BlacklistMode::Forbidden,
-                _ => return Err("Invalid blacklist mode"),
+                _ => {
+                    server.report("server.blacklist.mode", "Invalid blacklist mode");
+                    BlacklistMode::Block
+                }
             };
 
             BlacklistConfig {
@@ -247,74 +514,198 @@ impl Config {
         let tls_config = {
             let cert_file = hashmap.get_owned("server.tls.cert_file");
             let key_file = hashmap.get_owned("server.tls.key_file");
-            let force = hashmap.get_optional("server.tls.force", "false".into());
-
-            if force == "true" && threads < 2 {
-                return Err("A minimum of two threads are required to force HTTPS");
-            }
-
-            if force == "true" && port != 443 {
-                return Err("Forcing HTTPS redirects requires the port to be 443");
+            let force = server.boolean(
+                "server.tls.force",
+                false,
+                "server.tls.force must be a boolean",
+            );
+
+            if force && port != 443 {
+                server.report(
+                    "server.tls.force",
+                    "Forcing HTTPS redirects requires the port to be 443",
+                );
             }
 
-            if let Some(cert_file) = cert_file {
-                if let Some(key_file) = key_file {
-                    Some(TlsConfig {
-                        cert_file,
-                        key_file,
-                        force: force == "true",
-                    })
-                } else {
-                    return Err("Missing key file for TLS");
+            match (cert_file, key_file) {
+                (Some(cert_file), Some(key_file)) => Some(TlsConfig {
+                    cert_file,
+                    key_file,
+                    force,
+                }),
+                (Some(_), None) => {
+                    server.report("server.tls.cert_file", "Missing key file for TLS");
+                    None
                 }
-            } else {
-                None
+                (None, _) => None,
             }
         };
 
         // Get and validate the logging configuration
         let logging = {
-            let log_level = hashmap.get_optional_parsed(
-                "server.log.level",
-                LogLevel::Warn,
-                "Invalid log level",
-            )?;
+            let log_level = server.parsed("server.log.level", LogLevel::Warn, "Invalid log level");
             let log_file = hashmap.get_owned("server.log.file");
-            let log_console = hashmap.get_optional_parsed(
+            let log_format =
+                server.parsed("server.log.format", LogFormat::Text, "Invalid log format");
+            let log_console = server.boolean(
                 "server.log.console",
                 true,
                 "server.log.console must be a boolean",
-            )?;
+            );
+            let slow_request_threshold = server
+                .duration(
+                    "server.log.slow_request_threshold",
+                    Duration::from_millis(1),
+                    "Invalid slow request threshold",
+                )
+                .filter(|threshold| !threshold.is_zero());
+
+            let defaults = BodyLogConfig::default();
+            let bodies = BodyLogConfig {
+                limit: server
+                    .size("server.log.body_limit", "Invalid body logging limit")
+                    .unwrap_or(defaults.limit),
+                redact_headers: hashmap
+                    .get_owned("server.log.redact_headers")
+                    .map(|headers| {
+                        headers
+                            .split(',')
+                            .map(|header| header.trim().to_string())
+                            .filter(|header| !header.is_empty())
+                            .collect()
+                    })
+                    .unwrap_or(defaults.redact_headers),
+            };
 
             LoggingConfig {
                 level: log_level,
+                format: log_format,
                 console: log_console,
                 file: log_file,
+                slow_request_threshold,
+                bodies,
             }
         };
 
         // Get and validate the cache configuration
         let cache = {
-            let cache_size =
-                hashmap.get_optional_parsed("server.cache.size", 0_usize, "Invalid cache size")?;
-            let cache_time =
-                hashmap.get_optional_parsed("server.cache.time", 0_usize, "Invalid cache time")?;
+            let cache_size = server
+                .size("server.cache.size", "Invalid cache size")
+                .unwrap_or(0);
+            let cache_time = server
+                .duration(
+                    "server.cache.time",
+                    Duration::from_secs(1),
+                    "Invalid cache time",
+                )
+                .map_or(0, |time| time.as_secs() as usize);
+            let max_file_size = server.size(
+                "server.cache.max_file_size",
+                "Invalid maximum cached file size",
+            );
+            let ceiling = server
+                .size("server.cache.ceiling", "Invalid cache memory ceiling")
+                .unwrap_or(cache_size);
+
+            if ceiling < cache_size {
+                server.report(
+                    "server.cache.ceiling",
+                    "The cache memory ceiling cannot be smaller than the cache size",
+                );
+            }
 
             CacheConfig {
                 size_limit: cache_size,
                 time_limit: cache_time,
+                max_file_size,
+                ceiling,
             }
         };
 
+        // Get and validate the request normalisation configuration
+        let normalization = NormalizationConfig {
+            merge_slashes: server.boolean(
+                "server.normalize.merge_slashes",
+                true,
+                "server.normalize.merge_slashes must be a boolean",
+            ),
+            resolve_dots: server.boolean(
+                "server.normalize.resolve_dots",
+                true,
+                "server.normalize.resolve_dots must be a boolean",
+            ),
+            lowercase: server.boolean(
+                "server.normalize.lowercase",
+                false,
+                "server.normalize.lowercase must be a boolean",
+            ),
+        };
+
+        // Get and validate the method override configuration
+        let method_override = {
+            let mode = hashmap.get_optional("server.method_override.mode", "off".into());
+            let field = hashmap.get_owned("server.method_override.field");
+
+            if field.is_some() && mode != "form" {
+                server.report(
+                    "server.method_override.field",
+                    "A method override field can only be used with the form mode",
+                );
+            }
+
+            match mode.as_ref() {
+                "off" => None,
+                "header" => Some(MethodOverride::Header),
+                "form" => Some(MethodOverride::FormField(
+                    field.unwrap_or_else(|| "_method".into()),
+                )),
+                _ => {
+                    server.report(
+                        "server.method_override.mode",
+                        "Invalid method override mode",
+                    );
+                    None
+                }
+            }
+        };
+
+        // Get and validate the additional MIME types
+        let mime_types = {
+            let mut mime_types: Vec<(String, String)> = hashmap
+                .iter()
+                .filter_map(|(key, node)| {
+                    let extension = key.strip_prefix("server.mime_types.")?;
+                    Some((key, extension.to_string(), node.get_string()))
+                })
+                .filter_map(|(key, extension, mime_type)| match mime_type {
+                    Some(mime_type) if mime_type.contains('/') => Some((extension, mime_type)),
+                    _ => {
+                        server.report(key, "Invalid MIME type");
+                        None
+                    }
+                })
+                .collect();
+
+            mime_types.sort();
+            mime_types
+        };
+
+        // Keep every value as it is in the tree, for plugins which need settings the server does not use
+        let settings: HashMap<String, String> = hashmap
+            .iter()
+            .filter_map(|(key, node)| Some((key.clone(), node.get_string()?)))
+            .collect();
+
         // Get and validate the configuration for the different routes
-        let default_host = parse_host("*", &tree)?;
+        let default_host = parse_host("*", tree, "server", errors);
 
         let hosts = {
             let hosts_map = tree.get_hosts();
             let mut hosts: Vec<HostConfig> = Vec::with_capacity(hosts_map.len());
 
             for (host, conf) in hosts_map {
-                hosts.push(parse_host(&host, &conf)?);
+                let path = format!("server > host {}", host);
+                hosts.push(parse_host(&host, &conf, &path, errors));
             }
 
             hosts
@@ -327,25 +718,40 @@ impl Config {
             let mut plugins: Vec<PluginConfig> = Vec::new();
 
             for (name, conf) in plugins_map {
-                let library = conf.get_compulsory("library", "Plugin library not specified")?;
+                let path = format!("server > plugins > {}", name.name);
+                let mut plugin = Validator {
+                    map: &conf,
+                    path: &path,
+                    location: &name,
+                    errors,
+                };
+
+                let library = plugin.check(
+                    "library",
+                    conf.get_compulsory("library", "Plugin library not specified"),
+                );
+                let log_level = plugin.optional_parsed("log_level", "Invalid log level");
                 let mut additional_config: HashMap<String, String> = conf
-                    .clone()
                     .iter()
                     .map(|(k, v)| (k.clone(), v.get_string().unwrap()))
                     .collect();
-                additional_config.remove("library").unwrap();
-
-                plugins.push(PluginConfig {
-                    name,
-                    library,
-                    config: additional_config,
-                })
+                additional_config.remove("library");
+                additional_config.remove("log_level");
+
+                if let Some(library) = library {
+                    plugins.push(PluginConfig {
+                        name: name.name,
+                        library,
+                        log_level,
+                        config: additional_config,
+                    })
+                }
             }
 
             plugins
         };
 
-        Ok(Config {
+        Config {
             source: ConfigSource::Default,
             address,
             port,
@@ -361,7 +767,12 @@ impl Config {
             cache,
             blacklist,
             connection_timeout,
-        })
+            websocket_limits,
+            normalization,
+            method_override,
+            mime_types,
+            settings,
+        }
     }
 
     /// Get the route at the given host and route indices.
@@ -372,6 +783,39 @@ impl Config {
             &self.hosts[host - 1].routes[route]
         }
     }
+
+    /// Get the host which serves requests with the given `Host` header.
+    ///
+    /// Hosts are matched in the same way as the server matches them, so the first host whose wildcard
+    ///   matches the header is returned, or the default host if none match.
+    pub fn get_host(&self, host: Option<&str>) -> &HostConfig {
+        host.and_then(|host| {
+            self.hosts
+                .iter()
+                .find(|config| host_matches(&config.matches, host))
+        })
+        .unwrap_or(&self.default_host)
+    }
+
+    /// Get the settings of the plugin with the given name, merged with the server's settings, or
+    ///   `None` if there is no such plugin.
+    ///
+    /// Settings from the `server` section are given without their `server.` prefix, and are
+    ///   overridden by the same keys in the plugin's block. For example, a plugin which reads
+    ///   `timeout` gets the value from its own block if it has one, and the server's otherwise.
+    #[cfg(feature = "plugins")]
+    pub fn plugin_settings(&self, name: &str) -> Option<HashMap<String, String>> {
+        let plugin = self.plugins.iter().find(|plugin| plugin.name == name)?;
+
+        let mut settings: HashMap<String, String> = self
+            .settings
+            .iter()
+            .filter_map(|(key, value)| Some((key.strip_prefix("server.")?.into(), value.clone())))
+            .collect();
+        settings.extend(plugin.config.clone());
+
+        Some(settings)
+    }
 }
 
 /// Loads the configuration file.
@@ -411,28 +855,97 @@ fn load_list_file(path: Option<String>) -> Result<Vec<String>, &'static str> {
     }
 }
 
-/// Parses a node which contains the configuration for a host.
-fn parse_host(wild: &str, node: &ConfigNode) -> Result<HostConfig, &'static str> {
+/// Parses a node which contains the configuration for a host, where `path` describes the host in
+///   errors.
+fn parse_host(wild: &str, node: &ConfigNode, path: &str, errors: &mut ConfigErrors) -> HostConfig {
+    // Rewrite rules specified directly in the host apply to all of its proxy routes
+    let mut values: HashMap<String, ConfigNode> = HashMap::new();
+    if let ConfigNode::Section(_, children) | ConfigNode::Host(_, children) = node {
+        for child in children {
+            child.flatten(&mut values, &[]);
+        }
+    }
+
+    let rewrite = parse_rewrite(
+        &mut Validator {
+            map: &values,
+            path,
+            location: node.key(),
+            errors,
+        },
+        &RewriteConfig::default(),
+    );
+
     let routes_map = node.get_routes();
     let mut routes: Vec<RouteConfig> = Vec::with_capacity(routes_map.len());
 
-    for (wild, conf) in routes_map {
-        routes.extend(parse_route(&wild, conf)?);
+    for (route, conf) in routes_map {
+        let path = format!("{} > route {}", path, route.name);
+        let mut validator = Validator {
+            map: &conf,
+            path: &path,
+            location: &route,
+            errors,
+        };
+
+        routes.extend(parse_route(&route.name, &mut validator, &rewrite));
     }
 
-    Ok(HostConfig {
+    HostConfig {
         matches: wild.to_string(),
         routes,
-    })
+    }
 }
 
 /// Parses a route.
 fn parse_route(
     wild: &str,
-    conf: HashMap<String, ConfigNode>,
-) -> Result<Vec<RouteConfig>, &'static str> {
+    validator: &mut Validator,
+    host_rewrite: &RewriteConfig,
+) -> Vec<RouteConfig> {
+    let conf = validator.map;
     let mut routes: Vec<RouteConfig> = Vec::new();
 
+    validator.reject_unknown(ROUTE_KEYS);
+
+    let cache = parse_route_cache(validator);
+    let log_bodies = validator.boolean("log_bodies", false, "log_bodies must be a boolean");
+    let nosniff = validator.boolean("nosniff", true, "nosniff must be a boolean");
+    let untrusted_uploads = validator.boolean(
+        "untrusted_uploads",
+        false,
+        "untrusted_uploads must be a boolean",
+    );
+
+    if !conf.contains_key("file") && !conf.contains_key("directory") {
+        for key in FILE_KEYS.iter().filter(|key| conf.contains_key(**key)) {
+            validator.report(
+                key,
+                "File options can only be used on file and directory routes",
+            );
+        }
+    }
+
+    let rewrite = if conf.contains_key("proxy") {
+        parse_rewrite(validator, host_rewrite)
+    } else {
+        for key in REWRITE_KEYS.iter().filter(|key| conf.contains_key(**key)) {
+            validator.report(key, "Rewrite rules can only be used on proxy routes");
+        }
+
+        RewriteConfig::default()
+    };
+
+    let retry = if conf.contains_key("proxy") {
+        parse_retry(validator)
+    } else {
+        for key in RETRY_KEYS.iter().filter(|key| conf.contains_key(**key)) {
+            validator.report(key, "Retry rules can only be used on proxy routes");
+        }
+
+        RetryConfig::default()
+    };
+
     for wild in wild.split(',').map(|s| s.trim()) {
         let websocket_proxy = conf.get_owned("websocket");
 
@@ -447,6 +960,12 @@ fn parse_route(
                 path: Some(file),
                 load_balancer: None,
                 websocket_proxy,
+                cache,
+                rewrite: RewriteConfig::default(),
+                retry: RetryConfig::default(),
+                log_bodies,
+                nosniff,
+                untrusted_uploads,
             });
         } else if conf.contains_key("directory") {
             // This is a regular directory-serving route
@@ -459,6 +978,12 @@ fn parse_route(
                 path: Some(directory),
                 load_balancer: None,
                 websocket_proxy,
+                cache,
+                rewrite: RewriteConfig::default(),
+                retry: RetryConfig::default(),
+                log_bodies,
+                nosniff,
+                untrusted_uploads,
             });
         } else if conf.contains_key("proxy") {
             // This is a proxy route
@@ -471,20 +996,24 @@ fn parse_route(
                 .collect();
 
             let load_balancer_mode = conf.get_optional("load_balancer_mode", "round-robin".into());
-            let load_balancer_mode =
-                match load_balancer_mode.as_str() {
-                    "round-robin" => LoadBalancerMode::RoundRobin,
-                    "random" => LoadBalancerMode::Random,
-                    _ => return Err(
+            let load_balancer_mode = match load_balancer_mode.as_str() {
+                "round-robin" => LoadBalancerMode::RoundRobin,
+                "random" => LoadBalancerMode::Random,
+                _ => {
+                    validator.report(
+                        "load_balancer_mode",
                         "Invalid load balancer mode, valid options are `round-robin` or `random`",
-                    ),
-                };
+                    );
+                    LoadBalancerMode::RoundRobin
+                }
+            };
 
             let load_balancer = EqMutex::new(LoadBalancer {
                 targets,
                 mode: load_balancer_mode,
                 lcg: Lcg::new(),
                 index: 0,
+                unavailable: HashMap::new(),
             });
 
             routes.push(RouteConfig {
@@ -493,6 +1022,12 @@ fn parse_route(
                 path: None,
                 load_balancer: Some(load_balancer),
                 websocket_proxy,
+                cache,
+                rewrite: rewrite.clone(),
+                retry: retry.clone(),
+                log_bodies,
+                nosniff,
+                untrusted_uploads,
             });
         } else if conf.contains_key("redirect") {
             // This is a redirect route
@@ -505,9 +1040,17 @@ fn parse_route(
                 path: Some(target),
                 load_balancer: None,
                 websocket_proxy,
+                cache,
+                rewrite: RewriteConfig::default(),
+                retry: RetryConfig::default(),
+                log_bodies,
+                nosniff,
+                untrusted_uploads,
             });
         } else if !conf.contains_key("websocket") {
-            return Err("Invalid route configuration, every route must contain either the `file`, `directory`, `proxy` or `redirect` field, unless it defines a WebSocket proxy with the `websocket` field");
+            // The error is reported once for the route, even if it matches several paths
+            validator.report("", "Invalid route configuration, every route must contain either the `file`, `directory`, `proxy` or `redirect` field, unless it defines a WebSocket proxy with the `websocket` field");
+            break;
         } else {
             routes.push(RouteConfig {
                 route_type: RouteType::ExclusiveWebSocket,
@@ -515,9 +1058,241 @@ fn parse_route(
                 path: None,
                 load_balancer: None,
                 websocket_proxy,
+                cache,
+                rewrite: RewriteConfig::default(),
+                retry: RetryConfig::default(),
+                log_bodies,
+                nosniff,
+                untrusted_uploads,
             });
         }
     }
 
-    Ok(routes)
+    routes
+}
+
+/// Parses the rewrite rules of a route or host, falling back to the given defaults for any rules which
+///   are not specified.
+fn parse_rewrite(validator: &mut Validator, defaults: &RewriteConfig) -> RewriteConfig {
+    let conf = validator.map;
+
+    let path_prefix = match conf.get_owned("rewrite_path_prefix") {
+        Some(value) => validator.check("rewrite_path_prefix", parse_path_prefix_rewrite(&value)),
+        None => defaults.path_prefix.clone(),
+    };
+
+    RewriteConfig {
+        path_prefix,
+        upstream_host: conf
+            .get_owned("upstream_host")
+            .or_else(|| defaults.upstream_host.clone()),
+        response_location: validator.boolean(
+            "rewrite_response_location",
+            defaults.response_location,
+            "rewrite_response_location must be a boolean",
+        ),
+    }
+}
+
+/// Parses the value of a `rewrite_path_prefix` directive, which is two quoted paths such as
+///   `"/api" ""`. The value has already had its outer quotation marks removed by the parser.
+fn parse_path_prefix_rewrite(value: &str) -> Result<(String, String), &'static str> {
+    const ERROR: &str = "Invalid path prefix rewrite, it takes the prefix to replace and its replacement, both in quotation marks";
+
+    let (prefix, replacement) = value.split_once('"').ok_or(ERROR)?;
+    let replacement = replacement.trim_start().strip_prefix('"').ok_or(ERROR)?;
+
+    if !prefix.starts_with('/') || replacement.contains('"') {
+        return Err(ERROR);
+    }
+
+    Ok((prefix.to_string(), replacement.to_string()))
+}
+
+/// Parses the retry policy of a proxy route from its `retry` section.
+fn parse_retry(validator: &mut Validator) -> RetryConfig {
+    let defaults = RetryConfig::default();
+
+    let attempts = validator.parsed(
+        "retry.attempts",
+        defaults.attempts,
+        "Invalid number of retry attempts",
+    );
+    let delay = validator.duration(
+        "retry.delay",
+        Duration::from_millis(1),
+        "Invalid retry delay",
+    );
+    let unavailable_ttl = validator.duration(
+        "retry.unavailable_ttl",
+        Duration::from_millis(1),
+        "Invalid unavailable target TTL",
+    );
+
+    if attempts < 1 {
+        validator.report("retry.attempts", "You cannot specify less than 1 attempt");
+    }
+
+    let methods = match validator.map.get_owned("retry.methods") {
+        Some(methods) => validator
+            .check(
+                "retry.methods",
+                methods
+                    .split(',')
+                    .map(|method| Method::from_name(method.trim()).map_err(|_| "Invalid method"))
+                    .collect(),
+            )
+            .unwrap_or(defaults.methods),
+        None => defaults.methods,
+    };
+
+    RetryConfig {
+        attempts: attempts.max(1),
+        delay: delay.unwrap_or(defaults.delay),
+        methods,
+        unavailable_ttl: unavailable_ttl.filter(|ttl| !ttl.is_zero()),
+    }
+}
+
+/// Parses the caching rules of a route from its `cache` section.
+fn parse_route_cache(validator: &mut Validator) -> RouteCacheConfig {
+    let enabled = validator.boolean("cache.enabled", true, "cache.enabled must be a boolean");
+    let max_file_size = validator.size("cache.max_file_size", "Invalid maximum cached file size");
+    let priority = match validator
+        .map
+        .get_optional("cache.priority", "normal".into())
+        .as_str()
+    {
+        "normal" => CachePriority::Normal,
+        "high" => CachePriority::High,
+        _ => {
+            validator.report(
+                "cache.priority",
+                "Invalid cache priority, valid options are `normal` or `high`",
+            );
+            CachePriority::Normal
+        }
+    };
+
+    RouteCacheConfig {
+        enabled,
+        max_file_size,
+        priority,
+    }
+}
+
+/// Validates a flattened block of the configuration, recording errors against the nodes which
+///   caused them so that validation can continue.
+struct Validator<'a> {
+    /// The flattened values of the block.
+    map: &'a HashMap<String, ConfigNode>,
+    /// The path of the block, used to describe where errors occurred.
+    path: &'a str,
+    /// Where the block was defined, used for errors which are not caused by a value.
+    location: &'a ConfigKey,
+    /// The errors encountered so far.
+    errors: &'a mut ConfigErrors,
+}
+
+impl Validator<'_> {
+    /// Records an error against the value with the given key, or against the block if there is no
+    ///   such value.
+    fn report(&mut self, key: &str, message: &'static str) {
+        let location = self.map.get(key).map_or(self.location, |node| node.key());
+
+        // The block of a value is the block being validated followed by the sections in its key
+        let mut block = self.path.to_string();
+        if let Some((sections, _)) = key.rsplit_once('.') {
+            for section in sections.split('.') {
+                if !block.is_empty() {
+                    block.push_str(" > ");
+                }
+
+                block.push_str(section);
+            }
+        }
+
+        self.errors
+            .push(ConfigError::new(message, &location.file, location.line).with_block(block));
+    }
+
+    /// Records the error of the result against the value with the given key, if there is one.
+    fn check<T>(&mut self, key: &str, result: Result<T, &'static str>) -> Option<T> {
+        result.map_err(|error| self.report(key, error)).ok()
+    }
+
+    /// Gets and parses the value with the given key, or returns the given default if the value is
+    ///   not specified or is invalid, recording the given error in the latter case.
+    fn parsed<T>(&mut self, key: &'static str, default: T, error: &'static str) -> T
+    where
+        T: FromStr,
+    {
+        self.optional_parsed(key, error).unwrap_or(default)
+    }
+
+    /// Gets and parses the value with the given key, if it is specified, recording the given error if
+    ///   it is invalid.
+    fn optional_parsed<T>(&mut self, key: &'static str, error: &'static str) -> Option<T>
+    where
+        T: FromStr,
+    {
+        let map = self.map;
+
+        if map.contains_key(key) {
+            self.check(key, map.get_compulsory_parsed(key, error))
+        } else {
+            None
+        }
+    }
+
+    /// Gets the duration with the given key, interpreting a number without a unit in the given unit,
+    ///   if it is specified, recording the given error if it is invalid.
+    fn duration(
+        &mut self,
+        key: &'static str,
+        unit: Duration,
+        error: &'static str,
+    ) -> Option<Duration> {
+        let map = self.map;
+        self.check(key, map.get_duration(key, unit, error))
+            .flatten()
+    }
+
+    /// Gets the size in bytes with the given key, if it is specified, recording the given error if
+    ///   it is invalid.
+    fn size(&mut self, key: &'static str, error: &'static str) -> Option<usize> {
+        let map = self.map;
+        self.check(key, map.get_size(key, error)).flatten()
+    }
+
+    /// Gets the boolean with the given key, or returns the given default if the value is not
+    ///   specified or is invalid, recording the given error in the latter case.
+    fn boolean(&mut self, key: &'static str, default: bool, error: &'static str) -> bool {
+        let map = self.map;
+        self.check(key, map.get_bool(key, error))
+            .flatten()
+            .unwrap_or(default)
+    }
+
+    /// Records an error against every value in the block whose key is not one of the given keys.
+    /// Keys ending in `*` match any key with the same prefix.
+    fn reject_unknown(&mut self, known: &[&str]) {
+        let map = self.map;
+        let mut unknown: Vec<(&String, &ConfigNode)> = map
+            .iter()
+            .filter(|(key, _)| {
+                !known.iter().any(|known| match known.strip_suffix('*') {
+                    Some(prefix) => key.starts_with(prefix),
+                    None => key == known,
+                })
+            })
+            .collect();
+
+        // The map is unordered, so sort the errors to report them in the order they appear
+        unknown.sort_by_key(|(_, node)| (&node.key().file, node.key().line));
+
+        for (key, _) in unknown {
+            self.report(key, "Unknown key");
+        }
+    }
 }
@@ -1,10 +1,15 @@
 //! Provides default values for the configuration.
 
 use crate::config::{
-    BlacklistConfig, BlacklistMode, Config, ConfigSource, HostConfig, LoggingConfig, RouteConfig,
-    RouteType,
+    BlacklistConfig, BlacklistMode, BodyLogConfig, CachePriority, Config, ConfigSource, HostConfig,
+    LoggingConfig, NormalizationConfig, RetryConfig, RouteCacheConfig, RouteConfig, RouteType,
 };
-use crate::server::logger::LogLevel;
+use crate::server::logger::{LogFormat, LogLevel};
+
+use humphrey::http::method::Method;
+
+use std::collections::HashMap;
+use std::time::Duration;
 
 impl Default for Config {
     fn default() -> Self {
@@ -24,6 +29,11 @@ impl Default for Config {
             cache: Default::default(),
             blacklist: Default::default(),
             connection_timeout: Default::default(),
+            websocket_limits: Default::default(),
+            normalization: Default::default(),
+            method_override: None,
+            mime_types: Vec::new(),
+            settings: HashMap::new(),
         }
     }
 }
@@ -45,6 +55,33 @@ impl Default for RouteConfig {
             path: Some('.'.into()),
             load_balancer: None,
             websocket_proxy: None,
+            cache: Default::default(),
+            rewrite: Default::default(),
+            retry: Default::default(),
+            log_bodies: false,
+            nosniff: true,
+            untrusted_uploads: false,
+        }
+    }
+}
+
+impl Default for RouteCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_file_size: None,
+            priority: CachePriority::Normal,
+        }
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            attempts: 1,
+            delay: Duration::from_millis(100),
+            methods: vec![Method::Get, Method::Head, Method::Options],
+            unavailable_ttl: None,
         }
     }
 }
@@ -53,8 +90,25 @@ impl Default for LoggingConfig {
     fn default() -> Self {
         Self {
             level: LogLevel::Info,
+            format: LogFormat::Text,
             console: true,
             file: None,
+            slow_request_threshold: None,
+            bodies: Default::default(),
+        }
+    }
+}
+
+impl Default for BodyLogConfig {
+    fn default() -> Self {
+        Self {
+            limit: 1024,
+            redact_headers: vec![
+                "Authorization".into(),
+                "Proxy-Authorization".into(),
+                "Cookie".into(),
+                "Set-Cookie".into(),
+            ],
         }
     }
 }
@@ -67,3 +121,13 @@ impl Default for BlacklistConfig {
         }
     }
 }
+
+impl Default for NormalizationConfig {
+    fn default() -> Self {
+        Self {
+            merge_slashes: true,
+            resolve_dots: true,
+            lowercase: false,
+        }
+    }
+}
@@ -9,6 +9,17 @@ pub struct ConfigError {
     message: &'static str,
     file: String,
     line: u64,
+    block: String,
+}
+
+/// Represents every error encountered while parsing and validating a configuration.
+///
+/// Parsing continues past errors which only affect a single value, so that all of them can be
+///   reported at once. When displayed, the errors are grouped by the file and block in which they
+///   occurred.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ConfigErrors {
+    errors: Vec<ConfigError>,
 }
 
 impl ConfigError {
@@ -18,10 +29,58 @@ impl ConfigError {
             message,
             file: file.to_string(),
             line,
+            block: String::new(),
+        }
+    }
+
+    /// Sets the path of the block in which the error occurred, for example `server > cache`.
+    pub fn with_block(mut self, block: impl Into<String>) -> Self {
+        self.block = block.into();
+        self
+    }
+}
+
+impl ConfigErrors {
+    /// Creates a new, empty collection of configuration errors.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an error to the collection.
+    pub fn push(&mut self, error: ConfigError) {
+        self.errors.push(error);
+    }
+
+    /// Returns the errors in the order in which they were encountered.
+    pub fn errors(&self) -> &[ConfigError] {
+        &self.errors
+    }
+
+    /// Returns the number of errors.
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    /// Returns `true` if no errors have been encountered.
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+impl From<ConfigError> for ConfigErrors {
+    fn from(error: ConfigError) -> Self {
+        Self {
+            errors: vec![error],
         }
     }
 }
 
+impl From<Vec<ConfigError>> for ConfigErrors {
+    fn from(errors: Vec<ConfigError>) -> Self {
+        Self { errors }
+    }
+}
+
 impl Display for ConfigError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -32,4 +91,46 @@ impl Display for ConfigError {
     }
 }
 
+impl Display for ConfigErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Group the errors by file and block, in the order in which each group was first encountered
+        let mut groups: Vec<(&str, &str, Vec<&ConfigError>)> = Vec::new();
+
+        for error in &self.errors {
+            match groups
+                .iter_mut()
+                .find(|(file, block, _)| *file == error.file && *block == error.block)
+            {
+                Some((_, _, errors)) => errors.push(error),
+                None => groups.push((&error.file, &error.block, vec![error])),
+            }
+        }
+
+        write!(
+            f,
+            "Found {} error{} in the configuration:",
+            self.errors.len(),
+            if self.errors.len() == 1 { "" } else { "s" }
+        )?;
+
+        for (file, block, mut errors) in groups {
+            if block.is_empty() {
+                write!(f, "\n  {}:", file)?;
+            } else {
+                write!(f, "\n  {}, in `{}`:", file, block)?;
+            }
+
+            errors.sort_by_key(|error| error.line);
+
+            for error in errors {
+                write!(f, "\n    line {}: {}", error.line, error.message)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
 impl Error for ConfigError {}
+
+impl Error for ConfigErrors {}
@@ -1,9 +1,12 @@
-//! Provides an extension of the `HashMap` type called `ExtendedMap`.
+//! Provides an extension of the `HashMap` type called `ExtendedMap`, along with the parsers for
+//!   values with units, which are shared by the server's own configuration and plugins so that
+//!   units are interpreted identically everywhere.
 
 use crate::config::tree::ConfigNode;
 
 use std::collections::HashMap;
 use std::str::FromStr;
+use std::time::Duration;
 
 /// An extension of the standard HashMap that provides some syntactic sugar for working with
 ///   optional and compulsory items, as well as errors.
@@ -31,6 +34,87 @@ pub trait ExtendedMap<K, V> {
     fn get_compulsory_parsed<T>(&self, key: K, error: &'static str) -> Result<T, &'static str>
     where
         T: FromStr;
+
+    /// Gets a duration such as `30s` from the map, interpreting a number without a unit in the given
+    ///   unit, or returns the given error if it is invalid. See `parse_duration`.
+    fn get_duration(
+        &self,
+        key: K,
+        unit: Duration,
+        error: &'static str,
+    ) -> Result<Option<Duration>, &'static str>;
+
+    /// Gets a size in bytes such as `10M` from the map, or returns the given error if it is invalid.
+    ///   See `parse_size`.
+    fn get_size(&self, key: K, error: &'static str) -> Result<Option<usize>, &'static str>;
+
+    /// Gets a boolean such as `yes` from the map, or returns the given error if it is invalid. See
+    ///   `parse_bool`.
+    fn get_bool(&self, key: K, error: &'static str) -> Result<Option<bool>, &'static str>;
+}
+
+/// Parses a duration, which is a whole number followed by `ms`, `s`, `m`, `h` or `d`, such as `30s`.
+///
+/// A number without a unit is interpreted in the given unit, so that durations written as plain
+///   numbers before units were supported keep their meaning.
+pub fn parse_duration(value: &str, unit: Duration) -> Option<Duration> {
+    let (number, suffix) = split_number(value)?;
+
+    let unit = match suffix {
+        "" => unit,
+        "ms" => Duration::from_millis(1),
+        "s" => Duration::from_secs(1),
+        "m" => Duration::from_secs(60),
+        "h" => Duration::from_secs(60 * 60),
+        "d" => Duration::from_secs(24 * 60 * 60),
+        _ => return None,
+    };
+
+    unit.checked_mul(u32::try_from(number).ok()?)
+}
+
+/// Parses a size in bytes, which is a whole number optionally followed by `B`, `K`, `M` or `G`, or
+///   `KB`, `MB` or `GB`, in any case. For example, `4K` is 4096 bytes and `1M` is 1048576 bytes.
+pub fn parse_size(value: &str) -> Option<usize> {
+    let (number, suffix) = split_number(value)?;
+
+    let multiplier: usize = match suffix.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        _ => return None,
+    };
+
+    usize::try_from(number).ok()?.checked_mul(multiplier)
+}
+
+/// Parses a boolean, which is `true` or `yes`, or `false` or `no`.
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" | "yes" => Some(true),
+        "false" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// Splits a value into the whole number at its start and the unit which follows it.
+fn split_number(value: &str) -> Option<(u64, &str)> {
+    let end = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, suffix) = value.split_at(end);
+
+    Some((number.parse().ok()?, suffix))
+}
+
+/// Parses the value, if there is one, with the given parser, returning the error if it is invalid.
+fn parse_typed<T>(
+    value: Option<&str>,
+    parse: impl FnOnce(&str) -> Option<T>,
+    error: &'static str,
+) -> Result<Option<T>, &'static str> {
+    value.map(|value| parse(value).ok_or(error)).transpose()
 }
 
 impl ExtendedMap<&'static str, String> for HashMap<String, String> {
@@ -66,6 +150,27 @@ impl ExtendedMap<&'static str, String> for HashMap<String, String> {
         self.get(key)
             .map_or(Err(error), |s| s.parse::<T>().map_err(|_| error))
     }
+
+    fn get_duration(
+        &self,
+        key: &str,
+        unit: Duration,
+        error: &'static str,
+    ) -> Result<Option<Duration>, &'static str> {
+        parse_typed(
+            self.get(key).map(|s| s.as_str()),
+            |s| parse_duration(s, unit),
+            error,
+        )
+    }
+
+    fn get_size(&self, key: &str, error: &'static str) -> Result<Option<usize>, &'static str> {
+        parse_typed(self.get(key).map(|s| s.as_str()), parse_size, error)
+    }
+
+    fn get_bool(&self, key: &str, error: &'static str) -> Result<Option<bool>, &'static str> {
+        parse_typed(self.get(key).map(|s| s.as_str()), parse_bool, error)
+    }
 }
 
 impl ExtendedMap<&'static str, String> for HashMap<String, ConfigNode> {
@@ -131,4 +236,30 @@ impl ExtendedMap<&'static str, String> for HashMap<String, ConfigNode> {
             .unwrap_or(Err(()))
             .map_err(|_| error)
     }
+
+    fn get_duration(
+        &self,
+        key: &'static str,
+        unit: Duration,
+        error: &'static str,
+    ) -> Result<Option<Duration>, &'static str> {
+        let value = self.get_owned(key);
+        parse_typed(value.as_deref(), |s| parse_duration(s, unit), error)
+    }
+
+    fn get_size(
+        &self,
+        key: &'static str,
+        error: &'static str,
+    ) -> Result<Option<usize>, &'static str> {
+        parse_typed(self.get_owned(key).as_deref(), parse_size, error)
+    }
+
+    fn get_bool(
+        &self,
+        key: &'static str,
+        error: &'static str,
+    ) -> Result<Option<bool>, &'static str> {
+        parse_typed(self.get_owned(key).as_deref(), parse_bool, error)
+    }
 }
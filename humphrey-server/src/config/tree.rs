@@ -1,6 +1,7 @@
 //! Provides functionality for working with the configuration syntax tree.
 
-use crate::config::error::ConfigError;
+use crate::config::error::{ConfigError, ConfigErrors};
+use crate::config::extended_hashmap::{parse_bool, parse_duration, parse_size};
 use crate::config::traceback::TracebackIterator;
 use humphrey::krauss::wildcard_match;
 
@@ -8,22 +9,61 @@ use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
 use std::str::Lines;
+use std::time::Duration;
 
 /// Represents a node in the configuration syntax tree.
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub enum ConfigNode {
-    /// A node that contains a number.
-    Number(String, String),
-    /// A node that contains a boolean.
-    Boolean(String, String),
+    /// A node that contains a number. Sizes with a unit, such as `10M`, are converted to bytes, but
+    ///   durations with a unit, such as `30s`, are kept exactly as they were written.
+    Number(ConfigKey, String),
+    /// A node that contains a boolean, such as `true` or `yes`, exactly as it was written.
+    Boolean(ConfigKey, String),
     /// A node that contains a string.
-    String(String, String),
+    String(ConfigKey, String),
     /// A node that represents a section and contains a number of child nodes.
-    Section(String, Vec<ConfigNode>),
+    Section(ConfigKey, Vec<ConfigNode>),
     /// A node that represents a host's configuration and contains a number of child nodes.
-    Host(String, Vec<ConfigNode>),
+    Host(ConfigKey, Vec<ConfigNode>),
     /// A node that represents a route's configuration and contains a number of child nodes.
-    Route(String, Vec<ConfigNode>),
+    Route(ConfigKey, Vec<ConfigNode>),
+}
+
+/// Represents the name of a node in the configuration syntax tree, along with where it was defined.
+///
+/// Keys are compared by name only, so trees parsed from differently laid out files are equal if they
+///   contain the same configuration.
+#[derive(Clone, Debug, Eq)]
+pub struct ConfigKey {
+    /// The name of the node.
+    pub name: String,
+    /// The file in which the node was defined.
+    pub file: String,
+    /// The line on which the node was defined, or zero if it was not parsed from a file.
+    pub line: u64,
+}
+
+impl ConfigKey {
+    /// Creates a new key with the given name and location.
+    pub fn new(name: &str, file: &str, line: u64) -> Self {
+        Self {
+            name: name.to_string(),
+            file: file.to_string(),
+            line,
+        }
+    }
+}
+
+impl PartialEq for ConfigKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl From<&str> for ConfigKey {
+    fn from(name: &str) -> Self {
+        Self::new(name, "", 0)
+    }
 }
 
 impl ConfigNode {
@@ -31,9 +71,9 @@ impl ConfigNode {
     pub fn flatten(&self, hashmap: &mut HashMap<String, Self>, level: &[&str]) {
         match self {
             ConfigNode::Section(k, v) => {
-                if k != "plugins" {
+                if k.name != "plugins" {
                     let mut new_level = level.to_vec();
-                    new_level.push(k);
+                    new_level.push(&k.name);
                     for child in v {
                         child.flatten(hashmap, &new_level);
                     }
@@ -41,7 +81,7 @@ impl ConfigNode {
             }
             ConfigNode::Number(k, _) | ConfigNode::Boolean(k, _) | ConfigNode::String(k, _) => {
                 let mut new_level = level.to_vec();
-                new_level.push(k);
+                new_level.push(&k.name);
                 hashmap.insert(new_level.join("."), self.clone());
             }
             _ => (),
@@ -55,7 +95,7 @@ impl ConfigNode {
         if let ConfigNode::Section(_, children) = self {
             for child in children {
                 if let ConfigNode::Host(wild, _) = child {
-                    hosts.push((wild.clone(), child.clone()));
+                    hosts.push((wild.name.clone(), child.clone()));
                 }
             }
         }
@@ -64,8 +104,8 @@ impl ConfigNode {
     }
 
     /// Get the routes configured under this node.
-    pub fn get_routes(&self) -> Vec<(String, HashMap<String, Self>)> {
-        let mut routes: Vec<(String, HashMap<String, Self>)> = Vec::new();
+    pub fn get_routes(&self) -> Vec<(ConfigKey, HashMap<String, Self>)> {
+        let mut routes: Vec<(ConfigKey, HashMap<String, Self>)> = Vec::new();
 
         if let ConfigNode::Section(_, children) = self {
             for child in children {
@@ -97,12 +137,12 @@ impl ConfigNode {
     }
 
     /// Get the plugins configured under this node.
-    pub fn get_plugins(&self) -> Vec<(String, HashMap<String, Self>)> {
-        let mut plugins: Vec<(String, HashMap<String, Self>)> = Vec::new();
+    pub fn get_plugins(&self) -> Vec<(ConfigKey, HashMap<String, Self>)> {
+        let mut plugins: Vec<(ConfigKey, HashMap<String, Self>)> = Vec::new();
         if let ConfigNode::Section(_, children) = self {
             for child in children {
                 if let ConfigNode::Section(name, inner_children) = child {
-                    if name == "plugins" {
+                    if name.name == "plugins" {
                         for inner_child in inner_children {
                             if let ConfigNode::Section(inner_name, inner_inner_children) =
                                 inner_child
@@ -123,6 +163,18 @@ impl ConfigNode {
         plugins
     }
 
+    /// Get the key of this node, which records its name and where it was defined.
+    pub fn key(&self) -> &ConfigKey {
+        match self {
+            ConfigNode::Number(k, _)
+            | ConfigNode::Boolean(k, _)
+            | ConfigNode::String(k, _)
+            | ConfigNode::Section(k, _)
+            | ConfigNode::Host(k, _)
+            | ConfigNode::Route(k, _) => k,
+        }
+    }
+
     /// Get this node's value as a string, or `None` if this is not possible.
     pub fn get_string(&self) -> Option<String> {
         match self {
@@ -135,7 +187,23 @@ impl ConfigNode {
 }
 
 /// Parses an entire configuration string.
-pub fn parse_conf(conf: &str, filename: &str) -> Result<ConfigNode, ConfigError> {
+pub fn parse_conf(conf: &str, filename: &str) -> Result<ConfigNode, ConfigErrors> {
+    let mut errors = ConfigErrors::new();
+
+    match parse_conf_recovering(conf, filename, &mut errors) {
+        Some(tree) if errors.is_empty() => Ok(tree),
+        _ => Err(errors),
+    }
+}
+
+/// Parses an entire configuration string, recording errors in values and continuing.
+/// Returns `None` if the structure of the file could not be parsed, in which case the errors found
+///   up to that point are still recorded.
+pub fn parse_conf_recovering(
+    conf: &str,
+    filename: &str,
+    errors: &mut ConfigErrors,
+) -> Option<ConfigNode> {
     let mut lines = TracebackIterator::from(conf.lines());
 
     // Attempts to find the start of the configuration
@@ -144,24 +212,28 @@ pub fn parse_conf(conf: &str, filename: &str) -> Result<ConfigNode, ConfigError>
         if let Some(line) = lines.next() {
             line_content = clean_up(line);
         } else {
-            return Err(ConfigError::new(
+            errors.push(ConfigError::new(
                 "Could not find `server` section",
                 filename,
                 0,
             ));
+            return None;
         }
     }
 
     // Parses the main section
-    parse_section("server", &mut lines, filename)
+    parse_section("server", "server", &mut lines, filename, errors)
 }
 
-/// Recursively parses a section of the configuration.
+/// Recursively parses a section of the configuration, where `path` describes the section in errors.
 fn parse_section(
     name: &str,
+    path: &str,
     lines: &mut TracebackIterator<Lines>,
     filename: &str,
-) -> Result<ConfigNode, ConfigError> {
+    errors: &mut ConfigErrors,
+) -> Option<ConfigNode> {
+    let key = ConfigKey::new(name, filename, lines.current_line());
     let mut values: Vec<ConfigNode> = Vec::new();
 
     // While this section has not ended
@@ -178,7 +250,8 @@ fn parse_section(
                 if section_name.starts_with("route ") && section_name != "route {" {
                     // If the section is a route section, parse it as such
                     let route_name = section_name.splitn(2, ' ').last().unwrap().trim();
-                    let section = parse_section(route_name, lines, filename)?;
+                    let route_path = format!("{} > route {}", path, route_name);
+                    let section = parse_section(route_name, &route_path, lines, filename, errors)?;
                     if let ConfigNode::Section(route_name, inner_values) = section {
                         values.push(ConfigNode::Route(route_name, inner_values));
                     }
@@ -193,109 +266,126 @@ fn parse_section(
                         }
                     };
 
-                    let section = parse_section(&host_name, lines, filename)?;
+                    let host_path = format!("{} > host {}", path, host_name);
+                    let section = parse_section(&host_name, &host_path, lines, filename, errors)?;
                     if let ConfigNode::Section(host_name, inner_values) = section {
                         values.push(ConfigNode::Host(host_name, inner_values));
                     }
                 } else {
                     // If the section is just a regular section, parse it in the normal way
-                    values.push(parse_section(section_name, lines, filename)?);
+                    let section_path = format!("{} > {}", path, section_name);
+                    values.push(parse_section(
+                        section_name,
+                        &section_path,
+                        lines,
+                        filename,
+                        errors,
+                    )?);
                 }
             } else if line == "}" {
                 // If the line indicates the end of this section, return the parsed section
 
                 break;
             } else if !line.is_empty() {
-                // If the line is not empty, attempt to parse the value
+                // If the line is not empty, attempt to parse the value, skipping it if invalid
+
+                let error = |message| {
+                    ConfigError::new(message, filename, lines.current_line()).with_block(path)
+                };
 
                 let parts: Vec<&str> = line.splitn(2, ' ').collect();
-                quiet_assert(parts.len() == 2, "Syntax error", filename, lines)?;
+                if parts.len() != 2 {
+                    errors.push(error("Syntax error"));
+                    continue;
+                }
 
-                let key = parts[0].trim();
+                let key = ConfigKey::new(parts[0].trim(), filename, lines.current_line());
                 let value = parts[1].trim();
 
                 // If this is just a regular value
-                if key != "include" {
+                if key.name != "include" {
                     if wildcard_match("\"*\"", value) {
-                        values.push(ConfigNode::String(
-                            key.into(),
-                            value[1..value.len() - 1].into(),
-                        ))
+                        values.push(ConfigNode::String(key, value[1..value.len() - 1].into()))
                     } else if value.parse::<i64>().is_ok() {
-                        values.push(ConfigNode::Number(key.into(), value.into()))
-                    } else if value.parse::<bool>().is_ok() {
-                        values.push(ConfigNode::Boolean(key.into(), value.into()))
-                    } else if let Ok(size) = parse_size(value) {
-                        values.push(ConfigNode::Number(key.into(), size.to_string()))
+                        values.push(ConfigNode::Number(key, value.into()))
+                    } else if parse_bool(value).is_some() {
+                        values.push(ConfigNode::Boolean(key, value.into()))
+                    } else if parse_duration(value, Duration::ZERO).is_some() {
+                        // A value such as `5m` could be a duration or a size, so its unit is only
+                        //   interpreted once it is known which key it belongs to
+                        values.push(ConfigNode::Number(key, value.into()))
+                    } else if let Some(size) = parse_size(value) {
+                        values.push(ConfigNode::Number(key, size.to_string()))
                     } else {
-                        return Err(ConfigError::new(
-                            "Could not parse value",
-                            filename,
-                            lines.current_line(),
-                        ));
+                        errors.push(error("Could not parse value"));
                     }
                 } else if wildcard_match("\"*\"", value) {
-                    let include_result =
-                        include(&value[1..value.len() - 1], filename, lines.current_line());
-                    if let Ok(included_nodes) = include_result {
-                        values.extend(included_nodes);
-                    } else {
-                        return Err(include_result.unwrap_err());
-                    }
-                } else {
-                    return Err(ConfigError::new(
-                        "Invalid include value, it takes a file path in quotation marks as its value",
+                    values.extend(include(
+                        &value[1..value.len() - 1],
                         filename,
                         lines.current_line(),
+                        path,
+                        errors,
+                    )?);
+                } else {
+                    errors.push(error(
+                        "Invalid include value, it takes a file path in quotation marks as its value",
                     ));
                 }
             }
         } else {
-            // If the line could not be read, return an error
-
-            return Err(ConfigError::new(
-                "Unexpected end of file, expected `}`",
-                filename,
-                lines.current_line(),
-            ));
+            // If the line could not be read, the section can never be closed
+
+            errors.push(
+                ConfigError::new(
+                    "Unexpected end of file, expected `}`",
+                    filename,
+                    lines.current_line(),
+                )
+                .with_block(path),
+            );
+            return None;
         }
     }
 
-    Ok(ConfigNode::Section(name.into(), values))
+    Some(ConfigNode::Section(key, values))
 }
 
-/// Attempts to include the configuration file at the specified path into the tree,
-///   returning a `Vec` of `ConfigNode`s. If unsuccessful, returns a descriptive error.
-fn include(path: &str, containing_file: &str, line: u64) -> Result<Vec<ConfigNode>, ConfigError> {
+/// Attempts to include the configuration file at the specified path into the tree, at the block
+///   with the given path, returning a `Vec` of `ConfigNode`s. If the file cannot be read, the error is
+///   recorded and no nodes are returned, but if it cannot be parsed, returns `None`.
+fn include(
+    path: &str,
+    containing_file: &str,
+    line: u64,
+    block: &str,
+    errors: &mut ConfigErrors,
+) -> Option<Vec<ConfigNode>> {
+    let error = |message| ConfigError::new(message, containing_file, line).with_block(block);
+
     if let Ok(mut file) = File::open(path) {
         let mut buf = String::new();
         if file.read_to_string(&mut buf).is_ok() {
             buf.push_str("\n}");
 
             let mut iter = TracebackIterator::from(buf.lines());
-            let parsed_node = parse_section("temp_included_section", &mut iter, path)?;
+            let parsed_node =
+                parse_section("temp_included_section", block, &mut iter, path, errors)?;
 
             match parsed_node {
-                ConfigNode::Section(_, children) => Ok(children),
-                _ => Err(ConfigError::new(
-                    "Internal parser error",
-                    containing_file,
-                    line,
-                )),
+                ConfigNode::Section(_, children) => Some(children),
+                _ => {
+                    errors.push(error("Internal parser error"));
+                    Some(Vec::new())
+                }
             }
         } else {
-            Err(ConfigError::new(
-                "Could not read included file",
-                containing_file,
-                line,
-            ))
+            errors.push(error("Could not read included file"));
+            Some(Vec::new())
         }
     } else {
-        Err(ConfigError::new(
-            "Could not open included file",
-            containing_file,
-            line,
-        ))
+        errors.push(error("Could not open included file"));
+        Some(Vec::new())
     }
 }
 
@@ -303,45 +393,3 @@ fn include(path: &str, containing_file: &str, line: u64) -> Result<Vec<ConfigNod
 fn clean_up(line: &str) -> &str {
     line.split_once('#').map_or(line, |x| x.0).trim()
 }
-
-/// Parses a size string into its corresponding number of bytes.
-/// For example, 4K => 4096, 1M => 1048576.
-/// If no letter is provided at the end, assumes the number to be in bytes.
-fn parse_size(size: &str) -> Result<i64, ()> {
-    if size.is_empty() {
-        // Empty string
-
-        Err(())
-    } else if size.len() == 1 {
-        // One character so cannot possibly be valid
-
-        size.parse::<i64>().map_err(|_| ())
-    } else {
-        let last_char = size.chars().last().unwrap().to_ascii_uppercase();
-        let number: i64 = size[0..size.len() - 1].parse().map_err(|_| ())?;
-
-        match last_char {
-            'K' => Ok(number * 1024),
-            'M' => Ok(number * 1024 * 1024),
-            'G' => Ok(number * 1024 * 1024 * 1024),
-            '0'..='9' => size.parse::<i64>().map_err(|_| ()),
-            _ => Err(()),
-        }
-    }
-}
-
-/// Asserts a condition, returning a `Result` rather than panicking like the `assert!` macro.
-fn quiet_assert<T>(
-    condition: bool,
-    message: &'static str,
-    filename: &str,
-    iter: &mut TracebackIterator<T>,
-) -> Result<(), ConfigError>
-where
-    T: Iterator,
-{
-    match condition {
-        true => Ok(()),
-        false => Err(ConfigError::new(message, filename, iter.current_line())),
-    }
-}
@@ -23,6 +23,9 @@ pub trait Plugin: Any + Send + Sync + Debug {
     /// Called when the plugin is first loaded.
     /// Any set-up that needs to be done before requests are handled should be done here.
     ///
+    /// The configuration is the plugin's own section of the configuration file. Settings from the rest of
+    ///   the file can be read from `state.config`, for example with `Config::plugin_settings`.
+    ///
     /// If the plugin cannot load for any reason, it should return `PluginLoadResult::NonFatal("error message")`
     ///   if the error is not fatal, for example configuration could not be loaded and defaults must be used, or
     ///   `PluginLoadResult::Fatal("error message")` if the error is fatal and will prevent the plugin from
@@ -0,0 +1,148 @@
+//! Provides debug logging of request and response bodies for routes which opt in with `log_bodies`.
+//!
+//! Bodies are only ever borrowed, so nothing is copied when body logging is disabled, and at most
+//!   the configured number of bytes is rendered when it is enabled.
+
+use crate::config::BodyLogConfig;
+use crate::logger::LogLevel;
+use crate::server::server::AppState;
+
+use humphrey::http::address::Address;
+use humphrey::http::headers::{HeaderType, Headers};
+use humphrey::http::{Request, Response};
+
+use std::fmt::Write;
+
+/// The maximum number of bytes shown in the hex preview of a binary body.
+const HEX_PREVIEW_LENGTH: usize = 16;
+
+/// Logs the headers and body of the request at debug level.
+pub fn log_request_body(request: &Request, state: &AppState) {
+    let config = &state.config.logging.bodies;
+
+    state.logger.debug(format!(
+        "{}: Request {} {} headers [{}] body {}",
+        request.address,
+        request.method,
+        request.uri,
+        format_headers(&request.headers, config),
+        format_body(request.content.as_deref().unwrap_or_default(), config.limit)
+    ));
+}
+
+/// Logs the headers and body of the response to the request from the given address and URI at debug
+///   level.
+pub fn log_response_body(address: &Address, uri: &str, response: &Response, state: &AppState) {
+    let config = &state.config.logging.bodies;
+    let status: u16 = response.status_code.into();
+
+    state.logger.debug(format!(
+        "{}: Response {} {} headers [{}] body {}",
+        address,
+        status,
+        uri,
+        format_headers(&response.headers, config),
+        format_body(&response.body, config.limit)
+    ));
+}
+
+/// Returns whether bodies should be logged for the route, which requires both the route to opt in
+///   and the log level to be `debug`.
+pub fn body_logging_enabled(state: &AppState, route_opted_in: bool) -> bool {
+    route_opted_in && state.logger.enabled(LogLevel::Debug)
+}
+
+/// Formats the headers as a comma-separated list, replacing the values of redacted headers.
+pub fn format_headers(headers: &Headers, config: &BodyLogConfig) -> String {
+    let redacted: Vec<HeaderType> = config
+        .redact_headers
+        .iter()
+        .map(|name| HeaderType::from(name.as_str()))
+        .collect();
+
+    headers
+        .iter()
+        .map(|header| {
+            let value = if redacted.contains(&header.name) {
+                "[redacted]"
+            } else {
+                header.value.as_str()
+            };
+
+            format!("{}: {}", header.name.to_string(), value)
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Formats a body for logging, showing at most `limit` bytes of it.
+///
+/// Text is shown in quotation marks with control characters escaped, followed by the total length if
+///   it was truncated. Anything which is not valid UTF-8 or contains null bytes is treated as binary,
+///   and summarised by its length, its FNV-1a hash and a hex preview of its first bytes.
+pub fn format_body(body: &[u8], limit: usize) -> String {
+    if body.is_empty() {
+        return "(empty)".into();
+    }
+
+    let shown = &body[..body.len().min(limit)];
+
+    // A multi-byte character may have been cut off by the limit, which is not a sign of binary data
+    let text = match std::str::from_utf8(shown) {
+        Ok(text) => Some(text),
+        Err(e) if e.error_len().is_none() && shown.len() < body.len() => {
+            Some(std::str::from_utf8(&shown[..e.valid_up_to()]).unwrap())
+        }
+        Err(_) => None,
+    };
+
+    match text {
+        Some(text) if !text.contains('\0') => {
+            let mut formatted = String::with_capacity(text.len() + 2);
+            formatted.push('"');
+
+            for ch in text.chars() {
+                if ch.is_control() {
+                    formatted.extend(ch.escape_default());
+                } else {
+                    formatted.push(ch);
+                }
+            }
+
+            formatted.push('"');
+
+            if shown.len() < body.len() {
+                write!(formatted, "... ({} bytes)", body.len()).unwrap();
+            }
+
+            formatted
+        }
+        _ => {
+            let preview: Vec<String> = body
+                .iter()
+                .take(HEX_PREVIEW_LENGTH.min(limit))
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            let ellipsis = if preview.len() < body.len() {
+                " ..."
+            } else {
+                ""
+            };
+
+            format!(
+                "binary, {} bytes, fnv1a {:016x}, {}{}",
+                body.len(),
+                fnv1a(body),
+                preview.join(" "),
+                ellipsis
+            )
+        }
+    }
+}
+
+/// Computes the 64-bit FNV-1a hash of the bytes.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
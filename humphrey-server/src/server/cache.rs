@@ -1,9 +1,14 @@
 //! Provides caching functionality.
 
-use crate::config::Config;
+use crate::config::{CachePriority, Config, RouteCacheConfig};
 
+use humphrey::file::FileVersion;
 use humphrey::http::mime::MimeType;
-use std::{collections::VecDeque, time::SystemTime};
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::SystemTime;
 
 /// Represents the server's cache.
 #[derive(Default)]
@@ -11,8 +16,11 @@ pub struct Cache {
     /// The cache's maximum size.
     pub cache_limit: usize,
     cache_time_limit: u64,
+    max_file_size: Option<usize>,
+    ceiling: usize,
     cache_size: usize,
     data: VecDeque<CachedItem>,
+    stats: Mutex<HashMap<String, RouteStats>>,
 }
 
 /// Represents a cached item.
@@ -25,8 +33,30 @@ pub struct CachedItem {
     pub mime_type: MimeType,
     /// The time at which the item was cached.
     pub cache_time: u64,
+    /// Whether the item is pinned, meaning it is only evicted to stay within the memory ceiling.
+    pub pinned: bool,
     /// The item's data.
     pub data: Vec<u8>,
+    /// The file which the item was read from, if it was read from a file.
+    pub file: Option<CachedFile>,
+}
+
+/// Represents the file which a cached item was read from, so that changes to it can be detected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CachedFile {
+    /// The path of the file.
+    pub path: PathBuf,
+    /// The version of the file which was read.
+    pub version: FileVersion,
+}
+
+/// Represents the cache statistics for a configured route.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RouteStats {
+    /// The number of requests which were served from the cache.
+    pub hits: usize,
+    /// The number of requests which could not be served from the cache.
+    pub misses: usize,
 }
 
 impl Cache {
@@ -38,12 +68,7 @@ impl Cache {
             .unwrap()
             .as_secs();
 
-        let index = self
-            .data
-            .iter()
-            .position(|item| item.route == route && item.host == host);
-
-        if let Some(index) = index {
+        if let Some(index) = self.position(route, host) {
             let item = &self.data[index];
             if time - item.cache_time > self.cache_time_limit {
                 None
@@ -55,21 +80,105 @@ impl Cache {
         }
     }
 
-    /// Sets an item in the cache.
+    /// Records whether a lookup for the given configured route was a hit or a miss.
+    pub fn record_lookup(&self, route: &str, hit: bool) {
+        let mut stats = self.stats.lock().unwrap();
+        let entry = stats.entry(route.to_string()).or_default();
+
+        if hit {
+            entry.hits += 1;
+        } else {
+            entry.misses += 1;
+        }
+    }
+
+    /// Sets an item in the cache according to the caching rules of its route.
     /// Overwrites older versions if needed.
-    pub fn set(&mut self, route: &str, host: usize, value: Vec<u8>, mime_type: MimeType) {
+    ///
+    /// Unpinned items are evicted oldest-first to make space. Pinned items are only evicted if the
+    ///   cache would otherwise exceed its memory ceiling. Returns whether the item was cached.
+    pub fn set(
+        &mut self,
+        route: &str,
+        host: usize,
+        value: Vec<u8>,
+        mime_type: MimeType,
+        rules: &RouteCacheConfig,
+    ) -> bool {
+        self.insert(route, host, value, mime_type, rules, None)
+    }
+
+    /// Sets an item which was read from the given file in the cache, in the same way as `set`.
+    ///
+    /// The file is remembered so that the item can be invalidated once the file changes.
+    pub fn set_from_file(
+        &mut self,
+        route: &str,
+        host: usize,
+        value: Vec<u8>,
+        mime_type: MimeType,
+        rules: &RouteCacheConfig,
+        file: CachedFile,
+    ) -> bool {
+        self.insert(route, host, value, mime_type, rules, Some(file))
+    }
+
+    /// Removes the item for the given route and host from the cache, returning whether it was
+    ///   present.
+    pub fn invalidate(&mut self, route: &str, host: usize) -> bool {
+        match self.position(route, host) {
+            Some(index) => {
+                self.evict(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Inserts an item into the cache according to the caching rules of its route.
+    fn insert(
+        &mut self,
+        route: &str,
+        host: usize,
+        value: Vec<u8>,
+        mime_type: MimeType,
+        rules: &RouteCacheConfig,
+        file: Option<CachedFile>,
+    ) -> bool {
+        let max_file_size = rules
+            .max_file_size
+            .or(self.max_file_size)
+            .unwrap_or(self.cache_limit);
+        let pinned = rules.priority == CachePriority::High;
+        let ceiling = if pinned {
+            self.ceiling
+        } else {
+            self.cache_limit
+        };
+
+        if !rules.enabled || value.len() > max_file_size || value.len() > ceiling {
+            return false;
+        }
+
+        if let Some(existing_item) = self.position(route, host) {
+            self.evict(existing_item);
+        }
+
+        // Evict unpinned items to stay within the size limit
         while self.cache_size + value.len() > self.cache_limit {
-            self.cache_size -= self.data[0].data.len();
-            self.data.pop_front();
+            match self.data.iter().position(|item| !item.pinned) {
+                Some(index) => self.evict(index),
+                None => break,
+            }
         }
 
-        if let Some(existing_item) = self
-            .data
-            .iter()
-            .position(|item| item.route == route && item.host == host)
-        {
-            self.cache_size -= self.data[existing_item].data.len();
-            self.data.remove(existing_item);
+        // Only pinned items remain, so only a pinned item may use the space up to the ceiling
+        while self.cache_size + value.len() > ceiling {
+            if !pinned || self.data.is_empty() {
+                return false;
+            }
+
+            self.evict(0);
         }
 
         self.cache_size += value.len();
@@ -78,12 +187,101 @@ impl Cache {
             route: route.into(),
             host,
             data: value,
+            file,
             mime_type,
+            pinned,
             cache_time: SystemTime::now()
                 .duration_since(SystemTime::UNIX_EPOCH)
                 .unwrap()
                 .as_secs(),
         });
+
+        true
+    }
+
+    /// Returns the current size of the cache in bytes.
+    pub fn size(&self) -> usize {
+        self.cache_size
+    }
+
+    /// Returns the given number of items which occupy the most space, largest first.
+    pub fn largest_items(&self, count: usize) -> Vec<&CachedItem> {
+        let mut items: Vec<&CachedItem> = self.data.iter().collect();
+        items.sort_by_key(|item| Reverse(item.data.len()));
+        items.truncate(count);
+        items
+    }
+
+    /// Returns the hit and miss counts of every configured route which has been looked up.
+    pub fn route_stats(&self) -> HashMap<String, RouteStats> {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Generates a human-readable summary of the cache's usage, for debug logging.
+    pub fn report(&self) -> String {
+        let largest: Vec<String> = self
+            .largest_items(5)
+            .iter()
+            .map(|item| format!("{} ({} bytes)", item.route, item.data.len()))
+            .collect();
+
+        let mut stats: Vec<(String, RouteStats)> = self.route_stats().into_iter().collect();
+        stats.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let ratios: Vec<String> = stats
+            .iter()
+            .map(|(route, stats)| {
+                format!(
+                    "{} {:.0}% of {}",
+                    route,
+                    stats.hit_ratio() * 100.0,
+                    stats.hits + stats.misses
+                )
+            })
+            .collect();
+
+        format!(
+            "Cache using {}/{} bytes, largest items: [{}], hit ratios: [{}]",
+            self.cache_size,
+            self.cache_limit,
+            largest.join(", "),
+            ratios.join(", ")
+        )
+    }
+
+    /// Returns the index of the item for the given route and host.
+    fn position(&self, route: &str, host: usize) -> Option<usize> {
+        self.data
+            .iter()
+            .position(|item| item.route == route && item.host == host)
+    }
+
+    /// Removes the item at the given index.
+    fn evict(&mut self, index: usize) {
+        if let Some(item) = self.data.remove(index) {
+            self.cache_size -= item.data.len();
+        }
+    }
+}
+
+impl CachedItem {
+    /// Returns whether the file which the item was read from is unchanged, so the item can still be
+    ///   served. Items which were not read from a file are always current.
+    pub fn is_current(&self) -> bool {
+        match &self.file {
+            Some(file) => FileVersion::of(&file.path).is_ok_and(|version| version == file.version),
+            None => true,
+        }
+    }
+}
+
+impl RouteStats {
+    /// Returns the proportion of lookups which were hits, or zero if there have been none.
+    pub fn hit_ratio(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            total => self.hits as f64 / total as f64,
+        }
     }
 }
 
@@ -92,8 +290,11 @@ impl From<&Config> for Cache {
         Self {
             cache_limit: config.cache.size_limit,
             cache_time_limit: config.cache.time_limit as u64,
+            max_file_size: config.cache.max_file_size,
+            ceiling: config.cache.ceiling.max(config.cache.size_limit),
             cache_size: 0,
             data: VecDeque::new(),
+            stats: Default::default(),
         }
     }
 }
@@ -1,5 +1,14 @@
 //! Provides logging functionality.
+//!
+//! Plugins should log through a `ScopedLogger`, obtained with `Logger::scoped`, so that their
+//!   messages are attributed to them and respect their configured log level.
+//!
+//! Messages are written as plain text by default, or as one JSON object per line if the log format is
+//!   set to `json`. Each JSON line has the keys `timestamp` (in RFC 3339 format), `level`, `scope`
+//!   (the name of the plugin, or `null`), `message` and `fields`, which is an object holding any
+//!   fields attached with `log_with_fields`.
 
+use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
 use std::io::Write;
 use std::str::FromStr;
@@ -9,6 +18,8 @@ use std::sync::{Arc, Mutex};
 use humphrey::http::date::DateTime;
 use humphrey::monitor::event::{Event, EventType, ToEventMask};
 
+use humphrey_json::Value;
+
 use crate::config::Config;
 use crate::AppState;
 
@@ -21,7 +32,10 @@ pub const INTERNAL_MASK_WARN: u32 = INTERNAL_MASK_ERROR
     | EventType::RequestTimeout as u32
     | EventType::StreamDisconnectedWhileWaiting as u32
     | EventType::ThreadPoolOverload as u32
-    | EventType::ThreadRestarted as u32;
+    | EventType::ThreadRestarted as u32
+    | EventType::SlowRequest as u32
+    | EventType::ConnectionRejectedOverCapacity as u32
+    | EventType::RequestRateLimited as u32;
 
 /// Event mask for the `LogLevel::Info` log level.
 pub const INTERNAL_MASK_INFO: u32 = INTERNAL_MASK_WARN | EventType::HTTPSRedirect as u32;
@@ -36,10 +50,29 @@ pub const INTERNAL_MASK_DEBUG: u32 = INTERNAL_MASK_INFO
 /// Encapsulates logging methods and configuration.
 pub struct Logger {
     level: LogLevel,
+    plugin_levels: HashMap<String, LogLevel>,
+    format: LogFormat,
     console: bool,
     file: Option<Mutex<File>>,
 }
 
+/// A handle to the logger which tags messages with the name of a plugin and applies the log level
+///   configured for that plugin, falling back to the server's log level.
+pub struct ScopedLogger<'a> {
+    logger: &'a Logger,
+    name: &'a str,
+    level: &'a LogLevel,
+}
+
+/// A single message to be logged, from which both log formats are produced.
+struct Record<'a> {
+    time: DateTime,
+    level: &'a LogLevel,
+    scope: Option<&'a str>,
+    message: &'a str,
+    fields: &'a [(&'a str, &'a str)],
+}
+
 /// Represents a log level.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum LogLevel {
@@ -53,10 +86,22 @@ pub enum LogLevel {
     Debug,
 }
 
+/// Represents the format in which messages are logged.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Each message is logged as a line of text.
+    #[default]
+    Text,
+    /// Each message is logged as a JSON object on its own line.
+    Json,
+}
+
 impl Default for Logger {
     fn default() -> Self {
         Self {
             level: LogLevel::Warn,
+            plugin_levels: HashMap::new(),
+            format: LogFormat::Text,
             console: true,
             file: None,
         }
@@ -76,8 +121,20 @@ impl From<&Config> for Logger {
             )
         });
 
+        #[cfg(feature = "plugins")]
+        let plugin_levels = config
+            .plugins
+            .iter()
+            .filter_map(|plugin| Some((plugin.name.clone(), plugin.log_level.clone()?)))
+            .collect();
+
+        #[cfg(not(feature = "plugins"))]
+        let plugin_levels = HashMap::new();
+
         Self {
             level: config.logging.level.clone(),
+            plugin_levels,
+            format: config.logging.format.clone(),
             console: config.logging.console,
             file,
         }
@@ -85,52 +142,84 @@ impl From<&Config> for Logger {
 }
 
 impl Logger {
+    /// Returns a handle to the logger for the plugin with the given name, which should match the name
+    ///   of the plugin's section in the configuration file.
+    pub fn scoped<'a>(&'a self, name: &'a str) -> ScopedLogger<'a> {
+        ScopedLogger {
+            logger: self,
+            name,
+            level: self.plugin_levels.get(name).unwrap_or(&self.level),
+        }
+    }
+
+    /// Returns `true` if messages at the given level will be logged.
+    pub fn enabled(&self, level: LogLevel) -> bool {
+        self.level >= level
+    }
+
     /// Logs an error message.
     pub fn error(&self, message: impl AsRef<str>) {
-        let string = format!("{} [ERROR] {}", Logger::time_format(), message.as_ref());
-        self.log_to_console(&string);
-        self.log_to_file(&string);
+        self.log_with_fields(LogLevel::Error, message, &[]);
     }
 
     /// Logs a warning, provided that the log level allows this.
     pub fn warn(&self, message: impl AsRef<str>) {
-        if self.level >= LogLevel::Warn {
-            let string = format!("{} [WARN]  {}", Logger::time_format(), message.as_ref());
-            self.log_to_console(&string);
-            self.log_to_file(&string);
-        }
+        self.log_with_fields(LogLevel::Warn, message, &[]);
     }
 
     /// Logs information, provided that the log level allows this.
     pub fn info(&self, message: impl AsRef<str>) {
-        if self.level >= LogLevel::Info {
-            let string = format!("{} [INFO]  {}", Logger::time_format(), message.as_ref());
-            self.log_to_console(&string);
-            self.log_to_file(&string);
-        }
+        self.log_with_fields(LogLevel::Info, message, &[]);
     }
 
     /// Logs debug information, provided that the log level allows this.
     pub fn debug(&self, message: impl AsRef<str>) {
-        if self.level == LogLevel::Debug {
-            let string = format!("{} [DEBUG] {}", Logger::time_format(), message.as_ref());
-            self.log_to_console(&string);
-            self.log_to_file(&string);
+        self.log_with_fields(LogLevel::Debug, message, &[]);
+    }
+
+    /// Logs the message at the given level with the given fields attached, provided that the log
+    ///   level allows this.
+    ///
+    /// In the JSON format, the fields are logged as an object so they can be queried, while in the
+    ///   text format they are appended to the message as `key=value` pairs.
+    ///
+    /// ## Example
+    /// ```
+    /// logger.log_with_fields(
+    ///     LogLevel::Info,
+    ///     "Request served",
+    ///     &[("peer", "127.0.0.1"), ("status", "200"), ("route", "/*")],
+    /// );
+    /// ```
+    pub fn log_with_fields(
+        &self,
+        level: LogLevel,
+        message: impl AsRef<str>,
+        fields: &[(&str, &str)],
+    ) {
+        if self.enabled(level.clone()) {
+            self.write(&level, None, message.as_ref(), fields);
         }
     }
 
-    /// Formats the current time into the format `YYYY-MM-DD HH:MM:SS`
-    fn time_format() -> String {
-        let time = DateTime::now();
-        format!(
-            "{}-{:02}-{:02} {:02}:{:02}:{:02}",
-            time.year,
-            time.month + 1,
-            time.day,
-            time.hour,
-            time.minute,
-            time.second
-        )
+    /// Formats the message with the current time, level, scope and fields, and logs it to the console
+    ///   and the log file.
+    fn write(&self, level: &LogLevel, scope: Option<&str>, message: &str, fields: &[(&str, &str)]) {
+        let record = Record {
+            time: DateTime::now(),
+            level,
+            scope,
+            message,
+            fields,
+        };
+
+        let string = match self.format {
+            LogFormat::Text => record.to_text(),
+            LogFormat::Json => record.to_json(),
+        };
+
+        self.log_to_console(&string);
+        self.log_to_file(&string);
     }
 
     /// Logs the string to the console, if the logging configuration allows it
@@ -150,6 +239,142 @@ impl Logger {
     }
 }
 
+impl<'a> ScopedLogger<'a> {
+    /// Returns the name of the plugin which the logger is scoped to.
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    /// Returns `true` if messages at the given level will be logged.
+    pub fn enabled(&self, level: LogLevel) -> bool {
+        *self.level >= level
+    }
+
+    /// Logs the message returned by the closure at the given level, provided that the log level allows
+    ///   this. The closure is not called otherwise, so no formatting is done for disabled levels.
+    pub fn log_at<F, S>(&self, level: LogLevel, message: F)
+    where
+        F: FnOnce() -> S,
+        S: AsRef<str>,
+    {
+        if self.enabled(level.clone()) {
+            self.logger
+                .write(&level, Some(self.name), message().as_ref(), &[]);
+        }
+    }
+
+    /// Logs the message at the given level with the given fields attached, provided that the log
+    ///   level allows this.
+    pub fn log_with_fields(
+        &self,
+        level: LogLevel,
+        message: impl AsRef<str>,
+        fields: &[(&str, &str)],
+    ) {
+        if self.enabled(level.clone()) {
+            self.logger
+                .write(&level, Some(self.name), message.as_ref(), fields);
+        }
+    }
+
+    /// Logs an error message.
+    pub fn error(&self, message: impl AsRef<str>) {
+        self.log_at(LogLevel::Error, || message);
+    }
+
+    /// Logs a warning, provided that the log level allows this.
+    pub fn warn(&self, message: impl AsRef<str>) {
+        self.log_at(LogLevel::Warn, || message);
+    }
+
+    /// Logs information, provided that the log level allows this.
+    pub fn info(&self, message: impl AsRef<str>) {
+        self.log_at(LogLevel::Info, || message);
+    }
+
+    /// Logs debug information, provided that the log level allows this.
+    pub fn debug(&self, message: impl AsRef<str>) {
+        self.log_at(LogLevel::Debug, || message);
+    }
+}
+
+impl<'a> Record<'a> {
+    /// Formats the record as a line of text, in the format
+    ///   `YYYY-MM-DD HH:MM:SS [LEVEL] [scope] message key=value`.
+    fn to_text(&self) -> String {
+        let label = match self.level {
+            LogLevel::Error => "[ERROR]",
+            LogLevel::Warn => "[WARN] ",
+            LogLevel::Info => "[INFO] ",
+            LogLevel::Debug => "[DEBUG]",
+        };
+
+        let mut string = format!(
+            "{}-{:02}-{:02} {:02}:{:02}:{:02} {}",
+            self.time.year,
+            self.time.month + 1,
+            self.time.day,
+            self.time.hour,
+            self.time.minute,
+            self.time.second,
+            label
+        );
+
+        if let Some(scope) = self.scope {
+            string.push_str(&format!(" [{}]", scope));
+        }
+
+        string.push(' ');
+        string.push_str(self.message);
+
+        for (key, value) in self.fields {
+            string.push_str(&format!(" {}={}", key, value));
+        }
+
+        string
+    }
+
+    /// Formats the record as a JSON object on a single line.
+    fn to_json(&self) -> String {
+        let level = match self.level {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+        };
+
+        let timestamp = format!(
+            "{}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.time.year,
+            self.time.month + 1,
+            self.time.day,
+            self.time.hour,
+            self.time.minute,
+            self.time.second
+        );
+
+        let fields = self
+            .fields
+            .iter()
+            .map(|(key, value)| (key.to_string(), Value::String(value.to_string())))
+            .collect();
+
+        Value::Object(vec![
+            ("timestamp".into(), Value::String(timestamp)),
+            ("level".into(), Value::String(level.into())),
+            (
+                "scope".into(),
+                self.scope
+                    .map(|scope| Value::String(scope.into()))
+                    .unwrap_or(Value::Null),
+            ),
+            ("message".into(), Value::String(self.message.into())),
+            ("fields".into(), Value::Object(fields)),
+        ])
+        .serialize()
+    }
+}
+
 impl FromStr for LogLevel {
     type Err = &'static str;
 
@@ -164,6 +389,18 @@ impl FromStr for LogLevel {
     }
 }
 
+impl FromStr for LogFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err("Log format was invalid"),
+        }
+    }
+}
+
 impl ToEventMask for LogLevel {
     fn to_event_mask(&self) -> u32 {
         match self {
@@ -2,8 +2,10 @@
 
 #![allow(clippy::module_inception)]
 
+pub mod body_log;
 pub mod cache;
 pub mod logger;
+pub mod normalize;
 pub mod proxy;
 pub mod rand;
 pub mod server;
@@ -0,0 +1,78 @@
+//! Provides normalisation of request paths before they are routed.
+
+use humphrey::http::{Request, Response, StatusCode};
+
+use crate::config::NormalizationConfig;
+use crate::AppState;
+
+use std::sync::Arc;
+
+/// Normalises the path of the request in place, so that plugins, route matching and access checks
+///   all see the same canonical path.
+///
+/// The original URI remains available in `request.uri_raw`. If the path attempts to escape the root,
+///   the request is rejected with 400 Bad Request.
+pub fn normalize_request(request: &mut Request, state: Arc<AppState>) -> Option<Response> {
+    match normalize_path(&request.uri, &state.config.normalization) {
+        Some(path) => {
+            request.uri = path;
+            None
+        }
+        None => {
+            state.logger.warn(format!(
+                "{}: 400 Bad Request {}",
+                request.address, request.uri_raw
+            ));
+
+            Some(Response::empty(StatusCode::BadRequest))
+        }
+    }
+}
+
+/// Normalises a request path according to the configuration, returning `None` if the path resolves
+///   to somewhere above the root.
+///
+/// When dot segments are resolved, percent-encoded dots (`%2e`) are decoded first so that they
+///   cannot be used to hide `..` segments. Paths which do not start with a slash are left unchanged.
+pub fn normalize_path(path: &str, config: &NormalizationConfig) -> Option<String> {
+    if !path.starts_with('/') {
+        return Some(path.to_string());
+    }
+
+    let decoded = if config.resolve_dots {
+        path.replace("%2e", ".").replace("%2E", ".")
+    } else {
+        path.to_string()
+    };
+
+    let mut segments: Vec<&str> = Vec::new();
+    let mut trailing_slash = false;
+
+    for segment in decoded[1..].split('/') {
+        trailing_slash = true;
+
+        match segment {
+            "" if config.merge_slashes => (),
+            "." if config.resolve_dots => (),
+            ".." if config.resolve_dots => {
+                segments.pop()?;
+            }
+            _ => {
+                segments.push(segment);
+                trailing_slash = false;
+            }
+        }
+    }
+
+    let mut normalized = format!("/{}", segments.join("/"));
+
+    if trailing_slash && !segments.is_empty() {
+        normalized.push('/');
+    }
+
+    if config.lowercase {
+        normalized.make_ascii_lowercase();
+    }
+
+    Some(normalized)
+}
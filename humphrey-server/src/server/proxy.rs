@@ -1,16 +1,18 @@
 //! Provides HTTP proxy functionality.
 
-use crate::config::LoadBalancerMode;
+use crate::config::{LoadBalancerMode, RetryConfig, RewriteConfig};
 use crate::rand::{Choose, Lcg};
 use crate::server::server::AppState;
 
 use humphrey::http::headers::HeaderType;
-use humphrey::http::proxy::proxy_request;
+use humphrey::http::proxy::{try_proxy_request, ProxyError};
 use humphrey::http::{Request, Response, StatusCode};
 
+use std::collections::HashMap;
 use std::net::ToSocketAddrs;
 use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
-use std::time::Duration;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
 
 /// Represents a load balancer.
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -23,6 +25,8 @@ pub struct LoadBalancer {
     pub index: usize,
     /// The random number generator used by the load balancer.
     pub lcg: Lcg,
+    /// The targets which could not be connected to recently, and when they may next be tried.
+    pub unavailable: HashMap<String, Instant>,
 }
 
 impl LoadBalancer {
@@ -41,6 +45,63 @@ impl LoadBalancer {
             LoadBalancerMode::Random => self.targets.choose(&mut self.lcg).unwrap().clone(),
         }
     }
+
+    /// Selects a target according to the load balancer mode, preferring targets which have not
+    ///   already been tried and are not marked as unavailable.
+    ///
+    /// If every target has been tried, an available one is tried again, and if every target is
+    ///   unavailable, one is selected anyway so the caller can report the failure.
+    pub fn select_available_target(&mut self, tried: &[String]) -> String {
+        let now = Instant::now();
+        self.unavailable.retain(|_, until| *until > now);
+
+        let unavailable = std::mem::take(&mut self.unavailable);
+        let target = self
+            .select_target_where(|t| !tried.contains(t) && !unavailable.contains_key(t))
+            .or_else(|| self.select_target_where(|t| !unavailable.contains_key(t)))
+            .unwrap_or_else(|| self.select_target());
+        self.unavailable = unavailable;
+
+        target
+    }
+
+    /// Returns whether the target is marked as unavailable.
+    pub fn is_unavailable(&self, target: &str) -> bool {
+        matches!(self.unavailable.get(target), Some(until) if *until > Instant::now())
+    }
+
+    /// Marks the target as unavailable for the given time, so it is not selected while there are
+    ///   other targets available.
+    pub fn mark_unavailable(&mut self, target: &str, ttl: Duration) {
+        self.unavailable
+            .insert(target.to_string(), Instant::now() + ttl);
+    }
+
+    /// Selects a target which matches the predicate according to the load balancer mode, if there
+    ///   is one.
+    fn select_target_where(&mut self, predicate: impl Fn(&String) -> bool) -> Option<String> {
+        match self.mode {
+            LoadBalancerMode::RoundRobin => {
+                let len = self.targets.len();
+                let target_index = (0..len)
+                    .map(|offset| (self.index + offset) % len)
+                    .find(|&i| predicate(&self.targets[i]))?;
+                self.index = (target_index + 1) % len;
+
+                Some(self.targets[target_index].clone())
+            }
+            LoadBalancerMode::Random => {
+                let candidates: Vec<&String> =
+                    self.targets.iter().filter(|t| predicate(t)).collect();
+
+                if candidates.is_empty() {
+                    None
+                } else {
+                    candidates.choose(&mut self.lcg).map(|t| (*t).clone())
+                }
+            }
+        }
+    }
 }
 
 /// Handles proxy requests.
@@ -49,6 +110,8 @@ pub fn proxy_handler(
     state: Arc<AppState>,
     load_balancer: &EqMutex<LoadBalancer>,
     matches: &str,
+    rewrite: &RewriteConfig,
+    retry: &RetryConfig,
 ) -> Response {
     let mut simplified_uri = request.uri.clone();
 
@@ -79,28 +142,222 @@ pub fn proxy_handler(
             .with_header(HeaderType::ContentType, "text/html")
             .with_bytes(b"<h1>403 Forbidden</h1>")
     } else {
-        // Gets a load balancer target using the thread-safe `Mutex`
-        let mut load_balancer_lock = load_balancer.lock().unwrap();
-        let target = load_balancer_lock.select_target();
-        drop(load_balancer_lock);
-
         let mut proxied_request = request.clone();
         proxied_request.uri = simplified_uri;
+        rewrite_request(&mut proxied_request, rewrite);
+
+        let (mut response, target, attempts) =
+            proxy_with_retries(&proxied_request, load_balancer, retry);
+
+        if rewrite.response_location {
+            rewrite_location(&mut response, &request, &target, rewrite);
+        }
 
-        let target_sock = target.to_socket_addrs().unwrap().next().unwrap();
-        let response = proxy_request(&proxied_request, target_sock, Duration::from_secs(5));
         let status: u16 = response.status_code.into();
         let status_string: &str = response.status_code.into();
 
+        // Retries and failures are described at the end of the line, e.g. "(attempts: a connection
+        //   failed, b ok)"
+        let attempts_string = if attempts.len() > 1 || attempts[0].1.is_some() {
+            let attempts: Vec<String> = attempts
+                .iter()
+                .map(|(target, outcome)| {
+                    format!("{} {}", target, outcome.as_deref().unwrap_or("ok"))
+                })
+                .collect();
+
+            format!(" (attempts: {})", attempts.join(", "))
+        } else {
+            String::new()
+        };
+
         state.logger.info(format!(
-            "{}: {} {} {}",
-            request.address, status, status_string, request.uri
+            "{}: {} {} {}{}",
+            request.address, status, status_string, request.uri, attempts_string
         ));
 
+        if !response.trailers.is_empty() {
+            let trailers: Vec<String> = response
+                .trailers
+                .iter()
+                .map(|trailer| format!("{}: {}", trailer.name.to_string(), trailer.value))
+                .collect();
+
+            state.logger.debug(format!(
+                "{}: Proxied response trailers {}",
+                request.address,
+                trailers.join(", ")
+            ));
+        }
+
         response
     }
 }
 
+/// Proxies the request to a target selected by the load balancer, retrying according to the retry
+///   policy if the target cannot be reached.
+///
+/// Returns the response, the target it came from, and each target which was attempted along with a
+///   description of why the attempt failed, if it did.
+fn proxy_with_retries(
+    request: &Request,
+    load_balancer: &EqMutex<LoadBalancer>,
+    retry: &RetryConfig,
+) -> (Response, String, Vec<(String, Option<String>)>) {
+    let mut attempts: Vec<(String, Option<String>)> = Vec::with_capacity(retry.attempts);
+    let mut tried: Vec<String> = Vec::with_capacity(retry.attempts);
+
+    loop {
+        if !tried.is_empty() {
+            sleep(retry.delay);
+        }
+
+        // Gets a load balancer target using the thread-safe `Mutex`
+        let mut load_balancer_lock = load_balancer.lock().unwrap();
+        let target = load_balancer_lock.select_available_target(&tried);
+        let unavailable = load_balancer_lock.is_unavailable(&target);
+        drop(load_balancer_lock);
+
+        // An unavailable target is only selected if every target is unavailable, in which case no
+        //   connection is attempted unless this request is the one retrying it
+        if unavailable && !tried.contains(&target) {
+            attempts.push((target.clone(), Some("marked unavailable".into())));
+            return (Response::from(ProxyError::Connect), target, attempts);
+        }
+
+        let result = target
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addrs| addrs.next())
+            .ok_or(ProxyError::Connect)
+            .and_then(|addr| try_proxy_request(request, addr, Duration::from_secs(5)));
+
+        let error = match result {
+            Ok(response) => {
+                attempts.push((target.clone(), None));
+                return (response, target, attempts);
+            }
+            Err(error) => error,
+        };
+
+        if let (Some(ttl), ProxyError::Connect | ProxyError::Timeout) =
+            (retry.unavailable_ttl, error)
+        {
+            load_balancer.lock().unwrap().mark_unavailable(&target, ttl);
+        }
+
+        // Requests are only retried if the upstream never received them, or if it did but they are
+        //   safe to repeat
+        let retryable = match error {
+            ProxyError::Connect => true,
+            ProxyError::Reset => retry.methods.contains(&request.method),
+            ProxyError::Timeout | ProxyError::Response => false,
+        };
+
+        tried.push(target.clone());
+
+        if retryable && tried.len() < retry.attempts {
+            attempts.push((target, Some(error.to_string())));
+        } else {
+            let outcome = if !retryable && error == ProxyError::Reset && retry.attempts > 1 {
+                format!("{}, {} not retried", error, request.method)
+            } else {
+                error.to_string()
+            };
+
+            attempts.push((target.clone(), Some(outcome)));
+            return (Response::from(error), target, attempts);
+        }
+    }
+}
+
+/// Applies the rewrite rules to a request which is about to be proxied.
+fn rewrite_request(request: &mut Request, rewrite: &RewriteConfig) {
+    if let Some((prefix, replacement)) = &rewrite.path_prefix {
+        if let Some(uri) = rewrite_path_prefix(&request.uri, prefix, replacement) {
+            request.uri = uri;
+        }
+    }
+
+    if let Some(host) = &rewrite.upstream_host {
+        request.headers.remove(HeaderType::Host);
+        request.headers.add(HeaderType::Host, host);
+    }
+}
+
+/// Rewrites a `Location` header which points to the upstream so that it points to the public origin
+///   the client used instead, reversing any path prefix rewrite.
+///
+/// If the client did not send a `Host` header, the location is made relative.
+fn rewrite_location(
+    response: &mut Response,
+    request: &Request,
+    target: &str,
+    rewrite: &RewriteConfig,
+) {
+    let public_origin = request
+        .headers
+        .get(HeaderType::Host)
+        .map(|host| {
+            let scheme = if request.secure { "https" } else { "http" };
+            format!("{}://{}", scheme, host)
+        })
+        .unwrap_or_default();
+
+    let location = match response.headers.get_mut(HeaderType::Location) {
+        Some(location) => location,
+        None => return,
+    };
+
+    let upstream_origins = [Some(target), rewrite.upstream_host.as_deref()];
+    let path = upstream_origins
+        .iter()
+        .flatten()
+        .flat_map(|origin| [format!("http://{}", origin), format!("https://{}", origin)])
+        .find_map(|origin| {
+            location
+                .strip_prefix(&origin)
+                .filter(|rest| rest.is_empty() || rest.starts_with(['/', '?', '#']))
+        })
+        .or_else(|| Some(location.as_str()).filter(|location| location.starts_with('/')));
+
+    if let Some(path) = path {
+        let mut path = if path.starts_with('/') {
+            path.to_string()
+        } else {
+            format!("/{}", path)
+        };
+
+        if let Some((prefix, replacement)) = &rewrite.path_prefix {
+            if let Some(original) = rewrite_path_prefix(&path, replacement, prefix) {
+                path = original;
+            }
+        }
+
+        *location = format!("{}{}", public_origin, path);
+    }
+}
+
+/// Replaces the prefix of a path with the replacement, returning `None` if the path does not start
+///   with the prefix.
+///
+/// Prefixes only match whole path segments, so `/api` matches `/api` and `/api/users` but not
+///   `/apiary`. The result always starts with a slash.
+pub fn rewrite_path_prefix(path: &str, prefix: &str, replacement: &str) -> Option<String> {
+    let rest = path
+        .strip_prefix(prefix.trim_end_matches('/'))
+        .filter(|rest| rest.is_empty() || rest.starts_with(['/', '?', '#']))?;
+
+    let mut rewritten = replacement.trim_end_matches('/').to_string();
+    rewritten.push_str(rest);
+
+    if !rewritten.starts_with('/') {
+        rewritten.insert(0, '/');
+    }
+
+    Some(rewritten)
+}
+
 /// A `Mutex` which implements `PartialEq` for testing.
 #[derive(Debug)]
 pub struct EqMutex<T> {
@@ -109,7 +366,7 @@ pub struct EqMutex<T> {
 
 impl<T> EqMutex<T> {
     /// Locks the mutex.
-    pub fn lock(&self) -> Result<MutexGuard<T>, PoisonError<MutexGuard<T>>> {
+    pub fn lock(&self) -> Result<MutexGuard<'_, T>, PoisonError<MutexGuard<'_, T>>> {
         self.mutex.lock()
     }
 
@@ -1,8 +1,17 @@
-//! Provides a simple LCG implementation for generating random numbers.
+//! Provides random number generation.
+//!
+//! Two sources of randomness are provided. `SecureRandom` reads from the operating system's
+//!   cryptographically secure generator, and must be used for anything security-sensitive such as
+//!   tokens and identifiers. `Lcg` is a fast, seedable generator which is suitable for load balancing
+//!   and jitter, and can be seeded in tests for reproducible results, but is entirely predictable
+//!   and must never be used to generate secrets.
 
+use std::io;
 use std::time::SystemTime;
 
 /// Represents a linear congruential generator, used to generate random `u32` numbers.
+///
+/// This is **not** cryptographically secure. Use `SecureRandom` for security-sensitive values.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Lcg {
     modulus: usize,
@@ -11,28 +20,52 @@ pub struct Lcg {
     seed: usize,
 }
 
+/// Represents a cryptographically secure source of random bytes, provided by the operating system.
+///
+/// On Unix-like systems this reads from `/dev/urandom`, and on Windows it uses `BCryptGenRandom`.
+///   On other platforms, generating random bytes returns an error.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SecureRandom;
+
+/// Represents a fast source of random numbers which is not necessarily cryptographically secure.
+pub trait Rng {
+    /// Returns the next random number.
+    fn next_u32(&mut self) -> u32;
+}
+
 /// Allows random sampling on an object.
 pub trait Choose {
     /// The type of the object.
     type Item;
 
     /// Selects a random item from the collection.
-    fn choose(&self, lcg: &mut Lcg) -> Option<&Self::Item>;
+    fn choose<R: Rng>(&self, rng: &mut R) -> Option<&Self::Item>;
 }
 
 impl Lcg {
-    /// Creates a new linear congruential generator with default parameters.
+    /// Creates a new linear congruential generator with default parameters, seeded from the time.
     /// Default parameters are taken from [glibc](https://sourceware.org/git/?p=glibc.git;a=blob;f=stdlib/random_r.c;hb=glibc-2.26#l362).
     /// Modulus has been reduced by one so as to not be a direct power of two, reducing patterns.
     pub fn new() -> Self {
-        Lcg {
-            modulus: 2_usize.pow(31) - 1,
-            multiplier: 1103515245,
-            increment: 12345,
-            seed: SystemTime::now()
+        Self::seeded(
+            SystemTime::now()
                 .duration_since(SystemTime::UNIX_EPOCH)
                 .unwrap()
                 .as_secs() as usize,
+        )
+    }
+
+    /// Creates a new linear congruential generator with default parameters and the given seed.
+    ///
+    /// Generators with the same seed always produce the same sequence, which is useful in tests.
+    pub fn seeded(seed: usize) -> Self {
+        let modulus = 2_usize.pow(31) - 1;
+
+        Lcg {
+            modulus,
+            multiplier: 1103515245,
+            increment: 12345,
+            seed: seed % modulus,
         }
     }
 
@@ -70,11 +103,99 @@ impl Iterator for Lcg {
     }
 }
 
+impl Rng for Lcg {
+    fn next_u32(&mut self) -> u32 {
+        self.next().unwrap()
+    }
+}
+
+impl SecureRandom {
+    /// Fills the buffer with cryptographically secure random bytes.
+    pub fn fill(&self, buf: &mut [u8]) -> Result<(), io::Error> {
+        os::fill(buf)
+    }
+
+    /// Generates a random token of the given number of bytes, encoded as lowercase hexadecimal.
+    ///
+    /// The resulting string is twice as long as the number of bytes of entropy.
+    pub fn token(&self, bytes: usize) -> Result<String, io::Error> {
+        let mut buf = vec![0; bytes];
+        self.fill(&mut buf)?;
+
+        Ok(buf
+            .iter()
+            .fold(String::with_capacity(bytes * 2), |mut acc, &b| {
+                acc.push_str(&format!("{:02x}", b));
+                acc
+            }))
+    }
+}
+
 impl<T> Choose for [T] {
     type Item = T;
 
-    fn choose(&self, lcg: &mut Lcg) -> Option<&Self::Item> {
-        let value = lcg.next().unwrap();
+    fn choose<R: Rng>(&self, rng: &mut R) -> Option<&Self::Item> {
+        let value = rng.next_u32();
         self.get((value % self.len() as u32) as usize)
     }
 }
+
+#[cfg(unix)]
+mod os {
+    use std::fs::File;
+    use std::io::{self, Read};
+
+    pub fn fill(buf: &mut [u8]) -> Result<(), io::Error> {
+        File::open("/dev/urandom")?.read_exact(buf)
+    }
+}
+
+#[cfg(windows)]
+mod os {
+    use std::ffi::c_void;
+    use std::io;
+
+    const BCRYPT_USE_SYSTEM_PREFERRED_RNG: u32 = 0x00000002;
+
+    #[link(name = "bcrypt")]
+    extern "system" {
+        fn BCryptGenRandom(algorithm: *mut c_void, buffer: *mut u8, length: u32, flags: u32)
+            -> i32;
+    }
+
+    pub fn fill(buf: &mut [u8]) -> Result<(), io::Error> {
+        for chunk in buf.chunks_mut(u32::MAX as usize) {
+            // The buffer is valid for writes of `chunk.len()` bytes, and a null algorithm
+            //   handle is permitted when using the system-preferred generator.
+            let status = unsafe {
+                BCryptGenRandom(
+                    std::ptr::null_mut(),
+                    chunk.as_mut_ptr(),
+                    chunk.len() as u32,
+                    BCRYPT_USE_SYSTEM_PREFERRED_RNG,
+                )
+            };
+
+            if status != 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("BCryptGenRandom failed with status {:#x}", status),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod os {
+    use std::io;
+
+    pub fn fill(_: &mut [u8]) -> Result<(), io::Error> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "No secure random number generator is available on this platform",
+        ))
+    }
+}
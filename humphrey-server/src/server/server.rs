@@ -1,5 +1,6 @@
 //! Provides the core server functionality and manages the underlying Humphrey app.
 
+use humphrey::http::mime::MimeType;
 use humphrey::http::{Request, Response, StatusCode};
 use humphrey::monitor::event::ToEventMask;
 use humphrey::monitor::MonitorConfig;
@@ -10,13 +11,15 @@ use humphrey::{App, SubApp};
 use crate::plugins::manager::PluginManager;
 #[cfg(feature = "plugins")]
 use crate::plugins::plugin::PluginLoadResult;
-#[cfg(feature = "plugins")]
+#[cfg(any(feature = "plugins", feature = "tls"))]
 use std::process::exit;
-use std::thread::spawn;
+use std::thread::Builder;
 
+use crate::body_log::{body_logging_enabled, log_request_body, log_response_body};
 use crate::cache::Cache;
 use crate::config::{BlacklistMode, Config, ConfigSource, HostConfig, RouteType};
 use crate::logger::{monitor_thread, Logger};
+use crate::normalize::normalize_request;
 use crate::proxy::proxy_handler;
 use crate::r#static::{directory_handler, file_handler, redirect_handler};
 
@@ -56,20 +59,40 @@ impl From<Config> for AppState {
 
 /// Main function for the static server.
 pub fn main(config: Config) {
+    for (extension, mime_type) in &config.mime_types {
+        MimeType::register(extension, mime_type);
+    }
+
     let connection_timeout = config.connection_timeout;
+    let websocket_limits = config.websocket_limits;
+    let method_override = config.method_override.clone();
     let source = config.source;
 
     let (monitor_tx, monitor_rx) = channel();
     let mask = config.logging.level.to_event_mask();
+    let mut monitor = MonitorConfig::new(monitor_tx).with_subscription_to(mask);
+
+    if let Some(threshold) = config.logging.slow_request_threshold {
+        monitor = monitor.with_slow_request_threshold(threshold);
+    }
 
     let mut app: App<AppState> = App::new_with_config(config.threads, AppState::from(config))
         .with_connection_condition(verify_connection)
+        .with_request_rewriter(normalize_request)
         .with_connection_timeout(connection_timeout)
-        .with_monitor(MonitorConfig::new(monitor_tx).with_subscription_to(mask));
+        .with_websocket_limits(websocket_limits.global, websocket_limits.per_ip)
+        .with_monitor(monitor);
+
+    if let Some(method_override) = method_override {
+        app = app.with_method_override(method_override);
+    }
 
     let state = app.get_state();
     let monitor_state = app.get_state();
-    spawn(move || monitor_thread(monitor_rx, monitor_state));
+    Builder::new()
+        .name("humphrey-monitor".into())
+        .spawn(move || monitor_thread(monitor_rx, monitor_state))
+        .expect("Thread could not be spawned");
 
     let top_level_routes = init_app_routes(&state.config.default_host, 0);
 
@@ -81,9 +104,15 @@ pub fn main(config: Config) {
 
     #[cfg(feature = "tls")]
     if let Some(tls_config) = &state.config.tls_config {
-        app = app
-            .with_cert(&tls_config.cert_file, &tls_config.key_file)
-            .with_forced_https(tls_config.force);
+        app = match app.try_with_cert(&tls_config.cert_file, &tls_config.key_file) {
+            Ok(app) => app.with_forced_https(tls_config.force),
+            Err(e) => {
+                state
+                    .logger
+                    .error(format!("Could not load the TLS certificate: {}", e));
+                exit(1);
+            }
+        };
 
         if state.config.port != 443 {
             state.logger.warn(format!(
@@ -143,7 +172,7 @@ fn init_app_routes(host: &HostConfig, host_index: usize) -> SubApp<AppState> {
 
     for (route_index, route) in host.routes.iter().enumerate() {
         subapp = subapp.with_route(&route.matches, move |request, state| {
-            request_handler(request, state, host_index, route_index)
+            logged_request_handler(request, state, host_index, route_index)
         });
 
         if route.websocket_proxy.is_some() {
@@ -176,6 +205,29 @@ fn verify_connection(stream: &mut TcpStream, state: Arc<AppState>) -> bool {
     true
 }
 
+/// Handles the request, logging the bodies of the request and response if the route opts in.
+fn logged_request_handler(
+    request: Request,
+    state: Arc<AppState>,
+    host: usize,
+    route: usize,
+) -> Response {
+    let route_config = state.config.get_route(host, route);
+
+    if !body_logging_enabled(&state, route_config.log_bodies) {
+        return request_handler(request, state, host, route);
+    }
+
+    log_request_body(&request, &state);
+
+    let (address, uri) = (request.address.clone(), request.uri.clone());
+    let response = request_handler(request, state.clone(), host, route);
+
+    log_response_body(&address, &uri, &response, &state);
+
+    response
+}
+
 #[cfg(feature = "plugins")]
 fn request_handler(
     mut request: Request,
@@ -210,19 +262,15 @@ fn inner_request_handler(
     let route = state.config.get_route(host, route);
 
     match route.route_type {
-        RouteType::File => file_handler(request, state.clone(), route.path.as_ref().unwrap(), host),
-        RouteType::Directory => directory_handler(
-            request,
-            state.clone(),
-            route.path.as_ref().unwrap(),
-            &route.matches,
-            host,
-        ),
+        RouteType::File => file_handler(request, state.clone(), route, host),
+        RouteType::Directory => directory_handler(request, state.clone(), route, host),
         RouteType::Proxy => proxy_handler(
             request,
             state.clone(),
             route.load_balancer.as_ref().unwrap(),
             &route.matches,
+            &route.rewrite,
+            &route.retry,
         ),
         RouteType::Redirect => {
             redirect_handler(request, state.clone(), route.path.as_ref().unwrap())
@@ -1,30 +1,49 @@
 //! Provides functionality for serving static content.
 
+use crate::config::RouteConfig;
+use crate::server::cache::CachedFile;
 use crate::server::server::AppState;
 
+use humphrey::file::FileVersion;
+use humphrey::handlers::{serve_dir_with_hooks, serve_file_with_hooks, DirOptions, FileHooks};
 use humphrey::http::headers::HeaderType;
 use humphrey::http::mime::MimeType;
 use humphrey::http::{Request, Response, StatusCode};
-use humphrey::route::{try_find_path, LocatedPath};
 
-use std::fs::File;
-use std::io::Read;
-use std::path::PathBuf;
+use std::path::Path;
 use std::sync::Arc;
 
-const INDEX_FILES: [&str; 2] = ["index.html", "index.htm"];
+/// Returns the options with which files are served for the route, which send them exactly as they
+///   are stored.
+fn options(route: &RouteConfig) -> DirOptions {
+    DirOptions {
+        charset: false,
+        strip_bom: false,
+        stream_threshold: None,
+        nosniff: route.nosniff,
+        untrusted_uploads: route.untrusted_uploads,
+    }
+}
 
 /// Request handler for files.
-pub fn file_handler(request: Request, state: Arc<AppState>, file: &str, host: usize) -> Response {
+pub fn file_handler(
+    request: Request,
+    state: Arc<AppState>,
+    route: &RouteConfig,
+    host: usize,
+) -> Response {
     if let Some(response) = blacklist_check(&request, state.clone()) {
         return response;
     }
 
-    if let Some(response) = cache_check(&request, state.clone(), host) {
-        return response;
-    }
+    let file = route.path.as_ref().unwrap();
+    let hooks = ServerHooks {
+        state: &state,
+        route,
+        host,
+    };
 
-    inner_file_handler(request, state, file.into(), host)
+    serve_file_with_hooks(&request, Path::new(file), options(route), &hooks)
 }
 
 /// Request handler for directories.
@@ -32,47 +51,21 @@ pub fn file_handler(request: Request, state: Arc<AppState>, file: &str, host: us
 pub fn directory_handler(
     request: Request,
     state: Arc<AppState>,
-    directory: &str,
-    matches: &str,
+    route: &RouteConfig,
     host: usize,
 ) -> Response {
     if let Some(response) = blacklist_check(&request, state.clone()) {
         return response;
     }
 
-    if let Some(response) = cache_check(&request, state.clone(), host) {
-        return response;
-    }
+    let directory = route.path.as_ref().unwrap();
+    let hooks = ServerHooks {
+        state: &state,
+        route,
+        host,
+    };
 
-    let mut simplified_uri = request.uri.clone();
-
-    for ch in matches.chars() {
-        if ch != '*' {
-            simplified_uri.remove(0);
-        } else {
-            break;
-        }
-    }
-
-    if let Some(located) = try_find_path(directory, &simplified_uri, &INDEX_FILES) {
-        match located {
-            LocatedPath::Directory => {
-                state.logger.info(format!(
-                    "{}: 301 Moved Permanently {}",
-                    request.address, request.uri
-                ));
-                Response::empty(StatusCode::MovedPermanently)
-                    .with_header(HeaderType::Location, format!("{}/", &request.uri))
-            }
-            LocatedPath::File(path) => inner_file_handler(request, state, path, host),
-        }
-    } else {
-        state.logger.warn(format!(
-            "{}: 404 Not Found {}",
-            request.address, request.uri
-        ));
-        not_found()
-    }
+    serve_dir_with_hooks(&request, directory, &route.matches, options(route), &hooks)
 }
 
 /// Request handler for redirects.
@@ -88,36 +81,129 @@ pub fn redirect_handler(request: Request, state: Arc<AppState>, target: &str) ->
     Response::empty(StatusCode::MovedPermanently).with_header(HeaderType::Location, target)
 }
 
-fn inner_file_handler(
-    request: Request,
-    state: Arc<AppState>,
-    path: PathBuf,
+/// Connects the library's file handlers to the server's cache, logger and error pages.
+struct ServerHooks<'a> {
+    state: &'a AppState,
+    route: &'a RouteConfig,
     host: usize,
-) -> Response {
-    let file_extension = path.extension().map(|s| s.to_str().unwrap()).unwrap_or("");
-
-    let mime_type = MimeType::from_extension(file_extension);
-    let mut contents: Vec<u8> = Vec::new();
-
-    let mut file = File::open(path).unwrap();
-    file.read_to_end(&mut contents).unwrap();
-
-    if state.config.cache.size_limit >= contents.len() {
-        let mut cache = state.cache.write().unwrap();
-        cache.set(&request.uri, host, contents.clone(), mime_type);
-        state.logger.debug(format!("Cached route {}", request.uri));
-    } else if state.config.cache.size_limit > 0 {
-        state
-            .logger
-            .warn(format!("Couldn't cache, cache too small {}", request.uri));
+}
+
+impl ServerHooks<'_> {
+    /// Returns whether files served by the route are cached.
+    fn caching(&self) -> bool {
+        self.state.config.cache.size_limit > 0 && self.route.cache.enabled
+    }
+}
+
+impl FileHooks for ServerHooks<'_> {
+    fn lookup(&self, request: &Request) -> Option<Response> {
+        if !self.caching() {
+            return None;
+        }
+
+        let cache = self.state.cache.read().unwrap();
+        let cached = cache.get(&request.uri, self.host);
+
+        // Items whose files have changed on disk are read again, so deploys take effect straight away
+        if cached.is_some_and(|cached| !cached.is_current()) {
+            cache.record_lookup(&self.route.matches, false);
+            drop(cache);
+
+            if self
+                .state
+                .cache
+                .write()
+                .unwrap()
+                .invalidate(&request.uri, self.host)
+            {
+                self.state.logger.debug(format!(
+                    "Invalidated cached route {}, its file changed",
+                    request.uri
+                ));
+            }
+
+            return None;
+        }
+
+        cache.record_lookup(&self.route.matches, cached.is_some());
+
+        cached.map(|cached| {
+            self.state.logger.info(format!(
+                "{}: 200 OK (cached) {}",
+                request.address, request.uri
+            ));
+
+            let response = Response::empty(StatusCode::OK).with_bytes(cached.data.clone());
+
+            // Items read from files are sent with the same headers as when they are read from disk
+            match &cached.file {
+                Some(file) => options(self.route).with_file_headers(&file.path, response),
+                None => response.with_header(HeaderType::ContentType, cached.mime_type.to_string()),
+            }
+        })
     }
 
-    state
-        .logger
-        .info(format!("{}: 200 OK {}", request.address, request.uri));
-    Response::empty(StatusCode::OK)
-        .with_header(HeaderType::ContentType, mime_type.to_string())
-        .with_bytes(contents)
+    fn loaded(&self, request: &Request, path: &Path, version: FileVersion, response: &Response) {
+        if !self.caching() {
+            return;
+        }
+
+        let file_extension = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+        let mime_type = MimeType::from_extension(file_extension);
+        let mut cache = self.state.cache.write().unwrap();
+
+        let file = CachedFile {
+            path: path.into(),
+            version,
+        };
+
+        if cache.set_from_file(
+            &request.uri,
+            self.host,
+            response.body.clone(),
+            mime_type,
+            &self.route.cache,
+            file,
+        ) {
+            self.state
+                .logger
+                .debug(format!("Cached route {}", request.uri));
+            self.state.logger.debug(cache.report());
+        } else {
+            self.state.logger.debug(format!(
+                "Couldn't cache, file exceeds cache rules {}",
+                request.uri
+            ));
+        }
+    }
+
+    fn missing(&self, request: &Request, path: &Path) {
+        self.state.logger.warn(format!(
+            "{}: File {} was not found for {}",
+            request.address,
+            path.display(),
+            request.uri
+        ));
+    }
+
+    fn served(&self, request: &Request, response: &Response) {
+        let message = format!(
+            "{}: {} {}",
+            request.address,
+            status_line(response.status_code),
+            request.uri
+        );
+
+        if u16::from(response.status_code) < 400 {
+            self.state.logger.info(message);
+        } else {
+            self.state.logger.warn(message);
+        }
+    }
+
+    fn error(&self, _: &Request, status_code: StatusCode) -> Response {
+        error_page(status_code)
+    }
 }
 
 fn blacklist_check(request: &Request, state: Arc<AppState>) -> Option<Response> {
@@ -142,29 +228,20 @@ fn blacklist_check(request: &Request, state: Arc<AppState>) -> Option<Response>
     None
 }
 
-fn cache_check(request: &Request, state: Arc<AppState>, host: usize) -> Option<Response> {
-    if state.config.cache.size_limit > 0 {
-        let cache = state.cache.read().unwrap();
-        if let Some(cached) = cache.get(&request.uri, host) {
-            state.logger.info(format!(
-                "{}: 200 OK (cached) {}",
-                request.address, request.uri
-            ));
-            return Some(
-                Response::empty(StatusCode::OK)
-                    .with_header(HeaderType::ContentType, cached.mime_type.to_string())
-                    .with_bytes(cached.data.clone()),
-            );
-        }
-        drop(cache);
-    }
-
-    None
-}
-
 /// Generates a 404 response.
 pub fn not_found() -> Response {
-    Response::empty(StatusCode::NotFound)
+    error_page(StatusCode::NotFound)
+}
+
+/// Generates an error page for the given status code.
+pub fn error_page(status_code: StatusCode) -> Response {
+    Response::empty(status_code)
         .with_header(HeaderType::ContentType, "text/html")
-        .with_bytes(b"<h1>404 Not Found</h1>")
+        .with_bytes(format!("<h1>{}</h1>", status_line(status_code)))
+}
+
+/// Formats the status code and its reason phrase, for example "404 Not Found".
+fn status_line(status_code: StatusCode) -> String {
+    let reason: &str = status_code.into();
+    format!("{} {}", u16::from(status_code), reason)
 }
@@ -0,0 +1,145 @@
+use humphrey::http::headers::Headers;
+use humphrey::http::{Request, Response, StatusCode};
+use humphrey::App;
+use humphrey_server::body_log::{
+    body_logging_enabled, format_body, format_headers, log_request_body, log_response_body,
+};
+use humphrey_server::config::{BodyLogConfig, Config, LoggingConfig};
+use humphrey_server::logger::LogLevel;
+use humphrey_server::AppState;
+
+use std::fs::read_to_string;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+
+/// Returns the bytes of a PNG upload of the given length, starting with the PNG signature.
+fn png(length: usize) -> Vec<u8> {
+    let mut bytes = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+    bytes.extend((0..length - bytes.len()).map(|i| (i % 251) as u8));
+    bytes
+}
+
+#[test]
+fn test_json_body() {
+    let body = b"{\"name\": \"humphrey\",\n  \"tags\": [\"web\", \"\xf0\x9f\x90\x98\"]}";
+
+    assert_eq!(
+        format_body(body, 1024),
+        "\"{\"name\": \"humphrey\",\\n  \"tags\": [\"web\", \"\u{1f418}\"]}\""
+    );
+    assert_eq!(format_body(b"", 1024), "(empty)");
+}
+
+#[test]
+fn test_truncated_body() {
+    let body = "line\r\n".repeat(1000);
+
+    assert_eq!(
+        format_body(body.as_bytes(), 12),
+        "\"line\\r\\nline\\r\\n\"... (6000 bytes)"
+    );
+
+    // Multi-byte characters cut off by the limit are dropped rather than treated as binary
+    assert_eq!(format_body("ééé".as_bytes(), 3), "\"é\"... (6 bytes)");
+}
+
+#[test]
+fn test_png_body() {
+    let upload = png(2048);
+    let formatted = format_body(&upload, 1024);
+
+    assert!(formatted.starts_with("binary, 2048 bytes, fnv1a "));
+    assert!(formatted.ends_with(", 89 50 4e 47 0d 0a 1a 0a 00 00 00 0d 49 48 44 52 ..."));
+
+    // The hash covers the whole body, not just the logged part
+    let mut modified = upload.clone();
+    modified[2000] ^= 1;
+    assert_ne!(formatted, format_body(&modified, 1024));
+    assert_eq!(formatted, format_body(&upload, 1024));
+
+    assert_eq!(
+        format_body(b"a\0", 1024),
+        "binary, 2 bytes, fnv1a 089be207b544f1e4, 61 00"
+    );
+}
+
+#[test]
+fn test_redacted_headers() {
+    let mut headers = Headers::new();
+    headers.add("Authorization", "Bearer secret");
+    headers.add("Content-Type", "image/png");
+    headers.add("cookie", "session=secret");
+
+    assert_eq!(
+        format_headers(&headers, &BodyLogConfig::default()),
+        "Authorization: [redacted], Cookie: [redacted], Content-Type: image/png"
+    );
+}
+
+#[test]
+fn test_png_upload_logged() {
+    let log_path = std::env::temp_dir()
+        .join(format!("humphrey-body-log-{}.log", std::process::id()))
+        .to_string_lossy()
+        .to_string();
+
+    let config = Config {
+        logging: LoggingConfig {
+            level: LogLevel::Debug,
+            console: false,
+            file: Some(log_path.clone()),
+            bodies: BodyLogConfig {
+                limit: 8,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let (shutdown_tx, shutdown_rx) = channel();
+
+    let app: App<AppState> = App::new_with_config(2, AppState::from(config))
+        .with_route("/*", |request: Request, state: Arc<AppState>| {
+            assert!(body_logging_enabled(&state, true));
+            assert!(!body_logging_enabled(&state, false));
+
+            log_request_body(&request, &state);
+            let response = Response::new(StatusCode::Created, "{\"stored\": true}");
+            log_response_body(&request.address, &request.uri, &response, &state);
+
+            response
+        })
+        .with_shutdown(shutdown_rx);
+
+    let addr = crate::tests::start_app(app);
+    let mut stream = TcpStream::connect(&addr).unwrap();
+
+    let upload = png(64);
+    let mut request = format!(
+        "POST /upload HTTP/1.1\r\nAuthorization: Bearer secret\r\nContent-Length: {}\r\nConnection: Close\r\n\r\n",
+        upload.len()
+    )
+    .into_bytes();
+    request.extend(&upload);
+    stream.write_all(&request).unwrap();
+    stream.read_to_end(&mut Vec::new()).unwrap();
+
+    let lines: Vec<String> = read_to_string(&log_path)
+        .unwrap()
+        .lines()
+        .map(|line| line.split_once(": ").unwrap().1.to_string())
+        .collect();
+
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with(
+        "Request POST /upload headers [Authorization: [redacted], Connection: Close, Content-Length: 64] body binary, 64 bytes, fnv1a "
+    ));
+    assert!(lines[0].ends_with(", 89 50 4e 47 0d 0a 1a 0a ..."));
+    assert!(lines[1].starts_with("Response 201 /upload headers ["));
+    assert!(lines[1].ends_with("body \"{\"stored\"... (16 bytes)"));
+
+    shutdown_tx.send(()).unwrap();
+}
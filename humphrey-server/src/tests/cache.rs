@@ -0,0 +1,119 @@
+use humphrey::http::mime::MimeType;
+use humphrey_server::config::config::{CacheConfig, CachePriority, Config, RouteCacheConfig};
+use humphrey_server::server::cache::{Cache, RouteStats};
+
+fn cache(size_limit: usize, max_file_size: Option<usize>, ceiling: usize) -> Cache {
+    let config = Config {
+        cache: CacheConfig {
+            size_limit,
+            time_limit: 60,
+            max_file_size,
+            ceiling,
+        },
+        ..Default::default()
+    };
+
+    Cache::from(&config)
+}
+
+fn pinned() -> RouteCacheConfig {
+    RouteCacheConfig {
+        priority: CachePriority::High,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_unpinned_eviction() {
+    let mut cache = cache(10, None, 10);
+    let rules = RouteCacheConfig::default();
+
+    assert!(cache.set("/a", 0, vec![0; 4], MimeType::TextHtml, &rules));
+    assert!(cache.set("/b", 0, vec![0; 4], MimeType::TextHtml, &rules));
+    assert!(cache.set("/c", 0, vec![0; 4], MimeType::TextHtml, &rules));
+
+    assert!(cache.get("/a", 0).is_none());
+    assert!(cache.get("/b", 0).is_some());
+    assert!(cache.get("/c", 0).is_some());
+    assert_eq!(cache.size(), 8);
+}
+
+#[test]
+fn test_pinned_eviction() {
+    let mut cache = cache(10, None, 16);
+    let rules = RouteCacheConfig::default();
+
+    assert!(cache.set("/pinned", 0, vec![0; 6], MimeType::TextHtml, &pinned()));
+    assert!(cache.set("/a", 0, vec![0; 4], MimeType::TextHtml, &rules));
+
+    // The unpinned item is evicted rather than the older pinned one
+    assert!(cache.set("/b", 0, vec![0; 4], MimeType::TextHtml, &rules));
+    assert!(cache.get("/pinned", 0).is_some());
+    assert!(cache.get("/a", 0).is_none());
+
+    // Unpinned items cannot use the space between the size limit and the ceiling
+    assert!(!cache.set("/c", 0, vec![0; 6], MimeType::TextHtml, &rules));
+
+    // Pinned items can, but the ceiling is still enforced
+    assert!(cache.set("/pinned2", 0, vec![0; 8], MimeType::TextHtml, &pinned()));
+    assert!(cache.get("/pinned", 0).is_some());
+    assert_eq!(cache.size(), 14);
+
+    assert!(cache.set("/pinned3", 0, vec![0; 8], MimeType::TextHtml, &pinned()));
+    assert!(cache.get("/pinned", 0).is_none());
+    assert!(cache.get("/pinned2", 0).is_some());
+    assert!(cache.size() <= 16);
+}
+
+#[test]
+fn test_file_size_rules() {
+    let mut cache = cache(100, Some(10), 100);
+
+    assert!(!cache.set(
+        "/large",
+        0,
+        vec![0; 20],
+        MimeType::TextHtml,
+        &RouteCacheConfig::default()
+    ));
+
+    assert!(cache.set(
+        "/large",
+        0,
+        vec![0; 20],
+        MimeType::TextHtml,
+        &RouteCacheConfig {
+            max_file_size: Some(50),
+            ..Default::default()
+        }
+    ));
+
+    assert!(!cache.set(
+        "/disabled",
+        0,
+        vec![0; 1],
+        MimeType::TextHtml,
+        &RouteCacheConfig {
+            enabled: false,
+            ..Default::default()
+        }
+    ));
+
+    assert_eq!(cache.largest_items(1)[0].route, "/large");
+}
+
+#[test]
+fn test_route_stats() {
+    let cache = cache(100, None, 100);
+
+    cache.record_lookup("/static/*", false);
+    cache.record_lookup("/static/*", true);
+    cache.record_lookup("/static/*", true);
+    cache.record_lookup("/static/*", true);
+
+    let stats = cache.route_stats()["/static/*"];
+
+    assert_eq!(stats, RouteStats { hits: 3, misses: 1 });
+    assert_eq!(stats.hit_ratio(), 0.75);
+    assert!(cache.report().contains("/static/* 75% of 4"));
+}
@@ -1,20 +1,32 @@
 #![allow(unused_imports)]
+use super::extended_hashmap::{BOOLEANS, DURATIONS, SIZES};
 use super::tree::CONF;
 use humphrey_server::config::config::{
-    BlacklistConfig, BlacklistMode, CacheConfig, Config, ConfigSource, HostConfig,
-    LoadBalancerMode, LoggingConfig, RouteConfig, RouteType,
+    BlacklistConfig, BlacklistMode, BodyLogConfig, CacheConfig, CachePriority, Config,
+    ConfigSource, HostConfig, LoadBalancerMode, LoggingConfig, NormalizationConfig, RetryConfig,
+    RewriteConfig, RouteCacheConfig, RouteConfig, RouteType, WebsocketLimitsConfig,
 };
+use humphrey_server::config::extended_hashmap::ExtendedMap;
 use humphrey_server::config::tree::{parse_conf, ConfigNode};
-use humphrey_server::logger::LogLevel;
+use humphrey_server::logger::{LogFormat, LogLevel};
 
 #[cfg(feature = "plugins")]
 use humphrey_server::config::config::PluginConfig;
 use humphrey_server::proxy::{EqMutex, LoadBalancer};
 use humphrey_server::rand::Lcg;
 
+use humphrey::http::method::{Method, MethodOverride};
 use std::collections::HashMap;
 use std::time::Duration;
 
+/// Builds the map of settings which a configuration is expected to keep.
+pub fn settings(values: &[(&str, &str)]) -> HashMap<String, String> {
+    values
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
 #[test]
 fn test_parse_config() {
     let tree = parse_conf(CONF, "valid.conf").unwrap();
@@ -46,6 +58,12 @@ fn test_parse_config() {
                     path: Some("/var/www".into()),
                     load_balancer: None,
                     websocket_proxy: None,
+                    cache: RouteCacheConfig::default(),
+                    rewrite: RewriteConfig::default(),
+                    retry: RetryConfig::default(),
+                    log_bodies: false,
+                    nosniff: true,
+                    untrusted_uploads: false,
                 },
                 RouteConfig {
                     route_type: RouteType::Proxy,
@@ -56,8 +74,15 @@ fn test_parse_config() {
                         mode: LoadBalancerMode::RoundRobin,
                         index: 0,
                         lcg: Lcg::new(),
+                        unavailable: HashMap::new(),
                     })),
                     websocket_proxy: None,
+                    cache: RouteCacheConfig::default(),
+                    rewrite: RewriteConfig::default(),
+                    retry: RetryConfig::default(),
+                    log_bodies: false,
+                    nosniff: true,
+                    untrusted_uploads: false,
                 },
             ],
         },
@@ -66,27 +91,67 @@ fn test_parse_config() {
         plugins: vec![PluginConfig {
             name: "php".into(),
             library: "plugins/php/target/release/php.dll".into(),
+            log_level: None,
             config: expected_plugin_conf,
         }],
         logging: LoggingConfig {
             level: LogLevel::Info,
+            format: LogFormat::Text,
             console: true,
             file: Some("humphrey.log".into()),
+            slow_request_threshold: None,
+            bodies: BodyLogConfig::default(),
         },
         cache: CacheConfig {
             size_limit: 134217728,
             time_limit: 60,
+            max_file_size: None,
+            ceiling: 134217728,
         },
         blacklist: BlacklistConfig {
             list: Vec::new(),
             mode: BlacklistMode::Block,
         },
         connection_timeout: Some(Duration::from_secs(5)),
+        websocket_limits: WebsocketLimitsConfig::default(),
+        normalization: NormalizationConfig::default(),
+        method_override: None,
+        mime_types: Vec::new(),
+        settings: settings(&[
+            ("server.address", "0.0.0.0"),
+            ("server.port", "80"),
+            ("server.threads", "32"),
+            ("server.websocket", "localhost:1234"),
+            ("server.timeout", "5"),
+            ("server.blacklist.mode", "block"),
+            ("server.log.level", "info"),
+            ("server.log.console", "true"),
+            ("server.log.file", "humphrey.log"),
+            ("server.cache.size", "134217728"),
+            ("server.cache.time", "60"),
+        ]),
     };
 
     assert_eq!(conf, expected_conf);
 }
 
+#[test]
+fn test_slow_request_threshold() {
+    let tree = parse_conf(
+        include_str!("testcases/slow_request.conf"),
+        "slow_request.conf",
+    )
+    .unwrap();
+    let conf = Config::from_tree(tree).unwrap();
+
+    // A plain number is a number of milliseconds
+    assert_eq!(
+        conf.logging.slow_request_threshold,
+        Some(Duration::from_millis(500))
+    );
+    assert_eq!(conf.logging.level, LogLevel::Info);
+}
+
 #[test]
 fn test_host_config() {
     let tree = parse_conf(include_str!("testcases/hosts.conf"), "hosts.conf").unwrap();
@@ -108,6 +173,12 @@ fn test_host_config() {
                 path: Some("/var/www".into()),
                 load_balancer: None,
                 websocket_proxy: None,
+                cache: RouteCacheConfig::default(),
+                rewrite: RewriteConfig::default(),
+                retry: RetryConfig::default(),
+                log_bodies: false,
+                nosniff: true,
+                untrusted_uploads: false,
             }],
         },
         hosts: vec![
@@ -119,6 +190,12 @@ fn test_host_config() {
                     path: Some("/app/dev".into()),
                     load_balancer: None,
                     websocket_proxy: None,
+                    cache: RouteCacheConfig::default(),
+                    rewrite: RewriteConfig::default(),
+                    retry: RetryConfig::default(),
+                    log_bodies: false,
+                    nosniff: true,
+                    untrusted_uploads: false,
                 }],
             },
             HostConfig {
@@ -129,6 +206,12 @@ fn test_host_config() {
                     path: Some("/app/prod".into()),
                     load_balancer: None,
                     websocket_proxy: None,
+                    cache: RouteCacheConfig::default(),
+                    rewrite: RewriteConfig::default(),
+                    retry: RetryConfig::default(),
+                    log_bodies: false,
+                    nosniff: true,
+                    untrusted_uploads: false,
                 }],
             },
         ],
@@ -136,18 +219,32 @@ fn test_host_config() {
         plugins: Vec::new(),
         logging: LoggingConfig {
             level: LogLevel::Warn,
+            format: LogFormat::Text,
             console: true,
             file: None,
+            slow_request_threshold: None,
+            bodies: BodyLogConfig::default(),
         },
         cache: CacheConfig {
             size_limit: 0,
             time_limit: 0,
+            max_file_size: None,
+            ceiling: 0,
         },
         blacklist: BlacklistConfig {
             list: Vec::new(),
             mode: BlacklistMode::Block,
         },
         connection_timeout: None,
+        websocket_limits: WebsocketLimitsConfig::default(),
+        normalization: NormalizationConfig::default(),
+        method_override: None,
+        mime_types: Vec::new(),
+        settings: settings(&[
+            ("server.address", "0.0.0.0"),
+            ("server.port", "80"),
+            ("server.threads", "32"),
+        ]),
     };
 
     assert_eq!(conf, expected_conf);
@@ -175,6 +272,12 @@ fn comma_separated_routes() {
                     path: Some("/var/www".into()),
                     load_balancer: None,
                     websocket_proxy: None,
+                    cache: RouteCacheConfig::default(),
+                    rewrite: RewriteConfig::default(),
+                    retry: RetryConfig::default(),
+                    log_bodies: false,
+                    nosniff: true,
+                    untrusted_uploads: false,
                 },
                 RouteConfig {
                     route_type: RouteType::Directory,
@@ -182,6 +285,12 @@ fn comma_separated_routes() {
                     path: Some("/var/www".into()),
                     load_balancer: None,
                     websocket_proxy: None,
+                    cache: RouteCacheConfig::default(),
+                    rewrite: RewriteConfig::default(),
+                    retry: RetryConfig::default(),
+                    log_bodies: false,
+                    nosniff: true,
+                    untrusted_uploads: false,
                 },
             ],
         },
@@ -190,19 +299,572 @@ fn comma_separated_routes() {
         plugins: Vec::new(),
         logging: LoggingConfig {
             level: LogLevel::Warn,
+            format: LogFormat::Text,
             console: true,
             file: None,
+            slow_request_threshold: None,
+            bodies: BodyLogConfig::default(),
         },
         cache: CacheConfig {
             size_limit: 0,
             time_limit: 0,
+            max_file_size: None,
+            ceiling: 0,
         },
         blacklist: BlacklistConfig {
             list: Vec::new(),
             mode: BlacklistMode::Block,
         },
         connection_timeout: None,
+        websocket_limits: WebsocketLimitsConfig::default(),
+        normalization: NormalizationConfig::default(),
+        method_override: None,
+        mime_types: Vec::new(),
+        settings: settings(&[
+            ("server.address", "0.0.0.0"),
+            ("server.port", "80"),
+            ("server.threads", "32"),
+        ]),
     };
 
     assert_eq!(conf, expected_conf);
 }
+
+#[test]
+fn test_cache_rules() {
+    let tree = parse_conf(
+        include_str!("testcases/cache_rules.conf"),
+        "cache_rules.conf",
+    )
+    .unwrap();
+    let conf = Config::from_tree(tree).unwrap();
+
+    assert_eq!(
+        conf.cache,
+        CacheConfig {
+            size_limit: 65536,
+            time_limit: 60,
+            max_file_size: Some(16384),
+            ceiling: 131072,
+        }
+    );
+
+    let rules: Vec<RouteCacheConfig> = conf
+        .default_host
+        .routes
+        .iter()
+        .map(|route| route.cache)
+        .collect();
+
+    assert_eq!(
+        rules,
+        vec![
+            RouteCacheConfig {
+                enabled: false,
+                max_file_size: None,
+                priority: CachePriority::Normal,
+            },
+            RouteCacheConfig {
+                enabled: true,
+                max_file_size: Some(1048576),
+                priority: CachePriority::High,
+            },
+            RouteCacheConfig::default(),
+        ]
+    );
+}
+
+#[test]
+fn test_rewrite_rules() {
+    let tree = parse_conf(include_str!("testcases/rewrites.conf"), "rewrites.conf").unwrap();
+    let conf = Config::from_tree(tree).unwrap();
+
+    let rules: Vec<&RewriteConfig> = conf
+        .default_host
+        .routes
+        .iter()
+        .chain(conf.hosts[0].routes.iter())
+        .map(|route| &route.rewrite)
+        .collect();
+
+    assert_eq!(
+        rules,
+        vec![
+            &RewriteConfig {
+                path_prefix: Some(("/api".into(), "".into())),
+                upstream_host: Some("internal.service".into()),
+                response_location: false,
+            },
+            &RewriteConfig::default(),
+            &RewriteConfig {
+                path_prefix: Some(("/".into(), "/v1".into())),
+                upstream_host: Some("example.internal".into()),
+                response_location: true,
+            },
+            &RewriteConfig {
+                path_prefix: None,
+                upstream_host: Some("legacy.internal".into()),
+                response_location: false,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_invalid_rewrite_rules() {
+    let conf = r#"server {
+        route /static/* {
+            directory     "/var/www"
+            upstream_host "internal.service"
+        }
+
+        route /* {
+            proxy               "127.0.0.1:8000"
+            rewrite_path_prefix "/api"
+        }
+    }"#;
+
+    let errors = Config::parse(conf, "rewrites.conf").unwrap_err();
+    let messages: Vec<String> = errors.errors().iter().map(|e| e.to_string()).collect();
+
+    assert_eq!(
+        messages,
+        vec![
+            "Configuration error at rewrites.conf line 4: Rewrite rules can only be used on proxy routes",
+            "Configuration error at rewrites.conf line 9: Invalid path prefix rewrite, it takes the prefix to replace and its replacement, both in quotation marks",
+        ]
+    );
+}
+
+#[test]
+fn test_retry_policy() {
+    let conf = r#"server {
+        route /api/* {
+            proxy "127.0.0.1:8000,127.0.0.1:8001"
+
+            retry {
+                attempts        3
+                delay           50
+                methods         "GET, PUT"
+                unavailable_ttl 500
+            }
+        }
+
+        route /* {
+            proxy "127.0.0.1:8000"
+        }
+    }"#;
+
+    let conf = Config::parse(conf, "retry.conf").unwrap();
+
+    assert_eq!(
+        conf.default_host.routes[0].retry,
+        RetryConfig {
+            attempts: 3,
+            delay: Duration::from_millis(50),
+            methods: vec![Method::Get, Method::Put],
+            unavailable_ttl: Some(Duration::from_millis(500)),
+        }
+    );
+    assert_eq!(conf.default_host.routes[1].retry, RetryConfig::default());
+}
+
+#[test]
+fn test_file_options() {
+    let conf = r#"server {
+        route /uploads/* {
+            directory         "/var/uploads"
+            untrusted_uploads true
+        }
+
+        route /* {
+            directory "/var/www"
+            nosniff   false
+        }
+    }"#;
+
+    let conf = Config::parse(conf, "uploads.conf").unwrap();
+    let routes = &conf.default_host.routes;
+
+    assert!(routes[0].untrusted_uploads);
+    assert!(routes[0].nosniff);
+    assert!(!routes[1].untrusted_uploads);
+    assert!(!routes[1].nosniff);
+}
+
+#[test]
+fn test_invalid_file_options() {
+    let conf = r#"server {
+        route /api/* {
+            proxy             "127.0.0.1:8000"
+            untrusted_uploads true
+        }
+
+        route /* {
+            directory "/var/www"
+            nosniff   "sometimes"
+        }
+    }"#;
+
+    let errors = Config::parse(conf, "uploads.conf").unwrap_err();
+    let messages: Vec<String> = errors.errors().iter().map(|e| e.to_string()).collect();
+
+    assert_eq!(
+        messages,
+        vec![
+            "Configuration error at uploads.conf line 4: File options can only be used on file and directory routes",
+            "Configuration error at uploads.conf line 9: nosniff must be a boolean",
+        ]
+    );
+}
+
+#[test]
+fn test_invalid_retry_policy() {
+    let conf = r#"server {
+        route /static/* {
+            directory "/var/www"
+
+            retry {
+                attempts 3
+            }
+        }
+
+        route /* {
+            proxy "127.0.0.1:8000"
+
+            retry {
+                attempts 0
+                methods  "GET, GET/POST"
+            }
+        }
+    }"#;
+
+    let errors = Config::parse(conf, "retry.conf").unwrap_err();
+    let messages: Vec<String> = errors.errors().iter().map(|e| e.to_string()).collect();
+
+    assert_eq!(
+        messages,
+        vec![
+            "Configuration error at retry.conf line 6: Retry rules can only be used on proxy routes",
+            "Configuration error at retry.conf line 14: You cannot specify less than 1 attempt",
+            "Configuration error at retry.conf line 15: Invalid method",
+        ]
+    );
+}
+
+#[test]
+fn test_websocket_limits() {
+    let conf = r#"server {
+        websocket_limits {
+            global 1000
+            per_ip 8
+        }
+
+        route /* {
+            directory "/var/www"
+        }
+    }"#;
+
+    let conf = Config::parse(conf, "websocket_limits.conf").unwrap();
+
+    assert_eq!(
+        conf.websocket_limits,
+        WebsocketLimitsConfig {
+            global: Some(1000),
+            per_ip: Some(8),
+        }
+    );
+}
+
+#[test]
+fn test_invalid_websocket_limits() {
+    let conf = r#"server {
+        websocket_limits {
+            global "many"
+            per_ip 0
+        }
+
+        route /* {
+            directory "/var/www"
+        }
+    }"#;
+
+    let errors = Config::parse(conf, "websocket_limits.conf").unwrap_err();
+    let messages: Vec<String> = errors.errors().iter().map(|e| e.to_string()).collect();
+
+    assert_eq!(
+        messages,
+        vec![
+            "Configuration error at websocket_limits.conf line 3: Invalid global WebSocket connection limit",
+            "Configuration error at websocket_limits.conf line 4: The per-IP WebSocket connection limit must be at least 1",
+        ]
+    );
+}
+
+#[test]
+fn test_method_override() {
+    let parse = |lines: &[&str]| {
+        let conf = format!(
+            r#"server {{
+        method_override {{
+            {}
+        }}
+
+        route /* {{
+            directory "/var/www"
+        }}
+    }}"#,
+            lines.join("\n")
+        );
+
+        Config::parse(&conf, "method_override.conf")
+            .unwrap()
+            .method_override
+    };
+
+    assert_eq!(parse(&[r#"mode "off""#]), None);
+    assert_eq!(parse(&[r#"mode "header""#]), Some(MethodOverride::Header));
+    assert_eq!(
+        parse(&[r#"mode "form""#]),
+        Some(MethodOverride::FormField("_method".into()))
+    );
+    assert_eq!(
+        parse(&[r#"mode "form""#, r#"field "_verb""#]),
+        Some(MethodOverride::FormField("_verb".into()))
+    );
+}
+
+#[test]
+fn test_invalid_method_override() {
+    let conf = r#"server {
+        method_override {
+            mode "query"
+            field "_method"
+        }
+
+        route /* {
+            directory "/var/www"
+        }
+    }"#;
+
+    let errors = Config::parse(conf, "method_override.conf").unwrap_err();
+    let messages: Vec<String> = errors.errors().iter().map(|e| e.to_string()).collect();
+
+    assert_eq!(
+        messages,
+        vec![
+            "Configuration error at method_override.conf line 4: A method override field can only be used with the form mode",
+            "Configuration error at method_override.conf line 3: Invalid method override mode",
+        ]
+    );
+}
+
+#[test]
+fn test_mime_types() {
+    let conf = r#"server {
+        mime_types {
+            usdz "model/vnd.usdz+zip"
+            gltf "model/gltf+json"
+        }
+
+        route /* {
+            directory "/var/www"
+        }
+    }"#;
+
+    let conf = Config::parse(conf, "mime_types.conf").unwrap();
+
+    assert_eq!(
+        conf.mime_types,
+        vec![
+            ("gltf".to_string(), "model/gltf+json".to_string()),
+            ("usdz".to_string(), "model/vnd.usdz+zip".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_invalid_mime_types() {
+    let conf = r#"server {
+        mime_types {
+            usdz "usdz"
+        }
+
+        route /* {
+            directory "/var/www"
+        }
+    }"#;
+
+    let errors = Config::parse(conf, "mime_types.conf").unwrap_err();
+    let messages: Vec<String> = errors.errors().iter().map(|e| e.to_string()).collect();
+
+    assert_eq!(
+        messages,
+        vec!["Configuration error at mime_types.conf line 3: Invalid MIME type"]
+    );
+}
+
+#[test]
+fn test_typed_values() {
+    let parse = |conf: String| {
+        Config::parse(&conf, "typed.conf").map_err(|errors| errors.errors()[0].to_string())
+    };
+
+    // The server interprets values exactly as the getters available to plugins do
+    for &(value, duration) in DURATIONS {
+        let conf = parse(format!("server {{\n timeout \"{}\"\n}}", value));
+
+        match duration {
+            Some(duration) => assert_eq!(
+                conf.unwrap().connection_timeout,
+                Some(duration).filter(|duration| !duration.is_zero())
+            ),
+            None => assert_eq!(
+                conf.unwrap_err(),
+                "Configuration error at typed.conf line 2: Invalid connection timeout"
+            ),
+        }
+    }
+
+    for &(value, size) in SIZES {
+        let conf = parse(format!(
+            "server {{\n cache {{\n size \"{}\"\n }}\n}}",
+            value
+        ));
+
+        match size {
+            Some(size) => assert_eq!(conf.unwrap().cache.size_limit, size),
+            None => assert_eq!(
+                conf.unwrap_err(),
+                "Configuration error at typed.conf line 3: Invalid cache size"
+            ),
+        }
+    }
+
+    for &(value, boolean) in BOOLEANS {
+        let conf = parse(format!(
+            "server {{\n log {{\n console \"{}\"\n }}\n}}",
+            value
+        ));
+
+        match boolean {
+            Some(boolean) => assert_eq!(conf.unwrap().logging.console, boolean),
+            None => assert_eq!(
+                conf.unwrap_err(),
+                "Configuration error at typed.conf line 3: server.log.console must be a boolean"
+            ),
+        }
+    }
+}
+
+#[test]
+fn test_units() {
+    let conf = Config::parse(include_str!("testcases/units.conf"), "units.conf").unwrap();
+
+    assert_eq!(conf.connection_timeout, Some(Duration::from_secs(30)));
+    assert_eq!(conf.logging.format, LogFormat::Json);
+    assert!(!conf.logging.console);
+    assert_eq!(
+        conf.logging.slow_request_threshold,
+        Some(Duration::from_secs(2))
+    );
+    assert_eq!(conf.logging.bodies.limit, 16384);
+    assert_eq!(conf.cache.size_limit, 10485760);
+    assert_eq!(conf.cache.time_limit, 300);
+    assert_eq!(
+        conf.default_host.routes[0].retry.delay,
+        Duration::from_secs(1)
+    );
+
+    // Sizes are given in bytes, but other values are kept as they were written, so that their units
+    //   are interpreted by whoever reads them
+    assert_eq!(conf.settings["server.cache.size"], "10485760");
+    assert_eq!(conf.settings["server.cache.time"], "5m");
+    assert_eq!(conf.settings["server.log.console"], "no");
+}
+
+#[test]
+fn test_get_host() {
+    let tree = parse_conf(include_str!("testcases/hosts.conf"), "hosts.conf").unwrap();
+    let conf = Config::from_tree(tree).unwrap();
+
+    assert_eq!(conf.get_host(Some("localhost")).matches, "localhost");
+    assert_eq!(
+        conf.get_host(Some("www.example.com")).matches,
+        "*.example.com"
+    );
+    assert_eq!(conf.get_host(Some("example.org")).matches, "*");
+    assert_eq!(conf.get_host(None).matches, "*");
+}
+
+#[cfg(feature = "plugins")]
+#[test]
+fn test_plugin_log_level() {
+    let tree = parse_conf(
+        include_str!("testcases/plugin_log_level.conf"),
+        "plugin_log_level.conf",
+    )
+    .unwrap();
+    let conf = Config::from_tree(tree).unwrap();
+
+    let plugin = |name: &str| {
+        conf.plugins
+            .iter()
+            .find(|plugin| plugin.name == name)
+            .unwrap()
+    };
+
+    // The log level is not passed on to the plugin with the rest of its configuration
+    assert_eq!(plugin("php").log_level, Some(LogLevel::Debug));
+    assert!(plugin("php").config.is_empty());
+    assert_eq!(plugin("metrics").log_level, None);
+    assert_eq!(conf.logging.level, LogLevel::Warn);
+}
+
+#[cfg(feature = "plugins")]
+#[test]
+fn test_plugin_settings() {
+    let conf = r#"server {
+        port    8080
+        timeout 30s
+
+        log {
+            level "info"
+        }
+
+        plugins {
+            metrics {
+                library "metrics.so"
+                timeout 5s
+                labels  yes
+            }
+        }
+
+        route /* {
+            directory "/var/www"
+        }
+    }"#;
+
+    let conf = Config::parse(conf, "plugins.conf").unwrap();
+    let settings = conf.plugin_settings("metrics").unwrap();
+
+    // Keys in the plugin's block take precedence over the same keys in the server section
+    assert_eq!(settings["timeout"], "5s");
+    assert_eq!(settings["port"], "8080");
+    assert_eq!(settings["log.level"], "info");
+    assert_eq!(settings["labels"], "yes");
+    assert!(!settings.contains_key("library"));
+
+    assert_eq!(
+        settings.get_duration("timeout", Duration::from_secs(1), "Invalid timeout"),
+        Ok(Some(Duration::from_secs(5)))
+    );
+    assert_eq!(
+        settings.get_bool("labels", "Invalid labels"),
+        Ok(Some(true))
+    );
+
+    // The plugin's own configuration is unchanged
+    assert_eq!(conf.plugins[0].config.get("port"), None);
+    assert_eq!(conf.plugin_settings("missing"), None);
+}
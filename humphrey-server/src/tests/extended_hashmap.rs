@@ -0,0 +1,162 @@
+//! The accepted and rejected forms of values with units, which are shared by the tests of the
+//!   typed getters and of the server's own configuration so that both interpret them identically.
+
+use humphrey_server::config::extended_hashmap::ExtendedMap;
+use humphrey_server::config::tree::ConfigNode;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Durations and what they mean when a number without a unit is in seconds, or `None` if they are
+///   rejected.
+pub const DURATIONS: &[(&str, Option<Duration>)] = &[
+    ("30", Some(Duration::from_secs(30))),
+    ("0", Some(Duration::ZERO)),
+    ("30s", Some(Duration::from_secs(30))),
+    ("500ms", Some(Duration::from_millis(500))),
+    ("5m", Some(Duration::from_secs(300))),
+    ("2h", Some(Duration::from_secs(7200))),
+    ("1d", Some(Duration::from_secs(86400))),
+    ("", None),
+    ("s", None),
+    ("-5s", None),
+    ("1.5s", None),
+    ("30 s", None),
+    ("30S", None),
+    ("30sec", None),
+    ("1w", None),
+];
+
+/// Sizes and how many bytes they mean, or `None` if they are rejected.
+pub const SIZES: &[(&str, Option<usize>)] = &[
+    ("512", Some(512)),
+    ("0", Some(0)),
+    ("512B", Some(512)),
+    ("4K", Some(4096)),
+    ("4k", Some(4096)),
+    ("4KB", Some(4096)),
+    ("10M", Some(10485760)),
+    ("10mb", Some(10485760)),
+    ("1G", Some(1073741824)),
+    ("", None),
+    ("K", None),
+    ("-1K", None),
+    ("1.5M", None),
+    ("10 M", None),
+    ("10T", None),
+    ("10KiB", None),
+];
+
+/// Booleans and what they mean, or `None` if they are rejected.
+pub const BOOLEANS: &[(&str, Option<bool>)] = &[
+    ("true", Some(true)),
+    ("yes", Some(true)),
+    ("false", Some(false)),
+    ("no", Some(false)),
+    ("", None),
+    ("True", None),
+    ("YES", None),
+    ("1", None),
+    ("on", None),
+];
+
+const ERROR: &str = "Invalid value";
+
+fn string_map(value: &str) -> HashMap<String, String> {
+    HashMap::from([("key".to_string(), value.to_string())])
+}
+
+fn node_map(value: &str) -> HashMap<String, ConfigNode> {
+    HashMap::from([(
+        "key".to_string(),
+        ConfigNode::String("key".into(), value.into()),
+    )])
+}
+
+fn expected<T>(value: Option<T>) -> Result<Option<T>, &'static str> {
+    value.map(Some).ok_or(ERROR)
+}
+
+#[test]
+fn test_get_duration() {
+    let second = Duration::from_secs(1);
+
+    for &(value, duration) in DURATIONS {
+        assert_eq!(
+            string_map(value).get_duration("key", second, ERROR),
+            expected(duration),
+            "{:?}",
+            value
+        );
+        assert_eq!(
+            node_map(value).get_duration("key", second, ERROR),
+            expected(duration),
+            "{:?}",
+            value
+        );
+    }
+
+    // Numbers without a unit are in the given unit, and units override it
+    let millisecond = Duration::from_millis(1);
+    let map = string_map("250");
+    assert_eq!(
+        map.get_duration("key", millisecond, ERROR),
+        Ok(Some(Duration::from_millis(250)))
+    );
+    let map = string_map("2s");
+    assert_eq!(
+        map.get_duration("key", millisecond, ERROR),
+        Ok(Some(Duration::from_secs(2)))
+    );
+
+    assert_eq!(
+        HashMap::<String, String>::new().get_duration("key", second, ERROR),
+        Ok(None)
+    );
+}
+
+#[test]
+fn test_get_size() {
+    for &(value, size) in SIZES {
+        assert_eq!(
+            string_map(value).get_size("key", ERROR),
+            expected(size),
+            "{:?}",
+            value
+        );
+        assert_eq!(
+            node_map(value).get_size("key", ERROR),
+            expected(size),
+            "{:?}",
+            value
+        );
+    }
+
+    assert_eq!(
+        HashMap::<String, String>::new().get_size("key", ERROR),
+        Ok(None)
+    );
+}
+
+#[test]
+fn test_get_bool() {
+    for &(value, boolean) in BOOLEANS {
+        assert_eq!(
+            string_map(value).get_bool("key", ERROR),
+            expected(boolean),
+            "{:?}",
+            value
+        );
+        assert_eq!(
+            node_map(value).get_bool("key", ERROR),
+            expected(boolean),
+            "{:?}",
+            value
+        );
+    }
+
+    assert_eq!(
+        HashMap::<String, String>::new().get_bool("key", ERROR),
+        Ok(None)
+    );
+}
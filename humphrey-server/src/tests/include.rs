@@ -1,12 +1,15 @@
+use super::config::settings;
 use humphrey_server::config::tree::parse_conf;
 use humphrey_server::config::{
-    BlacklistConfig, BlacklistMode, CacheConfig, Config, ConfigSource, HostConfig,
-    LoadBalancerMode, LoggingConfig, RouteConfig, RouteType,
+    BlacklistConfig, BlacklistMode, BodyLogConfig, CacheConfig, Config, ConfigSource, HostConfig,
+    LoadBalancerMode, LoggingConfig, NormalizationConfig, RetryConfig, RewriteConfig,
+    RouteCacheConfig, RouteConfig, RouteType, WebsocketLimitsConfig,
 };
-use humphrey_server::logger::LogLevel;
+use humphrey_server::logger::{LogFormat, LogLevel};
 use humphrey_server::proxy::{EqMutex, LoadBalancer};
 use humphrey_server::rand::Lcg;
 
+use std::collections::HashMap;
 use std::env::set_current_dir;
 use std::path::Path;
 
@@ -35,6 +38,12 @@ fn include_route() {
                 path: Some("/var/www".into()),
                 load_balancer: None,
                 websocket_proxy: None,
+                cache: RouteCacheConfig::default(),
+                rewrite: RewriteConfig::default(),
+                retry: RetryConfig::default(),
+                log_bodies: false,
+                nosniff: true,
+                untrusted_uploads: false,
             }],
         },
         hosts: Vec::new(),
@@ -42,18 +51,32 @@ fn include_route() {
         plugins: Vec::new(),
         logging: LoggingConfig {
             level: LogLevel::Warn,
+            format: LogFormat::Text,
             console: true,
             file: None,
+            slow_request_threshold: None,
+            bodies: BodyLogConfig::default(),
         },
         cache: CacheConfig {
             size_limit: 0,
             time_limit: 0,
+            max_file_size: None,
+            ceiling: 0,
         },
         blacklist: BlacklistConfig {
             list: Vec::new(),
             mode: BlacklistMode::Block,
         },
         connection_timeout: None,
+        websocket_limits: WebsocketLimitsConfig::default(),
+        normalization: NormalizationConfig::default(),
+        method_override: None,
+        mime_types: Vec::new(),
+        settings: settings(&[
+            ("server.address", "0.0.0.0"),
+            ("server.port", "80"),
+            ("server.threads", "32"),
+        ]),
     });
 
     assert_eq!(config, expected_conf);
@@ -87,8 +110,15 @@ fn nested_include() {
                     mode: LoadBalancerMode::Random,
                     index: 0,
                     lcg: Lcg::new(),
+                    unavailable: HashMap::new(),
                 })),
                 websocket_proxy: None,
+                cache: RouteCacheConfig::default(),
+                rewrite: RewriteConfig::default(),
+                retry: RetryConfig::default(),
+                log_bodies: false,
+                nosniff: true,
+                untrusted_uploads: false,
             }],
         },
         hosts: Vec::new(),
@@ -96,18 +126,32 @@ fn nested_include() {
         plugins: Vec::new(),
         logging: LoggingConfig {
             level: LogLevel::Warn,
+            format: LogFormat::Text,
             console: true,
             file: None,
+            slow_request_threshold: None,
+            bodies: BodyLogConfig::default(),
         },
         cache: CacheConfig {
             size_limit: 0,
             time_limit: 0,
+            max_file_size: None,
+            ceiling: 0,
         },
         blacklist: BlacklistConfig {
             list: Vec::new(),
             mode: BlacklistMode::Block,
         },
         connection_timeout: None,
+        websocket_limits: WebsocketLimitsConfig::default(),
+        normalization: NormalizationConfig::default(),
+        method_override: None,
+        mime_types: Vec::new(),
+        settings: settings(&[
+            ("server.address", "0.0.0.0"),
+            ("server.port", "80"),
+            ("server.threads", "32"),
+        ]),
     });
 
     assert_eq!(config, expected_conf);
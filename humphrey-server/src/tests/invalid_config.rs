@@ -1,5 +1,6 @@
-use humphrey_server::config::error::ConfigError;
+use humphrey_server::config::error::{ConfigError, ConfigErrors};
 use humphrey_server::config::tree::parse_conf;
+use humphrey_server::config::Config;
 
 #[test]
 fn value_error() {
@@ -8,10 +9,9 @@ fn value_error() {
 
     assert_eq!(
         config,
-        Err(ConfigError::new(
-            "Could not parse value",
-            "value_error.conf",
-            34
+        Err(ConfigErrors::from(
+            ConfigError::new("Could not parse value", "value_error.conf", 34)
+                .with_block("server > cache")
         ))
     );
 }
@@ -23,10 +23,9 @@ fn eof_error() {
 
     assert_eq!(
         config,
-        Err(ConfigError::new(
-            "Unexpected end of file, expected `}`",
-            "eof_error.conf",
-            44
+        Err(ConfigErrors::from(
+            ConfigError::new("Unexpected end of file, expected `}`", "eof_error.conf", 44)
+                .with_block("server")
         ))
     );
 }
@@ -38,10 +37,64 @@ fn empty_file_error() {
 
     assert_eq!(
         config,
-        Err(ConfigError::new(
+        Err(ConfigErrors::from(ConfigError::new(
             "Could not find `server` section",
             "empty_file_error.conf",
             0
-        ))
+        )))
     );
 }
+
+#[test]
+fn multiple_errors() {
+    let string = include_str!("./testcases/multiple_errors.conf");
+    let errors = Config::parse(string, "multiple_errors.conf").unwrap_err();
+
+    let file = "multiple_errors.conf";
+    let expected = ConfigErrors::from(vec![
+        ConfigError::new("Could not parse value", file, 15).with_block("server > cache"),
+        ConfigError::new("Unknown key", file, 10).with_block("server > log"),
+        ConfigError::new("Invalid port", file, 5).with_block("server"),
+        ConfigError::new(
+            "Invalid cache priority, valid options are `normal` or `high`",
+            file,
+            22,
+        )
+        .with_block("server > route /static/* > cache"),
+        ConfigError::new(
+            "Invalid route configuration, every route must contain either the `file`, `directory`, `proxy` or `redirect` field, unless it defines a WebSocket proxy with the `websocket` field",
+            file,
+            26,
+        )
+        .with_block("server > route /api/*"),
+    ]);
+
+    assert_eq!(errors, expected);
+    assert_eq!(errors.len(), 5);
+
+    let report = errors.to_string();
+    assert!(report.starts_with("Found 5 errors in the configuration:"));
+    assert!(report.contains(
+        "\n  multiple_errors.conf, in `server > cache`:\n    line 15: Could not parse value"
+    ));
+    assert!(report.contains("\n  multiple_errors.conf, in `server`:\n    line 5: Invalid port"));
+}
+
+#[test]
+fn fatal_error() {
+    let string = include_str!("./testcases/fatal_error.conf");
+    let errors = Config::parse(string, "fatal_error.conf").unwrap_err();
+
+    // Errors found before the structural error are still reported
+    let expected = ConfigErrors::from(vec![
+        ConfigError::new("Could not parse value", "fatal_error.conf", 4).with_block("server"),
+        ConfigError::new(
+            "Unexpected end of file, expected `}`",
+            "fatal_error.conf",
+            8,
+        )
+        .with_block("server > route /*"),
+    ]);
+
+    assert_eq!(errors, expected);
+}
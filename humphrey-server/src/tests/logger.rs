@@ -0,0 +1,236 @@
+use humphrey_server::config::config::Config;
+use humphrey_server::logger::{LogFormat, LogLevel, Logger};
+
+use humphrey::clock::{override_clock, MockClock};
+use humphrey_json::Value;
+
+#[cfg(feature = "plugins")]
+use humphrey_server::config::config::PluginConfig;
+#[cfg(feature = "plugins")]
+use std::collections::HashMap;
+
+use std::fs::read_to_string;
+use std::time::Duration;
+
+/// Creates a logger which only logs to a file at the given level, returning it and the path of the
+///   file, with the given plugins configured.
+fn file_logger(name: &str, level: LogLevel, config: impl FnOnce(&mut Config)) -> (Logger, String) {
+    let path = std::env::temp_dir()
+        .join(format!(
+            "humphrey-logger-{}-{}.log",
+            name,
+            std::process::id()
+        ))
+        .to_string_lossy()
+        .to_string();
+
+    let mut conf = Config::default();
+    conf.logging.level = level;
+    conf.logging.console = false;
+    conf.logging.file = Some(path.clone());
+    config(&mut conf);
+
+    (Logger::from(&conf), path)
+}
+
+/// Reads the logged lines from the file, removing the time from the start of each line.
+fn logged_lines(path: &str) -> Vec<String> {
+    read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| line.splitn(3, ' ').nth(2).unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn test_scoped_logger() {
+    let (logger, path) = file_logger("scoped", LogLevel::Info, |_| ());
+
+    logger.info("Starting server");
+    logger.scoped("php").info("Connected");
+    logger.scoped("php").debug("Not logged");
+    logger.scoped("php").error("Lost connection");
+
+    assert_eq!(
+        logged_lines(&path),
+        vec![
+            "[INFO]  Starting server",
+            "[INFO]  [php] Connected",
+            "[ERROR] [php] Lost connection"
+        ]
+    );
+}
+
+#[test]
+fn test_scoped_logger_lazy() {
+    let (logger, path) = file_logger("lazy", LogLevel::Warn, |_| ());
+    let scoped = logger.scoped("hot-reload");
+
+    assert!(scoped.enabled(LogLevel::Warn));
+    assert!(!scoped.enabled(LogLevel::Info));
+
+    scoped.log_at(LogLevel::Debug, || -> String {
+        panic!("message formatted for a disabled level")
+    });
+    scoped.log_at(LogLevel::Warn, || format!("Watching {} routes", 2));
+
+    assert_eq!(
+        logged_lines(&path),
+        vec!["[WARN]  [hot-reload] Watching 2 routes"]
+    );
+}
+
+#[cfg(feature = "plugins")]
+#[test]
+fn test_plugin_log_level() {
+    let (logger, path) = file_logger("plugin-level", LogLevel::Info, |conf| {
+        conf.plugins = vec![
+            PluginConfig {
+                name: "php".into(),
+                library: "php.so".into(),
+                log_level: Some(LogLevel::Error),
+                config: HashMap::new(),
+            },
+            PluginConfig {
+                name: "hot-reload".into(),
+                library: "hot-reload.so".into(),
+                log_level: Some(LogLevel::Debug),
+                config: HashMap::new(),
+            },
+        ]
+    });
+
+    logger.debug("Not logged");
+    logger.scoped("php").warn("Not logged");
+    logger.scoped("php").error("Logged");
+    logger.scoped("hot-reload").debug("Logged");
+    logger.scoped("unconfigured").info("Logged");
+
+    assert_eq!(
+        logged_lines(&path),
+        vec![
+            "[ERROR] [php] Logged",
+            "[DEBUG] [hot-reload] Logged",
+            "[INFO]  [unconfigured] Logged"
+        ]
+    );
+}
+
+/// Parses each logged line as JSON, checking that every line has exactly the documented keys in order.
+fn logged_json(path: &str) -> Vec<Value> {
+    read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let value = Value::parse(line).unwrap();
+            let keys: Vec<&str> = value
+                .as_object()
+                .unwrap()
+                .iter()
+                .map(|(key, _)| key.as_str())
+                .collect();
+
+            assert_eq!(keys, ["timestamp", "level", "scope", "message", "fields"]);
+
+            value
+        })
+        .collect()
+}
+
+#[test]
+fn test_json_format() {
+    let _guard = override_clock(MockClock::at(Duration::from_secs(1700000000)));
+    let (logger, path) = file_logger("json", LogLevel::Info, |conf| {
+        conf.logging.format = LogFormat::Json;
+    });
+
+    logger.info("Starting server");
+    logger.debug("Not logged");
+    logger.scoped("php").warn("Lost connection");
+
+    let lines = logged_json(&path);
+
+    assert_eq!(lines.len(), 2);
+    assert_eq!(
+        lines[0].get("timestamp").unwrap().as_str(),
+        Some("2023-11-14T22:13:20Z")
+    );
+    assert_eq!(lines[0].get("level").unwrap().as_str(), Some("info"));
+    assert_eq!(lines[0].get("scope"), Some(&Value::Null));
+    assert_eq!(
+        lines[0].get("message").unwrap().as_str(),
+        Some("Starting server")
+    );
+    assert_eq!(lines[0].get("fields"), Some(&Value::Object(Vec::new())));
+
+    assert_eq!(lines[1].get("level").unwrap().as_str(), Some("warn"));
+    assert_eq!(lines[1].get("scope").unwrap().as_str(), Some("php"));
+    assert_eq!(
+        lines[1].get("message").unwrap().as_str(),
+        Some("Lost connection")
+    );
+}
+
+#[test]
+fn test_json_fields() {
+    let (logger, path) = file_logger("json-fields", LogLevel::Info, |conf| {
+        conf.logging.format = LogFormat::Json;
+    });
+
+    logger.log_with_fields(
+        LogLevel::Info,
+        "Served \"index.html\"\nfrom cache",
+        &[("peer", "127.0.0.1"), ("status", "200"), ("route", "/*")],
+    );
+    logger
+        .scoped("php")
+        .log_with_fields(LogLevel::Error, "Script failed", &[("status", "500")]);
+    logger.log_with_fields(LogLevel::Debug, "Not logged", &[("peer", "127.0.0.1")]);
+
+    let lines = logged_json(&path);
+
+    assert_eq!(lines.len(), 2);
+    assert_eq!(
+        lines[0].get("message").unwrap().as_str(),
+        Some("Served \"index.html\"\nfrom cache")
+    );
+
+    let fields = lines[0].get("fields").unwrap();
+    assert_eq!(fields.get("peer").unwrap().as_str(), Some("127.0.0.1"));
+    assert_eq!(fields.get("status").unwrap().as_str(), Some("200"));
+    assert_eq!(fields.get("route").unwrap().as_str(), Some("/*"));
+
+    assert_eq!(lines[1].get("level").unwrap().as_str(), Some("error"));
+    assert_eq!(lines[1].get("scope").unwrap().as_str(), Some("php"));
+    assert_eq!(
+        lines[1]
+            .get("fields")
+            .unwrap()
+            .get("status")
+            .unwrap()
+            .as_str(),
+        Some("500")
+    );
+}
+
+#[test]
+fn test_text_fields() {
+    let (logger, path) = file_logger("text-fields", LogLevel::Info, |_| ());
+
+    logger.log_with_fields(
+        LogLevel::Warn,
+        "File not found",
+        &[("peer", "127.0.0.1"), ("status", "404")],
+    );
+    logger
+        .scoped("php")
+        .log_with_fields(LogLevel::Info, "Connected", &[("route", "/*")]);
+
+    assert_eq!(
+        logged_lines(&path),
+        vec![
+            "[WARN]  File not found peer=127.0.0.1 status=404",
+            "[INFO]  [php] Connected route=/*"
+        ]
+    );
+}
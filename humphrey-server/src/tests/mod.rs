@@ -1,4 +1,43 @@
+pub mod body_log;
+pub mod cache;
 pub mod config;
+pub mod extended_hashmap;
 pub mod include;
 pub mod invalid_config;
+pub mod logger;
+pub mod normalize;
+pub mod proxy;
+pub mod rand;
+pub mod static_files;
 pub mod tree;
+
+use humphrey::App;
+
+use std::net::{TcpListener, TcpStream};
+use std::thread::{sleep, spawn};
+use std::time::Duration;
+
+/// Runs the app on a free port in the background, returning its address once it is listening.
+pub fn start_app<State>(app: App<State>) -> String
+where
+    State: Send + Sync + 'static,
+{
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .to_string();
+
+    let cloned_addr = addr.clone();
+    spawn(move || app.run(cloned_addr).unwrap());
+
+    for _ in 0..50 {
+        if TcpStream::connect(&addr).is_ok() {
+            break;
+        }
+
+        sleep(Duration::from_millis(10));
+    }
+
+    addr
+}
@@ -0,0 +1,142 @@
+use humphrey::http::{Request, Response, StatusCode};
+use humphrey::App;
+use humphrey_server::config::{Config, LoggingConfig, NormalizationConfig};
+use humphrey_server::logger::LogLevel;
+use humphrey_server::normalize::{normalize_path, normalize_request};
+use humphrey_server::AppState;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{channel, Sender};
+use std::time::Duration;
+
+fn normalize(path: &str) -> Option<String> {
+    normalize_path(path, &NormalizationConfig::default())
+}
+
+#[test]
+fn test_merge_slashes() {
+    assert_eq!(normalize("/"), Some("/".into()));
+    assert_eq!(normalize("//admin//panel"), Some("/admin/panel".into()));
+    assert_eq!(normalize("/static///app.js"), Some("/static/app.js".into()));
+    assert_eq!(normalize("/static//"), Some("/static/".into()));
+}
+
+#[test]
+fn test_resolve_dots() {
+    assert_eq!(normalize("/./static/app.js"), Some("/static/app.js".into()));
+    assert_eq!(
+        normalize("//admin//../admin/panel"),
+        Some("/admin/panel".into())
+    );
+    assert_eq!(normalize("/admin/%2e/panel"), Some("/admin/panel".into()));
+    assert_eq!(normalize("/a/b/%2E%2E"), Some("/a/".into()));
+    assert_eq!(normalize("/a/.."), Some("/".into()));
+    assert_eq!(normalize("/a/..."), Some("/a/...".into()));
+}
+
+#[test]
+fn test_escape_rejected() {
+    assert_eq!(normalize("/.."), None);
+    assert_eq!(normalize("/a/../../etc/passwd"), None);
+    assert_eq!(normalize("/%2e%2e/etc/passwd"), None);
+}
+
+#[test]
+fn test_options() {
+    let disabled = NormalizationConfig {
+        merge_slashes: false,
+        resolve_dots: false,
+        lowercase: false,
+    };
+
+    assert_eq!(
+        normalize_path("//a/./../%2e", &disabled),
+        Some("//a/./../%2e".into())
+    );
+
+    let lowercase = NormalizationConfig {
+        lowercase: true,
+        ..Default::default()
+    };
+
+    assert_eq!(
+        normalize_path("/Static/App.JS", &lowercase),
+        Some("/static/app.js".into())
+    );
+    assert_eq!(normalize_path("*", &lowercase), Some("*".into()));
+}
+
+/// Starts an app which protects `/admin/*`, returning its address and shutdown signal.
+fn start_app() -> (String, Sender<()>) {
+    let (shutdown_tx, shutdown_rx) = channel();
+
+    let config = Config {
+        logging: LoggingConfig {
+            level: LogLevel::Error,
+            console: false,
+            file: None,
+            slow_request_threshold: None,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let app: App<AppState> = App::new_with_config(2, AppState::from(config))
+        .with_request_rewriter(normalize_request)
+        .with_stateless_route("/admin/*", |_| {
+            Response::new(StatusCode::Forbidden, "forbidden")
+        })
+        .with_stateless_route("/*", |request: Request| {
+            Response::new(StatusCode::OK, request.uri)
+        })
+        .with_shutdown(shutdown_rx);
+
+    let addr = crate::tests::start_app(app);
+
+    (addr, shutdown_tx)
+}
+
+fn get(addr: &str, uri: &str) -> Response {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    stream
+        .write_all(format!("GET {} HTTP/1.1\r\nConnection: Close\r\n\r\n", uri).as_bytes())
+        .unwrap();
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+
+    Response::from_stream(&mut response.as_slice()).unwrap()
+}
+
+#[test]
+fn test_admin_rule_not_bypassed() {
+    let (addr, shutdown) = start_app();
+
+    assert_eq!(
+        get(&addr, "/admin/panel").status_code,
+        StatusCode::Forbidden
+    );
+    assert_eq!(
+        get(&addr, "//admin/%2e/panel").status_code,
+        StatusCode::Forbidden
+    );
+    assert_eq!(
+        get(&addr, "/public/../admin/panel").status_code,
+        StatusCode::Forbidden
+    );
+    assert_eq!(
+        get(&addr, "/../admin/panel").status_code,
+        StatusCode::BadRequest
+    );
+
+    let response = get(&addr, "/./static///app.js");
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body, b"/static/app.js");
+
+    shutdown.send(()).unwrap();
+}
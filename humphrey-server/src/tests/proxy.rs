@@ -0,0 +1,491 @@
+use humphrey::http::headers::HeaderType;
+use humphrey::http::{Request, Response, StatusCode};
+use humphrey::App;
+use humphrey_server::config::Config;
+use humphrey_server::proxy::{proxy_handler, rewrite_path_prefix};
+use humphrey_server::AppState;
+
+use std::fs::{read_to_string, remove_file};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{sleep, spawn, JoinHandle};
+use std::time::Duration;
+
+#[test]
+fn test_rewrite_path_prefix() {
+    assert_eq!(
+        rewrite_path_prefix("/api/users", "/api", ""),
+        Some("/users".into())
+    );
+    assert_eq!(rewrite_path_prefix("/api", "/api", ""), Some("/".into()));
+    assert_eq!(rewrite_path_prefix("/apiary", "/api", ""), None);
+    assert_eq!(
+        rewrite_path_prefix("/users", "/", "/v1"),
+        Some("/v1/users".into())
+    );
+    assert_eq!(
+        rewrite_path_prefix("/old/a/b", "/old/", "/new/"),
+        Some("/new/a/b".into())
+    );
+}
+
+/// Starts an upstream server which redirects each request to the next of the given locations,
+///   sending the URI and `Host` header of each request to the returned receiver.
+fn start_upstream(
+    locations: impl FnOnce(&str) -> Vec<String>,
+) -> (String, Receiver<(String, Option<String>)>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let locations = locations(&addr.to_string());
+    let (tx, rx) = channel();
+
+    spawn(move || {
+        for (stream, location) in listener.incoming().zip(locations) {
+            let mut stream = stream.unwrap();
+            let request = Request::from_stream(&mut stream, addr).unwrap();
+            let host = request.headers.get(HeaderType::Host).map(|h| h.to_string());
+            tx.send((request.uri, host)).unwrap();
+
+            let response = Response::empty(StatusCode::Found)
+                .with_header(HeaderType::Location, location)
+                .with_header(HeaderType::Connection, "Close");
+            let bytes: Vec<u8> = response.into();
+            stream.write_all(&bytes).unwrap();
+        }
+    });
+
+    (addr.to_string(), rx)
+}
+
+/// Starts the server with a single proxy route configured with the given rules.
+fn start_proxy(route: &str) -> (String, Sender<()>) {
+    start_proxy_with_logging(route, "    level \"error\"\n    console false")
+}
+
+/// Starts the server with a single proxy route configured with the given rules, logging requests to
+///   a file whose path is returned.
+fn start_logged_proxy(route: &str, name: &str) -> (String, Sender<()>, String) {
+    let path = std::env::temp_dir()
+        .join(format!(
+            "humphrey-proxy-{}-{}.log",
+            name,
+            std::process::id()
+        ))
+        .to_string_lossy()
+        .to_string();
+    let _ = remove_file(&path);
+
+    let logging = format!(
+        "    level \"info\"\n    console false\n    file \"{}\"",
+        path.replace('\\', "/")
+    );
+    let (addr, shutdown) = start_proxy_with_logging(route, &logging);
+
+    (addr, shutdown, path)
+}
+
+/// Starts the server with a single proxy route configured with the given rules and logging section.
+fn start_proxy_with_logging(route: &str, logging: &str) -> (String, Sender<()>) {
+    let (shutdown_tx, shutdown_rx) = channel();
+
+    let conf = format!(
+        "server {{\n  log {{\n{}\n  }}\n\n  route /* {{\n{}\n  }}\n}}",
+        logging, route
+    );
+    let config = Config::parse(&conf, "proxy.conf").unwrap();
+
+    let app: App<AppState> = App::new_with_config(2, AppState::from(config))
+        .with_route("/*", |request: Request, state: Arc<AppState>| {
+            let route = state.config.get_route(0, 0);
+            proxy_handler(
+                request,
+                state.clone(),
+                route.load_balancer.as_ref().unwrap(),
+                &route.matches,
+                &route.rewrite,
+                &route.retry,
+            )
+        })
+        .with_shutdown(shutdown_rx);
+
+    let addr = crate::tests::start_app(app);
+
+    (addr, shutdown_tx)
+}
+
+fn get(addr: &str, uri: &str) -> Response {
+    send(addr, "GET", uri)
+}
+
+fn send(addr: &str, method: &str, uri: &str) -> Response {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    stream
+        .write_all(
+            format!(
+                "{} {} HTTP/1.1\r\nHost: public.example\r\nConnection: Close\r\n\r\n",
+                method, uri
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+
+    Response::from_stream(&mut response.as_slice()).unwrap()
+}
+
+#[test]
+fn test_proxy_rewrites() {
+    let (upstream, requests) = start_upstream(|upstream| {
+        vec![
+            format!("http://{}/login", upstream),
+            "http://internal.service/account?tab=1".into(),
+            "https://elsewhere.example/".into(),
+        ]
+    });
+
+    let (addr, shutdown) = start_proxy(&format!(
+        "    proxy \"{}\"\n    rewrite_path_prefix \"/api\" \"\"\n    upstream_host \"internal.service\"\n    rewrite_response_location true",
+        upstream
+    ));
+
+    let response = get(&addr, "/api/users");
+    assert_eq!(
+        requests.recv().unwrap(),
+        ("/users".into(), Some("internal.service".into()))
+    );
+    assert_eq!(
+        response.headers.get(HeaderType::Location),
+        Some("http://public.example/api/login")
+    );
+
+    let response = get(&addr, "/api");
+    assert_eq!(
+        requests.recv().unwrap(),
+        ("/".into(), Some("internal.service".into()))
+    );
+    assert_eq!(
+        response.headers.get(HeaderType::Location),
+        Some("http://public.example/api/account?tab=1")
+    );
+
+    // Locations which do not point to the upstream are left alone
+    let response = get(&addr, "/other");
+    assert_eq!(
+        requests.recv().unwrap(),
+        ("/other".into(), Some("internal.service".into()))
+    );
+    assert_eq!(
+        response.headers.get(HeaderType::Location),
+        Some("https://elsewhere.example/")
+    );
+
+    shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_proxy_repeated_headers() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let upstream = listener.local_addr().unwrap();
+    let (tx, rx) = channel();
+
+    spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let request = Request::from_stream(&mut stream, upstream).unwrap();
+        let tags: Vec<String> = request
+            .headers
+            .get_all("X-Tag")
+            .into_iter()
+            .map(|tag| tag.to_string())
+            .collect();
+        tx.send(tags).unwrap();
+
+        let response = Response::empty(StatusCode::OK)
+            .with_header(HeaderType::SetCookie, "a=1")
+            .with_header(HeaderType::Via, "1.1 upstream")
+            .with_header(HeaderType::SetCookie, "b=2")
+            .with_header(HeaderType::Via, "1.1 cache")
+            .with_header(HeaderType::Connection, "Close");
+        let bytes: Vec<u8> = response.into();
+        stream.write_all(&bytes).unwrap();
+    });
+
+    let (addr, shutdown) = start_proxy(&format!("    proxy \"{}\"", upstream));
+
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nX-Tag: one\r\nX-Tag: two\r\nConnection: Close\r\n\r\n")
+        .unwrap();
+
+    let mut bytes = Vec::new();
+    stream.read_to_end(&mut bytes).unwrap();
+    let response = Response::from_stream(&mut bytes.as_slice()).unwrap();
+
+    assert_eq!(rx.recv().unwrap(), vec!["one", "two"]);
+    assert_eq!(
+        response.headers.get_all(HeaderType::SetCookie),
+        vec!["a=1", "b=2"]
+    );
+    assert_eq!(
+        response.headers.get_all(HeaderType::Via),
+        vec!["1.1 upstream", "1.1 cache"]
+    );
+
+    shutdown.send(()).unwrap();
+}
+
+/// Starts a fake upstream which handles one connection for each of the given bodies, responding
+///   with the body or, if there is none, closing the connection after reading the request.
+///
+/// Once every connection has been handled, the upstream is killed, so later connections are
+///   refused. The returned handle finishes when this happens.
+fn start_fake_upstream(bodies: Vec<Option<&'static str>>) -> (String, JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let handle = spawn(move || {
+        for body in bodies {
+            let (mut stream, _) = listener.accept().unwrap();
+            Request::from_stream(&mut stream, addr).unwrap();
+
+            if let Some(body) = body {
+                let response = Response::new(StatusCode::OK, body)
+                    .with_header(HeaderType::ContentLength, body.len().to_string())
+                    .with_header(HeaderType::Connection, "Close");
+                let bytes: Vec<u8> = response.into();
+                stream.write_all(&bytes).unwrap();
+            }
+        }
+    });
+
+    (addr.to_string(), handle)
+}
+
+/// Reads the logged lines from the file, removing the time, level and client address from the start
+///   of each line.
+fn logged_requests(path: &str) -> Vec<String> {
+    read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| line.split_once(": ").unwrap().1.to_string())
+        .collect()
+}
+
+#[test]
+fn test_proxy_retries_same_backend() {
+    let (upstream, upstream_handle) = start_fake_upstream(vec![None, Some("recovered"), None]);
+
+    let (addr, shutdown, log) = start_logged_proxy(
+        &format!(
+            "    proxy \"{}\"\n    retry {{\n      attempts 3\n      delay 10\n    }}",
+            upstream
+        ),
+        "same-backend",
+    );
+
+    // The upstream restarts after the request is sent, which is safe to retry for `GET` only
+    let response = get(&addr, "/one");
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body, b"recovered");
+
+    let response = send(&addr, "POST", "/two");
+    assert_eq!(response.status_code, StatusCode::BadGateway);
+
+    // Once the upstream is killed, connections are refused and every attempt is used
+    upstream_handle.join().unwrap();
+
+    let response = send(&addr, "POST", "/three");
+    assert_eq!(response.status_code, StatusCode::BadGateway);
+
+    assert_eq!(
+        logged_requests(&log),
+        vec![
+            format!(
+                "200 OK /one (attempts: {0} connection reset, {0} ok)",
+                upstream
+            ),
+            format!(
+                "502 Bad Gateway /two (attempts: {} connection reset, POST not retried)",
+                upstream
+            ),
+            format!(
+                "502 Bad Gateway /three (attempts: {0} connection failed, {0} connection failed, {0} connection failed)",
+                upstream
+            ),
+        ]
+    );
+
+    shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_proxy_retries_other_backend() {
+    let (first, first_handle) = start_fake_upstream(vec![Some("first")]);
+    let (second, _) = start_fake_upstream(vec![Some("second"); 4]);
+
+    let (addr, shutdown, log) = start_logged_proxy(
+        &format!(
+            "    proxy \"{},{}\"\n    retry {{\n      attempts 2\n      delay 10\n      unavailable_ttl 60000\n    }}",
+            first, second
+        ),
+        "other-backend",
+    );
+
+    assert_eq!(get(&addr, "/1").body, b"first");
+    assert_eq!(get(&addr, "/2").body, b"second");
+
+    // The first upstream is killed, so its turn is retried against the second and it is marked as
+    //   unavailable, so later requests skip it
+    first_handle.join().unwrap();
+
+    assert_eq!(get(&addr, "/3").body, b"second");
+    assert_eq!(get(&addr, "/4").body, b"second");
+    assert_eq!(get(&addr, "/5").body, b"second");
+
+    assert_eq!(
+        logged_requests(&log),
+        vec![
+            "200 OK /1".to_string(),
+            "200 OK /2".to_string(),
+            format!(
+                "200 OK /3 (attempts: {} connection failed, {} ok)",
+                first, second
+            ),
+            "200 OK /4".to_string(),
+            "200 OK /5".to_string(),
+        ]
+    );
+
+    shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_proxy_unavailable_ttl() {
+    let (upstream, upstream_handle) = start_fake_upstream(vec![Some("ok")]);
+
+    let (addr, shutdown, log) = start_logged_proxy(
+        &format!(
+            "    proxy \"{}\"\n    retry {{\n      attempts 2\n      delay 10\n      unavailable_ttl 200\n    }}",
+            upstream
+        ),
+        "unavailable-ttl",
+    );
+
+    assert_eq!(get(&addr, "/1").status_code, StatusCode::OK);
+    upstream_handle.join().unwrap();
+
+    // The request which finds the upstream dead still retries it, but the next request fails
+    //   without connecting until the TTL expires
+    assert_eq!(get(&addr, "/2").status_code, StatusCode::BadGateway);
+    assert_eq!(get(&addr, "/3").status_code, StatusCode::BadGateway);
+
+    sleep(Duration::from_millis(250));
+    assert_eq!(get(&addr, "/4").status_code, StatusCode::BadGateway);
+
+    assert_eq!(
+        logged_requests(&log),
+        vec![
+            "200 OK /1".to_string(),
+            format!(
+                "502 Bad Gateway /2 (attempts: {0} connection failed, {0} connection failed)",
+                upstream
+            ),
+            format!(
+                "502 Bad Gateway /3 (attempts: {} marked unavailable)",
+                upstream
+            ),
+            format!(
+                "502 Bad Gateway /4 (attempts: {0} connection failed, {0} connection failed)",
+                upstream
+            ),
+        ]
+    );
+
+    shutdown.send(()).unwrap();
+}
+
+/// Reads from the stream until the bytes end with the given suffix, returning everything read.
+fn read_until_suffix(stream: &mut TcpStream, suffix: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut buf = [0; 1];
+
+    while !bytes.ends_with(suffix) {
+        if stream.read(&mut buf).unwrap() == 0 {
+            break;
+        }
+
+        bytes.push(buf[0]);
+    }
+
+    bytes
+}
+
+#[test]
+fn test_proxy_relays_trailers() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let upstream = listener.local_addr().unwrap().to_string();
+
+    let upstream_handle = spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        // The head and the trailers both end with an empty line
+        let mut request = read_until_suffix(&mut stream, b"\r\n\r\n");
+        request.extend(read_until_suffix(&mut stream, b"\r\n\r\n"));
+
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nConnection: Close\r\n\r\n4\r\ndata\r\n0\r\nServer-Timing: total;dur=12\r\n\r\n")
+            .unwrap();
+
+        String::from_utf8(request).unwrap()
+    });
+
+    let (addr, shutdown) = start_proxy(&format!("    proxy \"{}\"", upstream));
+
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    stream
+        .write_all(b"POST /upload HTTP/1.1\r\nHost: public.example\r\nConnection: Close\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\nX-Checksum: 5d41\r\n\r\n")
+        .unwrap();
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+    let response = String::from_utf8(response).unwrap();
+
+    let request = upstream_handle.join().unwrap();
+    assert!(
+        request.contains("Transfer-Encoding: chunked\r\n"),
+        "{}",
+        request
+    );
+    assert!(!request.contains("Content-Length"), "{}", request);
+    assert!(
+        request.ends_with("\r\n\r\n5\r\nhello\r\n0\r\nx-checksum: 5d41\r\n\r\n"),
+        "{}",
+        request
+    );
+
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(
+        response.ends_with("\r\n\r\n4\r\ndata\r\n0\r\nserver-timing: total;dur=12\r\n\r\n"),
+        "{}",
+        response
+    );
+
+    shutdown.send(()).unwrap();
+}
@@ -0,0 +1,73 @@
+use humphrey_server::config::LoadBalancerMode;
+use humphrey_server::proxy::LoadBalancer;
+use humphrey_server::rand::{Choose, Lcg, SecureRandom};
+
+use std::collections::HashMap;
+
+#[test]
+fn test_seeded_lcg_is_deterministic() {
+    let a: Vec<u32> = Lcg::seeded(42).take(16).collect();
+    let b: Vec<u32> = Lcg::seeded(42).take(16).collect();
+    let c: Vec<u32> = Lcg::seeded(43).take(16).collect();
+
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+}
+
+#[test]
+fn test_lcg_distribution() {
+    let mut lcg = Lcg::seeded(1234);
+    let items = [0_usize, 1, 2, 3];
+    let mut counts = [0_usize; 4];
+
+    for _ in 0..40000 {
+        counts[*items.choose(&mut lcg).unwrap()] += 1;
+    }
+
+    // Each bucket should receive roughly a quarter of the samples
+    for count in counts {
+        assert!((9000..11000).contains(&count), "{:?}", counts);
+    }
+}
+
+#[test]
+fn test_random_load_balancer_is_reproducible() {
+    let load_balancer = || LoadBalancer {
+        targets: vec!["a".into(), "b".into(), "c".into()],
+        mode: LoadBalancerMode::Random,
+        index: 0,
+        lcg: Lcg::seeded(7),
+        unavailable: HashMap::new(),
+    };
+
+    let mut first = load_balancer();
+    let mut second = load_balancer();
+
+    let targets: Vec<String> = (0..32).map(|_| first.select_target()).collect();
+
+    assert_eq!(
+        targets,
+        (0..32)
+            .map(|_| second.select_target())
+            .collect::<Vec<String>>()
+    );
+    assert!(["a", "b", "c"]
+        .iter()
+        .all(|t| targets.contains(&t.to_string())));
+}
+
+#[test]
+fn test_secure_token() {
+    let a = SecureRandom.token(32).unwrap();
+    let b = SecureRandom.token(32).unwrap();
+
+    assert_eq!(a.len(), 64);
+    assert!(a
+        .chars()
+        .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c)));
+    assert_ne!(a, b);
+
+    let mut buf = [0; 256];
+    SecureRandom.fill(&mut buf).unwrap();
+    assert!(buf.iter().any(|&b| b != 0));
+}
@@ -0,0 +1,397 @@
+use humphrey::http::headers::HeaderType;
+use humphrey::http::{Request, Response, StatusCode};
+use humphrey_server::config::{
+    BlacklistConfig, BlacklistMode, CacheConfig, Config, LoggingConfig, RouteConfig, RouteType,
+};
+use humphrey_server::logger::LogLevel;
+use humphrey_server::r#static::{directory_handler, file_handler, redirect_handler};
+use humphrey_server::AppState;
+
+use std::fs::{read_to_string, remove_file, rename, write};
+use std::sync::Arc;
+
+// Absolute, since other tests change the working directory
+const DIRECTORY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/testcases/static");
+
+fn state(cache_size: usize, log_name: &str) -> (Arc<AppState>, String) {
+    let log_path = std::env::temp_dir()
+        .join(format!("humphrey-{}-{}.log", log_name, std::process::id()))
+        .to_string_lossy()
+        .to_string();
+
+    let config = Config {
+        logging: LoggingConfig {
+            level: LogLevel::Debug,
+            console: false,
+            file: Some(log_path.clone()),
+            ..Default::default()
+        },
+        cache: CacheConfig {
+            size_limit: cache_size,
+            ..Default::default()
+        },
+        blacklist: BlacklistConfig {
+            list: vec!["10.0.0.1".parse().unwrap()],
+            mode: BlacklistMode::Block,
+        },
+        ..Default::default()
+    };
+
+    (Arc::new(AppState::from(config)), log_path)
+}
+
+fn route(route_type: RouteType, matches: &str, path: &str) -> RouteConfig {
+    RouteConfig {
+        route_type,
+        matches: matches.into(),
+        path: Some(path.into()),
+        ..Default::default()
+    }
+}
+
+fn get(uri: &str) -> Request {
+    Request::builder().uri(uri).build()
+}
+
+fn assert_page(response: &Response, status_code: StatusCode, content_type: &str, body: &[u8]) {
+    assert_eq!(response.status_code, status_code);
+    assert_eq!(
+        response.headers.get(HeaderType::ContentType),
+        Some(content_type)
+    );
+    assert_eq!(response.body, body);
+}
+
+#[test]
+fn test_directory_route() {
+    let (state, _) = state(0, "static-directory");
+    let route = route(RouteType::Directory, "/*", DIRECTORY);
+    let serve = |uri: &str| directory_handler(get(uri), state.clone(), &route, 0);
+
+    assert_page(&serve("/"), StatusCode::OK, "text/html", b"<h1>index</h1>");
+    assert_page(
+        &serve("/index.html"),
+        StatusCode::OK,
+        "text/html",
+        b"<h1>index</h1>",
+    );
+    assert_page(&serve("/style.css"), StatusCode::OK, "text/css", b"body {}");
+    assert_page(&serve("/nested/"), StatusCode::OK, "text/html", b"nested");
+
+    // Directories without a trailing slash are redirected so that relative links resolve
+    let redirect = serve("/nested");
+    assert_eq!(redirect.status_code, StatusCode::MovedPermanently);
+    assert_eq!(redirect.headers.get(HeaderType::Location), Some("/nested/"));
+    assert!(redirect.body.is_empty());
+
+    let not_found = b"<h1>404 Not Found</h1>";
+    assert_page(
+        &serve("/missing"),
+        StatusCode::NotFound,
+        "text/html",
+        not_found,
+    );
+    assert_page(
+        &serve("/nested/missing/"),
+        StatusCode::NotFound,
+        "text/html",
+        not_found,
+    );
+    assert_page(
+        &serve("/../Cargo.toml"),
+        StatusCode::NotFound,
+        "text/html",
+        not_found,
+    );
+    assert_page(
+        &serve("/%2e%2e/Cargo.toml"),
+        StatusCode::NotFound,
+        "text/html",
+        not_found,
+    );
+}
+
+#[test]
+fn test_directory_route_prefix() {
+    let (state, _) = state(0, "static-prefix");
+    let route = route(RouteType::Directory, "/static/*", DIRECTORY);
+    let serve = |uri: &str| directory_handler(get(uri), state.clone(), &route, 0);
+
+    assert_page(
+        &serve("/static/style.css"),
+        StatusCode::OK,
+        "text/css",
+        b"body {}",
+    );
+    assert_page(
+        &serve("/static/"),
+        StatusCode::OK,
+        "text/html",
+        b"<h1>index</h1>",
+    );
+
+    let redirect = serve("/static/nested");
+    assert_eq!(
+        redirect.headers.get(HeaderType::Location),
+        Some("/static/nested/")
+    );
+}
+
+#[test]
+fn test_file_route() {
+    let (state, log_path) = state(0, "static-file");
+    let route = route(
+        RouteType::File,
+        "/about",
+        concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/tests/testcases/static/style.css"
+        ),
+    );
+
+    let response = file_handler(get("/about"), state.clone(), &route, 0);
+    assert_page(&response, StatusCode::OK, "text/css", b"body {}");
+
+    // A missing file is reported as not found rather than panicking the worker thread
+    let route = RouteConfig {
+        path: Some(
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/src/tests/testcases/static/missing.css"
+            )
+            .into(),
+        ),
+        ..route
+    };
+    let response = file_handler(get("/about"), state, &route, 0);
+    assert_page(
+        &response,
+        StatusCode::NotFound,
+        "text/html",
+        b"<h1>404 Not Found</h1>",
+    );
+
+    let log = read_to_string(log_path).unwrap();
+    assert!(log.contains("missing.css was not found for /about"));
+}
+
+#[test]
+fn test_redirect_route() {
+    let (state, _) = state(0, "static-redirect");
+
+    let response = redirect_handler(get("/old"), state, "/new");
+    assert_eq!(response.status_code, StatusCode::MovedPermanently);
+    assert_eq!(response.headers.get(HeaderType::Location), Some("/new"));
+}
+
+#[test]
+fn test_blacklisted() {
+    let (state, _) = state(0, "static-blacklist");
+    let route = route(RouteType::Directory, "/*", DIRECTORY);
+
+    let request = Request::builder()
+        .uri("/style.css")
+        .address("10.0.0.1:1234".parse().unwrap())
+        .build();
+
+    let response = directory_handler(request, state, &route, 0);
+    assert_page(
+        &response,
+        StatusCode::Forbidden,
+        "text/html",
+        b"<h1>403 Forbidden</h1>",
+    );
+}
+
+#[test]
+fn test_cached() {
+    let directory = std::env::temp_dir().join(format!("humphrey-static-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    write(directory.join("page.html"), "first").unwrap();
+
+    let (state, log_path) = state(1024, "static-cache");
+    let route = route(
+        RouteType::Directory,
+        "/*",
+        directory.to_string_lossy().as_ref(),
+    );
+    let serve = |uri: &str| directory_handler(get(uri), state.clone(), &route, 0);
+
+    assert_page(&serve("/page.html"), StatusCode::OK, "text/html", b"first");
+    assert_page(&serve("/page.html"), StatusCode::OK, "text/html", b"first");
+
+    let stats = state.cache.read().unwrap().route_stats();
+    assert_eq!(stats["/*"].hits, 1);
+    assert_eq!(stats["/*"].misses, 1);
+
+    // Once the file changes on disk, the cached item is invalidated and the new file is served
+    write(directory.join("page.html"), "second").unwrap();
+    assert_page(&serve("/page.html"), StatusCode::OK, "text/html", b"second");
+    assert_page(&serve("/page.html"), StatusCode::OK, "text/html", b"second");
+    assert_eq!(state.cache.read().unwrap().route_stats()["/*"].hits, 2);
+
+    // Files which are replaced, as they are by most deploys, are detected in the same way
+    let replacement = directory.join("page.html.new");
+    write(&replacement, "thirds").unwrap();
+    rename(&replacement, directory.join("page.html")).unwrap();
+    assert_page(&serve("/page.html"), StatusCode::OK, "text/html", b"thirds");
+
+    // Files which are removed are no longer served from the cache
+    remove_file(directory.join("page.html")).unwrap();
+    assert_eq!(serve("/page.html").status_code, StatusCode::NotFound);
+    assert_eq!(state.cache.read().unwrap().size(), 0);
+
+    // Files which are not found are not cached
+    assert_eq!(serve("/missing.html").status_code, StatusCode::NotFound);
+    assert_eq!(state.cache.read().unwrap().size(), 0);
+
+    let log = read_to_string(log_path).unwrap();
+    assert!(log.contains("Cached route /page.html"));
+    assert!(log.contains("200 OK /page.html"));
+    assert!(log.contains("200 OK (cached) /page.html"));
+    assert!(log.contains("Invalidated cached route /page.html, its file changed"));
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+/// Returns the `Content-Type`, `Content-Disposition` and `X-Content-Type-Options` headers of the
+///   response.
+fn file_headers(response: &Response) -> (Option<&str>, Option<&str>, Option<&str>) {
+    (
+        response.headers.get(HeaderType::ContentType),
+        response.headers.get(HeaderType::ContentDisposition),
+        response.headers.get("X-Content-Type-Options"),
+    )
+}
+
+#[test]
+fn test_untrusted_uploads() {
+    let directory = std::env::temp_dir().join(format!("humphrey-uploads-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+
+    for file in [
+        "page.html",
+        "image.svg",
+        "feed.xml",
+        "script.js",
+        "upload",
+        "upload.dat",
+        "photo.png",
+        "style.css",
+    ] {
+        write(directory.join(file), "contents").unwrap();
+    }
+
+    let (state, _) = state(1024, "static-uploads");
+    let trusted = route(
+        RouteType::Directory,
+        "/*",
+        directory.to_string_lossy().as_ref(),
+    );
+    let untrusted = RouteConfig {
+        untrusted_uploads: true,
+        ..route(
+            RouteType::Directory,
+            "/*",
+            directory.to_string_lossy().as_ref(),
+        )
+    };
+
+    let download = (
+        Some("application/octet-stream"),
+        Some("attachment"),
+        Some("nosniff"),
+    );
+
+    let cases = [
+        (
+            "/page.html",
+            (Some("text/html"), None, Some("nosniff")),
+            download,
+        ),
+        (
+            "/image.svg",
+            (Some("image/svg+xml"), None, Some("nosniff")),
+            download,
+        ),
+        (
+            "/feed.xml",
+            (Some("application/xml"), None, Some("nosniff")),
+            download,
+        ),
+        (
+            "/script.js",
+            (Some("text/javascript"), None, Some("nosniff")),
+            download,
+        ),
+        ("/upload", (None, None, Some("nosniff")), download),
+        (
+            "/upload.dat",
+            (Some("application/octet-stream"), None, Some("nosniff")),
+            download,
+        ),
+        (
+            "/photo.png",
+            (Some("image/png"), None, Some("nosniff")),
+            (Some("image/png"), None, Some("nosniff")),
+        ),
+        (
+            "/style.css",
+            (Some("text/css"), None, Some("nosniff")),
+            (Some("text/css"), None, Some("nosniff")),
+        ),
+    ];
+
+    // The routes are served on different hosts so that they do not share cached items
+    for (host, route) in [(0, &trusted), (1, &untrusted)] {
+        for (uri, trusted_headers, untrusted_headers) in cases {
+            let expected = match route.untrusted_uploads {
+                true => untrusted_headers,
+                false => trusted_headers,
+            };
+
+            // The second response is served from the cache, which must send the same headers
+            for _ in 0..2 {
+                let response = directory_handler(get(uri), state.clone(), route, host);
+
+                assert_eq!(response.status_code, StatusCode::OK);
+                assert_eq!(response.body, b"contents");
+                assert_eq!(file_headers(&response), expected, "{}", uri);
+            }
+        }
+    }
+
+    assert_eq!(state.cache.read().unwrap().route_stats()["/*"].hits, 16);
+
+    // The header which stops browsers from guessing types can be turned off
+    let route = RouteConfig {
+        nosniff: false,
+        ..untrusted
+    };
+    let response = directory_handler(get("/image.svg"), state, &route, 2);
+    assert_eq!(
+        file_headers(&response),
+        (Some("application/octet-stream"), Some("attachment"), None)
+    );
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn test_logged() {
+    let (state, log_path) = state(0, "static-log");
+    let route = route(RouteType::Directory, "/*", DIRECTORY);
+    let serve = |uri: &str| directory_handler(get(uri), state.clone(), &route, 0);
+
+    serve("/style.css");
+    serve("/nested");
+    serve("/missing");
+
+    let log = read_to_string(log_path).unwrap();
+    let lines: Vec<&str> = log.lines().collect();
+
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].contains("[INFO] ") && lines[0].ends_with(": 200 OK /style.css"));
+    assert!(lines[1].contains("[INFO] ") && lines[1].ends_with(": 301 Moved Permanently /nested"));
+    assert!(lines[2].contains("[WARN] ") && lines[2].ends_with(": 404 Not Found /missing"));
+}
@@ -49,6 +49,35 @@ fn test_build_tree() {
     assert_eq!(parsed_conf, expected_parsed_conf);
 }
 
+#[test]
+fn test_build_tree_units() {
+    // Sizes are converted to bytes, but durations are kept as written until their key is known
+    #[rustfmt::skip]
+    let expected_parsed_conf = ConfigNode::Section("server".into(), vec![
+        ConfigNode::Number("timeout".into(), "30s".into()),
+        ConfigNode::Section("log".into(), vec![
+            ConfigNode::String("format".into(), "json".into()),
+            ConfigNode::Boolean("console".into(), "no".into()),
+            ConfigNode::Number("slow_request_threshold".into(), "2s".into()),
+            ConfigNode::Number("body_limit".into(), "16384".into()),
+        ]),
+        ConfigNode::Section("cache".into(), vec![
+            ConfigNode::Number("size".into(), "10485760".into()),
+            ConfigNode::Number("time".into(), "5m".into())
+        ]),
+        ConfigNode::Route("/*".into(), vec![
+            ConfigNode::String("proxy".into(), "127.0.0.1:8000".into()),
+            ConfigNode::Section("retry".into(), vec![
+                ConfigNode::Number("delay".into(), "1s".into()),
+            ]),
+        ]),
+    ]);
+
+    let parsed_conf = parse_conf(include_str!("./testcases/units.conf"), "units.conf").unwrap();
+
+    assert_eq!(parsed_conf, expected_parsed_conf);
+}
+
 #[test]
 #[rustfmt::skip]
 fn test_flatten_config() {
@@ -78,7 +107,7 @@ fn test_flatten_config() {
 fn test_get_routes() {
     let parsed_conf = parse_conf(CONF, "valid.conf").unwrap();
 
-    let mut expected_map: Vec<(String, HashMap<String, ConfigNode>)> = Vec::new();
+    let mut expected_map: Vec<(ConfigKey, HashMap<String, ConfigNode>)> = Vec::new();
 
     let mut static_hashmap: HashMap<String, ConfigNode> = HashMap::new();
     static_hashmap.insert("directory".into(), ConfigNode::String("directory".into(), "/var/www".into()));
@@ -99,7 +128,7 @@ fn test_get_routes() {
 fn test_get_plugins() {
     let parsed_conf = parse_conf(CONF, "valid.conf").unwrap();
 
-    let mut expected_map: Vec<(String, HashMap<String, ConfigNode>)> = Vec::new();
+    let mut expected_map: Vec<(ConfigKey, HashMap<String, ConfigNode>)> = Vec::new();
 
     let mut php_hashmap: HashMap<String, ConfigNode> = HashMap::new();
     php_hashmap.insert("library".into(), ConfigNode::String("library".into(), "plugins/php/target/release/php.dll".into()));
@@ -2,11 +2,12 @@
 
 #![allow(clippy::new_without_default)]
 
+use crate::context::UpgradeContext;
 use crate::handler::async_websocket_handler;
 use crate::message::Message;
 use crate::ping::Heartbeat;
 use crate::restion::Restion;
-use crate::stream::WebsocketStream;
+use crate::stream::{WebsocketStream, DEFAULT_CLOSE_TIMEOUT};
 
 use humphrey::thread::pool::ThreadPool;
 use humphrey::App;
@@ -15,7 +16,7 @@ use std::collections::HashMap;
 use std::net::{SocketAddr, ToSocketAddrs};
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::{Arc, Mutex};
-use std::thread::{sleep, spawn};
+use std::thread::{sleep, spawn, JoinHandle};
 use std::time::{Duration, Instant};
 
 /// Represents an asynchronous WebSocket app.
@@ -40,6 +41,8 @@ where
     poll_interval: Option<Duration>,
     /// Ping configuration.
     heartbeat: Option<Heartbeat>,
+    /// How long to wait for clients to acknowledge close frames sent by the app.
+    close_timeout: Duration,
     /// A hashmap with the addresses as the keys and the actual streams as the values.
     streams: HashMap<SocketAddr, StatefulWebsocketStream<StreamState>>,
     /// A receiver which is sent new streams to add to the hashmap.
@@ -82,6 +85,7 @@ where
     /// The state of the stream.
     pub state: Arc<StreamState>,
     connected: bool,
+    context: Arc<UpgradeContext>,
 }
 
 /// Represents a global sender which can send messages to clients without waiting for events.
@@ -177,6 +181,7 @@ where
             state: Default::default(),
             poll_interval: Some(Duration::from_millis(10)),
             heartbeat: None,
+            close_timeout: DEFAULT_CLOSE_TIMEOUT,
             thread_pool: ThreadPool::new(32),
             streams: Default::default(),
             incoming_streams,
@@ -215,6 +220,7 @@ where
             state: Arc::new(state),
             poll_interval: Some(Duration::from_millis(10)),
             heartbeat: None,
+            close_timeout: DEFAULT_CLOSE_TIMEOUT,
             thread_pool: ThreadPool::new(handler_threads),
             streams: Default::default(),
             incoming_streams,
@@ -246,6 +252,7 @@ where
             state: Default::default(),
             poll_interval: Some(Duration::from_millis(10)),
             heartbeat: None,
+            close_timeout: DEFAULT_CLOSE_TIMEOUT,
             thread_pool: ThreadPool::new(32),
             streams: Default::default(),
             incoming_streams,
@@ -277,6 +284,7 @@ where
             state: Arc::new(state),
             poll_interval: Some(Duration::from_millis(10)),
             heartbeat: None,
+            close_timeout: DEFAULT_CLOSE_TIMEOUT,
             thread_pool: ThreadPool::new(handler_threads),
             streams: Default::default(),
             incoming_streams,
@@ -389,6 +397,17 @@ where
         self
     }
 
+    /// Sets how long to wait for clients to acknowledge the close frame when the app closes their
+    ///   connection, either because they failed to respond to heartbeats or because the app is
+    ///   shutting down.
+    ///
+    /// By default, this is three seconds. Connections are closed in the background, so this does not
+    ///   delay other clients.
+    pub fn with_close_timeout(mut self, timeout: Duration) -> Self {
+        self.close_timeout = timeout;
+        self
+    }
+
     /// Start the application on the main thread.
     pub fn run(mut self) {
         // Ensure that the underlying Humphrey application is running if it is internal.
@@ -440,7 +459,8 @@ where
                                     addr,
                                     self.message_sender.clone(),
                                     stream.state.clone(),
-                                );
+                                )
+                                .with_context(stream.inner.context.clone());
 
                                 let cloned_state = self.state.clone();
                                 let cloned_handler = handler.clone();
@@ -456,7 +476,8 @@ where
                                     addr,
                                     self.message_sender.clone(),
                                     stream.state.clone(),
-                                );
+                                )
+                                .with_context(stream.inner.context.clone());
 
                                 let cloned_state = self.state.clone();
                                 let cloned_handler = handler.clone();
@@ -465,7 +486,10 @@ where
                                     .execute(move || (cloned_handler)(async_stream, cloned_state));
                             }
 
-                            self.streams.remove(&addr);
+                            if let Some(stream) = self.streams.remove(&addr) {
+                                close_in_background(stream.inner);
+                            }
+
                             break 'inner;
                         }
                         Restion::None => break 'inner,
@@ -481,7 +505,8 @@ where
                                     addr,
                                     self.message_sender.clone(),
                                     stream.state.clone(),
-                                );
+                                )
+                                .with_context(stream.inner.context.clone());
 
                                 let cloned_state = self.state.clone();
                                 let cloned_handler = handler.clone();
@@ -490,7 +515,10 @@ where
                                     .execute(move || (cloned_handler)(async_stream, cloned_state));
                             }
 
-                            self.streams.remove(&addr);
+                            if let Some(stream) = self.streams.remove(&addr) {
+                                close_in_background(stream.inner);
+                            }
+
                             continue;
                         }
                     }
@@ -508,11 +536,13 @@ where
                 .try_iter()
                 .filter_map(|s| s.peer_addr().map(|a| (a, s)).ok())
             {
+                let stream = stream.with_close_timeout(self.close_timeout);
                 let stream_state = Arc::new(StreamState::default());
 
                 if let Some(handler) = &connect_handler {
                     let async_stream =
-                        AsyncStream::new(addr, self.message_sender.clone(), stream_state.clone());
+                        AsyncStream::new(addr, self.message_sender.clone(), stream_state.clone())
+                            .with_context(stream.context.clone());
                     let cloned_state = self.state.clone();
                     let cloned_handler = handler.clone();
 
@@ -552,6 +582,18 @@ where
                 sleep(interval);
             }
         }
+
+        // Close every connection concurrently, so that shutdown takes at most one close timeout.
+        let closing: Vec<JoinHandle<()>> = self
+            .streams
+            .drain()
+            .map(|(_, stream)| close_in_background(stream.inner))
+            .collect();
+
+        for handle in closing {
+            handle.join().ok();
+        }
+
         self.thread_pool.stop();
     }
 
@@ -573,6 +615,7 @@ where
             sender,
             state,
             connected: true,
+            context: Default::default(),
         }
     }
 
@@ -588,6 +631,7 @@ where
             sender,
             state,
             connected: false,
+            context: Default::default(),
         }
     }
 
@@ -608,6 +652,17 @@ where
     pub fn peer_addr(&self) -> SocketAddr {
         self.addr
     }
+
+    /// Get the context of the upgrade request which opened the stream.
+    pub fn context(&self) -> &UpgradeContext {
+        &self.context
+    }
+
+    /// Sets the context of the upgrade request which opened the stream.
+    pub(crate) fn with_context(mut self, context: Arc<UpgradeContext>) -> Self {
+        self.context = context;
+        self
+    }
 }
 
 impl AsyncSender {
@@ -621,3 +676,11 @@ impl AsyncSender {
         self.0.send(OutgoingMessage::Broadcast(message)).ok();
     }
 }
+
+/// Performs the closing handshake on a separate thread, so that waiting for the client's
+///   acknowledgement does not block the event loop.
+fn close_in_background(mut stream: WebsocketStream) -> JoinHandle<()> {
+    spawn(move || {
+        stream.close().ok();
+    })
+}
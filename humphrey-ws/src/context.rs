@@ -0,0 +1,89 @@
+//! Provides access to the details of the HTTP request which opened a WebSocket connection.
+
+use humphrey::http::cookie::Cookie;
+use humphrey::http::headers::{HeaderLike, Headers};
+use humphrey::http::Request;
+use humphrey::percent::PercentDecode;
+
+/// Represents the details of the HTTP upgrade request which opened a WebSocket connection.
+///
+/// This is populated during the handshake and can be accessed from the stream with `context()`.
+#[derive(Clone, Debug, Default)]
+pub struct UpgradeContext {
+    /// The URI to which the upgrade request was made, for example `/ws/room1`.
+    pub uri: String,
+    /// The query string of the upgrade request.
+    pub query: String,
+    /// The route pattern which the request matched, for example `/ws/*`.
+    ///
+    /// This is only available when the handler was registered as a path-aware WebSocket route.
+    pub route: Option<&'static str>,
+    /// The cookies sent with the upgrade request.
+    pub cookies: Vec<Cookie>,
+    /// The headers of the upgrade request, unless their retention was disabled.
+    pub headers: Option<Headers>,
+}
+
+/// Represents the options for what is retained from the upgrade request.
+#[derive(Clone, Copy, Debug)]
+pub struct ContextOptions {
+    retain_headers: bool,
+}
+
+impl UpgradeContext {
+    /// Creates a new context from the given upgrade request.
+    pub(crate) fn new(request: &Request, route: Option<&'static str>, options: ContextOptions) -> Self {
+        Self {
+            uri: request.uri.clone(),
+            query: request.query.clone(),
+            route,
+            cookies: request.get_cookies(),
+            headers: options.retain_headers.then(|| request.headers.clone()),
+        }
+    }
+
+    /// Gets the value of the given query parameter, percent-decoded.
+    pub fn query_param(&self, name: impl AsRef<str>) -> Option<String> {
+        self.query
+            .split('&')
+            .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+            .find(|(key, _)| *key == name.as_ref())
+            .and_then(|(_, value)| value.percent_decode())
+            .and_then(|value| String::from_utf8(value).ok())
+    }
+
+    /// Gets the cookie with the given name, if it was sent.
+    pub fn get_cookie(&self, name: impl AsRef<str>) -> Option<&Cookie> {
+        self.cookies
+            .iter()
+            .find(|cookie| cookie.name == name.as_ref())
+    }
+
+    /// Gets the value of the given header, if it was sent and headers were retained.
+    pub fn get_header(&self, name: impl HeaderLike) -> Option<&str> {
+        self.headers.as_ref().and_then(|headers| headers.get(name))
+    }
+}
+
+impl ContextOptions {
+    /// Creates a new set of context options, which retains everything.
+    pub fn new() -> Self {
+        Self {
+            retain_headers: true,
+        }
+    }
+
+    /// Sets whether the full header map of the upgrade request is retained.
+    ///
+    /// The URI, query string and cookies are always retained.
+    pub fn with_headers(mut self, retain_headers: bool) -> Self {
+        self.retain_headers = retain_headers;
+        self
+    }
+}
+
+impl Default for ContextOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,5 +1,7 @@
 //! Provides error handling for the WebSocket crate.
 
+use humphrey::http::{Response, StatusCode};
+
 use std::error::Error;
 use std::fmt::Display;
 
@@ -18,10 +20,99 @@ pub enum WebsocketError {
     ConnectionClosed,
 }
 
+/// Represents a reason why a WebSocket upgrade request was not accepted.
+#[derive(Debug, PartialEq, Eq)]
+pub enum HandshakeError {
+    /// The request is not a WebSocket upgrade request.
+    NotUpgrade,
+    /// The `Sec-WebSocket-Key` header is missing.
+    MissingKey,
+    /// The `Sec-WebSocket-Key` header is not a base64-encoded 16-byte value.
+    InvalidKey,
+    /// The `Sec-WebSocket-Version` header specifies a version other than 13.
+    UnsupportedVersion,
+}
+
 impl Display for WebsocketError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            WebsocketError::ReadError => {
+                write!(f, "WebSocket error: failed to read from the stream")
+            }
+            WebsocketError::WriteError => {
+                write!(f, "WebSocket error: failed to write to the stream")
+            }
+            WebsocketError::HandshakeError => write!(f, "WebSocket error: the handshake failed"),
+            WebsocketError::InvalidOpcode => write!(f, "WebSocket error: invalid frame opcode"),
+            WebsocketError::ConnectionClosed => {
+                write!(f, "WebSocket error: the connection has been closed")
+            }
+        }
     }
 }
 
 impl Error for WebsocketError {}
+
+impl Display for HandshakeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HandshakeError::NotUpgrade => {
+                write!(f, "Handshake error: the request is not a WebSocket upgrade")
+            }
+            HandshakeError::MissingKey => {
+                write!(
+                    f,
+                    "Handshake error: the Sec-WebSocket-Key header is missing"
+                )
+            }
+            HandshakeError::InvalidKey => {
+                write!(
+                    f,
+                    "Handshake error: the Sec-WebSocket-Key header is invalid"
+                )
+            }
+            HandshakeError::UnsupportedVersion => {
+                write!(f, "Handshake error: the WebSocket version is not supported")
+            }
+        }
+    }
+}
+
+impl Error for HandshakeError {}
+
+impl From<HandshakeError> for WebsocketError {
+    fn from(_: HandshakeError) -> Self {
+        WebsocketError::HandshakeError
+    }
+}
+
+impl From<HandshakeError> for Response {
+    /// Converts the error into a `400 Bad Request` response rejecting the upgrade request.
+    ///
+    /// If the version is not supported, the supported version is included in the
+    ///   `Sec-WebSocket-Version` header so the client can try again.
+    fn from(error: HandshakeError) -> Self {
+        let response = Response::new(StatusCode::BadRequest, error.to_string());
+
+        match error {
+            HandshakeError::UnsupportedVersion => {
+                response.with_header("Sec-WebSocket-Version", "13")
+            }
+            _ => response,
+        }
+    }
+}
+
+#[cfg(feature = "error")]
+impl From<WebsocketError> for humphrey::error::Error {
+    fn from(error: WebsocketError) -> Self {
+        humphrey::error::Error::Websocket(Box::new(error))
+    }
+}
+
+#[cfg(feature = "error")]
+impl From<HandshakeError> for humphrey::error::Error {
+    fn from(error: HandshakeError) -> Self {
+        humphrey::error::Error::Websocket(Box::new(error))
+    }
+}
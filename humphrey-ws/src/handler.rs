@@ -1,13 +1,11 @@
 //! Provides a Humphrey-compatible WebSocket handler for performing the handshake.
 
+use crate::context::{ContextOptions, UpgradeContext};
 use crate::error::WebsocketError;
+use crate::handshake::accept_response;
 use crate::stream::WebsocketStream;
-use crate::util::base64::Base64Encode;
-use crate::util::sha1::SHA1Hash;
-use crate::MAGIC_STRING;
 
-use humphrey::http::headers::HeaderType;
-use humphrey::http::{Request, Response, StatusCode};
+use humphrey::http::{Request, Response};
 use humphrey::stream::Stream;
 
 use std::io::Write;
@@ -45,9 +43,36 @@ pub fn websocket_handler<T, S>(handler: T) -> impl Fn(Request, Stream, Arc<S>)
 where
     T: WebsocketHandler<S>,
 {
-    move |request: Request, mut stream: Stream, state: Arc<S>| {
-        if handshake(request, &mut stream).is_ok() {
-            handler(WebsocketStream::new(stream), state);
+    move |request: Request, stream: Stream, state: Arc<S>| {
+        if let Some(stream) = accept(request, stream, None, ContextOptions::default()) {
+            handler(stream, state);
+        }
+    }
+}
+
+/// Provides WebSocket handshake functionality for a path-aware WebSocket route.
+/// Supply a `WebsocketHandler` to handle the subsequent messages.
+///
+/// The route pattern is made available through the stream's `context()`, and the options control
+///   how much of the upgrade request is retained.
+///
+/// ## Example
+/// ```
+/// let app: App<()> = App::new().with_path_aware_websocket_route(
+///     "/ws/*",
+///     path_aware_websocket_handler(my_handler, ContextOptions::new().with_headers(false)),
+/// );
+/// ```
+pub fn path_aware_websocket_handler<T, S>(
+    handler: T,
+    options: ContextOptions,
+) -> impl Fn(Request, Stream, Arc<S>, &'static str)
+where
+    T: WebsocketHandler<S>,
+{
+    move |request: Request, stream: Stream, state: Arc<S>, route: &'static str| {
+        if let Some(stream) = accept(request, stream, Some(route), options) {
+            handler(stream, state);
         }
     }
 }
@@ -94,29 +119,51 @@ where
 pub fn async_websocket_handler<S>(
     hook: Arc<Mutex<Sender<WebsocketStream>>>,
 ) -> impl Fn(Request, Stream, Arc<S>) {
-    move |request: Request, mut stream: Stream, _: Arc<S>| {
-        if handshake(request, &mut stream).is_ok() {
-            hook.lock().unwrap().send(WebsocketStream::new(stream)).ok();
+    move |request: Request, stream: Stream, _: Arc<S>| {
+        if let Some(stream) = accept(request, stream, None, ContextOptions::default()) {
+            hook.lock().unwrap().send(stream).ok();
         }
     }
 }
 
-/// Performs the WebSocket handshake.
-fn handshake(request: Request, stream: &mut Stream) -> Result<(), WebsocketError> {
-    // Get the handshake key header
-    let handshake_key = request
-        .headers
-        .get("Sec-WebSocket-Key")
-        .ok_or(WebsocketError::HandshakeError)?;
+/// Provides asynchronous WebSocket functionality for a path-aware WebSocket route.
+/// Supply a hook to an asynchronous WebSocket app to handle the subsequent messages.
+///
+/// The route pattern is made available through the stream's `context()`, and the options control
+///   how much of the upgrade request is retained.
+pub fn path_aware_async_websocket_handler<S>(
+    hook: Arc<Mutex<Sender<WebsocketStream>>>,
+    options: ContextOptions,
+) -> impl Fn(Request, Stream, Arc<S>, &'static str) {
+    move |request: Request, stream: Stream, _: Arc<S>, route: &'static str| {
+        if let Some(stream) = accept(request, stream, Some(route), options) {
+            hook.lock().unwrap().send(stream).ok();
+        }
+    }
+}
 
-    // Calculate the handshake response
-    let sec_websocket_accept = format!("{}{}", handshake_key, MAGIC_STRING).hash().encode();
+/// Performs the WebSocket handshake and wraps the stream, retaining the context of the upgrade request.
+///
+/// If the handshake fails, the client is sent the reason and `None` is returned.
+fn accept(
+    request: Request,
+    mut stream: Stream,
+    route: Option<&'static str>,
+    options: ContextOptions,
+) -> Option<WebsocketStream> {
+    let context = UpgradeContext::new(&request, route, options);
+
+    handshake(&request, &mut stream)
+        .ok()
+        .map(|_| WebsocketStream::new(stream).with_context(context))
+}
 
-    // Serialise the handshake response
-    let response = Response::empty(StatusCode::SwitchingProtocols)
-        .with_header(HeaderType::Upgrade, "websocket")
-        .with_header(HeaderType::Connection, "Upgrade")
-        .with_header("Sec-WebSocket-Accept", sec_websocket_accept);
+/// Performs the WebSocket handshake, transmitting the response to the client.
+fn handshake(request: &Request, stream: &mut Stream) -> Result<(), WebsocketError> {
+    let (response, result) = match accept_response(request) {
+        Ok(response) => (response, Ok(())),
+        Err(e) => (Response::from(e), Err(WebsocketError::HandshakeError)),
+    };
 
     // Transmit the handshake response
     let response_bytes: Vec<u8> = response.into();
@@ -124,5 +171,5 @@ fn handshake(request: Request, stream: &mut Stream) -> Result<(), WebsocketError
         .write_all(&response_bytes)
         .map_err(|_| WebsocketError::WriteError)?;
 
-    Ok(())
+    result
 }
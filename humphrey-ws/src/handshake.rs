@@ -0,0 +1,67 @@
+//! Provides the WebSocket handshake as pure functions, independent of the connection.
+//!
+//! These functions only operate on requests and responses, so they can be used to perform the
+//!   upgrade on any server, for example one built with the Tokio version of Humphrey. Once the
+//!   response has been written, the connection speaks the WebSocket protocol.
+//!
+//! ## Example
+//! ```
+//! fn upgrade(request: Request) -> Response {
+//!     match handshake::accept_response(&request) {
+//!         Ok(response) => response,
+//!         Err(error) => error.into(),
+//!     }
+//! }
+//! ```
+
+use crate::error::HandshakeError;
+use crate::util::base64::{Base64Decode, Base64Encode};
+use crate::util::sha1::SHA1Hash;
+use crate::MAGIC_STRING;
+
+use humphrey::http::headers::HeaderType;
+use humphrey::http::{Request, Response, StatusCode};
+
+/// Validates the WebSocket upgrade request and generates the `101 Switching Protocols` response
+///   which completes the handshake.
+///
+/// The request must have an `Upgrade: websocket` header and a `Sec-WebSocket-Key` header containing
+///   a base64-encoded 16-byte value. If the `Sec-WebSocket-Version` header is present, it must be 13.
+pub fn accept_response(request: &Request) -> Result<Response, HandshakeError> {
+    let is_upgrade = request
+        .headers
+        .get(HeaderType::Upgrade)
+        .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"));
+
+    if !is_upgrade {
+        return Err(HandshakeError::NotUpgrade);
+    }
+
+    if request
+        .headers
+        .get("Sec-WebSocket-Version")
+        .is_some_and(|version| version.trim() != "13")
+    {
+        return Err(HandshakeError::UnsupportedVersion);
+    }
+
+    let key = request
+        .headers
+        .get("Sec-WebSocket-Key")
+        .ok_or(HandshakeError::MissingKey)?
+        .trim();
+
+    if key.len() != 24 || key.decode().map_or(true, |key| key.len() != 16) {
+        return Err(HandshakeError::InvalidKey);
+    }
+
+    Ok(Response::empty(StatusCode::SwitchingProtocols)
+        .with_header(HeaderType::Upgrade, "websocket")
+        .with_header(HeaderType::Connection, "Upgrade")
+        .with_header("Sec-WebSocket-Accept", derive_accept_key(key)))
+}
+
+/// Derives the value of the `Sec-WebSocket-Accept` header from the client's `Sec-WebSocket-Key`.
+pub fn derive_accept_key(key: &str) -> String {
+    format!("{}{}", key, MAGIC_STRING).hash().encode()
+}
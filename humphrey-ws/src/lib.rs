@@ -9,16 +9,21 @@
 const MAGIC_STRING: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
 
 pub mod async_app;
+pub mod context;
 pub mod error;
 pub mod handler;
+pub mod handshake;
 pub mod message;
 pub mod ping;
 pub mod stream;
 
 pub use handler::async_websocket_handler;
+pub use handler::path_aware_async_websocket_handler;
+pub use handler::path_aware_websocket_handler;
 pub use handler::websocket_handler;
 
 pub use async_app::{AsyncStream, AsyncWebsocketApp};
+pub use context::{ContextOptions, UpgradeContext};
 pub use message::Message;
 pub use stream::WebsocketStream;
 
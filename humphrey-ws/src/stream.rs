@@ -2,6 +2,7 @@
 
 use humphrey::stream::Stream;
 
+use crate::context::UpgradeContext;
 use crate::error::WebsocketError;
 use crate::frame::{Frame, Opcode};
 use crate::message::Message;
@@ -9,7 +10,17 @@ use crate::restion::Restion;
 
 use std::io::{Read, Write};
 use std::net::SocketAddr;
-use std::time::Instant;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The status code sent in close frames when the connection is closed normally.
+pub const NORMAL_CLOSURE: u16 = 1000;
+
+/// The status code reported when the peer's close frame does not include a status code.
+pub const NO_STATUS_RECEIVED: u16 = 1005;
+
+/// The default amount of time to wait for the peer to acknowledge a close frame.
+pub const DEFAULT_CLOSE_TIMEOUT: Duration = Duration::from_secs(3);
 
 /// Represents a WebSocket stream.
 ///
@@ -20,21 +31,47 @@ use std::time::Instant;
 pub struct WebsocketStream {
     pub(crate) stream: Stream,
     pub(crate) closed: bool,
+    pub(crate) close_code: Option<u16>,
+    pub(crate) close_timeout: Duration,
     pub(crate) last_pong: Instant,
+    pub(crate) context: Arc<UpgradeContext>,
 }
 
 impl WebsocketStream {
     /// Creates a new `WebsocketStream` wrapping an underlying Humphrey stream.
     ///
-    /// When the `WebsocketStream` is dropped, a close frame will be sent to the client.
+    /// When the `WebsocketStream` is dropped, it is closed with `close`.
     pub fn new(stream: Stream) -> Self {
         Self {
             stream,
             closed: false,
+            close_code: None,
+            close_timeout: DEFAULT_CLOSE_TIMEOUT,
             last_pong: Instant::now(),
+            context: Default::default(),
         }
     }
 
+    /// Sets the context of the upgrade request which opened the stream.
+    /// Returns itself for use in a builder pattern.
+    pub fn with_context(mut self, context: UpgradeContext) -> Self {
+        self.context = Arc::new(context);
+        self
+    }
+
+    /// Sets how long to wait for the peer to acknowledge a close frame before shutting down the
+    ///   connection anyway. By default, this is three seconds.
+    /// Returns itself for use in a builder pattern.
+    pub fn with_close_timeout(mut self, timeout: Duration) -> Self {
+        self.close_timeout = timeout;
+        self
+    }
+
+    /// Returns the context of the upgrade request which opened the stream.
+    pub fn context(&self) -> &UpgradeContext {
+        &self.context
+    }
+
     /// Blocks until a message is received from the client.
     pub fn recv(&mut self) -> Result<Message, WebsocketError> {
         let message = Message::from_stream(self);
@@ -78,6 +115,58 @@ impl WebsocketStream {
             .map_err(|_| WebsocketError::WriteError)
     }
 
+    /// Performs the closing handshake, as specified in [RFC 6455 Section 7](https://datatracker.ietf.org/doc/html/rfc6455#section-7).
+    ///
+    /// A close frame with status code 1000 is sent, then any data frames from the peer are discarded
+    ///   until it acknowledges with its own close frame or the close timeout expires, after which the
+    ///   underlying connection is shut down. Does nothing if the stream is already closed.
+    ///
+    /// Returns an error if the close frame could not be sent, or if the peer did not acknowledge it.
+    pub fn close(&mut self) -> Result<(), WebsocketError> {
+        if self.closed {
+            return Ok(());
+        }
+
+        self.closed = true;
+
+        let frame: Vec<u8> =
+            Frame::new(Opcode::Close, NORMAL_CLOSURE.to_be_bytes().to_vec()).into();
+        let result = self.send_raw(frame).and_then(|_| self.await_close());
+
+        self.stream.shutdown().ok();
+
+        result
+    }
+
+    /// Returns the status code of the close frame sent by the peer, or `None` if no close frame has
+    ///   been received, for example because the connection was lost.
+    pub fn close_code(&self) -> Option<u16> {
+        self.close_code
+    }
+
+    /// Discards frames from the peer until its close frame arrives or the close timeout expires.
+    fn await_close(&mut self) -> Result<(), WebsocketError> {
+        let deadline = Instant::now() + self.close_timeout;
+
+        loop {
+            let remaining = deadline
+                .checked_duration_since(Instant::now())
+                .filter(|remaining| !remaining.is_zero())
+                .ok_or(WebsocketError::ReadError)?;
+
+            self.stream
+                .set_timeout(Some(remaining))
+                .map_err(|_| WebsocketError::ReadError)?;
+
+            let frame = Frame::from_stream(&mut self.stream)?;
+
+            if frame.opcode == Opcode::Close {
+                self.close_code = Some(close_code(&frame.payload));
+                return Ok(());
+            }
+        }
+    }
+
     /// Attempts to get the peer address of this stream.
     pub fn peer_addr(&self) -> Result<SocketAddr, std::io::Error> {
         self.stream.peer_addr()
@@ -133,10 +222,14 @@ impl Write for WebsocketStream {
 
 impl Drop for WebsocketStream {
     fn drop(&mut self) {
-        if !self.closed {
-            self.stream
-                .write_all(Frame::new(Opcode::Close, Vec::new()).as_ref())
-                .ok();
-        }
+        self.close().ok();
+    }
+}
+
+/// Gets the status code from the payload of a close frame.
+pub(crate) fn close_code(payload: &[u8]) -> u16 {
+    match payload {
+        [high, low, ..] => u16::from_be_bytes([*high, *low]),
+        _ => NO_STATUS_RECEIVED,
     }
 }
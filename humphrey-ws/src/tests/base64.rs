@@ -35,3 +35,13 @@ fn test_base64_decode() {
     let padding_2_result = padding_2_input.decode().unwrap();
     assert_eq!(padding_2_result, padding_2_expected);
 }
+
+#[test]
+fn test_base64_decode_symbols() {
+    let input = "+/+/";
+    let expected = [0xfb, 0xff, 0xbf];
+    assert_eq!(input.decode().unwrap(), expected);
+
+    let round_trip = [0xfb, 0xef, 0xbe, 0xff];
+    assert_eq!(round_trip.encode().decode().unwrap(), round_trip);
+}
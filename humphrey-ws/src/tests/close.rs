@@ -0,0 +1,60 @@
+use crate::error::WebsocketError;
+use crate::message::Message;
+use crate::stream::{WebsocketStream, NORMAL_CLOSURE};
+
+use humphrey::stream::Stream;
+
+use std::net::{TcpListener, TcpStream};
+use std::thread::spawn;
+use std::time::{Duration, Instant};
+
+fn stream_pair() -> (WebsocketStream, WebsocketStream) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (server, _) = listener.accept().unwrap();
+
+    (
+        WebsocketStream::new(Stream::Tcp(server)),
+        WebsocketStream::new(Stream::Tcp(client)),
+    )
+}
+
+#[test]
+fn test_clean_close() {
+    let (mut server, mut client) = stream_pair();
+
+    let closing = spawn(move || {
+        let result = server.close();
+        (result, server.close_code())
+    });
+
+    // Data sent before the client sees the close frame is discarded by the server
+    client.send(Message::new("ignored")).unwrap();
+
+    assert!(matches!(
+        client.recv(),
+        Err(WebsocketError::ConnectionClosed)
+    ));
+    assert_eq!(client.close_code(), Some(NORMAL_CLOSURE));
+
+    let (result, code) = closing.join().unwrap();
+    assert!(result.is_ok());
+    assert_eq!(code, Some(NORMAL_CLOSURE));
+}
+
+#[test]
+fn test_close_timeout() {
+    let (server, _client) = stream_pair();
+    let mut server = server.with_close_timeout(Duration::from_millis(200));
+
+    let start = Instant::now();
+    let result = server.close();
+
+    assert!(result.is_err());
+    assert_eq!(server.close_code(), None);
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    assert!(start.elapsed() < Duration::from_secs(2));
+
+    // Closing again does nothing
+    assert!(server.close().is_ok());
+}
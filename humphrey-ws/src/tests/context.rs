@@ -0,0 +1,91 @@
+use crate::context::ContextOptions;
+use crate::handler::path_aware_websocket_handler;
+use crate::stream::WebsocketStream;
+
+use humphrey::cancellation::CancellationToken;
+use humphrey::http::address::Address;
+use humphrey::http::headers::{HeaderType, Headers};
+use humphrey::http::method::Method;
+use humphrey::http::request::BodyState;
+use humphrey::http::Request;
+use humphrey::route::RouteParams;
+use humphrey::stream::Stream;
+
+use std::io::Read;
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::channel;
+use std::sync::{Arc, Mutex};
+
+fn upgrade_request() -> Request {
+    let mut headers = Headers::new();
+    headers.add(HeaderType::Upgrade, "websocket");
+    headers.add(HeaderType::Cookie, "session=abc; theme=dark");
+    headers.add("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ==");
+
+    Request {
+        method: Method::Get,
+        overridden_method: None,
+        uri: "/ws/room1".into(),
+        uri_raw: "/ws/room1".into(),
+        query: "name=Humphrey%20Bot&empty".into(),
+        version: "HTTP/1.1".into(),
+        headers,
+        content: None,
+        body_state: BodyState::Complete,
+        trailers: Headers::new(),
+        address: Address::new("127.0.0.1:80").unwrap(),
+        secure: false,
+        local_addr: None,
+        deadline: None,
+        trace: None,
+        cancellation: CancellationToken::default(),
+        params: RouteParams::default(),
+    }
+}
+
+/// Runs the given handler on a real connection, returning the context of the resulting stream.
+fn run_handler(options: ContextOptions) -> crate::context::UpgradeContext {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (server, _) = listener.accept().unwrap();
+
+    let (tx, rx) = channel();
+    let tx = Mutex::new(tx);
+
+    let handler = path_aware_websocket_handler(
+        move |stream: WebsocketStream, _: Arc<()>| {
+            tx.lock().unwrap().send(stream.context().clone()).unwrap();
+        },
+        options,
+    );
+
+    handler(upgrade_request(), Stream::Tcp(server), Arc::new(()), "/ws/*");
+
+    let mut response = [0; 12];
+    client.read_exact(&mut response).unwrap();
+    assert_eq!(&response, b"HTTP/1.1 101");
+
+    rx.recv().unwrap()
+}
+
+#[test]
+fn test_context_retained() {
+    let context = run_handler(ContextOptions::new());
+
+    assert_eq!(context.uri, "/ws/room1");
+    assert_eq!(context.route, Some("/ws/*"));
+    assert_eq!(context.query_param("name"), Some("Humphrey Bot".into()));
+    assert_eq!(context.query_param("empty"), Some(String::new()));
+    assert_eq!(context.query_param("missing"), None);
+    assert_eq!(context.get_cookie("theme").unwrap().value, "dark");
+    assert_eq!(context.get_header(HeaderType::Upgrade), Some("websocket"));
+}
+
+#[test]
+fn test_context_without_headers() {
+    let context = run_handler(ContextOptions::new().with_headers(false));
+
+    assert!(context.headers.is_none());
+    assert_eq!(context.get_header(HeaderType::Upgrade), None);
+    assert_eq!(context.get_cookie("session").unwrap().value, "abc");
+}
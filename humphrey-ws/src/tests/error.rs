@@ -0,0 +1,13 @@
+use crate::error::WebsocketError;
+
+use humphrey::error::Error;
+use humphrey::http::StatusCode;
+
+#[test]
+fn test_websocket_conversion() {
+    let error = Error::from(WebsocketError::HandshakeError);
+
+    assert!(matches!(error, Error::Websocket(_)));
+    assert_eq!(error.status_code(), StatusCode::InternalError);
+    assert_eq!(error.to_string(), "WebSocket error: the handshake failed");
+}
@@ -0,0 +1,115 @@
+use crate::error::HandshakeError;
+use crate::handshake::{accept_response, derive_accept_key};
+
+use humphrey::cancellation::CancellationToken;
+use humphrey::http::address::Address;
+use humphrey::http::headers::{HeaderType, Headers};
+use humphrey::http::method::Method;
+use humphrey::http::request::BodyState;
+use humphrey::http::{Request, Response, StatusCode};
+use humphrey::route::RouteParams;
+
+/// The example key from RFC 6455 section 1.3.
+const KEY: &str = "dGhlIHNhbXBsZSBub25jZQ==";
+
+fn upgrade_request(headers: &[(&str, &str)]) -> Request {
+    let mut request_headers = Headers::new();
+    for (name, value) in headers {
+        request_headers.add(*name, *value);
+    }
+
+    Request {
+        method: Method::Get,
+        overridden_method: None,
+        uri: "/chat".into(),
+        uri_raw: "/chat".into(),
+        query: String::new(),
+        version: "HTTP/1.1".into(),
+        headers: request_headers,
+        content: None,
+        body_state: BodyState::Complete,
+        trailers: Headers::new(),
+        address: Address::new("127.0.0.1:80").unwrap(),
+        secure: false,
+        local_addr: None,
+        deadline: None,
+        trace: None,
+        cancellation: CancellationToken::default(),
+        params: RouteParams::default(),
+    }
+}
+
+#[test]
+fn test_derive_accept_key() {
+    assert_eq!(derive_accept_key(KEY), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+}
+
+#[test]
+fn test_accept_response() {
+    let request = upgrade_request(&[
+        ("Upgrade", "websocket"),
+        ("Connection", "Upgrade"),
+        ("Sec-WebSocket-Key", KEY),
+        ("Sec-WebSocket-Version", "13"),
+    ]);
+
+    let response = accept_response(&request).unwrap();
+    assert_eq!(response.status_code, StatusCode::SwitchingProtocols);
+    assert_eq!(response.headers.get(HeaderType::Upgrade), Some("websocket"));
+    assert_eq!(
+        response.headers.get(HeaderType::Connection),
+        Some("Upgrade")
+    );
+    assert_eq!(
+        response.headers.get("Sec-WebSocket-Accept"),
+        Some("s3pPLMBiTxaQ9kYGzzhZRbK+xOo=")
+    );
+    assert!(response.body.is_empty());
+
+    // The version is optional, and the upgrade token is case-insensitive
+    let request = upgrade_request(&[("Upgrade", "WebSocket"), ("Sec-WebSocket-Key", KEY)]);
+    assert!(accept_response(&request).is_ok());
+}
+
+#[test]
+fn test_missing_or_invalid_key() {
+    let request = upgrade_request(&[("Upgrade", "websocket")]);
+    assert_eq!(
+        accept_response(&request).unwrap_err(),
+        HandshakeError::MissingKey
+    );
+
+    for key in [
+        "",
+        "not base64 at all!!!!!!!",
+        "Zm9v",
+        "dGhlIHNhbXBsZSBub25jZQ==dGhl",
+    ] {
+        let request = upgrade_request(&[("Upgrade", "websocket"), ("Sec-WebSocket-Key", key)]);
+        assert_eq!(
+            accept_response(&request).unwrap_err(),
+            HandshakeError::InvalidKey
+        );
+    }
+}
+
+#[test]
+fn test_rejected_requests() {
+    let request = upgrade_request(&[("Sec-WebSocket-Key", KEY)]);
+    assert_eq!(
+        accept_response(&request).unwrap_err(),
+        HandshakeError::NotUpgrade
+    );
+
+    let request = upgrade_request(&[
+        ("Upgrade", "websocket"),
+        ("Sec-WebSocket-Key", KEY),
+        ("Sec-WebSocket-Version", "8"),
+    ]);
+    let error = accept_response(&request).unwrap_err();
+    assert_eq!(error, HandshakeError::UnsupportedVersion);
+
+    let response = Response::from(error);
+    assert_eq!(response.status_code, StatusCode::BadRequest);
+    assert_eq!(response.headers.get("Sec-WebSocket-Version"), Some("13"));
+}
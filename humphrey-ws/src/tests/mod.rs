@@ -1,4 +1,9 @@
 mod base64;
+mod close;
+mod context;
+#[cfg(feature = "error")]
+mod error;
 mod frame;
+mod handshake;
 mod mock_stream;
 mod sha1;
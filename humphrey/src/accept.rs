@@ -0,0 +1,298 @@
+//! Provides resilient handling of errors when accepting connections.
+//!
+//! When the process runs out of file descriptors, accepting a connection fails immediately for as
+//!   long as the condition lasts, so retrying straight away would spin at full CPU usage. Instead,
+//!   the accept loop backs off exponentially and reports the condition through the monitor at most
+//!   once per second. Optionally, a reserve file descriptor is held which can be closed to accept
+//!   and immediately close one pending connection, so that clients are not left waiting in the
+//!   listener's queue. Errors which mean that the listener itself has failed, such as it having
+//!   been closed, stop the accept loop instead of being retried.
+//!
+//! The number of connections being handled at once can also be limited, in which case connections
+//!   beyond the limit are answered with `503 Service Unavailable` and closed as soon as they are
+//!   accepted, instead of waiting in the queue of the thread pool for as long as the load lasts.
+
+use crate::monitor::event::EventType;
+use crate::monitor::MonitorConfig;
+
+use std::fs::File;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "tokio")]
+use std::task::{Context, Poll};
+
+/// The minimum time between monitor events reporting that the process has run out of file
+///   descriptors, with any errors in between counted as suppressed.
+const EVENT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The file which is opened to hold the reserve file descriptor.
+#[cfg(unix)]
+const RESERVE_PATH: Option<&str> = Some("/dev/null");
+#[cfg(windows)]
+const RESERVE_PATH: Option<&str> = Some("NUL");
+#[cfg(not(any(unix, windows)))]
+const RESERVE_PATH: Option<&str> = None;
+
+/// The OS error codes which indicate that the process or system has run out of file descriptors.
+#[cfg(unix)]
+const EXHAUSTION_CODES: &[i32] = &[23, 24]; // `ENFILE` and `EMFILE`
+#[cfg(windows)]
+const EXHAUSTION_CODES: &[i32] = &[10024]; // `WSAEMFILE`
+#[cfg(not(any(unix, windows)))]
+const EXHAUSTION_CODES: &[i32] = &[];
+
+/// The OS error codes which indicate that the listener itself can no longer accept connections.
+#[cfg(unix)]
+const FAILURE_CODES: &[i32] = &[9, 22]; // `EBADF` and `EINVAL`
+#[cfg(windows)]
+const FAILURE_CODES: &[i32] = &[10009, 10022, 10038]; // `WSAEBADF`, `WSAEINVAL` and `WSAENOTSOCK`
+#[cfg(not(any(unix, windows)))]
+const FAILURE_CODES: &[i32] = &[];
+
+/// The response written to connections which are rejected because the app is already handling its
+///   maximum number of connections.
+pub(crate) const OVER_CAPACITY_RESPONSE: &[u8] =
+    b"HTTP/1.1 503 Service Unavailable\r\nConnection: close\r\nContent-Length: 0\r\n\r\n";
+
+/// Represents a source of incoming connections, such as a TCP listener.
+///
+/// This is implemented for `TcpListener`, and allows the accept loop to be driven by other
+///   listeners, for example in tests.
+#[cfg(not(feature = "tokio"))]
+pub trait Listener {
+    /// The type of the accepted connections.
+    type Stream;
+
+    /// Accepts a new connection.
+    fn accept(&self) -> io::Result<Self::Stream>;
+}
+
+/// Represents a source of incoming connections, such as a TCP listener.
+///
+/// This is implemented for `TcpListener`, and allows the accept loop to be driven by other
+///   listeners, for example in tests.
+#[cfg(feature = "tokio")]
+pub trait Listener {
+    /// The type of the accepted connections.
+    type Stream;
+
+    /// Polls to accept a new connection.
+    fn poll_accept(&self, cx: &mut Context<'_>) -> Poll<io::Result<Self::Stream>>;
+}
+
+/// Represents the options which control how connections are accepted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AcceptOptions {
+    /// The maximum number of connections to accept at once before checking for shutdown again.
+    ///
+    /// Larger bursts increase throughput under heavy load, but delay shutdown by up to the time
+    ///   taken to accept that many connections.
+    pub burst: usize,
+    /// The time to wait before retrying after the process first runs out of file descriptors.
+    pub min_backoff: Duration,
+    /// The maximum time to wait between retries while the process is out of file descriptors.
+    pub max_backoff: Duration,
+    /// Whether to hold a reserve file descriptor which can be closed to drain the listener's queue
+    ///   when the process is out of file descriptors.
+    pub reserve_fd: bool,
+    /// The time to wait between checks for new connections when the listener is nonblocking and no
+    ///   connections are waiting. This is used by the synchronous app when a shutdown signal is set.
+    pub poll_interval: Duration,
+}
+
+/// Counts the connections being handled, so that connections beyond the maximum can be rejected.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ConnectionLimit {
+    max: Option<usize>,
+    count: Arc<AtomicUsize>,
+}
+
+/// Represents a connection counted by a `ConnectionLimit`, which stops being counted when this is
+///   dropped, including when its handler panics.
+pub(crate) struct ConnectionPermit(Arc<AtomicUsize>);
+
+/// Tracks errors while accepting connections, calculating the backoff and rate-limiting events.
+pub(crate) struct AcceptErrors {
+    min_backoff: Duration,
+    max_backoff: Duration,
+    delay: Option<Duration>,
+    last_event: Option<Instant>,
+    suppressed: usize,
+    reserve_enabled: bool,
+    reserve: Option<File>,
+}
+
+impl Default for AcceptOptions {
+    fn default() -> Self {
+        Self {
+            burst: 32,
+            min_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+            reserve_fd: true,
+            poll_interval: Duration::from_millis(5),
+        }
+    }
+}
+
+impl AcceptErrors {
+    /// Creates a new error tracker, opening the reserve file descriptor if enabled.
+    pub fn new(options: &AcceptOptions) -> Self {
+        Self {
+            min_backoff: options.min_backoff,
+            max_backoff: options.max_backoff,
+            delay: None,
+            last_event: None,
+            suppressed: 0,
+            reserve_enabled: options.reserve_fd,
+            reserve: options.reserve_fd.then(open_reserve).flatten(),
+        }
+    }
+
+    /// Resets the backoff after a connection is successfully accepted.
+    pub fn reset(&mut self) {
+        self.delay = None;
+    }
+
+    /// Records an error from accepting a connection.
+    ///
+    /// If the process has run out of file descriptors, the backoff is increased and the time to wait
+    ///   before retrying is returned. Otherwise, the error is reported as a connection error and
+    ///   the loop can continue straight away.
+    pub fn record(&mut self, error: &io::Error, monitor: &MonitorConfig) -> Option<Duration> {
+        if !is_exhaustion(error) {
+            monitor.send_with(EventType::ConnectionError, |event| {
+                event.with_info(error.to_string())
+            });
+
+            return None;
+        }
+
+        let delay = self
+            .delay
+            .map_or(self.min_backoff, |delay| delay * 2)
+            .min(self.max_backoff);
+        self.delay = Some(delay);
+
+        if self
+            .last_event
+            .is_none_or(|last_event| last_event.elapsed() >= EVENT_INTERVAL)
+        {
+            let suppressed = self.suppressed;
+            monitor.send_with(EventType::AcceptBackoff, |event| {
+                event.with_info(match suppressed {
+                    0 => format!("{}, retrying in {}ms", error, delay.as_millis()),
+                    n => format!(
+                        "{}, retrying in {}ms ({} similar errors suppressed)",
+                        error,
+                        delay.as_millis(),
+                        n
+                    ),
+                })
+            });
+            self.last_event = Some(Instant::now());
+            self.suppressed = 0;
+        } else {
+            self.suppressed += 1;
+        }
+
+        Some(delay)
+    }
+
+    /// Closes the reserve file descriptor so that a pending connection can be accepted and closed,
+    ///   returning `false` if there was no reserve to close.
+    pub fn release_reserve(&mut self) -> bool {
+        self.reserve.take().is_some()
+    }
+
+    /// Reopens the reserve file descriptor if it is enabled and not currently held.
+    pub fn restore_reserve(&mut self) {
+        if self.reserve_enabled && self.reserve.is_none() {
+            self.reserve = open_reserve();
+        }
+    }
+}
+
+impl ConnectionLimit {
+    /// Creates a limit of the given number of connections, or no limit if `None`.
+    pub fn new(max: Option<usize>) -> Self {
+        Self {
+            max,
+            count: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Counts a new connection, returning `None` if the maximum number of connections are already
+    ///   being handled.
+    pub fn acquire(&self) -> Option<ConnectionPermit> {
+        let max = self.max.unwrap_or(usize::MAX);
+
+        self.count
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                (count < max).then_some(count + 1)
+            })
+            .ok()?;
+
+        Some(ConnectionPermit(self.count.clone()))
+    }
+
+    /// Returns the number of connections being handled.
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::SeqCst)
+    }
+
+    /// Describes the number of connections being handled, for the information of monitor events.
+    pub fn describe(&self) -> String {
+        match self.max {
+            Some(max) => format!("{}/{} connections", self.count.load(Ordering::SeqCst), max),
+            None => format!("{} connections", self.count.load(Ordering::SeqCst)),
+        }
+    }
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(not(feature = "tokio"))]
+impl Listener for std::net::TcpListener {
+    type Stream = std::net::TcpStream;
+
+    fn accept(&self) -> io::Result<Self::Stream> {
+        std::net::TcpListener::accept(self).map(|(stream, _)| stream)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Listener for tokio::net::TcpListener {
+    type Stream = tokio::net::TcpStream;
+
+    fn poll_accept(&self, cx: &mut Context<'_>) -> Poll<io::Result<Self::Stream>> {
+        tokio::net::TcpListener::poll_accept(self, cx).map_ok(|(stream, _)| stream)
+    }
+}
+
+/// Returns `true` if the error indicates that the process or system has run out of file descriptors.
+fn is_exhaustion(error: &io::Error) -> bool {
+    error
+        .raw_os_error()
+        .is_some_and(|code| EXHAUSTION_CODES.contains(&code))
+}
+
+/// Returns `true` if the error indicates that the listener can no longer accept connections, so
+///   retrying would fail forever.
+pub(crate) fn is_listener_failure(error: &io::Error) -> bool {
+    error.kind() == io::ErrorKind::InvalidInput
+        || error
+            .raw_os_error()
+            .is_some_and(|code| FAILURE_CODES.contains(&code))
+}
+
+/// Opens the reserve file descriptor, if possible on this platform.
+fn open_reserve() -> Option<File> {
+    RESERVE_PATH.and_then(|path| File::open(path).ok())
+}
@@ -2,30 +2,69 @@
 
 #![allow(clippy::new_without_default)]
 
+use crate::accept::{
+    is_listener_failure, AcceptErrors, AcceptOptions, ConnectionLimit, Listener,
+    OVER_CAPACITY_RESPONSE,
+};
+use crate::drain::{draining_response, DrainOptions, DrainSignal};
+use crate::endpoint::{Endpoint, EndpointKind};
+#[cfg(feature = "tls")]
+use crate::endpoint::{redirect_error, redirect_response, REDIRECT_TIMEOUT};
+use crate::http::body::RequestBody;
 use crate::http::cors::Cors;
 use crate::http::date::DateTime;
-use crate::http::headers::HeaderType;
-use crate::http::method::Method;
-use crate::http::request::{Request, RequestError};
+use crate::http::headers::{HeaderLike, HeaderType, Headers};
+use crate::http::method::{Method, MethodOverride};
+use crate::http::request::{
+    BodyDeferral, BodyState, HeadCondition, Request, RequestError, RequestLimits,
+};
 use crate::http::response::Response;
 use crate::http::status::StatusCode;
-use crate::krauss::wildcard_match;
+use crate::keep_alive::{IdleConnections, KeepAliveConfig};
+use crate::middleware::{
+    find_middleware, run_request_middleware, run_response_middleware, Middleware,
+};
 use crate::monitor::event::{Event, EventType};
+use crate::monitor::timing::{RequestDetails, RequestTimer};
 use crate::monitor::MonitorConfig;
-use crate::route::{Route, RouteHandler, SubApp};
+use crate::proxy_protocol;
+use crate::rate_limit::{rate_limited_response, RateLimitConfig, RateLimiter};
+use crate::recording::{Recorder, RecordingConfig};
+use crate::route::{
+    allow_header, apply_default_headers, apply_method_override, apply_trailing_slash,
+    build_indexes, find_route, find_streaming_route, find_subapp, handle_error, handler_request,
+    ErrorCause, ErrorContext, RouteHandler, RouteMatch, StreamingRouteHandler, SubApp,
+    TrailingSlash, UpgradeDecision, WebsocketGuard, WebsocketRouteHandler,
+};
+use crate::security::SecurityOptions;
+use crate::shutdown::{AppCounters, ExitReason, ShutdownSignal, ShutdownSummary};
 use crate::stream::Stream;
+use crate::test_client::TestClient;
+use crate::thread::context::RequestContext;
 use crate::thread::pool::ThreadPool;
-
-use std::io::Write;
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::Receiver;
-use std::sync::Arc;
+use crate::trace::TraceOptions;
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Write};
+use std::net::{IpAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+#[cfg(feature = "tls")]
+use crate::tls::{self, CertError};
 #[cfg(feature = "tls")]
 use rustls::ServerConfig;
+#[cfg(feature = "tls")]
+use std::net::SocketAddr;
+#[cfg(feature = "tls")]
+use std::path::Path;
+
+/// The ID of the next connection to be handled, used to identify connections in request contexts.
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(0);
 
 /// Represents the Humphrey app.
 ///
@@ -39,39 +78,171 @@ where
     thread_pool: ThreadPool,
     subapps: Vec<SubApp<State>>,
     default_subapp: SubApp<State>,
-    error_handler: ErrorHandler,
+    error_handler: ErrorHandler<State>,
+    error_context_handler: Option<ErrorHandler<State>>,
     state: Arc<State>,
     monitor: MonitorConfig,
     connection_handler: ConnectionHandler<State>,
     connection_condition: ConnectionCondition<State>,
-    connection_timeout: Option<Duration>,
-    shutdown: Option<Receiver<()>>,
+    request_condition: Option<RequestCondition<State>>,
+    request_rewriter: Option<RequestRewriter<State>>,
+    connection_options: ConnectionOptions,
+    accept_options: AcceptOptions,
+    connection_limit: ConnectionLimit,
+    shutdown: Option<ShutdownSignal>,
     #[cfg(feature = "tls")]
     tls_config: Option<Arc<ServerConfig>>,
     #[cfg(feature = "tls")]
     force_https: bool,
+    #[cfg(feature = "tls")]
+    force_https_addr: SocketAddr,
 }
 
 /// Represents a function able to handle a connection.
 /// In most cases, the default connection handler should be used.
-pub type ConnectionHandler<State> = fn(
+pub type ConnectionHandler<State> = Box<
+    dyn Fn(
+            Stream,
+            Arc<Vec<SubApp<State>>>,
+            Arc<SubApp<State>>,
+            Arc<ErrorHandler<State>>,
+            Arc<State>,
+            MonitorConfig,
+            Arc<ConnectionOptions>,
+        ) + Send
+        + Sync,
+>;
+
+/// Represents a function pointer able to handle a connection.
+///
+/// ## Deprecated
+/// `with_custom_connection_handler` now accepts closures, which are stored as a `ConnectionHandler`.
+#[deprecated(since = "0.8.0", note = "Please use `ConnectionHandler` instead")]
+pub type ConnectionHandlerFn<State> = fn(
     Stream,
     Arc<Vec<SubApp<State>>>,
     Arc<SubApp<State>>,
-    Arc<ErrorHandler>,
+    Arc<ErrorHandler<State>>,
     Arc<State>,
     MonitorConfig,
-    Option<Duration>,
+    Arc<ConnectionOptions>,
 );
 
+/// Represents the options which apply to every connection handled by the app.
+///
+/// These are shared between connections and passed to the connection handler, so custom connection
+///   handlers should respect them where possible.
+#[derive(Default)]
+pub struct ConnectionOptions {
+    /// The amount of time to wait between keep-alive requests, if any.
+    pub timeout: Option<Duration>,
+    /// The recorder for raw requests, if request recording is enabled.
+    pub recorder: Option<Recorder>,
+    /// The request condition, already bound to the app's state, if one was set.
+    pub request_condition: Option<BoundRequestCondition>,
+    /// The request rewriter, already bound to the app's state, if one was set.
+    pub request_rewriter: Option<BoundRequestRewriter>,
+    /// The maximum number of workers which can be held by WebSocket connections at once, if any.
+    pub websocket_limit: Option<usize>,
+    /// The number of workers currently held by WebSocket connections.
+    pub websocket_workers: Arc<AtomicUsize>,
+    /// The maximum number of WebSocket connections which can be open from a single IP address at
+    ///   once, if any.
+    pub websocket_limit_per_ip: Option<usize>,
+    /// The number of WebSocket connections currently open from each IP address.
+    ///
+    /// Addresses are removed as soon as their last connection closes.
+    pub websocket_clients: Arc<Mutex<HashMap<IpAddr, usize>>>,
+    /// The signal which reports whether the app has started draining connections.
+    pub drain_signal: DrainSignal,
+    /// The options for draining connections during graceful shutdown, if enabled.
+    pub drain_options: Option<DrainOptions>,
+    /// The security-related headers added to responses and the limits applied to requests.
+    pub security: SecurityOptions,
+    /// Where `POST` requests can specify another method to be handled as, if enabled.
+    pub method_override: Option<MethodOverride>,
+    /// The proxies whose `X-Request-Deadline` header is used as the deadline of their requests.
+    pub deadline_proxies: Vec<IpAddr>,
+    /// The options for trace propagation, if enabled.
+    pub trace: Option<TraceOptions>,
+    /// The rate limiter which requests are counted against, if rate limiting is enabled.
+    pub rate_limiter: Option<RateLimiter>,
+    /// The counts of connections and responses from which the app's `ShutdownSummary` is built.
+    pub counters: AppCounters,
+    /// Whether each connection starts with a PROXY protocol header giving the address of the client.
+    pub proxy_protocol: bool,
+    /// How long keep-alive connections are kept open while idle and how many requests can be sent
+    ///   on each of them, if limited.
+    pub keep_alive: Option<KeepAliveConfig>,
+    /// The connections which are waiting for their next request, which are closed when the app
+    ///   shuts down.
+    pub idle_connections: IdleConnections,
+}
+
+/// Reports the number of WebSocket connections currently open, both in total and from each IP
+///   address.
+///
+/// This is returned by `App::websocket_connections`, and shares its counts with the running app.
+#[derive(Clone)]
+pub struct WebsocketConnections {
+    total: Arc<AtomicUsize>,
+    clients: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+/// Represents a request condition which has been given access to the app's state.
+pub type BoundRequestCondition = Box<dyn Fn(&Request) -> Option<Response> + Send + Sync>;
+
+/// Represents a request rewriter which has been given access to the app's state.
+pub type BoundRequestRewriter = Box<dyn Fn(&mut Request) -> Option<Response> + Send + Sync>;
+
 /// Represents a function able to calculate whether a connection will be accepted.
-pub type ConnectionCondition<State> = fn(&mut TcpStream, Arc<State>) -> bool;
+pub type ConnectionCondition<State> = Box<dyn Fn(&mut TcpStream, Arc<State>) -> bool + Send + Sync>;
+
+/// Represents a function pointer able to calculate whether a connection will be accepted.
+///
+/// ## Deprecated
+/// `with_connection_condition` now accepts closures, which are stored as a `ConnectionCondition`.
+#[deprecated(since = "0.8.0", note = "Please use `ConnectionCondition` instead")]
+pub type ConnectionConditionFn<State> = fn(&mut TcpStream, Arc<State>) -> bool;
+
+/// Represents a function able to reject a request once its head has been parsed, but before its
+///   body has been read.
+///
+/// The request passed to the function has no content. Returning `Some` rejects the request with the
+///   given response.
+pub type RequestCondition<State> =
+    Box<dyn Fn(&Request, Arc<State>) -> Option<Response> + Send + Sync>;
+
+/// Represents a function pointer able to reject a request once its head has been parsed.
+///
+/// ## Deprecated
+/// `with_request_condition` now accepts closures, which are stored as a `RequestCondition`.
+#[deprecated(since = "0.8.0", note = "Please use `RequestCondition` instead")]
+pub type RequestConditionFn<State> = fn(&Request, Arc<State>) -> Option<Response>;
+
+/// Represents a function able to modify a request after it has been parsed but before it is routed,
+///   for example to normalise its path.
+///
+/// Returning `Some` rejects the request with the given response instead of routing it.
+pub type RequestRewriter<State> =
+    Box<dyn Fn(&mut Request, Arc<State>) -> Option<Response> + Send + Sync>;
+
+/// Represents a function pointer able to modify a request before it is routed.
+///
+/// ## Deprecated
+/// `with_request_rewriter` now accepts closures, which are stored as a `RequestRewriter`.
+#[deprecated(since = "0.8.0", note = "Please use `RequestRewriter` instead")]
+pub type RequestRewriterFn<State> = fn(&mut Request, Arc<State>) -> Option<Response>;
 
 pub use crate::handler_traits::*;
 
 /// Represents a function able to handle an error.
-/// The first parameter of type `Option<Request>` will be `Some` if the request could be parsed.
-/// Otherwise, it will be `None` and the status code will be `StatusCode::BadRequest`.
+/// The first parameter is an `ErrorContext` describing the error, including the request if it could
+///   be parsed, and the second is the app's state.
+///
+/// Error handlers which only need the status code can be set with `with_error_handler`, those which
+///   also use the request and state with `with_error_handler_with_state`, and those which use the
+///   whole context with `with_error_context_handler`.
 ///
 /// Every app has a default error handler, which simply displays the status code.
 /// The source code for this default error handler is copied below since it is a good example.
@@ -88,7 +259,18 @@ pub use crate::handler_traits::*;
 ///     Response::new(status_code, body.as_bytes())
 /// }
 /// ```
-pub type ErrorHandler = fn(StatusCode) -> Response;
+///
+/// Closures are also accepted, so the error handler can capture configuration such as a branded
+///   error page.
+pub type ErrorHandler<State = ()> =
+    Box<dyn Fn(&ErrorContext, Arc<State>) -> Response + Send + Sync>;
+
+/// Represents a function pointer able to handle an error.
+///
+/// ## Deprecated
+/// `with_error_handler` now accepts closures, which are stored as an `ErrorHandler`.
+#[deprecated(since = "0.8.0", note = "Please use `ErrorHandler` instead")]
+pub type ErrorHandlerFn = fn(StatusCode) -> Response;
 
 /// Represents a generic error with the program.
 pub type HumphreyError = Box<dyn std::error::Error>;
@@ -111,17 +293,24 @@ where
             thread_pool: ThreadPool::new(32),
             subapps: Vec::new(),
             default_subapp: SubApp::default(),
-            error_handler,
+            error_handler: Box::new(|context, _| error_handler(context.status_code)),
+            error_context_handler: None,
             state: Arc::new(State::default()),
             monitor: MonitorConfig::default(),
-            connection_handler: client_handler,
-            connection_condition: |_, _| true,
-            connection_timeout: None,
+            connection_handler: Box::new(client_handler),
+            connection_condition: Box::new(|_, _| true),
+            request_condition: None,
+            request_rewriter: None,
+            connection_options: ConnectionOptions::default(),
+            accept_options: AcceptOptions::default(),
+            connection_limit: ConnectionLimit::default(),
             shutdown: None,
             #[cfg(feature = "tls")]
             tls_config: None,
             #[cfg(feature = "tls")]
             force_https: false,
+            #[cfg(feature = "tls")]
+            force_https_addr: SocketAddr::from(([0, 0, 0, 0], 80)),
         }
     }
 
@@ -131,213 +320,291 @@ where
             thread_pool: ThreadPool::new(threads),
             subapps: Vec::new(),
             default_subapp: SubApp::default(),
-            error_handler,
+            error_handler: Box::new(|context, _| error_handler(context.status_code)),
+            error_context_handler: None,
             state: Arc::new(state),
             monitor: MonitorConfig::default(),
-            connection_handler: client_handler,
-            connection_condition: |_, _| true,
-            connection_timeout: None,
+            connection_handler: Box::new(client_handler),
+            connection_condition: Box::new(|_, _| true),
+            request_condition: None,
+            request_rewriter: None,
+            connection_options: ConnectionOptions::default(),
+            accept_options: AcceptOptions::default(),
+            connection_limit: ConnectionLimit::default(),
             shutdown: None,
             #[cfg(feature = "tls")]
             tls_config: None,
             #[cfg(feature = "tls")]
             force_https: false,
+            #[cfg(feature = "tls")]
+            force_https_addr: SocketAddr::from(([0, 0, 0, 0], 80)),
         }
     }
 
     /// Runs the Humphrey app on the given socket address.
-    /// This function will only return if a fatal error is thrown such as the port being in use.
-    pub fn run<A>(mut self, addr: A) -> Result<(), HumphreyError>
+    /// This function will only return once the app has been shut down, in which case a summary of
+    ///   what it did is returned, or if a fatal error is thrown such as the port being in use.
+    pub fn run<A>(self, addr: A) -> Result<ShutdownSummary, HumphreyError>
     where
         A: ToSocketAddrs + Clone,
     {
-        let socket = TcpListener::bind(addr.clone())?;
-        let subapps = Arc::new(self.subapps);
-        let default_subapp = Arc::new(self.default_subapp);
-        let error_handler = Arc::new(self.error_handler);
-
-        self.thread_pool.register_monitor(self.monitor.clone());
-        self.thread_pool.start();
-
-        // Shared shutdown signal between socket.incoming() and shutdown signal receiver.
-        let shutdown = Arc::new(AtomicBool::new(false));
-        let shutdown_clone = shutdown.clone();
-        let main_app_thread = thread::spawn(move || {
-            for stream in socket.incoming() {
-                if shutdown_clone.load(Ordering::SeqCst) {
-                    break;
-                }
-
-                match stream {
-                    Ok(mut stream) => {
-                        let cloned_state = self.state.clone();
-
-                        // Check that the client is allowed to connect
-                        if (self.connection_condition)(&mut stream, cloned_state) {
-                            let cloned_state = self.state.clone();
-                            let cloned_monitor = self.monitor.clone();
-                            let cloned_subapps = subapps.clone();
-                            let cloned_default_subapp = default_subapp.clone();
-                            let cloned_error_handler = error_handler.clone();
-                            let cloned_handler = self.connection_handler;
-                            let cloned_timeout = self.connection_timeout;
-
-                            cloned_monitor.send(
-                                Event::new(EventType::ConnectionSuccess)
-                                    .with_peer_result(stream.peer_addr()),
-                            );
-
-                            // Spawn a new thread to handle the connection
-                            self.thread_pool.execute(move || {
-                                cloned_monitor.send(
-                                    Event::new(EventType::ThreadPoolProcessStarted)
-                                        .with_peer_result(stream.peer_addr()),
-                                );
-
-                                (cloned_handler)(
-                                    Stream::Tcp(stream),
-                                    cloned_subapps,
-                                    cloned_default_subapp,
-                                    cloned_error_handler,
-                                    cloned_state,
-                                    cloned_monitor,
-                                    cloned_timeout,
-                                )
-                            });
-                        } else {
-                            self.monitor.send(
-                                Event::new(EventType::ConnectionDenied)
-                                    .with_peer_result(stream.peer_addr()),
-                            );
-                        }
-                    }
-                    Err(e) => self
-                        .monitor
-                        .send(Event::new(EventType::ConnectionError).with_info(e.to_string())),
-                }
-            }
-            self.thread_pool.stop();
-        });
-
-        if let Some(s) = self.shutdown {
-            // We wait for the shutdown signal, then wake up the main app thread with a new connection
-            let _ = s.recv();
-            shutdown.store(true, Ordering::SeqCst);
-            let _ = TcpStream::connect(unspecified_socket_to_loopback(addr));
-        };
-
-        let _ = main_app_thread.join();
+        let socket = TcpListener::bind(addr)?;
 
-        Ok(())
+        self.serve(vec![(socket, EndpointKind::Plain)])
     }
 
     /// Securely runs the Humphrey app on the given socket address.
-    /// This function will only return if a fatal error is thrown such as the port being in use or the TLS certificate being invalid.
+    /// This function will only return once the app has been shut down, in which case a summary of
+    ///   what it did is returned, or if a fatal error is thrown such as the port being in use or the
+    ///   TLS certificate being invalid.
     #[cfg(feature = "tls")]
-    pub fn run_tls<A>(mut self, addr: A) -> Result<(), HumphreyError>
+    pub fn run_tls<A>(self, addr: A) -> Result<ShutdownSummary, HumphreyError>
     where
         A: ToSocketAddrs + Clone,
     {
-        use rustls::ServerConnection;
+        let mut listeners = vec![(TcpListener::bind(addr)?, EndpointKind::Tls)];
+
+        if self.force_https {
+            let socket = TcpListener::bind(self.force_https_addr)?;
+            listeners.push((socket, EndpointKind::Redirect));
+        }
+
+        self.serve(listeners)
+    }
+
+    /// Runs the Humphrey app on every given endpoint at once, sharing its routes, state and thread
+    ///   pool between them.
+    ///
+    /// The shutdown signal stops every endpoint. `with_forced_https` has no effect on apps run with
+    ///   this function, so plaintext requests are only redirected to HTTPS on endpoints created with
+    ///   `Endpoint::redirect`.
+    /// This function will only return once the app has been shut down, in which case a summary of
+    ///   what it did is returned, or if a fatal error is thrown such as a port being in use.
+    pub fn run_multi(self, endpoints: Vec<Endpoint>) -> Result<ShutdownSummary, HumphreyError> {
+        let mut listeners = Vec::with_capacity(endpoints.len());
+
+        for endpoint in endpoints {
+            let socket = TcpListener::bind(&endpoint.addrs?[..])?;
+            listeners.push((socket, endpoint.kind));
+        }
 
-        let socket = TcpListener::bind(addr.clone())?;
+        self.serve(listeners)
+    }
+
+    /// Accepts connections on every listener until the shutdown signal is received or every listener
+    ///   has failed, handling them in the thread pool according to the kind of endpoint they were
+    ///   accepted on.
+    fn serve(
+        mut self,
+        listeners: Vec<(TcpListener, EndpointKind)>,
+    ) -> Result<ShutdownSummary, HumphreyError> {
+        let started = Instant::now();
+
+        #[cfg(feature = "tls")]
+        if self.tls_config.is_none() && listeners.iter().any(|(_, kind)| *kind == EndpointKind::Tls)
+        {
+            return Err("TLS certificate not supplied".into());
+        }
+
+        // The accept loops can only check for shutdown if the listeners do not block
+        if self.shutdown.is_some() {
+            for (socket, _) in &listeners {
+                socket.set_nonblocking(true)?;
+            }
+        }
+
+        let connection_options = self.take_connection_options();
+        let counters = connection_options.counters.clone();
+        build_indexes(&mut self.subapps, &mut self.default_subapp);
         let subapps = Arc::new(self.subapps);
         let default_subapp = Arc::new(self.default_subapp);
-        let error_handler = Arc::new(self.error_handler);
+        let error_handler = Arc::new(self.error_context_handler.unwrap_or(self.error_handler));
+        let connection_handler = Arc::new(self.connection_handler);
+        let connection_condition = Arc::new(self.connection_condition);
+        let accept_options = Arc::new(self.accept_options);
+        let connection_limit = self.connection_limit;
+        #[cfg(feature = "tls")]
+        let tls_config = self.tls_config;
+        #[cfg(feature = "tls")]
+        let https_port = https_port(&listeners);
 
         self.thread_pool.register_monitor(self.monitor.clone());
         self.thread_pool.start();
+        let mut thread_pool = Arc::new(self.thread_pool);
 
-        if self.force_https {
-            let cloned_monitor = self.monitor.clone();
+        // Shared shutdown signal between the accept loops and shutdown signal receiver.
+        let shutdown = Arc::new(AtomicBool::new(false));
 
-            if self.thread_pool.thread_count() < 2 {
-                println!("Error: A minimum of two threads are required to force HTTPS since one is required for redirects.");
-                std::process::exit(1);
+        let drain_signal = connection_options.drain_signal.clone();
+        let drain_options = connection_options.drain_options.clone();
+        let idle_connections = connection_options.idle_connections.clone();
+
+        let mut accept_threads = Vec::with_capacity(listeners.len());
+
+        for (socket, kind) in listeners {
+            let state = self.state.clone();
+            let monitor = self.monitor.clone();
+            let subapps = subapps.clone();
+            let default_subapp = default_subapp.clone();
+            let error_handler = error_handler.clone();
+            let connection_handler = connection_handler.clone();
+            let connection_condition = connection_condition.clone();
+            let connection_options = connection_options.clone();
+            let accept_options = accept_options.clone();
+            let connection_limit = connection_limit.clone();
+            let counters = counters.clone();
+            let thread_pool = thread_pool.clone();
+            let cloned_shutdown = shutdown.clone();
+            #[cfg(feature = "tls")]
+            let tls_config = tls_config.clone();
+
+            let accept_thread =
+                thread::Builder::new()
+                    .name("humphrey-accept".into())
+                    .spawn(move || {
+                        accept_loop(
+                            &socket,
+                            &accept_options,
+                            &monitor,
+                            &cloned_shutdown,
+                            |mut stream| {
+                                let _ = stream.set_nonblocking(false);
+
+                                // Check that the client is allowed to connect
+                                if !(connection_condition)(&mut stream, state.clone()) {
+                                    monitor.send_with(EventType::ConnectionDenied, |event| {
+                                        event.with_peer_result(stream.peer_addr())
+                                    });
+
+                                    return;
+                                }
+
+                                // Count the connection until it has been handled, unless the app is
+                                //   already handling as many as it can
+                                let permit = match connection_limit.acquire() {
+                                    Some(permit) => permit,
+                                    None => {
+                                        monitor.send_with(
+                                            EventType::ConnectionRejectedOverCapacity,
+                                            |event| {
+                                                event
+                                                    .with_peer_result(stream.peer_addr())
+                                                    .with_info(connection_limit.describe())
+                                            },
+                                        );
+
+                                        if !kind.is_tls() {
+                                            let _ = stream.write_all(OVER_CAPACITY_RESPONSE);
+                                        }
+
+                                        return;
+                                    }
+                                };
+
+                                let cloned_state = state.clone();
+                                let cloned_monitor = monitor.clone();
+                                let cloned_subapps = subapps.clone();
+                                let cloned_default_subapp = default_subapp.clone();
+                                let cloned_error_handler = error_handler.clone();
+                                let cloned_handler = connection_handler.clone();
+                                let cloned_options = connection_options.clone();
+                                #[cfg(feature = "tls")]
+                                let cloned_config = tls_config.clone();
+
+                                cloned_monitor.send_with(EventType::ConnectionSuccess, |event| {
+                                    event.with_peer_result(stream.peer_addr())
+                                });
+                                counters.record_connection();
+
+                                // Spawn a new thread to handle the connection
+                                thread_pool.execute(move || {
+                                    let _permit = permit;
+
+                                    cloned_monitor
+                                        .send_with(EventType::ThreadPoolProcessStarted, |event| {
+                                            event.with_peer_result(stream.peer_addr())
+                                        });
+
+                                    let stream = match kind {
+                                        EndpointKind::Plain => Stream::Tcp(stream),
+                                        #[cfg(feature = "tls")]
+                                        EndpointKind::Tls => {
+                                            let config = cloned_config.unwrap();
+                                            let server =
+                                                rustls::ServerConnection::new(config).unwrap();
+
+                                            Stream::Tls(rustls::StreamOwned::new(server, stream))
+                                        }
+                                        #[cfg(feature = "tls")]
+                                        EndpointKind::Redirect => {
+                                            return redirect_to_https(
+                                                stream,
+                                                https_port,
+                                                &cloned_options,
+                                                &cloned_monitor,
+                                            );
+                                        }
+                                    };
+
+                                    (cloned_handler)(
+                                        stream,
+                                        cloned_subapps,
+                                        cloned_default_subapp,
+                                        cloned_error_handler,
+                                        cloned_state,
+                                        cloned_monitor,
+                                        cloned_options,
+                                    )
+                                });
+                            },
+                        )
+                    });
+
+            match accept_thread {
+                Ok(accept_thread) => accept_threads.push(accept_thread),
+                Err(e) => {
+                    shutdown.store(true, Ordering::SeqCst);
+                    return Err(e.into());
+                }
             }
-
-            self.thread_pool
-                .execute(|| force_https_thread(cloned_monitor).unwrap_or(()));
         }
 
-        // Shared shutdown signal between socket.incoming() and shutdown signal receiver.
-        let shutdown = Arc::new(AtomicBool::new(false));
-        let shutdown_clone = shutdown.clone();
-        let main_app_thread = thread::spawn(move || {
-            for sock in socket.incoming() {
-                if shutdown_clone.load(Ordering::SeqCst) {
-                    break;
-                }
+        let mut triggered = false;
 
-                match sock {
-                    Ok(mut sock) => {
-                        let cloned_state = self.state.clone();
-
-                        // Check that the client is allowed to connect
-                        if (self.connection_condition)(&mut sock, cloned_state) {
-                            let cloned_state = self.state.clone();
-                            let cloned_subapps = subapps.clone();
-                            let cloned_default_subapp = default_subapp.clone();
-                            let cloned_error_handler = error_handler.clone();
-                            let cloned_handler = self.connection_handler;
-                            let cloned_timeout = self.connection_timeout;
-                            let cloned_monitor = self.monitor.clone();
-                            let cloned_config = self
-                                .tls_config
-                                .as_ref()
-                                .expect("TLS certificate not supplied")
-                                .clone();
-
-                            cloned_monitor.send(
-                                Event::new(EventType::ConnectionSuccess)
-                                    .with_peer_result(sock.peer_addr()),
-                            );
+        if let Some(s) = self.shutdown {
+            // We wait for the shutdown signal, then the accept loops stop at their next check
+            while !s.is_triggered() && !accept_threads.iter().all(|t| t.is_finished()) {
+                thread::sleep(accept_options.poll_interval);
+            }
 
-                            // Spawn a new thread to handle the connection
-                            self.thread_pool.execute(move || {
-                                cloned_monitor.send(
-                                    Event::new(EventType::ThreadPoolProcessStarted)
-                                        .with_peer_result(sock.peer_addr()),
-                                );
-
-                                let server = ServerConnection::new(cloned_config).unwrap();
-                                let tls_stream = rustls::StreamOwned::new(server, sock);
-                                let stream = Stream::Tls(tls_stream);
-
-                                (cloned_handler)(
-                                    stream,
-                                    cloned_subapps,
-                                    cloned_default_subapp,
-                                    cloned_error_handler,
-                                    cloned_state,
-                                    cloned_monitor,
-                                    cloned_timeout,
-                                )
-                            });
-                        } else {
-                            self.monitor.send(
-                                Event::new(EventType::ConnectionDenied)
-                                    .with_peer_result(sock.peer_addr()),
-                            );
-                        }
-                    }
-                    Err(e) => self
-                        .monitor
-                        .send(Event::new(EventType::ConnectionError).with_info(e.to_string())),
-                }
+            // If draining is enabled, keep accepting connections for the grace period first
+            triggered = s.is_triggered();
+            if let (true, Some(drain_options)) = (triggered, drain_options) {
+                drain_signal.start();
+                thread::sleep(drain_options.period);
             }
-            self.thread_pool.stop();
-        });
-        if let Some(s) = self.shutdown {
-            // We wait for the shutdown signal, then wake up the main app thread with a new connection
-            let _ = s.recv();
+
             shutdown.store(true, Ordering::SeqCst);
-            let _ = TcpStream::connect(unspecified_socket_to_loopback(addr));
         };
 
-        let _ = main_app_thread.join();
+        // Without a shutdown signal, the accept loops only stop once their listeners have failed
+        let mut reason = ExitReason::Shutdown;
+        for accept_thread in accept_threads {
+            if let (Ok(Err(e)), false) = (accept_thread.join(), triggered) {
+                reason = ExitReason::ListenerError(e);
+            }
+        }
+
+        // Connections waiting for their next request would otherwise hold their workers until the
+        //   client closes them, which browsers can leave for minutes
+        idle_connections.close(&connection_limit);
+
+        // Every accept loop has stopped, so no other references to the thread pool remain
+        if let Some(thread_pool) = Arc::get_mut(&mut thread_pool) {
+            thread_pool.stop();
+        }
 
-        Ok(())
+        Ok(counters.summary(started, connection_limit.count(), reason))
     }
 
     /// Sets the default state for the server.
@@ -351,6 +618,10 @@ where
     /// Adds a new host sub-app to the server.
     /// The host can contain wildcards, for example `*.example.com`.
     ///
+    /// The port of the `Host` header is ignored unless the host specifies one, so `localhost` also
+    ///   handles requests for `localhost:8080`, while `localhost:8080` only handles those for that
+    ///   port. IPv6 addresses are written in square brackets, for example `[::1]`.
+    ///
     /// ## Panics
     /// This function will panic if the host is equal to `*`, since this is the default host.
     /// If you want to add a route to every host, simply add it directly to the main app.
@@ -367,14 +638,63 @@ where
 
     /// Adds a route and associated handler to the server.
     /// Routes can include wildcards, for example `/blog/*`.
+    ///
+    /// If several routes match a path, the most specific one handles it, whatever order they were
+    ///   added in. Routes without wildcards or parameters are preferred, then routes with longer
+    ///   literal parts, so `/contact` is preferred to `/*`.
+    ///
+    /// The handler is called from whichever worker happens to receive the request, so it must be
+    ///   `Send + Sync`. Requiring this here means that a closure capturing an `Rc` or a reference is
+    ///   reported at the call site rather than somewhere inside the app.
     pub fn with_route<T>(mut self, route: &str, handler: T) -> Self
     where
-        T: RequestHandler<State> + 'static,
+        T: RequestHandler<State> + Send + Sync + 'static,
     {
         self.default_subapp = self.default_subapp.with_route(route, handler);
         self
     }
 
+    /// Adds a route and associated handler to the server, giving it a name by which it can be
+    ///   identified when resolving requests with `resolve`.
+    /// Routes can include wildcards, for example `/blog/*`.
+    pub fn with_named_route<T>(mut self, name: &str, route: &str, handler: T) -> Self
+    where
+        T: RequestHandler<State> + Send + Sync + 'static,
+    {
+        self.default_subapp = self.default_subapp.with_named_route(name, route, handler);
+        self
+    }
+
+    /// Adds a route and associated handler to the server which only handles requests with the given
+    ///   method.
+    /// Routes can include wildcards, for example `/api/items/*`.
+    ///
+    /// Requests to the route's path with other methods fall through to other matching routes, and if
+    ///   no route handles them, are answered with `405 Method Not Allowed` and an `Allow` header
+    ///   listing the methods of the routes which match the path.
+    pub fn with_route_method<T>(mut self, route: &str, method: Method, handler: T) -> Self
+    where
+        T: RequestHandler<State> + Send + Sync + 'static,
+    {
+        self.default_subapp = self
+            .default_subapp
+            .with_route_method(route, method, handler);
+        self
+    }
+
+    /// Adds a route and associated handler to the server which only handles `HEAD` requests.
+    /// Routes can include wildcards, for example `/files/*`.
+    ///
+    /// Without one, `HEAD` requests are handled by the route which would handle them as `GET`
+    ///   requests, and the body of its response is not sent.
+    pub fn with_head_route<T>(mut self, route: &str, handler: T) -> Self
+    where
+        T: RequestHandler<State> + Send + Sync + 'static,
+    {
+        self.default_subapp = self.default_subapp.with_head_route(route, handler);
+        self
+    }
+
     /// Adds a route and associated handler to the server.
     /// Does not pass the state to the handler.
     /// Routes can include wildcards, for example `/blog/*`.
@@ -382,7 +702,7 @@ where
     /// If you want to access the app's state in the handler, consider using `with_route`.
     pub fn with_stateless_route<T>(mut self, route: &str, handler: T) -> Self
     where
-        T: StatelessRequestHandler<State> + 'static,
+        T: StatelessRequestHandler<State> + Send + Sync + 'static,
     {
         self.default_subapp = self.default_subapp.with_stateless_route(route, handler);
         self
@@ -393,23 +713,77 @@ where
     /// Will also pass the route to the handler at runtime.
     pub fn with_path_aware_route<T>(mut self, route: &'static str, handler: T) -> Self
     where
-        T: PathAwareRequestHandler<State> + 'static,
+        T: PathAwareRequestHandler<State> + Send + Sync + 'static,
     {
         self.default_subapp = self.default_subapp.with_path_aware_route(route, handler);
         self
     }
 
+    /// Adds a route and associated handler to the server whose request bodies are streamed to the
+    ///   handler as it reads them, rather than read into memory before it is called. This allows
+    ///   uploads which are too large to buffer to be copied to disk incrementally.
+    /// Routes can include wildcards, for example `/uploads/*`.
+    ///
+    /// The handler is given a `RequestBody` to read the body from, which stops at the end of the
+    ///   body so the connection can be reused afterwards. The rest of the body is discarded if the
+    ///   handler returns before reading it all, or the connection is closed if too much remains.
+    ///
+    /// Bodies are still limited by the app's maximum body size, which defaults to 10 MiB and can be
+    ///   raised with `with_max_body_size`.
+    ///
+    /// Streaming routes are checked before other routes, and are matched against the request before
+    ///   it is rewritten.
+    pub fn with_streaming_route<T>(mut self, route: &str, handler: T) -> Self
+    where
+        T: StreamingRequestHandler<State> + Send + Sync + 'static,
+    {
+        self.default_subapp = self.default_subapp.with_streaming_route(route, handler);
+        self
+    }
+
     /// Adds a WebSocket route and associated handler to the server.
     /// Routes can include wildcards, for example `/ws/*`.
     /// The handler is passed the stream, state, and the request which triggered its calling.
     pub fn with_websocket_route<T>(mut self, route: &str, handler: T) -> Self
     where
-        T: WebsocketHandler<State> + 'static,
+        T: WebsocketHandler<State> + Send + Sync + 'static,
     {
         self.default_subapp = self.default_subapp.with_websocket_route(route, handler);
         self
     }
 
+    /// Adds a WebSocket route and associated handler to the server, with a guard which decides
+    ///   whether each upgrade request is accepted before the handler is called.
+    /// Routes can include wildcards, for example `/ws/*`.
+    ///
+    /// If the guard rejects the request, its response is sent instead of upgrading the connection,
+    ///   and the handler is not called. The guard is passed the request with its route parameters,
+    ///   so it can check them as well as headers such as `Authorization` or
+    ///   `Sec-WebSocket-Protocol`.
+    pub fn with_guarded_websocket_route<G, T>(mut self, route: &str, guard: G, handler: T) -> Self
+    where
+        G: WebsocketGuard<State> + 'static,
+        T: WebsocketHandler<State> + Send + Sync + 'static,
+    {
+        self.default_subapp = self
+            .default_subapp
+            .with_guarded_websocket_route(route, guard, handler);
+        self
+    }
+
+    /// Adds a path-aware WebSocket route and associated handler to the server.
+    /// Routes can include wildcards, for example `/ws/*`.
+    /// Will also pass the route to the handler at runtime.
+    pub fn with_path_aware_websocket_route<T>(mut self, route: &'static str, handler: T) -> Self
+    where
+        T: PathAwareWebsocketHandler<State> + Send + Sync + 'static,
+    {
+        self.default_subapp = self
+            .default_subapp
+            .with_path_aware_websocket_route(route, handler);
+        self
+    }
+
     /// Sets the default sub-app for the server.
     /// This overrides all the routes added, as they will be replaced by the routes in the default sub-app.
     pub fn with_default_subapp(mut self, subapp: SubApp<State>) -> Self {
@@ -424,171 +798,913 @@ where
     }
 
     /// Registers a shutdown signal to gracefully shutdown the app, ending the run/run_tls loop.
-    pub fn with_shutdown(mut self, shutdown_receiver: Receiver<()>) -> Self {
-        self.shutdown = Some(shutdown_receiver);
+    ///
+    /// As well as a `ShutdownSignal`, this accepts an `Arc<AtomicBool>` which shuts down the app
+    ///   when it is set, or a `Receiver<()>` which shuts down the app when a message is received.
+    pub fn with_shutdown(mut self, shutdown: impl Into<ShutdownSignal>) -> Self {
+        self.shutdown = Some(shutdown.into());
+        self
+    }
+
+    /// Enables connection draining during graceful shutdown with the given options.
+    ///
+    /// Once the shutdown signal is received, the app keeps accepting connections for the configured
+    ///   period, but new requests, including those on existing keep-alive connections, receive
+    ///   `503 Service Unavailable` with a `Retry-After` header and `Connection: Close`. Requests
+    ///   which are already being processed complete normally.
+    pub fn with_draining(mut self, options: DrainOptions) -> Self {
+        self.connection_options.drain_options = Some(options);
+        self
+    }
+
+    /// Returns a handle which reports whether the app has started draining connections.
+    ///
+    /// This can be stored in the app's state or captured by a handler, for example to make a
+    ///   readiness check fail as soon as draining starts.
+    pub fn drain_signal(&self) -> DrainSignal {
+        self.connection_options.drain_signal.clone()
+    }
+
+    /// Sets the options which control how connections are accepted, such as the backoff when the
+    ///   process runs out of file descriptors and the number of connections accepted at once.
+    pub fn with_accept_options(mut self, options: AcceptOptions) -> Self {
+        self.accept_options = options;
         self
     }
 
     /// Sets the error handler for the server.
-    pub fn with_error_handler(mut self, handler: ErrorHandler) -> Self {
-        self.error_handler = handler;
+    pub fn with_error_handler<T>(self, handler: T) -> Self
+    where
+        T: Fn(StatusCode) -> Response + Send + Sync + 'static,
+    {
+        self.with_error_handler_with_state(move |status_code, _, _| handler(status_code))
+    }
+
+    /// Sets the error handler for the server, which is also given the request if it could be parsed
+    ///   and the app's state, so that it can mention the requested path or log the error.
+    ///
+    /// Sub-apps can override the error handler for their host with `SubApp::with_error_handler`.
+    pub fn with_error_handler_with_state<T>(mut self, handler: T) -> Self
+    where
+        T: Fn(StatusCode, Option<&Request>, Arc<State>) -> Response + Send + Sync + 'static,
+    {
+        self.error_handler =
+            Box::new(move |context, state| handler(context.status_code, context.request, state));
+        self
+    }
+
+    /// Sets the error handler for the server, which is given an `ErrorContext` describing what went
+    ///   wrong, such as why the request could not be read or the message of a panicking handler.
+    ///
+    /// This is used instead of the handler set with `with_error_handler` or
+    ///   `with_error_handler_with_state` if both are set. Sub-apps with their own error handler still
+    ///   handle the errors for their host.
+    pub fn with_error_context_handler<T>(mut self, handler: T) -> Self
+    where
+        T: Fn(&ErrorContext) -> Response + Send + Sync + 'static,
+    {
+        self.error_context_handler = Some(Box::new(move |context, _| handler(context)));
         self
     }
 
     /// Sets the connection condition, a function which decides whether to accept the connection.
     /// For example, this could be used for implementing whitelists and blacklists.
-    pub fn with_connection_condition(mut self, condition: ConnectionCondition<State>) -> Self {
-        self.connection_condition = condition;
+    pub fn with_connection_condition<T>(mut self, condition: T) -> Self
+    where
+        T: Fn(&mut TcpStream, Arc<State>) -> bool + Send + Sync + 'static,
+    {
+        self.connection_condition = Box::new(condition);
+        self
+    }
+
+    /// Sets the request condition, a function which decides whether to reject a request based on its
+    ///   head, before the body is read.
+    ///
+    /// Unlike the connection condition, this has access to the request line and headers, but since the
+    ///   body of a rejected request is never buffered, large rejected uploads cost almost nothing.
+    pub fn with_request_condition<T>(mut self, condition: T) -> Self
+    where
+        T: Fn(&Request, Arc<State>) -> Option<Response> + Send + Sync + 'static,
+    {
+        self.request_condition = Some(Box::new(condition));
+        self
+    }
+
+    /// Sets the request rewriter, a function which can modify every request before it is routed.
+    ///
+    /// This runs after the request condition, so the condition sees the request as it was received,
+    ///   but route matching, WebSocket handlers and request handlers all see the rewritten request.
+    pub fn with_request_rewriter<T>(mut self, rewriter: T) -> Self
+    where
+        T: Fn(&mut Request, Arc<State>) -> Option<Response> + Send + Sync + 'static,
+    {
+        self.request_rewriter = Some(Box::new(rewriter));
+        self
+    }
+
+    /// Enables the method override, which lets a `POST` request be handled as a `PUT`, `PATCH` or
+    ///   `DELETE` request, for clients such as HTML forms which can only send `GET` and `POST`.
+    ///
+    /// The method is taken from the `X-HTTP-Method-Override` header or a form field, as determined by
+    ///   the given `MethodOverride`, once the request rewriter has run. Handlers see the overridden
+    ///   method in `request.method`, and the method the request was sent with is available from
+    ///   `request.original_method()`. Requests from other origins are only overridden to methods
+    ///   which the CORS configuration of their route allows them to use.
+    pub fn with_method_override(mut self, method_override: MethodOverride) -> Self {
+        self.connection_options.method_override = Some(method_override);
         self
     }
 
     /// Sets the connection timeout, the amount of time to wait between keep-alive requests.
+    ///
+    /// Requests are also given a deadline of this long after they have been received, which is
+    ///   available to handlers in `request.deadline`. If keep-alive limits are set with
+    ///   `with_keep_alive`, their idle timeout is used between requests instead.
     pub fn with_connection_timeout(mut self, timeout: Option<Duration>) -> Self {
-        self.connection_timeout = timeout;
+        self.connection_options.timeout = timeout;
         self
     }
 
-    /// Sets the CORS configuration for the app.
-    ///
-    /// This overrides the CORS configuration for existing and future individual routes.
+    /// Limits how long keep-alive connections are kept open while idle and how many requests can be
+    ///   sent on each of them.
     ///
-    /// To simply allow every origin, method and header, use `Cors::wildcard()`.
-    pub fn with_cors(mut self, cors: Cors) -> Self {
-        self.default_subapp = self.default_subapp.with_cors(cors);
+    /// Responses on connections which will be kept open have a `Keep-Alive` header advertising the
+    ///   limits. Connections which are idle for longer than the idle timeout are closed without a
+    ///   response, and the connection is closed after the last request allowed on it.
+    pub fn with_keep_alive(mut self, config: KeepAliveConfig) -> Self {
+        self.connection_options.keep_alive = Some(config);
         self
     }
 
-    /// Sets the CORS configuration for the specified route.
+    /// Trusts the `X-Request-Deadline` header of requests received directly from the given proxies,
+    ///   which gives the time remaining until the deadline of the request in milliseconds.
     ///
-    /// To simply allow every origin, method and header, use `Cors::wildcard()`.
-    pub fn with_cors_config(mut self, route: &str, cors: Cors) -> Self {
-        self.default_subapp = self.default_subapp.with_cors_config(route, cors);
+    /// Handlers can pass the deadline of a request, available in `request.deadline`, on to the
+    ///   services they call with `ClientRequest::with_deadline`, which sends the header. Apps which
+    ///   call each other can therefore share the deadline of the original request. When the app also
+    ///   has a connection timeout, the earlier of the two deadlines is used.
+    pub fn with_deadline_propagation(mut self, trusted_proxies: Vec<IpAddr>) -> Self {
+        self.connection_options.deadline_proxies = trusted_proxies;
         self
     }
 
-    /// Sets whether HTTPS should be forced on all connections. Defaults to false.
+    /// Enables trace propagation, which gives every request a trace context in `request.trace`.
     ///
-    /// If this is set to true, a background thread will be spawned when `run_tls` is called to send
-    ///   redirect responses to all insecure requests on port 80.
-    #[cfg(feature = "tls")]
-    pub fn with_forced_https(mut self, forced: bool) -> Self {
-        self.force_https = forced;
+    /// Requests with a valid `traceparent` header continue the caller's trace and follow its sampling
+    ///   decision, and other requests start a new trace which is sampled if `sample_new_traces` is
+    ///   `true`. The trace and span IDs are included in the monitoring events about each request and
+    ///   in request recordings.
+    pub fn with_trace_propagation(mut self, sample_new_traces: bool) -> Self {
+        self.connection_options.trace = Some(TraceOptions { sample_new_traces });
         self
     }
 
-    /// Sets the TLS configuration for the server.
+    /// Converts the app into a `TestClient`, which sends requests to the app's routes without
+    ///   binding a port, for integration tests.
     ///
-    /// This **must** be called before `run_tls` is called.
-    #[cfg(feature = "tls")]
-    pub fn with_cert(mut self, cert_path: impl AsRef<str>, key_path: impl AsRef<str>) -> Self {
-        use rustls::{Certificate, PrivateKey};
-        use rustls_pemfile::{read_one, Item};
+    /// Requests are handled exactly as `run` would handle them, including sub-apps, middleware, CORS
+    ///   and the error handler, but WebSocket routes and the connection condition are not used.
+    pub fn into_test_client(mut self) -> TestClient<State> {
+        let connection_options = self.take_connection_options();
+        build_indexes(&mut self.subapps, &mut self.default_subapp);
+
+        TestClient::new(
+            Arc::new(self.subapps),
+            Arc::new(self.default_subapp),
+            Arc::new(self.error_context_handler.unwrap_or(self.error_handler)),
+            self.state,
+            connection_options,
+        )
+    }
 
-        use std::fs::File;
-        use std::io::BufReader;
+    /// Sets the maximum number of connections which can be handled at once, including those waiting
+    ///   for a worker thread.
+    ///
+    /// Without a limit, connections wait in the queue of the thread pool for as long as the app is
+    ///   overloaded. Once the limit is reached, further connections are answered with
+    ///   `503 Service Unavailable` and closed as soon as they are accepted, or closed straight away on
+    ///   TLS endpoints, and a `ConnectionRejectedOverCapacity` monitor event is sent. A connection is
+    ///   counted until its handler returns, including when it returns by panicking.
+    pub fn with_max_concurrent_connections(mut self, limit: usize) -> Self {
+        self.connection_limit = ConnectionLimit::new(Some(limit));
+        self
+    }
 
-        let mut cert_file =
-            BufReader::new(File::open(cert_path.as_ref()).expect("failed to open cert file"));
-        let mut key_file =
-            BufReader::new(File::open(key_path.as_ref()).expect("failed to open key file"));
+    /// Limits the rate at which each client, identified by its IP address, can make requests.
+    ///
+    /// Every request is counted, including each request on a keep-alive connection. Requests from a
+    ///   client which has used up its allowance are answered with `429 Too Many Requests` and a
+    ///   `Retry-After` header before they are routed, and a `RequestRateLimited` monitor event is sent.
+    pub fn with_rate_limit(mut self, config: RateLimitConfig) -> Self {
+        self.connection_options.rate_limiter = Some(RateLimiter::new(config));
+        self
+    }
 
-        let certs: Vec<Certificate> = match read_one(&mut cert_file).unwrap().unwrap() {
-            Item::X509Certificate(cert) => vec![Certificate(cert)],
-            _ => panic!("failed to parse cert file"),
-        };
+    /// Sets whether every connection starts with a PROXY protocol header, as sent by load balancers
+    ///   such as HAProxy and AWS Network Load Balancers. Defaults to false.
+    ///
+    /// If this is set to true, the address of the client given by the header is used in place of
+    ///   the address of the load balancer, both in `request.address` and in monitor events, and so
+    ///   for rate limiting. Connections which do not start with a valid header are closed, and the
+    ///   `ConnectionError` event is sent. The connection condition is still given the connection
+    ///   from the load balancer, and custom connection handlers must read the header themselves
+    ///   with `proxy_protocol::read_header`.
+    pub fn with_proxy_protocol(mut self, enabled: bool) -> Self {
+        self.connection_options.proxy_protocol = enabled;
+        self
+    }
 
-        let key: PrivateKey = match read_one(&mut key_file).unwrap().unwrap() {
-            Item::PKCS8Key(key) => PrivateKey(key),
-            _ => panic!("failed to parse key file"),
-        };
+    /// Sets the maximum number of worker threads which can be held by WebSocket connections at once.
+    ///
+    /// A WebSocket connection occupies a worker for its entire lifetime, so without a limit, enough
+    ///   connected clients will leave no workers to serve HTTP requests. Once the limit is reached,
+    ///   further upgrade requests are refused with `503 Service Unavailable`. The number of workers
+    ///   held by WebSocket connections is included in the information of the WebSocket monitor events.
+    pub fn with_websocket_limit(mut self, limit: usize) -> Self {
+        self.connection_options.websocket_limit = Some(limit);
+        self
+    }
 
-        let config = Arc::new(
-            ServerConfig::builder()
-                .with_safe_defaults()
-                .with_no_client_auth()
-                .with_single_cert(certs, key)
-                .expect("failed to create server config"),
-        );
+    /// Sets the maximum number of WebSocket connections which can be open at once, both in total and
+    ///   from a single IP address. `None` leaves the corresponding limit unbounded.
+    ///
+    /// The global limit is the same as that set by `with_websocket_limit`. The per-IP limit stops a
+    ///   single client from using up the global limit by itself, and is based on the address of the
+    ///   TCP connection, so clients behind the same proxy share it. Upgrade requests beyond either
+    ///   limit are refused with `503 Service Unavailable` before the handshake, and a
+    ///   `WebsocketConnectionRefused` monitor event is sent describing which limit was reached.
+    pub fn with_websocket_limits(mut self, global: Option<usize>, per_ip: Option<usize>) -> Self {
+        self.connection_options.websocket_limit = global;
+        self.connection_options.websocket_limit_per_ip = per_ip;
+        self
+    }
 
-        self.tls_config = Some(config);
+    /// Returns a handle which reports the number of WebSocket connections currently open.
+    ///
+    /// Connections are counted from when their upgrade request is accepted until their handler
+    ///   returns, including when it returns by panicking.
+    pub fn websocket_connections(&self) -> WebsocketConnections {
+        WebsocketConnections {
+            total: self.connection_options.websocket_workers.clone(),
+            clients: self.connection_options.websocket_clients.clone(),
+        }
+    }
 
+    /// Enables recording of raw requests for debugging, according to the given configuration.
+    ///
+    /// Requests are recorded as they are read from the stream, so malformed requests which could not
+    ///   be parsed are recorded too.
+    pub fn with_request_recording(mut self, config: RecordingConfig) -> Self {
+        self.connection_options.recorder = Some(Recorder::new(config));
         self
     }
 
-    /// Adds a global WebSocket handler to the server.
+    /// Applies the hardened security preset described by `SecurityOptions::hardened`.
     ///
-    /// ## Deprecated
-    /// This function is deprecated and will be removed in a future version.
-    /// Please use `with_websocket_route` instead.
-    #[deprecated(since = "0.3.0", note = "Please use `with_websocket_route` instead")]
-    pub fn with_websocket_handler<T>(mut self, handler: T) -> Self
-    where
-        T: WebsocketHandler<State> + 'static,
-    {
-        self.default_subapp = self.default_subapp.with_websocket_route("*", handler);
+    /// Request limits which were already set explicitly are kept. Every other option is replaced, but
+    ///   can be overridden individually by calling the corresponding method afterwards.
+    pub fn with_hardened_defaults(mut self) -> Self {
+        let limits = self.connection_options.security.request_limits;
+        self.connection_options.security = SecurityOptions::hardened();
+        self.connection_options.security.request_limits = limits.or(RequestLimits::conservative());
         self
     }
 
-    /// Overrides the default connection handler, allowing for manual control over the TCP requests and responses.
-    /// Not recommended as it basically disables most of the server's features.
-    pub fn with_custom_connection_handler(mut self, handler: ConnectionHandler<State>) -> Self {
-        self.connection_handler = handler;
+    /// Sets the value of the `Server` header sent with every response, or `None` to omit it.
+    /// Defaults to `Humphrey`.
+    pub fn with_server_header(mut self, value: Option<&str>) -> Self {
+        self.connection_options.security.server_header = value.map(|v| v.to_string());
         self
     }
 
-    /// Gets a reference to the app's state.
-    /// This should only be used in the main thread, as the state is passed to request handlers otherwise.
-    pub fn get_state(&self) -> Arc<State> {
-        self.state.clone()
+    /// Sets the value of the `X-Content-Type-Options` header sent with every response, or `None` to
+    ///   omit it. Defaults to `None`.
+    pub fn with_content_type_options(mut self, value: Option<&str>) -> Self {
+        self.connection_options.security.content_type_options = value.map(|v| v.to_string());
+        self
     }
-}
 
-/// Handles a connection with a client.
-/// The connection will be opened upon the first request and closed as soon as a request is
-///   received without the `Connection: Keep-Alive` header.
-#[allow(clippy::too_many_arguments)]
-fn client_handler<State>(
-    mut stream: Stream,
-    subapps: Arc<Vec<SubApp<State>>>,
-    default_subapp: Arc<SubApp<State>>,
-    error_handler: Arc<ErrorHandler>,
-    state: Arc<State>,
-    monitor: MonitorConfig,
-    timeout: Option<Duration>,
-) {
-    let addr = if let Ok(addr) = stream.peer_addr() {
-        addr
-    } else {
-        monitor.send(EventType::StreamDisconnectedWhileWaiting);
+    /// Sets the value of the `X-Frame-Options` header sent with every response, or `None` to omit it.
+    /// Defaults to `None`.
+    pub fn with_frame_options(mut self, value: Option<&str>) -> Self {
+        self.connection_options.security.frame_options = value.map(|v| v.to_string());
+        self
+    }
 
-        return;
-    };
+    /// Sets the `max-age` of the `Strict-Transport-Security` header sent with every response over
+    ///   TLS, or `None` to omit it. Defaults to `None`.
+    pub fn with_hsts(mut self, max_age: Option<Duration>) -> Self {
+        self.connection_options.security.hsts_max_age = max_age;
+        self
+    }
+
+    /// Sets the limits on the size of requests.
+    ///
+    /// Requests which exceed a limit are rejected with `414 URI Too Long`,
+    ///   `431 Request Header Fields Too Large` or `413 Payload Too Large` as appropriate, and the
+    ///   connection is closed. By default, the headers are limited to `DEFAULT_MAX_HEADER_SIZE` and
+    ///   the body to `DEFAULT_MAX_BODY_SIZE`, but the URI is not limited.
+    pub fn with_request_limits(mut self, limits: RequestLimits) -> Self {
+        self.connection_options.security.request_limits = limits;
+        self
+    }
+
+    /// Sets the maximum size of a request body in bytes, beyond which requests are rejected with
+    ///   `413 Payload Too Large` before the body is read. Defaults to `DEFAULT_MAX_BODY_SIZE`.
+    ///
+    /// This also limits the bodies of streaming routes, so must be raised for apps which accept
+    ///   large uploads.
+    pub fn with_max_body_size(mut self, size: usize) -> Self {
+        self.connection_options
+            .security
+            .request_limits
+            .max_body_size = Some(size);
+        self
+    }
+
+    /// Sets the maximum total size of the headers of a request in bytes, beyond which requests are
+    ///   rejected with `431 Request Header Fields Too Large`. Defaults to `DEFAULT_MAX_HEADER_SIZE`.
+    pub fn with_max_header_size(mut self, size: usize) -> Self {
+        self.connection_options
+            .security
+            .request_limits
+            .max_header_size = Some(size);
+        self
+    }
+
+    /// Returns the security-related headers and request limits which the app will apply.
+    pub fn security_options(&self) -> &SecurityOptions {
+        &self.connection_options.security
+    }
+
+    /// Sets the CORS configuration for the app.
+    ///
+    /// This overrides the CORS configuration for existing and future individual routes.
+    ///
+    /// To simply allow every origin, method and header, use `Cors::wildcard()`.
+    pub fn with_cors(mut self, cors: Cors) -> Self {
+        self.default_subapp = self.default_subapp.with_cors(cors);
+        self
+    }
+
+    /// Sets the CORS configuration for the specified route.
+    ///
+    /// To simply allow every origin, method and header, use `Cors::wildcard()`.
+    pub fn with_cors_config(mut self, route: &str, cors: Cors) -> Self {
+        self.default_subapp = self.default_subapp.with_cors_config(route, cors);
+        self
+    }
+
+    /// Adds a header to every response from the app, including those generated by the error
+    ///   handler, unless the handler already set it.
+    ///
+    /// This can be called repeatedly to add several headers. `Set-Cookie` headers are always added,
+    ///   since a response can set many cookies, and informational (`1xx`) responses are left alone.
+    ///   Default headers of a sub-app added with `with_host` take precedence for requests to its
+    ///   host.
+    pub fn with_default_header(mut self, name: impl HeaderLike, value: impl AsRef<str>) -> Self {
+        self.default_subapp = self.default_subapp.with_default_header(name, value);
+        self
+    }
+
+    /// Adds a middleware which runs before and after the handlers for every request to the app,
+    ///   including requests for the hosts of sub-apps, whose own middleware runs after it.
+    ///
+    /// Middleware runs in the order it was added before the request is handled, and in the reverse
+    ///   order afterwards. See `Middleware` for details.
+    pub fn with_middleware(mut self, middleware: Middleware<State>) -> Self {
+        self.default_subapp = self.default_subapp.with_middleware(middleware);
+        self
+    }
+
+    /// Sets how paths which differ from a route only by a trailing slash are handled, for example
+    ///   whether `/about/` is handled by the route `/about`.
+    ///
+    /// This applies to the app's own routes, while sub-apps added with `with_host` have their own
+    ///   policy, set with `SubApp::with_trailing_slash_policy`. See `TrailingSlash` for details.
+    ///
+    /// ## Example
+    /// ```
+    /// let app: App<()> = App::new()
+    ///     .with_trailing_slash_policy(TrailingSlash::Redirect)
+    ///     .with_route("/about", about_handler);
+    /// ```
+    pub fn with_trailing_slash_policy(mut self, policy: TrailingSlash) -> Self {
+        self.default_subapp = self.default_subapp.with_trailing_slash_policy(policy);
+        self
+    }
+
+    /// Sets the headers to add to every response from the app, replacing any added previously with
+    ///   `with_default_header`.
+    pub fn with_default_headers(mut self, headers: Headers) -> Self {
+        self.default_subapp = self.default_subapp.with_default_headers(headers);
+        self
+    }
+
+    /// Sets whether HTTPS should be forced on all connections. Defaults to false.
+    ///
+    /// If this is set to true, `run_tls` also listens on port 80, or the port set with
+    ///   `with_forced_https_port`, and redirects every insecure request to the same URL on the port
+    ///   the app is served over HTTPS on. Apps run with `run_multi` ignore this setting, and instead
+    ///   redirect requests on the endpoints created with `Endpoint::redirect`.
+    #[cfg(feature = "tls")]
+    pub fn with_forced_https(mut self, forced: bool) -> Self {
+        self.force_https = forced;
+        self
+    }
+
+    /// Forces HTTPS on all connections, redirecting insecure requests received on the given port
+    ///   instead of port 80.
+    #[cfg(feature = "tls")]
+    pub fn with_forced_https_port(mut self, port: u16) -> Self {
+        self.force_https = true;
+        self.force_https_addr.set_port(port);
+        self
+    }
+
+    /// Sets the address on which insecure requests are received when HTTPS is forced. Defaults to
+    ///   `0.0.0.0`, so requests are received on every interface.
+    #[cfg(feature = "tls")]
+    pub fn with_forced_https_address(mut self, addr: IpAddr) -> Self {
+        self.force_https_addr.set_ip(addr);
+        self
+    }
+
+    /// Sets the TLS configuration for the server, reading the certificate chain and private key from
+    ///   the PEM files at the given paths.
+    ///
+    /// This **must** be called before `run_tls` is called, or before `run_multi` is called with a TLS
+    ///   endpoint. Every certificate in the certificate file is used, so it can contain a full chain,
+    ///   and the private key can be a PKCS#8, PKCS#1 (RSA) or SEC1 (EC) key.
+    ///
+    /// ## Panics
+    /// This function panics if the certificate or key cannot be loaded. Use `try_with_cert` to handle
+    ///   the error instead.
+    #[cfg(feature = "tls")]
+    pub fn with_cert(self, cert_path: impl AsRef<str>, key_path: impl AsRef<str>) -> Self {
+        self.try_with_cert(cert_path, key_path)
+            .unwrap_or_else(|e| panic!("failed to load certificate: {}", e))
+    }
+
+    /// Sets the TLS configuration for the server in the same way as `with_cert`, returning an error
+    ///   if the certificate or key cannot be loaded.
+    #[cfg(feature = "tls")]
+    pub fn try_with_cert(
+        mut self,
+        cert_path: impl AsRef<str>,
+        key_path: impl AsRef<str>,
+    ) -> Result<Self, CertError> {
+        self.tls_config = Some(tls::config_from_files(
+            Path::new(cert_path.as_ref()),
+            Path::new(key_path.as_ref()),
+        )?);
+
+        Ok(self)
+    }
+
+    /// Sets the TLS configuration for the server in the same way as `with_cert`, but from PEM data
+    ///   which has already been loaded, for example from a secret store or with `include_bytes!`.
+    ///
+    /// ## Panics
+    /// This function panics if the certificate or key cannot be loaded. Use `try_with_cert_bytes` to
+    ///   handle the error instead.
+    #[cfg(feature = "tls")]
+    pub fn with_cert_bytes(self, cert_pem: &[u8], key_pem: &[u8]) -> Self {
+        self.try_with_cert_bytes(cert_pem, key_pem)
+            .unwrap_or_else(|e| panic!("failed to load certificate: {}", e))
+    }
+
+    /// Sets the TLS configuration for the server in the same way as `with_cert_bytes`, returning an
+    ///   error if the certificate or key cannot be loaded.
+    #[cfg(feature = "tls")]
+    pub fn try_with_cert_bytes(
+        mut self,
+        cert_pem: &[u8],
+        key_pem: &[u8],
+    ) -> Result<Self, CertError> {
+        self.tls_config = Some(tls::config_from_pem(cert_pem, key_pem)?);
+
+        Ok(self)
+    }
+
+    /// Adds a global WebSocket handler to the server.
+    ///
+    /// ## Deprecated
+    /// This function is deprecated and will be removed in a future version.
+    /// Please use `with_websocket_route` instead.
+    #[deprecated(since = "0.3.0", note = "Please use `with_websocket_route` instead")]
+    pub fn with_websocket_handler<T>(mut self, handler: T) -> Self
+    where
+        T: WebsocketHandler<State> + Send + Sync + 'static,
+    {
+        self.default_subapp = self.default_subapp.with_websocket_route("*", handler);
+        self
+    }
+
+    /// Overrides the default connection handler, allowing for manual control over the TCP requests and responses.
+    /// Not recommended as it basically disables most of the server's features.
+    pub fn with_custom_connection_handler<T>(mut self, handler: T) -> Self
+    where
+        T: Fn(
+                Stream,
+                Arc<Vec<SubApp<State>>>,
+                Arc<SubApp<State>>,
+                Arc<ErrorHandler<State>>,
+                Arc<State>,
+                MonitorConfig,
+                Arc<ConnectionOptions>,
+            ) + Send
+            + Sync
+            + 'static,
+    {
+        self.connection_handler = Box::new(handler);
+        self
+    }
+
+    /// Gets a reference to the app's state.
+    /// This should only be used in the main thread, as the state is passed to request handlers otherwise.
+    pub fn get_state(&self) -> Arc<State> {
+        self.state.clone()
+    }
+
+    /// Returns the route which would handle a request with the given method, path and `Host`
+    ///   header, without calling its handler.
+    ///
+    /// The path should be percent-decoded and without the query string, as in `request.uri`. Host
+    ///   sub-apps and routes are checked in the same order as when requests are handled, but the
+    ///   request rewriter, request condition and trailing slash policy are not applied.
+    pub fn resolve<'a>(
+        &'a self,
+        method: &Method,
+        path: &'a str,
+        host: Option<&str>,
+    ) -> Option<RouteMatch<'a>> {
+        find_route(&self.subapps, &self.default_subapp, method, host, path)
+            .map(|(subapp, index)| subapp.route_match(index, method, path))
+    }
+
+    /// Takes the connection options to share between connections, binding the request condition and
+    ///   rewriter to the app's state.
+    fn take_connection_options(&mut self) -> Arc<ConnectionOptions> {
+        if let Some(condition) = self.request_condition.take() {
+            let state = self.state.clone();
+            self.connection_options.request_condition =
+                Some(Box::new(move |request| condition(request, state.clone())));
+        }
+
+        if let Some(rewriter) = self.request_rewriter.take() {
+            let state = self.state.clone();
+            self.connection_options.request_rewriter =
+                Some(Box::new(move |request| rewriter(request, state.clone())));
+        }
+
+        Arc::new(std::mem::take(&mut self.connection_options))
+    }
+}
+
+/// Accepts connections from the listener and passes them to the given function until shutdown,
+///   or until the listener fails, in which case the error is returned.
+///
+/// In nonblocking mode, up to `options.burst` connections are accepted between checks for shutdown,
+///   and the listener is polled every `options.poll_interval` while it is idle. If the process runs
+///   out of file descriptors, the loop backs off instead of retrying immediately.
+pub(crate) fn accept_loop<L>(
+    listener: &L,
+    options: &AcceptOptions,
+    monitor: &MonitorConfig,
+    shutdown: &AtomicBool,
+    mut handle: impl FnMut(L::Stream),
+) -> io::Result<()>
+where
+    L: Listener,
+{
+    let mut errors = AcceptErrors::new(options);
+
+    while !shutdown.load(Ordering::SeqCst) {
+        for _ in 0..options.burst.max(1) {
+            match listener.accept() {
+                Ok(stream) => {
+                    errors.reset();
+                    handle(stream);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    thread::sleep(options.poll_interval);
+                    break;
+                }
+                Err(e) if is_listener_failure(&e) => return Err(e),
+                Err(e) => {
+                    if let Some(delay) = errors.record(&e, monitor) {
+                        // Free a descriptor to accept and close one waiting connection
+                        if errors.release_reserve() {
+                            drop(listener.accept());
+                            errors.restore_reserve();
+                        }
+
+                        thread::sleep(delay);
+                    }
+
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Handles a connection with a client.
+/// The connection will be opened upon the first request and closed as soon as a request is
+///   received without the `Connection: Keep-Alive` header.
+#[allow(clippy::too_many_arguments)]
+fn client_handler<State>(
+    mut stream: Stream,
+    subapps: Arc<Vec<SubApp<State>>>,
+    default_subapp: Arc<SubApp<State>>,
+    error_handler: Arc<ErrorHandler<State>>,
+    state: Arc<State>,
+    monitor: MonitorConfig,
+    options: Arc<ConnectionOptions>,
+) {
+    let addr = if let Ok(addr) = stream.peer_addr() {
+        addr
+    } else {
+        monitor.send(EventType::StreamDisconnectedWhileWaiting);
+
+        return;
+    };
+    let local_addr = stream.local_addr().ok();
+
+    // Use the address of the client from the header sent by the load balancer, which comes before
+    //   the TLS handshake if there is one
+    let addr = match options.proxy_protocol {
+        true => {
+            let _ = stream.set_timeout(options.timeout);
+
+            match proxy_protocol::client_addr(stream.tcp_mut(), &monitor) {
+                Some(addr) => addr,
+                None => return,
+            }
+        }
+        false => addr,
+    };
+
+    // Another handle to the connection, so that it can be checked for disconnection while handlers run
+    let socket = stream.try_clone_socket().ok().map(Arc::new);
+
+    let connection_id = NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed);
+    let slow_request_threshold = monitor.slow_request_threshold();
+
+    // Bodies only need to be left unread if there are streaming routes to read them
+    let streaming = !default_subapp.streaming_routes.is_empty()
+        || subapps
+            .iter()
+            .any(|subapp| !subapp.streaming_routes.is_empty());
+
+    // Errors are handled by the error handler for the request's host
+    let error_handler =
+        |context: ErrorContext| handle_error(&context, &subapps, &error_handler, &state);
+
+    // The number of requests received on the connection so far
+    let mut requests: u32 = 0;
 
     loop {
-        // Parses the request from the stream
-        let request = match timeout {
-            Some(timeout) => Request::from_stream_with_timeout(&mut stream, addr, timeout),
-            None => Request::from_stream(&mut stream, addr),
+        // Parses the request from the stream, keeping a copy of the raw bytes if recording
+        let mut timer = RequestTimer::new();
+        let mut raw: Vec<u8> = Vec::new();
+        let mut rejection: Option<(Request, Response)> = None;
+        let mut check_head = |head: &Request| {
+            let response = options
+                .request_condition
+                .as_ref()
+                .and_then(|condition| condition(head));
+            let accepted = response.is_none();
+            rejection = response.map(|response| (head.clone(), response));
+
+            accepted
+        };
+        let condition = options
+            .request_condition
+            .as_ref()
+            .map(|_| &mut check_head as HeadCondition);
+
+        // Leave the body unread if the request is for a streaming route
+        let mut is_streamed = |head: &Request| {
+            head.method != Method::Options
+                && get_streaming_handler(head, &subapps, &default_subapp).is_some()
+        };
+        let mut deferral = streaming.then_some(BodyDeferral {
+            condition: &mut is_streamed as HeadCondition,
+            buffered: None,
+        });
+
+        // Only requests which may be reported as slow need the arrival of their first byte timed
+        let timed = slow_request_threshold.is_some();
+        let limits = &options.security.request_limits;
+        let simple = !timed && !streaming && *limits == RequestLimits::default();
+
+        // Between requests, the connection is closed when the app shuts down, and after the idle
+        //   timeout if keep-alive limits are set
+        let idle = requests > 0;
+        if let (true, Some(socket)) = (idle, &socket) {
+            if !options.idle_connections.enter(connection_id, socket) {
+                break;
+            }
+        }
+        let timeout = match (idle, &options.keep_alive) {
+            (true, Some(keep_alive)) => Some(keep_alive.idle_timeout),
+            _ => options.timeout,
         };
 
+        let mut request = match (&options.recorder, timeout, condition) {
+            (None, Some(timeout), None) if simple => {
+                Request::from_stream_with_timeout(&mut stream, addr, timeout)
+            }
+            (None, None, None) if simple => Request::from_stream(&mut stream, addr),
+            (recorder, timeout, condition) => Request::from_stream_with_options(
+                &mut stream,
+                addr,
+                timeout,
+                recorder.as_ref().map(|_| &mut raw),
+                recorder.as_ref().is_some_and(|recorder| recorder.includes_body()),
+                condition,
+                deferral.as_mut(),
+                Some(&mut timer.received),
+                limits,
+            ),
+        };
+        timer.parsed();
+
+        if idle {
+            options.idle_connections.leave(connection_id);
+
+            // Idle connections are closed without a response once they time out
+            if let (Some(_), Err(RequestError::Timeout)) = (&options.keep_alive, &request) {
+                break;
+            }
+        }
+
+        requests += 1;
+
+        let buffered = deferral.and_then(|deferral| deferral.buffered);
+
+        if let Ok(request) = &mut request {
+            request.secure = stream.is_secure();
+            request.local_addr = local_addr;
+            request.set_deadline(options.timeout, &options.deadline_proxies);
+            request.set_trace(options.trace);
+        }
+
+        // If the request was rejected by the request condition, respond using its head
+        let (mut request, mut rejected) = match (request, rejection) {
+            (Err(RequestError::Rejected), Some((head, response))) => (Ok(head), Some(response)),
+            (request, _) => (request, None),
+        };
+
+        // Rewrite the request before it is routed, which may also reject it
+        if let (Ok(request), None, Some(rewriter)) =
+            (&mut request, &rejected, &options.request_rewriter)
+        {
+            rejected = rewriter(request);
+        }
+
+        // Handle the request as the method it asks for, if the method override is enabled
+        if let (Ok(request), None, Some(method_override)) =
+            (&mut request, &rejected, &options.method_override)
+        {
+            apply_method_override(request, method_override, &subapps, &default_subapp);
+        }
+
+        // Merge or redirect paths which only match a route with a different trailing slash
+        if let (Ok(request), None) = (&mut request, &rejected) {
+            rejected = apply_trailing_slash(request, &subapps, &default_subapp);
+        }
+
+        // If the app is draining, refuse new requests and close the connection
+        let mut draining = false;
+        if let (Ok(request), None) = (&request, &rejected) {
+            rejected = draining_response(
+                &options.drain_signal,
+                options.drain_options.as_ref(),
+                &|status_code| {
+                    error_handler(
+                        ErrorContext::new(status_code)
+                            .with_request(request)
+                            .with_cause(ErrorCause::Draining),
+                    )
+                },
+            );
+            draining = rejected.is_some();
+        }
+
+        // Refuse the request if its client has used up its allowance under the rate limit
+        if let (Ok(request), None, Some(limiter)) = (&request, &rejected, &options.rate_limiter) {
+            if let Err(retry_after) = limiter.check(addr.ip()) {
+                monitor.send_with(EventType::RequestRateLimited, |event| {
+                    event
+                        .with_peer(addr)
+                        .with_info(limiter.describe())
+                        .with_trace(request.trace.as_ref())
+                });
+
+                rejected = Some(rate_limited_response(retry_after, &|status_code| {
+                    error_handler(
+                        ErrorContext::new(status_code)
+                            .with_request(request)
+                            .with_cause(ErrorCause::LimitReached(limiter.describe())),
+                    )
+                }));
+            }
+        }
+
+        // Run the request side of the middleware for the request's host, which may answer it itself
+        let host = request
+            .as_ref()
+            .ok()
+            .and_then(|request| request.headers.get(&HeaderType::Host));
+        let middleware = find_middleware(&subapps, &default_subapp, host);
+        let mut middleware_ran = 0;
+        if let (Ok(request), None) = (&mut request, &rejected) {
+            if request.method != Method::Options {
+                (middleware_ran, rejected) = run_request_middleware(&middleware, request, &state);
+            }
+        }
+
         let cloned_state = state.clone();
 
         // If the request is valid an is a WebSocket request, call the corresponding handler
-        if let Ok(req) = &request {
+        if let (Ok(req), None) = (&request, &rejected) {
             if req.headers.get(&HeaderType::Upgrade) == Some("websocket") {
-                monitor.send(Event::new(EventType::WebsocketConnectionRequested).with_peer(addr));
+                let handler = get_websocket_handler(req, &subapps, &default_subapp);
+
+                // The route's guard decides first, then the connection must fit within the limits
+                let decision = handler.map(|handler| (handler, handler.check_upgrade(req, &state)));
+                let upgrade = match decision {
+                    Some((handler, UpgradeDecision::Reject(response))) => Err((
+                        format!("refused by the guard of route {}", handler.route),
+                        Some(response),
+                    )),
+                    _ => WebsocketWorker::acquire(&options, addr.ip()).map_err(|e| (e, None)),
+                };
 
-                call_websocket_handler(req, &subapps, &default_subapp, cloned_state, stream);
+                match upgrade {
+                    Ok(worker) => {
+                        monitor.send_with(EventType::WebsocketConnectionRequested, |event| {
+                            event.with_peer(addr).with_info(worker.describe())
+                        });
+
+                        if let Some(handler) = handler {
+                            handler.handler.serve(
+                                handler_request(req, &handler.route, false),
+                                stream,
+                                cloned_state,
+                            );
+                        }
 
-                monitor.send(Event::new(EventType::WebsocketConnectionClosed).with_peer(addr));
-                break;
+                        drop(worker);
+                        monitor.send_with(EventType::WebsocketConnectionClosed, |event| {
+                            event
+                                .with_peer(addr)
+                                .with_info(WebsocketWorker::describe_count(&options))
+                        });
+                        break;
+                    }
+                    Err((reason, response)) => {
+                        monitor.send_with(EventType::WebsocketConnectionRefused, |event| {
+                            event.with_peer(addr).with_info(reason.clone())
+                        });
+
+                        rejected = Some(response.unwrap_or_else(|| {
+                            error_handler(
+                                ErrorContext::new(StatusCode::ServiceUnavailable)
+                                    .with_request(req)
+                                    .with_cause(ErrorCause::LimitReached(reason)),
+                            )
+                        }));
+                    }
+                }
+            }
+        }
+
+        // A connection whose request body was cut short cannot be reused, since the rest of the body
+        //   may still arrive and be mistaken for the next request
+        if let Ok(request) = &request {
+            if let BodyState::Truncated { received, expected } = request.body_state {
+                monitor.send_with(EventType::RequestBodyIncomplete, |event| {
+                    event
+                        .with_peer(addr)
+                        .with_info(format!("{} of {} bytes received", received, expected))
+                        .with_trace(request.trace.as_ref())
+                });
             }
         }
 
         // Get the keep alive information from the request before it is consumed by the handler
-        let keep_alive = if let Ok(request) = &request {
+        let mut keep_alive = if let Ok(request) = &request {
             if let Some(connection) = request.headers.get(&HeaderType::Connection) {
                 connection.to_ascii_lowercase() == "keep-alive"
+                    && !draining
+                    && request.body_complete()
+                    && (rejected.is_none() || request.rejected_body_drained())
             } else {
                 false
             }
@@ -597,15 +1713,22 @@ fn client_handler<State>(
         };
 
         // Generate the response based on the handlers
-        let response = match &request {
-            Ok(request) if request.method == Method::Options => {
-                let handler = get_handler(request, &subapps, &default_subapp);
+        let mut route: Option<String> = None;
+        let mut response = match &request {
+            Ok(request) if request.method == Method::Options && rejected.is_none() => {
+                let handler = get_handler(request, &subapps, &default_subapp)
+                    .map(|handler| (&handler.route, &handler.cors))
+                    .or_else(|| {
+                        get_streaming_handler(request, &subapps, &default_subapp)
+                            .map(|handler| (&handler.route, &handler.cors))
+                    });
 
                 match handler {
-                    Some(handler) => {
+                    Some((handler_route, cors)) => {
+                        route = timed.then(|| handler_route.clone());
+
                         let mut response = Response::empty(StatusCode::NoContent)
                             .with_header(HeaderType::Date, DateTime::now().to_string())
-                            .with_header(HeaderType::Server, "Humphrey")
                             .with_header(
                                 HeaderType::Connection,
                                 match keep_alive {
@@ -614,45 +1737,153 @@ fn client_handler<State>(
                                 },
                             );
 
-                        handler.cors.set_headers(&mut response.headers);
+                        cors.set_headers(&mut response.headers);
+
+                        // Browsers only send the override header if the preflight response allows it
+                        if options.method_override == Some(MethodOverride::Header) {
+                            cors.allow_override_header(&mut response.headers);
+                        }
+
+                        if let Some(allow) = get_allow_header(request, &subapps, &default_subapp) {
+                            response.headers.add(HeaderType::Allow, allow);
+                        }
 
                         response
                     }
-                    None => error_handler(StatusCode::NotFound),
+                    None => {
+                        error_handler(ErrorContext::new(StatusCode::NotFound).with_request(request))
+                    }
                 }
             }
             Ok(request) => {
                 let handler = get_handler(request, &subapps, &default_subapp);
+                let streaming_handler = get_streaming_handler(request, &subapps, &default_subapp);
+                let mut body = buffered.map(|buffered| {
+                    RequestBody::new(buffered, &mut stream, &request.headers, limits)
+                });
+
+                let mut response = match (rejected, streaming_handler, &mut body, handler) {
+                    (Some(mut response), ..) => {
+                        if !keep_alive {
+                            response.headers.add(HeaderType::Connection, "Close");
+                        }
+
+                        response
+                    }
+                    (None, Some(handler), Some(body), _) => {
+                        route = timed.then(|| handler.route.clone());
+
+                        // Describe the request while the handler runs, so that panics can report it
+                        let context = RequestContext {
+                            route: handler.route.clone(),
+                            peer: addr,
+                            connection_id,
+                        }
+                        .enter();
+
+                        let served = catch_unwind(AssertUnwindSafe(|| {
+                            handler.handler.serve(
+                                handler_request(request, &handler.route, false),
+                                body,
+                                state.clone(),
+                            )
+                        }));
+
+                        drop(context);
+
+                        let mut response = served.unwrap_or_else(|payload| {
+                            keep_alive = false;
+                            panic_response(request, &handler.route, payload, &error_handler)
+                        });
+
+                        handler.cors.set_headers(&mut response.headers);
 
-                let mut response = match handler {
-                    Some(handler) => {
-                        let mut response: Response =
-                            handler.handler.serve(request.clone(), state.clone());
+                        response
+                    }
+                    (None, _, _, Some(handler)) => {
+                        route = timed.then(|| handler.route.clone());
+
+                        // Describe the request while the handler runs, so that panics can report it
+                        let context = RequestContext {
+                            route: handler.route.clone(),
+                            peer: addr,
+                            connection_id,
+                        }
+                        .enter();
+                        let watch = socket
+                            .clone()
+                            .map(|socket| request.cancellation.watch(socket));
+
+                        let served = catch_unwind(AssertUnwindSafe(|| {
+                            handler.handler.serve(
+                                handler_request(
+                                    request,
+                                    &handler.route,
+                                    handler.declares(&Method::Head),
+                                ),
+                                state.clone(),
+                            )
+                        }));
+
+                        drop(watch);
+                        drop(context);
+
+                        let mut response = served.unwrap_or_else(|payload| {
+                            keep_alive = false;
+                            panic_response(request, &handler.route, payload, &error_handler)
+                        });
 
                         handler.cors.set_headers(&mut response.headers);
 
                         response
                     }
-                    None => error_handler(StatusCode::NotFound),
+                    // Methods only routed to routes added for them are not implemented otherwise
+                    (None, ..) if request.method.requires_declared_route() => error_handler(
+                        ErrorContext::new(StatusCode::NotImplemented).with_request(request),
+                    ),
+                    (None, ..) => match get_allow_header(request, &subapps, &default_subapp) {
+                        Some(allow) => error_handler(
+                            ErrorContext::new(StatusCode::MethodNotAllowed).with_request(request),
+                        )
+                        .with_header(HeaderType::Allow, allow),
+                        None => error_handler(
+                            ErrorContext::new(StatusCode::NotFound).with_request(request),
+                        ),
+                    },
                 };
 
-                // Automatically generate required headers
+                run_response_middleware(
+                    &middleware[..middleware_ran],
+                    &mut response,
+                    request,
+                    &state,
+                );
+
+                // Whatever remains of an unread body must be discarded before the connection can be
+                //   reused, which is only done if little enough remains
+                if let Some(mut body) = body {
+                    if !body.finish() {
+                        keep_alive = false;
+                        response.headers.remove(HeaderType::Connection);
+                        response.headers.add(HeaderType::Connection, "Close");
+                    }
+                }
+
+                // Automatically generate required headers, and close the connection if the
+                //   response says it will be, as event streams do
                 match response.headers.get_mut(HeaderType::Connection) {
+                    Some(connection) if connection.eq_ignore_ascii_case("close") => {
+                        keep_alive = false;
+                    }
                     Some(_) => (),
-                    None => {
-                        if let Some(connection) = &request.headers.get(&HeaderType::Connection) {
+                    None => match &request.headers.get(&HeaderType::Connection) {
+                        Some(connection) if request.body_complete() => {
                             response.headers.add(HeaderType::Connection, connection);
-                        } else {
+                        }
+                        _ => {
                             response.headers.add(HeaderType::Connection, "Close");
                         }
-                    }
-                }
-
-                match response.headers.get_mut(HeaderType::Server) {
-                    Some(_) => (),
-                    None => {
-                        response.headers.add(HeaderType::Server, "Humphrey");
-                    }
+                    },
                 }
 
                 match response.headers.get_mut(HeaderType::Date) {
@@ -664,14 +1895,8 @@ fn client_handler<State>(
                     }
                 }
 
-                match response.headers.get_mut(HeaderType::ContentLength) {
-                    Some(_) => (),
-                    None => {
-                        response
-                            .headers
-                            .add(HeaderType::ContentLength, response.body.len().to_string());
-                    }
-                }
+                // Remove the body and adjust the framing where the status code or method require it
+                response.finalise_framing(&request.method);
 
                 // Set HTTP version
                 response.version = request.version.clone();
@@ -679,68 +1904,240 @@ fn client_handler<State>(
                 response
             }
             Err(e) => match e {
-                RequestError::Request => error_handler(StatusCode::BadRequest),
-                RequestError::Timeout => error_handler(StatusCode::RequestTimeout),
+                RequestError::Request => error_handler(
+                    ErrorContext::new(StatusCode::BadRequest)
+                        .with_cause(ErrorCause::Request(e.clone())),
+                ),
+                RequestError::Timeout => error_handler(
+                    ErrorContext::new(StatusCode::RequestTimeout)
+                        .with_cause(ErrorCause::Request(e.clone())),
+                ),
+                RequestError::TooLarge(part) => error_handler(
+                    ErrorContext::new(part.status_code())
+                        .with_cause(ErrorCause::Request(e.clone())),
+                )
+                .with_header(HeaderType::Connection, "Close"),
                 RequestError::Disconnected => return,
-                RequestError::Stream => {
+                RequestError::Stream | RequestError::Rejected => {
                     return monitor.send(Event::new(EventType::RequestServedError))
                 }
             },
         };
 
+        // HTTP/1.0 clients do not understand chunked framing and only keep the connection open if
+        //   they asked to
+        if let Ok(request) = &request {
+            if request.version == "HTTP/1.0" {
+                response.finalise_http_1_0(keep_alive);
+            }
+        }
+
+        // Advertise the keep-alive limits, closing the connection after the last request allowed
+        if let Some(keep_alive_config) = &options.keep_alive {
+            keep_alive_config.limit(&mut response, &mut keep_alive, requests);
+        }
+
+        let host = request
+            .as_ref()
+            .ok()
+            .and_then(|request| request.headers.get(&HeaderType::Host));
+        apply_default_headers(&mut response, &subapps, &default_subapp, host);
+        options.security.apply(&mut response, stream.is_secure());
+
         // Write the response to the stream
+        timer.handled();
         let status = response.status_code;
-        let response_bytes: Vec<u8> = response.into();
+        let hijack = response.hijack.take();
+        let close_delimited = response.is_close_delimited();
+
+        // Streamed bodies are written as they are read, so reading them can also fail here, and
+        //   anything still working on the request is told to stop
+        if let Err(e) = response.write_to(&mut stream) {
+            if let Ok(request) = &request {
+                request.cancellation.cancel();
+            }
 
-        if let Err(e) = stream.write_all(&response_bytes) {
-            monitor.send(
-                Event::new(EventType::RequestServedError)
-                    .with_peer(addr)
-                    .with_info(e.to_string()),
-            );
+            monitor.send_with(EventType::RequestServedError, |event| {
+                event.with_peer(addr).with_info(e.to_string())
+            });
 
             break;
         };
 
+        options.counters.record_response(status);
+        let timing = timer.written();
+
+        if let Some(recorder) = &options.recorder {
+            recorder.record(
+                addr,
+                &raw,
+                request.as_ref().ok(),
+                status,
+                timing.handler + timing.write,
+            );
+        }
+
+        // Only the handler counts towards the threshold, since a slow client can delay the write
+        if let (Some(threshold), Ok(request)) = (slow_request_threshold, &request) {
+            if timing.handler > threshold {
+                monitor.send_with(EventType::SlowRequest, |event| {
+                    event
+                        .with_peer(addr)
+                        .with_request(RequestDetails {
+                            method: request.original_method().clone(),
+                            uri: request.uri.clone(),
+                            route,
+                            status,
+                            timing,
+                        })
+                        .with_trace(request.trace.as_ref())
+                });
+            }
+        }
+
         let status_str: &str = status.into();
 
         match status {
-            StatusCode::OK => monitor.send(
-                Event::new(EventType::RequestServedSuccess)
-                    .with_peer(addr)
-                    .with_info(format!("200 OK {}", request.unwrap().uri)),
-            ),
-            StatusCode::RequestTimeout => monitor.send(
-                Event::new(EventType::RequestTimeout)
+            StatusCode::OK => monitor.send_with(EventType::RequestServedSuccess, |event| {
+                let request = request.unwrap();
+
+                event
                     .with_peer(addr)
-                    .with_info("408 Request Timeout"),
-            ),
+                    .with_info(format!("200 OK {}", request.uri))
+                    .with_trace(request.trace.as_ref())
+            }),
+            StatusCode::RequestTimeout => monitor.send_with(EventType::RequestTimeout, |event| {
+                event.with_peer(addr).with_info("408 Request Timeout")
+            }),
             e => {
                 if let Ok(request) = request {
-                    monitor.send(
-                        Event::new(EventType::RequestServedError)
+                    monitor.send_with(EventType::RequestServedError, |event| {
+                        event
                             .with_peer(addr)
-                            .with_info(format!("{} {} {}", u16::from(e), status_str, request.uri)),
-                    )
+                            .with_info(format!("{} {} {}", u16::from(e), status_str, request.uri))
+                            .with_trace(request.trace.as_ref())
+                    })
                 } else {
-                    monitor.send(
-                        Event::new(EventType::RequestServedError)
+                    monitor.send_with(EventType::RequestServedError, |event| {
+                        event
                             .with_peer(addr)
-                            .with_info(format!("{} {}", u16::from(e), status_str)),
-                    )
+                            .with_info(format!("{} {}", u16::from(e), status_str))
+                    })
                 }
             }
         }
 
-        // If the request specified to keep the connection open, respect this
-        if !keep_alive {
+        // If the handler asked for the connection, hand it over and forget about it
+        if let Some(hijack) = hijack {
+            monitor.send_with(EventType::ConnectionHijacked, |event| event.with_peer(addr));
+            hijack.run(stream);
+
+            return;
+        }
+
+        // If the request specified to keep the connection open, respect this, unless the end of the
+        //   body was marked by closing the connection
+        if !keep_alive || close_delimited {
             break;
         }
 
-        monitor.send(Event::new(EventType::KeepAliveRespected).with_peer(addr));
+        monitor.send_with(EventType::KeepAliveRespected, |event| event.with_peer(addr));
+    }
+
+    monitor.send_with(EventType::ConnectionClosed, |event| event.with_peer(addr));
+}
+
+/// Represents a worker held by a WebSocket connection, which is released when dropped so that the
+///   count stays accurate even if the handler panics.
+struct WebsocketWorker<'a> {
+    options: &'a ConnectionOptions,
+    ip: IpAddr,
+}
+
+impl<'a> WebsocketWorker<'a> {
+    /// Attempts to hold a worker for a WebSocket connection from the given address, returning a
+    ///   description of the limit which was reached if it cannot.
+    fn acquire(options: &'a ConnectionOptions, ip: IpAddr) -> Result<Self, String> {
+        let limit = options.websocket_limit.unwrap_or(usize::MAX);
+        let limit_per_ip = options.websocket_limit_per_ip.unwrap_or(usize::MAX);
+
+        // The lock is held until the connection is counted, so that concurrent upgrades from the same
+        //   address cannot both take its last place
+        let mut clients = options.websocket_clients.lock().unwrap();
+        let from_ip = clients.get(&ip).copied().unwrap_or(0);
+
+        if from_ip >= limit_per_ip {
+            return Err(format!(
+                "{}/{} WebSocket connections from {}",
+                from_ip, limit_per_ip, ip
+            ));
+        }
+
+        options
+            .websocket_workers
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                (count < limit).then_some(count + 1)
+            })
+            .map_err(|_| Self::describe_count(options))?;
+
+        clients.insert(ip, from_ip + 1);
+
+        Ok(Self { options, ip })
+    }
+
+    /// Describes the number of workers currently held, including this one.
+    fn describe(&self) -> String {
+        Self::describe_count(self.options)
+    }
+
+    /// Describes the number of workers currently held by WebSocket connections.
+    fn describe_count(options: &ConnectionOptions) -> String {
+        let count = options.websocket_workers.load(Ordering::SeqCst);
+
+        match options.websocket_limit {
+            Some(limit) => format!("{}/{} workers held by WebSockets", count, limit),
+            None => format!("{} workers held by WebSockets", count),
+        }
+    }
+}
+
+impl Drop for WebsocketWorker<'_> {
+    fn drop(&mut self) {
+        self.options
+            .websocket_workers
+            .fetch_sub(1, Ordering::SeqCst);
+
+        // This runs while unwinding from a panicking handler, so it must not panic itself
+        let mut clients = match self.options.websocket_clients.lock() {
+            Ok(clients) => clients,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        if let Some(count) = clients.get_mut(&self.ip) {
+            *count -= 1;
+
+            if *count == 0 {
+                clients.remove(&self.ip);
+            }
+        }
+    }
+}
+
+impl WebsocketConnections {
+    /// Returns the total number of WebSocket connections currently open.
+    pub fn total(&self) -> usize {
+        self.total.load(Ordering::SeqCst)
     }
 
-    monitor.send(Event::new(EventType::ConnectionClosed).with_peer(addr));
+    /// Returns the number of WebSocket connections currently open from the given IP address.
+    pub fn from_ip(&self, ip: IpAddr) -> usize {
+        self.clients.lock().unwrap().get(&ip).copied().unwrap_or(0)
+    }
+
+    /// Returns the number of IP addresses with at least one WebSocket connection currently open.
+    pub fn clients(&self) -> usize {
+        self.clients.lock().unwrap().len()
+    }
 }
 
 /// Gets the correct handler for the given request.
@@ -749,101 +2146,129 @@ pub(crate) fn get_handler<'a, State>(
     subapps: &'a [SubApp<State>],
     default_subapp: &'a SubApp<State>,
 ) -> Option<&'a RouteHandler<State>> {
-    // Iterate over the sub-apps and find the one which matches the host
-    if let Some(host) = request.headers.get(&HeaderType::Host) {
-        if let Some(subapp) = subapps
-            .iter()
-            .find(|subapp| wildcard_match(&subapp.host, host))
-        {
-            // If the sub-app has a handler for this route, call it
-            if let Some(handler) = subapp
-                .routes // Get the routes of the sub-app
-                .iter() // Iterate over the routes
-                .find(|route| route.route.route_matches(&request.uri))
-            // Find the route that matches
-            {
-                return Some(handler);
-            }
-        }
-    }
-
-    // If no sub-app was found, try to use the handler on the default sub-app
-    if let Some(handler) = default_subapp
-        .routes
-        .iter()
-        .find(|route| route.route.route_matches(&request.uri))
-    {
-        return Some(handler);
-    }
+    let host = request.headers.get(&HeaderType::Host);
 
-    None
+    find_route(subapps, default_subapp, &request.method, host, &request.uri)
+        .map(|(subapp, index)| &subapp.routes[index])
 }
 
-/// Calls the correct WebSocket handler for the given request.
-fn call_websocket_handler<State>(
+/// Gets the `Allow` header for the path of the given request, if every route for the path only
+///   handles specific methods.
+pub(crate) fn get_allow_header<State>(
     request: &Request,
     subapps: &[SubApp<State>],
     default_subapp: &SubApp<State>,
-    state: Arc<State>,
-    stream: Stream,
-) {
-    // Iterate over the sub-apps and find the one which matches the host
-    if let Some(host) = request.headers.get(&HeaderType::Host) {
-        if let Some(subapp) = subapps
-            .iter()
-            .find(|subapp| wildcard_match(&subapp.host, host))
-        {
-            // If the sub-app has a handler for this route, call it
-            if let Some(handler) = subapp
-                .websocket_routes // Get the WebSocket routes of the sub-app
-                .iter() // Iterate over the routes
-                .find(|route| route.route.route_matches(&request.uri))
-            {
-                handler.handler.serve(request.clone(), stream, state);
-                return;
-            }
-        }
-    }
+) -> Option<String> {
+    let host = request.headers.get(&HeaderType::Host);
+
+    allow_header(subapps, default_subapp, host, &request.uri)
+}
 
-    // If no sub-app was found, try to use the handler on the default sub-app
-    if let Some(handler) = default_subapp
-        .websocket_routes
+/// Gets the correct streaming handler for the given request.
+pub(crate) fn get_streaming_handler<'a, State>(
+    request: &Request,
+    subapps: &'a [SubApp<State>],
+    default_subapp: &'a SubApp<State>,
+) -> Option<&'a StreamingRouteHandler<State>> {
+    let host = request.headers.get(&HeaderType::Host);
+
+    find_streaming_route(subapps, default_subapp, host, &request.uri)
+}
+
+/// Gets the correct WebSocket handler for the given request.
+fn get_websocket_handler<'a, State>(
+    request: &Request,
+    subapps: &'a [SubApp<State>],
+    default_subapp: &'a SubApp<State>,
+) -> Option<&'a WebsocketRouteHandler<State>> {
+    // Use the sub-app which matches the host if it has a handler for this route, otherwise try to
+    //   use the handler on the default sub-app
+    request
+        .headers
+        .get(&HeaderType::Host)
+        .and_then(|host| find_subapp(subapps, host))
+        .and_then(|subapp| subapp.websocket_route(&request.uri))
+        .or_else(|| default_subapp.websocket_route(&request.uri))
+}
+
+/// Returns the port that insecure requests are redirected to, which is that of the first TLS
+///   listener so that, for example, requests on port 8080 are redirected to port 8443.
+#[cfg(feature = "tls")]
+fn https_port(listeners: &[(TcpListener, EndpointKind)]) -> u16 {
+    listeners
         .iter()
-        .find(|route| route.route.route_matches(&request.uri))
-    {
-        handler.handler.serve(request.clone(), stream, state)
-    }
+        .filter(|(_, kind)| *kind == EndpointKind::Tls)
+        .find_map(|(socket, _)| socket.local_addr().ok())
+        .map_or(443, |addr| addr.port())
 }
 
+/// Reads a request from the stream and redirects it to the same URL over HTTPS on the given port,
+///   reading the PROXY protocol header first if there is one.
+///
+/// The request must arrive within the connection timeout, or `REDIRECT_TIMEOUT` if there is none, so
+///   that slow clients cannot hold the worker. Requests which cannot be parsed are answered with an
+///   error instead of being redirected.
 #[cfg(feature = "tls")]
-fn force_https_thread(monitor: MonitorConfig) -> Result<(), Box<dyn std::error::Error>> {
-    let socket = TcpListener::bind("0.0.0.0:80")?;
-
-    for mut stream in socket.incoming().flatten() {
-        let addr = stream.peer_addr()?;
-        let request = Request::from_stream(&mut stream, addr)?;
-
-        let response = if let Some(host) = request.headers.get(&HeaderType::Host) {
-            Response::empty(StatusCode::MovedPermanently)
-                .with_header(
-                    HeaderType::Location,
-                    format!("https://{}{}", host, request.uri),
-                )
-                .with_header(HeaderType::Connection, "Close")
-        } else {
-            Response::empty(StatusCode::OK)
-                .with_bytes(b"<h1>Please access over HTTPS</h1>")
-                .with_header(HeaderType::ContentLength, "33")
-                .with_header(HeaderType::Connection, "Close")
-        };
+fn redirect_to_https(
+    mut stream: TcpStream,
+    https_port: u16,
+    options: &ConnectionOptions,
+    monitor: &MonitorConfig,
+) {
+    let timeout = options.timeout.unwrap_or(REDIRECT_TIMEOUT);
+    let _ = stream.set_read_timeout(Some(timeout));
+    let _ = stream.set_write_timeout(Some(timeout));
 
-        let response_bytes: Vec<u8> = response.into();
-        stream.write_all(&response_bytes)?;
+    let addr = match options.proxy_protocol {
+        true => proxy_protocol::client_addr(&mut stream, monitor),
+        false => stream.peer_addr().ok(),
+    };
+    let Some(addr) = addr else {
+        return;
+    };
 
-        monitor.send(Event::new(EventType::HTTPSRedirect).with_peer(addr));
+    let request = Request::from_reader_checked(
+        &mut stream,
+        addr,
+        None,
+        None,
+        &options.security.request_limits,
+    );
+
+    let response = match &request {
+        Ok(request) => redirect_response(request, https_port),
+        Err(e) => match redirect_error(e.clone(), addr, monitor) {
+            Some(response) => response,
+            None => return,
+        },
+    };
+
+    match response.write_to(&mut stream) {
+        Ok(()) if request.is_ok() => {
+            monitor.send_with(EventType::HTTPSRedirect, |event| event.with_peer(addr))
+        }
+        Ok(()) => (),
+        Err(e) => monitor.send_with(EventType::RequestServedError, |event| {
+            event.with_peer(addr).with_info(e.to_string())
+        }),
     }
+}
 
-    Ok(())
+/// Generates the response for a request whose handler panicked, which closes the connection since
+///   the handler may have left it part of the way through reading the body.
+pub(crate) fn panic_response(
+    request: &Request,
+    route: &str,
+    payload: Box<dyn Any + Send>,
+    error_handler: &dyn Fn(ErrorContext) -> Response,
+) -> Response {
+    error_handler(
+        ErrorContext::new(StatusCode::InternalError)
+            .with_request(request)
+            .with_route(route)
+            .with_cause(ErrorCause::panic(payload)),
+    )
+    .with_header(HeaderType::Connection, "Close")
 }
 
 /// The default error handler for every Humphrey app.
@@ -857,17 +2282,3 @@ pub(crate) fn error_handler(status_code: StatusCode) -> Response {
 
     Response::new(status_code, body.as_bytes())
 }
-
-fn unspecified_socket_to_loopback<S>(socket: S) -> SocketAddr
-where
-    S: ToSocketAddrs,
-{
-    let mut socket = socket.to_socket_addrs().unwrap().next().unwrap(); // This can't fail, because the server was able to start.
-    if socket.ip().is_unspecified() {
-        match socket.ip() {
-            IpAddr::V4(_) => socket.set_ip(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))),
-            IpAddr::V6(_) => socket.set_ip(IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0x1))),
-        };
-    }
-    socket
-}
@@ -0,0 +1,309 @@
+//! Provides cancellation of handler work when the client disconnects.
+//!
+//! Every request has a `CancellationToken`, available from `request.cancellation_token()`, which is
+//!   signalled when the app notices that the client which sent the request has disconnected.
+//!   Handlers which do a lot of work can check the token or register a callback with it, and stop
+//!   working once nobody is waiting for the response.
+//!
+//! While the handler of a route runs, the app watches the connection for the client closing it. The
+//!   synchronous app checks the connection whenever the token is checked, and every
+//!   `WATCH_INTERVAL` while callbacks are registered, and the tokio app waits for the connection to
+//!   close alongside the handler. Failing to write the response, including a streamed body, also
+//!   signals the token, so that whatever produces a streamed body can stop.
+//!
+//! Cancellation is cooperative, so handlers which never check the token run to completion as usual.
+//!   Only the end of the connection is noticed, so a client which closes its half of the connection
+//!   after sending its request is treated as having disconnected. Disconnections are not noticed
+//!   while data which the client sent after the request, such as a pipelined request or a TLS close
+//!   notification, remains unread, nor for routes which stream the request body, whose reads fail
+//!   instead.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+#[cfg(all(feature = "runtime", not(feature = "tokio")))]
+use std::io::ErrorKind;
+#[cfg(all(feature = "runtime", not(feature = "tokio")))]
+use std::net::TcpStream;
+#[cfg(all(feature = "runtime", not(feature = "tokio")))]
+use std::sync::{OnceLock, Weak};
+#[cfg(all(feature = "runtime", not(feature = "tokio")))]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "tokio")]
+use std::future::Future;
+#[cfg(feature = "tokio")]
+use tokio::net::TcpStream;
+#[cfg(feature = "tokio")]
+use tokio::sync::Notify;
+
+/// How often the connections of requests with registered callbacks are checked for disconnection.
+#[cfg(all(feature = "runtime", not(feature = "tokio")))]
+pub const WATCH_INTERVAL: Duration = Duration::from_millis(20);
+
+/// The minimum time between two checks of a connection when the token is checked repeatedly.
+#[cfg(all(feature = "runtime", not(feature = "tokio")))]
+const CHECK_INTERVAL: Duration = Duration::from_millis(5);
+
+/// The tokens whose connections are checked every `WATCH_INTERVAL`, which are removed once they are
+///   cancelled or the handler of their request has returned.
+#[cfg(all(feature = "runtime", not(feature = "tokio")))]
+static WATCHED: OnceLock<Mutex<Vec<Weak<Inner>>>> = OnceLock::new();
+
+/// A callback which is run when a token is cancelled.
+type Callback = Box<dyn FnOnce() + Send>;
+
+/// A token which is signalled when the client which sent a request disconnects.
+///
+/// Cloned tokens share their state, so a token can be moved to another thread doing work for the
+///   handler. Tokens which are not part of a request served by an app, such as those of requests
+///   built with `Request::builder`, are only cancelled with `cancel`.
+///
+/// ## Example
+/// ```
+/// fn report_handler(request: Request) -> Response {
+///     let cancellation = request.cancellation_token();
+///     let mut report = String::new();
+///
+///     for row in 0..1_000_000 {
+///         if cancellation.is_cancelled() {
+///             return Response::empty(StatusCode::ServiceUnavailable);
+///         }
+///
+///         report.push_str(&expensive_row(row));
+///     }
+///
+///     Response::new(StatusCode::OK, report)
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct CancellationToken(Arc<Inner>);
+
+/// The state shared between clones of a token.
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    callbacks: Mutex<Vec<Callback>>,
+    /// The connection of the request and when it was last checked, while its handler runs.
+    #[cfg(all(feature = "runtime", not(feature = "tokio")))]
+    connection: Mutex<Option<(Arc<TcpStream>, Option<Instant>)>>,
+    #[cfg(feature = "tokio")]
+    notify: Notify,
+}
+
+/// Stops the connection of a request from being checked when dropped, once its handler has returned.
+#[cfg(all(feature = "runtime", not(feature = "tokio")))]
+pub(crate) struct Watch<'a>(&'a CancellationToken);
+
+impl CancellationToken {
+    /// Creates a new token which is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `true` if the request has been cancelled.
+    ///
+    /// In the synchronous app, this also checks whether the client has disconnected, at most once
+    ///   every few milliseconds, so it is cheap enough to call in a loop.
+    pub fn is_cancelled(&self) -> bool {
+        #[cfg(all(feature = "runtime", not(feature = "tokio")))]
+        if !self.0.cancelled.load(Ordering::SeqCst) {
+            self.check(false);
+        }
+
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Registers a callback to run when the request is cancelled, or runs it immediately if it has
+    ///   already been cancelled.
+    ///
+    /// The callback may run on any thread, including one of the app's, so it should return quickly,
+    ///   for example by signalling the thread doing the work. Callbacks registered after the handler
+    ///   has returned only run if writing the response fails.
+    pub fn on_cancel<F>(&self, callback: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut callbacks = self.0.callbacks.lock().unwrap();
+
+        if self.0.cancelled.load(Ordering::SeqCst) {
+            drop(callbacks);
+            return callback();
+        }
+
+        callbacks.push(Box::new(callback));
+        drop(callbacks);
+
+        #[cfg(all(feature = "runtime", not(feature = "tokio")))]
+        self.watch_in_background();
+    }
+
+    /// Waits until the request is cancelled.
+    ///
+    /// This never completes if the client stays connected, so it should be raced against the work
+    ///   of the handler, for example with `tokio::select!`.
+    #[cfg(feature = "tokio")]
+    pub async fn cancelled(&self) {
+        loop {
+            let notified = self.0.notify.notified();
+
+            if self.0.cancelled.load(Ordering::SeqCst) {
+                return;
+            }
+
+            notified.await;
+        }
+    }
+
+    /// Cancels the request, running every registered callback.
+    ///
+    /// The app does this when the client disconnects, but it can also be used to test how handlers
+    ///   react to cancellation.
+    pub fn cancel(&self) {
+        if self.0.cancelled.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let callbacks = std::mem::take(&mut *self.0.callbacks.lock().unwrap());
+        for callback in callbacks {
+            callback();
+        }
+
+        #[cfg(feature = "tokio")]
+        self.0.notify.notify_waiters();
+    }
+
+    /// Checks the given connection for disconnection while the handler of the request runs, until
+    ///   the returned guard is dropped.
+    #[cfg(all(feature = "runtime", not(feature = "tokio")))]
+    pub(crate) fn watch(&self, connection: Arc<TcpStream>) -> Watch<'_> {
+        *self.0.connection.lock().unwrap() = Some((connection, None));
+
+        if !self.0.callbacks.lock().unwrap().is_empty() {
+            self.watch_in_background();
+        }
+
+        Watch(self)
+    }
+
+    /// Checks whether the client has disconnected, cancelling the request if so, unless the
+    ///   connection was checked very recently and `force` is `false`.
+    ///
+    /// Returns `false` if the connection is no longer being watched.
+    #[cfg(all(feature = "runtime", not(feature = "tokio")))]
+    fn check(&self, force: bool) -> bool {
+        let mut connection = self.0.connection.lock().unwrap();
+        let (stream, last_checked) = match connection.as_mut() {
+            Some(connection) => connection,
+            None => return false,
+        };
+
+        if !force && last_checked.is_some_and(|last| last.elapsed() < CHECK_INTERVAL) {
+            return true;
+        }
+
+        *last_checked = Some(Instant::now());
+
+        // The connection is only checked while the handler runs, so nothing else is using it while
+        //   it is briefly non-blocking
+        let mut buf = [0; 1];
+        let disconnected = stream.set_nonblocking(true).is_ok()
+            && match stream.peek(&mut buf) {
+                Ok(0) => true,
+                Ok(_) => false,
+                Err(e) => !matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::Interrupted),
+            };
+        stream.set_nonblocking(false).ok();
+
+        if disconnected {
+            *connection = None;
+            drop(connection);
+            self.cancel();
+        }
+
+        !disconnected
+    }
+
+    /// Adds the token to those whose connections are checked by the watcher thread, starting it if
+    ///   necessary.
+    #[cfg(all(feature = "runtime", not(feature = "tokio")))]
+    fn watch_in_background(&self) {
+        let watched = WATCHED.get_or_init(|| {
+            std::thread::spawn(|| loop {
+                std::thread::sleep(WATCH_INTERVAL);
+
+                let tokens: Vec<CancellationToken> = {
+                    let mut watched = WATCHED.get().unwrap().lock().unwrap();
+                    watched.retain(|token| token.strong_count() > 0);
+                    watched
+                        .iter()
+                        .filter_map(|token| token.upgrade().map(CancellationToken))
+                        .collect()
+                };
+
+                // Tokens are checked without holding the lock, since cancelling runs callbacks
+                let finished: Vec<CancellationToken> = tokens
+                    .into_iter()
+                    .filter(|token| !token.check(true))
+                    .collect();
+
+                WATCHED.get().unwrap().lock().unwrap().retain(|token| {
+                    !finished
+                        .iter()
+                        .any(|finished| std::ptr::eq(token.as_ptr(), Arc::as_ptr(&finished.0)))
+                });
+            });
+
+            Mutex::new(Vec::new())
+        });
+
+        let mut watched = watched.lock().unwrap();
+        if self.0.connection.lock().unwrap().is_some()
+            && !watched
+                .iter()
+                .any(|token| std::ptr::eq(token.as_ptr(), Arc::as_ptr(&self.0)))
+        {
+            watched.push(Arc::downgrade(&self.0));
+        }
+    }
+}
+
+/// Runs the handler of a request until it completes, cancelling the request if the client closes the
+///   given connection in the meantime.
+#[cfg(feature = "tokio")]
+pub(crate) async fn watch<F, T>(handler: F, connection: &TcpStream, token: &CancellationToken) -> T
+where
+    F: Future<Output = T>,
+{
+    let disconnected = async {
+        let mut buf = [0; 1];
+
+        // Data which has already arrived cannot be looked past, so only the end of the connection
+        //   right after the request is noticed
+        if let Ok(0) | Err(_) = connection.peek(&mut buf).await {
+            token.cancel();
+        }
+
+        std::future::pending::<()>().await
+    };
+
+    tokio::select! {
+        result = handler => result,
+        _ = disconnected => unreachable!(),
+    }
+}
+
+#[cfg(all(feature = "runtime", not(feature = "tokio")))]
+impl Drop for Watch<'_> {
+    fn drop(&mut self) {
+        *self.0 .0.connection.lock().unwrap() = None;
+    }
+}
+
+impl std::fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.0.cancelled.load(Ordering::SeqCst))
+            .finish_non_exhaustive()
+    }
+}
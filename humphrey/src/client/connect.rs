@@ -0,0 +1,302 @@
+//! Provides connection establishment for the client.
+//!
+//! When a host resolves to several addresses, connection attempts are made in turn, alternating
+//!   between IPv6 and IPv4 addresses, in the manner of "happy eyeballs"
+//!   ([RFC 8305](https://datatracker.ietf.org/doc/html/rfc8305)). If an attempt has not finished
+//!   after a short delay, the next one is started without abandoning it, and whichever connects
+//!   first is used. This means that an address which silently drops packets only delays the
+//!   connection by the attempt delay instead of the full TCP timeout.
+
+use std::error::Error;
+use std::fmt::Display;
+use std::io;
+use std::net::{SocketAddr, TcpStream};
+use std::sync::mpsc::channel;
+use std::thread::spawn;
+use std::time::Duration;
+
+/// The time to wait for a connection attempt before starting the next one.
+pub(crate) const ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// An error returned when a connection could not be made to any of the addresses of a host.
+#[derive(Debug)]
+pub struct ConnectError {
+    /// The addresses which were tried, in the order in which they were tried, and the error
+    ///   encountered when connecting to each one.
+    pub attempts: Vec<(SocketAddr, io::Error)>,
+}
+
+/// Connects to the first of the given addresses which accepts the connection, trying them in turn
+///   as described in the module documentation.
+///
+/// If a local address is given, the connection is made from it, and only addresses of the same IP
+///   version can be connected to.
+pub(crate) fn connect(
+    addresses: &[SocketAddr],
+    local_address: Option<SocketAddr>,
+    timeout: Duration,
+) -> Result<TcpStream, ConnectError> {
+    let (tx, rx) = channel();
+    let mut pending: usize = 0;
+    let mut attempts: Vec<(usize, SocketAddr, io::Error)> = Vec::new();
+
+    for (index, address) in interleave(addresses).into_iter().enumerate() {
+        let tx = tx.clone();
+        pending += 1;
+
+        spawn(move || {
+            let result = attempt(address, local_address, timeout);
+            tx.send((index, address, result)).ok();
+        });
+
+        // Wait for the attempt delay, moving on to the next address early if the attempt fails
+        match rx.recv_timeout(ATTEMPT_DELAY) {
+            Ok((_, _, Ok(stream))) => return Ok(stream),
+            Ok((index, address, Err(e))) => {
+                pending -= 1;
+                attempts.push((index, address, e));
+            }
+            Err(_) => (),
+        }
+    }
+
+    // Every attempt ends within the timeout, so this will not wait forever
+    while pending > 0 {
+        match rx.recv() {
+            Ok((_, _, Ok(stream))) => return Ok(stream),
+            Ok((index, address, Err(e))) => {
+                pending -= 1;
+                attempts.push((index, address, e));
+            }
+            Err(_) => break,
+        }
+    }
+
+    attempts.sort_by_key(|(index, _, _)| *index);
+
+    Err(ConnectError {
+        attempts: attempts
+            .into_iter()
+            .map(|(_, address, e)| (address, e))
+            .collect(),
+    })
+}
+
+/// Makes a single connection attempt.
+fn attempt(
+    address: SocketAddr,
+    local_address: Option<SocketAddr>,
+    timeout: Duration,
+) -> io::Result<TcpStream> {
+    match local_address {
+        Some(local) if local.is_ipv4() != address.is_ipv4() => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "The local address is of a different IP version",
+        )),
+        Some(local) => os::connect_from(local, address, timeout),
+        None => TcpStream::connect_timeout(&address, timeout),
+    }
+}
+
+/// Reorders the addresses so that the IP versions alternate, starting with the version of the first
+///   address, while otherwise keeping the order given by the resolver.
+pub(crate) fn interleave(addresses: &[SocketAddr]) -> Vec<SocketAddr> {
+    let first_is_ipv4 = match addresses.first() {
+        Some(address) => address.is_ipv4(),
+        None => return Vec::new(),
+    };
+
+    let (mut preferred, mut other): (Vec<SocketAddr>, Vec<SocketAddr>) = addresses
+        .iter()
+        .partition(|address| address.is_ipv4() == first_is_ipv4);
+
+    let mut result = Vec::with_capacity(addresses.len());
+    preferred.reverse();
+    other.reverse();
+
+    while !preferred.is_empty() || !other.is_empty() {
+        result.extend(preferred.pop());
+        result.extend(other.pop());
+    }
+
+    result
+}
+
+impl Display for ConnectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.attempts.is_empty() {
+            return write!(f, "No addresses to connect to");
+        }
+
+        write!(f, "Could not connect to ")?;
+
+        for (i, (address, error)) in self.attempts.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+
+            write!(f, "{} ({})", address, error)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for ConnectError {}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod os {
+    use std::ffi::{c_int, c_long, c_void};
+    use std::io;
+    use std::net::{SocketAddr, TcpStream};
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+    use std::time::Duration;
+
+    const AF_INET: c_int = 2;
+    const AF_INET6: c_int = 10;
+    const SOCK_STREAM: c_int = 1;
+    const SOCK_CLOEXEC: c_int = 0o2000000;
+    const SOL_SOCKET: c_int = 1;
+    const SO_SNDTIMEO: c_int = 21;
+    const EINPROGRESS: i32 = 115;
+
+    #[repr(C)]
+    struct SockaddrIn {
+        sin_family: u16,
+        sin_port: u16,
+        sin_addr: [u8; 4],
+        sin_zero: [u8; 8],
+    }
+
+    #[repr(C)]
+    struct SockaddrIn6 {
+        sin6_family: u16,
+        sin6_port: u16,
+        sin6_flowinfo: u32,
+        sin6_addr: [u8; 16],
+        sin6_scope_id: u32,
+    }
+
+    #[repr(C)]
+    struct Timeval {
+        tv_sec: c_long,
+        tv_usec: c_long,
+    }
+
+    extern "C" {
+        fn socket(domain: c_int, ty: c_int, protocol: c_int) -> c_int;
+        fn bind(fd: c_int, addr: *const c_void, len: u32) -> c_int;
+        fn connect(fd: c_int, addr: *const c_void, len: u32) -> c_int;
+        fn setsockopt(
+            fd: c_int,
+            level: c_int,
+            name: c_int,
+            value: *const c_void,
+            len: u32,
+        ) -> c_int;
+    }
+
+    /// Connects to the remote address from the local address, since the standard library cannot
+    ///   bind a socket before connecting it.
+    pub fn connect_from(
+        local: SocketAddr,
+        remote: SocketAddr,
+        timeout: Duration,
+    ) -> io::Result<TcpStream> {
+        let domain = if remote.is_ipv4() { AF_INET } else { AF_INET6 };
+
+        // The socket is owned by the stream as soon as it is created, so it is closed on error.
+        let fd = unsafe { socket(domain, SOCK_STREAM | SOCK_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let stream = unsafe { TcpStream::from_raw_fd(fd) };
+
+        // Linux applies the send timeout to `connect`, failing with `EINPROGRESS` when it expires.
+        let timeval = Timeval {
+            tv_sec: timeout.as_secs() as c_long,
+            tv_usec: timeout.subsec_micros() as c_long,
+        };
+        check(unsafe {
+            setsockopt(
+                stream.as_raw_fd(),
+                SOL_SOCKET,
+                SO_SNDTIMEO,
+                &timeval as *const Timeval as *const c_void,
+                std::mem::size_of::<Timeval>() as u32,
+            )
+        })?;
+
+        with_sockaddr(local, |addr, len| unsafe {
+            check(bind(stream.as_raw_fd(), addr, len))
+        })?;
+
+        with_sockaddr(remote, |addr, len| unsafe {
+            check(connect(stream.as_raw_fd(), addr, len))
+        })
+        .map_err(|e| match e.raw_os_error() {
+            Some(EINPROGRESS) => io::Error::new(io::ErrorKind::TimedOut, "Connection timed out"),
+            _ => e,
+        })?;
+
+        stream.set_write_timeout(None)?;
+
+        Ok(stream)
+    }
+
+    /// Calls the function with a pointer to the C representation of the address and its length.
+    fn with_sockaddr<T>(address: SocketAddr, f: impl FnOnce(*const c_void, u32) -> T) -> T {
+        match address {
+            SocketAddr::V4(address) => {
+                let sockaddr = SockaddrIn {
+                    sin_family: AF_INET as u16,
+                    sin_port: address.port().to_be(),
+                    sin_addr: address.ip().octets(),
+                    sin_zero: [0; 8],
+                };
+
+                f(
+                    &sockaddr as *const SockaddrIn as *const c_void,
+                    std::mem::size_of::<SockaddrIn>() as u32,
+                )
+            }
+            SocketAddr::V6(address) => {
+                let sockaddr = SockaddrIn6 {
+                    sin6_family: AF_INET6 as u16,
+                    sin6_port: address.port().to_be(),
+                    sin6_flowinfo: address.flowinfo(),
+                    sin6_addr: address.ip().octets(),
+                    sin6_scope_id: address.scope_id(),
+                };
+
+                f(
+                    &sockaddr as *const SockaddrIn6 as *const c_void,
+                    std::mem::size_of::<SockaddrIn6>() as u32,
+                )
+            }
+        }
+    }
+
+    /// Converts the return value of a system call into a result.
+    fn check(result: c_int) -> io::Result<()> {
+        if result < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+mod os {
+    use std::io;
+    use std::net::{SocketAddr, TcpStream};
+    use std::time::Duration;
+
+    pub fn connect_from(_: SocketAddr, _: SocketAddr, _: Duration) -> io::Result<TcpStream> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Binding the local address is not supported on this platform",
+        ))
+    }
+}
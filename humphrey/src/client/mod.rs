@@ -0,0 +1,678 @@
+//! Provides an HTTP client implementation for Humphrey.
+
+pub(crate) mod connect;
+
+pub use connect::ConnectError;
+
+use crate::cancellation::CancellationToken;
+use crate::http::address::Address;
+use crate::http::cookie::Cookie;
+use crate::http::headers::{Header, HeaderLike, HeaderType, Headers};
+use crate::http::method::Method;
+use crate::http::request::{BodyState, DEADLINE_HEADER};
+use crate::http::{Request, Response, StatusCode};
+use crate::route::RouteParams;
+use crate::trace::{TraceContext, TRACEPARENT_HEADER, TRACESTATE_HEADER};
+
+use std::error::Error;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "tls")]
+use rustls::{Certificate, ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+#[cfg(feature = "tls")]
+use rustls_native_certs::load_native_certs;
+#[cfg(feature = "tls")]
+use std::convert::TryInto;
+#[cfg(feature = "tls")]
+use std::sync::Arc;
+
+/// Represents a function which resolves a host, given as `host:port`, to its socket addresses.
+pub type Resolver = fn(&str) -> io::Result<Vec<SocketAddr>>;
+
+/// The default time to wait for a single connection attempt.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Represents an HTTP client.
+///
+/// When TLS is enabled, this is fairly expensive to instantiate,
+///   so should only be done once per program instead of once per request.
+pub struct Client {
+    local_address: Option<SocketAddr>,
+    resolver: Option<Resolver>,
+    connect_timeout: Duration,
+    #[cfg(feature = "tls")]
+    tls_config: Option<Arc<ClientConfig>>,
+}
+
+impl Client {
+    /// Creates a new HTTP client.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Binds outgoing connections to the given local address, so they leave from a specific interface.
+    ///
+    /// The port may be zero to let the operating system choose one. Only addresses of the same IP
+    ///   version as the local address can be connected to.
+    pub fn with_local_address(mut self, address: SocketAddr) -> Self {
+        self.local_address = Some(address);
+        self
+    }
+
+    /// Overrides how hosts are resolved to socket addresses, for example for service discovery or testing.
+    ///
+    /// The resolver is given the host and port as `host:port` and should return the addresses to try
+    ///   in order of preference.
+    pub fn with_resolver(mut self, resolver: Resolver) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    /// Sets the time to wait for a single connection attempt before it fails.
+    ///
+    /// When a host has several addresses, the next one is tried after a short delay regardless of this,
+    ///   so an unresponsive address does not hold up the others.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Creates a GET request to the given URL.
+    pub fn get(&mut self, url: impl AsRef<str>) -> Result<ClientRequest, Box<dyn Error>> {
+        let url = Self::parse_url(url).ok_or("Invalid URL")?;
+        let addresses = self.resolve(&url.host)?;
+        let request = Request {
+            method: Method::Get,
+            overridden_method: None,
+            uri_raw: url.path.clone(),
+            uri: url.path,
+            headers: url.host_headers,
+            query: url.query,
+            version: "HTTP/1.1".to_string(),
+            content: None,
+            body_state: BodyState::Complete,
+            trailers: Headers::new(),
+            address: Address::new(&addresses[..])?,
+            secure: false,
+            local_addr: None,
+            deadline: None,
+            trace: None,
+            cancellation: CancellationToken::default(),
+            params: RouteParams::default(),
+        };
+
+        Ok(ClientRequest {
+            addresses,
+            client: self,
+            protocol: url.protocol,
+            request,
+            follow_redirects: false,
+            cookies: Vec::new(),
+        })
+    }
+
+    /// Creates a POST request to the given URL.
+    pub fn post(
+        &mut self,
+        url: impl AsRef<str>,
+        data: Vec<u8>,
+    ) -> Result<ClientRequest, Box<dyn Error>> {
+        let url = Self::parse_url(url).ok_or("Invalid URL")?;
+        let addresses = self.resolve(&url.host)?;
+        let content_length = Header::new("Content-Length", data.len().to_string());
+
+        let mut request = Request {
+            method: Method::Post,
+            overridden_method: None,
+            uri_raw: url.path.clone(),
+            uri: url.path,
+            headers: url.host_headers,
+            query: url.query,
+            version: "HTTP/1.1".to_string(),
+            content: Some(data),
+            body_state: BodyState::Complete,
+            trailers: Headers::new(),
+            address: Address::new(&addresses[..])?,
+            secure: false,
+            local_addr: None,
+            deadline: None,
+            trace: None,
+            cancellation: CancellationToken::default(),
+            params: RouteParams::default(),
+        };
+
+        request.headers.push(content_length);
+
+        Ok(ClientRequest {
+            addresses,
+            client: self,
+            protocol: url.protocol,
+            request,
+            follow_redirects: false,
+            cookies: Vec::new(),
+        })
+    }
+
+    /// Creates a PUT request to the given URL.
+    pub fn put(
+        &mut self,
+        url: impl AsRef<str>,
+        data: Vec<u8>,
+    ) -> Result<ClientRequest, Box<dyn Error>> {
+        let url = Self::parse_url(url).ok_or("Invalid URL")?;
+        let addresses = self.resolve(&url.host)?;
+        let content_length = Header::new("Content-Length", data.len().to_string());
+
+        let mut request = Request {
+            method: Method::Put,
+            overridden_method: None,
+            uri_raw: url.path.clone(),
+            uri: url.path,
+            headers: url.host_headers,
+            query: url.query,
+            version: "HTTP/1.1".to_string(),
+            content: Some(data),
+            body_state: BodyState::Complete,
+            trailers: Headers::new(),
+            address: Address::new(&addresses[..])?,
+            secure: false,
+            local_addr: None,
+            deadline: None,
+            trace: None,
+            cancellation: CancellationToken::default(),
+            params: RouteParams::default(),
+        };
+
+        request.headers.push(content_length);
+
+        Ok(ClientRequest {
+            addresses,
+            client: self,
+            protocol: url.protocol,
+            request,
+            follow_redirects: false,
+            cookies: Vec::new(),
+        })
+    }
+
+    /// Creates a PATCH request to the given URL.
+    pub fn patch(
+        &mut self,
+        url: impl AsRef<str>,
+        data: Vec<u8>,
+    ) -> Result<ClientRequest, Box<dyn Error>> {
+        let url = Self::parse_url(url).ok_or("Invalid URL")?;
+        let addresses = self.resolve(&url.host)?;
+        let content_length = Header::new("Content-Length", data.len().to_string());
+
+        let mut request = Request {
+            method: Method::Patch,
+            overridden_method: None,
+            uri_raw: url.path.clone(),
+            uri: url.path,
+            headers: url.host_headers,
+            query: url.query,
+            version: "HTTP/1.1".to_string(),
+            content: Some(data),
+            body_state: BodyState::Complete,
+            trailers: Headers::new(),
+            address: Address::new(&addresses[..])?,
+            secure: false,
+            local_addr: None,
+            deadline: None,
+            trace: None,
+            cancellation: CancellationToken::default(),
+            params: RouteParams::default(),
+        };
+
+        request.headers.push(content_length);
+
+        Ok(ClientRequest {
+            addresses,
+            client: self,
+            protocol: url.protocol,
+            request,
+            follow_redirects: false,
+            cookies: Vec::new(),
+        })
+    }
+
+    /// Creates a DELETE request to the given URL.
+    pub fn delete(&mut self, url: impl AsRef<str>) -> Result<ClientRequest, Box<dyn Error>> {
+        let url = Self::parse_url(url).ok_or("Invalid URL")?;
+        let addresses = self.resolve(&url.host)?;
+        let request = Request {
+            method: Method::Delete,
+            overridden_method: None,
+            uri_raw: url.path.clone(),
+            uri: url.path,
+            headers: url.host_headers,
+            query: url.query,
+            version: "HTTP/1.1".to_string(),
+            content: None,
+            body_state: BodyState::Complete,
+            trailers: Headers::new(),
+            address: Address::new(&addresses[..])?,
+            secure: false,
+            local_addr: None,
+            deadline: None,
+            trace: None,
+            cancellation: CancellationToken::default(),
+            params: RouteParams::default(),
+        };
+
+        Ok(ClientRequest {
+            addresses,
+            client: self,
+            protocol: url.protocol,
+            request,
+            follow_redirects: false,
+            cookies: Vec::new(),
+        })
+    }
+
+    /// Sends a raw request to the given address.
+    ///
+    /// If the request has a deadline, the request fails once it has passed, and the time remaining
+    ///   is sent in the `X-Request-Deadline` header.
+    pub fn request(
+        &self,
+        address: impl ToSocketAddrs,
+        request: Request,
+    ) -> Result<Response, Box<dyn Error>> {
+        let mut stream = self.connect(address, request.deadline)?;
+        let method = request.method.clone();
+        let request_bytes = request_bytes(request);
+        stream.write_all(&request_bytes)?;
+
+        let response = Response::from_stream_for(&mut stream, &method)?;
+
+        Ok(response)
+    }
+
+    /// Sends a raw request to the given address using TLS.
+    #[cfg(not(feature = "tls"))]
+    pub fn request_tls(
+        &mut self,
+        _: impl ToSocketAddrs,
+        _: Request,
+    ) -> Result<Response, Box<dyn Error>> {
+        Err("TLS feature is not enabled".into())
+    }
+
+    /// Sends a raw request to the given address using TLS.
+    ///
+    /// If the request has a deadline, the request fails once it has passed, and the time remaining
+    ///   is sent in the `X-Request-Deadline` header.
+    #[cfg(feature = "tls")]
+    pub fn request_tls(
+        &mut self,
+        address: impl ToSocketAddrs,
+        request: Request,
+    ) -> Result<Response, Box<dyn Error>> {
+        if self.tls_config.is_none() {
+            let mut roots = RootCertStore::empty();
+            for cert in load_native_certs()? {
+                roots.add(&Certificate(cert.0))?;
+            }
+
+            let conf = ClientConfig::builder()
+                .with_safe_defaults()
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+
+            self.tls_config = Some(Arc::new(conf))
+        }
+
+        let conn = ClientConnection::new(
+            self.tls_config.as_ref().unwrap().clone(),
+            request
+                .headers
+                .get(&HeaderType::Host)
+                .unwrap()
+                .try_into()
+                .unwrap(),
+        )?;
+        let sock = self.connect(address, request.deadline)?;
+        let mut tls = StreamOwned::new(conn, sock);
+
+        let method = request.method.clone();
+        let request_bytes = request_bytes(request);
+        tls.write_all(&request_bytes)?;
+
+        let response = Response::from_stream_for(&mut tls, &method)?;
+
+        Ok(response)
+    }
+
+    /// Resolves the host, given as `host:port`, using the resolver if one was set.
+    fn resolve(&self, host: &str) -> io::Result<Vec<SocketAddr>> {
+        match self.resolver {
+            Some(resolver) => resolver(host),
+            None => Ok(host.to_socket_addrs()?.collect()),
+        }
+    }
+
+    /// Connects to the first of the addresses which accepts the connection, failing if the deadline
+    ///   passes first.
+    fn connect(
+        &self,
+        address: impl ToSocketAddrs,
+        deadline: Option<Instant>,
+    ) -> Result<DeadlineStream, Box<dyn Error>> {
+        let addresses: Vec<SocketAddr> = address.to_socket_addrs()?.collect();
+        let timeout = match deadline {
+            Some(deadline) => remaining(deadline)?.min(self.connect_timeout),
+            None => self.connect_timeout,
+        };
+
+        Ok(DeadlineStream {
+            inner: connect::connect(&addresses, self.local_address, timeout)?,
+            deadline,
+        })
+    }
+
+    /// Parses a URL into a URL struct.
+    pub(crate) fn parse_url(url: impl AsRef<str>) -> Option<ParsedUrl> {
+        let url = url.as_ref();
+
+        if let Some(stripped) = url.strip_prefix("http://") {
+            let protocol = Protocol::Http;
+            let (host, path) = stripped.split_once('/').unwrap_or((stripped, ""));
+
+            let mut headers = Headers::new();
+            headers.add(HeaderType::Host, host);
+
+            let host = with_default_port(host, 80);
+
+            let (path, query) = path.split_once('?').unwrap_or((path, ""));
+
+            Some(ParsedUrl {
+                protocol,
+                host,
+                host_headers: headers,
+                path: format!("/{}", path),
+                query: query.to_string(),
+            })
+        } else if let Some(stripped) = url.strip_prefix("https://") {
+            let protocol = Protocol::Https;
+            let (host, path) = stripped.split_once('/').unwrap_or((stripped, ""));
+
+            let mut headers = Headers::new();
+            headers.add(HeaderType::Host, host);
+
+            let host = with_default_port(host, 443);
+
+            let (path, query) = path.split_once('?').unwrap_or((path, ""));
+
+            Some(ParsedUrl {
+                protocol,
+                host,
+                host_headers: headers,
+                path: format!("/{}", path),
+                query: query.to_string(),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self {
+            local_address: None,
+            resolver: None,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            #[cfg(feature = "tls")]
+            tls_config: None,
+        }
+    }
+}
+
+/// Converts the request into the bytes to send, replacing any `X-Request-Deadline` header with the
+///   time remaining until its deadline, and any trace context headers with its trace context.
+fn request_bytes(mut request: Request) -> Vec<u8> {
+    if let Some(remaining) = request.time_remaining() {
+        request.headers.remove(DEADLINE_HEADER);
+        request
+            .headers
+            .add(DEADLINE_HEADER, remaining.as_millis().to_string());
+    }
+
+    if let Some(trace) = &request.trace {
+        request.headers.remove(TRACEPARENT_HEADER);
+        request.headers.remove(TRACESTATE_HEADER);
+        request
+            .headers
+            .add(TRACEPARENT_HEADER, trace.traceparent().to_string());
+
+        if let Some(state) = &trace.state {
+            request.headers.add(TRACESTATE_HEADER, state);
+        }
+    }
+
+    request.into()
+}
+
+/// Returns the time remaining until the deadline, or a timeout error if it has passed.
+fn remaining(deadline: Instant) -> io::Result<Duration> {
+    match deadline.saturating_duration_since(Instant::now()) {
+        Duration::ZERO => Err(io::Error::new(
+            ErrorKind::TimedOut,
+            "The deadline of the request has passed",
+        )),
+        remaining => Ok(remaining),
+    }
+}
+
+/// A connection whose reads and writes fail once the deadline, if any, has passed.
+struct DeadlineStream {
+    inner: TcpStream,
+    deadline: Option<Instant>,
+}
+
+impl DeadlineStream {
+    /// Limits the next read or write to the time remaining until the deadline, using the given
+    ///   function to set the timeout.
+    fn limit(
+        &self,
+        set_timeout: fn(&TcpStream, Option<Duration>) -> io::Result<()>,
+    ) -> io::Result<()> {
+        match self.deadline {
+            Some(deadline) => set_timeout(&self.inner, Some(remaining(deadline)?)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Read for DeadlineStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.limit(TcpStream::set_read_timeout)?;
+        self.inner.read(buf)
+    }
+}
+
+impl Write for DeadlineStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.limit(TcpStream::set_write_timeout)?;
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Appends the default port to the host, unless it already specifies a port.
+fn with_default_port(host: &str, port: u16) -> String {
+    match host.rsplit_once(':') {
+        Some((_, explicit)) if explicit.parse::<u16>().is_ok() => host.to_string(),
+        _ => format!("{}:{}", host, port),
+    }
+}
+
+/// Represents a request to be sent.
+pub struct ClientRequest<'a> {
+    client: &'a mut Client,
+    protocol: Protocol,
+    addresses: Vec<SocketAddr>,
+    request: Request,
+    follow_redirects: bool,
+    cookies: Vec<Cookie>,
+}
+
+impl<'a> ClientRequest<'a> {
+    /// Adds a header to the request.
+    pub fn with_header(mut self, header: impl HeaderLike, value: impl AsRef<str>) -> Self {
+        self.request.headers.add(header, value);
+        self
+    }
+
+    /// Sets the method of the request, so that requests can be sent with any method.
+    ///
+    /// ## Example
+    /// ```
+    /// let response = client
+    ///     .get("http://localhost/resource")?
+    ///     .with_method(Method::Custom("PURGE".into()))
+    ///     .send()?;
+    /// ```
+    pub fn with_method(mut self, method: Method) -> Self {
+        self.request.method = method;
+        self
+    }
+
+    /// Adds a cookie to the request.
+    pub fn with_cookie(mut self, cookie: Cookie) -> Self {
+        self.cookies.push(cookie);
+        self
+    }
+
+    /// Specifies whether to follow redirects.
+    pub fn with_redirects(mut self, follow_redirects: bool) -> Self {
+        self.follow_redirects = follow_redirects;
+        self
+    }
+
+    /// Sets the time by which the response must have been received, after which the request fails.
+    ///
+    /// The time remaining is used as the timeout for connecting, and limits every read and write
+    ///   including those of any redirects. It is also sent in the `X-Request-Deadline` header, so a
+    ///   Humphrey app which trusts this one with `with_deadline_propagation` shares the deadline.
+    ///   Handlers calling other services can pass on the deadline of their own request, found in
+    ///   `request.deadline`.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.request.deadline = Some(deadline);
+        self
+    }
+
+    /// Continues the given trace in the request by sending the `traceparent` and `tracestate`
+    ///   headers, replacing any which were already set, including with any redirects.
+    ///
+    /// The span of the trace context becomes the parent of the span of the service being called, so
+    ///   handlers should pass on the context of their own request, found in `request.trace`.
+    pub fn with_trace_context(mut self, trace: &TraceContext) -> Self {
+        self.request.trace = Some(trace.clone());
+        self
+    }
+
+    /// Sends the request.
+    pub fn send(mut self) -> Result<Response, Box<dyn Error>> {
+        let mut request = self.request.clone();
+
+        if let Some(header) = Cookie::to_header(&self.cookies) {
+            request.headers.push(header);
+        }
+
+        let response = match self.protocol {
+            Protocol::Http => self.client.request(&self.addresses[..], request),
+            Protocol::Https => self.client.request_tls(&self.addresses[..], request),
+        };
+
+        // Follow a redirect if appropriate.
+        if self.follow_redirects
+            && response
+                .as_ref()
+                .map(|r| {
+                    r.status_code == StatusCode::MovedPermanently
+                        || r.status_code == StatusCode::TemporaryRedirect
+                        || r.status_code == StatusCode::Found
+                })
+                .unwrap_or(false)
+        {
+            response
+                .and_then(|r| {
+                    let location = r
+                        .headers
+                        .get(&HeaderType::Location)
+                        .map_or(Err("No location header"), |s| Ok(s.to_string()))?;
+
+                    Ok((location, r))
+                })
+                .and_then(|(l, r)| {
+                    if l.starts_with('/') {
+                        // Cookies set by a redirect on the same host are sent with the next request
+                        for cookie in r.get_cookies() {
+                            self.cookies.retain(|existing| existing.name != cookie.name);
+                            self.cookies.push(cookie);
+                        }
+
+                        self.request.uri = l;
+                    } else {
+                        let new_url = Client::parse_url(l).ok_or("Invalid URL")?;
+                        let addresses = self.client.resolve(&new_url.host)?;
+                        let request = Request {
+                            method: self.request.method,
+                            overridden_method: None,
+                            uri_raw: new_url.path.clone(),
+                            uri: new_url.path,
+                            headers: new_url.host_headers,
+                            query: new_url.query,
+                            version: "HTTP/1.1".to_string(),
+                            content: self.request.content,
+                            body_state: BodyState::Complete,
+                            trailers: Headers::new(),
+                            address: Address::new(&addresses[..])?,
+                            secure: false,
+                            local_addr: None,
+                            deadline: self.request.deadline,
+                            trace: self.request.trace,
+                            cancellation: CancellationToken::default(),
+                            params: RouteParams::default(),
+                        };
+
+                        self.protocol = new_url.protocol;
+                        self.addresses = addresses;
+                        self.request = request;
+                    }
+
+                    self.send()
+                })
+        } else {
+            response
+        }
+    }
+
+    /// Extracts the raw inner request.
+    pub fn into_inner(self) -> Request {
+        self.request
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct ParsedUrl {
+    pub(crate) protocol: Protocol,
+    pub(crate) host: String,
+    pub(crate) host_headers: Headers,
+    pub(crate) path: String,
+    pub(crate) query: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Protocol {
+    Http,
+    Https,
+}
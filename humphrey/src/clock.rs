@@ -0,0 +1,155 @@
+//! Provides the clock from which the current time is read, so that tests can control it.
+//!
+//! Everything which depends on the time, such as the `Date` header, the timestamps of monitoring
+//!   events, request deadlines, rate limits and the expiry of Humphrey Auth sessions, reads it with
+//!   `now_unix` and `now_monotonic`. These read the system clock unless a different clock has been
+//!   installed on the current thread with `override_clock`, which lets tests simulate the passing of
+//!   time with a `MockClock` instead of sleeping.
+//!
+//! The override only applies to the thread which installed it, so tests running in parallel do not
+//!   affect one another. Work done on other threads, such as by the workers of a running app, still
+//!   reads the system clock, as do timeouts which are enforced by the operating system, such as how
+//!   long an idle connection is kept open.
+//!
+//! ## Example
+//! ```
+//! let clock = MockClock::new();
+//! let _guard = override_clock(clock.clone());
+//!
+//! let session = Session::create_with_lifetime(60);
+//! clock.advance(Duration::from_secs(61));
+//! assert!(session.expired());
+//! ```
+
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+thread_local! {
+    /// The clock installed on the current thread, if any.
+    static OVERRIDE: RefCell<Option<Arc<dyn Clock>>> = const { RefCell::new(None) };
+}
+
+/// A source of the current time.
+pub trait Clock: Send + Sync {
+    /// Returns the time elapsed since the UNIX epoch, which is used for timestamps.
+    fn now_unix(&self) -> Duration;
+
+    /// Returns the current instant, which is used to measure durations and enforce timeouts.
+    fn now_monotonic(&self) -> Instant;
+}
+
+/// The clock of the operating system, which is used unless another clock has been installed.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+/// A clock which only moves when it is told to, for simulating the passing of time in tests.
+///
+/// Clones share the same time, so a test can install one clone and advance another.
+#[derive(Clone, Debug)]
+pub struct MockClock(Arc<Mutex<MockTime>>);
+
+#[derive(Debug)]
+struct MockTime {
+    unix: Duration,
+    monotonic: Instant,
+}
+
+/// Restores the clock which was previously installed on the thread when dropped.
+///
+/// This cannot be sent to another thread, since the override only applies to the thread which
+///   installed it.
+#[must_use = "the clock is uninstalled as soon as the guard is dropped"]
+pub struct ClockGuard {
+    previous: Option<Arc<dyn Clock>>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Clock for SystemClock {
+    fn now_unix(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+
+    fn now_monotonic(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+impl MockClock {
+    /// Creates a mock clock which starts at the current time of the system clock.
+    pub fn new() -> Self {
+        Self::at(SystemClock.now_unix())
+    }
+
+    /// Creates a mock clock which starts at the given time since the UNIX epoch.
+    pub fn at(unix: Duration) -> Self {
+        Self(Arc::new(Mutex::new(MockTime {
+            unix,
+            monotonic: Instant::now(),
+        })))
+    }
+
+    /// Moves the clock forward by the given amount of time.
+    pub fn advance(&self, duration: Duration) {
+        let mut time = self.0.lock().unwrap();
+        time.unix += duration;
+        time.monotonic += duration;
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now_unix(&self) -> Duration {
+        self.0.lock().unwrap().unix
+    }
+
+    fn now_monotonic(&self) -> Instant {
+        self.0.lock().unwrap().monotonic
+    }
+}
+
+impl Drop for ClockGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        OVERRIDE.with(|clock| *clock.borrow_mut() = previous);
+    }
+}
+
+/// Installs the given clock on the current thread until the returned guard is dropped.
+pub fn override_clock(clock: impl Clock + 'static) -> ClockGuard {
+    let previous = OVERRIDE.with(|current| current.borrow_mut().replace(Arc::new(clock)));
+
+    ClockGuard {
+        previous,
+        _not_send: PhantomData,
+    }
+}
+
+/// Returns the time elapsed since the UNIX epoch according to the current thread's clock.
+pub fn now_unix() -> Duration {
+    with_clock(|clock| clock.now_unix())
+}
+
+/// Returns the current instant according to the current thread's clock.
+pub fn now_monotonic() -> Instant {
+    with_clock(|clock| clock.now_monotonic())
+}
+
+/// Calls the function with the clock installed on the current thread, or the system clock if none
+///   has been.
+fn with_clock<T>(f: impl FnOnce(&dyn Clock) -> T) -> T {
+    let installed = OVERRIDE.with(|clock| clock.borrow().clone());
+
+    match installed {
+        Some(clock) => f(clock.as_ref()),
+        None => f(&SystemClock),
+    }
+}
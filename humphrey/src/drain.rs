@@ -0,0 +1,78 @@
+//! Provides connection draining during graceful shutdown.
+//!
+//! When draining is enabled and the shutdown signal is received, the app keeps accepting
+//!   connections for a grace period, but responds to every new request with
+//!   `503 Service Unavailable`, a `Retry-After` header and `Connection: Close`. Requests which were
+//!   already being processed complete normally. Once the grace period has elapsed, the app stops
+//!   accepting connections.
+
+use crate::http::headers::HeaderType;
+use crate::http::response::Response;
+use crate::http::status::StatusCode;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Represents the options which control how connections are drained during graceful shutdown.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DrainOptions {
+    /// How long to keep accepting connections after the shutdown signal, responding to new requests
+    ///   with `503 Service Unavailable`.
+    pub period: Duration,
+    /// How long clients should wait before retrying, sent in the `Retry-After` header.
+    pub retry_after: Duration,
+}
+
+/// A handle which reports whether the app has started draining connections.
+///
+/// This can be obtained from the app with `App::drain_signal` and stored in the app's state, so that
+///   handlers can react to draining, for example by failing a readiness check so that load balancers
+///   stop sending traffic.
+#[derive(Clone, Debug, Default)]
+pub struct DrainSignal(Arc<AtomicBool>);
+
+impl Default for DrainOptions {
+    fn default() -> Self {
+        Self {
+            period: Duration::from_secs(5),
+            retry_after: Duration::from_secs(5),
+        }
+    }
+}
+
+impl DrainSignal {
+    /// Creates a new drain signal which is not draining.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `true` if the app has started draining connections.
+    pub fn is_draining(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Marks the app as draining.
+    pub(crate) fn start(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Returns the response to send instead of routing a new request, if the app is draining.
+pub(crate) fn draining_response(
+    signal: &DrainSignal,
+    options: Option<&DrainOptions>,
+    error_handler: &dyn Fn(StatusCode) -> Response,
+) -> Option<Response> {
+    let options = options.filter(|_| signal.is_draining())?;
+
+    // Round up so that clients never retry before the requested time
+    let retry_after =
+        options.retry_after.as_secs() + u64::from(options.retry_after.subsec_nanos() > 0);
+
+    Some(
+        error_handler(StatusCode::ServiceUnavailable)
+            .with_header(HeaderType::RetryAfter, retry_after.to_string())
+            .with_header(HeaderType::Connection, "Close"),
+    )
+}
@@ -0,0 +1,174 @@
+//! Provides functionality for embedding static assets into the executable.
+//!
+//! Assets are included at compile time with the `embed!` macro, which allows an app to be deployed
+//!   as a single binary without a directory of static files. The resulting `EmbeddedDir` can be
+//!   served with the `serve_embedded` handler.
+//!
+//! ## Example
+//! ```
+//! let assets = humphrey::embed!("../static", ["index.html", "css/style.css", "img/logo.png"]);
+//!
+//! let app: App<()> = App::new().with_path_aware_route("/*", serve_embedded(assets));
+//! ```
+
+use crate::http::conditional::fnv1a;
+use crate::http::headers::HeaderType;
+use crate::http::mime::MimeType;
+use crate::http::url::encode_path;
+use crate::http::{Request, Response, StatusCode};
+use crate::percent::decode_file_path;
+
+use std::collections::BTreeMap;
+
+/// Embeds the given files from a directory into the executable, returning an `EmbeddedDir`.
+///
+/// The directory is relative to the file in which the macro is used, like `include_bytes!`. Since
+///   declarative macros cannot list the contents of a directory, every file must be named, but
+///   files in nested directories can be included with paths such as `css/style.css`.
+#[macro_export]
+macro_rules! embed {
+    ($dir:literal, [$($path:literal),* $(,)?]) => {
+        $crate::embed::EmbeddedDir::new(&[
+            $(($path, include_bytes!(concat!($dir, "/", $path)) as &'static [u8])),*
+        ])
+    };
+}
+
+/// The index files which are served for requests to a directory.
+const INDEX_FILES: [&str; 2] = ["index.html", "index.htm"];
+
+/// The `Cache-Control` header value for files which rarely change, such as favicons, allowing them to
+///   be cached for a week.
+pub(crate) const LONG_CACHE_CONTROL: &str = "public, max-age=604800";
+
+/// Represents a directory of files which have been embedded into the executable.
+pub struct EmbeddedDir {
+    files: BTreeMap<&'static str, EmbeddedFile>,
+}
+
+/// Represents a single file which has been embedded into the executable.
+pub struct EmbeddedFile {
+    /// The contents of the file.
+    pub contents: &'static [u8],
+    /// The MIME type of the file, inferred from its extension.
+    pub mime_type: MimeType,
+    /// The entity tag of the file, derived from a hash of its contents.
+    pub etag: String,
+}
+
+impl EmbeddedDir {
+    /// Creates a new embedded directory from a list of paths and their contents.
+    ///
+    /// This is usually called by the `embed!` macro. The entity tag of each file is calculated here,
+    ///   so it is only done once.
+    pub fn new(files: &[(&'static str, &'static [u8])]) -> Self {
+        let files = files
+            .iter()
+            .map(|(path, contents)| {
+                let path = path.trim_start_matches("./").trim_start_matches('/');
+
+                (path, EmbeddedFile::new(path, contents))
+            })
+            .collect();
+
+        Self { files }
+    }
+
+    /// Gets the file at the given path, relative to the embedded directory.
+    pub fn get(&self, path: &str) -> Option<&EmbeddedFile> {
+        self.files.get(path.trim_start_matches('/'))
+    }
+
+    /// Returns the number of embedded files.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Returns `true` if no files are embedded.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Generates the response to a request for the given path, relative to the embedded directory.
+    ///
+    /// Requests for a directory are served its index file, and requests for a directory without a
+    ///   trailing slash are redirected, in the same way as `serve_dir`.
+    pub fn serve(&self, request: &Request, path: &str) -> Option<Response> {
+        let path = decode_file_path(path)?;
+        let path = path.trim_start_matches('/');
+
+        if path.is_empty() || path.ends_with('/') {
+            return INDEX_FILES
+                .iter()
+                .find_map(|index| self.get(&format!("{}{}", path, index)))
+                .map(|file| file.serve(request));
+        }
+
+        if let Some(file) = self.get(path) {
+            return Some(file.serve(request));
+        }
+
+        let is_directory = INDEX_FILES
+            .iter()
+            .any(|index| self.get(&format!("{}/{}", path, index)).is_some());
+
+        is_directory.then(|| {
+            Response::empty(StatusCode::MovedPermanently).with_header(
+                HeaderType::Location,
+                format!("{}/", encode_path(&request.uri)),
+            )
+        })
+    }
+}
+
+impl EmbeddedFile {
+    /// Creates a new embedded file, inferring its MIME type from the extension of its path.
+    pub fn new(path: &str, contents: &'static [u8]) -> Self {
+        let mime_type = path
+            .rsplit_once('.')
+            .map(|(_, extension)| MimeType::from_extension(extension))
+            .unwrap_or(MimeType::ApplicationOctetStream);
+
+        Self {
+            contents,
+            mime_type,
+            etag: format!("\"{:016x}\"", fnv1a(contents)),
+        }
+    }
+
+    /// Creates a new embedded favicon, detecting whether it is a PNG, GIF or SVG image from its
+    ///   contents and otherwise assuming it is an icon file.
+    pub fn favicon(contents: &'static [u8]) -> Self {
+        let path = if contents.starts_with(b"\x89PNG") {
+            "favicon.png"
+        } else if contents.starts_with(b"GIF8") {
+            "favicon.gif"
+        } else if contents.starts_with(b"<svg") || contents.starts_with(b"<?xml") {
+            "favicon.svg"
+        } else {
+            "favicon.ico"
+        };
+
+        Self::new(path, contents)
+    }
+
+    /// Generates the response to a request for this file.
+    ///
+    /// If the request's `If-None-Match` header matches the file's entity tag, an empty
+    ///   `304 Not Modified` response is returned instead of the contents.
+    pub fn serve(&self, request: &Request) -> Response {
+        let not_modified = request.headers.get("If-None-Match").is_some_and(|tags| {
+            tags.split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == self.etag || tag == "*")
+        });
+
+        let response = match not_modified {
+            true => Response::empty(StatusCode::NotModified),
+            false => Response::new(StatusCode::OK, self.contents)
+                .with_header(HeaderType::ContentType, self.mime_type.to_string()),
+        };
+
+        response.with_header(HeaderType::ETag, self.etag.clone())
+    }
+}
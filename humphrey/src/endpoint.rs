@@ -0,0 +1,160 @@
+//! Provides endpoints for serving one app on several addresses at once.
+//!
+//! An app run with `App::run_multi` accepts connections on every endpoint it is given, sharing its
+//!   routes, state and workers between them, so one process can serve HTTPS on one port and plain
+//!   HTTP on another without building the app twice. Handlers can tell the endpoints apart with
+//!   `request.secure` and `request.local_addr`.
+
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+
+#[cfg(feature = "tls")]
+use crate::http::headers::HeaderType;
+#[cfg(feature = "tls")]
+use crate::http::method::Method;
+#[cfg(feature = "tls")]
+use crate::http::request::RequestError;
+#[cfg(feature = "tls")]
+use crate::http::url::Origin;
+#[cfg(feature = "tls")]
+use crate::http::{Request, Response, StatusCode};
+#[cfg(feature = "tls")]
+use crate::monitor::event::EventType;
+#[cfg(feature = "tls")]
+use crate::monitor::MonitorConfig;
+#[cfg(feature = "tls")]
+use std::time::Duration;
+
+/// How long a redirect endpoint waits for the request on each connection if no connection timeout
+///   is set, so that slow clients cannot hold a worker indefinitely.
+#[cfg(feature = "tls")]
+pub(crate) const REDIRECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Represents an address on which an app accepts connections, and how those connections are served.
+#[derive(Debug)]
+pub struct Endpoint {
+    pub(crate) addrs: io::Result<Vec<SocketAddr>>,
+    pub(crate) kind: EndpointKind,
+}
+
+/// Represents how the connections accepted on an endpoint are served.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EndpointKind {
+    /// Requests are handled by the app over plain HTTP.
+    Plain,
+    /// Requests are handled by the app over TLS, using the certificate set with `App::with_cert`.
+    #[cfg(feature = "tls")]
+    Tls,
+    /// Every request is redirected to the same URL over HTTPS, and is never handled by the app.
+    #[cfg(feature = "tls")]
+    Redirect,
+}
+
+impl EndpointKind {
+    /// Returns whether connections accepted on endpoints of this kind are encrypted with TLS, in which
+    ///   case they cannot be answered with plain HTTP before the handshake.
+    pub(crate) fn is_tls(&self) -> bool {
+        match self {
+            EndpointKind::Plain => false,
+            #[cfg(feature = "tls")]
+            EndpointKind::Tls => true,
+            #[cfg(feature = "tls")]
+            EndpointKind::Redirect => false,
+        }
+    }
+}
+
+impl Endpoint {
+    /// Creates an endpoint which serves the app over plain HTTP on the given address.
+    pub fn plain(addr: impl ToSocketAddrs) -> Self {
+        Self::new(addr, EndpointKind::Plain)
+    }
+
+    /// Creates an endpoint which serves the app over TLS on the given address.
+    ///
+    /// The app's certificate **must** be set with `App::with_cert` to run the app with a TLS endpoint.
+    #[cfg(feature = "tls")]
+    pub fn tls(addr: impl ToSocketAddrs) -> Self {
+        Self::new(addr, EndpointKind::Tls)
+    }
+
+    /// Creates an endpoint which redirects every request on the given address to HTTPS.
+    ///
+    /// This is the equivalent of `App::with_forced_https` for apps run with `App::run_multi`, and only
+    ///   affects the endpoints created with this function, so other plaintext endpoints, such as one
+    ///   used for health checks, are still handled by the app.
+    #[cfg(feature = "tls")]
+    pub fn redirect(addr: impl ToSocketAddrs) -> Self {
+        Self::new(addr, EndpointKind::Redirect)
+    }
+
+    /// Returns how the connections accepted on this endpoint are served.
+    pub fn kind(&self) -> EndpointKind {
+        self.kind
+    }
+
+    /// Creates an endpoint of the given kind, resolving its address.
+    ///
+    /// Errors resolving the address are returned when the app is run.
+    fn new(addr: impl ToSocketAddrs, kind: EndpointKind) -> Self {
+        Self {
+            addrs: addr.to_socket_addrs().map(|addrs| addrs.collect()),
+            kind,
+        }
+    }
+}
+
+/// Generates the response which redirects the request to the same URL over HTTPS on the given port.
+///
+/// Requests without a `Host` header cannot be redirected, so they are asked to use HTTPS instead.
+#[cfg(feature = "tls")]
+pub(crate) fn redirect_response(request: &Request, https_port: u16) -> Response {
+    let location = Origin::from_request(request, &[]).map(|origin| {
+        origin
+            .into_secure_on(https_port)
+            .url(&request.uri, Some(&request.query))
+    });
+
+    let mut response = match location {
+        Some(location) => Response::empty(StatusCode::MovedPermanently)
+            .with_header(HeaderType::Location, location),
+        None => Response::new(StatusCode::OK, "<h1>Please access over HTTPS</h1>"),
+    }
+    .with_header(HeaderType::Connection, "Close");
+
+    response.finalise_framing(&request.method);
+    response
+}
+
+/// Reports the error encountered reading a request on a redirect endpoint, returning the response
+///   to send if the client can still be answered.
+#[cfg(feature = "tls")]
+pub(crate) fn redirect_error(
+    error: RequestError,
+    peer: SocketAddr,
+    monitor: &MonitorConfig,
+) -> Option<Response> {
+    let status_code = match &error {
+        RequestError::Request => Some(StatusCode::BadRequest),
+        RequestError::TooLarge(part) => Some(part.status_code()),
+        RequestError::Stream => None,
+        RequestError::Timeout => {
+            monitor.send_with(EventType::RequestTimeout, |event| event.with_peer(peer));
+            return None;
+        }
+        RequestError::Disconnected | RequestError::Rejected => return None,
+    };
+
+    monitor.send_with(EventType::RequestServedError, |event| {
+        event
+            .with_peer(peer)
+            .with_info(format!("Could not redirect to HTTPS: {}", error))
+    });
+
+    status_code.map(|status_code| {
+        let mut response =
+            Response::empty(status_code).with_header(HeaderType::Connection, "Close");
+        response.finalise_framing(&Method::Get);
+        response
+    })
+}
@@ -0,0 +1,151 @@
+//! Provides a single error type which errors from across the Humphrey ecosystem can be converted into.
+//!
+//! This allows handlers which touch several crates, for example parsing JSON, authenticating a user
+//!   and reading a file, to use the `?` operator throughout and convert the final error into a
+//!   response. Humphrey Auth and Humphrey WebSocket provide conversions into this type when their
+//!   `error` feature is enabled, and conversions from Humphrey JSON's errors are provided when the
+//!   `json` feature of this crate is enabled.
+//!
+//! ## Example
+//! ```
+//! fn handler(request: Request) -> Result<Response, Error> {
+//!     let body = std::fs::read_to_string("index.html")?;
+//!
+//!     Ok(Response::new(StatusCode::OK, body))
+//! }
+//!
+//! let response = handler(request).unwrap_or_else(Response::from);
+//! ```
+
+use crate::http::request::RequestError;
+use crate::http::response::ResponseError;
+use crate::http::status::StatusCodeError;
+use crate::http::{Response, StatusCode};
+
+use std::error::Error as StdError;
+use std::fmt::Display;
+
+/// A boxed error from another crate, which can be sent between threads.
+pub type BoxedError = Box<dyn StdError + Send + Sync>;
+
+/// Represents an error from anywhere in the Humphrey ecosystem.
+#[derive(Debug)]
+pub enum Error {
+    /// An I/O error, for example when reading a file or a stream.
+    Io(std::io::Error),
+    /// An error parsing or receiving a request.
+    Request(RequestError),
+    /// An error parsing or receiving a response.
+    Response(ResponseError),
+    /// An invalid status code was encountered.
+    Status(StatusCodeError),
+    /// An error parsing or deserializing JSON, converted from Humphrey JSON.
+    Json(BoxedError),
+    /// An authentication error, converted from Humphrey Auth.
+    Auth(BoxedError),
+    /// A WebSocket error, converted from Humphrey WebSocket.
+    Websocket(BoxedError),
+    /// Any other error.
+    Other(BoxedError),
+}
+
+impl Error {
+    /// Returns the status code which best describes the error when it is returned to a client.
+    ///
+    /// Errors caused by the client's input are mapped to `4xx` codes, and everything else is treated
+    ///   as an internal server error.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Error::Request(RequestError::Timeout) => StatusCode::RequestTimeout,
+            Error::Request(RequestError::TooLarge(part)) => part.status_code(),
+            Error::Request(_) | Error::Json(_) => StatusCode::BadRequest,
+            Error::Auth(_) => StatusCode::Unauthorized,
+            _ => StatusCode::InternalError,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Request(e) => write!(f, "{}", e),
+            Error::Response(e) => write!(f, "{}", e),
+            Error::Status(e) => write!(f, "{}", e),
+            Error::Json(e) => write!(f, "{}", e),
+            Error::Auth(e) => write!(f, "Authentication error: {}", e),
+            Error::Websocket(e) => write!(f, "{}", e),
+            Error::Other(e) => write!(f, "Error: {}", e),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Request(e) => Some(e),
+            Error::Response(e) => Some(e),
+            Error::Status(e) => Some(e),
+            Error::Json(e) | Error::Auth(e) | Error::Websocket(e) | Error::Other(e) => {
+                Some(e.as_ref())
+            }
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<RequestError> for Error {
+    fn from(error: RequestError) -> Self {
+        Error::Request(error)
+    }
+}
+
+impl From<ResponseError> for Error {
+    fn from(error: ResponseError) -> Self {
+        Error::Response(error)
+    }
+}
+
+impl From<StatusCodeError> for Error {
+    fn from(error: StatusCodeError) -> Self {
+        Error::Status(error)
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<humphrey_json::error::ParseError> for Error {
+    fn from(error: humphrey_json::error::ParseError) -> Self {
+        Error::Json(Box::new(error))
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<humphrey_json::error::TracebackError> for Error {
+    fn from(error: humphrey_json::error::TracebackError) -> Self {
+        Error::Json(Box::new(error))
+    }
+}
+
+impl From<BoxedError> for Error {
+    fn from(error: BoxedError) -> Self {
+        Error::Other(error)
+    }
+}
+
+impl From<Error> for Response {
+    /// Converts the error into a response with the appropriate status code.
+    ///
+    /// The body only contains the status code's reason phrase, since the error itself may contain
+    ///   details which should not be revealed to the client. The error should be logged separately.
+    fn from(error: Error) -> Self {
+        let status_code = error.status_code();
+
+        Response::new(status_code, <&str>::from(status_code))
+    }
+}
@@ -0,0 +1,200 @@
+//! Provides detection of changes to files which are served by an app, and files which are held open
+//!   so that they can be served without opening them for every request.
+//!
+//! Files are usually replaced during a deploy by writing the new file elsewhere and renaming it over
+//!   the old one, so a `FileVersion` changes when the file at a path is a different file, as well as
+//!   when it is modified in place.
+
+use std::fs::{File, Metadata};
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+/// Identifies the contents of a file at a point in time, so that changes to it can be detected
+///   without reading it.
+///
+/// On Unix, the version includes the device and inode of the file, so a file which replaced another
+///   always has a different version. On Windows, which does not expose a stable file identifier, the
+///   creation time is used instead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileVersion {
+    len: u64,
+    modified: Option<SystemTime>,
+    #[cfg(unix)]
+    inode: (u64, u64),
+    #[cfg(windows)]
+    created: Option<SystemTime>,
+}
+
+/// A file which is held open so that it can be served without opening it for every request, which
+///   is useful for very frequently requested files.
+///
+/// The file is served as it was when it was opened, even if it is replaced or removed, until
+///   `reopen` is called, for example by a deploy hook. Clones share the same open file, so reopening
+///   one reopens all of them.
+#[derive(Clone, Debug)]
+pub struct HeldFile {
+    path: Arc<Path>,
+    open: Arc<RwLock<Arc<OpenFile>>>,
+}
+
+/// A file which has been opened, along with its version when it was opened.
+#[derive(Debug)]
+struct OpenFile {
+    file: File,
+    version: FileVersion,
+}
+
+impl FileVersion {
+    /// Returns the current version of the file at the given path.
+    pub fn of(path: impl AsRef<Path>) -> io::Result<Self> {
+        std::fs::metadata(path).map(|metadata| Self::from_metadata(&metadata))
+    }
+
+    /// Returns the version of the file with the given metadata.
+    pub fn from_metadata(metadata: &Metadata) -> Self {
+        #[cfg(unix)]
+        use std::os::unix::fs::MetadataExt;
+
+        Self {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            #[cfg(unix)]
+            inode: (metadata.dev(), metadata.ino()),
+            #[cfg(windows)]
+            created: metadata.created().ok(),
+        }
+    }
+}
+
+impl HeldFile {
+    /// Opens the file at the given path and holds it open.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path: Arc<Path> = path.as_ref().into();
+        let open = OpenFile::open(&path)?;
+
+        Ok(Self {
+            path,
+            open: Arc::new(RwLock::new(Arc::new(open))),
+        })
+    }
+
+    /// Opens the file which is now at the path, so that it is served instead of the file which was
+    ///   held before.
+    ///
+    /// If the file cannot be opened, the error is returned and the previous file is still served.
+    pub fn reopen(&self) -> io::Result<()> {
+        let open = OpenFile::open(&self.path)?;
+        *self.open.write().unwrap() = Arc::new(open);
+
+        Ok(())
+    }
+
+    /// Returns the path of the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the version of the file when it was opened.
+    pub fn version(&self) -> FileVersion {
+        self.open.read().unwrap().version
+    }
+
+    /// Returns whether the file at the path has changed or been removed since the held file was
+    ///   opened, in which case it should be reopened.
+    pub fn is_stale(&self) -> bool {
+        FileVersion::of(&self.path).map_or(true, |version| version != self.version())
+    }
+
+    /// Reads the whole of the held file.
+    ///
+    /// The file is read from the start every time without moving a shared cursor, so it can be read
+    ///   by any number of threads at once.
+    pub fn read(&self) -> io::Result<Vec<u8>> {
+        // The lock is released before reading, so reopening does not wait for slow readers
+        let open = self.open.read().unwrap().clone();
+        let mut contents = Vec::with_capacity(open.version.len as usize);
+        let mut buf = [0; 8192];
+
+        loop {
+            match read_at(&open.file, &mut buf, contents.len() as u64) {
+                Ok(0) => return Ok(contents),
+                Ok(n) => contents.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl OpenFile {
+    /// Opens the file at the given path, recording its version.
+    fn open(path: &Path) -> io::Result<Self> {
+        let file = open_shared(path)?;
+        let version = FileVersion::from_metadata(&file.metadata()?);
+
+        Ok(Self { file, version })
+    }
+}
+
+/// Returns whether the error from opening a file means that there is no longer a file at its path,
+///   rather than that the file could not be read.
+pub(crate) fn is_missing(error: &io::Error) -> bool {
+    if matches!(error.kind(), ErrorKind::NotFound | ErrorKind::NotADirectory) {
+        return true;
+    }
+
+    // Windows keeps a removed file in its directory until every handle to it is closed, and refuses
+    //   to open it until then with `ERROR_DELETE_PENDING`
+    #[cfg(windows)]
+    if error.raw_os_error() == Some(303) {
+        return true;
+    }
+
+    false
+}
+
+/// Opens the file at the given path for reading.
+#[cfg(not(windows))]
+fn open_shared(path: &Path) -> io::Result<File> {
+    File::open(path)
+}
+
+/// Opens the file at the given path for reading, allowing other processes to replace or remove it
+///   while it is open, as they can on Unix.
+#[cfg(windows)]
+fn open_shared(path: &Path) -> io::Result<File> {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    const FILE_SHARE_READ_WRITE_DELETE: u32 = 0x7;
+
+    std::fs::OpenOptions::new()
+        .read(true)
+        .share_mode(FILE_SHARE_READ_WRITE_DELETE)
+        .open(path)
+}
+
+/// Reads from the file at the given offset, without moving its cursor.
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+/// Reads from the file at the given offset.
+///
+/// This moves the cursor of the file, but since every read gives its own offset, concurrent reads
+///   do not affect each other.
+#[cfg(windows)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
+
+/// Reads from the file at the given offset, which is not supported on this platform.
+#[cfg(not(any(unix, windows)))]
+fn read_at(_: &File, _: &mut [u8], _: u64) -> io::Result<usize> {
+    Err(io::Error::new(
+        ErrorKind::Unsupported,
+        "held files can only be read on Unix and Windows",
+    ))
+}
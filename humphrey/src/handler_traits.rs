@@ -1,11 +1,22 @@
 //! Defines traits for handler functions.
+//!
+//! Handlers are shared between every worker thread in the app, so all of these traits require
+//!   `Send + Sync`. A closure which captures an `Rc`, a `RefCell` or a borrowed reference will
+//!   therefore not be accepted as a handler. Shared data should instead be stored in the app's
+//!   state, or captured as an `Arc` (with a `Mutex` or `RwLock` if it needs to be mutated).
 
+use crate::http::body::RequestBody;
 use crate::http::{Request, Response};
 use crate::stream::Stream;
 
 use std::sync::Arc;
 
 /// Represents a function able to handle a WebSocket handshake and consequent data frames.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a valid WebSocket handler",
+    label = "expected a thread-safe `Fn(Request, Stream, Arc<State>)`",
+    note = "handlers must be `Send + Sync`, so capture shared data with `Arc` instead of `Rc` or references"
+)]
 pub trait WebsocketHandler<State>: Send + Sync {
     #[allow(missing_docs)]
     fn serve(&self, request: Request, stream: Stream, state: Arc<State>);
@@ -19,6 +30,26 @@ where
     }
 }
 
+/// Represents a function able to handle a WebSocket handshake and consequent data frames, which is
+///   also passed the route pattern it was registered with.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a valid path-aware WebSocket handler",
+    label = "expected a thread-safe `Fn(Request, Stream, Arc<State>, &'static str)`",
+    note = "handlers must be `Send + Sync`, so capture shared data with `Arc` instead of `Rc` or references"
+)]
+pub trait PathAwareWebsocketHandler<State>: Send + Sync {
+    #[allow(missing_docs)]
+    fn serve(&self, request: Request, stream: Stream, state: Arc<State>, route: &'static str);
+}
+impl<F, State> PathAwareWebsocketHandler<State> for F
+where
+    F: Fn(Request, Stream, Arc<State>, &'static str) + Send + Sync,
+{
+    fn serve(&self, request: Request, stream: Stream, state: Arc<State>, route: &'static str) {
+        self(request, stream, state, route)
+    }
+}
+
 /// Represents a function able to handle a request.
 /// It is passed the request as well as the app's state, and must return a response.
 ///
@@ -29,6 +60,11 @@ where
 ///     Response::new(StatusCode::OK, b"Success")
 /// }
 /// ```
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a valid request handler",
+    label = "expected a thread-safe `Fn(Request, Arc<State>) -> Response`",
+    note = "handlers must be `Send + Sync`, so capture shared data with `Arc` instead of `Rc` or references"
+)]
 pub trait RequestHandler<State>: Send + Sync {
     #[allow(missing_docs)]
     fn serve(&self, request: Request, state: Arc<State>) -> Response;
@@ -42,6 +78,41 @@ where
     }
 }
 
+/// Represents a function able to handle a request whose body is streamed from the connection
+///   rather than buffered.
+/// It is passed the request, whose `content` is always `None`, a reader for its body, and the app's
+///   state, and must return a response.
+///
+/// ## Example
+/// A handler which saves an upload to disk without holding it in memory would be as follows:
+/// ```
+/// fn handler(_: Request, body: &mut RequestBody, _: Arc<()>) -> Response {
+///     let mut file = File::create("upload.bin").unwrap();
+///
+///     match std::io::copy(body, &mut file) {
+///         Ok(_) => Response::empty(StatusCode::Created),
+///         Err(_) => Response::empty(StatusCode::BadRequest),
+///     }
+/// }
+/// ```
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a valid streaming request handler",
+    label = "expected a thread-safe `Fn(Request, &mut RequestBody, Arc<State>) -> Response`",
+    note = "handlers must be `Send + Sync`, so capture shared data with `Arc` instead of `Rc` or references"
+)]
+pub trait StreamingRequestHandler<State>: Send + Sync {
+    #[allow(missing_docs)]
+    fn serve(&self, request: Request, body: &mut RequestBody, state: Arc<State>) -> Response;
+}
+impl<F, State> StreamingRequestHandler<State> for F
+where
+    F: Fn(Request, &mut RequestBody, Arc<State>) -> Response + Send + Sync,
+{
+    fn serve(&self, request: Request, body: &mut RequestBody, state: Arc<State>) -> Response {
+        self(request, body, state)
+    }
+}
+
 /// Represents a function able to handle a request.
 /// It is passed only the request, and must return a response.
 /// If you want access to the app's state, consider using the `RequestHandler` trait instead.
@@ -53,6 +124,11 @@ where
 ///     Response::new(StatusCode::OK, b"Success")
 /// }
 /// ```
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a valid stateless request handler",
+    label = "expected a thread-safe `Fn(Request) -> Response`",
+    note = "handlers must be `Send + Sync`, so capture shared data with `Arc` instead of `Rc` or references"
+)]
 pub trait StatelessRequestHandler<State>: Send + Sync {
     #[allow(missing_docs)]
     fn serve(&self, request: Request) -> Response;
@@ -76,6 +152,11 @@ where
 ///     Response::new(StatusCode::OK, format!("Success matching route {}", route))
 /// }
 /// ```
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a valid path-aware request handler",
+    label = "expected a thread-safe `Fn(Request, Arc<State>, &'static str) -> Response`",
+    note = "handlers must be `Send + Sync`, so capture shared data with `Arc` instead of `Rc` or references"
+)]
 pub trait PathAwareRequestHandler<State>: Send + Sync {
     #[allow(missing_docs)]
     fn serve(&self, request: Request, state: Arc<State>, route: &'static str) -> Response;
@@ -1,14 +1,19 @@
 //! Provides a number of useful handlers for Humphrey apps.
 
 use crate::app::error_handler;
+use crate::embed::{EmbeddedDir, EmbeddedFile, LONG_CACHE_CONTROL};
+use crate::file::{is_missing, FileVersion, HeldFile};
 use crate::http::headers::HeaderType;
-use crate::http::mime::MimeType;
+use crate::http::url::encode_path;
 use crate::http::{Request, Response, StatusCode};
+use crate::monitor::event::EventType;
+use crate::monitor::MonitorConfig;
+use crate::percent::decode_file_path;
 use crate::route::{try_find_path, LocatedPath};
 
-use std::fs::File;
-use std::io::Read;
-use std::path::PathBuf;
+pub use crate::route::DirOptions;
+
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 const INDEX_FILES: [&str; 2] = ["index.html", "index.htm"];
@@ -17,25 +22,50 @@ const INDEX_FILES: [&str; 2] = ["index.html", "index.htm"];
 pub fn serve_file<T>(file_path: &'static str) -> impl Fn(Request, Arc<T>) -> Response {
     let path_buf = PathBuf::from(file_path);
 
-    move |_, _| {
-        if let Ok(mut file) = File::open(&path_buf) {
-            let mut buf = Vec::new();
-            if file.read_to_end(&mut buf).is_ok() {
-                return if let Some(extension) = path_buf.extension() {
-                    Response::new(StatusCode::OK, buf).with_header(
-                        HeaderType::ContentType,
-                        MimeType::from_extension(extension.to_str().unwrap()).to_string(),
-                    )
-                } else {
-                    Response::new(StatusCode::OK, buf)
-                };
-            }
-        }
+    move |request, _| serve_file_with_hooks(&request, &path_buf, DirOptions::default(), &())
+}
 
-        error_handler(StatusCode::NotFound)
+/// Serves the given held file, without opening it for every request.
+///
+/// This is intended for very frequently requested files. The file is served as it was when it was
+///   opened until `HeldFile::reopen` is called, for example from a deploy hook, so keep a clone of the
+///   held file to reopen it.
+///
+/// ## Example
+/// ```
+/// let index = HeldFile::open("./static/index.html")?;
+/// let app: App = App::new().with_route("/", serve_held_file(index.clone()));
+/// ```
+pub fn serve_held_file<T>(file: HeldFile) -> impl Fn(Request, Arc<T>) -> Response {
+    move |_, _| match file.read() {
+        Ok(contents) => DirOptions::default().file_response(file.path(), contents),
+        Err(_) => error_handler(StatusCode::InternalError),
     }
 }
 
+/// Serves the file at the given path in response to the request, calling the given hooks along the
+///   way.
+///
+/// This is the function behind `serve_file`, for handlers which need to customise it. The file is
+///   opened for every request, so changes to it are served straight away. If the file cannot be
+///   found, including if it has been removed since the app started, the `missing` hook is called and
+///   the hooks generate a 404 error. If it cannot be read, they generate a 500 error.
+pub fn serve_file_with_hooks(
+    request: &Request,
+    path: &Path,
+    options: DirOptions,
+    hooks: &impl FileHooks,
+) -> Response {
+    if let Some(response) = hooks.lookup(request) {
+        return response;
+    }
+
+    let response = open_file_with_hooks(request, path, options, hooks);
+    hooks.served(request, &response);
+
+    response
+}
+
 /// Treat the request URI as a file path relative to the given directory and serve files from there.
 ///
 /// ## Examples
@@ -47,26 +77,18 @@ pub fn serve_file<T>(file_path: &'static str) -> impl Fn(Request, Arc<T>) -> Res
 pub fn serve_as_file_path<T>(directory_path: &'static str) -> impl Fn(Request, Arc<T>) -> Response {
     move |request: Request, _| {
         let directory_path = directory_path.strip_suffix('/').unwrap_or(directory_path);
-        let file_path = request.uri.strip_prefix('/').unwrap_or(&request.uri);
+        let file_path = match decode_file_path(&request.uri) {
+            Some(file_path) => file_path,
+            None => return error_handler(StatusCode::NotFound),
+        };
+        let file_path = file_path.strip_prefix('/').unwrap_or(&file_path);
         let path = format!("{}/{}", directory_path, file_path);
 
         let path_buf = PathBuf::from(path);
 
-        if let Ok(mut file) = File::open(&path_buf) {
-            let mut buf = Vec::new();
-            if file.read_to_end(&mut buf).is_ok() {
-                return if let Some(extension) = path_buf.extension() {
-                    Response::new(StatusCode::OK, buf).with_header(
-                        HeaderType::ContentType,
-                        MimeType::from_extension(extension.to_str().unwrap()).to_string(),
-                    )
-                } else {
-                    Response::new(StatusCode::OK, buf)
-                };
-            }
-        }
-
-        error_handler(StatusCode::NotFound)
+        DirOptions::default()
+            .open_file(&path_buf)
+            .unwrap_or_else(|_| error_handler(StatusCode::NotFound))
     }
 }
 
@@ -76,6 +98,151 @@ pub fn serve_as_file_path<T>(directory_path: &'static str) -> impl Fn(Request, A
 ///   - requests to `/directory` will return either the file `directory`, 301 redirect to `/directory/` if it is a directory, or return 404
 ///   - requests to `/directory/` will return either the file `/directory/index.html` or `/directory/index.htm`, or return 404
 pub fn serve_dir<T>(directory_path: &'static str) -> impl Fn(Request, Arc<T>, &str) -> Response {
+    serve_dir_with_options(directory_path, DirOptions::default())
+}
+
+/// Serves a directory of files in the same way as `serve_dir`, but with the given options.
+///
+/// ## Example
+/// ```
+/// serve_dir_with_options("./static", DirOptions::new().with_strip_bom(true))
+/// ```
+pub fn serve_dir_with_options<T>(
+    directory_path: &'static str,
+    options: DirOptions,
+) -> impl Fn(Request, Arc<T>, &str) -> Response {
+    move |request: Request, _, route| {
+        serve_dir_with_hooks(&request, directory_path, route, options, &())
+    }
+}
+
+/// Serves the file requested from the given directory in the same way as `serve_dir`, calling the
+///   given hooks along the way.
+///
+/// This is the function behind `serve_dir`, for handlers which need to customise it. The route is
+///   the pattern which matched the request, and everything before its wildcard is removed from the
+///   URI before the file is located.
+pub fn serve_dir_with_hooks(
+    request: &Request,
+    directory_path: &str,
+    route: &str,
+    options: DirOptions,
+    hooks: &impl FileHooks,
+) -> Response {
+    if let Some(response) = hooks.lookup(request) {
+        return response;
+    }
+
+    let route_without_wildcard = route.strip_suffix('*').unwrap_or(route);
+    let uri_without_route = request
+        .uri
+        .strip_prefix(route_without_wildcard)
+        .unwrap_or(&request.uri);
+
+    let response = match try_find_path(directory_path, uri_without_route, &INDEX_FILES) {
+        Some(LocatedPath::Directory) => Response::empty(StatusCode::MovedPermanently).with_header(
+            HeaderType::Location,
+            format!("{}/", encode_path(&request.uri)),
+        ),
+        Some(LocatedPath::File(path)) => open_file_with_hooks(request, &path, options, hooks),
+        None => hooks.error(request, StatusCode::NotFound),
+    };
+
+    hooks.served(request, &response);
+
+    response
+}
+
+/// Customises how files are served by `serve_file_with_hooks` and `serve_dir_with_hooks`, for example
+///   to cache files in memory or to log requests.
+///
+/// Every method has a default which does nothing, or generates errors with the default error
+///   handler, so only the methods which are needed have to be implemented. The hooks do nothing when
+///   implemented by `()`.
+pub trait FileHooks {
+    /// Called before the filesystem is used. If a response is returned, it is sent instead of the
+    ///   file, and no other hooks are called.
+    fn lookup(&self, _request: &Request) -> Option<Response> {
+        None
+    }
+
+    /// Called when a file has been read into memory, with the version of the file which was read and
+    ///   the response which will be sent for it. This is not called for files which are streamed
+    ///   from disk.
+    ///
+    /// Hooks which cache the response can compare the version with `FileVersion::of` the path to
+    ///   find out whether the file has since changed.
+    fn loaded(
+        &self,
+        _request: &Request,
+        _path: &Path,
+        _version: FileVersion,
+        _response: &Response,
+    ) {
+    }
+
+    /// Called when there is no file at the path which was going to be served, just before the 404
+    ///   error is generated. This is only called for `serve_dir_with_hooks` if the file was found
+    ///   but removed before it could be opened.
+    fn missing(&self, _request: &Request, _path: &Path) {}
+
+    /// Called with every response generated from the filesystem, including redirects and errors,
+    ///   just before it is returned.
+    fn served(&self, _request: &Request, _response: &Response) {}
+
+    /// Generates the response for an error with the given status code.
+    fn error(&self, _request: &Request, status_code: StatusCode) -> Response {
+        error_handler(status_code)
+    }
+}
+
+impl FileHooks for () {}
+
+/// Sends the `FileMissing` event when a file which was going to be served is not found.
+impl FileHooks for MonitorConfig {
+    fn missing(&self, request: &Request, path: &Path) {
+        self.send_with(EventType::FileMissing, |event| {
+            event
+                .with_peer((request.address.origin_addr, request.address.port))
+                .with_info(format!("{} ({})", request.uri, path.display()))
+        });
+    }
+}
+
+/// Opens the file at the given path, calling the `loaded` hook if it is read into memory and
+///   generating an error with the hooks if it cannot be opened.
+fn open_file_with_hooks(
+    request: &Request,
+    path: &Path,
+    options: DirOptions,
+    hooks: &impl FileHooks,
+) -> Response {
+    match options.open_file_versioned(path) {
+        Ok((response, version)) => {
+            if response.streamed_body.is_none() {
+                hooks.loaded(request, path, version, &response);
+            }
+
+            response
+        }
+        Err(e) if is_missing(&e) => {
+            hooks.missing(request, path);
+            hooks.error(request, StatusCode::NotFound)
+        }
+        Err(_) => hooks.error(request, StatusCode::InternalError),
+    }
+}
+
+/// Redirects requests to the given location with status code 301.
+pub fn redirect<T>(location: &'static str) -> impl Fn(Request, Arc<T>) -> Response {
+    move |_, _| Response::permanent_redirect(location)
+}
+
+/// Serves files which have been embedded into the executable with the `embed!` macro.
+///
+/// The request URI is resolved relative to the route, so this should be used with a wildcard route
+///   such as `/static/*`. Index files and directory redirects behave in the same way as `serve_dir`.
+pub fn serve_embedded<T>(dir: EmbeddedDir) -> impl Fn(Request, Arc<T>, &str) -> Response {
     move |request: Request, _, route| {
         let route_without_wildcard = route.strip_suffix('*').unwrap_or(route);
         let uri_without_route = request
@@ -83,38 +250,30 @@ pub fn serve_dir<T>(directory_path: &'static str) -> impl Fn(Request, Arc<T>, &s
             .strip_prefix(route_without_wildcard)
             .unwrap_or(&request.uri);
 
-        let located = try_find_path(directory_path, uri_without_route, &INDEX_FILES);
-
-        if let Some(located) = located {
-            match located {
-                LocatedPath::Directory => Response::empty(StatusCode::MovedPermanently)
-                    .with_header(HeaderType::Location, format!("{}/", &request.uri)),
-                LocatedPath::File(path) => {
-                    if let Ok(mut file) = File::open(&path) {
-                        let mut buf = Vec::new();
-                        if file.read_to_end(&mut buf).is_ok() {
-                            return if let Some(extension) = path.extension() {
-                                Response::new(StatusCode::OK, buf).with_header(
-                                    HeaderType::ContentType,
-                                    MimeType::from_extension(extension.to_str().unwrap())
-                                        .to_string(),
-                                )
-                            } else {
-                                Response::new(StatusCode::OK, buf)
-                            };
-                        }
-                    }
-
-                    error_handler(StatusCode::InternalError)
-                }
-            }
-        } else {
-            error_handler(StatusCode::NotFound)
-        }
+        dir.serve(&request, uri_without_route)
+            .unwrap_or_else(|| error_handler(StatusCode::NotFound))
     }
 }
 
-/// Redirects requests to the given location with status code 301.
-pub fn redirect<T>(location: &'static str) -> impl Fn(Request, Arc<T>) -> Response {
-    move |_, _| Response::redirect(location)
+/// Serves the given bytes as a favicon, allowing clients to cache it for a week.
+///
+/// The image type is detected from the contents, so PNG, GIF and SVG favicons are supported as well
+///   as icon files. This is intended to be used with `include_bytes!`.
+pub fn favicon<T>(bytes: &'static [u8]) -> impl Fn(Request, Arc<T>) -> Response {
+    let file = EmbeddedFile::favicon(bytes);
+
+    move |request, _| {
+        file.serve(&request)
+            .with_header(HeaderType::CacheControl, LONG_CACHE_CONTROL)
+    }
+}
+
+/// Serves the given rules as `robots.txt`, allowing clients to cache them for a week.
+pub fn robots_txt<T>(rules: &'static str) -> impl Fn(Request, Arc<T>) -> Response {
+    let file = EmbeddedFile::new("robots.txt", rules.as_bytes());
+
+    move |request, _| {
+        file.serve(&request)
+            .with_header(HeaderType::CacheControl, LONG_CACHE_CONTROL)
+    }
 }
@@ -0,0 +1,507 @@
+//! Provides functionality for streaming request bodies to handlers.
+//!
+//! Requests to routes added with `with_streaming_route` have their heads parsed as usual, but their
+//!   bodies are left on the connection and read only as the handler asks for them, so arbitrarily
+//!   large uploads can be copied to disk without being held in memory.
+
+use crate::http::headers::{HeaderType, Headers};
+use crate::http::request::{
+    chunked_codings, content_length, parse_chunk_size, RequestLimits, CHUNK_SIZE_LINE_LIMIT,
+    REJECTED_BODY_DRAIN_LIMIT,
+};
+
+use std::io::{Cursor, Error, ErrorKind};
+
+#[cfg(not(feature = "tokio"))]
+use std::io::{Chain, Read};
+
+#[cfg(feature = "tokio")]
+use std::future::Future;
+#[cfg(feature = "tokio")]
+use std::pin::Pin;
+#[cfg(feature = "tokio")]
+use std::task::{ready, Context, Poll};
+
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, AsyncReadExt, Chain, ReadBuf};
+#[cfg(feature = "tokio")]
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+
+/// The body of a request to a streaming route, which is read from the connection as the handler
+///   reads from it.
+///
+/// Reading stops at the end of the body as declared by its `Content-Length` header or by its final
+///   chunk, so the connection can be reused afterwards. Chunked bodies are decoded, and their trailer
+///   fields are discarded. If the connection ends or fails before the end of the body, reading fails
+///   with `ErrorKind::UnexpectedEof` or the underlying error, so a partial upload can be discarded.
+///
+/// If the handler does not read the whole body, the rest is discarded once it returns if it is no
+///   larger than `REJECTED_BODY_DRAIN_LIMIT`, and otherwise the connection is closed.
+#[cfg(not(feature = "tokio"))]
+pub struct RequestBody<'a> {
+    source: Chain<Cursor<Vec<u8>>, &'a mut dyn Read>,
+    framing: BodyFraming,
+}
+
+/// The body of a request to a streaming route, which is read from the connection as the handler
+///   reads from it.
+///
+/// Reading stops at the end of the body as declared by its `Content-Length` header or by its final
+///   chunk, so the connection can be reused afterwards. Chunked bodies are decoded, and their trailer
+///   fields are discarded. If the connection ends or fails before the end of the body, reading fails
+///   with `ErrorKind::UnexpectedEof` or the underlying error, so a partial upload can be discarded.
+///
+/// The body is only read from the connection while the handler's future is running, so it must be
+///   read before the handler returns. If the handler does not read the whole body, the rest is
+///   discarded if it is no larger than `REJECTED_BODY_DRAIN_LIMIT`, and otherwise the connection is
+///   closed.
+#[cfg(feature = "tokio")]
+pub struct RequestBody {
+    receiver: Receiver<Result<Vec<u8>, Error>>,
+    chunk: Vec<u8>,
+    position: usize,
+    content_length: Option<u64>,
+    complete: bool,
+}
+
+/// The body of a request which was left unread for a streaming route, which is sent to the
+///   handler's `RequestBody` as the handler reads from it.
+#[cfg(feature = "tokio")]
+pub(crate) struct DeferredBody<R> {
+    source: Chain<Cursor<Vec<u8>>, R>,
+    framing: BodyFraming,
+}
+
+/// Tracks how much of a request body remains to be read, decoding chunked framing one byte at a time
+///   so that nothing after the end of the body is read.
+#[derive(Debug)]
+pub(crate) struct BodyFraming {
+    state: FramingState,
+    content_length: Option<u64>,
+    declared: u64,
+    max_body_size: Option<u64>,
+    max_trailer_size: Option<usize>,
+}
+
+/// The part of a request body which is being read.
+#[derive(Debug)]
+enum FramingState {
+    /// The given number of bytes of a body with a known length remain.
+    Length(u64),
+    /// The size line of the next chunk, which is read so far.
+    ChunkSize(Vec<u8>),
+    /// The given number of bytes of the current chunk remain.
+    ChunkData(u64),
+    /// The line break after the data of a chunk, which is read so far.
+    ChunkEnd(Vec<u8>),
+    /// The current trailer line, which is read so far, and the size of the trailers so far.
+    Trailers(Vec<u8>, usize),
+    /// The body has been read to its end.
+    Done,
+    /// The length of the body was not declared and it is not chunked, so its end cannot be found.
+    Unknown,
+    /// The body could not be read, so the rest of it cannot be found.
+    Failed,
+}
+
+/// What to read from the stream next.
+enum Step {
+    /// Up to the given number of bytes of the body.
+    Data(u64),
+    /// A single byte of framing.
+    Framing,
+    /// Nothing, since the body has ended.
+    End,
+}
+
+/// The number of chunks which can be read from the connection ahead of the handler.
+#[cfg(feature = "tokio")]
+const CHANNEL_CAPACITY: usize = 4;
+
+/// The largest amount of the body which is read from the connection at once, in bytes.
+#[cfg(feature = "tokio")]
+const READ_SIZE: usize = 16 * 1024;
+
+#[cfg(not(feature = "tokio"))]
+impl<'a> RequestBody<'a> {
+    /// Creates a reader for the body of the request with the given headers, which begins with the
+    ///   bytes which were read with the head and continues on the stream.
+    pub(crate) fn new(
+        buffered: Vec<u8>,
+        stream: &'a mut dyn Read,
+        headers: &Headers,
+        limits: &RequestLimits,
+    ) -> Self {
+        Self {
+            source: Cursor::new(buffered).chain(stream),
+            framing: BodyFraming::new(headers, limits),
+        }
+    }
+
+    /// Returns the length of the body declared by its `Content-Length` header, if any.
+    pub fn content_length(&self) -> Option<u64> {
+        self.framing.content_length
+    }
+
+    /// Returns `true` if the whole body has been read.
+    pub fn is_complete(&self) -> bool {
+        self.framing.is_complete()
+    }
+
+    /// Reads and discards the rest of the body if it is small, returning `true` if the whole body has
+    ///   been read so that the connection can be reused.
+    pub(crate) fn finish(&mut self) -> bool {
+        let _ = std::io::copy(
+            &mut self.by_ref().take(REJECTED_BODY_DRAIN_LIMIT as u64),
+            &mut std::io::sink(),
+        );
+
+        self.framing.is_complete()
+    }
+}
+
+#[cfg(not(feature = "tokio"))]
+impl Read for RequestBody<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            match self.framing.step()? {
+                Step::Data(remaining) => {
+                    let length = remaining.min(buf.len() as u64) as usize;
+                    let read = self.source.read(&mut buf[..length]);
+                    let read = self.framing.check(read)?;
+                    self.framing.data(read);
+
+                    return Ok(read);
+                }
+                Step::Framing => {
+                    let mut byte = [0; 1];
+                    let read = self.source.read(&mut byte);
+                    self.framing.check(read)?;
+                    self.framing.framing(byte[0])?;
+                }
+                Step::End => return Ok(0),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl RequestBody {
+    /// Returns the length of the body declared by its `Content-Length` header, if any.
+    pub fn content_length(&self) -> Option<u64> {
+        self.content_length
+    }
+
+    /// Returns `true` if the whole body has been read.
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncRead for RequestBody {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        while self.position == self.chunk.len() && !self.complete {
+            match ready!(self.receiver.poll_recv(cx)) {
+                Some(Ok(chunk)) if chunk.is_empty() => self.complete = true,
+                Some(Ok(chunk)) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                Some(Err(e)) => return Poll::Ready(Err(e)),
+                None => {
+                    return Poll::Ready(Err(Error::new(
+                        ErrorKind::UnexpectedEof,
+                        "the handler returned before the request body was read",
+                    )))
+                }
+            }
+        }
+
+        let length = (self.chunk.len() - self.position).min(buf.remaining());
+        let start = self.position;
+        buf.put_slice(&self.chunk[start..start + length]);
+        self.position += length;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<R> DeferredBody<R>
+where
+    R: AsyncRead + Unpin,
+{
+    /// Creates a deferred body for the request with the given headers, which begins with the bytes
+    ///   which were read with the head and continues on the stream.
+    pub(crate) fn new(
+        buffered: Vec<u8>,
+        stream: R,
+        headers: &Headers,
+        limits: &RequestLimits,
+    ) -> Self {
+        Self {
+            source: Cursor::new(buffered).chain(stream),
+            framing: BodyFraming::new(headers, limits),
+        }
+    }
+
+    /// Runs the handler with a reader for the body, reading the body from the stream only as fast
+    ///   as the handler reads it.
+    pub(crate) async fn stream<F, Fut, T>(&mut self, handler: F) -> T
+    where
+        F: FnOnce(RequestBody) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let (sender, receiver) = channel(CHANNEL_CAPACITY);
+        let body = RequestBody {
+            receiver,
+            chunk: Vec::new(),
+            position: 0,
+            content_length: self.framing.content_length,
+            complete: false,
+        };
+
+        let handler = handler(body);
+        let send = send_body(&mut self.source, &mut self.framing, sender);
+        tokio::pin!(handler, send);
+
+        let mut sending = true;
+
+        loop {
+            tokio::select! {
+                response = &mut handler => return response,
+                _ = &mut send, if sending => sending = false,
+            }
+        }
+    }
+
+    /// Reads and discards the rest of the body if it is small, returning `true` if the whole body has
+    ///   been read so that the connection can be reused.
+    pub(crate) async fn finish(&mut self) -> bool {
+        let mut buf = vec![0; REJECTED_BODY_DRAIN_LIMIT];
+        let mut drained = 0;
+
+        while drained < buf.len() {
+            match read_body(&mut self.source, &mut self.framing, &mut buf[drained..]).await {
+                Ok(0) | Err(_) => break,
+                Ok(read) => drained += read,
+            }
+        }
+
+        self.framing.is_complete()
+    }
+}
+
+impl BodyFraming {
+    /// Determines how the body of the request with the given headers is framed.
+    pub(crate) fn new(headers: &Headers, limits: &RequestLimits) -> Self {
+        let content_length = content_length(headers).ok().flatten().map(|l| l as u64);
+
+        let state = if chunked_codings(headers).is_some() {
+            FramingState::ChunkSize(Vec::new())
+        } else if let Some(length) = content_length {
+            FramingState::Length(length)
+        } else if headers.get(&HeaderType::TransferEncoding).is_some() {
+            FramingState::Unknown
+        } else {
+            FramingState::Length(0)
+        };
+
+        Self {
+            state,
+            content_length,
+            declared: 0,
+            max_body_size: limits.max_body_size.map(|limit| limit as u64),
+            max_trailer_size: limits.max_header_size,
+        }
+    }
+
+    /// Returns `true` if the whole body has been read.
+    pub(crate) fn is_complete(&self) -> bool {
+        matches!(self.state, FramingState::Done | FramingState::Length(0))
+    }
+
+    /// Returns what to read from the stream next.
+    fn step(&self) -> std::io::Result<Step> {
+        match &self.state {
+            FramingState::Length(0) | FramingState::Done => Ok(Step::End),
+            FramingState::Length(remaining) | FramingState::ChunkData(remaining) => {
+                Ok(Step::Data(*remaining))
+            }
+            FramingState::ChunkSize(_) | FramingState::ChunkEnd(_) | FramingState::Trailers(..) => {
+                Ok(Step::Framing)
+            }
+            FramingState::Unknown => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "the length of the request body is unknown",
+            )),
+            FramingState::Failed => Err(Error::other("the request body could not be read")),
+        }
+    }
+
+    /// Checks the result of reading from the stream, failing if the stream ended or failed.
+    fn check(&mut self, result: std::io::Result<usize>) -> std::io::Result<usize> {
+        match result {
+            Ok(0) => Err(self.fail(Error::new(
+                ErrorKind::UnexpectedEof,
+                "the connection ended before the end of the request body",
+            ))),
+            Ok(read) => Ok(read),
+            Err(e) if e.kind() == ErrorKind::Interrupted => Err(e),
+            Err(e) => Err(self.fail(e)),
+        }
+    }
+
+    /// Records that the given number of bytes of the body were read.
+    fn data(&mut self, read: usize) {
+        match &mut self.state {
+            FramingState::Length(remaining) => *remaining -= read as u64,
+            FramingState::ChunkData(remaining) => {
+                *remaining -= read as u64;
+
+                if *remaining == 0 {
+                    self.state = FramingState::ChunkEnd(Vec::with_capacity(2));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Processes a byte of the chunked framing.
+    fn framing(&mut self, byte: u8) -> std::io::Result<()> {
+        match &mut self.state {
+            FramingState::ChunkSize(line) => {
+                line.push(byte);
+
+                if line.len() > CHUNK_SIZE_LINE_LIMIT {
+                    return Err(self.invalid("chunk size line too long"));
+                }
+
+                if byte == b'\n' {
+                    let size = match parse_chunk_size(line) {
+                        Ok(size) => size as u64,
+                        Err(_) => return Err(self.invalid("invalid chunk size")),
+                    };
+
+                    self.declared = self.declared.saturating_add(size);
+
+                    if self
+                        .max_body_size
+                        .is_some_and(|limit| self.declared > limit)
+                    {
+                        return Err(self.invalid("request body too large"));
+                    }
+
+                    self.state = match size {
+                        0 => FramingState::Trailers(Vec::new(), 0),
+                        size => FramingState::ChunkData(size),
+                    };
+                }
+            }
+            FramingState::ChunkEnd(crlf) => {
+                crlf.push(byte);
+
+                if crlf == b"\r\n" {
+                    self.state = FramingState::ChunkSize(Vec::new());
+                } else if !b"\r\n".starts_with(crlf) {
+                    return Err(self.invalid("missing line break after chunk"));
+                }
+            }
+            FramingState::Trailers(line, size) => {
+                line.push(byte);
+                *size += 1;
+
+                if self.max_trailer_size.is_some_and(|limit| *size > limit) {
+                    return Err(self.invalid("trailers too large"));
+                }
+
+                if byte == b'\n' {
+                    match line.as_slice() {
+                        b"\r\n" => self.state = FramingState::Done,
+                        _ => line.clear(),
+                    }
+                }
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// Marks the body as unreadable, returning an error describing the invalid framing.
+    fn invalid(&mut self, message: &str) -> Error {
+        self.fail(Error::new(ErrorKind::InvalidData, message))
+    }
+
+    /// Marks the body as unreadable, returning the error which caused it.
+    fn fail(&mut self, error: Error) -> Error {
+        self.state = FramingState::Failed;
+        error
+    }
+}
+
+/// Reads the next part of the body into the buffer, returning `Ok(0)` at its end.
+#[cfg(feature = "tokio")]
+async fn read_body<R>(
+    source: &mut R,
+    framing: &mut BodyFraming,
+    buf: &mut [u8],
+) -> std::io::Result<usize>
+where
+    R: AsyncRead + Unpin,
+{
+    if buf.is_empty() {
+        return Ok(0);
+    }
+
+    loop {
+        match framing.step()? {
+            Step::Data(remaining) => {
+                let length = remaining.min(buf.len() as u64) as usize;
+                let read = source.read(&mut buf[..length]).await;
+                let read = framing.check(read)?;
+                framing.data(read);
+
+                return Ok(read);
+            }
+            Step::Framing => {
+                let mut byte = [0; 1];
+                let read = source.read(&mut byte).await;
+                framing.check(read)?;
+                framing.framing(byte[0])?;
+            }
+            Step::End => return Ok(0),
+        }
+    }
+}
+
+/// Sends the body to the handler's `RequestBody`, only reading more of it once there is room in the
+///   channel, and finishing with an empty chunk at the end of the body.
+#[cfg(feature = "tokio")]
+async fn send_body<R>(
+    source: &mut R,
+    framing: &mut BodyFraming,
+    sender: Sender<Result<Vec<u8>, Error>>,
+) where
+    R: AsyncRead + Unpin,
+{
+    let mut buf = vec![0; READ_SIZE];
+
+    // The handler dropping its `RequestBody` closes the channel, which stops reading
+    while let Ok(permit) = sender.reserve().await {
+        match read_body(source, framing, &mut buf).await {
+            Ok(0) => return permit.send(Ok(Vec::new())),
+            Ok(read) => permit.send(Ok(buf[..read].to_vec())),
+            Err(e) => return permit.send(Err(e)),
+        }
+    }
+}
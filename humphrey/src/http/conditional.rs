@@ -0,0 +1,339 @@
+//! Provides support for conditional requests according to [RFC 7232](https://datatracker.ietf.org/doc/html/rfc7232).
+//!
+//! A handler which knows the current entity tag or modification date of a resource can evaluate the
+//!   `If-Match`, `If-None-Match`, `If-Modified-Since` and `If-Unmodified-Since` headers of a request
+//!   against them, and reply with `304 Not Modified` or `412 Precondition Failed` without generating
+//!   the body when the client's copy is up to date or its precondition does not hold.
+//!
+//! ## Example
+//! ```
+//! fn handler(request: Request, state: Arc<State>) -> Response {
+//!     let version = state.version.load(Ordering::SeqCst);
+//!
+//!     respond_conditional(&request, Some(ETag::from_version(version)), None, || {
+//!         Response::json(&state.items())
+//!     })
+//! }
+//! ```
+
+use crate::http::date::DateTime;
+use crate::http::headers::HeaderType;
+use crate::http::method::Method;
+use crate::http::request::Request;
+use crate::http::response::Response;
+use crate::http::status::StatusCode;
+
+use std::fmt::Display;
+
+/// Represents an entity tag, which identifies a specific version of a resource.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ETag {
+    /// The opaque tag, without the surrounding quotes.
+    pub tag: String,
+    /// Whether the tag is weak, meaning that it identifies a semantically equivalent version of the
+    ///   resource rather than an identical one.
+    pub weak: bool,
+}
+
+/// Represents the validators of the current version of a resource, against which the preconditions of
+///   a request are evaluated.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Validators {
+    /// The entity tag of the current version of the resource, if any.
+    pub etag: Option<ETag>,
+    /// The date at which the resource was last modified, if known.
+    pub last_modified: Option<DateTime>,
+    /// Whether the resource currently exists, which determines whether `If-Match: *` and
+    ///   `If-None-Match: *` match.
+    pub exists: bool,
+}
+
+/// Represents the result of evaluating the preconditions of a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConditionalResult {
+    /// Every precondition holds, so the request should be handled as normal.
+    Proceed,
+    /// The client's copy of the resource is up to date, so a `304 Not Modified` response should be
+    ///   sent without a body.
+    NotModified,
+    /// A precondition does not hold, so a `412 Precondition Failed` response should be sent and the
+    ///   request should not be applied.
+    PreconditionFailed,
+}
+
+impl ETag {
+    /// Creates a strong entity tag with the given opaque tag, which must not contain double quotes.
+    pub fn strong(tag: impl AsRef<str>) -> Self {
+        Self {
+            tag: tag.as_ref().to_string(),
+            weak: false,
+        }
+    }
+
+    /// Creates a weak entity tag with the given opaque tag, which must not contain double quotes.
+    pub fn weak(tag: impl AsRef<str>) -> Self {
+        Self {
+            tag: tag.as_ref().to_string(),
+            weak: true,
+        }
+    }
+
+    /// Creates a strong entity tag from a hash of the given bytes, such as the body of a response.
+    pub fn from_bytes(bytes: impl AsRef<[u8]>) -> Self {
+        Self::strong(format!("{:016x}", fnv1a(bytes.as_ref())))
+    }
+
+    /// Creates a strong entity tag from a version counter which changes whenever the resource does,
+    ///   which avoids generating the body to find its hash.
+    pub fn from_version(version: u64) -> Self {
+        Self::strong(format!("v{}", version))
+    }
+
+    /// Parses a single entity tag, such as `"abc"` or `W/"abc"`.
+    ///
+    /// Returns `None` if the value is not exactly one entity tag.
+    pub fn parse(value: &str) -> Option<Self> {
+        match parse_list(value.trim()) {
+            (tags, "") if tags.len() == 1 => tags.into_iter().next(),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the two entity tags match using the strong comparison function, under which
+    ///   both must be strong and have the same opaque tag.
+    pub fn strong_eq(&self, other: &Self) -> bool {
+        !self.weak && !other.weak && self.tag == other.tag
+    }
+
+    /// Returns whether the two entity tags match using the weak comparison function, under which only
+    ///   the opaque tags must be the same.
+    pub fn weak_eq(&self, other: &Self) -> bool {
+        self.tag == other.tag
+    }
+}
+
+impl Display for ETag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.weak {
+            write!(f, "W/\"{}\"", self.tag)
+        } else {
+            write!(f, "\"{}\"", self.tag)
+        }
+    }
+}
+
+impl Validators {
+    /// Creates the validators of an existing resource with no entity tag or modification date.
+    pub fn new() -> Self {
+        Self {
+            etag: None,
+            last_modified: None,
+            exists: true,
+        }
+    }
+
+    /// Creates the validators of a resource which does not currently exist, such as the target of a
+    ///   `PUT` request which creates it.
+    pub fn missing() -> Self {
+        Self {
+            exists: false,
+            ..Self::new()
+        }
+    }
+
+    /// Sets the entity tag of the current version of the resource.
+    /// Returns itself for use in a builder pattern.
+    pub fn with_etag(mut self, etag: ETag) -> Self {
+        self.etag = Some(etag);
+        self
+    }
+
+    /// Sets the date at which the resource was last modified.
+    /// Returns itself for use in a builder pattern.
+    pub fn with_last_modified(mut self, last_modified: DateTime) -> Self {
+        self.last_modified = Some(last_modified);
+        self
+    }
+}
+
+impl Default for Validators {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Evaluates the preconditions of the request against the validators of the current version of the
+///   resource, in the order given in [section 6 of RFC 7232](https://datatracker.ietf.org/doc/html/rfc7232#section-6).
+///
+/// `If-Match` is evaluated with the strong comparison function and `If-None-Match` with the weak one.
+///   `If-Unmodified-Since` is ignored if the request has an `If-Match` header, and `If-Modified-Since`
+///   is ignored if it has an `If-None-Match` header or is not a `GET` or `HEAD` request. Dates which
+///   cannot be parsed are ignored, as are dates when the modification date of the resource is unknown.
+///
+/// When `If-None-Match` does not hold, `GET` and `HEAD` requests are answered with `304 Not Modified`
+///   and other requests with `412 Precondition Failed`.
+///
+/// This should only be used when the response would otherwise be successful, since the preconditions
+///   do not apply to error responses.
+pub fn evaluate(request: &Request, validators: &Validators) -> ConditionalResult {
+    let is_get_or_head = matches!(request.method, Method::Get | Method::Head);
+
+    let if_match = request.headers.get_all(HeaderType::IfMatch);
+    let if_none_match = request.headers.get_all(HeaderType::IfNoneMatch);
+
+    if !if_match.is_empty() {
+        if !matches_any(&if_match, validators, ETag::strong_eq) {
+            return ConditionalResult::PreconditionFailed;
+        }
+    } else if let Some(since) = request
+        .headers
+        .get(HeaderType::IfUnmodifiedSince)
+        .and_then(DateTime::parse)
+    {
+        if validators
+            .last_modified
+            .is_some_and(|last_modified| last_modified > since)
+        {
+            return ConditionalResult::PreconditionFailed;
+        }
+    }
+
+    if !if_none_match.is_empty() {
+        if matches_any(&if_none_match, validators, ETag::weak_eq) {
+            return match is_get_or_head {
+                true => ConditionalResult::NotModified,
+                false => ConditionalResult::PreconditionFailed,
+            };
+        }
+    } else if let Some(since) = request
+        .headers
+        .get(HeaderType::IfModifiedSince)
+        .and_then(DateTime::parse)
+        .filter(|_| is_get_or_head)
+    {
+        if validators
+            .last_modified
+            .is_some_and(|last_modified| last_modified <= since)
+        {
+            return ConditionalResult::NotModified;
+        }
+    }
+
+    ConditionalResult::Proceed
+}
+
+/// Responds to a `GET` or `HEAD` request for a resource with the given validators, only calling
+///   `build` to generate the response if the client does not already have an up-to-date copy.
+///
+/// The `ETag` and `Last-Modified` headers are added to both full and `304 Not Modified` responses.
+///   If a precondition does not hold, an empty `412 Precondition Failed` response is returned.
+///
+/// ## Example
+/// ```
+/// respond_conditional(&request, Some(ETag::from_version(3)), None, || {
+///     Response::new(StatusCode::OK, "version 3")
+/// })
+/// ```
+pub fn respond_conditional<F>(
+    request: &Request,
+    etag: Option<ETag>,
+    last_modified: Option<DateTime>,
+    build: F,
+) -> Response
+where
+    F: FnOnce() -> Response,
+{
+    let validators = Validators {
+        etag,
+        last_modified,
+        exists: true,
+    };
+
+    let response = match evaluate(request, &validators) {
+        ConditionalResult::Proceed => build(),
+        ConditionalResult::NotModified => Response::empty(StatusCode::NotModified),
+        ConditionalResult::PreconditionFailed => {
+            return Response::empty(StatusCode::PreconditionFailed)
+        }
+    };
+
+    let response = match validators.etag {
+        Some(etag) => response.with_etag(etag),
+        None => response,
+    };
+
+    match validators.last_modified {
+        Some(date) => response.with_header(HeaderType::LastModified, date.to_string()),
+        None => response,
+    }
+}
+
+/// Returns whether any of the given header values, each either `*` or a list of entity tags, match
+///   the current version of the resource using the given comparison function.
+fn matches_any(
+    values: &[&str],
+    validators: &Validators,
+    compare: fn(&ETag, &ETag) -> bool,
+) -> bool {
+    values.iter().any(|value| {
+        if value.trim() == "*" {
+            return validators.exists;
+        }
+
+        match (&validators.etag, validators.exists) {
+            (Some(current), true) => parse_list(value).0.iter().any(|tag| compare(tag, current)),
+            _ => false,
+        }
+    })
+}
+
+/// Parses a comma-separated list of entity tags, stopping at the first malformed one.
+///
+/// Returns the tags which were parsed, along with the rest of the value from the malformed tag onwards.
+fn parse_list(value: &str) -> (Vec<ETag>, &str) {
+    let mut tags = Vec::new();
+    let mut rest = value;
+
+    loop {
+        rest = rest.trim_start_matches(|c: char| c == ',' || c.is_ascii_whitespace());
+
+        if rest.is_empty() {
+            return (tags, rest);
+        }
+
+        let (weak, quoted) = match rest.strip_prefix("W/") {
+            Some(quoted) => (true, quoted),
+            None => (false, rest),
+        };
+
+        // The opaque tag may contain commas, so it is read up to the closing quote
+        let tag = quoted
+            .strip_prefix('"')
+            .and_then(|quoted| quoted.split_once('"'))
+            .filter(|(tag, _)| tag.bytes().all(is_etagc));
+
+        match tag {
+            Some((tag, after)) => {
+                tags.push(ETag {
+                    tag: tag.to_string(),
+                    weak,
+                });
+                rest = after;
+            }
+            None => return (tags, rest),
+        }
+    }
+}
+
+/// Returns whether the byte may appear in an opaque tag, which excludes whitespace, double quotes and
+///   control characters.
+fn is_etagc(byte: u8) -> bool {
+    byte == 0x21 || (0x23..=0x7e).contains(&byte) || byte >= 0x80
+}
+
+/// Hashes the given bytes with the 64-bit FNV-1a hash function.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
@@ -39,7 +39,7 @@ pub struct SetCookie {
 }
 
 /// Represents the SameSite value of the cookie.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SameSite {
     /// Cookies will only be sent in a first-party context and not be sent along with requests
     ///   initiated by third party websites.
@@ -62,6 +62,28 @@ impl Cookie {
         }
     }
 
+    /// Parses a single `name=value` pair, as found in the `Cookie` header or at the start of the
+    ///   `Set-Cookie` header.
+    ///
+    /// Whitespace around the name and value is ignored, and a value enclosed in double quotes is
+    ///   unquoted. Returns `None` if the pair has no `=` or the name is empty.
+    pub fn parse(pair: &str) -> Option<Self> {
+        let (name, value) = pair.split_once('=')?;
+        let name = name.trim();
+
+        if name.is_empty() {
+            return None;
+        }
+
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .unwrap_or(value);
+
+        Some(Self::new(name, value))
+    }
+
     /// Convert a collection of cookies into a `Cookie` header.
     pub fn to_header(cookies: impl AsRef<[Cookie]>) -> Option<Header> {
         let cookies = cookies.as_ref();
@@ -138,6 +160,9 @@ impl SetCookie {
     }
 
     /// Set the SameSite configuration of the cookie.
+    ///
+    /// Browsers reject cookies with `SameSite=None` unless they are secure, so the `Secure` attribute
+    ///   is always sent with `SameSite::None`.
     pub fn with_same_site(mut self, same_site: SameSite) -> Self {
         self.same_site = Some(same_site);
         self
@@ -176,7 +201,7 @@ impl From<SetCookie> for Header {
             );
         }
 
-        if cookie.secure {
+        if cookie.secure || cookie.same_site == Some(SameSite::None) {
             value = format!("{}; Secure", value);
         }
 
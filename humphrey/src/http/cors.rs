@@ -1,9 +1,14 @@
 //! Contains the CORS implementation for Humphrey.
 
-use crate::http::headers::{HeaderLike, HeaderType, Headers};
+use crate::http::headers::HeaderLike;
 use crate::http::method::Method;
 
-#[derive(Clone)]
+#[cfg(feature = "runtime")]
+use crate::http::headers::{HeaderType, Headers};
+#[cfg(feature = "runtime")]
+use crate::http::method::{MethodOverride, METHOD_OVERRIDE_HEADER};
+
+#[derive(Clone, Debug)]
 enum Wildcardable<T> {
     Wildcard,
     Value(T),
@@ -15,7 +20,7 @@ enum Wildcardable<T> {
 ///   its own from which a browser should permit loading resources.
 ///
 /// Learn more about CORS at the [MDN docs](https://developer.mozilla.org/en-US/docs/Web/HTTP/CORS).
-#[derive(Clone, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct Cors {
     allowed_origins: Wildcardable<Vec<String>>,
     allowed_methods: Wildcardable<Vec<Method>>,
@@ -89,7 +94,63 @@ impl Cors {
         self
     }
 
+    /// Returns whether requests from the given origin are allowed to use the given method.
+    #[cfg(feature = "runtime")]
+    pub(crate) fn allows(&self, origin: &str, method: &Method) -> bool {
+        let origin_allowed = match self.allowed_origins {
+            Wildcardable::Wildcard => true,
+            Wildcardable::Value(ref origins) => origins.iter().any(|o| o == origin),
+        };
+
+        let method_allowed = match self.allowed_methods {
+            Wildcardable::Wildcard => true,
+            Wildcardable::Value(ref methods) => methods.contains(method),
+        };
+
+        origin_allowed && method_allowed
+    }
+
+    /// Allows the `X-HTTP-Method-Override` header in a preflight response, if this configuration
+    ///   allows any origin to use a method which `POST` requests can be overridden to.
+    #[cfg(feature = "runtime")]
+    pub(crate) fn allow_override_header(&self, headers: &mut Headers) {
+        let any_origin = match self.allowed_origins {
+            Wildcardable::Wildcard => true,
+            Wildcardable::Value(ref origins) => !origins.is_empty(),
+        };
+
+        let any_method = match self.allowed_methods {
+            Wildcardable::Wildcard => true,
+            Wildcardable::Value(ref methods) => MethodOverride::METHODS
+                .iter()
+                .any(|method| methods.contains(method)),
+        };
+
+        if !any_origin || !any_method {
+            return;
+        }
+
+        match headers.get_mut(HeaderType::AccessControlAllowHeaders) {
+            Some(allowed) => {
+                let already_allowed = allowed == "*"
+                    || allowed
+                        .split(',')
+                        .any(|header| header.trim().eq_ignore_ascii_case(METHOD_OVERRIDE_HEADER));
+
+                if !already_allowed {
+                    allowed.push_str(", ");
+                    allowed.push_str(METHOD_OVERRIDE_HEADER);
+                }
+            }
+            None => headers.add(
+                HeaderType::AccessControlAllowHeaders,
+                METHOD_OVERRIDE_HEADER,
+            ),
+        }
+    }
+
     /// Sets the appropriate headers for the CORS configuration.
+    #[cfg(feature = "runtime")]
     pub(crate) fn set_headers(&self, headers: &mut Headers) {
         if headers.get(HeaderType::AccessControlAllowOrigin).is_none() {
             match self.allowed_origins {
@@ -1,6 +1,7 @@
 //! Provides functionality for handling HTTP date timestamps.
 
-use std::time::SystemTime;
+use std::ops::Sub;
+use std::time::{Duration, SystemTime};
 
 const DAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
 const MONTHS: [&str; 12] = [
@@ -18,6 +19,10 @@ const DAYS_400_YEARS: i64 = 365 * 400 + 97;
 const MARCH_01_2000: i64 = 951868800;
 
 /// Represents a date and time.
+///
+/// Dates are compared by their timestamps, so a handler can compare a parsed `If-Modified-Since`
+///   header against the modification time of a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct DateTime {
     /// The UNIX timestamp of the date.
     pub timestamp: i64,
@@ -38,20 +43,152 @@ pub struct DateTime {
 }
 
 impl DateTime {
-    /// Creates a new `DateTime` from the current time.
+    /// Creates a new `DateTime` from the current time, as given by the current thread's clock.
+    #[cfg(feature = "runtime")]
     pub fn now() -> Self {
-        let timestamp = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
+        Self::from(SystemTime::UNIX_EPOCH + crate::clock::now_unix())
+    }
 
-        Self::from(timestamp)
+    /// Parses an HTTP date, such as the value of an `If-Modified-Since` header.
+    ///
+    /// As well as the preferred RFC 1123 format (`Sun, 06 Nov 1994 08:49:37 GMT`), the obsolete
+    ///   RFC 850 (`Sunday, 06-Nov-94 08:49:37 GMT`) and asctime (`Sun Nov  6 08:49:37 1994`) formats
+    ///   are accepted, since clients may still send them. A two-digit RFC 850 year is taken to be the
+    ///   most recent year ending in those digits which is not more than 50 years in the future.
+    ///   Without the `runtime` feature there is no clock to find the current year, so RFC 850 dates
+    ///   are rejected.
+    ///
+    /// Returns `None` if the date is in none of these formats or does not exist, such as 29 February
+    ///   in a year which is not a leap year.
+    ///
+    /// ## Example
+    /// ```
+    /// let date = DateTime::parse("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+    /// assert_eq!(date.timestamp, 784111777);
+    /// ```
+    pub fn parse(date: &str) -> Option<Self> {
+        let date = date.trim();
+
+        let (day, month, year, time) = match date.split_once(',') {
+            Some((weekday, rest)) => {
+                if !weekday.chars().all(|c| c.is_ascii_alphabetic()) {
+                    return None;
+                }
+
+                match rest.split_whitespace().collect::<Vec<&str>>().as_slice() {
+                    // RFC 1123, for example `Sun, 06 Nov 1994 08:49:37 GMT`
+                    [day, month, year, time, "GMT"] => {
+                        (digits(day, 2)?, *month, digits(year, 4)?, *time)
+                    }
+                    // RFC 850, for example `Sunday, 06-Nov-94 08:49:37 GMT`
+                    [date, time, "GMT"] => {
+                        let mut parts = date.split('-');
+                        let day = digits(parts.next()?, 2)?;
+                        let month = parts.next()?;
+                        let year = digits(parts.next()?, 2)?;
+
+                        if parts.next().is_some() {
+                            return None;
+                        }
+
+                        (day, month, expand_year(year, current_year()?), *time)
+                    }
+                    _ => return None,
+                }
+            }
+            // asctime, for example `Sun Nov  6 08:49:37 1994`
+            None => match date.split_whitespace().collect::<Vec<&str>>().as_slice() {
+                [weekday, month, day, time, year]
+                    if weekday.chars().all(|c| c.is_ascii_alphabetic()) =>
+                {
+                    let day = digits(day, 1).or_else(|| digits(day, 2))?;
+                    (day, *month, digits(year, 4)?, *time)
+                }
+                _ => return None,
+            },
+        };
+
+        let month = MONTHS.iter().position(|m| *m == month)?;
+
+        let mut time = time.split(':');
+        let hour = digits(time.next()?, 2)?;
+        let minute = digits(time.next()?, 2)?;
+        let second = digits(time.next()?, 2)?;
+
+        if time.next().is_some() || hour > 23 || minute > 59 || second > 59 || day < 1 {
+            return None;
+        }
+
+        let timestamp =
+            days_from_civil(year, month as i64, day) * DAY + hour * HOUR + minute * MINUTE + second;
+        let date = Self::from(timestamp);
+
+        // Days beyond the end of the month roll over into the next, so they are rejected here
+        (date.day as i64 == day && date.month as usize == month).then_some(date)
     }
 
     /// Returns the UNIX timestamp of the date.
     pub fn get_timestamp(&self) -> i64 {
         self.timestamp
     }
+
+    /// Returns the date the given number of seconds after this one, such as the expiry of a cookie
+    ///   or cached response.
+    ///
+    /// Every field is recalculated, including the weekday. Like UNIX timestamps, the result ignores
+    ///   leap seconds, so every day is exactly 86400 seconds long.
+    ///
+    /// ## Example
+    /// ```
+    /// let expires = DateTime::now().add_seconds(60 * 60 * 24);
+    /// response.with_header(HeaderType::Expires, expires.to_string())
+    /// ```
+    pub fn add_seconds(&self, seconds: u64) -> Self {
+        let seconds = i64::try_from(seconds).unwrap_or(i64::MAX);
+
+        Self::from(self.timestamp.saturating_add(seconds))
+    }
+}
+
+impl From<SystemTime> for DateTime {
+    /// Converts from a system time, such as the modification time of a file, into a date and time.
+    ///
+    /// HTTP dates only have a precision of one second, so any fraction of a second is discarded.
+    fn from(time: SystemTime) -> Self {
+        let timestamp = match time.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(duration) => duration.as_secs() as i64,
+            Err(e) => -(e.duration().as_secs_f64().ceil() as i64),
+        };
+
+        Self::from(timestamp)
+    }
+}
+
+impl From<DateTime> for SystemTime {
+    /// Converts from a date and time into a system time, such as to compare it with the current time.
+    fn from(date: DateTime) -> Self {
+        let offset = Duration::from_secs(date.timestamp.unsigned_abs());
+
+        match date.timestamp >= 0 {
+            true => SystemTime::UNIX_EPOCH + offset,
+            false => SystemTime::UNIX_EPOCH - offset,
+        }
+    }
+}
+
+impl Sub for DateTime {
+    type Output = i64;
+
+    /// Returns the number of seconds from the other date to this one, which is negative if the other
+    ///   date is later.
+    ///
+    /// ## Example
+    /// ```
+    /// let retry_after = retry_at - DateTime::now();
+    /// ```
+    fn sub(self, other: Self) -> i64 {
+        self.timestamp - other.timestamp
+    }
 }
 
 impl From<i64> for DateTime {
@@ -139,7 +276,7 @@ impl ToString for DateTime {
     /// ```
     fn to_string(&self) -> String {
         format!(
-            "{}, {:02} {:02} {} {:02}:{:02}:{:02} GMT",
+            "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
             DAYS[self.weekday as usize],
             self.day,
             MONTHS[self.month as usize],
@@ -150,3 +287,52 @@ impl ToString for DateTime {
         )
     }
 }
+
+/// Parses a number with exactly the given number of digits.
+fn digits(value: &str, length: usize) -> Option<i64> {
+    if value.len() == length && value.bytes().all(|b| b.is_ascii_digit()) {
+        value.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Returns the current year, or `None` if there is no clock to read it from.
+#[cfg(feature = "runtime")]
+fn current_year() -> Option<i64> {
+    Some(DateTime::now().year as i64)
+}
+
+/// Returns the current year, or `None` if there is no clock to read it from.
+#[cfg(not(feature = "runtime"))]
+fn current_year() -> Option<i64> {
+    None
+}
+
+/// Expands a two-digit year into the most recent year ending in those digits which is not more than
+///   50 years after the current year, as required by RFC 7231.
+pub(crate) fn expand_year(year: i64, current_year: i64) -> i64 {
+    let mut expanded = current_year - current_year.rem_euclid(100) + year;
+
+    if expanded > current_year + 50 {
+        expanded -= 100;
+    } else if expanded <= current_year - 50 {
+        expanded += 100;
+    }
+
+    expanded
+}
+
+/// Returns the number of days between the UNIX epoch and the given date, where the month starts from
+///   zero for January.
+/// Implementation modified from [here](https://howardhinnant.github.io/date_algorithms.html#days_from_civil).
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // Count years from March so that the leap day is at the end of the year
+    let year = if month < 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * ((month + 10) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * DAYS_400_YEARS + day_of_era - 719468
+}
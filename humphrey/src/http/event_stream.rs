@@ -0,0 +1,245 @@
+//! Provides functionality for pushing server-sent events to clients.
+//!
+//! A handler returns the response from `Response::event_stream` and keeps the `EventSender` which
+//!   comes with it, moving it to another thread or task to send events to the client as they happen.
+//!   The response is sent with `Content-Type: text/event-stream` and each event is written to the
+//!   connection as soon as it is sent. The stream ends when every clone of the sender is dropped,
+//!   after which the connection is closed rather than reused.
+//!
+//! Learn more about server-sent events [here](https://html.spec.whatwg.org/multipage/server-sent-events.html).
+
+use std::error::Error;
+use std::fmt::Display;
+use std::time::Duration;
+
+#[cfg(not(feature = "tokio"))]
+use std::io::Read;
+#[cfg(not(feature = "tokio"))]
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+#[cfg(feature = "tokio")]
+use std::pin::Pin;
+#[cfg(feature = "tokio")]
+use std::task::{ready, Context, Poll};
+
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, ReadBuf};
+#[cfg(feature = "tokio")]
+use tokio::sync::mpsc::{
+    unbounded_channel as channel, UnboundedReceiver as Receiver, UnboundedSender as Sender,
+};
+
+/// Represents an event sent to the client over an event stream.
+///
+/// ## Example
+/// ```
+/// Event::new("{\"price\": 42}")
+///     .with_event("price")
+///     .with_id("17")
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Event {
+    /// The ID of the event, which the client sends back in the `Last-Event-ID` header if it
+    ///   reconnects.
+    pub id: Option<String>,
+    /// The type of the event, which defaults to `message` on the client.
+    pub event: Option<String>,
+    /// The data of the event, which may span multiple lines.
+    pub data: String,
+    /// How long the client should wait before reconnecting if the connection is lost.
+    pub retry: Option<Duration>,
+}
+
+/// Sends events to the client over the event stream it was created with.
+///
+/// Clones send to the same stream, and the stream ends once every clone has been dropped.
+#[derive(Clone, Debug)]
+pub struct EventSender {
+    sender: Sender<Vec<u8>>,
+}
+
+/// The body of an event stream, which reads each event as soon as it is sent and ends once every
+///   sender has been dropped.
+pub(crate) struct EventReader {
+    receiver: Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+/// An error returned when sending to an event stream whose connection has been closed, for example
+///   because the client disconnected.
+#[derive(Debug, PartialEq, Eq)]
+pub struct StreamClosed;
+
+impl Event {
+    /// Creates a new event with the given data.
+    pub fn new(data: impl AsRef<str>) -> Self {
+        Self {
+            data: data.as_ref().to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Sets the ID of the event.
+    /// Returns itself for use in a builder pattern.
+    pub fn with_id(mut self, id: impl AsRef<str>) -> Self {
+        self.id = Some(id.as_ref().to_string());
+        self
+    }
+
+    /// Sets the type of the event.
+    /// Returns itself for use in a builder pattern.
+    pub fn with_event(mut self, event: impl AsRef<str>) -> Self {
+        self.event = Some(event.as_ref().to_string());
+        self
+    }
+
+    /// Sets how long the client should wait before reconnecting.
+    /// Returns itself for use in a builder pattern.
+    pub fn with_retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Serialises the event in the format of an event stream.
+    ///
+    /// The data is split into one `data` field per line, so that the client joins the lines back
+    ///   together. The ID and type of the event cannot span lines, so any line breaks in them are
+    ///   removed.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.data.len() + 16);
+
+        if let Some(id) = &self.id {
+            write_field(&mut bytes, "id", &single_line(id));
+        }
+
+        if let Some(event) = &self.event {
+            write_field(&mut bytes, "event", &single_line(event));
+        }
+
+        if let Some(retry) = self.retry {
+            write_field(&mut bytes, "retry", &retry.as_millis().to_string());
+        }
+
+        for line in lines(&self.data) {
+            write_field(&mut bytes, "data", line);
+        }
+
+        bytes.push(b'\n');
+        bytes
+    }
+}
+
+impl EventSender {
+    /// Sends the event to the client.
+    ///
+    /// Returns an error if the stream has been closed, in which case nothing more can be sent.
+    pub fn send(&self, event: Event) -> Result<(), StreamClosed> {
+        self.sender.send(event.to_bytes()).map_err(|_| StreamClosed)
+    }
+
+    /// Sends a comment to the client, which is ignored by it but can be used to stop proxies from
+    ///   closing a quiet connection.
+    ///
+    /// Returns an error if the stream has been closed, in which case nothing more can be sent.
+    pub fn comment(&self, comment: impl AsRef<str>) -> Result<(), StreamClosed> {
+        let mut bytes = Vec::new();
+
+        for line in lines(comment.as_ref()) {
+            write_field(&mut bytes, "", line);
+        }
+
+        bytes.push(b'\n');
+        self.sender.send(bytes).map_err(|_| StreamClosed)
+    }
+}
+
+/// Creates a connected sender and body for an event stream.
+pub(crate) fn event_stream() -> (EventSender, EventReader) {
+    let (sender, receiver) = channel();
+
+    (
+        EventSender { sender },
+        EventReader {
+            receiver,
+            chunk: Vec::new(),
+            position: 0,
+        },
+    )
+}
+
+#[cfg(not(feature = "tokio"))]
+impl Read for EventReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.position == self.chunk.len() {
+            match self.receiver.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+
+        let length = (self.chunk.len() - self.position).min(buf.len());
+        let start = self.position;
+        buf[..length].copy_from_slice(&self.chunk[start..start + length]);
+        self.position += length;
+
+        Ok(length)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncRead for EventReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        if self.position == self.chunk.len() {
+            match ready!(self.receiver.poll_recv(cx)) {
+                Some(chunk) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                None => return Poll::Ready(Ok(())),
+            }
+        }
+
+        let length = (self.chunk.len() - self.position).min(buf.remaining());
+        let start = self.position;
+        buf.put_slice(&self.chunk[start..start + length]);
+        self.position += length;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Display for StreamClosed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Event stream error: the stream has been closed")
+    }
+}
+
+impl Error for StreamClosed {}
+
+/// Appends a field to the buffer, where a field with no name is a comment.
+fn write_field(buf: &mut Vec<u8>, name: &str, value: &str) {
+    buf.extend(name.as_bytes());
+    buf.extend(b": ");
+    buf.extend(value.as_bytes());
+    buf.push(b'\n');
+}
+
+/// Splits the text into lines at any of the line breaks recognised in event streams, which are
+///   `\r\n`, `\r` and `\n`.
+fn lines(text: &str) -> impl Iterator<Item = &str> {
+    text.split('\n')
+        .flat_map(|line| line.strip_suffix('\r').unwrap_or(line).split('\r'))
+}
+
+/// Removes any line breaks from the text.
+fn single_line(text: &str) -> String {
+    text.replace(['\r', '\n'], "")
+}
@@ -70,8 +70,11 @@ impl Headers {
             .map(|h| &mut h.value)
     }
 
-    /// Get a list of all the values of the headers with the given name.
+    /// Get a list of all the values of the headers with the given name, in the order they were added.
     /// If no headers with the given name exist, an empty list is returned.
+    ///
+    /// This should be used for headers which can occur multiple times, such as `Set-Cookie`, since
+    ///   `get` only returns the first value.
     pub fn get_all(&self, name: impl HeaderLike) -> Vec<&str> {
         let header = name.to_header();
         self.0
@@ -87,10 +90,12 @@ impl Headers {
         self.0.retain(|h| h.name != header);
     }
 
-    /// Return an iterator over the headers in the collection.
+    /// Return an iterator over the headers in the collection, sorted by name.
+    ///
+    /// Headers which occur multiple times, such as `Set-Cookie`, are kept in the order they were added.
     pub fn iter(&self) -> impl Iterator<Item = Header> {
         let mut headers = self.0.clone();
-        headers.sort_unstable_by_key(|h| h.name.clone());
+        headers.sort_by_key(|h| h.name.clone());
         headers.into_iter()
     }
 }
@@ -176,12 +181,29 @@ pub enum HeaderType {
     From,
     /// Specifies the host to which the request is being sent, e.g. "www.example.com".
     Host,
+    /// Makes the request conditional on the current entity tag of the resource matching one of the given tags.
+    IfMatch,
+    /// Makes the request conditional on the resource having been modified since the given date.
+    IfModifiedSince,
+    /// Makes the request conditional on the current entity tag of the resource matching none of the given tags.
+    IfNoneMatch,
+    /// Makes a `Range` request conditional on the resource not having changed.
+    IfRange,
+    /// Makes the request conditional on the resource not having been modified since the given date.
+    IfUnmodifiedSince,
+    /// Indicates how long an idle connection will be kept open and how many more requests can be
+    ///   sent on it.
+    KeepAlive,
     /// Indicates the origin that caused the request.
     Origin,
     /// Contains backwards-compatible caching information.
     Pragma,
+    /// Requests only part of a resource, such as `bytes=0-1023`.
+    Range,
     /// Indicates the absolute or partial address of the page making the request.
     Referer,
+    /// Lists the header fields which will be sent as trailers after a chunked message body.
+    Trailer,
     /// Indicates that the connection is to be upgraded to a different protocol, e.g. WebSocket.
     Upgrade,
     /// Informs the server of basic browser and device information.
@@ -197,6 +219,8 @@ pub enum HeaderType {
     AccessControlAllowHeaders,
     /// Indicates whether certain methods can be used.
     AccessControlAllowMethods,
+    /// Indicates whether the server supports range requests for the resource.
+    AcceptRanges,
     /// Contains the time in seconds that the object has been cached.
     Age,
     /// The set of methods supported by the resource.
@@ -207,6 +231,8 @@ pub enum HeaderType {
     ContentLanguage,
     /// Indicates an alternative location for the returned data.
     ContentLocation,
+    /// Indicates which part of the resource is contained in a partial response.
+    ContentRange,
     /// Identifies a specific version of a resource.
     ETag,
     /// Contains the date and time at which the response is considered expired.
@@ -217,6 +243,8 @@ pub enum HeaderType {
     Link,
     /// Indicates the location at which the resource can be found, used for redirects.
     Location,
+    /// Indicates how long the client should wait before making a follow-up request.
+    RetryAfter,
     /// Contains information about the server which served the request.
     Server,
     /// Indicates that the client should set the specified cookies.
@@ -265,9 +293,17 @@ impl From<&str> for HeaderType {
             "forwarded" => Self::Forwarded,
             "from" => Self::From,
             "host" => Self::Host,
+            "if-match" => Self::IfMatch,
+            "if-modified-since" => Self::IfModifiedSince,
+            "if-none-match" => Self::IfNoneMatch,
+            "if-range" => Self::IfRange,
+            "if-unmodified-since" => Self::IfUnmodifiedSince,
+            "keep-alive" => Self::KeepAlive,
             "origin" => Self::Origin,
             "pragma" => Self::Pragma,
+            "range" => Self::Range,
             "referer" => Self::Referer,
+            "trailer" => Self::Trailer,
             "upgrade" => Self::Upgrade,
             "user-agent" => Self::UserAgent,
             "via" => Self::Via,
@@ -275,16 +311,19 @@ impl From<&str> for HeaderType {
             "access-control-allow-origin" => Self::AccessControlAllowOrigin,
             "access-control-allow-headers" => Self::AccessControlAllowHeaders,
             "access-control-allow-methods" => Self::AccessControlAllowMethods,
+            "accept-ranges" => Self::AcceptRanges,
             "age" => Self::Age,
             "allow" => Self::Allow,
             "content-disposition" => Self::ContentDisposition,
             "content-language" => Self::ContentLanguage,
             "content-location" => Self::ContentLocation,
+            "content-range" => Self::ContentRange,
             "etag" => Self::ETag,
             "expires" => Self::Expires,
             "last-modified" => Self::LastModified,
             "link" => Self::Link,
             "location" => Self::Location,
+            "retry-after" => Self::RetryAfter,
             "server" => Self::Server,
             "set-cookie" => Self::SetCookie,
             "transfer-encoding" => Self::TransferEncoding,
@@ -318,9 +357,17 @@ impl ToString for HeaderType {
             HeaderType::Forwarded => "Forwarded",
             HeaderType::From => "From",
             HeaderType::Host => "Host",
+            HeaderType::IfMatch => "If-Match",
+            HeaderType::IfModifiedSince => "If-Modified-Since",
+            HeaderType::IfNoneMatch => "If-None-Match",
+            HeaderType::IfRange => "If-Range",
+            HeaderType::IfUnmodifiedSince => "If-Unmodified-Since",
+            HeaderType::KeepAlive => "Keep-Alive",
             HeaderType::Origin => "Origin",
             HeaderType::Pragma => "Pragma",
+            HeaderType::Range => "Range",
             HeaderType::Referer => "Referer",
+            HeaderType::Trailer => "Trailer",
             HeaderType::Upgrade => "Upgrade",
             HeaderType::UserAgent => "User-Agent",
             HeaderType::Via => "Via",
@@ -328,16 +375,19 @@ impl ToString for HeaderType {
             HeaderType::AccessControlAllowOrigin => "Access-Control-Allow-Origin",
             HeaderType::AccessControlAllowHeaders => "Access-Control-Allow-Headers",
             HeaderType::AccessControlAllowMethods => "Access-Control-Allow-Methods",
+            HeaderType::AcceptRanges => "Accept-Ranges",
             HeaderType::Age => "Age",
             HeaderType::Allow => "Allow",
             HeaderType::ContentDisposition => "Content-Disposition",
             HeaderType::ContentLanguage => "Content-Language",
             HeaderType::ContentLocation => "Content-Location",
+            HeaderType::ContentRange => "Content-Range",
             HeaderType::ETag => "ETag",
             HeaderType::Expires => "Expires",
             HeaderType::LastModified => "Last-Modified",
             HeaderType::Link => "Link",
             HeaderType::Location => "Location",
+            HeaderType::RetryAfter => "Retry-After",
             HeaderType::Server => "Server",
             HeaderType::SetCookie => "Set-Cookie",
             HeaderType::TransferEncoding => "Transfer-Encoding",
@@ -363,6 +413,7 @@ impl HeaderType {
             HeaderType::AccessControlAllowOrigin => HeaderCategory::Other,
             HeaderType::AccessControlAllowHeaders => HeaderCategory::Other,
             HeaderType::AccessControlAllowMethods => HeaderCategory::Other,
+            HeaderType::AcceptRanges => HeaderCategory::Response,
             HeaderType::Age => HeaderCategory::Response,
             HeaderType::Allow => HeaderCategory::Entity,
             HeaderType::CacheControl => HeaderCategory::General,
@@ -372,6 +423,7 @@ impl HeaderType {
             HeaderType::ContentLanguage => HeaderCategory::Entity,
             HeaderType::ContentLength => HeaderCategory::Entity,
             HeaderType::ContentLocation => HeaderCategory::Entity,
+            HeaderType::ContentRange => HeaderCategory::Entity,
             HeaderType::ContentType => HeaderCategory::Entity,
             HeaderType::Date => HeaderCategory::General,
             HeaderType::ETag => HeaderCategory::Response,
@@ -379,6 +431,7 @@ impl HeaderType {
             HeaderType::LastModified => HeaderCategory::Entity,
             HeaderType::Link => HeaderCategory::Other,
             HeaderType::Location => HeaderCategory::Response,
+            HeaderType::RetryAfter => HeaderCategory::Response,
             HeaderType::Pragma => HeaderCategory::General,
             HeaderType::Server => HeaderCategory::Response,
             HeaderType::SetCookie => HeaderCategory::Other,
@@ -398,8 +451,16 @@ impl HeaderType {
             HeaderType::Forwarded => HeaderCategory::Response,
             HeaderType::From => HeaderCategory::Response,
             HeaderType::Host => HeaderCategory::General,
+            HeaderType::IfMatch => HeaderCategory::General,
+            HeaderType::IfModifiedSince => HeaderCategory::General,
+            HeaderType::IfNoneMatch => HeaderCategory::General,
+            HeaderType::IfRange => HeaderCategory::General,
+            HeaderType::IfUnmodifiedSince => HeaderCategory::General,
+            HeaderType::KeepAlive => HeaderCategory::General,
+            HeaderType::Range => HeaderCategory::General,
             HeaderType::Origin => HeaderCategory::General,
             HeaderType::Referer => HeaderCategory::General,
+            HeaderType::Trailer => HeaderCategory::General,
             HeaderType::UserAgent => HeaderCategory::General,
             HeaderType::Custom(_) => HeaderCategory::Other,
         }
@@ -1,9 +1,14 @@
 //! Provides functionality for handling HTTP methods.
 
-use super::request::RequestError;
+use super::headers::HeaderType;
+use super::request::{Request, RequestError};
+use crate::percent::PercentDecode;
 
 use std::fmt::Display;
 
+/// The header from which `MethodOverride::Header` takes the method.
+pub const METHOD_OVERRIDE_HEADER: &str = "X-HTTP-Method-Override";
+
 /// Represents an HTTP method.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Method {
@@ -13,15 +18,35 @@ pub enum Method {
     Post,
     /// The `PUT` method.
     Put,
+    /// The `PATCH` method.
+    Patch,
     /// The `DELETE` method.
     Delete,
     /// The `OPTIONS` method.
     Options,
+    /// The `HEAD` method, which is identical to `GET` except that no body is sent in the response.
+    Head,
+    /// The `TRACE` method, which asks for the request to be echoed back.
+    ///
+    /// Only routes added specifically for this method handle it, since a route which handles every
+    ///   method could otherwise echo credentials from the request's headers.
+    Trace,
+    /// The `CONNECT` method, which asks for a tunnel to the host given as the request target.
+    ///
+    /// Only routes added specifically for this method handle it.
+    Connect,
+    /// Any other method, with its name exactly as it was sent.
+    ///
+    /// Method names are case-sensitive, so `get` is a custom method rather than `GET`.
+    Custom(String),
 }
 
 impl Method {
     /// Attempts to convert from the HTTP verb into an enum variant.
     ///
+    /// Names which are not standard methods are converted into `Method::Custom`, so this only fails
+    ///   if the name is not a valid token, for example if it is empty or contains a space.
+    ///
     /// ## Example
     /// ```
     /// let method = Method::from_name("GET");
@@ -32,11 +57,107 @@ impl Method {
             "GET" => Ok(Self::Get),
             "POST" => Ok(Self::Post),
             "PUT" => Ok(Self::Put),
+            "PATCH" => Ok(Self::Patch),
             "DELETE" => Ok(Self::Delete),
             "OPTIONS" => Ok(Self::Options),
+            "HEAD" => Ok(Self::Head),
+            "TRACE" => Ok(Self::Trace),
+            "CONNECT" => Ok(Self::Connect),
+            name if is_token(name) => Ok(Self::Custom(name.to_string())),
             _ => Err(RequestError::Request),
         }
     }
+
+    /// Returns whether requests with this method are only handled by routes added specifically for
+    ///   it, rather than by routes which handle every method.
+    ///
+    /// Requests with these methods which no route handles are answered with `501 Not Implemented`.
+    #[cfg(feature = "runtime")]
+    pub(crate) fn requires_declared_route(&self) -> bool {
+        matches!(self, Self::Trace | Self::Connect)
+    }
+}
+
+/// Returns whether the name is a valid token, as defined in RFC 9110 section 5.6.2.
+fn is_token(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte))
+}
+
+/// Determines where a `POST` request can specify another method to be handled as, for clients such as
+///   HTML forms which can only send `GET` and `POST` requests.
+///
+/// Only `POST` requests can be overridden, and only to `PUT`, `PATCH` or `DELETE`. Any other
+///   override is ignored, and the request is handled with the method it was sent with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MethodOverride {
+    /// The method is taken from the `X-HTTP-Method-Override` header.
+    Header,
+    /// The method is taken from the field with the given name, conventionally `_method`, of a request
+    ///   body encoded as `application/x-www-form-urlencoded`.
+    FormField(String),
+}
+
+impl MethodOverride {
+    /// The methods which a `POST` request can be overridden to.
+    pub const METHODS: &'static [Method] = &[Method::Put, Method::Patch, Method::Delete];
+
+    /// Returns the method which the request asks to be handled as, if it is a `POST` request asking
+    ///   for one of the methods it can be overridden to.
+    ///
+    /// ## Example
+    /// ```
+    /// let request = Request::builder()
+    ///     .method(Method::Post)
+    ///     .uri("/posts/1")
+    ///     .header("X-HTTP-Method-Override", "DELETE")
+    ///     .build();
+    ///
+    /// assert_eq!(MethodOverride::Header.requested(&request), Some(Method::Delete));
+    /// ```
+    pub fn requested(&self, request: &Request) -> Option<Method> {
+        if request.method != Method::Post {
+            return None;
+        }
+
+        let name = match self {
+            Self::Header => request.headers.get(METHOD_OVERRIDE_HEADER)?.to_string(),
+            Self::FormField(field) => form_field(request, field)?,
+        };
+
+        Method::from_name(&name.trim().to_ascii_uppercase())
+            .ok()
+            .filter(|method| Self::METHODS.contains(method))
+    }
+}
+
+/// Finds the value of the given field in a request body encoded as
+///   `application/x-www-form-urlencoded`.
+fn form_field(request: &Request, field: &str) -> Option<String> {
+    let content_type = request.headers.get(HeaderType::ContentType)?;
+    let mime_type = content_type.split(';').next()?.trim();
+
+    if !mime_type.eq_ignore_ascii_case("application/x-www-form-urlencoded") {
+        return None;
+    }
+
+    let body = std::str::from_utf8(request.content.as_ref()?).ok()?;
+
+    body.split('&').find_map(|pair| {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+
+        match decode_form_component(name)? == field {
+            true => decode_form_component(value),
+            false => None,
+        }
+    })
+}
+
+/// Decodes a name or value from a form body, where spaces may be encoded as `+`.
+fn decode_form_component(component: &str) -> Option<String> {
+    String::from_utf8(component.replace('+', " ").percent_decode()?).ok()
 }
 
 impl Display for Method {
@@ -48,8 +169,13 @@ impl Display for Method {
                 Method::Get => "GET",
                 Method::Post => "POST",
                 Method::Put => "PUT",
+                Method::Patch => "PATCH",
                 Method::Delete => "DELETE",
                 Method::Options => "OPTIONS",
+                Method::Head => "HEAD",
+                Method::Trace => "TRACE",
+                Method::Connect => "CONNECT",
+                Method::Custom(name) => name,
             }
         )
     }
@@ -1,7 +1,16 @@
 //! Provides functionality for handling MIME types.
+//!
+//! Extensions which are not built in can be mapped to MIME types with `MimeType::register`, which
+//!   affects every static file handler, including those of Humphrey Server.
+
+use std::sync::RwLock;
+
+/// The registered mappings from extensions to MIME types, which take precedence over the built-in
+///   mappings.
+static REGISTRY: RwLock<Vec<(String, &'static str)>> = RwLock::new(Vec::new());
 
 /// Represents a MIME type as used in the `Content-Type` header.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum MimeType {
     /// The `text/css` MIME type.
     TextCss,
@@ -11,6 +20,8 @@ pub enum MimeType {
     TextJavaScript,
     /// The `text/plain` MIME type.
     TextPlain,
+    /// The `text/csv` MIME type.
+    TextCsv,
     /// The `image/bmp` MIME type.
     ImageBmp,
     /// The `image/gif` MIME type.
@@ -25,6 +36,8 @@ pub enum MimeType {
     ImageSvg,
     /// The `image/vnd.microsoft.icon` MIME type.
     ImageIcon,
+    /// The `image/avif` MIME type.
+    ImageAvif,
     /// The `application/octet-stream` MIME type.
     ApplicationOctetStream,
     /// The `application/json` MIME type.
@@ -33,6 +46,18 @@ pub enum MimeType {
     ApplicationPdf,
     /// The `application/zip` MIME type.
     ApplicationZip,
+    /// The `application/xml` MIME type.
+    ApplicationXml,
+    /// The `application/wasm` MIME type.
+    ApplicationWasm,
+    /// The `application/manifest+json` MIME type, used for web app manifests.
+    ApplicationManifest,
+    /// The `audio/mpeg` MIME type.
+    AudioMpeg,
+    /// The `audio/ogg` MIME type.
+    AudioOgg,
+    /// The `audio/wav` MIME type.
+    AudioWav,
     /// The `video/mp4` MIME type.
     VideoMp4,
     /// The `video/ogg` MIME type.
@@ -47,12 +72,31 @@ pub enum MimeType {
     FontWoff,
     /// The `font/woff2` MIME type.
     FontWoff2,
+    /// The `model/gltf+json` MIME type.
+    ModelGltf,
+    /// The `model/gltf-binary` MIME type.
+    ModelGltfBinary,
+    /// A MIME type which is not built in, such as one added with `MimeType::register`.
+    Custom(&'static str),
 }
 
 impl MimeType {
     /// Converts from a file extension without the `.` to the enum variant.
     /// If the MIME type cannot be inferred from the extension, returns `MimeType::ApplicationOctetStream`.
+    ///
+    /// Extensions added with `MimeType::register` are checked before the built-in extensions.
     pub fn from_extension(extension: &str) -> Self {
+        let registered = REGISTRY
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(registered, _)| registered == extension)
+            .map(|(_, mime_type)| MimeType::Custom(mime_type));
+
+        if let Some(mime_type) = registered {
+            return mime_type;
+        }
+
         match extension {
             "css" => MimeType::TextCss,
             "html" => MimeType::TextHtml,
@@ -60,6 +104,7 @@ impl MimeType {
             "js" => MimeType::TextJavaScript,
             "mjs" => MimeType::TextJavaScript,
             "txt" => MimeType::TextPlain,
+            "csv" => MimeType::TextCsv,
             "bmp" => MimeType::ImageBmp,
             "gif" => MimeType::ImageGif,
             "jpeg" => MimeType::ImageJpeg,
@@ -68,9 +113,17 @@ impl MimeType {
             "webp" => MimeType::ImageWebp,
             "svg" => MimeType::ImageSvg,
             "ico" => MimeType::ImageIcon,
+            "avif" => MimeType::ImageAvif,
             "json" => MimeType::ApplicationJson,
             "pdf" => MimeType::ApplicationPdf,
             "zip" => MimeType::ApplicationZip,
+            "xml" => MimeType::ApplicationXml,
+            "wasm" => MimeType::ApplicationWasm,
+            "webmanifest" => MimeType::ApplicationManifest,
+            "mp3" => MimeType::AudioMpeg,
+            "oga" => MimeType::AudioOgg,
+            "ogg" => MimeType::AudioOgg,
+            "wav" => MimeType::AudioWav,
             "mp4" => MimeType::VideoMp4,
             "ogv" => MimeType::VideoOgg,
             "webm" => MimeType::VideoWebm,
@@ -78,9 +131,93 @@ impl MimeType {
             "otf" => MimeType::FontOtf,
             "woff" => MimeType::FontWoff,
             "woff2" => MimeType::FontWoff2,
+            "gltf" => MimeType::ModelGltf,
+            "glb" => MimeType::ModelGltfBinary,
             _ => MimeType::ApplicationOctetStream,
         }
     }
+
+    /// Maps the given extension, without the `.`, to the given MIME type for every subsequent call
+    ///   to `MimeType::from_extension`, replacing any previous mapping for the extension, including
+    ///   a built-in one.
+    ///
+    /// This is intended to be called when the program starts, since the MIME type is kept for the
+    ///   rest of the program.
+    ///
+    /// ## Example
+    /// ```
+    /// MimeType::register("usdz", "model/vnd.usdz+zip");
+    /// ```
+    pub fn register(extension: impl Into<String>, mime_type: impl Into<String>) {
+        let extension = extension.into();
+        let mime_type: &'static str = Box::leak(mime_type.into().into_boxed_str());
+        let mut registry = REGISTRY.write().unwrap();
+
+        match registry
+            .iter_mut()
+            .find(|(registered, _)| *registered == extension)
+        {
+            Some(entry) => entry.1 = mime_type,
+            None => registry.push((extension, mime_type)),
+        }
+    }
+
+    /// Returns `true` if the MIME type represents text, which is the case for `text/*` types, JSON
+    ///   and XML.
+    pub fn is_textual(&self) -> bool {
+        match self {
+            MimeType::Custom(mime_type) => {
+                mime_type.starts_with("text/")
+                    || mime_type.ends_with("/json")
+                    || mime_type.ends_with("+json")
+                    || mime_type.ends_with("/xml")
+                    || mime_type.ends_with("+xml")
+            }
+            _ => matches!(
+                self,
+                MimeType::TextCss
+                    | MimeType::TextHtml
+                    | MimeType::TextJavaScript
+                    | MimeType::TextPlain
+                    | MimeType::TextCsv
+                    | MimeType::ApplicationJson
+                    | MimeType::ApplicationXml
+                    | MimeType::ApplicationManifest
+                    | MimeType::ModelGltf
+            ),
+        }
+    }
+
+    /// Returns `true` if browsers may run scripts contained in content of this type, which is the
+    ///   case for HTML, SVG and other XML documents, and JavaScript.
+    pub fn is_executable(&self) -> bool {
+        match self {
+            MimeType::Custom(mime_type) => {
+                mime_type.starts_with("text/html")
+                    || mime_type.ends_with("/javascript")
+                    || mime_type.ends_with("/xml")
+                    || mime_type.ends_with("+xml")
+            }
+            _ => matches!(
+                self,
+                MimeType::TextHtml
+                    | MimeType::TextJavaScript
+                    | MimeType::ImageSvg
+                    | MimeType::ApplicationXml
+            ),
+        }
+    }
+
+    /// Returns the value of the `Content-Type` header for this MIME type, which has the
+    ///   `charset=utf-8` parameter if the type is textual so that clients do not have to guess the
+    ///   encoding, for example `text/html; charset=utf-8`.
+    pub fn to_content_type(&self) -> String {
+        if self.is_textual() {
+            format!("{}; charset=utf-8", self.to_string())
+        } else {
+            self.to_string()
+        }
+    }
 }
 
 impl From<MimeType> for String {
@@ -96,6 +233,7 @@ impl ToString for MimeType {
             MimeType::TextHtml => "text/html",
             MimeType::TextJavaScript => "text/javascript",
             MimeType::TextPlain => "text/plain",
+            MimeType::TextCsv => "text/csv",
             MimeType::ImageBmp => "image/bmp",
             MimeType::ImageGif => "image/gif",
             MimeType::ImageJpeg => "image/jpeg",
@@ -103,10 +241,17 @@ impl ToString for MimeType {
             MimeType::ImageWebp => "image/webp",
             MimeType::ImageSvg => "image/svg+xml",
             MimeType::ImageIcon => "image/vnd.microsoft.icon",
+            MimeType::ImageAvif => "image/avif",
             MimeType::ApplicationOctetStream => "application/octet-stream",
             MimeType::ApplicationJson => "application/json",
             MimeType::ApplicationPdf => "application/pdf",
             MimeType::ApplicationZip => "application/zip",
+            MimeType::ApplicationXml => "application/xml",
+            MimeType::ApplicationWasm => "application/wasm",
+            MimeType::ApplicationManifest => "application/manifest+json",
+            MimeType::AudioMpeg => "audio/mpeg",
+            MimeType::AudioOgg => "audio/ogg",
+            MimeType::AudioWav => "audio/wav",
             MimeType::VideoMp4 => "video/mp4",
             MimeType::VideoOgg => "video/ogg",
             MimeType::VideoWebm => "video/webm",
@@ -114,6 +259,9 @@ impl ToString for MimeType {
             MimeType::FontOtf => "font/otf",
             MimeType::FontWoff => "font/woff",
             MimeType::FontWoff2 => "font/woff2",
+            MimeType::ModelGltf => "model/gltf+json",
+            MimeType::ModelGltfBinary => "model/gltf-binary",
+            MimeType::Custom(mime_type) => mime_type,
         }
         .to_string()
     }
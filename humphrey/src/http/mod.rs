@@ -1,16 +1,25 @@
 //! Contains the Humphrey HTTP implementation.
 
 pub mod address;
+#[cfg(feature = "runtime")]
+pub mod body;
+pub mod conditional;
 pub mod cookie;
 pub mod cors;
 pub mod date;
+pub mod event_stream;
 pub mod headers;
 pub mod method;
 pub mod mime;
+pub mod multipart;
+pub mod params;
+#[cfg(feature = "runtime")]
 pub mod proxy;
+pub mod range;
 pub mod request;
 pub mod response;
 pub mod status;
+pub mod url;
 
 pub use request::Request;
 pub use response::Response;
@@ -0,0 +1,511 @@
+//! Provides a pull-based reader for `multipart/form-data` request bodies.
+//!
+//! Parts are read one at a time, and the content of each part is read through its own `Read`
+//!   implementation, so a large file can be copied to disk in pieces rather than being collected
+//!   into a `Vec` first. Limits on the size of fields, files and the whole body are enforced as the
+//!   content is read, and exceeding one returns a `MultipartError` describing it.
+//!
+//! The reader works over any `Read`. For a received request, `MultipartReader::from_request` reads
+//!   from the body in `request.content`, which is already held in memory, so the size of the whole
+//!   body should also be limited with `RequestLimits::max_body_size`.
+//!
+//! ## Example
+//! ```
+//! let limits = MultipartLimits {
+//!     max_field_size: Some(4 * 1024),
+//!     ..Default::default()
+//! };
+//! let mut multipart = MultipartReader::from_request(&request, limits)?;
+//!
+//! while let Some(mut part) = multipart.next_part()? {
+//!     match part.filename.clone() {
+//!         Some(filename) => {
+//!             std::io::copy(&mut part, &mut File::create(filename)?)?;
+//!         }
+//!         None => {
+//!             let mut value = String::new();
+//!             part.read_to_string(&mut value)?;
+//!         }
+//!     }
+//! }
+//! ```
+
+use crate::http::headers::{HeaderType, Headers};
+use crate::http::Request;
+
+use std::error::Error;
+use std::fmt::Display;
+use std::io::Read;
+
+/// The number of bytes requested from the underlying reader at once.
+const READ_SIZE: usize = 8 * 1024;
+
+/// The maximum total size of the headers of one part, in bytes.
+const MAX_PART_HEADER_SIZE: usize = 8 * 1024;
+
+/// Limits on the size of the content of a multipart body, beyond which reading fails.
+///
+/// `None` leaves the corresponding size unlimited, which is the default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MultipartLimits {
+    /// The maximum size of the content of a part without a filename, such as a text field, in bytes.
+    pub max_field_size: Option<usize>,
+    /// The maximum size of the content of a part with a filename, in bytes.
+    pub max_file_size: Option<usize>,
+    /// The maximum total size of the content of all parts, in bytes.
+    pub max_total_size: Option<usize>,
+}
+
+/// An error which occurred while reading a multipart body.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MultipartError {
+    /// The request does not have a `multipart/form-data` content type with a boundary.
+    NotMultipart,
+    /// The body is not valid multipart data, or it ended before the closing boundary.
+    Malformed,
+    /// The content of a part was larger than the limit for its kind of part.
+    PartTooLarge {
+        /// The name of the part.
+        name: String,
+        /// The limit which was exceeded, in bytes.
+        limit: usize,
+    },
+    /// The total content of all parts was larger than the limit.
+    TotalTooLarge {
+        /// The limit which was exceeded, in bytes.
+        limit: usize,
+    },
+    /// The next part was requested before the content of the previous part was read to the end or
+    ///   skipped.
+    PartNotFinished,
+    /// The underlying reader failed with an error of the given kind.
+    Io(std::io::ErrorKind),
+}
+
+/// A pull-based reader of the parts of a `multipart/form-data` body.
+pub struct MultipartReader<R> {
+    reader: R,
+    delimiter: Vec<u8>,
+    buf: Vec<u8>,
+    pos: usize,
+    eof: bool,
+    limits: MultipartLimits,
+    total: usize,
+    state: State,
+}
+
+/// One part of a multipart body, whose content can be read with its `Read` implementation.
+///
+/// The content must be read to the end, or skipped with `skip`, before the next part can be read.
+pub struct Part<'a, R> {
+    /// The name of the form field, from the `Content-Disposition` header.
+    pub name: String,
+    /// The filename of the uploaded file, if the part is a file.
+    pub filename: Option<String>,
+    /// The content type of the part, if given.
+    pub content_type: Option<String>,
+    /// All the headers of the part.
+    pub headers: Headers,
+    multipart: &'a mut MultipartReader<R>,
+}
+
+/// The position of the reader within the body.
+enum State {
+    /// Before the first boundary.
+    Start,
+    /// At the start of a delimiter, which is either followed by another part or ends the body.
+    Boundary,
+    /// Within the content of a part.
+    Content {
+        name: String,
+        limit: Option<usize>,
+        read: usize,
+    },
+    /// After the closing boundary.
+    Finished,
+    /// Reading failed with the given error, which is returned from then on.
+    Failed(MultipartError),
+}
+
+impl<'a> MultipartReader<&'a [u8]> {
+    /// Creates a reader of the body of the request, using the boundary from its `Content-Type`
+    ///   header.
+    pub fn from_request(
+        request: &'a Request,
+        limits: MultipartLimits,
+    ) -> Result<Self, MultipartError> {
+        let boundary = request
+            .headers
+            .get(HeaderType::ContentType)
+            .and_then(parse_boundary)
+            .ok_or(MultipartError::NotMultipart)?;
+
+        Ok(Self::new(
+            request.content.as_deref().unwrap_or_default(),
+            boundary,
+            limits,
+        ))
+    }
+}
+
+impl<R> MultipartReader<R>
+where
+    R: Read,
+{
+    /// Creates a reader of the multipart body read from `reader`, whose parts are separated by the
+    ///   given boundary.
+    pub fn new(reader: R, boundary: impl AsRef<str>, limits: MultipartLimits) -> Self {
+        let mut delimiter = b"\r\n--".to_vec();
+        delimiter.extend(boundary.as_ref().as_bytes());
+
+        Self {
+            reader,
+            delimiter,
+            // The first boundary is not preceded by a line break, so one is added to find it in
+            //   the same way as the others
+            buf: b"\r\n".to_vec(),
+            pos: 0,
+            eof: false,
+            limits,
+            total: 0,
+            state: State::Start,
+        }
+    }
+
+    /// Returns the next part of the body, or `None` if there are no more parts.
+    ///
+    /// Returns `MultipartError::PartNotFinished` if the content of the previous part has not been
+    ///   read to the end or skipped.
+    pub fn next_part(&mut self) -> Result<Option<Part<'_, R>>, MultipartError> {
+        match &self.state {
+            State::Start => self.skip_preamble()?,
+            State::Boundary => (),
+            State::Content { .. } => return Err(MultipartError::PartNotFinished),
+            State::Finished => return Ok(None),
+            State::Failed(e) => return Err(e.clone()),
+        }
+
+        // Consume the delimiter, which is followed by `--` if it is the closing boundary
+        self.fill_to(self.delimiter.len() + 2)?;
+        self.pos += self.delimiter.len();
+
+        if self.buf[self.pos..].starts_with(b"--") {
+            self.state = State::Finished;
+            return Ok(None);
+        }
+
+        let padding = self.read_line()?;
+        if !padding.iter().all(|b| *b == b' ' || *b == b'\t') {
+            return Err(self.fail(MultipartError::Malformed));
+        }
+
+        let mut headers = Headers::new();
+        let mut header_size = 0;
+
+        loop {
+            let line = self.read_line()?;
+            header_size += line.len() + 2;
+
+            if line.is_empty() {
+                break;
+            } else if header_size > MAX_PART_HEADER_SIZE {
+                return Err(self.fail(MultipartError::Malformed));
+            }
+
+            let line =
+                std::str::from_utf8(&line).map_err(|_| self.fail(MultipartError::Malformed))?;
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| self.fail(MultipartError::Malformed))?;
+
+            headers.add(HeaderType::from(name), value.trim());
+        }
+
+        let disposition = headers.get("Content-Disposition").unwrap_or_default();
+        let name = disposition_parameter(disposition, "name")
+            .ok_or_else(|| self.fail(MultipartError::Malformed))?;
+        let filename = disposition_parameter(disposition, "filename");
+        let content_type = headers.get(HeaderType::ContentType).map(|t| t.to_string());
+
+        let limit = match filename {
+            Some(_) => self.limits.max_file_size,
+            None => self.limits.max_field_size,
+        };
+
+        self.state = State::Content {
+            name: name.clone(),
+            limit,
+            read: 0,
+        };
+
+        Ok(Some(Part {
+            name,
+            filename,
+            content_type,
+            headers,
+            multipart: self,
+        }))
+    }
+
+    /// Skips the rest of the content of the current part, if any, returning the number of bytes
+    ///   skipped.
+    ///
+    /// The skipped content still counts towards the limits.
+    pub fn skip_part(&mut self) -> Result<u64, MultipartError> {
+        let mut skipped = 0;
+        let mut buf = [0; READ_SIZE];
+
+        loop {
+            match self.read_content(&mut buf)? {
+                0 => return Ok(skipped),
+                n => skipped += n as u64,
+            }
+        }
+    }
+
+    /// Reads content of the current part into `out`, returning zero at the end of the part.
+    fn read_content(&mut self, out: &mut [u8]) -> Result<usize, MultipartError> {
+        match &self.state {
+            State::Content { .. } => (),
+            State::Failed(e) => return Err(e.clone()),
+            _ => return Ok(0),
+        }
+
+        if out.is_empty() {
+            return Ok(0);
+        }
+
+        let available = loop {
+            let remaining = &self.buf[self.pos..];
+
+            // Content can be returned up to the delimiter, or up to where it could begin
+            match find(remaining, &self.delimiter) {
+                Some(0) => {
+                    self.state = State::Boundary;
+                    return Ok(0);
+                }
+                Some(index) => break index,
+                None if remaining.len() >= self.delimiter.len() => {
+                    break remaining.len() + 1 - self.delimiter.len()
+                }
+                None if self.eof => return Err(self.fail(MultipartError::Malformed)),
+                None => self.fill()?,
+            }
+        };
+
+        let length = available.min(out.len());
+        self.count(length)?;
+
+        out[..length].copy_from_slice(&self.buf[self.pos..self.pos + length]);
+        self.pos += length;
+
+        Ok(length)
+    }
+
+    /// Counts the given number of bytes of content towards the limits.
+    fn count(&mut self, length: usize) -> Result<(), MultipartError> {
+        let error = match &mut self.state {
+            State::Content { name, limit, read } => {
+                *read += length;
+                self.total += length;
+
+                match (*limit, self.limits.max_total_size) {
+                    (Some(limit), _) if *read > limit => Some(MultipartError::PartTooLarge {
+                        name: name.clone(),
+                        limit,
+                    }),
+                    (_, Some(limit)) if self.total > limit => {
+                        Some(MultipartError::TotalTooLarge { limit })
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+
+        match error {
+            Some(error) => Err(self.fail(error)),
+            None => Ok(()),
+        }
+    }
+
+    /// Discards everything before the first boundary.
+    fn skip_preamble(&mut self) -> Result<(), MultipartError> {
+        loop {
+            if let Some(index) = find(&self.buf[self.pos..], &self.delimiter) {
+                self.pos += index;
+                self.state = State::Boundary;
+                return Ok(());
+            }
+
+            if self.eof {
+                return Err(self.fail(MultipartError::Malformed));
+            }
+
+            // Keep only what could be the start of the delimiter
+            self.pos = self
+                .buf
+                .len()
+                .saturating_sub(self.delimiter.len() - 1)
+                .max(self.pos);
+            self.fill()?;
+        }
+    }
+
+    /// Reads a line ending in CRLF from the buffer, returning it without the CRLF.
+    fn read_line(&mut self) -> Result<Vec<u8>, MultipartError> {
+        loop {
+            if let Some(index) = find(&self.buf[self.pos..], b"\r\n") {
+                let line = self.buf[self.pos..self.pos + index].to_vec();
+                self.pos += index + 2;
+                return Ok(line);
+            }
+
+            if self.eof || self.buf.len() - self.pos > MAX_PART_HEADER_SIZE {
+                return Err(self.fail(MultipartError::Malformed));
+            }
+
+            self.fill()?;
+        }
+    }
+
+    /// Reads until at least `length` bytes are buffered or the reader ends.
+    fn fill_to(&mut self, length: usize) -> Result<(), MultipartError> {
+        while self.buf.len() - self.pos < length {
+            if self.eof {
+                return Err(self.fail(MultipartError::Malformed));
+            }
+
+            self.fill()?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads more data from the underlying reader into the buffer, discarding data which has
+    ///   already been consumed.
+    fn fill(&mut self) -> Result<(), MultipartError> {
+        if self.pos > 0 {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+        }
+
+        let start = self.buf.len();
+        self.buf.resize(start + READ_SIZE, 0);
+
+        let result = loop {
+            match self.reader.read(&mut self.buf[start..]) {
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                result => break result,
+            }
+        };
+
+        match result {
+            Ok(length) => {
+                self.buf.truncate(start + length);
+                self.eof = length == 0;
+                Ok(())
+            }
+            Err(e) => {
+                self.buf.truncate(start);
+                Err(self.fail(MultipartError::Io(e.kind())))
+            }
+        }
+    }
+
+    /// Stops reading because of the error, returning it.
+    fn fail(&mut self, error: MultipartError) -> MultipartError {
+        self.state = State::Failed(error.clone());
+        error
+    }
+}
+
+impl<R> Part<'_, R>
+where
+    R: Read,
+{
+    /// Skips the rest of the content of this part, returning the number of bytes skipped.
+    pub fn skip(self) -> Result<u64, MultipartError> {
+        self.multipart.skip_part()
+    }
+}
+
+impl<R> Read for Part<'_, R>
+where
+    R: Read,
+{
+    /// Reads the content of the part.
+    ///
+    /// If a limit is exceeded or the body is malformed, an error of kind `InvalidData` is returned
+    ///   which contains the `MultipartError`.
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.multipart.read_content(buf).map_err(|e| match e {
+            MultipartError::Io(kind) => kind.into(),
+            e => std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+        })
+    }
+}
+
+impl Display for MultipartError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MultipartError::NotMultipart => write!(f, "Multipart error: the body is not multipart"),
+            MultipartError::Malformed => write!(f, "Multipart error: the body was malformed"),
+            MultipartError::PartTooLarge { name, limit } => write!(
+                f,
+                "Multipart error: the part `{}` was larger than {} bytes",
+                name, limit
+            ),
+            MultipartError::TotalTooLarge { limit } => write!(
+                f,
+                "Multipart error: the parts were larger than {} bytes in total",
+                limit
+            ),
+            MultipartError::PartNotFinished => write!(
+                f,
+                "Multipart error: the previous part was not read to the end"
+            ),
+            MultipartError::Io(kind) => {
+                write!(f, "Multipart error: {}", std::io::Error::from(*kind))
+            }
+        }
+    }
+}
+
+impl Error for MultipartError {}
+
+/// Parses the boundary from a `multipart/form-data` content type.
+fn parse_boundary(content_type: &str) -> Option<String> {
+    let mut parameters = content_type.split(';');
+
+    if !parameters
+        .next()?
+        .trim()
+        .eq_ignore_ascii_case("multipart/form-data")
+    {
+        return None;
+    }
+
+    parameters
+        .filter_map(|parameter| parameter.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("boundary"))
+        .map(|(_, value)| value.trim().trim_matches('"').to_string())
+        .filter(|boundary| !boundary.is_empty() && boundary.len() <= 70)
+}
+
+/// Returns the value of the parameter with the given name from a `Content-Disposition` header.
+fn disposition_parameter(disposition: &str, name: &str) -> Option<String> {
+    disposition
+        .split(';')
+        .skip(1)
+        .filter_map(|parameter| parameter.split_once('='))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim().trim_matches('"').to_string())
+}
+
+/// Returns the index of the first occurrence of `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
@@ -0,0 +1,193 @@
+//! Provides functionality for capturing the parameters of routes.
+
+use crate::krauss::wildcard_captures;
+
+use std::str::FromStr;
+
+/// The values of the `:param` segments of the route which matched a request, as found in
+///   `request.params`.
+///
+/// A parameter matches exactly one non-empty segment of the path, so `/user/:id` matches `/user/42`
+///   but not `/user/42/posts` or `/user/`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RouteParams {
+    params: Vec<(String, String)>,
+}
+
+/// A part of a route, which is either matched literally, by a wildcard or by a parameter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Segment<'a> {
+    Literal(&'a str),
+    Wildcard,
+    Param(&'a str),
+}
+
+impl RouteParams {
+    /// Captures the parameters of the route from the path, which are empty if the route has no
+    ///   parameters or does not match the path.
+    pub fn from_route(route: &str, path: &str) -> Self {
+        let params = route_captures(route, path)
+            .map(|(_, params)| params)
+            .unwrap_or_default();
+
+        Self {
+            params: params
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Returns the value of the parameter with the given name, if the route has one.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Parses the value of the parameter with the given name, returning `None` if the route has no
+    ///   such parameter or its value cannot be parsed as the type.
+    ///
+    /// ## Example
+    /// ```
+    /// let id: u64 = match request.params.parse("id") {
+    ///     Some(id) => id,
+    ///     None => return Response::empty(StatusCode::NotFound),
+    /// };
+    /// ```
+    pub fn parse<T>(&self, name: &str) -> Option<T>
+    where
+        T: FromStr,
+    {
+        self.get(name).and_then(|value| value.parse().ok())
+    }
+
+    /// Returns an iterator over the names and values of the parameters, in the order they appear in
+    ///   the route.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.params
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Returns whether there are no parameters.
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+}
+
+/// Returns whether the route has any `:param` segments.
+pub(crate) fn has_params(route: &str) -> bool {
+    route.starts_with(':') || route.contains("/:")
+}
+
+/// Splits a route into its literal parts, wildcards and parameters.
+///
+/// A parameter is a segment of the route starting with a colon, and its name continues to the end
+///   of the segment. Colons elsewhere are matched literally.
+pub(crate) fn segments(route: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut rest = route;
+
+    while !rest.is_empty() {
+        let segment_start =
+            rest.len() == route.len() || route[..route.len() - rest.len()].ends_with('/');
+
+        if let Some(after) = rest.strip_prefix('*') {
+            segments.push(Segment::Wildcard);
+            rest = after;
+        } else if let (true, Some(after)) = (segment_start, rest.strip_prefix(':')) {
+            let end = after.find('/').unwrap_or(after.len());
+            segments.push(Segment::Param(&after[..end]));
+            rest = &after[end..];
+        } else {
+            // A literal part continues until the next wildcard or parameter
+            let end = rest
+                .char_indices()
+                .skip(1)
+                .find(|&(i, c)| c == '*' || (c == ':' && rest[..i].ends_with('/')))
+                .map_or(rest.len(), |(i, _)| i);
+            segments.push(Segment::Literal(&rest[..end]));
+            rest = &rest[end..];
+        }
+    }
+
+    segments
+}
+
+/// The parts of a path matched by each wildcard of a route, and the names and values of its parameters.
+type Captures<'a> = (Vec<&'a str>, Vec<(&'a str, &'a str)>);
+
+/// Returns the parts of the path matched by each wildcard of the route, and the names and values of
+///   its parameters, or `None` if the route does not match the path.
+pub(crate) fn route_captures<'a>(route: &'a str, path: &'a str) -> Option<Captures<'a>> {
+    if !has_params(route) {
+        return wildcard_captures(route, path).map(|wildcards| (wildcards, Vec::new()));
+    }
+
+    let mut wildcards = Vec::new();
+    let mut params = Vec::new();
+
+    match captures_from(&segments(route), path, &mut wildcards, &mut params) {
+        true => Some((wildcards, params)),
+        false => None,
+    }
+}
+
+/// Matches the path against the segments of a route, pushing the parts matched by each wildcard and
+///   parameter.
+///
+/// Each wildcard matches as few characters as possible, as with routes without parameters.
+fn captures_from<'a>(
+    segments: &[Segment<'a>],
+    path: &'a str,
+    wildcards: &mut Vec<&'a str>,
+    params: &mut Vec<(&'a str, &'a str)>,
+) -> bool {
+    let (segment, rest) = match segments.split_first() {
+        Some(split) => split,
+        None => return path.is_empty(),
+    };
+
+    match *segment {
+        Segment::Literal(literal) => match path.strip_prefix(literal) {
+            Some(path) => captures_from(rest, path, wildcards, params),
+            None => false,
+        },
+        Segment::Param(name) => {
+            let end = path.find('/').unwrap_or(path.len());
+
+            if end == 0 {
+                return false;
+            }
+
+            params.push((name, &path[..end]));
+
+            if captures_from(rest, &path[end..], wildcards, params) {
+                return true;
+            }
+
+            params.pop();
+            false
+        }
+        Segment::Wildcard => {
+            let ends = path
+                .char_indices()
+                .map(|(i, _)| i)
+                .chain(std::iter::once(path.len()));
+
+            for end in ends {
+                wildcards.push(&path[..end]);
+
+                if captures_from(rest, &path[end..], wildcards, params) {
+                    return true;
+                }
+
+                wildcards.pop();
+            }
+
+            false
+        }
+    }
+}
@@ -3,27 +3,74 @@
 use crate::http::response::ResponseError;
 use crate::http::{Request, Response, StatusCode};
 
-use std::io::Write;
+use std::error::Error;
+use std::fmt::Display;
+use std::io::{ErrorKind, Read, Write};
 use std::net::{SocketAddr, TcpStream};
 use std::time::Duration;
 
+/// Represents an error which occurred while proxying a request, describing how far the exchange
+///   got so that callers can decide whether it is safe to retry.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ProxyError {
+    /// The connection to the target could not be established, so none of the request was sent.
+    Connect,
+    /// The connection to the target timed out, so none of the request was sent.
+    Timeout,
+    /// The connection was closed or reset after some of the request was sent but before any of the
+    ///   response was received.
+    Reset,
+    /// Some of the response was received, but it was incomplete or malformed.
+    Response,
+}
+
+impl Display for ProxyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ProxyError::Connect => write!(f, "connection failed"),
+            ProxyError::Timeout => write!(f, "connection timed out"),
+            ProxyError::Reset => write!(f, "connection reset"),
+            ProxyError::Response => write!(f, "invalid response"),
+        }
+    }
+}
+
+impl Error for ProxyError {}
+
+impl From<ProxyError> for Response {
+    /// Creates the error response sent to the client when proxying fails, which is 504 Gateway
+    ///   Timeout if the connection timed out and 502 Bad Gateway otherwise.
+    fn from(error: ProxyError) -> Self {
+        match error {
+            ProxyError::Timeout => Response::empty(StatusCode::GatewayTimeout)
+                .with_bytes(b"<html><body><h1>504 Gateway Timeout</h1></body></html>"),
+            _ => Response::empty(StatusCode::BadGateway)
+                .with_bytes(b"<html><body><h1>502 Bad Gateway</h1></body></html>"),
+        }
+    }
+}
+
 /// Proxies a request to the given target, timing out and returning an error 502 after `timeout`.
 /// Always returns a response.
 pub fn proxy_request(request: &Request, target: SocketAddr, timeout: Duration) -> Response {
-    match proxy_request_internal(request, target, timeout) {
+    match try_proxy_request(request, target, timeout) {
         Ok(response) => response,
         Err(_) => Response::empty(StatusCode::BadGateway)
             .with_bytes(b"<html><body><h1>502 Bad Gateway</h1></body></html>"),
     }
 }
 
-fn proxy_request_internal(
+/// Proxies a request to the given target, returning an error describing how far the exchange got
+///   if it fails. Connecting times out after `timeout`.
+pub fn try_proxy_request(
     request: &Request,
     target: SocketAddr,
     timeout: Duration,
-) -> Result<Response, ResponseError> {
-    let mut stream =
-        TcpStream::connect_timeout(&target, timeout).map_err(|_| ResponseError::Stream)?;
+) -> Result<Response, ProxyError> {
+    let mut stream = TcpStream::connect_timeout(&target, timeout).map_err(|e| match e.kind() {
+        ErrorKind::TimedOut | ErrorKind::WouldBlock => ProxyError::Timeout,
+        _ => ProxyError::Connect,
+    })?;
 
     let mut cloned_request = request.clone();
     cloned_request
@@ -32,7 +79,30 @@ fn proxy_request_internal(
     let request_bytes: Vec<u8> = cloned_request.into();
     stream
         .write_all(&request_bytes)
-        .map_err(|_| ResponseError::Stream)?;
+        .map_err(|_| ProxyError::Reset)?;
+
+    let mut counting_stream = CountingReader {
+        inner: stream,
+        count: 0,
+    };
+
+    Response::from_stream_for(&mut counting_stream, &request.method).map_err(|e| match e {
+        ResponseError::Stream if counting_stream.count == 0 => ProxyError::Reset,
+        _ => ProxyError::Response,
+    })
+}
 
-    Response::from_stream(&mut stream)
+/// Counts the bytes read from the inner reader, so that a connection which was closed before the
+///   response started can be told apart from one which was closed part of the way through it.
+struct CountingReader<T> {
+    inner: T,
+    count: usize,
+}
+
+impl<T: Read> Read for CountingReader<T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let bytes = self.inner.read(buf)?;
+        self.count += bytes;
+        Ok(bytes)
+    }
 }
@@ -0,0 +1,241 @@
+//! Provides support for range requests according to [RFC 7233](https://datatracker.ietf.org/doc/html/rfc7233).
+//!
+//! A handler serving a resource of a known length can parse the `Range` header of the request,
+//!   resolve it against the length, and reply with either a `206 Partial Content` response or a
+//!   `416 Requested Range Not Satisfiable` response.
+//!
+//! ## Example
+//! ```
+//! fn handler(request: Request) -> Response {
+//!     let body = std::fs::read("video.mp4").unwrap();
+//!
+//!     let range = match request.headers.get(HeaderType::Range).and_then(Range::parse) {
+//!         Some(range) => range,
+//!         None => return Response::new(StatusCode::OK, body),
+//!     };
+//!
+//!     match range.resolve(body.len() as u64) {
+//!         Ok(ranges) => Response::partial_content(ranges[0], ranges[0].slice(&body)),
+//!         Err(e) => e.into(),
+//!     }
+//! }
+//! ```
+
+use crate::http::headers::HeaderType;
+use crate::http::response::Response;
+use crate::http::status::StatusCode;
+
+use std::error::Error;
+use std::fmt::Display;
+
+/// Represents the value of a `Range` header, which requests one or more parts of a resource.
+///
+/// Only the `bytes` unit is supported.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Range {
+    /// The requested ranges, in the order in which they were given.
+    pub ranges: Vec<ByteRange>,
+}
+
+/// Represents a single range of bytes requested in a `Range` header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ByteRange {
+    /// The bytes between the two positions inclusive, as in `bytes=0-1023`.
+    Bounded(u64, u64),
+    /// The bytes from the given position to the end of the resource, as in `bytes=500-`.
+    From(u64),
+    /// The given number of bytes at the end of the resource, as in `bytes=-500`.
+    Suffix(u64),
+}
+
+/// Represents a range of bytes within a resource of a known length, as sent in the `Content-Range`
+///   header of a partial response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ContentRange {
+    /// The position of the first byte in the range.
+    pub start: u64,
+    /// The position of the last byte in the range, which is included in the range.
+    pub end: u64,
+    /// The total length of the resource.
+    pub length: u64,
+}
+
+/// An error returned when none of the requested ranges overlap the resource.
+///
+/// Converts into a `416 Requested Range Not Satisfiable` response with a `Content-Range` header
+///   giving the length of the resource, as in `Content-Range: bytes */1024`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RangeNotSatisfiable {
+    /// The total length of the resource.
+    pub length: u64,
+}
+
+impl Range {
+    /// Creates a new range header value with the given ranges.
+    pub fn new(ranges: impl AsRef<[ByteRange]>) -> Self {
+        Self {
+            ranges: ranges.as_ref().to_vec(),
+        }
+    }
+
+    /// Parses the value of a `Range` header, such as `bytes=0-1023`, `bytes=500-`, `bytes=-500` or
+    ///   a comma-separated list of these.
+    ///
+    /// Returns `None` if the value uses a unit other than `bytes` or any of the ranges is malformed,
+    ///   in which case the specification requires the header to be ignored and the whole resource
+    ///   to be sent.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (unit, ranges) = value.split_once('=')?;
+
+        if !unit.trim().eq_ignore_ascii_case("bytes") {
+            return None;
+        }
+
+        let ranges = ranges
+            .split(',')
+            .map(|range| range.trim())
+            .filter(|range| !range.is_empty())
+            .map(ByteRange::parse)
+            .collect::<Option<Vec<ByteRange>>>()?;
+
+        if ranges.is_empty() {
+            None
+        } else {
+            Some(Self { ranges })
+        }
+    }
+
+    /// Resolves the requested ranges against a resource of the given length.
+    ///
+    /// Ranges which start beyond the end of the resource are ignored, and ranges which extend beyond
+    ///   it are shortened. The remaining ranges are sorted, and any which overlap or are adjacent
+    ///   are merged, so a client cannot cause the same bytes to be sent many times over.
+    ///
+    /// Returns an error if none of the ranges overlap the resource.
+    pub fn resolve(&self, length: u64) -> Result<Vec<ContentRange>, RangeNotSatisfiable> {
+        let mut resolved: Vec<ContentRange> = self
+            .ranges
+            .iter()
+            .filter_map(|range| range.resolve(length))
+            .collect();
+
+        resolved.sort_by_key(|range| range.start);
+
+        let mut merged: Vec<ContentRange> = Vec::with_capacity(resolved.len());
+
+        for range in resolved {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end + 1 => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+
+        if merged.is_empty() {
+            Err(RangeNotSatisfiable { length })
+        } else {
+            Ok(merged)
+        }
+    }
+}
+
+impl ByteRange {
+    /// Parses a single range, without the `bytes=` prefix.
+    fn parse(range: &str) -> Option<Self> {
+        let (first, last) = range.split_once('-')?;
+
+        match (first.trim(), last.trim()) {
+            ("", last) => Some(Self::Suffix(parse_position(last)?)),
+            (first, "") => Some(Self::From(parse_position(first)?)),
+            (first, last) => {
+                let first = parse_position(first)?;
+                let last = parse_position(last)?;
+
+                (first <= last).then_some(Self::Bounded(first, last))
+            }
+        }
+    }
+
+    /// Resolves the range against a resource of the given length, returning `None` if it does not
+    ///   overlap the resource.
+    fn resolve(&self, length: u64) -> Option<ContentRange> {
+        let (start, end) = match *self {
+            Self::Bounded(first, last) => (first, last.min(length.checked_sub(1)?)),
+            Self::From(first) => (first, length.checked_sub(1)?),
+            Self::Suffix(0) => return None,
+            Self::Suffix(suffix) => (length.saturating_sub(suffix), length.checked_sub(1)?),
+        };
+
+        (start < length).then_some(ContentRange { start, end, length })
+    }
+}
+
+impl ContentRange {
+    /// Returns the number of bytes in the range.
+    pub fn size(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    /// Returns the part of the given resource which is within the range.
+    ///
+    /// ## Panics
+    /// Panics if the resource is shorter than the end of the range.
+    pub fn slice<'a>(&self, resource: &'a [u8]) -> &'a [u8] {
+        &resource[self.start as usize..=self.end as usize]
+    }
+}
+
+impl Display for Range {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bytes=")?;
+
+        for (i, range) in self.ranges.iter().enumerate() {
+            if i != 0 {
+                write!(f, ",")?;
+            }
+
+            match range {
+                ByteRange::Bounded(first, last) => write!(f, "{}-{}", first, last)?,
+                ByteRange::From(first) => write!(f, "{}-", first)?,
+                ByteRange::Suffix(suffix) => write!(f, "-{}", suffix)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for ContentRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bytes {}-{}/{}", self.start, self.end, self.length)
+    }
+}
+
+impl Display for RangeNotSatisfiable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "None of the requested ranges overlap the resource of {} bytes",
+            self.length
+        )
+    }
+}
+
+impl Error for RangeNotSatisfiable {}
+
+impl From<RangeNotSatisfiable> for Response {
+    fn from(error: RangeNotSatisfiable) -> Self {
+        Response::empty(StatusCode::RequestedRangeNotSatisfiable).with_header(
+            HeaderType::ContentRange,
+            format!("bytes */{}", error.length),
+        )
+    }
+}
+
+/// Parses a byte position, which must consist only of digits.
+fn parse_position(position: &str) -> Option<u64> {
+    if !position.is_empty() && position.bytes().all(|b| b.is_ascii_digit()) {
+        position.parse().ok()
+    } else {
+        None
+    }
+}
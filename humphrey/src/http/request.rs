@@ -1,20 +1,36 @@
 //! Provides functionality for handling HTTP requests.
 
+use crate::cancellation::CancellationToken;
+use crate::clock;
 use crate::http::address::Address;
 use crate::http::cookie::Cookie;
-use crate::http::headers::{HeaderType, Headers};
+use crate::http::headers::{Header, HeaderLike, HeaderType, Headers};
 use crate::http::method::Method;
+use crate::http::params::RouteParams;
+use crate::http::response::is_permitted_trailer;
+use crate::http::status::StatusCode;
+use crate::http::url::{encode_path, Origin};
+use crate::percent::decode_path;
+use crate::trace::TraceContext;
 
 use std::error::Error;
-use std::net::SocketAddr;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
 
-#[cfg(not(feature = "tokio"))]
+#[cfg(feature = "runtime")]
+use crate::trace::TraceOptions;
+
+#[cfg(all(feature = "runtime", not(feature = "tokio")))]
+use crate::recording::TeeReader;
+#[cfg(all(feature = "runtime", not(feature = "tokio")))]
 use crate::stream::Stream;
 #[cfg(not(feature = "tokio"))]
 use std::io::{BufRead, BufReader, ErrorKind, Read};
-#[cfg(not(feature = "tokio"))]
-use std::time::Duration;
 
+#[cfg(feature = "tokio")]
+use std::future::Future;
+#[cfg(feature = "tokio")]
+use std::pin::Pin;
 #[cfg(feature = "tokio")]
 use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
 
@@ -24,8 +40,19 @@ use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
 pub struct Request {
     /// The method used in making the request, e.g. "GET".
     pub method: Method,
-    /// The URI to which the request was made.
+    /// The method the request was sent with, if it was replaced by the app's method override.
+    ///
+    /// Use `original_method` to get the method the request was sent with in either case.
+    pub overridden_method: Option<Method>,
+    /// The path to which the request was made, which is percent-decoded and used for routing.
+    ///
+    /// Encoded slashes (`%2F`) and percent signs (`%25`) are left encoded, so that an encoded slash
+    ///   is never treated as a path separator. See `percent::decode_path` for details.
     pub uri: String,
+    /// The path exactly as it was received, before any decoding or normalisation.
+    ///
+    /// This should be used for logging, but never for routing or access control.
+    pub uri_raw: String,
     /// The query string of the request.
     pub query: String,
     /// The HTTP version of the request.
@@ -34,12 +61,85 @@ pub struct Request {
     pub headers: Headers,
     /// The request body, if supplied.
     pub content: Option<Vec<u8>>,
+    /// Whether the whole request body was received.
+    ///
+    /// If the body is incomplete, `content` only contains the bytes which were received, so
+    ///   handlers which store uploads should check this before persisting anything.
+    pub body_state: BodyState,
+    /// The trailer fields which followed a chunked body.
+    ///
+    /// These are kept apart from `headers`, since they arrive after the request has been routed and
+    ///   checked, so they must never be trusted as if they were headers. Fields which could change
+    ///   how the message is framed, routed or authorised, such as `Host` or `Authorization`, are
+    ///   dropped.
+    pub trailers: Headers,
     /// The address from which the request came
     pub address: Address,
+    /// Whether the request was received over a secure (TLS) connection.
+    pub secure: bool,
+    /// The local address of the connection the request was received on, which identifies the
+    ///   listener that accepted it when the app is run on several endpoints.
+    pub local_addr: Option<SocketAddr>,
+    /// The time by which the request should have been responded to, if the app has a connection
+    ///   timeout or the request came from a trusted proxy which sent the `X-Request-Deadline` header.
+    ///
+    /// Handlers which call other services should pass this to `ClientRequest::with_deadline`, so
+    ///   that those calls give up once the response is no longer useful.
+    pub deadline: Option<Instant>,
+    /// The trace context of the request, if the app has trace propagation enabled.
+    ///
+    /// Handlers which call other services should pass this to `ClientRequest::with_trace_context`,
+    ///   so that those calls are part of the same trace.
+    pub trace: Option<TraceContext>,
+    /// The token which is signalled when the client which sent the request disconnects.
+    ///
+    /// Use `cancellation_token` to get a handle to it which can be moved to other threads.
+    pub cancellation: CancellationToken,
+    /// The values of the `:param` segments of the route which is handling the request, such as
+    ///   `id` for the route `/user/:id`.
+    ///
+    /// This is empty until the request is passed to the handler of a route.
+    pub params: RouteParams,
+}
+
+/// Builds a request without a stream, for example to test a handler.
+///
+/// ## Example
+/// ```
+/// let request = Request::builder()
+///     .method(Method::Post)
+///     .uri("/api/notes")
+///     .header(HeaderType::ContentType, "application/json")
+///     .body(r#"{"text": "Hello, world!"}"#)
+///     .build();
+///
+/// assert_eq!(add_note(request, state).status_code, StatusCode::Created);
+/// ```
+#[derive(Clone, Debug)]
+pub struct RequestBuilder {
+    request: Request,
+}
+
+/// Describes whether the whole body of a request was received.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BodyState {
+    /// The whole body was received, or the request had no body.
+    Complete,
+    /// The stream ended, failed or timed out before the declared length of the body was received.
+    Truncated {
+        /// The number of bytes of the body which were received.
+        received: usize,
+        /// The number of bytes of the body which were declared by the `Content-Length` header, or
+        ///   by the sizes of the chunks received so far if the body was chunked.
+        expected: usize,
+    },
+    /// The request has a body whose length was not declared and which was not chunked, so it was not
+    ///   read.
+    Unknown,
 }
 
 /// An error which occurred during the parsing of a request.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum RequestError {
     /// The request could not be parsed due to invalid data.
     Request,
@@ -49,6 +149,80 @@ pub enum RequestError {
     Disconnected,
     /// The request timed out.
     Timeout,
+    /// The request was rejected by the request condition after its head was parsed.
+    ///
+    /// The body of a rejected request is never buffered. If it was declared to be no larger than
+    ///   `REJECTED_BODY_DRAIN_LIMIT`, it is read and discarded so the connection can be reused.
+    Rejected,
+    /// Part of the request was larger than allowed by the request limits.
+    ///
+    /// The request is rejected as soon as the limit is exceeded, so an oversized body is never read.
+    TooLarge(RequestPart),
+}
+
+/// Represents a part of a request which can be limited in size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequestPart {
+    /// The URI, including the query string.
+    Uri,
+    /// The headers, including the line breaks between them.
+    Headers,
+    /// The body.
+    Body,
+}
+
+/// Limits on the size of the parts of a request, beyond which the request is rejected.
+///
+/// `None` leaves the corresponding part unlimited, which is the default for this type. Apps apply
+///   `DEFAULT_MAX_HEADER_SIZE` and `DEFAULT_MAX_BODY_SIZE` unless configured otherwise.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RequestLimits {
+    /// The maximum length of the URI, including the query string, in bytes.
+    pub max_uri_length: Option<usize>,
+    /// The maximum total size of the headers, in bytes.
+    pub max_header_size: Option<usize>,
+    /// The maximum declared length of the body, in bytes.
+    pub max_body_size: Option<usize>,
+}
+
+/// The maximum total size of the headers of a request which apps accept by default, in bytes.
+pub const DEFAULT_MAX_HEADER_SIZE: usize = 16 * 1024;
+
+/// The maximum size of a request body which apps accept by default, in bytes.
+pub const DEFAULT_MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
+
+/// The header in which the time remaining until the deadline of a request is sent, in milliseconds.
+pub const DEADLINE_HEADER: &str = "X-Request-Deadline";
+
+/// The number of bytes allowed in the request line in addition to the URI, to account for the
+///   method and version.
+const REQUEST_LINE_OVERHEAD: usize = 32;
+
+/// The maximum length of a chunk size line, including any chunk extensions, in bytes.
+pub(crate) const CHUNK_SIZE_LINE_LIMIT: usize = 1024;
+
+/// The largest declared body of a rejected request which will be drained from the stream, in bytes.
+///
+/// Rejected requests with larger bodies cause the connection to be closed instead.
+pub const REJECTED_BODY_DRAIN_LIMIT: usize = 8192;
+
+/// A condition which is checked against the head of a request before its body is read.
+/// The request passed to the condition always has no content.
+#[cfg(not(feature = "tokio"))]
+pub(crate) type HeadCondition<'a> = &'a mut dyn FnMut(&Request) -> bool;
+
+/// A condition which is checked against the head of a request before its body is read.
+/// The request passed to the condition always has no content.
+#[cfg(feature = "tokio")]
+pub(crate) type HeadCondition<'a> = &'a mut (dyn FnMut(&Request) -> bool + Send);
+
+/// Decides from the head of a request whether to leave its body unread, so that the handler can
+///   stream it from the connection instead.
+pub(crate) struct BodyDeferral<'a> {
+    /// Returns `true` if the body of the request with the given head should be left unread.
+    pub condition: HeadCondition<'a>,
+    /// The bytes which were read from the stream after the head, set if the body was left unread.
+    pub buffered: Option<Vec<u8>>,
 }
 
 trait OptionToRequestResult<T> {
@@ -63,13 +237,141 @@ impl<T> OptionToRequestResult<T> for Option<T> {
 
 impl std::fmt::Display for RequestError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "RequestError")
+        match self {
+            RequestError::Request => write!(f, "Request error: the request was malformed"),
+            RequestError::Stream => write!(f, "Request error: the stream could not be read"),
+            RequestError::Disconnected => write!(f, "Request error: the client disconnected"),
+            RequestError::Timeout => write!(f, "Request error: the request timed out"),
+            RequestError::Rejected => {
+                write!(
+                    f,
+                    "Request error: the request was rejected by the request condition"
+                )
+            }
+            RequestError::TooLarge(part) => {
+                write!(f, "Request error: the request {} was too large", part)
+            }
+        }
     }
 }
 
 impl Error for RequestError {}
 
+impl RequestPart {
+    /// Returns the status code with which a request is rejected when this part is too large.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            RequestPart::Uri => StatusCode::RequestURITooLong,
+            RequestPart::Headers => StatusCode::RequestHeaderFieldsTooLarge,
+            RequestPart::Body => StatusCode::RequestEntityTooLarge,
+        }
+    }
+}
+
+impl std::fmt::Display for RequestPart {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RequestPart::Uri => write!(f, "URI"),
+            RequestPart::Headers => write!(f, "headers"),
+            RequestPart::Body => write!(f, "body"),
+        }
+    }
+}
+
+impl RequestLimits {
+    /// Returns conservative limits suitable for most apps: 8 KiB for the URI, 16 KiB for the headers
+    ///   and 10 MiB for the body.
+    pub fn conservative() -> Self {
+        Self {
+            max_uri_length: Some(8 * 1024),
+            max_header_size: Some(DEFAULT_MAX_HEADER_SIZE),
+            max_body_size: Some(DEFAULT_MAX_BODY_SIZE),
+        }
+    }
+
+    /// Returns these limits, with any limit which is not set taken from `other`.
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            max_uri_length: self.max_uri_length.or(other.max_uri_length),
+            max_header_size: self.max_header_size.or(other.max_header_size),
+            max_body_size: self.max_body_size.or(other.max_body_size),
+        }
+    }
+
+    /// Checks the declared length of a body against the limit.
+    fn check_body(&self, content_length: Option<usize>) -> Result<(), RequestError> {
+        match (self.max_body_size, content_length) {
+            (Some(limit), Some(length)) if length > limit => {
+                Err(RequestError::TooLarge(RequestPart::Body))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
 impl Request {
+    /// Returns a builder for a `GET` request to `/` over HTTP/1.1 from `127.0.0.1`, which can be
+    ///   changed using the builder's methods.
+    pub fn builder() -> RequestBuilder {
+        RequestBuilder::new()
+    }
+
+    /// Returns the method the request was sent with, which differs from `method` if the request was
+    ///   handled as another method because of the app's method override.
+    ///
+    /// This should be used for logging, but `method` should be used for everything else.
+    pub fn original_method(&self) -> &Method {
+        self.overridden_method.as_ref().unwrap_or(&self.method)
+    }
+
+    /// Returns the time remaining until the deadline of the request, which is zero if it has passed,
+    ///   or `None` if the request has no deadline.
+    pub fn time_remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(clock::now_monotonic()))
+    }
+
+    /// Returns a handle to the token which is signalled when the client which sent the request
+    ///   disconnects, so that long-running handlers can stop working once nobody is waiting for the
+    ///   response.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+
+    /// Sets the deadline of the request to the given timeout from now, or to the deadline sent in
+    ///   the `X-Request-Deadline` header if that is earlier and the request was received directly
+    ///   from one of the trusted proxies.
+    #[cfg(feature = "runtime")]
+    pub(crate) fn set_deadline(&mut self, timeout: Option<Duration>, trusted_proxies: &[IpAddr]) {
+        let now = clock::now_monotonic();
+        let peer = self
+            .address
+            .proxies
+            .last()
+            .unwrap_or(&self.address.origin_addr);
+
+        let propagated = self
+            .headers
+            .get(DEADLINE_HEADER)
+            .filter(|_| trusted_proxies.contains(peer))
+            .and_then(|remaining| remaining.trim().parse::<u64>().ok())
+            .and_then(|remaining| now.checked_add(Duration::from_millis(remaining)));
+        let own = timeout.and_then(|timeout| now.checked_add(timeout));
+
+        self.deadline = match (own, propagated) {
+            (Some(own), Some(propagated)) => Some(own.min(propagated)),
+            (own, propagated) => own.or(propagated),
+        };
+    }
+
+    /// Sets the trace context of the request from its `traceparent` and `tracestate` headers if
+    ///   trace propagation is enabled, starting a new trace if they are missing or invalid.
+    #[cfg(feature = "runtime")]
+    pub(crate) fn set_trace(&mut self, options: Option<TraceOptions>) {
+        self.trace = options
+            .map(|options| TraceContext::from_headers(&self.headers, options.sample_new_traces));
+    }
+
     /// Attempts to read and parse one HTTP request from the given reader.
     #[cfg(not(feature = "tokio"))]
     pub fn from_stream<T>(stream: &mut T, address: SocketAddr) -> Result<Self, RequestError>
@@ -81,7 +383,42 @@ impl Request {
             .read_exact(&mut first_buf)
             .map_err(|_| RequestError::Disconnected)?;
 
-        Self::from_stream_inner(stream, address, first_buf[0])
+        Self::from_stream_inner(
+            stream,
+            address,
+            first_buf[0],
+            None,
+            None,
+            &RequestLimits::default(),
+        )
+    }
+
+    /// Attempts to read and parse one HTTP request from the given reader, checking `condition` and
+    ///   `defer` against the request head and applying the limits as `from_stream_with_options`
+    ///   does.
+    ///
+    /// Any read timeout set on the reader applies to the whole request.
+    #[cfg(all(feature = "runtime", not(feature = "tokio")))]
+    pub(crate) fn from_reader_checked<T>(
+        stream: &mut T,
+        address: SocketAddr,
+        condition: Option<HeadCondition<'_>>,
+        defer: Option<&mut BodyDeferral<'_>>,
+        limits: &RequestLimits,
+    ) -> Result<Self, RequestError>
+    where
+        T: Read,
+    {
+        let mut first_buf: [u8; 1] = [0; 1];
+        stream
+            .read_exact(&mut first_buf)
+            .map_err(|e| match e.kind() {
+                ErrorKind::TimedOut => RequestError::Timeout,
+                ErrorKind::WouldBlock => RequestError::Timeout,
+                _ => RequestError::Disconnected,
+            })?;
+
+        Self::from_stream_inner(stream, address, first_buf[0], condition, defer, limits)
     }
 
     /// Attempts to read and parse one HTTP request from the given reader.
@@ -96,11 +433,59 @@ impl Request {
             .await
             .map_err(|_| RequestError::Disconnected)?;
 
-        Self::from_stream_inner(stream, address, first_buf[0]).await
+        Self::from_stream_inner(
+            stream,
+            address,
+            first_buf[0],
+            None,
+            None,
+            &RequestLimits::default(),
+        )
+        .await
+    }
+
+    /// Attempts to read and parse one HTTP request from the given reader, checking the condition
+    ///   against the request head before the body is read.
+    ///
+    /// If `defer` is given and its condition holds for the request head, the body is left unread.
+    /// If `received` is given, it is set to the instant at which the first byte arrived.
+    /// If `idle` is given and completes before the first byte arrives, the request times out.
+    #[cfg(feature = "tokio")]
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn from_stream_checked<T>(
+        stream: &mut T,
+        address: SocketAddr,
+        condition: Option<HeadCondition<'_>>,
+        defer: Option<&mut BodyDeferral<'_>>,
+        received: Option<&mut Instant>,
+        idle: Option<Pin<&mut (dyn Future<Output = ()> + Send)>>,
+        limits: &RequestLimits,
+    ) -> Result<Self, RequestError>
+    where
+        T: AsyncReadExt + Unpin,
+    {
+        let mut first_buf: [u8; 1] = [0; 1];
+        let read = stream.read_exact(&mut first_buf);
+
+        // Reading a single byte either completes or reads nothing, so it can safely be abandoned
+        let read = match idle {
+            Some(idle) => tokio::select! {
+                read = read => read,
+                () = idle => return Err(RequestError::Timeout),
+            },
+            None => read.await,
+        };
+        read.map_err(|_| RequestError::Disconnected)?;
+
+        if let Some(received) = received {
+            *received = clock::now_monotonic();
+        }
+
+        Self::from_stream_inner(stream, address, first_buf[0], condition, defer, limits).await
     }
 
     /// Attempts to read and parse one HTTP request from the given stream, timing out after the timeout.
-    #[cfg(not(feature = "tokio"))]
+    #[cfg(all(feature = "runtime", not(feature = "tokio")))]
     pub fn from_stream_with_timeout(
         stream: &mut Stream,
         address: SocketAddr,
@@ -121,23 +506,90 @@ impl Request {
 
         stream.set_timeout(None).map_err(|_| RequestError::Stream)?;
 
-        Self::from_stream_inner(stream, address, first_buf[0])
+        Self::from_stream_inner(
+            stream,
+            address,
+            first_buf[0],
+            None,
+            None,
+            &RequestLimits::default(),
+        )
+    }
+
+    /// Attempts to read and parse one HTTP request from the given stream with the given options.
+    ///
+    /// If `raw` is given, every byte read is copied into it, even if the request cannot be parsed,
+    ///   unless `raw_body` is false, in which case copying stops at the end of the head.
+    /// If `condition` is given, it is checked against the request head before the body is read.
+    /// If `defer` is given and its condition holds for the request head, the body is left unread.
+    /// If `received` is given, it is set to the instant at which the first byte arrived.
+    /// The request is rejected if any part of it exceeds the limits.
+    #[cfg(all(feature = "runtime", not(feature = "tokio")))]
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn from_stream_with_options(
+        stream: &mut Stream,
+        address: SocketAddr,
+        timeout: Option<Duration>,
+        raw: Option<&mut Vec<u8>>,
+        raw_body: bool,
+        condition: Option<HeadCondition<'_>>,
+        defer: Option<&mut BodyDeferral<'_>>,
+        received: Option<&mut Instant>,
+        limits: &RequestLimits,
+    ) -> Result<Self, RequestError> {
+        if timeout.is_some() {
+            stream
+                .set_timeout(timeout)
+                .map_err(|_| RequestError::Stream)?;
+        }
+
+        let mut first_buf: [u8; 1] = [0; 1];
+        stream
+            .read_exact(&mut first_buf)
+            .map_err(|e| match e.kind() {
+                ErrorKind::TimedOut => RequestError::Timeout,
+                ErrorKind::WouldBlock => RequestError::Timeout,
+                _ => RequestError::Disconnected,
+            })?;
+
+        if let Some(received) = received {
+            *received = clock::now_monotonic();
+        }
+
+        if timeout.is_some() {
+            stream.set_timeout(None).map_err(|_| RequestError::Stream)?;
+        }
+
+        match raw {
+            Some(raw) => {
+                raw.push(first_buf[0]);
+
+                Self::from_stream_inner(
+                    &mut TeeReader::new(stream, raw, !raw_body),
+                    address,
+                    first_buf[0],
+                    condition,
+                    defer,
+                    limits,
+                )
+            }
+            None => {
+                Self::from_stream_inner(stream, address, first_buf[0], condition, defer, limits)
+            }
+        }
     }
 
     /// Get the cookies from the request.
+    ///
+    /// Cookies from every `Cookie` header are returned in order. Whitespace around names and values
+    ///   is ignored, and quoted values are unquoted.
     pub fn get_cookies(&self) -> Vec<Cookie> {
         self.headers
-            .get(HeaderType::Cookie)
-            .map(|cookies| {
-                cookies
-                    .split(';')
-                    .filter_map(|cookie| {
-                        let (k, v) = cookie.split_once('=')?;
-                        Some(Cookie::new(k.trim(), v.trim()))
-                    })
-                    .collect()
-            })
-            .unwrap_or_default()
+            .get_all(HeaderType::Cookie)
+            .into_iter()
+            .flat_map(|cookies| cookies.split(';'))
+            .filter_map(Cookie::parse)
+            .collect()
     }
 
     /// Attempts to get a specific cookie from the request.
@@ -147,21 +599,69 @@ impl Request {
             .find(|cookie| cookie.name == name.as_ref())
     }
 
+    /// Returns the base URL at which the request was received, consisting of the scheme, host and any
+    ///   non-default port, for example `https://example.com:8443`.
+    ///
+    /// Forwarded headers are only respected if the request came directly from one of the given
+    ///   trusted proxies, as described in `Origin::from_request`. Returns `None` if the host is missing
+    ///   or invalid.
+    pub fn base_url(&self, trusted_proxies: &[IpAddr]) -> Option<String> {
+        Origin::from_request(self, trusted_proxies).map(|origin| origin.to_string())
+    }
+
+    /// Returns the absolute URL of the given path on the origin at which the request was received,
+    ///   which is suitable for use in a `Location` header.
+    ///
+    /// The path is percent-encoded where necessary. The query is appended if given, so to preserve the
+    ///   query of this request, pass `Some(&request.query)`.
+    pub fn absolute_url(
+        &self,
+        path: &str,
+        query: Option<&str>,
+        trusted_proxies: &[IpAddr],
+    ) -> Option<String> {
+        Origin::from_request(self, trusted_proxies).map(|origin| origin.url(path, query))
+    }
+
+    /// Returns `true` if the whole body of the request was received, or the request had no body.
+    ///
+    /// If this returns `false`, the connection which the request was received on is closed after
+    ///   the response is sent.
+    pub fn body_complete(&self) -> bool {
+        self.body_state == BodyState::Complete
+    }
+
+    /// Checks whether the body of this request would have been drained if it were rejected, meaning
+    ///   the connection can be reused.
+    #[cfg(feature = "runtime")]
+    pub(crate) fn rejected_body_drained(&self) -> bool {
+        self.headers.get(&HeaderType::TransferEncoding).is_none()
+            && content_length(&self.headers)
+                .ok()
+                .flatten()
+                .is_none_or(|length| length <= REJECTED_BODY_DRAIN_LIMIT)
+    }
+
     /// Attempts to read and parse one HTTP request from the given reader.
     #[cfg(not(feature = "tokio"))]
     fn from_stream_inner<T>(
         stream: &mut T,
         address: SocketAddr,
         first_byte: u8,
+        condition: Option<HeadCondition<'_>>,
+        defer: Option<&mut BodyDeferral<'_>>,
+        limits: &RequestLimits,
     ) -> Result<Self, RequestError>
     where
         T: Read,
     {
         let mut reader = BufReader::new(stream);
         let mut start_line_buf: Vec<u8> = Vec::with_capacity(256);
-        reader
-            .read_until(0xA, &mut start_line_buf)
-            .map_err(|_| RequestError::Stream)?;
+        let start_line_limit = limits
+            .max_uri_length
+            .map(|limit| limit + REQUEST_LINE_OVERHEAD);
+        read_line(&mut reader, &mut start_line_buf, start_line_limit)
+            .map_err(|e| e.into_request_error(RequestPart::Uri))?;
 
         start_line_buf.insert(0, first_byte);
 
@@ -170,10 +670,16 @@ impl Request {
         let mut start_line = start_line_string.split(' ');
 
         let method = Method::from_name(start_line.next().to_error(RequestError::Request)?)?;
-        let mut uri_iter = start_line
-            .next()
-            .to_error(RequestError::Request)?
-            .splitn(2, '?');
+        let target = start_line.next().to_error(RequestError::Request)?;
+
+        if limits
+            .max_uri_length
+            .is_some_and(|limit| target.len() > limit)
+        {
+            return Err(RequestError::TooLarge(RequestPart::Uri));
+        }
+
+        let mut uri_iter = target.splitn(2, '?');
         let version = start_line
             .next()
             .to_error(RequestError::Request)?
@@ -181,67 +687,124 @@ impl Request {
             .unwrap_or("")
             .to_string();
 
-        safe_assert(!version.is_empty())?;
+        // Any token is a valid method, so the version is what distinguishes a request line from any
+        //   other three words
+        safe_assert(version.starts_with("HTTP/"))?;
 
-        let uri = uri_iter.next().unwrap().to_string();
+        let uri_raw = uri_iter.next().unwrap().to_string();
+        let uri = decode_path(&uri_raw).to_error(RequestError::Request)?;
         let query = uri_iter.next().unwrap_or("").to_string();
 
         let mut headers = Headers::new();
+        let mut header_size: usize = 0;
 
         loop {
             let mut line_buf: Vec<u8> = Vec::with_capacity(256);
-            reader
-                .read_until(0xA, &mut line_buf)
-                .map_err(|_| RequestError::Stream)?;
+            let header_limit = limits
+                .max_header_size
+                .map(|limit| limit.saturating_sub(header_size));
+            read_line(&mut reader, &mut line_buf, header_limit)
+                .map_err(|e| e.into_request_error(RequestPart::Headers))?;
+            header_size += line_buf.len();
             let line = std::str::from_utf8(&line_buf).map_err(|_| RequestError::Request)?;
 
             if line == "\r\n" {
                 break;
             } else {
-                safe_assert(line.len() >= 2)?;
-                let line_without_crlf = &line[0..line.len() - 2];
-                let mut line_parts = line_without_crlf.splitn(2, ':');
-                headers.add(
-                    HeaderType::from(line_parts.next().to_error(RequestError::Request)?),
-                    line_parts
-                        .next()
-                        .to_error(RequestError::Request)?
-                        .trim_start(),
-                );
+                let (name, value) = parse_header_line(line)?;
+                headers.add(name, value);
             }
         }
 
         let address =
             Address::from_headers(&headers, address).map_err(|_| RequestError::Request)?;
+        let content_length = content_length(&headers)?;
+        limits.check_body(content_length)?;
+
+        // A body framed both ways could be read differently by another server, so it is refused
+        let chunked_codings = chunked_codings(&headers);
+        safe_assert(chunked_codings.is_none() || content_length.is_none())?;
 
-        if let Some(content_length) = headers.get(&HeaderType::ContentLength) {
-            let content_length: usize =
-                content_length.parse().map_err(|_| RequestError::Request)?;
+        let mut request = Self {
+            method,
+            overridden_method: None,
+            uri,
+            uri_raw,
+            query,
+            version,
+            headers,
+            content: None,
+            body_state: BodyState::Complete,
+            trailers: Headers::new(),
+            address,
+            secure: false,
+            local_addr: None,
+            deadline: None,
+            trace: None,
+            cancellation: CancellationToken::default(),
+            params: RouteParams::default(),
+        };
+
+        // Check the condition before the body is read, draining small bodies so the connection can be reused
+        if let Some(condition) = condition {
+            if !condition(&request) {
+                let length = content_length.unwrap_or(0);
+
+                if length <= REJECTED_BODY_DRAIN_LIMIT {
+                    std::io::copy(&mut (&mut reader).take(length as u64), &mut std::io::sink())
+                        .map_err(|_| RequestError::Stream)?;
+                }
+
+                return Err(RequestError::Rejected);
+            }
+        }
+
+        // Leave the body to be streamed to the handler, keeping any of it which was read with the head
+        if let Some(defer) = defer {
+            if (defer.condition)(&request) {
+                defer.buffered = Some(reader.buffer().to_vec());
+
+                return Ok(request);
+            }
+        }
+
+        if let Some(content_length) = content_length {
             let mut content_buf: Vec<u8> = vec![0u8; content_length];
-            reader
-                .read_exact(&mut content_buf)
-                .map_err(|_| RequestError::Stream)?;
+            let mut received = 0;
 
-            Ok(Self {
-                method,
-                uri,
-                query,
-                version,
-                headers,
-                content: Some(content_buf),
-                address,
-            })
-        } else {
-            Ok(Self {
-                method,
-                uri,
-                query,
-                version,
-                headers,
-                content: None,
-                address,
-            })
+            // Stop reading if the stream ends, fails or times out, keeping what was received
+            while received < content_length {
+                match reader.read(&mut content_buf[received..]) {
+                    Ok(0) => break,
+                    Ok(n) => received += n,
+                    Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                    Err(_) => break,
+                }
+            }
+
+            content_buf.truncate(received);
+            request.content = Some(content_buf);
+            request.body_state = body_state(received, content_length);
+        } else if let Some(codings) = chunked_codings {
+            let (content, body_state) =
+                read_chunked_body(&mut reader, &mut request.trailers, limits, header_size)?;
+
+            // The body is no longer chunked, so the headers are updated to describe it as it is now
+            request.headers.remove(HeaderType::TransferEncoding);
+            if !codings.is_empty() {
+                request.headers.add(HeaderType::TransferEncoding, codings);
+            }
+            request
+                .headers
+                .add(HeaderType::ContentLength, content.len().to_string());
+
+            request.content = Some(content);
+            request.body_state = body_state;
+        } else if request.headers.get(&HeaderType::TransferEncoding).is_some() {
+            request.body_state = BodyState::Unknown;
         }
+
+        Ok(request)
     }
 
     /// Attempts to read and parse one HTTP request from the given reader.
@@ -250,16 +813,21 @@ impl Request {
         stream: &mut T,
         address: SocketAddr,
         first_byte: u8,
+        condition: Option<HeadCondition<'_>>,
+        defer: Option<&mut BodyDeferral<'_>>,
+        limits: &RequestLimits,
     ) -> Result<Self, RequestError>
     where
         T: AsyncReadExt + Unpin,
     {
         let mut reader = BufReader::new(stream);
         let mut start_line_buf: Vec<u8> = Vec::with_capacity(256);
-        reader
-            .read_until(0xA, &mut start_line_buf)
+        let start_line_limit = limits
+            .max_uri_length
+            .map(|limit| limit + REQUEST_LINE_OVERHEAD);
+        read_line(&mut reader, &mut start_line_buf, start_line_limit)
             .await
-            .map_err(|_| RequestError::Stream)?;
+            .map_err(|e| e.into_request_error(RequestPart::Uri))?;
 
         start_line_buf.insert(0, first_byte);
 
@@ -268,10 +836,16 @@ impl Request {
         let mut start_line = start_line_string.split(' ');
 
         let method = Method::from_name(start_line.next().to_error(RequestError::Request)?)?;
-        let mut uri_iter = start_line
-            .next()
-            .to_error(RequestError::Request)?
-            .splitn(2, '?');
+        let target = start_line.next().to_error(RequestError::Request)?;
+
+        if limits
+            .max_uri_length
+            .is_some_and(|limit| target.len() > limit)
+        {
+            return Err(RequestError::TooLarge(RequestPart::Uri));
+        }
+
+        let mut uri_iter = target.splitn(2, '?');
         let version = start_line
             .next()
             .to_error(RequestError::Request)?
@@ -279,70 +853,551 @@ impl Request {
             .unwrap_or("")
             .to_string();
 
-        safe_assert(!version.is_empty())?;
+        // Any token is a valid method, so the version is what distinguishes a request line from any
+        //   other three words
+        safe_assert(version.starts_with("HTTP/"))?;
 
-        let uri = uri_iter.next().unwrap().to_string();
+        let uri_raw = uri_iter.next().unwrap().to_string();
+        let uri = decode_path(&uri_raw).to_error(RequestError::Request)?;
         let query = uri_iter.next().unwrap_or("").to_string();
 
         let mut headers = Headers::new();
+        let mut header_size: usize = 0;
 
         loop {
             let mut line_buf: Vec<u8> = Vec::with_capacity(256);
-            reader
-                .read_until(0xA, &mut line_buf)
+            let header_limit = limits
+                .max_header_size
+                .map(|limit| limit.saturating_sub(header_size));
+            read_line(&mut reader, &mut line_buf, header_limit)
                 .await
-                .map_err(|_| RequestError::Stream)?;
+                .map_err(|e| e.into_request_error(RequestPart::Headers))?;
+            header_size += line_buf.len();
             let line = std::str::from_utf8(&line_buf).map_err(|_| RequestError::Request)?;
 
             if line == "\r\n" {
                 break;
             } else {
-                safe_assert(line.len() >= 2)?;
-                let line_without_crlf = &line[0..line.len() - 2];
-                let mut line_parts = line_without_crlf.splitn(2, ':');
-                headers.add(
-                    HeaderType::from(line_parts.next().to_error(RequestError::Request)?),
-                    line_parts
-                        .next()
-                        .to_error(RequestError::Request)?
-                        .trim_start(),
-                );
+                let (name, value) = parse_header_line(line)?;
+                headers.add(name, value);
             }
         }
 
         let address =
             Address::from_headers(&headers, address).map_err(|_| RequestError::Request)?;
+        let content_length = content_length(&headers)?;
+        limits.check_body(content_length)?;
+
+        // A body framed both ways could be read differently by another server, so it is refused
+        let chunked_codings = chunked_codings(&headers);
+        safe_assert(chunked_codings.is_none() || content_length.is_none())?;
 
-        if let Some(content_length) = headers.get(&HeaderType::ContentLength) {
-            let content_length: usize =
-                content_length.parse().map_err(|_| RequestError::Request)?;
+        let mut request = Self {
+            method,
+            overridden_method: None,
+            uri,
+            uri_raw,
+            query,
+            version,
+            headers,
+            content: None,
+            body_state: BodyState::Complete,
+            trailers: Headers::new(),
+            address,
+            secure: false,
+            local_addr: None,
+            deadline: None,
+            trace: None,
+            cancellation: CancellationToken::default(),
+            params: RouteParams::default(),
+        };
+
+        // Check the condition before the body is read, draining small bodies so the connection can be reused
+        if let Some(condition) = condition {
+            if !condition(&request) {
+                let length = content_length.unwrap_or(0);
+
+                if length <= REJECTED_BODY_DRAIN_LIMIT {
+                    tokio::io::copy(
+                        &mut (&mut reader).take(length as u64),
+                        &mut tokio::io::sink(),
+                    )
+                    .await
+                    .map_err(|_| RequestError::Stream)?;
+                }
+
+                return Err(RequestError::Rejected);
+            }
+        }
+
+        // Leave the body to be streamed to the handler, keeping any of it which was read with the head
+        if let Some(defer) = defer {
+            if (defer.condition)(&request) {
+                defer.buffered = Some(reader.buffer().to_vec());
+
+                return Ok(request);
+            }
+        }
+
+        if let Some(content_length) = content_length {
             let mut content_buf: Vec<u8> = vec![0u8; content_length];
+            let mut received = 0;
+
+            // Stop reading if the stream ends or fails, keeping what was received
+            while received < content_length {
+                match reader.read(&mut content_buf[received..]).await {
+                    Ok(0) => break,
+                    Ok(n) => received += n,
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => (),
+                    Err(_) => break,
+                }
+            }
+
+            content_buf.truncate(received);
+            request.content = Some(content_buf);
+            request.body_state = body_state(received, content_length);
+        } else if let Some(codings) = chunked_codings {
+            let (content, body_state) =
+                read_chunked_body(&mut reader, &mut request.trailers, limits, header_size).await?;
+
+            // The body is no longer chunked, so the headers are updated to describe it as it is now
+            request.headers.remove(HeaderType::TransferEncoding);
+            if !codings.is_empty() {
+                request.headers.add(HeaderType::TransferEncoding, codings);
+            }
+            request
+                .headers
+                .add(HeaderType::ContentLength, content.len().to_string());
+
+            request.content = Some(content);
+            request.body_state = body_state;
+        } else if request.headers.get(&HeaderType::TransferEncoding).is_some() {
+            request.body_state = BodyState::Unknown;
+        }
+
+        Ok(request)
+    }
+}
+
+/// An error which occurred while reading a line of the request head.
+enum LineError {
+    /// The stream could not be read.
+    Stream,
+    /// The line was longer than the limit.
+    TooLong,
+}
+
+impl LineError {
+    /// Converts the error into a request error, blaming the given part of the request if the line
+    ///   was too long.
+    fn into_request_error(self, part: RequestPart) -> RequestError {
+        match self {
+            LineError::Stream => RequestError::Stream,
+            LineError::TooLong => RequestError::TooLarge(part),
+        }
+    }
+}
+
+/// Reads a line into the buffer, reading no more than `limit` bytes if a limit is given.
+#[cfg(not(feature = "tokio"))]
+fn read_line<R>(reader: &mut R, buf: &mut Vec<u8>, limit: Option<usize>) -> Result<(), LineError>
+where
+    R: BufRead,
+{
+    match limit {
+        Some(limit) => {
             reader
-                .read_exact(&mut content_buf)
+                .take(limit as u64 + 1)
+                .read_until(0xA, buf)
+                .map_err(|_| LineError::Stream)?;
+
+            match buf.len() > limit {
+                true => Err(LineError::TooLong),
+                false => Ok(()),
+            }
+        }
+        None => reader
+            .read_until(0xA, buf)
+            .map(|_| ())
+            .map_err(|_| LineError::Stream),
+    }
+}
+
+/// Reads a line into the buffer, reading no more than `limit` bytes if a limit is given.
+#[cfg(feature = "tokio")]
+async fn read_line<R>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    limit: Option<usize>,
+) -> Result<(), LineError>
+where
+    R: AsyncBufReadExt + AsyncReadExt + Unpin,
+{
+    match limit {
+        Some(limit) => {
+            reader
+                .take(limit as u64 + 1)
+                .read_until(0xA, buf)
                 .await
-                .map_err(|_| RequestError::Stream)?;
+                .map_err(|_| LineError::Stream)?;
 
-            Ok(Self {
-                method,
-                uri,
-                query,
-                version,
-                headers,
-                content: Some(content_buf),
-                address,
-            })
-        } else {
-            Ok(Self {
-                method,
-                uri,
-                query,
-                version,
-                headers,
+            match buf.len() > limit {
+                true => Err(LineError::TooLong),
+                false => Ok(()),
+            }
+        }
+        None => reader
+            .read_until(0xA, buf)
+            .await
+            .map(|_| ())
+            .map_err(|_| LineError::Stream),
+    }
+}
+
+/// Reads a chunked body, adding any trailer fields to the trailers.
+///
+/// The trailer fields count towards the limit on the size of the headers, of which `header_size`
+///   bytes have already been used. If the stream ends, fails or times out before the final chunk,
+///   the chunks received so far are returned and the body is marked as truncated.
+#[cfg(not(feature = "tokio"))]
+fn read_chunked_body<R>(
+    reader: &mut R,
+    trailers: &mut Headers,
+    limits: &RequestLimits,
+    mut header_size: usize,
+) -> Result<(Vec<u8>, BodyState), RequestError>
+where
+    R: BufRead,
+{
+    let mut body: Vec<u8> = Vec::new();
+    let mut declared: usize = 0;
+
+    loop {
+        let mut line_buf: Vec<u8> = Vec::with_capacity(16);
+        match read_line(reader, &mut line_buf, Some(CHUNK_SIZE_LINE_LIMIT)) {
+            Ok(()) if line_buf.ends_with(b"\n") => (),
+            Ok(()) | Err(LineError::Stream) => return Ok(truncated_chunked_body(body, declared)),
+            Err(LineError::TooLong) => return Err(RequestError::Request),
+        }
+
+        let size = parse_chunk_size(&line_buf)?;
+        if size == 0 {
+            break;
+        }
+
+        declared = declared.checked_add(size).to_error(RequestError::Request)?;
+        limits.check_body(Some(declared))?;
+
+        // Bytes read before an error are kept in the body
+        let _ = (&mut *reader).take(size as u64).read_to_end(&mut body);
+        if body.len() < declared {
+            return Ok(truncated_chunked_body(body, declared));
+        }
+
+        let mut crlf: Vec<u8> = Vec::with_capacity(2);
+        match read_line(reader, &mut crlf, Some(2)) {
+            Ok(()) if crlf == b"\r\n" => (),
+            Ok(()) | Err(LineError::Stream) if b"\r\n".starts_with(&crlf) => {
+                return Ok(truncated_chunked_body(body, declared))
+            }
+            _ => return Err(RequestError::Request),
+        }
+    }
+
+    loop {
+        let mut line_buf: Vec<u8> = Vec::with_capacity(256);
+        let trailer_limit = limits
+            .max_header_size
+            .map(|limit| limit.saturating_sub(header_size));
+        read_line(reader, &mut line_buf, trailer_limit)
+            .map_err(|e| e.into_request_error(RequestPart::Headers))?;
+        header_size += line_buf.len();
+
+        // The whole body has been received, so a missing end of the trailers is not an error
+        if line_buf == b"\r\n" || !line_buf.ends_with(b"\n") {
+            break;
+        }
+
+        let line = std::str::from_utf8(&line_buf).map_err(|_| RequestError::Request)?;
+        add_trailer(trailers, parse_header_line(line)?);
+    }
+
+    Ok((body, BodyState::Complete))
+}
+
+/// Reads a chunked body, adding any trailer fields to the trailers.
+///
+/// The trailer fields count towards the limit on the size of the headers, of which `header_size`
+///   bytes have already been used. If the stream ends or fails before the final chunk, the chunks
+///   received so far are returned and the body is marked as truncated.
+#[cfg(feature = "tokio")]
+async fn read_chunked_body<R>(
+    reader: &mut R,
+    trailers: &mut Headers,
+    limits: &RequestLimits,
+    mut header_size: usize,
+) -> Result<(Vec<u8>, BodyState), RequestError>
+where
+    R: AsyncBufReadExt + AsyncReadExt + Unpin,
+{
+    let mut body: Vec<u8> = Vec::new();
+    let mut declared: usize = 0;
+
+    loop {
+        let mut line_buf: Vec<u8> = Vec::with_capacity(16);
+        match read_line(reader, &mut line_buf, Some(CHUNK_SIZE_LINE_LIMIT)).await {
+            Ok(()) if line_buf.ends_with(b"\n") => (),
+            Ok(()) | Err(LineError::Stream) => return Ok(truncated_chunked_body(body, declared)),
+            Err(LineError::TooLong) => return Err(RequestError::Request),
+        }
+
+        let size = parse_chunk_size(&line_buf)?;
+        if size == 0 {
+            break;
+        }
+
+        declared = declared.checked_add(size).to_error(RequestError::Request)?;
+        limits.check_body(Some(declared))?;
+
+        // Bytes read before an error are kept in the body
+        let _ = (&mut *reader)
+            .take(size as u64)
+            .read_to_end(&mut body)
+            .await;
+        if body.len() < declared {
+            return Ok(truncated_chunked_body(body, declared));
+        }
+
+        let mut crlf: Vec<u8> = Vec::with_capacity(2);
+        match read_line(reader, &mut crlf, Some(2)).await {
+            Ok(()) if crlf == b"\r\n" => (),
+            Ok(()) | Err(LineError::Stream) if b"\r\n".starts_with(&crlf) => {
+                return Ok(truncated_chunked_body(body, declared))
+            }
+            _ => return Err(RequestError::Request),
+        }
+    }
+
+    loop {
+        let mut line_buf: Vec<u8> = Vec::with_capacity(256);
+        let trailer_limit = limits
+            .max_header_size
+            .map(|limit| limit.saturating_sub(header_size));
+        read_line(reader, &mut line_buf, trailer_limit)
+            .await
+            .map_err(|e| e.into_request_error(RequestPart::Headers))?;
+        header_size += line_buf.len();
+
+        // The whole body has been received, so a missing end of the trailers is not an error
+        if line_buf == b"\r\n" || !line_buf.ends_with(b"\n") {
+            break;
+        }
+
+        let line = std::str::from_utf8(&line_buf).map_err(|_| RequestError::Request)?;
+        add_trailer(trailers, parse_header_line(line)?);
+    }
+
+    Ok((body, BodyState::Complete))
+}
+
+/// Parses a chunk size line, ignoring any chunk extensions.
+pub(crate) fn parse_chunk_size(line: &[u8]) -> Result<usize, RequestError> {
+    let line = std::str::from_utf8(line).map_err(|_| RequestError::Request)?;
+    let size = line
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches(['\r', '\n'])
+        .trim_matches([' ', '\t']);
+
+    safe_assert(!size.is_empty() && size.bytes().all(|b| b.is_ascii_hexdigit()))?;
+    usize::from_str_radix(size, 16).map_err(|_| RequestError::Request)
+}
+
+/// Describes a chunked body which ended before its final chunk.
+fn truncated_chunked_body(body: Vec<u8>, declared: usize) -> (Vec<u8>, BodyState) {
+    let received = body.len();
+
+    (
+        body,
+        BodyState::Truncated {
+            received,
+            expected: declared,
+        },
+    )
+}
+
+/// Adds a trailer field to the trailers, unless it is not allowed to be sent as a trailer.
+fn add_trailer(trailers: &mut Headers, (name, value): (HeaderType, &str)) {
+    if is_permitted_trailer(&name) {
+        trailers.add(name, value);
+    }
+}
+
+/// Parses a header line, including its trailing CRLF, into the name and value of the header.
+///
+/// Lines which do not end with CRLF are rejected.
+fn parse_header_line(line: &str) -> Result<(HeaderType, &str), RequestError> {
+    let (name, value) = line
+        .strip_suffix("\r\n")
+        .to_error(RequestError::Request)?
+        .split_once(':')
+        .to_error(RequestError::Request)?;
+
+    Ok((HeaderType::from(name), value.trim_start()))
+}
+
+/// Returns the transfer codings applied to the body before it was chunked, if the final coding of
+///   the `Transfer-Encoding` header is `chunked`.
+pub(crate) fn chunked_codings(headers: &Headers) -> Option<String> {
+    let codings = headers.get(&HeaderType::TransferEncoding)?;
+    let (rest, last) = match codings.rsplit_once(',') {
+        Some((rest, last)) => (rest.trim(), last),
+        None => ("", codings),
+    };
+
+    match last.trim().eq_ignore_ascii_case("chunked") {
+        true => Some(rest.to_string()),
+        false => None,
+    }
+}
+
+/// Parses the declared length of the request body, if any.
+pub(crate) fn content_length(headers: &Headers) -> Result<Option<usize>, RequestError> {
+    headers
+        .get(&HeaderType::ContentLength)
+        .map(|length| length.parse().map_err(|_| RequestError::Request))
+        .transpose()
+}
+
+/// Describes the state of a body of which the given number of bytes were received.
+fn body_state(received: usize, expected: usize) -> BodyState {
+    if received == expected {
+        BodyState::Complete
+    } else {
+        BodyState::Truncated { received, expected }
+    }
+}
+
+impl RequestBuilder {
+    /// Creates a new builder, equivalent to `Request::builder`.
+    pub fn new() -> Self {
+        Self {
+            request: Request {
+                method: Method::Get,
+                overridden_method: None,
+                uri: "/".into(),
+                uri_raw: "/".into(),
+                query: String::new(),
+                version: "HTTP/1.1".into(),
+                headers: Headers::new(),
                 content: None,
-                address,
-            })
+                body_state: BodyState::Complete,
+                trailers: Headers::new(),
+                address: Address {
+                    origin_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+                    proxies: Vec::new(),
+                    port: 0,
+                },
+                secure: false,
+                local_addr: None,
+                deadline: None,
+                trace: None,
+                cancellation: CancellationToken::default(),
+                params: RouteParams::default(),
+            },
         }
     }
+
+    /// Sets the method of the request.
+    pub fn method(mut self, method: Method) -> Self {
+        self.request.method = method;
+        self
+    }
+
+    /// Sets the URI of the request. If the URI contains a query string, it is used as the query.
+    ///
+    /// The path is percent-decoded in the same way as a received request, unless it is invalid.
+    pub fn uri(mut self, uri: impl AsRef<str>) -> Self {
+        let (uri, query) = match uri.as_ref().split_once('?') {
+            Some((uri, query)) => (uri, query),
+            None => (uri.as_ref(), ""),
+        };
+
+        self.request.uri = decode_path(uri).unwrap_or_else(|| uri.to_string());
+        self.request.uri_raw = uri.to_string();
+        self.request.query = query.to_string();
+        self
+    }
+
+    /// Sets the query string of the request, without the leading `?`.
+    pub fn query(mut self, query: impl AsRef<str>) -> Self {
+        self.request.query = query.as_ref().to_string();
+        self
+    }
+
+    /// Sets the HTTP version of the request, for example `HTTP/1.0`.
+    pub fn version(mut self, version: impl AsRef<str>) -> Self {
+        self.request.version = version.as_ref().to_string();
+        self
+    }
+
+    /// Adds the given header to the request.
+    pub fn header(mut self, header: impl HeaderLike, value: impl AsRef<str>) -> Self {
+        self.request.headers.add(header, value);
+        self
+    }
+
+    /// Sets the body of the request, along with its `Content-Length` header.
+    pub fn body(mut self, body: impl AsRef<[u8]>) -> Self {
+        let body = body.as_ref().to_vec();
+
+        self.request.headers.remove(HeaderType::ContentLength);
+        self.request
+            .headers
+            .add(HeaderType::ContentLength, body.len().to_string());
+        self.request.content = Some(body);
+        self
+    }
+
+    /// Sets the address from which the request came.
+    pub fn address(mut self, address: SocketAddr) -> Self {
+        self.request.address = Address {
+            origin_addr: address.ip(),
+            proxies: Vec::new(),
+            port: address.port(),
+        };
+        self
+    }
+
+    /// Sets whether the request was received over a secure (TLS) connection.
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.request.secure = secure;
+        self
+    }
+
+    /// Sets the time by which the request should have been responded to.
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.request.deadline = Some(deadline);
+        self
+    }
+
+    /// Sets the trace context of the request.
+    pub fn trace(mut self, trace: TraceContext) -> Self {
+        self.request.trace = Some(trace);
+        self
+    }
+
+    /// Builds the request.
+    pub fn build(self) -> Request {
+        self.request
+    }
+}
+
+impl Default for RequestBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// Asserts that the condition is true, returning a `Result`.
@@ -354,11 +1409,33 @@ fn safe_assert(condition: bool) -> Result<(), RequestError> {
 }
 
 impl From<Request> for Vec<u8> {
-    fn from(req: Request) -> Self {
+    /// Serialises the request.
+    ///
+    /// Trailers can only follow a chunked body, so if there are any, the body is sent as a single
+    ///   chunk followed by the trailers instead of with a `Content-Length`. Trailers which could
+    ///   affect how the request is framed or handled are never sent.
+    fn from(mut req: Request) -> Self {
+        let trailers: Vec<Header> = req
+            .trailers
+            .iter()
+            .filter(|trailer| is_permitted_trailer(&trailer.name))
+            .collect();
+        let chunked = !trailers.is_empty();
+
+        if chunked {
+            req.headers.remove(HeaderType::ContentLength);
+
+            match req.headers.get_mut(HeaderType::TransferEncoding) {
+                Some(codings) => codings.push_str(", chunked"),
+                None => req.headers.add(HeaderType::TransferEncoding, "chunked"),
+            }
+        }
+
+        let uri = encode_path(&req.uri);
         let start_line = if req.query.is_empty() {
-            format!("{} {} {}", req.method, req.uri, req.version)
+            format!("{} {} {}", req.method, uri, req.version)
         } else {
-            format!("{} {}?{} {}", req.method, req.uri, req.query, req.version)
+            format!("{} {}?{} {}", req.method, uri, req.query, req.version)
         };
 
         let headers = req
@@ -374,7 +1451,25 @@ impl From<Request> for Vec<u8> {
         bytes.extend(headers.as_bytes());
         bytes.extend(b"\r\n\r\n");
 
-        if let Some(content) = req.content {
+        if chunked {
+            let content = req.content.unwrap_or_default();
+
+            if !content.is_empty() {
+                bytes.extend(format!("{:x}\r\n", content.len()).as_bytes());
+                bytes.extend(content);
+                bytes.extend(b"\r\n");
+            }
+
+            bytes.extend(b"0\r\n");
+
+            for trailer in trailers {
+                bytes.extend(
+                    format!("{}: {}\r\n", trailer.name.to_string(), trailer.value).as_bytes(),
+                );
+            }
+
+            bytes.extend(b"\r\n");
+        } else if let Some(content) = req.content {
             bytes.extend(content);
         }
 
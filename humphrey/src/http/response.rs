@@ -1,13 +1,42 @@
 //! Provides functionality for handling HTTP responses.
 
-use crate::http::cookie::SetCookie;
-use crate::http::headers::{HeaderLike, HeaderType, Headers};
+use crate::http::conditional::ETag;
+use crate::http::cookie::{Cookie, SetCookie};
+use crate::http::event_stream::{event_stream, EventSender};
+use crate::http::headers::{Header, HeaderLike, HeaderType, Headers};
+use crate::http::method::Method;
+use crate::http::range::ContentRange;
 use crate::http::status::StatusCode;
 
 use std::convert::TryFrom;
 use std::error::Error;
 use std::io::{BufRead, BufReader, Read};
 
+#[cfg(not(feature = "tokio"))]
+use std::io::Write;
+
+#[cfg(feature = "runtime")]
+use crate::stream::Stream;
+
+#[cfg(feature = "tokio")]
+use std::future::Future;
+#[cfg(feature = "tokio")]
+use std::pin::Pin;
+
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// The largest body which will be read when parsing a response, so that a server cannot use up the
+///   memory of the client by declaring or sending a huge body.
+pub const MAX_RESPONSE_BODY_SIZE: usize = 256 * 1024 * 1024;
+
+/// The longest line, including its line ending, which will be read when parsing the head, chunk
+///   sizes or trailers of a response.
+pub const MAX_RESPONSE_LINE_LENGTH: usize = 16 * 1024;
+
+/// The largest number of headers, or of trailers, which will be read when parsing a response.
+pub const MAX_RESPONSE_HEADERS: usize = 128;
+
 /// Represents a response from the server.
 /// Implements `Into<Vec<u8>>` so can be serialised into bytes to transmit.
 ///
@@ -32,8 +61,61 @@ pub struct Response {
     pub headers: Headers,
     /// The body of the response.
     pub body: Vec<u8>,
+    /// The trailer fields of the response.
+    ///
+    /// If there are any trailers, the response will be sent using chunked transfer encoding so that
+    ///   they can follow the body. Trailers which could affect the framing of the message, such as
+    ///   `Content-Length`, are never parsed or sent.
+    pub trailers: Headers,
+    /// The function to hand the connection to once the response has been written, if any.
+    pub(crate) hijack: Option<Hijack>,
+    /// The reader from which the rest of the body is streamed after `body` is sent, if any.
+    pub(crate) streamed_body: Option<StreamedBody>,
+}
+
+/// The size of the buffer used to read a streamed body, which is the largest chunk that is sent.
+const STREAMED_CHUNK_SIZE: usize = 16 * 1024;
+
+/// A body which is read while the response is being sent, rather than held in memory.
+pub(crate) struct StreamedBody {
+    /// The reader from which the body is read.
+    #[cfg(not(feature = "tokio"))]
+    reader: Box<dyn Read + Send>,
+    /// The reader from which the body is read.
+    #[cfg(feature = "tokio")]
+    reader: Box<dyn AsyncRead + Send + Unpin>,
+    /// The number of bytes to read, if it is known in advance.
+    length: Option<u64>,
+}
+
+/// How the end of the body of a response is marked when it is written.
+enum Framing {
+    /// The length of the body is given by `Content-Length`, so nothing follows it.
+    Length,
+    /// The body is sent using chunked transfer encoding, followed by the given trailers.
+    Chunked(Vec<Header>),
+    /// The end of the body is marked by closing the connection.
+    Close,
+}
+
+/// A function which takes ownership of the underlying connection after a response is written.
+///
+/// Once a response carrying one of these has been sent, the app will not read from, write to or
+///   close the stream again, so the function is responsible for everything that happens on the
+///   connection from then on.
+pub struct Hijack {
+    #[cfg(feature = "runtime")]
+    handler: HijackHandler,
 }
 
+/// The function which takes ownership of a hijacked connection.
+#[cfg(all(feature = "runtime", not(feature = "tokio")))]
+type HijackHandler = Box<dyn FnOnce(Stream) + Send>;
+
+/// The function which takes ownership of a hijacked connection, returning the future which uses it.
+#[cfg(feature = "tokio")]
+type HijackHandler = Box<dyn FnOnce(Stream) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send>;
+
 /// An error which occurred during the parsing of a response.
 #[derive(Debug, PartialEq, Eq)]
 pub enum ResponseError {
@@ -45,7 +127,10 @@ pub enum ResponseError {
 
 impl std::fmt::Display for ResponseError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "ResponseError")
+        match self {
+            ResponseError::Response => write!(f, "Response error: the response was malformed"),
+            ResponseError::Stream => write!(f, "Response error: the stream could not be read"),
+        }
     }
 }
 
@@ -71,6 +156,9 @@ impl Response {
             status_code,
             headers: Headers::new(),
             body: bytes.as_ref().to_vec(),
+            trailers: Headers::new(),
+            hijack: None,
+            streamed_body: None,
         }
     }
 
@@ -82,17 +170,218 @@ impl Response {
             status_code,
             headers: Headers::new(),
             body: Vec::new(),
+            trailers: Headers::new(),
+            hijack: None,
+            streamed_body: None,
         }
     }
 
-    /// Creates a redirect response to the given location.
+    /// Creates a `302 Found` response which redirects to the given location.
+    ///
+    /// ## Example
+    /// ```
+    /// Response::redirect("/login").with_cookie(SetCookie::new("next", "/profile"))
+    /// ```
     pub fn redirect<T>(location: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        Self::empty(StatusCode::Found).with_header(HeaderType::Location, location)
+    }
+
+    /// Creates a `301 Moved Permanently` response which redirects to the given location.
+    pub fn permanent_redirect<T>(location: T) -> Self
     where
         T: AsRef<str>,
     {
         Self::empty(StatusCode::MovedPermanently).with_header(HeaderType::Location, location)
     }
 
+    /// Creates a `200 OK` response containing the value serialized as JSON, setting the
+    ///   `Content-Type` header to `application/json`.
+    ///
+    /// ## Example
+    /// ```
+    /// Response::json(&json!({ "id": 1, "name": "Ferris" }))
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn json<T>(value: &T) -> Self
+    where
+        T: humphrey_json::traits::IntoJson + ?Sized,
+    {
+        Self::new(StatusCode::OK, value.to_json().serialize())
+            .with_header(HeaderType::ContentType, "application/json")
+    }
+
+    /// Creates a `206 Partial Content` response containing one range of a resource, setting the
+    ///   `Content-Range` and `Content-Length` headers.
+    ///
+    /// The bytes must be exactly those within the range, which can be taken from the whole resource
+    ///   with `ContentRange::slice`. To reject a request for a range which cannot be satisfied,
+    ///   convert the `RangeNotSatisfiable` error into a response instead.
+    ///
+    /// ## Example
+    /// ```
+    /// let range = Range::parse("bytes=0-4").unwrap().resolve(11).unwrap()[0];
+    /// Response::partial_content(range, range.slice(b"Hello world"))
+    /// ```
+    pub fn partial_content<T>(range: ContentRange, bytes: T) -> Self
+    where
+        T: AsRef<[u8]>,
+    {
+        let bytes = bytes.as_ref();
+
+        Self::new(StatusCode::PartialContent, bytes)
+            .with_header(HeaderType::ContentRange, range.to_string())
+            .with_header(HeaderType::ContentLength, bytes.len().to_string())
+    }
+
+    /// Creates a response whose body is read from the given reader while it is being sent, for
+    ///   bodies whose length is not known in advance or which are too large to hold in memory.
+    ///
+    /// The body is sent using chunked transfer encoding, so the connection can still be kept alive.
+    ///   HTTP/1.0 clients do not understand chunked transfer encoding, so the body is sent to them
+    ///   as it is and the connection is closed to mark its end. If reading fails part of the way
+    ///   through, the connection is closed without finishing the body, so the client can tell that
+    ///   it is incomplete.
+    ///
+    /// Any bytes added with `with_bytes` are sent before the data from the reader, and any trailers
+    ///   are sent after it.
+    ///
+    /// ## Example
+    /// ```
+    /// Response::chunked(StatusCode::OK, File::open("export.csv")?)
+    ///     .with_header(HeaderType::ContentType, "text/csv")
+    /// ```
+    #[cfg(not(feature = "tokio"))]
+    pub fn chunked<R>(status_code: StatusCode, reader: R) -> Self
+    where
+        R: Read + Send + 'static,
+    {
+        Self::streamed(status_code, Box::new(reader), None)
+    }
+
+    /// Creates a response whose body is read from the given reader while it is being sent, for
+    ///   bodies whose length is not known in advance or which are too large to hold in memory.
+    ///
+    /// The body is sent using chunked transfer encoding, so the connection can still be kept alive.
+    ///   HTTP/1.0 clients do not understand chunked transfer encoding, so the body is sent to them
+    ///   as it is and the connection is closed to mark its end. If reading fails part of the way
+    ///   through, the connection is closed without finishing the body, so the client can tell that
+    ///   it is incomplete.
+    ///
+    /// Any bytes added with `with_bytes` are sent before the data from the reader, and any trailers
+    ///   are sent after it.
+    #[cfg(feature = "tokio")]
+    pub fn chunked<R>(status_code: StatusCode, reader: R) -> Self
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+    {
+        Self::streamed(status_code, Box::new(reader), None)
+    }
+
+    /// Creates a response whose body of `length` bytes is read from the given reader while it is
+    ///   being sent, so that large bodies such as files never have to be held in memory.
+    ///
+    /// Since the length is known, the body is sent with a `Content-Length` rather than chunked. If
+    ///   the reader ends early or fails, the connection is closed without finishing the body, so the
+    ///   client can tell that it is incomplete. Anything after the first `length` bytes is not read.
+    ///
+    /// Any bytes added with `with_bytes` are sent before the data from the reader.
+    ///
+    /// ## Example
+    /// ```
+    /// let file = File::open("video.mp4")?;
+    /// let length = file.metadata()?.len();
+    ///
+    /// Response::from_reader(StatusCode::OK, file, length)
+    ///     .with_header(HeaderType::ContentType, "video/mp4")
+    /// ```
+    #[cfg(not(feature = "tokio"))]
+    pub fn from_reader<R>(status_code: StatusCode, reader: R, length: u64) -> Self
+    where
+        R: Read + Send + 'static,
+    {
+        Self::streamed(status_code, Box::new(reader), Some(length))
+    }
+
+    /// Creates a response whose body of `length` bytes is read from the given reader while it is
+    ///   being sent, so that large bodies such as files never have to be held in memory.
+    ///
+    /// Since the length is known, the body is sent with a `Content-Length` rather than chunked. If
+    ///   the reader ends early or fails, the connection is closed without finishing the body, so the
+    ///   client can tell that it is incomplete. Anything after the first `length` bytes is not read.
+    ///
+    /// Any bytes added with `with_bytes` are sent before the data from the reader.
+    #[cfg(feature = "tokio")]
+    pub fn from_reader<R>(status_code: StatusCode, reader: R, length: u64) -> Self
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+    {
+        Self::streamed(status_code, Box::new(reader), Some(length))
+    }
+
+    /// Creates a response which pushes server-sent events to the client, along with the sender
+    ///   through which the events are sent.
+    ///
+    /// The response has the `Content-Type: text/event-stream` and `Cache-Control: no-cache` headers,
+    ///   and `X-Accel-Buffering: no` to stop proxies such as nginx from holding events back. Each
+    ///   event is written to the connection as soon as it is sent, and the stream ends when every
+    ///   clone of the sender has been dropped. The connection is then closed instead of being kept
+    ///   alive, so a single stream never holds up other requests.
+    ///
+    /// ## Example
+    /// ```
+    /// let (response, sender) = Response::event_stream();
+    ///
+    /// std::thread::spawn(move || {
+    ///     for i in 0.. {
+    ///         if sender.send(Event::new(i.to_string())).is_err() {
+    ///             break;
+    ///         }
+    ///
+    ///         std::thread::sleep(Duration::from_secs(1));
+    ///     }
+    /// });
+    ///
+    /// response
+    /// ```
+    pub fn event_stream() -> (Self, EventSender) {
+        let (sender, reader) = event_stream();
+
+        let response = Self::streamed(StatusCode::OK, Box::new(reader), None)
+            .with_header(HeaderType::ContentType, "text/event-stream")
+            .with_header(HeaderType::CacheControl, "no-cache")
+            .with_header("X-Accel-Buffering", "no")
+            .with_header(HeaderType::Connection, "Close");
+
+        (response, sender)
+    }
+
+    /// Creates a response with the given streamed body.
+    #[cfg(not(feature = "tokio"))]
+    fn streamed(
+        status_code: StatusCode,
+        reader: Box<dyn Read + Send>,
+        length: Option<u64>,
+    ) -> Self {
+        let mut response = Self::empty(status_code);
+        response.streamed_body = Some(StreamedBody { reader, length });
+        response
+    }
+
+    /// Creates a response with the given streamed body.
+    #[cfg(feature = "tokio")]
+    fn streamed(
+        status_code: StatusCode,
+        reader: Box<dyn AsyncRead + Send + Unpin>,
+        length: Option<u64>,
+    ) -> Self {
+        let mut response = Self::empty(status_code);
+        response.streamed_body = Some(StreamedBody { reader, length });
+        response
+    }
+
     /// Adds the given header to the response.
     /// Returns itself for use in a builder pattern.
     pub fn with_header(mut self, header: impl HeaderLike, value: impl AsRef<str>) -> Self {
@@ -119,6 +408,12 @@ impl Response {
         self
     }
 
+    /// Sets the `ETag` header of the response to the given entity tag.
+    /// Returns itself for use in a builder pattern.
+    pub fn with_etag(self, etag: ETag) -> Self {
+        self.with_header(HeaderType::ETag, etag.to_string())
+    }
+
     /// Appends the given bytes to the body.
     /// Returns itself for use in a builder pattern.
     pub fn with_bytes<T>(mut self, bytes: T) -> Self
@@ -129,118 +424,481 @@ impl Response {
         self
     }
 
+    /// Hands the connection to the given function once this response has been written.
+    /// Returns itself for use in a builder pattern.
+    ///
+    /// This allows a handler to speak a different protocol on the same connection after an
+    ///   ordinary HTTP response, for example to push raw bytes to the client. The app will not
+    ///   touch the stream again, so keep-alive does not apply and the function should close the
+    ///   connection when it is done with it.
+    ///
+    /// ## Example
+    /// ```
+    /// Response::empty(StatusCode::OK).with_hijack(|mut stream| {
+    ///     stream.write_all(b"raw bytes").ok();
+    /// })
+    /// ```
+    #[cfg(all(feature = "runtime", not(feature = "tokio")))]
+    pub fn with_hijack<F>(mut self, handler: F) -> Self
+    where
+        F: FnOnce(Stream) + Send + 'static,
+    {
+        self.hijack = Some(Hijack {
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    /// Hands the connection to the given function once this response has been written.
+    /// Returns itself for use in a builder pattern.
+    ///
+    /// This allows a handler to speak a different protocol on the same connection after an
+    ///   ordinary HTTP response, for example to push raw bytes to the client. The app will not
+    ///   touch the stream again, so keep-alive does not apply and the function should close the
+    ///   connection when it is done with it.
+    #[cfg(feature = "tokio")]
+    pub fn with_hijack<F, Fut>(mut self, handler: F) -> Self
+    where
+        F: FnOnce(Stream) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.hijack = Some(Hijack {
+            handler: Box::new(move |stream| Box::pin(handler(stream))),
+        });
+        self
+    }
+
+    /// Returns `true` if the connection will be handed to a function after this response is written.
+    pub fn is_hijacked(&self) -> bool {
+        self.hijack.is_some()
+    }
+
+    /// Returns `true` if the body of this response is streamed from a reader while it is sent.
+    pub fn is_streamed(&self) -> bool {
+        self.streamed_body.is_some()
+    }
+
+    /// Returns `true` if the end of the body will be marked by closing the connection, which is the
+    ///   case for streamed bodies of unknown length sent to HTTP/1.0 clients.
+    pub(crate) fn is_close_delimited(&self) -> bool {
+        self.has_unknown_length() && self.version == "HTTP/1.0"
+    }
+
+    /// Returns `true` if the body is streamed and its length is not known in advance.
+    fn has_unknown_length(&self) -> bool {
+        matches!(self.streamed_body, Some(StreamedBody { length: None, .. }))
+    }
+
     /// Returns a reference to the response's headers.
     pub fn get_headers(&self) -> &Headers {
         &self.headers
     }
 
+    /// Returns the name and value of every cookie set by the response, in the order they were set.
+    ///
+    /// Attributes such as `Path` and `Max-Age` are not included.
+    pub fn get_cookies(&self) -> Vec<Cookie> {
+        self.headers
+            .get_all(HeaderType::SetCookie)
+            .into_iter()
+            .filter_map(|header| Cookie::parse(header.split(';').next()?))
+            .collect()
+    }
+
     /// Returns the body as text, if possible.
     pub fn text(&self) -> Option<String> {
         String::from_utf8(self.body.clone()).ok()
     }
 
+    /// Adjusts the body and framing headers of the response before it is sent in reply to a request
+    ///   with the given method.
+    ///
+    /// `1xx` and `204 No Content` responses never have a body, so the body and any `Content-Length`
+    ///   or `Transfer-Encoding` headers are removed. A `304 Not Modified` response also has no body,
+    ///   but a `Content-Length` set by the handler is kept since it describes the cached entity.
+    ///   Responses to `HEAD` requests keep the `Content-Length` of the body they would have had, but
+    ///   the body itself is not sent. Otherwise, `Content-Length` is added if it is missing.
+    ///
+    /// Streamed bodies of known length are given a `Content-Length` which includes any bytes added
+    ///   before them, and those of unknown length are sent using chunked transfer encoding. Either is
+    ///   declared even in reply to `HEAD` requests, and the body is discarded if the response cannot
+    ///   have one.
+    #[cfg(feature = "runtime")]
+    pub(crate) fn finalise_framing(&mut self, method: &Method) {
+        if let Some(streamed_body) = &self.streamed_body {
+            self.headers.remove(HeaderType::ContentLength);
+            self.headers.remove(HeaderType::TransferEncoding);
+
+            if !self.status_code.has_no_body() {
+                match streamed_body.length {
+                    Some(length) => self.headers.add(
+                        HeaderType::ContentLength,
+                        (self.body.len() as u64 + length).to_string(),
+                    ),
+                    None => self.headers.add(HeaderType::TransferEncoding, "chunked"),
+                }
+
+                if *method != Method::Head {
+                    return;
+                }
+            }
+
+            self.streamed_body = None;
+        } else if self.status_code == StatusCode::NotModified {
+            self.headers.remove(HeaderType::TransferEncoding);
+        } else if self.status_code.has_no_body() {
+            self.headers.remove(HeaderType::ContentLength);
+            self.headers.remove(HeaderType::TransferEncoding);
+        } else {
+            if self.headers.get(HeaderType::ContentLength).is_none() {
+                self.headers
+                    .add(HeaderType::ContentLength, self.body.len().to_string());
+            }
+
+            if *method != Method::Head {
+                return;
+            }
+        }
+
+        self.body.clear();
+        self.trailers = Headers::new();
+    }
+
+    /// Adjusts the response for an HTTP/1.0 client, which does not understand chunked transfer
+    ///   encoding and only keeps the connection open if it asked to.
+    ///
+    /// Trailers and `Transfer-Encoding` are removed so the body is always sent with a
+    ///   `Content-Length`, and `Connection: Close` is sent unless `keep_alive` is true. Streamed
+    ///   bodies of unknown length are delimited by closing the connection instead. This should be
+    ///   called after `finalise_framing`.
+    #[cfg(feature = "runtime")]
+    pub(crate) fn finalise_http_1_0(&mut self, keep_alive: bool) {
+        self.version = "HTTP/1.0".into();
+        self.trailers = Headers::new();
+        self.headers.remove(HeaderType::TransferEncoding);
+
+        if !self.status_code.has_no_body()
+            && self.streamed_body.is_none()
+            && self.headers.get(HeaderType::ContentLength).is_none()
+        {
+            self.headers
+                .add(HeaderType::ContentLength, self.body.len().to_string());
+        }
+
+        if !keep_alive || self.has_unknown_length() {
+            self.headers.remove(HeaderType::Connection);
+            self.headers.add(HeaderType::Connection, "Close");
+        }
+    }
+
     /// Attempts to read and parse one HTTP response from the given stream.
     ///
-    /// Converts chunked transfer encoding into a regular body.
+    /// Converts chunked transfer encoding into a regular body. This assumes that the response is to
+    ///   a request which is not `HEAD`, so see `from_stream_for` if it might be.
     pub fn from_stream<T>(stream: &mut T) -> Result<Self, ResponseError>
     where
         T: Read,
     {
-        let mut reader = BufReader::new(stream);
-        let mut start_line_buf: Vec<u8> = Vec::new();
-        reader
-            .read_until(0xA, &mut start_line_buf)
-            .map_err(|_| ResponseError::Stream)?;
-
-        let start_line_string =
-            String::from_utf8(start_line_buf).map_err(|_| ResponseError::Response)?;
-        let start_line: Vec<&str> = start_line_string.splitn(3, ' ').collect();
-
-        safe_assert(start_line.len() == 3)?;
+        Self::from_stream_for(stream, &Method::Get)
+    }
 
-        let version = start_line[0].to_string();
-        let status_code: u16 = start_line[1].parse().map_err(|_| ResponseError::Response)?;
-        let status = StatusCode::try_from(status_code).map_err(|_| ResponseError::Response)?;
+    /// Attempts to read and parse one HTTP response to a request with the given method from the
+    ///   given stream.
+    ///
+    /// Converts chunked transfer encoding into a regular body. Responses to `HEAD` requests, `1xx`,
+    ///   `204 No Content` and `304 Not Modified` responses never have a body, even if they declare its
+    ///   length. Otherwise, a response with neither `Content-Length` nor chunked transfer encoding has
+    ///   a body which ends when the connection is closed.
+    ///
+    /// Interim `1xx` responses other than `101 Switching Protocols`, such as `100 Continue` or
+    ///   `103 Early Hints`, are skipped even if they were not asked for, and the final response which
+    ///   follows them is returned. If the stream ends part of the way through the response, a
+    ///   `ResponseError::Stream` is returned rather than a partial response. If the body is larger
+    ///   than `MAX_RESPONSE_BODY_SIZE`, a line is longer than `MAX_RESPONSE_LINE_LENGTH` or there are
+    ///   more than `MAX_RESPONSE_HEADERS` headers, a `ResponseError::Response` is returned.
+    ///
+    /// The stream is read through a buffer which is dropped afterwards, so anything sent after the
+    ///   response is lost. Use `from_buffered_stream_for` to read pipelined responses.
+    pub fn from_stream_for<T>(stream: &mut T, method: &Method) -> Result<Self, ResponseError>
+    where
+        T: Read,
+    {
+        Self::from_buffered_stream_for(&mut BufReader::new(stream), method)
+    }
 
-        let mut headers = Headers::new();
+    /// Attempts to read and parse one HTTP response to a request with the given method from the
+    ///   given buffered stream, in the same way as `from_stream_for`.
+    ///
+    /// Only the response is consumed, so anything sent after it, such as the response to a
+    ///   pipelined request, stays in the buffer and can be read by calling this again.
+    pub fn from_buffered_stream_for<R>(
+        reader: &mut R,
+        method: &Method,
+    ) -> Result<Self, ResponseError>
+    where
+        R: BufRead,
+    {
+        let (version, status, mut headers) = loop {
+            let (version, status, headers) = parse_head(reader)?;
+            let code = u16::from(status);
 
-        loop {
-            let mut line_buf: Vec<u8> = Vec::new();
-            reader
-                .read_until(0xA, &mut line_buf)
-                .map_err(|_| ResponseError::Stream)?;
-            let line = String::from_utf8(line_buf).map_err(|_| ResponseError::Response)?;
-
-            if line == "\r\n" {
-                break;
-            } else {
-                safe_assert(line.len() >= 2)?;
-                let line_without_crlf = &line[0..line.len() - 2];
-                let line_parts: Vec<&str> = line_without_crlf.splitn(2, ':').collect();
-                headers.add(HeaderType::from(line_parts[0]), line_parts[1].trim_start());
+            if !(100..200).contains(&code) || code == 101 {
+                break (version, status, headers);
             }
-        }
+        };
+
+        let mut body: Vec<u8> = Vec::new();
+        let mut trailers = Headers::new();
 
-        if headers
+        if *method == Method::Head || status.has_no_body() {
+            // There is no body, even if the length of the entity is declared
+        } else if headers
             .get(&HeaderType::TransferEncoding)
-            .and_then(|te| if te == "chunked" { Some(()) } else { None })
-            .is_some()
+            .is_some_and(is_chunked)
         {
-            let mut body: Vec<u8> = Vec::new();
-
-            while let Some(chunk) = parse_chunk(&mut reader) {
+            while let Some(chunk) = parse_chunk(reader, MAX_RESPONSE_BODY_SIZE - body.len())? {
                 body.extend(chunk);
             }
 
+            trailers = parse_trailers(reader)?;
+
             headers.remove(&HeaderType::TransferEncoding);
             headers.add(HeaderType::ContentLength, body.len().to_string());
-
-            Ok(Self {
-                version,
-                status_code: status,
-                headers,
-                body,
-            })
         } else if let Some(content_length) = headers.get(&HeaderType::ContentLength) {
             let content_length: usize = content_length
+                .trim()
                 .parse()
                 .map_err(|_| ResponseError::Response)?;
-            let mut content_buf: Vec<u8> = vec![0u8; content_length];
+            safe_assert(content_length <= MAX_RESPONSE_BODY_SIZE)?;
+            read_exactly(reader, &mut body, content_length)?;
+        } else {
             reader
-                .read_exact(&mut content_buf)
+                .by_ref()
+                .take(MAX_RESPONSE_BODY_SIZE as u64 + 1)
+                .read_to_end(&mut body)
                 .map_err(|_| ResponseError::Stream)?;
-
-            Ok(Self {
-                version,
-                status_code: status,
-                headers,
-                body: content_buf,
-            })
-        } else {
-            Ok(Self {
-                version,
-                status_code: status,
-                headers,
-                body: Vec::new(),
-            })
+            safe_assert(body.len() <= MAX_RESPONSE_BODY_SIZE)?;
         }
+
+        Ok(Self {
+            version,
+            status_code: status,
+            headers,
+            body,
+            trailers,
+            hijack: None,
+            streamed_body: None,
+        })
     }
 }
 
-impl From<Response> for Vec<u8> {
-    fn from(val: Response) -> Self {
+impl Hijack {
+    /// Gives the stream to the hijacking function.
+    #[cfg(all(feature = "runtime", not(feature = "tokio")))]
+    pub(crate) fn run(self, stream: Stream) {
+        (self.handler)(stream)
+    }
+
+    /// Gives the stream to the hijacking function.
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn run(self, stream: Stream) {
+        (self.handler)(stream).await
+    }
+}
+
+impl std::fmt::Debug for Hijack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Hijack").finish_non_exhaustive()
+    }
+}
+
+impl Response {
+    /// Writes the response to the writer, reading and sending a streamed body as it goes.
+    ///
+    /// If the body is streamed, the head of the response is written first and then each chunk is
+    ///   written and flushed as soon as it is read. Otherwise, the whole response is written at once.
+    #[cfg(not(feature = "tokio"))]
+    pub(crate) fn write_to<W>(mut self, writer: &mut W) -> std::io::Result<()>
+    where
+        W: Write,
+    {
+        let (mut bytes, streamed_body, framing) = self.take_head();
+
+        if let Some(StreamedBody { mut reader, length }) = streamed_body {
+            writer.write_all(&bytes)?;
+            bytes.clear();
+
+            let mut buf = vec![0; STREAMED_CHUNK_SIZE];
+            let mut remaining = length;
+
+            while remaining != Some(0) {
+                let limit = remaining.map_or(buf.len(), |r| r.min(buf.len() as u64) as usize);
+
+                let length = match reader.read(&mut buf[..limit]) {
+                    Ok(0) if remaining.is_some() => {
+                        return Err(std::io::ErrorKind::UnexpectedEof.into())
+                    }
+                    Ok(0) => break,
+                    Ok(length) => length,
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                };
+
+                if let Some(remaining) = &mut remaining {
+                    *remaining -= length as u64;
+                }
+
+                framing.write_data(&mut bytes, &buf[..length]);
+                writer.write_all(&bytes)?;
+                writer.flush()?;
+                bytes.clear();
+            }
+        }
+
+        framing.write_end(&mut bytes);
+        writer.write_all(&bytes)
+    }
+
+    /// Writes the response to the writer, reading and sending a streamed body as it goes.
+    ///
+    /// If the body is streamed, the head of the response is written first and then each chunk is
+    ///   written and flushed as soon as it is read. Otherwise, the whole response is written at once.
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn write_to<W>(mut self, writer: &mut W) -> std::io::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let (mut bytes, streamed_body, framing) = self.take_head();
+
+        if let Some(StreamedBody { mut reader, length }) = streamed_body {
+            writer.write_all(&bytes).await?;
+            bytes.clear();
+
+            let mut buf = vec![0; STREAMED_CHUNK_SIZE];
+            let mut remaining = length;
+
+            while remaining != Some(0) {
+                let limit = remaining.map_or(buf.len(), |r| r.min(buf.len() as u64) as usize);
+
+                let length = match reader.read(&mut buf[..limit]).await {
+                    Ok(0) if remaining.is_some() => {
+                        return Err(std::io::ErrorKind::UnexpectedEof.into())
+                    }
+                    Ok(0) => break,
+                    Ok(length) => length,
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                };
+
+                if let Some(remaining) = &mut remaining {
+                    *remaining -= length as u64;
+                }
+
+                framing.write_data(&mut bytes, &buf[..length]);
+                writer.write_all(&bytes).await?;
+                writer.flush().await?;
+                bytes.clear();
+            }
+        }
+
+        framing.write_end(&mut bytes);
+        writer.write_all(&bytes).await
+    }
+
+    /// Reads the whole of a streamed body into `body`, so that the response can be handled like
+    ///   any other.
+    #[cfg(all(feature = "runtime", not(feature = "tokio")))]
+    pub(crate) fn read_streamed_body(&mut self) -> std::io::Result<()> {
+        if let Some(StreamedBody { mut reader, length }) = self.streamed_body.take() {
+            match length {
+                Some(length) => {
+                    let start = self.body.len();
+                    (&mut reader).take(length).read_to_end(&mut self.body)?;
+
+                    if ((self.body.len() - start) as u64) < length {
+                        return Err(std::io::ErrorKind::UnexpectedEof.into());
+                    }
+                }
+                None => {
+                    reader.read_to_end(&mut self.body)?;
+                }
+            }
+
+            self.headers.remove(HeaderType::TransferEncoding);
+            self.headers.remove(HeaderType::ContentLength);
+        }
+
+        Ok(())
+    }
+
+    /// Reads the whole of a streamed body into `body`, so that the response can be handled like
+    ///   any other.
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn read_streamed_body(&mut self) -> std::io::Result<()> {
+        if let Some(StreamedBody { mut reader, length }) = self.streamed_body.take() {
+            match length {
+                Some(length) => {
+                    let start = self.body.len();
+                    (&mut reader)
+                        .take(length)
+                        .read_to_end(&mut self.body)
+                        .await?;
+
+                    if ((self.body.len() - start) as u64) < length {
+                        return Err(std::io::ErrorKind::UnexpectedEof.into());
+                    }
+                }
+                None => {
+                    reader.read_to_end(&mut self.body).await?;
+                }
+            }
+
+            self.headers.remove(HeaderType::TransferEncoding);
+            self.headers.remove(HeaderType::ContentLength);
+        }
+
+        Ok(())
+    }
+
+    /// Serialises the head of the response and the start of its body, taking the streamed body
+    ///   which must then be written with the returned framing.
+    fn take_head(&mut self) -> (Vec<u8>, Option<StreamedBody>, Framing) {
+        let trailers: Vec<Header> = self
+            .trailers
+            .iter()
+            .filter(|trailer| is_permitted_trailer(&trailer.name))
+            .collect();
+        let close_delimited = self.is_close_delimited();
+        let chunked = !close_delimited && (self.has_unknown_length() || !trailers.is_empty());
+        let streamed_body = self.streamed_body.take();
+
+        // Trailers can only follow a chunked body, so change the framing if there are any
+        if chunked || close_delimited {
+            self.headers.remove(HeaderType::ContentLength);
+            self.headers.remove(HeaderType::TransferEncoding);
+        }
+
+        if chunked {
+            self.headers.add(HeaderType::TransferEncoding, "chunked");
+
+            if !trailers.is_empty() && self.headers.get(HeaderType::Trailer).is_none() {
+                let names: Vec<String> = trailers.iter().map(|t| t.name.to_string()).collect();
+                self.headers.add(HeaderType::Trailer, names.join(", "));
+            }
+        }
+
         let status_line = format!(
             "{} {} {}",
-            val.version,
-            Into::<u16>::into(val.status_code),
-            Into::<&str>::into(val.status_code)
+            self.version,
+            Into::<u16>::into(self.status_code),
+            Into::<&str>::into(self.status_code)
         );
 
         let mut bytes: Vec<u8> =
-            Vec::with_capacity(status_line.len() + val.body.len() + val.headers.len() * 32);
+            Vec::with_capacity(status_line.len() + self.body.len() + self.headers.len() * 32);
         bytes.extend(status_line.as_bytes());
 
-        for header in val.get_headers().iter() {
+        for header in self.get_headers().iter() {
             bytes.extend(b"\r\n");
             bytes.extend(header.name.to_string().as_bytes());
             bytes.extend(b": ");
@@ -249,34 +907,276 @@ impl From<Response> for Vec<u8> {
 
         bytes.extend(b"\r\n\r\n");
 
-        if !val.body.is_empty() {
-            bytes.extend(val.body);
-            bytes.extend(b"\r\n");
+        let framing = match (close_delimited, chunked) {
+            (true, _) => Framing::Close,
+            (false, true) => Framing::Chunked(trailers),
+            (false, false) => Framing::Length,
+        };
+
+        framing.write_data(&mut bytes, &std::mem::take(&mut self.body));
+
+        (bytes, streamed_body, framing)
+    }
+}
+
+impl Framing {
+    /// Appends part of the body to the buffer.
+    fn write_data(&self, buf: &mut Vec<u8>, data: &[u8]) {
+        match self {
+            Framing::Chunked(_) => write_chunk(buf, data),
+            Framing::Length | Framing::Close => buf.extend(data),
         }
+    }
+
+    /// Appends whatever follows the body to the buffer.
+    fn write_end(self, buf: &mut Vec<u8>) {
+        match self {
+            Framing::Chunked(trailers) => {
+                buf.extend(b"0\r\n");
+
+                for trailer in trailers {
+                    buf.extend(trailer.name.to_string().as_bytes());
+                    buf.extend(b": ");
+                    buf.extend(trailer.value.as_bytes());
+                    buf.extend(b"\r\n");
+                }
+
+                buf.extend(b"\r\n");
+            }
+            Framing::Length | Framing::Close => (),
+        }
+    }
+}
+
+impl From<Response> for Vec<u8> {
+    /// Serialises the response, reading the whole of a streamed body.
+    ///
+    /// If reading a streamed body fails, the bytes stop where the failure occurred.
+    #[cfg(not(feature = "tokio"))]
+    fn from(val: Response) -> Self {
+        let mut bytes: Vec<u8> = Vec::new();
+        val.write_to(&mut bytes).ok();
+        bytes
+    }
 
+    /// Serialises the response.
+    ///
+    /// A streamed body cannot be read without blocking, so it is left out. Responses with streamed
+    ///   bodies are only sent in full when they are returned from a handler.
+    #[cfg(feature = "tokio")]
+    fn from(mut val: Response) -> Self {
+        let (mut bytes, _, framing) = val.take_head();
+        framing.write_end(&mut bytes);
         bytes
     }
 }
 
-/// Parses a chunk using the chunked transfer encoding.
-fn parse_chunk<T>(stream: &mut BufReader<T>) -> Option<Vec<u8>>
+impl std::fmt::Debug for StreamedBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamedBody")
+            .field("length", &self.length)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Appends the data to the buffer as one chunk of a chunked body, unless it is empty, since an
+///   empty chunk would mark the end of the body.
+fn write_chunk(buf: &mut Vec<u8>, data: &[u8]) {
+    if !data.is_empty() {
+        buf.extend(format!("{:x}\r\n", data.len()).as_bytes());
+        buf.extend(data);
+        buf.extend(b"\r\n");
+    }
+}
+
+/// Parses the status line and headers of a response.
+///
+/// The reason phrase is optional, and lines may end with a bare line feed. Header values which are
+///   not valid UTF-8 are decoded lossily rather than rejecting the response.
+fn parse_head<R>(reader: &mut R) -> Result<(String, StatusCode, Headers), ResponseError>
+where
+    R: BufRead,
+{
+    let mut start_line_buf: Vec<u8> = Vec::new();
+    read_line(reader, &mut start_line_buf)?;
+
+    let start_line_string =
+        String::from_utf8(start_line_buf).map_err(|_| ResponseError::Response)?;
+    let mut start_line = strip_line_ending(&start_line_string).splitn(3, ' ');
+
+    let version = start_line.next().unwrap_or_default().to_string();
+    let status_code = start_line.next().ok_or(ResponseError::Response)?;
+
+    safe_assert(version.starts_with("HTTP/"))?;
+    safe_assert(status_code.len() == 3 && status_code.bytes().all(|b| b.is_ascii_digit()))?;
+
+    let status_code: u16 = status_code.parse().map_err(|_| ResponseError::Response)?;
+    let status = StatusCode::try_from(status_code).map_err(|_| ResponseError::Response)?;
+
+    let mut headers = Headers::new();
+
+    loop {
+        let mut line_buf: Vec<u8> = Vec::new();
+        read_line(reader, &mut line_buf)?;
+        let line = String::from_utf8_lossy(&line_buf);
+        let line = strip_line_ending(&line);
+
+        if line.is_empty() {
+            break;
+        }
+
+        safe_assert(headers.len() < MAX_RESPONSE_HEADERS)?;
+        let (name, value) = line.split_once(':').ok_or(ResponseError::Response)?;
+
+        // Each occurrence is stored separately, so repeated headers like `Set-Cookie` are not merged
+        headers.add(HeaderType::from(name), value.trim());
+    }
+
+    Ok((version, status, headers))
+}
+
+/// Reads one line, including its line ending, into the buffer, returning an error if the stream
+///   ends before the line is complete.
+fn read_line<R>(reader: &mut R, buf: &mut Vec<u8>) -> Result<(), ResponseError>
+where
+    R: BufRead,
+{
+    read_limited_line(reader, buf)?;
+
+    match buf.last() {
+        Some(0xA) => Ok(()),
+        _ => Err(ResponseError::Stream),
+    }
+}
+
+/// Reads up to the end of the line or of the stream into the buffer, returning an error if the line
+///   is longer than `MAX_RESPONSE_LINE_LENGTH`.
+fn read_limited_line<R>(reader: &mut R, buf: &mut Vec<u8>) -> Result<(), ResponseError>
+where
+    R: BufRead,
+{
+    reader
+        .take(MAX_RESPONSE_LINE_LENGTH as u64 + 1)
+        .read_until(0xA, buf)
+        .map_err(|_| ResponseError::Stream)?;
+
+    safe_assert(buf.len() <= MAX_RESPONSE_LINE_LENGTH)
+}
+
+/// Reads exactly `length` bytes onto the end of the buffer, returning an error if the stream ends
+///   first.
+///
+/// The buffer grows as the data arrives rather than being allocated up front, so a declared length
+///   which is never sent does not allocate memory.
+fn read_exactly<T>(reader: &mut T, buf: &mut Vec<u8>, length: usize) -> Result<(), ResponseError>
 where
     T: Read,
+{
+    let read = reader
+        .take(length as u64)
+        .read_to_end(buf)
+        .map_err(|_| ResponseError::Stream)?;
+
+    if read == length {
+        Ok(())
+    } else {
+        Err(ResponseError::Stream)
+    }
+}
+
+/// Removes the line feed and any carriage return from the end of the line.
+fn strip_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+/// Returns `true` if the value of a `Transfer-Encoding` header shows that the body is chunked, which
+///   is the case when chunked is the last coding applied.
+fn is_chunked(transfer_encoding: &str) -> bool {
+    transfer_encoding
+        .rsplit(',')
+        .next()
+        .is_some_and(|coding| coding.trim().eq_ignore_ascii_case("chunked"))
+}
+
+/// Parses a chunk using the chunked transfer encoding, returning `None` once the last chunk has
+///   been read. Chunks longer than `max_length` are rejected.
+fn parse_chunk<R>(stream: &mut R, max_length: usize) -> Result<Option<Vec<u8>>, ResponseError>
+where
+    R: BufRead,
 {
     let mut length_line_buf: Vec<u8> = Vec::new();
-    stream.read_until(0xA, &mut length_line_buf).ok()?;
-    let length: usize =
-        usize::from_str_radix(std::str::from_utf8(&length_line_buf).ok()?.trim_end(), 16).ok()?;
+    read_line(stream, &mut length_line_buf)?;
+    let length_line = std::str::from_utf8(&length_line_buf).map_err(|_| ResponseError::Response)?;
+    let length_string = length_line.split(';').next().unwrap_or_default().trim();
+    let length = usize::from_str_radix(length_string, 16).map_err(|_| ResponseError::Response)?;
 
     if length == 0 {
-        stream.read_exact(&mut [0u8, 0]).ok()?;
-        None
+        Ok(None)
     } else {
-        let mut content_buf: Vec<u8> = vec![0u8; length];
-        stream.read_exact(&mut content_buf).ok()?;
-        stream.read_exact(&mut [0u8, 0]).ok()?;
-        Some(content_buf)
+        safe_assert(length <= max_length)?;
+
+        let mut content_buf: Vec<u8> = Vec::new();
+        read_exactly(stream, &mut content_buf, length)?;
+
+        let mut line_ending: Vec<u8> = Vec::new();
+        read_line(stream, &mut line_ending)?;
+        safe_assert(strip_line_ending(&String::from_utf8_lossy(&line_ending)).is_empty())?;
+
+        Ok(Some(content_buf))
+    }
+}
+
+/// Parses the trailer section which follows the last chunk of a chunked body, consuming the
+///   blank line which ends the message.
+///
+/// Trailers which could be used to change how the message is framed are dropped.
+fn parse_trailers<R>(stream: &mut R) -> Result<Headers, ResponseError>
+where
+    R: BufRead,
+{
+    let mut trailers = Headers::new();
+
+    for count in 0.. {
+        let mut line_buf: Vec<u8> = Vec::new();
+        read_limited_line(stream, &mut line_buf)?;
+        let line = String::from_utf8_lossy(&line_buf);
+        let line = strip_line_ending(&line);
+
+        if line.is_empty() {
+            break;
+        }
+
+        safe_assert(count < MAX_RESPONSE_HEADERS)?;
+        let (name, value) = line.split_once(':').ok_or(ResponseError::Response)?;
+        let name = HeaderType::from(name);
+
+        if is_permitted_trailer(&name) {
+            trailers.add(name, value.trim());
+        }
     }
+
+    Ok(trailers)
+}
+
+/// Returns `true` if the header is allowed to be sent as a trailer.
+///
+/// Headers which control framing, routing or the interpretation of the body must not be sent after
+///   the body, since a recipient which merges trailers into the headers could otherwise be tricked
+///   into handling the message differently to the sender.
+pub(crate) fn is_permitted_trailer(name: &HeaderType) -> bool {
+    !matches!(
+        name,
+        HeaderType::ContentLength
+            | HeaderType::TransferEncoding
+            | HeaderType::Trailer
+            | HeaderType::ContentEncoding
+            | HeaderType::ContentType
+            | HeaderType::Host
+            | HeaderType::Connection
+            | HeaderType::Authorization
+            | HeaderType::SetCookie
+    )
 }
 
 /// Asserts that the condition is true, returning a `Result`.
@@ -1,10 +1,15 @@
 //! Provides functionality for handling HTTP status codes.
 
 use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt::Display;
 
 /// Represents an HTTP status code.
 /// Can be converted to and from both `u16` and `&str`.
 ///
+/// Any code from 100 to 599 can be represented, with codes which have no variant of their own
+///   becoming `StatusCode::Custom`.
+///
 /// ## Example
 /// ```
 /// let status = StatusCode::NotFound;
@@ -22,6 +27,10 @@ pub enum StatusCode {
     Continue,
     /// `101 Switching Protocols`: Protocol upgrade.
     SwitchingProtocols,
+    /// `102 Processing`: The request has been received and is being processed, but no response is available yet.
+    Processing,
+    /// `103 Early Hints`: Lets the client start preloading resources while the server prepares a response.
+    EarlyHints,
     /// `200 OK`: Request succeeded.
     OK,
     /// `201 Created`: Resource created.
@@ -36,6 +45,12 @@ pub enum StatusCode {
     ResetContent,
     /// `206 Partial Content`: This response only contains part of a resource.
     PartialContent,
+    /// `207 Multi-Status`: The response contains the statuses of several independent operations.
+    MultiStatus,
+    /// `208 Already Reported`: The members of a binding have already been listed in a previous part of the response.
+    AlreadyReported,
+    /// `226 IM Used`: The response is the result of instance-manipulations applied to the current instance.
+    IMUsed,
     /// `300 Multiple Choice`: The request has multiple possible responses.
     MultipleChoices,
     /// `301 Moved Permanently`: The resource has moved permanently to a new location.
@@ -50,10 +65,14 @@ pub enum StatusCode {
     UseProxy,
     /// `307 Temporary Redirect`: The resource has moved temporarily to a new location.
     TemporaryRedirect,
+    /// `308 Permanent Redirect`: The resource has moved permanently to a new location, and the method must not change.
+    PermanentRedirect,
     /// `400 Bad Request`: The request could not be understood by the server.
     BadRequest,
     /// `401 Unauthorized`: The request requires user authentication.
     Unauthorized,
+    /// `402 Payment Required`: Reserved for future use.
+    PaymentRequired,
     /// `403 Forbidden`: The client is not allowed to access this content.
     Forbidden,
     /// `404 Not Found`: The server can not find the requested resource.
@@ -84,6 +103,28 @@ pub enum StatusCode {
     RequestedRangeNotSatisfiable,
     /// `417 Expectation Failed`: The expectation given in the `Expect` header could not be met by the server.
     ExpectationFailed,
+    /// `418 I'm a teapot`: The server refuses to brew coffee because it is a teapot.
+    ImATeapot,
+    /// `421 Misdirected Request`: The request was directed at a server which is not able to produce a response.
+    MisdirectedRequest,
+    /// `422 Unprocessable Entity`: The request was well-formed but could not be processed because of semantic errors.
+    UnprocessableEntity,
+    /// `423 Locked`: The resource which is being accessed is locked.
+    Locked,
+    /// `424 Failed Dependency`: The request failed because it depended on another request which failed.
+    FailedDependency,
+    /// `425 Too Early`: The server is unwilling to process a request which might be replayed.
+    TooEarly,
+    /// `426 Upgrade Required`: The client must switch to a different protocol to perform the request.
+    UpgradeRequired,
+    /// `428 Precondition Required`: The server requires the request to be conditional.
+    PreconditionRequired,
+    /// `429 Too Many Requests`: The client has sent too many requests in a given amount of time.
+    TooManyRequests,
+    /// `431 Request Header Fields Too Large`: The request's headers are too large for the server to process.
+    RequestHeaderFieldsTooLarge,
+    /// `451 Unavailable For Legal Reasons`: The resource cannot legally be provided.
+    UnavailableForLegalReasons,
     /// `500 Internal Server Error`: The server encountered an unexpected error which prevented it from fulfilling the request.
     InternalError,
     /// `501 Not Implemented`: The server does not support the functionality required to fulfill the request.
@@ -96,17 +137,55 @@ pub enum StatusCode {
     GatewayTimeout,
     /// `505 HTTP Version Not Supported`: The server does not support the HTTP protocol version used in the request.
     VersionNotSupported,
+    /// `506 Variant Also Negotiates`: The server has an internal configuration error in its content negotiation.
+    VariantAlsoNegotiates,
+    /// `507 Insufficient Storage`: The server is unable to store the representation needed to complete the request.
+    InsufficientStorage,
+    /// `508 Loop Detected`: The server detected an infinite loop while processing the request.
+    LoopDetected,
+    /// `510 Not Extended`: Further extensions to the request are required for the server to fulfill it.
+    NotExtended,
+    /// `511 Network Authentication Required`: The client needs to authenticate to gain network access.
+    NetworkAuthenticationRequired,
+    /// Any other status code from 100 to 599, such as a vendor-specific code.
+    ///
+    /// This should only be used for codes without a variant of their own, since it is not equal to
+    ///   that variant. Converting from a `u16` always chooses the named variant where there is one.
+    /// The reason phrase is the generic phrase for the class of the code, for example "Client Error".
+    Custom(u16),
+}
+
+impl StatusCode {
+    /// Returns `true` if a response with this status code never has a body, which is the case for
+    ///   `1xx`, `204 No Content` and `304 Not Modified` responses.
+    pub fn has_no_body(&self) -> bool {
+        let code = u16::from(*self);
+
+        (100..200).contains(&code) || code == 204 || code == 304
+    }
 }
 
 /// Represents an error with the status code.
-#[derive(PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct StatusCodeError;
 
+impl Display for StatusCodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Status code error: the status code is not supported")
+    }
+}
+
+impl Error for StatusCodeError {}
+
 impl TryFrom<u16> for StatusCode {
+    // Codes with a variant of their own are matched before the range which covers every other code
+    #[allow(clippy::match_overlapping_arm)]
     fn try_from(code: u16) -> Result<Self, StatusCodeError> {
         match code {
             100 => Ok(StatusCode::Continue),
             101 => Ok(StatusCode::SwitchingProtocols),
+            102 => Ok(StatusCode::Processing),
+            103 => Ok(StatusCode::EarlyHints),
             200 => Ok(StatusCode::OK),
             201 => Ok(StatusCode::Created),
             202 => Ok(StatusCode::Accepted),
@@ -114,6 +193,9 @@ impl TryFrom<u16> for StatusCode {
             204 => Ok(StatusCode::NoContent),
             205 => Ok(StatusCode::ResetContent),
             206 => Ok(StatusCode::PartialContent),
+            207 => Ok(StatusCode::MultiStatus),
+            208 => Ok(StatusCode::AlreadyReported),
+            226 => Ok(StatusCode::IMUsed),
             300 => Ok(StatusCode::MultipleChoices),
             301 => Ok(StatusCode::MovedPermanently),
             302 => Ok(StatusCode::Found),
@@ -121,8 +203,10 @@ impl TryFrom<u16> for StatusCode {
             304 => Ok(StatusCode::NotModified),
             305 => Ok(StatusCode::UseProxy),
             307 => Ok(StatusCode::TemporaryRedirect),
+            308 => Ok(StatusCode::PermanentRedirect),
             400 => Ok(StatusCode::BadRequest),
             401 => Ok(StatusCode::Unauthorized),
+            402 => Ok(StatusCode::PaymentRequired),
             403 => Ok(StatusCode::Forbidden),
             404 => Ok(StatusCode::NotFound),
             405 => Ok(StatusCode::MethodNotAllowed),
@@ -138,12 +222,29 @@ impl TryFrom<u16> for StatusCode {
             415 => Ok(StatusCode::UnsupportedMediaType),
             416 => Ok(StatusCode::RequestedRangeNotSatisfiable),
             417 => Ok(StatusCode::ExpectationFailed),
+            418 => Ok(StatusCode::ImATeapot),
+            421 => Ok(StatusCode::MisdirectedRequest),
+            422 => Ok(StatusCode::UnprocessableEntity),
+            423 => Ok(StatusCode::Locked),
+            424 => Ok(StatusCode::FailedDependency),
+            425 => Ok(StatusCode::TooEarly),
+            426 => Ok(StatusCode::UpgradeRequired),
+            428 => Ok(StatusCode::PreconditionRequired),
+            429 => Ok(StatusCode::TooManyRequests),
+            431 => Ok(StatusCode::RequestHeaderFieldsTooLarge),
+            451 => Ok(StatusCode::UnavailableForLegalReasons),
             500 => Ok(StatusCode::InternalError),
             501 => Ok(StatusCode::NotImplemented),
             502 => Ok(StatusCode::BadGateway),
             503 => Ok(StatusCode::ServiceUnavailable),
             504 => Ok(StatusCode::GatewayTimeout),
             505 => Ok(StatusCode::VersionNotSupported),
+            506 => Ok(StatusCode::VariantAlsoNegotiates),
+            507 => Ok(StatusCode::InsufficientStorage),
+            508 => Ok(StatusCode::LoopDetected),
+            510 => Ok(StatusCode::NotExtended),
+            511 => Ok(StatusCode::NetworkAuthenticationRequired),
+            100..=599 => Ok(StatusCode::Custom(code)),
             _ => Err(StatusCodeError),
         }
     }
@@ -156,6 +257,8 @@ impl From<StatusCode> for u16 {
         match val {
             StatusCode::Continue => 100,
             StatusCode::SwitchingProtocols => 101,
+            StatusCode::Processing => 102,
+            StatusCode::EarlyHints => 103,
             StatusCode::OK => 200,
             StatusCode::Created => 201,
             StatusCode::Accepted => 202,
@@ -163,6 +266,9 @@ impl From<StatusCode> for u16 {
             StatusCode::NoContent => 204,
             StatusCode::ResetContent => 205,
             StatusCode::PartialContent => 206,
+            StatusCode::MultiStatus => 207,
+            StatusCode::AlreadyReported => 208,
+            StatusCode::IMUsed => 226,
             StatusCode::MultipleChoices => 300,
             StatusCode::MovedPermanently => 301,
             StatusCode::Found => 302,
@@ -170,8 +276,10 @@ impl From<StatusCode> for u16 {
             StatusCode::NotModified => 304,
             StatusCode::UseProxy => 305,
             StatusCode::TemporaryRedirect => 307,
+            StatusCode::PermanentRedirect => 308,
             StatusCode::BadRequest => 400,
             StatusCode::Unauthorized => 401,
+            StatusCode::PaymentRequired => 402,
             StatusCode::Forbidden => 403,
             StatusCode::NotFound => 404,
             StatusCode::MethodNotAllowed => 405,
@@ -187,12 +295,29 @@ impl From<StatusCode> for u16 {
             StatusCode::UnsupportedMediaType => 415,
             StatusCode::RequestedRangeNotSatisfiable => 416,
             StatusCode::ExpectationFailed => 417,
+            StatusCode::ImATeapot => 418,
+            StatusCode::MisdirectedRequest => 421,
+            StatusCode::UnprocessableEntity => 422,
+            StatusCode::Locked => 423,
+            StatusCode::FailedDependency => 424,
+            StatusCode::TooEarly => 425,
+            StatusCode::UpgradeRequired => 426,
+            StatusCode::PreconditionRequired => 428,
+            StatusCode::TooManyRequests => 429,
+            StatusCode::RequestHeaderFieldsTooLarge => 431,
+            StatusCode::UnavailableForLegalReasons => 451,
             StatusCode::InternalError => 500,
             StatusCode::NotImplemented => 501,
             StatusCode::BadGateway => 502,
             StatusCode::ServiceUnavailable => 503,
             StatusCode::GatewayTimeout => 504,
             StatusCode::VersionNotSupported => 505,
+            StatusCode::VariantAlsoNegotiates => 506,
+            StatusCode::InsufficientStorage => 507,
+            StatusCode::LoopDetected => 508,
+            StatusCode::NotExtended => 510,
+            StatusCode::NetworkAuthenticationRequired => 511,
+            StatusCode::Custom(code) => code,
         }
     }
 }
@@ -202,6 +327,8 @@ impl From<StatusCode> for &str {
         match val {
             StatusCode::Continue => "Continue",
             StatusCode::SwitchingProtocols => "Switching Protocols",
+            StatusCode::Processing => "Processing",
+            StatusCode::EarlyHints => "Early Hints",
             StatusCode::OK => "OK",
             StatusCode::Created => "Created",
             StatusCode::Accepted => "Accepted",
@@ -209,6 +336,9 @@ impl From<StatusCode> for &str {
             StatusCode::NoContent => "No Content",
             StatusCode::ResetContent => "Reset Content",
             StatusCode::PartialContent => "Partial Content",
+            StatusCode::MultiStatus => "Multi-Status",
+            StatusCode::AlreadyReported => "Already Reported",
+            StatusCode::IMUsed => "IM Used",
             StatusCode::MultipleChoices => "Multiple Choices",
             StatusCode::MovedPermanently => "Moved Permanently",
             StatusCode::Found => "Found",
@@ -216,8 +346,10 @@ impl From<StatusCode> for &str {
             StatusCode::NotModified => "Not Modified",
             StatusCode::UseProxy => "Use Proxy",
             StatusCode::TemporaryRedirect => "Temporary Redirect",
+            StatusCode::PermanentRedirect => "Permanent Redirect",
             StatusCode::BadRequest => "Bad Request",
             StatusCode::Unauthorized => "Unauthorized",
+            StatusCode::PaymentRequired => "Payment Required",
             StatusCode::Forbidden => "Forbidden",
             StatusCode::NotFound => "Not Found",
             StatusCode::MethodNotAllowed => "Method Not Allowed",
@@ -233,12 +365,35 @@ impl From<StatusCode> for &str {
             StatusCode::UnsupportedMediaType => "Unsupported Media Type",
             StatusCode::RequestedRangeNotSatisfiable => "Requested Range Not Satisfiable",
             StatusCode::ExpectationFailed => "Expectation Failed",
+            StatusCode::ImATeapot => "I'm a teapot",
+            StatusCode::MisdirectedRequest => "Misdirected Request",
+            StatusCode::UnprocessableEntity => "Unprocessable Entity",
+            StatusCode::Locked => "Locked",
+            StatusCode::FailedDependency => "Failed Dependency",
+            StatusCode::TooEarly => "Too Early",
+            StatusCode::UpgradeRequired => "Upgrade Required",
+            StatusCode::PreconditionRequired => "Precondition Required",
+            StatusCode::TooManyRequests => "Too Many Requests",
+            StatusCode::RequestHeaderFieldsTooLarge => "Request Header Fields Too Large",
+            StatusCode::UnavailableForLegalReasons => "Unavailable For Legal Reasons",
             StatusCode::InternalError => "Internal Server Error",
             StatusCode::NotImplemented => "Not Implemented",
             StatusCode::BadGateway => "Bad Gateway",
             StatusCode::ServiceUnavailable => "Service Unavailable",
             StatusCode::GatewayTimeout => "Gateway Timeout",
             StatusCode::VersionNotSupported => "HTTP Version Not Supported",
+            StatusCode::VariantAlsoNegotiates => "Variant Also Negotiates",
+            StatusCode::InsufficientStorage => "Insufficient Storage",
+            StatusCode::LoopDetected => "Loop Detected",
+            StatusCode::NotExtended => "Not Extended",
+            StatusCode::NetworkAuthenticationRequired => "Network Authentication Required",
+            StatusCode::Custom(code) => match code {
+                100..=199 => "Informational",
+                200..=299 => "Success",
+                300..=399 => "Redirection",
+                400..=499 => "Client Error",
+                _ => "Server Error",
+            },
         }
     }
 }
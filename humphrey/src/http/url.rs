@@ -0,0 +1,221 @@
+//! Provides functionality for matching hosts and constructing absolute URLs, for example for
+//!   redirects.
+
+use crate::http::headers::HeaderType;
+use crate::http::Request;
+use crate::krauss::wildcard_match;
+
+use std::fmt::Display;
+use std::net::{IpAddr, Ipv6Addr};
+
+/// Characters which are allowed in a path segment without being percent-encoded, in addition to
+///   alphanumeric characters.
+const PATH_CHARACTERS: &[u8] = b"-._~!$&'()*+,;=:@/";
+
+/// Characters which are allowed in a query string without being percent-encoded, in addition to
+///   alphanumeric characters.
+const QUERY_CHARACTERS: &[u8] = b"-._~!$&'()*+,;=:@/?";
+
+/// Represents the origin of a request, consisting of its scheme, host and port.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Origin {
+    /// Whether the scheme is `https`.
+    pub secure: bool,
+    /// The host, in lowercase. IPv6 addresses are enclosed in square brackets.
+    pub host: String,
+    /// The port, if it is not the default port for the scheme.
+    pub port: Option<u16>,
+}
+
+impl Origin {
+    /// Determines the origin of the given request.
+    ///
+    /// The scheme is determined by whether the request was received over a secure connection, and the
+    ///   host and port are taken from the `Host` header. If the request was received directly from one
+    ///   of the given trusted proxies, the `X-Forwarded-Proto` and `X-Forwarded-Host` headers are used
+    ///   instead where present. These headers are ignored from any other client, since they could be
+    ///   forged.
+    ///
+    /// Returns `None` if the host is missing or invalid.
+    pub fn from_request(request: &Request, trusted_proxies: &[IpAddr]) -> Option<Self> {
+        let peer = request
+            .address
+            .proxies
+            .last()
+            .unwrap_or(&request.address.origin_addr);
+        let trusted = trusted_proxies.contains(peer);
+
+        let forwarded_proto = first_forwarded(request, "X-Forwarded-Proto").filter(|_| trusted);
+        let forwarded_host = first_forwarded(request, "X-Forwarded-Host").filter(|_| trusted);
+
+        let secure = match forwarded_proto.map(|proto| proto.to_ascii_lowercase()) {
+            Some(proto) if proto == "https" => true,
+            Some(proto) if proto == "http" => false,
+            Some(_) => return None,
+            None => request.secure,
+        };
+
+        let (host, port) = parse_host(forwarded_host.or(request.headers.get(HeaderType::Host))?)?;
+        let default_port = if secure { 443 } else { 80 };
+
+        Some(Self {
+            secure,
+            host,
+            port: port.filter(|port| *port != default_port),
+        })
+    }
+
+    /// Converts the origin into the equivalent secure origin on the default HTTPS port, for example
+    ///   when redirecting insecure requests.
+    pub fn into_secure(self) -> Self {
+        self.into_secure_on(443)
+    }
+
+    /// Converts the origin into the equivalent secure origin on the given port, which is omitted
+    ///   from URLs if it is the default HTTPS port.
+    pub fn into_secure_on(self, port: u16) -> Self {
+        Self {
+            secure: true,
+            port: Some(port).filter(|port| *port != 443),
+            ..self
+        }
+    }
+
+    /// Returns the scheme of the origin, either `http` or `https`.
+    pub fn scheme(&self) -> &'static str {
+        match self.secure {
+            true => "https",
+            false => "http",
+        }
+    }
+
+    /// Constructs an absolute URL on this origin with the given path and query.
+    ///
+    /// Characters which are not allowed in the path or query are percent-encoded, but existing
+    ///   percent-encoded sequences are left as they are. An empty query is omitted.
+    pub fn url(&self, path: &str, query: Option<&str>) -> String {
+        let path = path.strip_prefix('/').unwrap_or(path);
+        let mut url = format!("{}/{}", self, encode(path, PATH_CHARACTERS));
+
+        if let Some(query) = query.map(|query| query.strip_prefix('?').unwrap_or(query)) {
+            if !query.is_empty() {
+                url.push('?');
+                url.push_str(&encode(query, QUERY_CHARACTERS));
+            }
+        }
+
+        url
+    }
+}
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.port {
+            Some(port) => write!(f, "{}://{}:{}", self.scheme(), self.host, port),
+            None => write!(f, "{}://{}", self.scheme(), self.host),
+        }
+    }
+}
+
+/// Gets the first value of a header which may have been appended to by several proxies.
+fn first_forwarded<'a>(request: &'a Request, header: &str) -> Option<&'a str> {
+    request
+        .headers
+        .get(header)
+        .and_then(|value| value.split(',').next())
+        .map(|value| value.trim())
+        .filter(|value| !value.is_empty())
+}
+
+/// Checks whether the host pattern, which can contain wildcards such as `*.example.com`, matches
+///   the value of a `Host` header.
+///
+/// Patterns without a port match the host whatever port it specifies, so `localhost` matches both
+///   `localhost` and `localhost:8080`. Patterns with a port, such as `localhost:8080` or
+///   `localhost:*`, only match hosts which specify a matching port.
+pub fn host_matches(pattern: &str, host: &str) -> bool {
+    wildcard_match(pattern, host) || wildcard_match(pattern, strip_port(host))
+}
+
+/// Removes the port from a host, as found in the `Host` header, if it has one.
+///
+/// IPv6 addresses keep their square brackets, so `[::1]:8080` becomes `[::1]`. Anything after a
+///   colon which is not a port, such as part of an IPv6 address without brackets, is kept.
+fn strip_port(host: &str) -> &str {
+    let is_port = |port: &str| !port.is_empty() && port.bytes().all(|byte| byte.is_ascii_digit());
+
+    let end = match host.strip_prefix('[') {
+        Some(rest) => rest.find(']').map(|end| end + 2),
+        None => host.find(':').filter(|&end| !host[end + 1..].contains(':')),
+    };
+
+    match end {
+        Some(end) if host[end..].strip_prefix(':').is_some_and(is_port) => &host[..end],
+        _ => host,
+    }
+}
+
+/// Parses and validates a host and optional port, as found in the `Host` header.
+fn parse_host(host: &str) -> Option<(String, Option<u16>)> {
+    let (host, port) = if let Some(rest) = host.strip_prefix('[') {
+        let (address, rest) = rest.split_once(']')?;
+        address.parse::<Ipv6Addr>().ok()?;
+
+        let port = match rest {
+            "" => None,
+            rest => Some(rest.strip_prefix(':')?),
+        };
+
+        (format!("[{}]", address), port)
+    } else {
+        let (host, port) = match host.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (host, None),
+        };
+
+        let valid = !host.is_empty()
+            && host
+                .bytes()
+                .all(|byte| byte.is_ascii_alphanumeric() || byte == b'.' || byte == b'-');
+
+        if !valid {
+            return None;
+        }
+
+        (host.to_string(), port)
+    };
+
+    let port = match port {
+        Some(port) if port.bytes().all(|byte| byte.is_ascii_digit()) => Some(port.parse().ok()?),
+        Some(_) => return None,
+        None => None,
+    };
+
+    Some((host.to_ascii_lowercase(), port))
+}
+
+/// Percent-encodes a decoded request path so that it can be sent in a request line, leaving the
+///   escapes which were not decoded by `decode_path` as they are.
+pub(crate) fn encode_path(path: &str) -> String {
+    encode(path, PATH_CHARACTERS)
+}
+
+fn encode(value: &str, allowed: &[u8]) -> String {
+    let bytes = value.as_bytes();
+    let mut encoded = String::with_capacity(bytes.len());
+
+    for (i, byte) in bytes.iter().enumerate() {
+        let is_escape = *byte == b'%'
+            && bytes.len() > i + 2
+            && bytes[i + 1].is_ascii_hexdigit()
+            && bytes[i + 2].is_ascii_hexdigit();
+
+        if byte.is_ascii_alphanumeric() || allowed.contains(byte) || is_escape {
+            encoded.push(*byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+
+    encoded
+}
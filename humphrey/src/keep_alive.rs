@@ -0,0 +1,200 @@
+//! Provides limits on how long keep-alive connections are kept open while idle and how many
+//!   requests can be sent on each of them.
+//!
+//! When the limits are set with `App::with_keep_alive`, every response on a connection which will be
+//!   kept open has a `Keep-Alive` header such as `Keep-Alive: timeout=5, max=99`, which tells the
+//!   client how many seconds the connection will be kept open while idle and how many more requests
+//!   can be sent on it. Connections which are idle for longer are closed without a response, and the
+//!   last response allowed on a connection has the `Connection: Close` header.
+//!
+//! Whether or not the limits are set, connections which are waiting for their next request are
+//!   closed as soon as the app shuts down, so that they do not keep it waiting.
+
+use crate::accept::ConnectionLimit;
+use crate::http::headers::HeaderType;
+use crate::http::response::Response;
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[cfg(not(feature = "tokio"))]
+use std::collections::HashMap;
+#[cfg(not(feature = "tokio"))]
+use std::net::{Shutdown, TcpStream};
+
+#[cfg(feature = "tokio")]
+use tokio_util::sync::CancellationToken;
+
+/// How long to wait for the connections closed when the app shuts down to finish, so that they are
+///   not counted as abandoned.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Represents how long keep-alive connections are kept open while idle and how many requests can be
+///   sent on each of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeepAliveConfig {
+    /// How long to wait for the next request on a connection before closing it.
+    ///
+    /// This is advertised to clients in whole seconds, rounded down, so that they stop reusing the
+    ///   connection before it is closed.
+    pub idle_timeout: Duration,
+    /// The number of requests which can be sent on each connection, which must be at least one.
+    pub max_requests: u32,
+}
+
+/// Tracks the connections which are waiting for their next request, so that they can be closed when
+///   the app shuts down instead of holding their workers until the client closes them.
+///
+/// Clones share the same connections.
+#[cfg(not(feature = "tokio"))]
+#[derive(Clone, Debug, Default)]
+pub struct IdleConnections(Arc<Mutex<Idle>>);
+
+#[cfg(not(feature = "tokio"))]
+#[derive(Debug, Default)]
+struct Idle {
+    closed: bool,
+    sockets: HashMap<u64, Arc<TcpStream>>,
+}
+
+/// Tracks the number of connections which are waiting for their next request, and wakes them when
+///   the app shuts down.
+///
+/// Clones share the same connections.
+#[cfg(feature = "tokio")]
+#[derive(Clone, Debug, Default)]
+pub(crate) struct IdleConnections {
+    count: Arc<Mutex<usize>>,
+    closed: CancellationToken,
+}
+
+impl KeepAliveConfig {
+    /// Creates a configuration which closes connections after they have been idle for the given
+    ///   amount of time or have had the given number of requests sent on them.
+    pub fn new(idle_timeout: Duration, max_requests: u32) -> Self {
+        Self {
+            idle_timeout,
+            max_requests,
+        }
+    }
+
+    /// Limits the response to the given request on its connection, where the first request is `1`.
+    ///
+    /// If the connection is being kept open, the `Keep-Alive` header is added to responses which
+    ///   say so, unless this was the last request allowed on the connection, in which case it is
+    ///   closed instead.
+    pub(crate) fn limit(&self, response: &mut Response, keep_alive: &mut bool, request: u32) {
+        if !*keep_alive {
+            return;
+        }
+
+        let kept_open = response
+            .headers
+            .get(HeaderType::Connection)
+            .is_some_and(|connection| connection.eq_ignore_ascii_case("keep-alive"));
+
+        match self.max_requests.saturating_sub(request) {
+            0 => {
+                *keep_alive = false;
+                response.headers.remove(HeaderType::Connection);
+                response.headers.add(HeaderType::Connection, "Close");
+            }
+            remaining if kept_open => response.headers.add(
+                HeaderType::KeepAlive,
+                format!("timeout={}, max={}", self.idle_timeout.as_secs(), remaining),
+            ),
+            _ => (),
+        }
+    }
+}
+
+impl Default for KeepAliveConfig {
+    /// Closes connections after five seconds of idleness or 100 requests.
+    fn default() -> Self {
+        Self::new(Duration::from_secs(5), 100)
+    }
+}
+
+#[cfg(not(feature = "tokio"))]
+impl IdleConnections {
+    /// Records that the connection with the given ID is waiting for its next request, returning
+    ///   `false` if the app is shutting down, in which case the connection should be closed.
+    pub fn enter(&self, id: u64, socket: &Arc<TcpStream>) -> bool {
+        let mut idle = self.0.lock().unwrap();
+
+        if !idle.closed {
+            idle.sockets.insert(id, socket.clone());
+        }
+
+        !idle.closed
+    }
+
+    /// Records that the connection with the given ID is no longer waiting for a request.
+    pub fn leave(&self, id: u64) {
+        self.0.lock().unwrap().sockets.remove(&id);
+    }
+
+    /// Closes every connection which is waiting for its next request, waking the workers waiting
+    ///   on them, and stops any more connections from waiting.
+    ///
+    /// This waits briefly for the closed connections to finish, so that only connections which were
+    ///   busy are still counted by the connection limit.
+    pub(crate) fn close(&self, limit: &ConnectionLimit) {
+        let open = limit.count();
+        let mut idle = self.0.lock().unwrap();
+        idle.closed = true;
+
+        let remaining = open.saturating_sub(idle.sockets.len());
+        for (_, socket) in idle.sockets.drain() {
+            let _ = socket.shutdown(Shutdown::Both);
+        }
+        drop(idle);
+
+        let started = Instant::now();
+        while limit.count() > remaining && started.elapsed() < CLOSE_TIMEOUT {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl IdleConnections {
+    /// Records that a connection is waiting for its next request, returning `false` if the app is
+    ///   shutting down, in which case the connection should be closed.
+    pub(crate) fn enter(&self) -> bool {
+        let mut count = self.count.lock().unwrap();
+        *count += usize::from(!self.closed.is_cancelled());
+
+        !self.closed.is_cancelled()
+    }
+
+    /// Records that a connection is no longer waiting for a request.
+    pub(crate) fn leave(&self) {
+        *self.count.lock().unwrap() -= 1;
+    }
+
+    /// Waits until the app shuts down, after which idle connections should be closed.
+    pub(crate) async fn closed(&self) {
+        self.closed.cancelled().await
+    }
+
+    /// Wakes every connection which is waiting for its next request so that it is closed, and stops
+    ///   any more connections from waiting.
+    ///
+    /// This waits briefly for the closed connections to finish, so that only connections which were
+    ///   busy are still counted by the connection limit.
+    pub(crate) async fn close(&self, limit: &ConnectionLimit) {
+        let open = limit.count();
+        let remaining = {
+            let count = self.count.lock().unwrap();
+            self.closed.cancel();
+
+            open.saturating_sub(*count)
+        };
+
+        let started = Instant::now();
+        while limit.count() > remaining && started.elapsed() < CLOSE_TIMEOUT {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+}
@@ -78,3 +78,48 @@ pub fn wildcard_match(wild: &str, tame: &str) -> bool {
         wild_iter.next();
     }
 }
+
+/// Returns the parts of a tame string matched by each wildcard of a wild string, or `None` if the
+///   strings do not match.
+///
+/// Each wildcard matches as few characters as possible, so `/*/*` matches `/a/b/c` with the parts
+///   `a` and `b/c`.
+pub fn wildcard_captures<'a>(wild: &str, tame: &'a str) -> Option<Vec<&'a str>> {
+    let mut captures = Vec::new();
+
+    match captures_from(wild, tame, &mut captures) {
+        true => Some(captures),
+        false => None,
+    }
+}
+
+/// Matches the tame string against the wild string, pushing the part matched by each wildcard.
+fn captures_from<'a>(wild: &str, tame: &'a str, captures: &mut Vec<&'a str>) -> bool {
+    let (literal, wild_rest) = match wild.split_once('*') {
+        Some(parts) => parts,
+        None => return wild == tame,
+    };
+
+    let tame_rest = match tame.strip_prefix(literal) {
+        Some(tame_rest) => tame_rest,
+        None => return false,
+    };
+
+    // Try each possible end of the wildcard's part, shortest first
+    let ends = tame_rest
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(tame_rest.len()));
+
+    for end in ends {
+        captures.push(&tame_rest[..end]);
+
+        if captures_from(wild_rest, &tame_rest[end..], captures) {
+            return true;
+        }
+
+        captures.pop();
+    }
+
+    false
+}
@@ -4,11 +4,11 @@
 
 #![warn(missing_docs)]
 
-#[cfg(not(feature = "tokio"))]
+#[cfg(all(feature = "runtime", not(feature = "tokio")))]
 pub mod app;
-#[cfg(not(feature = "tokio"))]
+#[cfg(all(feature = "runtime", not(feature = "tokio")))]
 pub mod handler_traits;
-#[cfg(not(feature = "tokio"))]
+#[cfg(all(feature = "runtime", not(feature = "tokio")))]
 pub mod handlers;
 
 #[cfg(feature = "tokio")]
@@ -17,20 +17,67 @@ pub mod tokio;
 #[cfg(feature = "tokio")]
 pub use crate::tokio::*;
 
-#[cfg(not(feature = "tokio"))]
+#[cfg(all(feature = "runtime", not(feature = "tokio")))]
+pub mod recording;
+#[cfg(all(feature = "runtime", not(feature = "tokio")))]
 pub mod stream;
+#[cfg(all(feature = "runtime", not(feature = "tokio")))]
+pub mod test_client;
 
+#[cfg(feature = "runtime")]
+pub mod accept;
+pub mod cancellation;
+#[cfg(feature = "runtime")]
 pub mod client;
+pub mod clock;
+#[cfg(feature = "runtime")]
+pub mod drain;
+#[cfg(feature = "runtime")]
+pub mod embed;
+#[cfg(feature = "runtime")]
+pub mod endpoint;
+#[cfg(feature = "error")]
+pub mod error;
+#[cfg(feature = "runtime")]
+pub mod file;
 pub mod http;
+#[cfg(feature = "runtime")]
+pub mod keep_alive;
 pub mod krauss;
+#[cfg(feature = "runtime")]
+pub mod middleware;
+#[cfg(feature = "runtime")]
 pub mod monitor;
 pub mod percent;
+pub mod prelude;
+#[cfg(feature = "runtime")]
+pub mod proxy_protocol;
+#[cfg(feature = "runtime")]
+pub mod rate_limit;
+#[cfg(feature = "runtime")]
 pub mod route;
+#[cfg(feature = "runtime")]
+mod route_index;
+#[cfg(feature = "runtime")]
+pub mod security;
+#[cfg(feature = "runtime")]
+pub mod shutdown;
+#[cfg(feature = "runtime")]
 pub mod thread;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod trace;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "runtime"))]
 mod tests;
 
+#[cfg(feature = "runtime")]
 pub use app::App;
+#[cfg(feature = "runtime")]
 pub use client::Client;
+#[cfg(feature = "runtime")]
 pub use route::SubApp;
+
+pub use http::headers::HeaderType;
+pub use http::method::Method;
+pub use http::{Request, Response, StatusCode};
@@ -0,0 +1,143 @@
+//! Provides middleware, which runs before and after the handlers of an app or sub-app.
+//!
+//! Middleware added to the app applies to every request, and middleware added to a host sub-app
+//!   applies to every request for that host, running after the app's middleware. The request side
+//!   of each middleware runs in the order it was added, and can answer the request itself to stop the
+//!   request from being handled any further. The response side then runs in the reverse order, so
+//!   that each middleware wraps those added after it, for every middleware whose request side ran.
+//!
+//! Middleware does not run for requests which were rejected before being routed, such as by the
+//!   request condition, or for `OPTIONS` requests, which the app answers itself.
+
+use crate::http::{Request, Response};
+use crate::route::{find_subapp, SubApp};
+
+use std::sync::Arc;
+
+/// Represents the request side of a middleware, which can modify the request before it is handled.
+///
+/// Returning `Some` answers the request with the given response instead of handling it.
+pub type RequestMiddleware<State> =
+    Box<dyn Fn(&mut Request, Arc<State>) -> Option<Response> + Send + Sync>;
+
+/// Represents the response side of a middleware, which can modify the response to a request before
+///   it is sent.
+pub type ResponseMiddleware<State> = Box<dyn Fn(&mut Response, &Request, Arc<State>) + Send + Sync>;
+
+/// Represents a middleware, which can run before a request is handled, after it is handled, or both.
+///
+/// ## Example
+/// ```
+/// let app: App<()> = App::new()
+///     .with_middleware(
+///         Middleware::new()
+///             .with_request(|request, _| {
+///                 match request.headers.get(HeaderType::Authorization) {
+///                     Some(_) => None,
+///                     None => Some(Response::empty(StatusCode::Unauthorized)),
+///                 }
+///             })
+///             .with_response(|response, _, _| {
+///                 response.headers.add("X-Powered-By", "Humphrey");
+///             }),
+///     )
+///     .with_stateless_route("/", home);
+/// ```
+pub struct Middleware<State> {
+    /// The request side of the middleware, if any.
+    pub request: Option<RequestMiddleware<State>>,
+    /// The response side of the middleware, if any.
+    pub response: Option<ResponseMiddleware<State>>,
+}
+
+impl<State> Default for Middleware<State> {
+    fn default() -> Self {
+        Self {
+            request: None,
+            response: None,
+        }
+    }
+}
+
+impl<State> Middleware<State> {
+    /// Creates a new middleware which does nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the function to run before the request is handled.
+    ///
+    /// The function can modify the request, and returning `Some` answers the request with the given
+    ///   response, so that neither later middleware nor the handler is called.
+    pub fn with_request<T>(mut self, middleware: T) -> Self
+    where
+        T: Fn(&mut Request, Arc<State>) -> Option<Response> + Send + Sync + 'static,
+    {
+        self.request = Some(Box::new(middleware));
+        self
+    }
+
+    /// Sets the function to run after the request is handled, which can modify the response.
+    ///
+    /// This is also called if the request was answered by the request side of this middleware or of
+    ///   a middleware added after it.
+    pub fn with_response<T>(mut self, middleware: T) -> Self
+    where
+        T: Fn(&mut Response, &Request, Arc<State>) + Send + Sync + 'static,
+    {
+        self.response = Some(Box::new(middleware));
+        self
+    }
+}
+
+/// Returns the middleware which applies to requests for the given host, which is that of the default
+///   sub-app followed by that of the first sub-app whose host matches.
+pub(crate) fn find_middleware<'a, State>(
+    subapps: &'a [SubApp<State>],
+    default_subapp: &'a SubApp<State>,
+    host: Option<&str>,
+) -> Vec<&'a Middleware<State>> {
+    let subapp = host.and_then(|host| find_subapp(subapps, host));
+
+    default_subapp
+        .middleware
+        .iter()
+        .chain(subapp.into_iter().flat_map(|subapp| &subapp.middleware))
+        .collect()
+}
+
+/// Runs the request side of the middleware in order until one of them answers the request.
+///
+/// Returns the number of middleware which were run, including any which answered the request, and
+///   its response.
+pub(crate) fn run_request_middleware<State>(
+    middleware: &[&Middleware<State>],
+    request: &mut Request,
+    state: &Arc<State>,
+) -> (usize, Option<Response>) {
+    for (index, middleware) in middleware.iter().enumerate() {
+        if let Some(response) = middleware
+            .request
+            .as_ref()
+            .and_then(|middleware| middleware(request, state.clone()))
+        {
+            return (index + 1, Some(response));
+        }
+    }
+
+    (middleware.len(), None)
+}
+
+/// Runs the response side of the given middleware in reverse order.
+pub(crate) fn run_response_middleware<State>(
+    middleware: &[&Middleware<State>],
+    response: &mut Response,
+    request: &Request,
+    state: &Arc<State>,
+) {
+    for middleware in middleware.iter().rev() {
+        if let Some(middleware) = &middleware.response {
+            middleware(response, request, state.clone());
+        }
+    }
+}
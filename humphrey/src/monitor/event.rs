@@ -1,6 +1,8 @@
 //! Event types for monitoring.
 
 use crate::http::date::DateTime;
+use crate::monitor::timing::RequestDetails;
+use crate::trace::{SpanId, TraceContext, TraceId};
 
 use std::borrow::Cow;
 use std::fmt::Display;
@@ -14,6 +16,13 @@ pub struct Event {
     pub peer: Option<SocketAddr>,
     /// Additional information about the event, if applicable.
     pub info: Option<Cow<'static, str>>,
+    /// The request which the event is about and how long it took, if applicable.
+    pub request: Option<RequestDetails>,
+    /// The ID of the trace of the request which the event is about, if trace propagation is enabled.
+    pub trace_id: Option<TraceId>,
+    /// The ID of the span of the handling of the request which the event is about, if trace
+    ///   propagation is enabled.
+    pub span_id: Option<SpanId>,
 }
 
 /// Represents the type of event.
@@ -57,6 +66,28 @@ pub enum EventType {
     ThreadPoolPanic = 0x4000,
     /// A thread in the thread pool was restarted.
     ThreadRestarted = 0x8000,
+    /// A handler took ownership of the connection after its response was written.
+    ConnectionHijacked = 0x010000,
+    /// A WebSocket connection was refused because too many workers are held by WebSocket connections.
+    WebsocketConnectionRefused = 0x020000,
+    /// Accepting connections was paused because the process ran out of file descriptors.
+    ///
+    /// This event is rate-limited, so the number of errors since the last event is included.
+    AcceptBackoff = 0x040000,
+    /// The body of a request ended before its declared length, so the connection will be closed.
+    RequestBodyIncomplete = 0x080000,
+    /// The handler of a request took longer than the slow request threshold of the monitor.
+    ///
+    /// The request and how long each stage of serving it took are included in the event.
+    SlowRequest = 0x100000,
+    /// A connection was rejected because the app was already handling its maximum number of
+    ///   concurrent connections.
+    ConnectionRejectedOverCapacity = 0x200000,
+    /// A request was refused because its client had used up its allowance under the rate limit.
+    RequestRateLimited = 0x400000,
+    /// A file which was going to be served was not found, for example because it was removed while
+    ///   the app was running.
+    FileMissing = 0x800000,
 }
 
 /// Represents a category of events.
@@ -64,11 +95,11 @@ pub enum EventType {
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum EventLevel {
     /// Only critical errors are logged.
-    Error = 0b0100_0000_1000_0100,
+    Error = 0b0100_0000_0100_0000_1000_0100,
     /// Only errors and warnings are logged.
-    Warning = 0b0110_0001_1010_0110,
+    Warning = 0b1111_1110_0110_0001_1010_0110,
     /// Informative messages are logged.
-    Info = 0b1111_1101_1110_1110,
+    Info = 0b1111_1111_1111_1101_1110_1110,
     /// Everything is logged.
     Debug = u32::MAX,
 }
@@ -86,6 +117,9 @@ impl Event {
             kind,
             peer: None,
             info: None,
+            request: None,
+            trace_id: None,
+            span_id: None,
         }
     }
 
@@ -110,6 +144,22 @@ impl Event {
         self
     }
 
+    /// Adds the request which the event is about to the event, also describing it in the event's
+    ///   information.
+    pub fn with_request(mut self, request: RequestDetails) -> Self {
+        self.info = Some(request.to_string().into());
+        self.request = Some(request);
+        self
+    }
+
+    /// Adds the trace and span IDs of the request which the event is about to the event, if the
+    ///   request has a trace context.
+    pub fn with_trace(mut self, trace: Option<&TraceContext>) -> Self {
+        self.trace_id = trace.map(|trace| trace.trace_id);
+        self.span_id = trace.map(|trace| trace.span_id);
+        self
+    }
+
     /// Adds information to the event.
     pub fn with_info<T>(mut self, info: T) -> Self
     where
@@ -148,6 +198,14 @@ impl From<EventType> for &'static str {
             EventType::ThreadPoolOverload => "Thread pool overloaded",
             EventType::ThreadPoolPanic => "Thread pool panic",
             EventType::ThreadRestarted => "Thread restarted",
+            EventType::ConnectionHijacked => "Connection hijacked by handler",
+            EventType::WebsocketConnectionRefused => "WebSocket connection refused",
+            EventType::AcceptBackoff => "Accepting connections paused",
+            EventType::RequestBodyIncomplete => "Request body incomplete",
+            EventType::SlowRequest => "Slow request",
+            EventType::ConnectionRejectedOverCapacity => "Connection rejected over capacity",
+            EventType::RequestRateLimited => "Request rate limited",
+            EventType::FileMissing => "File missing",
         }
     }
 }
@@ -176,7 +234,7 @@ impl Display for Event {
                     .unwrap_or_else(|| "".into()),
                 self.kind.to_string(),
                 info
-            )
+            )?;
         } else {
             write!(
                 f,
@@ -185,8 +243,14 @@ impl Display for Event {
                     .map(|p| p.to_string() + " ")
                     .unwrap_or_else(|| "".into()),
                 self.kind.to_string()
-            )
+            )?;
         }
+
+        if let (Some(trace_id), Some(span_id)) = (self.trace_id, self.span_id) {
+            write!(f, " (trace {} span {})", trace_id, span_id)?;
+        }
+
+        Ok(())
     }
 }
 
@@ -1,10 +1,12 @@
 //! Monitoring functionality.
 
 pub mod event;
+pub mod timing;
 
-use event::{Event, ToEventMask};
+use event::{Event, EventType, ToEventMask};
 
 use std::sync::mpsc::Sender;
+use std::time::Duration;
 
 /// Represents configuration for monitoring.
 ///
@@ -18,6 +20,7 @@ use std::sync::mpsc::Sender;
 pub struct MonitorConfig {
     mask: u32,
     sender: Option<Sender<Event>>,
+    slow_request_threshold: Option<Duration>,
 }
 
 impl MonitorConfig {
@@ -26,6 +29,7 @@ impl MonitorConfig {
         Self {
             mask: 0,
             sender: Some(sender),
+            slow_request_threshold: None,
         }
     }
 
@@ -39,7 +43,22 @@ impl MonitorConfig {
         self
     }
 
+    /// Sends an `EventType::SlowRequest` event for every request whose handler takes longer than the
+    ///   given threshold, subscribing the monitor to the event.
+    ///
+    /// Only the time taken to generate the response counts towards the threshold, so slow clients do
+    ///   not cause events, but the time taken to parse the request and write the response are also
+    ///   included in the event.
+    pub fn with_slow_request_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_request_threshold = Some(threshold);
+        self.mask |= EventType::SlowRequest.to_event_mask();
+        self
+    }
+
     /// Send a monitoring event.
+    ///
+    /// The event is built before checking whether the monitor is subscribed to it, so events whose
+    ///   information is expensive to build should be sent with `send_with` instead.
     pub fn send(&self, event: impl Into<Event>) {
         if let Some(sender) = &self.sender {
             let event = event.into();
@@ -50,10 +69,40 @@ impl MonitorConfig {
         }
     }
 
+    /// Send a monitoring event of the given type, calling `build` to add details such as the peer
+    ///   and information to the event only if the monitor is subscribed to the type.
+    ///
+    /// ## Example
+    /// ```
+    /// monitor.send_with(EventType::RequestServedSuccess, |event| {
+    ///     event.with_peer(addr).with_info(format!("200 OK {}", uri))
+    /// });
+    /// ```
+    pub fn send_with<F>(&self, kind: EventType, build: F)
+    where
+        F: FnOnce(Event) -> Event,
+    {
+        if let Some(sender) = &self.sender {
+            if self.mask & kind.to_event_mask() != 0 {
+                sender.send(build(Event::new(kind))).ok();
+            }
+        }
+    }
+
+    /// Returns whether the monitor would send events of the given type.
+    pub fn is_subscribed_to(&self, kind: EventType) -> bool {
+        self.sender.is_some() && self.mask & kind.to_event_mask() != 0
+    }
+
     /// Get the mask of the monitor.
     pub const fn mask(&self) -> u32 {
         self.mask
     }
+
+    /// Get the slow request threshold of the monitor, if any.
+    pub const fn slow_request_threshold(&self) -> Option<Duration> {
+        self.slow_request_threshold
+    }
 }
 
 impl Clone for MonitorConfig {
@@ -61,6 +110,7 @@ impl Clone for MonitorConfig {
         MonitorConfig {
             mask: self.mask,
             sender: self.sender.clone(),
+            slow_request_threshold: self.slow_request_threshold,
         }
     }
 }
@@ -0,0 +1,119 @@
+//! Timing instrumentation for requests.
+
+use crate::clock;
+use crate::http::method::Method;
+use crate::http::StatusCode;
+
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+/// Represents how long each stage of serving a request took.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RequestTiming {
+    /// The time from the first byte of the request arriving to the whole request being read and
+    ///   parsed, which includes receiving the body.
+    pub parse: Duration,
+    /// The time taken to route the request and generate the response, which includes running the
+    ///   handler.
+    pub handler: Duration,
+    /// The time taken to serialise the response and write it to the stream.
+    pub write: Duration,
+}
+
+/// Represents a request which has been served and how long it took, as attached to monitoring events
+///   such as `EventType::SlowRequest`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestDetails {
+    /// The method of the request.
+    pub method: Method,
+    /// The URI of the request.
+    pub uri: String,
+    /// The pattern of the route which handled the request, or `None` if no route matched.
+    pub route: Option<String>,
+    /// The status code of the response.
+    pub status: StatusCode,
+    /// How long each stage of serving the request took.
+    pub timing: RequestTiming,
+}
+
+/// Records the instants at which each stage of serving a request finished.
+pub(crate) struct RequestTimer {
+    /// When the first byte of the request arrived.
+    pub(crate) received: Instant,
+    /// When the request had been parsed.
+    pub(crate) parsed: Instant,
+    /// When the response had been generated.
+    pub(crate) handled: Instant,
+}
+
+impl RequestTiming {
+    /// Returns the total time taken to serve the request.
+    pub fn total(&self) -> Duration {
+        self.parse + self.handler + self.write
+    }
+}
+
+impl RequestTimer {
+    /// Creates a new timer with every stage starting now.
+    pub(crate) fn new() -> Self {
+        let now = clock::now_monotonic();
+
+        Self {
+            received: now,
+            parsed: now,
+            handled: now,
+        }
+    }
+
+    /// Marks the request as parsed.
+    pub(crate) fn parsed(&mut self) {
+        self.parsed = clock::now_monotonic();
+    }
+
+    /// Marks the response as generated.
+    pub(crate) fn handled(&mut self) {
+        self.handled = clock::now_monotonic();
+    }
+
+    /// Marks the response as written, returning how long each stage took.
+    pub(crate) fn written(&self) -> RequestTiming {
+        RequestTiming {
+            parse: self.parsed.saturating_duration_since(self.received),
+            handler: self.handled.saturating_duration_since(self.parsed),
+            write: self.handled.elapsed(),
+        }
+    }
+}
+
+impl Display for RequestTiming {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "parse {:.2}ms, handler {:.2}ms, write {:.2}ms",
+            self.parse.as_secs_f64() * 1000.0,
+            self.handler.as_secs_f64() * 1000.0,
+            self.write.as_secs_f64() * 1000.0
+        )
+    }
+}
+
+impl Display for RequestDetails {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status: &str = self.status.into();
+
+        write!(
+            f,
+            "{} {} {} {}",
+            self.method,
+            self.uri,
+            u16::from(self.status),
+            status
+        )?;
+
+        if let Some(route) = &self.route {
+            write!(f, " (route {})", route)?;
+        }
+
+        write!(f, " took {}", self.timing)
+    }
+}
@@ -1,4 +1,8 @@
 //! Provides percent-encoding functionality.
+//!
+//! Request paths are decoded with `decode_path` when requests are parsed. Encoded slashes (`%2F`)
+//!   and percent signs (`%25`) are left encoded, so that an encoded slash can never be mistaken
+//!   for a path separator, and every `%` in a decoded path is the start of one of these escapes.
 
 const UNRESERVED_CHARACTERS: &[u8] =
     b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_.~";
@@ -47,6 +51,11 @@ where
         while let Some(character) = chars.next() {
             if character == b'%' {
                 let [hex_dig_1, hex_dig_2] = [chars.next()?, chars.next()?];
+
+                if !hex_dig_1.is_ascii_hexdigit() || !hex_dig_2.is_ascii_hexdigit() {
+                    return None;
+                }
+
                 let hex = format!("{}{}", hex_dig_1 as char, hex_dig_2 as char);
                 let byte = u8::from_str_radix(&hex, 16).ok()?;
                 decoded.push(byte);
@@ -58,3 +67,58 @@ where
         Some(decoded)
     }
 }
+
+/// Percent-decodes a request path, except for encoded slashes (`%2F`) and percent signs (`%25`),
+///   which are left encoded in uppercase.
+///
+/// Returns `None` if the path contains an invalid escape or a control character, whether encoded or
+///   not, or does not decode to valid UTF-8. Paths which have already been decoded are returned unchanged.
+pub fn decode_path(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes.get(i + 1..i + 3)?;
+
+            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+
+            let byte = u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?;
+
+            match byte {
+                b'/' => decoded.extend(b"%2F"),
+                b'%' => decoded.extend(b"%25"),
+                byte if byte.is_ascii_control() => return None,
+                byte => decoded.push(byte),
+            }
+
+            i += 3;
+        } else if bytes[i].is_ascii_control() {
+            return None;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+/// Converts a request path into a path which is safe to resolve against a directory, decoding the
+///   escapes left by `decode_path`.
+///
+/// Returns `None` if the path is invalid, or contains an encoded slash, a backslash or a null
+///   character, since these could otherwise be used to escape the directory.
+#[cfg(feature = "runtime")]
+pub(crate) fn decode_file_path(path: &str) -> Option<String> {
+    let path = decode_path(path)?;
+
+    if path.contains("%2F") || path.contains('\\') || path.contains('\0') {
+        return None;
+    }
+
+    Some(path.replace("%25", "%"))
+}
@@ -0,0 +1,32 @@
+//! Re-exports the types which most apps need, so that they can be imported with a single `use`.
+//!
+//! The paths are the same whether or not the `tokio` feature is enabled, since the app, handler
+//!   traits and stream re-exported here are those of the runtime in use. This means that switching
+//!   runtime only requires the handlers themselves to be changed, not their imports.
+//!
+//! ## Example
+//! ```
+//! use humphrey::prelude::*;
+//!
+//! fn main() {
+//!     let app: App = App::new().with_stateless_route("/", |_: Request| {
+//!         Response::new(StatusCode::OK, "Hello, Humphrey!")
+//!             .with_header(HeaderType::ContentType, "text/plain")
+//!     });
+//!
+//!     app.run("0.0.0.0:80").unwrap();
+//! }
+//! ```
+
+pub use crate::http::headers::{HeaderType, Headers};
+pub use crate::http::method::Method;
+pub use crate::http::{Request, Response, StatusCode};
+
+#[cfg(feature = "runtime")]
+pub use crate::handler_traits::{
+    PathAwareRequestHandler, RequestHandler, StatelessRequestHandler, WebsocketHandler,
+};
+#[cfg(feature = "runtime")]
+pub use crate::stream::Stream;
+#[cfg(feature = "runtime")]
+pub use crate::{App, Client, SubApp};
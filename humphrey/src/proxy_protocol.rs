@@ -0,0 +1,287 @@
+//! Provides parsing of the PROXY protocol header, which load balancers such as HAProxy and AWS
+//!   Network Load Balancers send at the start of each connection to pass on the address of the
+//!   client.
+//!
+//! Both the human-readable version 1 and the binary version 2 of the protocol are supported. The
+//!   header is read without reading any of the connection after it, so it can be read before a TLS
+//!   handshake.
+//!
+//! Learn more about the protocol [here](https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt).
+
+use crate::monitor::event::EventType;
+use crate::monitor::MonitorConfig;
+
+use std::error::Error;
+use std::fmt::Display;
+use std::io::{self, Read};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// The signature at the start of a version 2 header.
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// The maximum length of a version 1 header, including its line ending.
+const V1_MAX_LENGTH: usize = 107;
+
+/// The header sent by a proxy at the start of a connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProxyHeader {
+    /// The connection was proxied from the client at the source address to the destination address
+    ///   on the proxy.
+    Proxied {
+        /// The address of the client.
+        source: SocketAddr,
+        /// The address on the proxy which the client connected to.
+        destination: SocketAddr,
+    },
+    /// The connection was made by the proxy itself, for example for a health check, or the proxy
+    ///   could not describe the client's address.
+    Local,
+}
+
+/// An error encountered while reading a PROXY protocol header.
+#[derive(Debug)]
+pub enum ProxyHeaderError {
+    /// The header could not be read from the stream.
+    Io(io::Error),
+    /// The header was malformed, or the stream did not start with one.
+    Invalid(&'static str),
+}
+
+/// How far through reading a header the bytes read so far are.
+enum Progress {
+    /// This many more bytes must be read before the header can be parsed.
+    Need(usize),
+    /// The header has been read completely.
+    Done(ProxyHeader),
+}
+
+impl ProxyHeader {
+    /// Returns the address of the client, if the proxy described it.
+    pub fn source(&self) -> Option<SocketAddr> {
+        match self {
+            ProxyHeader::Proxied { source, .. } => Some(*source),
+            ProxyHeader::Local => None,
+        }
+    }
+}
+
+/// Reads the PROXY protocol header from the start of the stream, leaving the stream at the first
+///   byte after it.
+///
+/// Nothing after the header is read, so version 1 headers are read one byte at a time. If the stream
+///   does not start with a valid header, an error is returned.
+pub fn read_header(stream: &mut impl Read) -> Result<ProxyHeader, ProxyHeaderError> {
+    let mut buf = Vec::with_capacity(16);
+
+    loop {
+        match advance(&buf)? {
+            Progress::Need(n) => {
+                let start = buf.len();
+                buf.resize(start + n, 0);
+                stream.read_exact(&mut buf[start..])?;
+            }
+            Progress::Done(header) => return Ok(header),
+        }
+    }
+}
+
+/// Reads the PROXY protocol header from the start of the stream in the same way as `read_header`.
+#[cfg(feature = "tokio")]
+pub async fn read_header_async(
+    stream: &mut (impl tokio::io::AsyncRead + Unpin),
+) -> Result<ProxyHeader, ProxyHeaderError> {
+    use tokio::io::AsyncReadExt;
+
+    let mut buf = Vec::with_capacity(16);
+
+    loop {
+        match advance(&buf)? {
+            Progress::Need(n) => {
+                let start = buf.len();
+                buf.resize(start + n, 0);
+                stream.read_exact(&mut buf[start..]).await?;
+            }
+            Progress::Done(header) => return Ok(header),
+        }
+    }
+}
+
+/// Reads the PROXY protocol header from the start of the connection and returns the address of the
+///   client, which is the address of the peer if the proxy did not describe the client.
+///
+/// If the header is invalid, the `ConnectionError` event is sent and `None` is returned, in which
+///   case the connection should be closed.
+#[cfg(not(feature = "tokio"))]
+pub(crate) fn client_addr(
+    stream: &mut std::net::TcpStream,
+    monitor: &MonitorConfig,
+) -> Option<SocketAddr> {
+    let header = read_header(stream);
+    resolve(header, stream.peer_addr(), monitor)
+}
+
+/// Reads the PROXY protocol header from the start of the connection and returns the address of the
+///   client, which is the address of the peer if the proxy did not describe the client.
+///
+/// If the header is invalid, the `ConnectionError` event is sent and `None` is returned, in which
+///   case the connection should be closed.
+#[cfg(feature = "tokio")]
+pub(crate) async fn client_addr(
+    stream: &mut tokio::net::TcpStream,
+    monitor: &MonitorConfig,
+) -> Option<SocketAddr> {
+    let header = read_header_async(stream).await;
+    resolve(header, stream.peer_addr(), monitor)
+}
+
+/// Returns the address of the client given the header read from the connection and the address of
+///   the peer, reporting the error if the header could not be read.
+fn resolve(
+    header: Result<ProxyHeader, ProxyHeaderError>,
+    peer: io::Result<SocketAddr>,
+    monitor: &MonitorConfig,
+) -> Option<SocketAddr> {
+    match header {
+        Ok(header) => header.source().or_else(|| peer.ok()),
+        Err(e) => {
+            monitor.send_with(EventType::ConnectionError, |event| {
+                event
+                    .with_peer_result(peer)
+                    .with_info(format!("Invalid PROXY protocol header: {}", e))
+            });
+
+            None
+        }
+    }
+}
+
+/// Works out how many more bytes must be read to complete the header which starts with the given
+///   bytes, or parses it if they are the whole header.
+fn advance(buf: &[u8]) -> Result<Progress, ProxyHeaderError> {
+    // Eight bytes are enough to tell the versions apart, and no header is shorter
+    if buf.len() < 8 {
+        return Ok(Progress::Need(8 - buf.len()));
+    }
+
+    if buf.starts_with(b"PROXY ") {
+        return match buf.ends_with(b"\r\n") {
+            true => parse_v1(buf).map(Progress::Done),
+            false if buf.len() >= V1_MAX_LENGTH => Err(ProxyHeaderError::Invalid("too long")),
+            false => Ok(Progress::Need(1)),
+        };
+    }
+
+    if !V2_SIGNATURE.starts_with(&buf[..8]) {
+        return Err(ProxyHeaderError::Invalid("missing header"));
+    }
+
+    if buf.len() < 16 {
+        return Ok(Progress::Need(16 - buf.len()));
+    }
+
+    let length = 16 + u16::from_be_bytes([buf[14], buf[15]]) as usize;
+
+    match buf.len() < length {
+        true => Ok(Progress::Need(length - buf.len())),
+        false => parse_v2(buf).map(Progress::Done),
+    }
+}
+
+/// Parses a version 1 header, such as `PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\n`.
+fn parse_v1(line: &[u8]) -> Result<ProxyHeader, ProxyHeaderError> {
+    let line = std::str::from_utf8(&line[..line.len() - 2])
+        .map_err(|_| ProxyHeaderError::Invalid("not ASCII"))?;
+    let fields: Vec<&str> = line.split(' ').collect();
+
+    // The rest of the line is ignored if the proxy does not know the protocol
+    if fields.get(1) == Some(&"UNKNOWN") {
+        return Ok(ProxyHeader::Local);
+    }
+
+    let [_, protocol, source, destination, source_port, destination_port] = fields[..] else {
+        return Err(ProxyHeaderError::Invalid("wrong number of fields"));
+    };
+
+    let ip = |addr: &str| -> Result<IpAddr, ProxyHeaderError> {
+        match protocol {
+            "TCP4" => addr.parse::<Ipv4Addr>().map(IpAddr::V4),
+            "TCP6" => addr.parse::<Ipv6Addr>().map(IpAddr::V6),
+            _ => return Err(ProxyHeaderError::Invalid("unknown protocol")),
+        }
+        .map_err(|_| ProxyHeaderError::Invalid("invalid address"))
+    };
+    let port = |port: &str| {
+        port.parse::<u16>()
+            .map_err(|_| ProxyHeaderError::Invalid("invalid port"))
+    };
+
+    Ok(ProxyHeader::Proxied {
+        source: SocketAddr::new(ip(source)?, port(source_port)?),
+        destination: SocketAddr::new(ip(destination)?, port(destination_port)?),
+    })
+}
+
+/// Parses a whole version 2 header, whose signature has already been checked.
+fn parse_v2(header: &[u8]) -> Result<ProxyHeader, ProxyHeaderError> {
+    if header[..12] != V2_SIGNATURE {
+        return Err(ProxyHeaderError::Invalid("missing header"));
+    }
+
+    if header[12] >> 4 != 2 {
+        return Err(ProxyHeaderError::Invalid("unsupported version"));
+    }
+
+    let addresses = &header[16..];
+
+    match (header[12] & 0x0F, header[13] >> 4) {
+        // Connections made by the proxy itself carry no addresses
+        (0x0, _) => Ok(ProxyHeader::Local),
+        (0x1, 0x1) if addresses.len() >= 12 => {
+            let ip = |at: usize| {
+                let octets: [u8; 4] = addresses[at..at + 4].try_into().unwrap();
+                IpAddr::from(octets)
+            };
+            let port = |at: usize| u16::from_be_bytes([addresses[at], addresses[at + 1]]);
+
+            Ok(ProxyHeader::Proxied {
+                source: SocketAddr::new(ip(0), port(8)),
+                destination: SocketAddr::new(ip(4), port(10)),
+            })
+        }
+        (0x1, 0x2) if addresses.len() >= 36 => {
+            let ip = |at: usize| {
+                let octets: [u8; 16] = addresses[at..at + 16].try_into().unwrap();
+                IpAddr::from(octets)
+            };
+            let port = |at: usize| u16::from_be_bytes([addresses[at], addresses[at + 1]]);
+
+            Ok(ProxyHeader::Proxied {
+                source: SocketAddr::new(ip(0), port(32)),
+                destination: SocketAddr::new(ip(16), port(34)),
+            })
+        }
+        (0x1, 0x1 | 0x2) => Err(ProxyHeaderError::Invalid("addresses too short")),
+        // Unix sockets and unspecified families do not describe an IP address, so the address of
+        //   the peer is used, as the specification requires
+        (0x1, 0x0 | 0x3) => Ok(ProxyHeader::Local),
+        (0x1, _) => Err(ProxyHeaderError::Invalid("unknown address family")),
+        _ => Err(ProxyHeaderError::Invalid("unknown command")),
+    }
+}
+
+impl From<io::Error> for ProxyHeaderError {
+    fn from(e: io::Error) -> Self {
+        ProxyHeaderError::Io(e)
+    }
+}
+
+impl Display for ProxyHeaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProxyHeaderError::Io(e) => write!(f, "{}", e),
+            ProxyHeaderError::Invalid(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl Error for ProxyHeaderError {}
@@ -0,0 +1,199 @@
+//! Provides per-client rate limiting of requests.
+//!
+//! When rate limiting is enabled with `App::with_rate_limit`, every request is counted against the IP
+//!   address of the client which sent it, including each request on a keep-alive connection. Once a
+//!   client has used up its allowance, its requests are answered with `429 Too Many Requests` and a
+//!   `Retry-After` header without being routed, until enough time has passed for another request.
+//!
+//! The allowance is replenished continuously rather than being reset at the end of each window, so
+//!   there is no boundary around which a client can make twice as many requests as it should.
+
+use crate::clock;
+use crate::http::headers::HeaderType;
+use crate::http::response::Response;
+use crate::http::status::StatusCode;
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Represents how many requests each client can make and how many clients are tracked at once.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RateLimitConfig {
+    /// The number of requests each client can make per window, which must be at least one.
+    pub requests: u32,
+    /// The length of the window.
+    pub window: Duration,
+    /// The number of requests each client can make at once, which must be at least one.
+    ///
+    /// In any period, a client can make at most this many requests, plus one for every
+    ///   `window / requests` which has elapsed.
+    pub burst: u32,
+    /// The maximum number of clients whose requests are tracked at once.
+    ///
+    /// Clients are forgotten as soon as their allowance has been fully replenished. If the limit is
+    ///   reached regardless, the client closest to having its allowance replenished is forgotten.
+    pub max_clients: usize,
+}
+
+/// Tracks the requests made by each client and decides whether they are allowed.
+///
+/// Clones share the same counts, so a limiter can be stored in the app's state to check other
+///   events, such as failed login attempts, against the same allowance.
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    clients: Arc<Mutex<Clients>>,
+}
+
+/// The times at which the allowance of each client will be fully replenished.
+#[derive(Debug)]
+struct Clients {
+    replenished_at: HashMap<IpAddr, Instant>,
+    last_sweep: Instant,
+}
+
+impl RateLimitConfig {
+    /// Creates a configuration which allows each client to make the given number of requests per
+    ///   window, all of which can be made at once, and tracks up to 10,000 clients.
+    pub fn new(requests: u32, window: Duration) -> Self {
+        Self {
+            requests,
+            window,
+            burst: requests,
+            max_clients: 10_000,
+        }
+    }
+
+    /// Sets the number of requests each client can make at once.
+    /// Returns itself for use in a builder pattern.
+    pub fn with_burst(mut self, burst: u32) -> Self {
+        self.burst = burst;
+        self
+    }
+
+    /// Sets the maximum number of clients whose requests are tracked at once.
+    /// Returns itself for use in a builder pattern.
+    pub fn with_max_clients(mut self, max_clients: usize) -> Self {
+        self.max_clients = max_clients;
+        self
+    }
+
+    /// Returns the time between requests once a client has used up its burst.
+    fn interval(&self) -> Duration {
+        self.window / self.requests.max(1)
+    }
+
+    /// Returns how far ahead of the present a client's allowance can be used up while still allowing
+    ///   another request.
+    fn tolerance(&self) -> Duration {
+        self.interval() * (self.burst.max(1) - 1)
+    }
+}
+
+impl RateLimiter {
+    /// Creates a new rate limiter with the given configuration, which is not tracking any clients.
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            clients: Arc::new(Mutex::new(Clients {
+                replenished_at: HashMap::new(),
+                last_sweep: clock::now_monotonic(),
+            })),
+        }
+    }
+
+    /// Returns the configuration of the rate limiter.
+    pub fn config(&self) -> &RateLimitConfig {
+        &self.config
+    }
+
+    /// Counts a request from the given client, returning how long the client must wait before trying
+    ///   again if the request is not allowed.
+    ///
+    /// Requests which are not allowed are not counted.
+    pub fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+        self.check_at(ip, clock::now_monotonic())
+    }
+
+    /// Counts a request from the given client as if it were made at the given time.
+    pub(crate) fn check_at(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut clients = self.clients.lock().unwrap();
+
+        // Clients whose allowance has been replenished are forgotten at most once per window, so that
+        //   the cost of sweeping is spread over many requests
+        if now.saturating_duration_since(clients.last_sweep) >= self.config.window {
+            clients.sweep(now);
+        }
+
+        if !clients.replenished_at.contains_key(&ip)
+            && clients.replenished_at.len() >= self.config.max_clients
+        {
+            clients.sweep(now);
+            clients.evict();
+        }
+
+        let replenished_at = clients.replenished_at.get(&ip).copied().unwrap_or(now);
+        let used = replenished_at.saturating_duration_since(now);
+        let tolerance = self.config.tolerance();
+
+        if used > tolerance {
+            return Err(used - tolerance);
+        }
+
+        clients
+            .replenished_at
+            .insert(ip, now.max(replenished_at) + self.config.interval());
+
+        Ok(())
+    }
+
+    /// Returns the number of clients currently being tracked.
+    pub fn clients(&self) -> usize {
+        self.clients.lock().unwrap().replenished_at.len()
+    }
+
+    /// Describes the limit, for monitoring and error handlers.
+    pub(crate) fn describe(&self) -> String {
+        format!(
+            "{} requests per {:?}",
+            self.config.requests, self.config.window
+        )
+    }
+}
+
+impl Clients {
+    /// Forgets every client whose allowance has been fully replenished.
+    fn sweep(&mut self, now: Instant) {
+        self.replenished_at
+            .retain(|_, replenished_at| *replenished_at > now);
+        self.last_sweep = now;
+    }
+
+    /// Forgets the client closest to having its allowance replenished.
+    fn evict(&mut self) {
+        let closest = self
+            .replenished_at
+            .iter()
+            .min_by_key(|(_, replenished_at)| **replenished_at)
+            .map(|(ip, _)| *ip);
+
+        if let Some(ip) = closest {
+            self.replenished_at.remove(&ip);
+        }
+    }
+}
+
+/// Returns the response to send instead of routing a request from a client which must wait the given
+///   amount of time before making another request.
+pub(crate) fn rate_limited_response(
+    retry_after: Duration,
+    error_handler: &dyn Fn(StatusCode) -> Response,
+) -> Response {
+    // Round up so that clients never retry before the requested time
+    let retry_after = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+
+    error_handler(StatusCode::TooManyRequests)
+        .with_header(HeaderType::RetryAfter, retry_after.to_string())
+}
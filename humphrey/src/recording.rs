@@ -0,0 +1,393 @@
+//! Provides functionality for recording the raw bytes of requests for debugging.
+//!
+//! Requests are captured as they are read from the stream, before any parsing takes place, so
+//!   requests which fail to parse are recorded exactly as they were received. Recordings written
+//!   to a directory can be replayed against a server with a tool such as `nc`.
+
+use crate::http::date::DateTime;
+use crate::http::headers::{HeaderLike, HeaderType};
+use crate::http::{Request, StatusCode};
+use crate::krauss::wildcard_match;
+use crate::trace::{SpanId, TraceId};
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Represents the configuration for recording requests.
+///
+/// By default, every request is recorded without its body, the `Authorization` and `Cookie`
+///   headers are redacted, and at most one megabyte is recorded per minute.
+///
+/// ## Example
+/// ```
+/// let config = RecordingConfig::new(RecordingSink::Directory("./recordings".into()))
+///     .with_route("/api/*")
+///     .with_status_class(4)
+///     .with_body(true);
+/// ```
+pub struct RecordingConfig {
+    sink: RecordingSink,
+    routes: Vec<String>,
+    status_classes: Vec<u16>,
+    sample_every: usize,
+    include_body: bool,
+    redacted_headers: Vec<HeaderType>,
+    byte_limit: usize,
+}
+
+/// Represents a destination for recorded requests.
+pub enum RecordingSink {
+    /// Writes each recording to a pair of files in the given directory.
+    ///
+    /// The raw request is written to a file with the `.http` extension, and the response status,
+    ///   timing, peer address and any trace IDs are written to a file with the same name and the
+    ///   `.meta` extension.
+    Directory(PathBuf),
+    /// Sends each recording down the given channel.
+    Channel(Sender<Recording>),
+}
+
+/// Represents a single recorded request.
+#[derive(Clone, Debug)]
+pub struct Recording {
+    /// The address of the client which sent the request.
+    pub peer: SocketAddr,
+    /// The UNIX timestamp at which the request was recorded.
+    pub timestamp: i64,
+    /// The raw request line and headers, after redaction.
+    pub head: Vec<u8>,
+    /// The raw request body, if bodies are being recorded and there was one.
+    pub body: Option<Vec<u8>>,
+    /// Whether the request could be parsed.
+    pub parsed: bool,
+    /// The status code of the response which was sent.
+    pub status: StatusCode,
+    /// The time taken to handle the request and write the response.
+    pub duration: Duration,
+    /// The ID of the trace of the request, if it was parsed and trace propagation is enabled.
+    pub trace_id: Option<TraceId>,
+    /// The ID of the span of the handling of the request, if it was parsed and trace propagation is
+    ///   enabled.
+    pub span_id: Option<SpanId>,
+}
+
+/// Records requests according to a `RecordingConfig`.
+pub struct Recorder {
+    config: RecordingConfig,
+    matched: AtomicUsize,
+    budget: Mutex<(Instant, usize)>,
+}
+
+/// Copies every byte read from the inner reader into a buffer, or only those up to the end of the
+///   request head if `head_only` is set.
+pub(crate) struct TeeReader<'a, R> {
+    inner: &'a mut R,
+    copy: &'a mut Vec<u8>,
+    head_only: bool,
+    finished: bool,
+}
+
+impl RecordingConfig {
+    /// Creates a new recording configuration which records to the given sink.
+    pub fn new(sink: RecordingSink) -> Self {
+        Self {
+            sink,
+            routes: Vec::new(),
+            status_classes: Vec::new(),
+            sample_every: 1,
+            include_body: false,
+            redacted_headers: vec![HeaderType::Authorization, HeaderType::Cookie],
+            byte_limit: 1024 * 1024,
+        }
+    }
+
+    /// Only records requests to routes matching the given pattern, for example `/api/*`.
+    /// Can be called multiple times to record requests matching any of the patterns.
+    ///
+    /// If the request could not be parsed, the path is taken from the raw request line where possible.
+    pub fn with_route(mut self, route: impl AsRef<str>) -> Self {
+        self.routes.push(route.as_ref().to_string());
+        self
+    }
+
+    /// Only records requests whose response status is in the given class, for example `4` for 4xx errors.
+    /// Can be called multiple times to record requests in any of the classes.
+    pub fn with_status_class(mut self, class: u16) -> Self {
+        self.status_classes.push(class);
+        self
+    }
+
+    /// Only records one in every `n` requests which match the other conditions.
+    pub fn with_sampling(mut self, n: usize) -> Self {
+        self.sample_every = n.max(1);
+        self
+    }
+
+    /// Sets whether request bodies should be recorded as well as the request line and headers.
+    pub fn with_body(mut self, include_body: bool) -> Self {
+        self.include_body = include_body;
+        self
+    }
+
+    /// Redacts the value of the given header before the request is recorded.
+    pub fn with_redacted_header(mut self, header: impl HeaderLike) -> Self {
+        self.redacted_headers.push(header.to_header());
+        self
+    }
+
+    /// Stops the default `Authorization` and `Cookie` headers from being redacted.
+    /// Headers added with `with_redacted_header` are still redacted.
+    pub fn without_default_redaction(mut self) -> Self {
+        self.redacted_headers
+            .retain(|h| h != &HeaderType::Authorization && h != &HeaderType::Cookie);
+        self
+    }
+
+    /// Sets the maximum number of bytes to record per minute.
+    /// Recordings which would exceed this limit are discarded.
+    pub fn with_byte_limit(mut self, bytes_per_minute: usize) -> Self {
+        self.byte_limit = bytes_per_minute;
+        self
+    }
+}
+
+impl Recorder {
+    /// Creates a new recorder with the given configuration.
+    pub fn new(config: RecordingConfig) -> Self {
+        Self {
+            config,
+            matched: AtomicUsize::new(0),
+            budget: Mutex::new((Instant::now(), 0)),
+        }
+    }
+
+    /// Records the given raw request if it matches the configuration.
+    ///
+    /// `request` should be the parsed request, or `None` if the raw bytes could not be parsed.
+    pub fn record(
+        &self,
+        peer: SocketAddr,
+        raw: &[u8],
+        request: Option<&Request>,
+        status: StatusCode,
+        duration: Duration,
+    ) {
+        if raw.is_empty() {
+            return;
+        }
+
+        let index = match self.matches(raw, request, status) {
+            Some(index) => index,
+            None => return,
+        };
+
+        let (head, body) = match raw.windows(4).position(|w| w == b"\r\n\r\n") {
+            Some(index) => (&raw[..index + 4], Some(&raw[index + 4..])),
+            None => (raw, None),
+        };
+
+        let head = redact(head, &self.config.redacted_headers);
+        let body = body
+            .filter(|body| self.config.include_body && !body.is_empty())
+            .map(|body| body.to_vec());
+
+        if !self.take_budget(head.len() + body.as_ref().map_or(0, |b| b.len())) {
+            return;
+        }
+
+        let trace = request.and_then(|request| request.trace.as_ref());
+        let recording = Recording {
+            peer,
+            timestamp: DateTime::now().get_timestamp(),
+            head,
+            body,
+            parsed: request.is_some(),
+            status,
+            duration,
+            trace_id: trace.map(|trace| trace.trace_id),
+            span_id: trace.map(|trace| trace.span_id),
+        };
+
+        match &self.config.sink {
+            RecordingSink::Directory(directory) => {
+                write_recording(directory, &recording, index).ok();
+            }
+            RecordingSink::Channel(sender) => {
+                sender.send(recording).ok();
+            }
+        }
+    }
+
+    /// Returns whether the request bodies should be captured as well as the request heads.
+    pub(crate) fn includes_body(&self) -> bool {
+        self.config.include_body
+    }
+
+    /// Checks whether the request matches the route, status and sampling conditions, returning the
+    ///   index of the request among those which matched the route and status if it does.
+    ///
+    /// The index is unique to this request even if others are recorded at the same time, so it is
+    ///   used to name the files of the recording.
+    fn matches(&self, raw: &[u8], request: Option<&Request>, status: StatusCode) -> Option<usize> {
+        if !self.config.routes.is_empty() {
+            let uri = match request {
+                Some(request) => Some(request.uri.clone()),
+                None => raw_uri(raw),
+            };
+
+            let matches_route = uri.is_some_and(|uri| {
+                self.config
+                    .routes
+                    .iter()
+                    .any(|route| wildcard_match(route, &uri))
+            });
+
+            if !matches_route {
+                return None;
+            }
+        }
+
+        if !self.config.status_classes.is_empty()
+            && !self
+                .config
+                .status_classes
+                .contains(&(u16::from(status) / 100))
+        {
+            return None;
+        }
+
+        let index = self.matched.fetch_add(1, Ordering::SeqCst);
+
+        index
+            .is_multiple_of(self.config.sample_every)
+            .then_some(index)
+    }
+
+    /// Attempts to use the given number of bytes from the current minute's budget.
+    fn take_budget(&self, bytes: usize) -> bool {
+        let mut budget = self.budget.lock().unwrap();
+
+        if budget.0.elapsed() >= Duration::from_secs(60) {
+            *budget = (Instant::now(), 0);
+        }
+
+        if budget.1 + bytes > self.config.byte_limit {
+            false
+        } else {
+            budget.1 += bytes;
+            true
+        }
+    }
+}
+
+impl<'a, R> TeeReader<'a, R> {
+    /// Creates a new reader which copies everything read from `inner` into `copy`, stopping at the
+    ///   end of the request head if `head_only` is set.
+    pub(crate) fn new(inner: &'a mut R, copy: &'a mut Vec<u8>, head_only: bool) -> Self {
+        Self {
+            inner,
+            copy,
+            head_only,
+            finished: false,
+        }
+    }
+}
+
+impl<'a, R> Read for TeeReader<'a, R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let length = self.inner.read(buf)?;
+
+        if self.finished {
+            return Ok(length);
+        }
+
+        // The end of the head may span the previous read, so the search starts just before it
+        let start = self.copy.len().saturating_sub(3);
+        self.copy.extend_from_slice(&buf[..length]);
+
+        if self.head_only {
+            if let Some(index) = self.copy[start..].windows(4).position(|w| w == b"\r\n\r\n") {
+                self.copy.truncate(start + index + 4);
+                self.finished = true;
+            }
+        }
+
+        Ok(length)
+    }
+}
+
+/// Replaces the values of the given headers in a raw request head.
+/// Works line-by-line on the raw bytes so that malformed requests can still be redacted.
+fn redact(head: &[u8], headers: &[HeaderType]) -> Vec<u8> {
+    let mut redacted: Vec<u8> = Vec::with_capacity(head.len());
+
+    for (i, line) in head.split_inclusive(|&b| b == b'\n').enumerate() {
+        let name = line
+            .iter()
+            .position(|&b| b == b':')
+            .filter(|_| i > 0)
+            .map(|colon| &line[..colon]);
+
+        match name {
+            Some(name)
+                if headers.contains(&HeaderType::from(String::from_utf8_lossy(name).trim())) =>
+            {
+                redacted.extend_from_slice(name);
+                redacted.extend_from_slice(b": [REDACTED]\r\n");
+            }
+            _ => redacted.extend_from_slice(line),
+        }
+    }
+
+    redacted
+}
+
+/// Attempts to extract the path from the request line of a raw request.
+fn raw_uri(raw: &[u8]) -> Option<String> {
+    let line_end = raw.iter().position(|&b| b == b'\n').unwrap_or(raw.len());
+    let line = std::str::from_utf8(&raw[..line_end]).ok()?;
+    let target = line.split(' ').nth(1)?;
+
+    Some(target.split('?').next()?.to_string())
+}
+
+/// Writes a recording and its metadata to files in the given directory.
+fn write_recording(
+    directory: &Path,
+    recording: &Recording,
+    index: usize,
+) -> Result<(), std::io::Error> {
+    let name = format!("{}-{}", recording.timestamp, index);
+
+    let mut file = File::create(directory.join(format!("{}.http", name)))?;
+    file.write_all(&recording.head)?;
+    if let Some(body) = &recording.body {
+        file.write_all(body)?;
+    }
+
+    let mut meta = File::create(directory.join(format!("{}.meta", name)))?;
+    write!(
+        meta,
+        "peer: {}\ntime: {}\nparsed: {}\nstatus: {}\nduration_ms: {}\n",
+        recording.peer,
+        DateTime::from(recording.timestamp).to_string(),
+        recording.parsed,
+        u16::from(recording.status),
+        recording.duration.as_millis()
+    )?;
+
+    if let (Some(trace_id), Some(span_id)) = (recording.trace_id, recording.span_id) {
+        write!(meta, "trace_id: {}\nspan_id: {}\n", trace_id, span_id)?;
+    }
+
+    Ok(())
+}
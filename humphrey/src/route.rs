@@ -1,14 +1,36 @@
 //! Provides functionality for handling app routes.
 
 use crate::app::{
-    PathAwareRequestHandler, RequestHandler, StatelessRequestHandler, WebsocketHandler,
+    ErrorHandler, PathAwareRequestHandler, PathAwareWebsocketHandler, RequestHandler,
+    StatelessRequestHandler, StreamingRequestHandler, WebsocketHandler,
 };
 use crate::http::cors::Cors;
-use crate::krauss;
-use crate::percent::PercentDecode;
+use crate::http::headers::{Header, HeaderLike, HeaderType, Headers};
+use crate::http::method::{Method, MethodOverride};
+use crate::http::mime::MimeType;
+use crate::http::params::{has_params, route_captures, segments, Segment};
+use crate::http::request::RequestError;
+use crate::http::url::{host_matches, Origin};
+use crate::http::{Request, Response, StatusCode};
+use crate::krauss::wildcard_match;
+use crate::middleware::Middleware;
+use crate::percent::decode_file_path;
+use crate::route_index::RouteIndex;
+use crate::trace::TraceId;
 
+use std::any::Any;
+use std::cmp::Reverse;
 use std::fs::metadata;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[cfg(not(feature = "tokio"))]
+use crate::file::FileVersion;
+
+pub use crate::http::params::RouteParams;
+
+/// The UTF-8 byte order mark, which some editors write at the start of text files.
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
 
 /// Represents a sub-app to run for a specific host.
 pub struct SubApp<State> {
@@ -18,19 +40,108 @@ pub struct SubApp<State> {
     pub routes: Vec<RouteHandler<State>>,
     /// The routes to process WebSocket requests for and their handlers.
     pub websocket_routes: Vec<WebsocketRouteHandler<State>>,
+    /// The routes whose request bodies are streamed to their handlers, and their handlers.
+    pub streaming_routes: Vec<StreamingRouteHandler<State>>,
     /// The CORS configuration for this subapp.
     /// If not specified, it is down to the individual routes to specify CORS configuration.
     pub cors: Option<Cors>,
+    /// The headers added to responses for this subapp's host which do not already set them.
+    pub default_headers: Headers,
+    /// The middleware which runs before and after the handlers for this subapp's host.
+    pub middleware: Vec<Middleware<State>>,
+    /// The error handler for this subapp's host, which is used instead of the app's if set.
+    pub error_handler: Option<ErrorHandler<State>>,
+    /// How paths which differ from a route only by a trailing slash are handled.
+    pub trailing_slash: TrailingSlash,
+    /// The indexes of the routes, built when the app is run.
+    index: Option<SubAppIndex>,
+}
+
+/// The indexes of each kind of route of a sub-app.
+struct SubAppIndex {
+    routes: RouteIndex,
+    websocket_routes: RouteIndex,
+    streaming_routes: RouteIndex,
+}
+
+/// Decides how paths which differ from a route only by a trailing slash, such as `/about/` for the
+///   route `/about`, are handled.
+///
+/// Only routes without wildcards are affected, since a wildcard route such as `/blog/*` already
+///   matches paths with and without a trailing slash after its prefix, and the root path `/` is never
+///   changed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TrailingSlash {
+    /// Paths only match routes with exactly the same trailing slash. This is the default.
+    #[default]
+    Strict,
+    /// Paths are handled by the route they would match with or without the trailing slash, and the
+    ///   request's `uri` is changed to the route's form before it is handled.
+    Merge,
+    /// Requests to paths which only match a route with or without the trailing slash are redirected
+    ///   to that form with `301 Moved Permanently`.
+    Redirect,
 }
 
 /// Encapsulates a route and its handler.
 pub struct RouteHandler<State> {
     /// The route that this handler will match.
     pub route: String,
+    /// The name given to the route when it was added with `with_named_route`, if any.
+    pub name: Option<String>,
     /// The handler to run when the route is matched.
     pub handler: Box<dyn RequestHandler<State>>,
     /// The CORS configuration for the route.
     pub cors: Cors,
+    /// The methods which the route handles, if it was added for specific methods with
+    ///   `with_route_method` or `with_head_route`, or `None` if it handles every method.
+    pub methods: Option<Vec<Method>>,
+}
+
+/// Describes which route a request would be handled by, as returned by `SubApp::resolve` and
+///   `App::resolve`.
+#[derive(Clone, Debug)]
+pub struct RouteMatch<'a> {
+    /// The host of the sub-app containing the route, which is `*` for the app's own routes.
+    pub host: &'a str,
+    /// The route which matched, such as `/blog/*`.
+    pub route: &'a str,
+    /// The name of the route, if it was added with `with_named_route`.
+    pub name: Option<&'a str>,
+    /// The position of the route in the order its sub-app's routes were added, starting at zero.
+    pub index: usize,
+    /// The parts of the path matched by each wildcard in the route, in order.
+    pub wildcards: Vec<&'a str>,
+    /// The names and values of the `:param` segments of the route, in order.
+    pub params: Vec<(&'a str, &'a str)>,
+    /// The CORS configuration of the route.
+    pub cors: &'a Cors,
+    /// Whether the request is a CORS preflight request, which the app answers itself using the
+    ///   route's CORS configuration instead of calling the handler.
+    pub preflight: bool,
+}
+
+/// How specifically a route matches paths, which decides the route that handles a path when
+///   several match it.
+///
+/// Routes without wildcards or parameters are the most specific, followed by routes with more
+///   literal characters, then by routes with fewer wildcards, so `/user/:id` is preferred to
+///   `/user/*` and both are preferred to `/*`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Specificity {
+    exact: bool,
+    literal: usize,
+    wildcards: Reverse<usize>,
+}
+
+/// Encapsulates a route and its streaming handler.
+pub struct StreamingRouteHandler<State> {
+    /// The route that this handler will match.
+    pub route: String,
+    /// The handler to run when the route is matched.
+    pub handler: Box<dyn StreamingRequestHandler<State>>,
+    /// The CORS configuration for the route.
+    pub cors: Cors,
 }
 
 /// Encapsulates a route and its WebSocket handler.
@@ -39,6 +150,191 @@ pub struct WebsocketRouteHandler<State> {
     pub route: String,
     /// The handler to run when the route is matched.
     pub handler: Box<dyn WebsocketHandler<State>>,
+    /// The guard which decides whether upgrade requests to the route are accepted, if any.
+    pub guard: Option<Box<dyn WebsocketGuard<State>>>,
+}
+
+impl<State> WebsocketRouteHandler<State> {
+    /// Asks the route's guard, if it has one, whether the upgrade request is accepted.
+    pub(crate) fn check_upgrade(&self, request: &Request, state: &Arc<State>) -> UpgradeDecision {
+        match &self.guard {
+            Some(guard) => {
+                guard.check(&handler_request(request, &self.route, false), state.clone())
+            }
+            None => UpgradeDecision::Accept,
+        }
+    }
+}
+
+/// The decision made by a `WebsocketGuard` about a WebSocket upgrade request.
+pub enum UpgradeDecision {
+    /// The connection is upgraded and passed to the route's handler as usual.
+    Accept,
+    /// The response is sent instead, and the connection is kept alive if the request allows it.
+    Reject(Response),
+}
+
+/// Represents a function able to decide whether a WebSocket upgrade request is accepted, before the
+///   route's handler is called.
+///
+/// This allows connections to be refused with a proper HTTP response, for example
+///   `401 Unauthorized` if the client has not authenticated, rather than by abandoning the handshake
+///   in the handler.
+///
+/// ## Example
+/// ```
+/// fn require_token(request: &Request, _: Arc<()>) -> UpgradeDecision {
+///     match request.query.contains("token=secret") {
+///         true => UpgradeDecision::Accept,
+///         false => UpgradeDecision::Reject(Response::empty(StatusCode::Unauthorized)),
+///     }
+/// }
+/// ```
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a valid WebSocket guard",
+    label = "expected a thread-safe `Fn(&Request, Arc<State>) -> UpgradeDecision`",
+    note = "guards must be `Send + Sync`, so capture shared data with `Arc` instead of `Rc` or references"
+)]
+pub trait WebsocketGuard<State>: Send + Sync {
+    #[allow(missing_docs)]
+    fn check(&self, request: &Request, state: Arc<State>) -> UpgradeDecision;
+}
+impl<F, State> WebsocketGuard<State> for F
+where
+    F: Fn(&Request, Arc<State>) -> UpgradeDecision + Send + Sync,
+{
+    fn check(&self, request: &Request, state: Arc<State>) -> UpgradeDecision {
+        self(request, state)
+    }
+}
+
+/// Describes an error which is about to be sent to the client, so that the error handler can explain
+///   what went wrong.
+///
+/// ## Example
+/// ```
+/// fn error_handler(context: &ErrorContext) -> Response {
+///     let reason = match &context.cause {
+///         Some(ErrorCause::Panic(message)) => format!("the handler failed: {}", message),
+///         Some(cause) => cause.to_string(),
+///         None => <&str>::from(context.status_code).to_string(),
+///     };
+///
+///     let mut response = Response::new(context.status_code, reason);
+///
+///     if let Some(trace_id) = context.trace_id() {
+///         response = response.with_header("X-Trace-Id", trace_id.to_string());
+///     }
+///
+///     response
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ErrorContext<'a> {
+    /// The status code of the error.
+    pub status_code: StatusCode,
+    /// What caused the error, or `None` if the status code says everything there is to say, such as
+    ///   when no route matched the request.
+    pub cause: Option<ErrorCause>,
+    /// The request, or `None` if it could not be parsed.
+    pub request: Option<&'a Request>,
+    /// The route whose handler was running when the error occurred, if any.
+    pub route: Option<&'a str>,
+}
+
+/// Represents what caused an error, as given to error handlers in an `ErrorContext`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ErrorCause {
+    /// The request could not be read, such as when it was malformed, too large or too slow.
+    Request(RequestError),
+    /// The handler of the route panicked with the given message.
+    Panic(String),
+    /// The request was refused because a limit was reached, such as the number of WebSocket
+    ///   connections, for the given reason.
+    LimitReached(String),
+    /// The request was refused because the app is draining.
+    Draining,
+}
+
+impl<'a> ErrorContext<'a> {
+    /// Creates a context for an error with the given status code and nothing else known about it.
+    pub fn new(status_code: StatusCode) -> Self {
+        Self {
+            status_code,
+            cause: None,
+            request: None,
+            route: None,
+        }
+    }
+
+    /// Sets the request which caused the error.
+    pub fn with_request(mut self, request: &'a Request) -> Self {
+        self.request = Some(request);
+        self
+    }
+
+    /// Sets what caused the error.
+    pub fn with_cause(mut self, cause: ErrorCause) -> Self {
+        self.cause = Some(cause);
+        self
+    }
+
+    /// Sets the route whose handler was running when the error occurred.
+    pub fn with_route(mut self, route: &'a str) -> Self {
+        self.route = Some(route);
+        self
+    }
+
+    /// Returns the ID of the request's trace, which can be given to the client to correlate the error
+    ///   with the app's logs, or `None` if the request could not be parsed or tracing is disabled.
+    pub fn trace_id(&self) -> Option<TraceId> {
+        self.request
+            .and_then(|request| request.trace.as_ref())
+            .map(|trace| trace.trace_id)
+    }
+}
+
+impl std::fmt::Display for ErrorCause {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorCause::Request(e) => write!(f, "{}", e),
+            ErrorCause::Panic(message) => write!(f, "Handler panicked: {}", message),
+            ErrorCause::LimitReached(reason) => write!(f, "Limit reached: {}", reason),
+            ErrorCause::Draining => write!(f, "The app is draining"),
+        }
+    }
+}
+
+impl ErrorCause {
+    /// Describes the panic of a handler from the payload it panicked with.
+    pub(crate) fn panic(payload: Box<dyn Any + Send>) -> Self {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast::<&'static str>() {
+                Ok(message) => message.to_string(),
+                Err(_) => "<unknown>".to_string(),
+            },
+        };
+
+        ErrorCause::Panic(message)
+    }
+}
+
+impl<State> RouteHandler<State> {
+    /// Returns whether the route handles requests with the given method, which it does for every
+    ///   method unless it was added for specific methods.
+    pub fn allows(&self, method: &Method) -> bool {
+        self.methods
+            .as_ref()
+            .is_none_or(|methods| methods.contains(method))
+    }
+
+    /// Returns whether the route was added specifically to handle the given method.
+    pub(crate) fn declares(&self, method: &Method) -> bool {
+        self.methods
+            .as_ref()
+            .is_some_and(|methods| methods.contains(method))
+    }
 }
 
 impl<State> Default for SubApp<State> {
@@ -47,7 +343,13 @@ impl<State> Default for SubApp<State> {
             host: "*".to_string(),
             routes: Vec::new(),
             websocket_routes: Vec::new(),
+            streaming_routes: Vec::new(),
             cors: None,
+            default_headers: Headers::new(),
+            middleware: Vec::new(),
+            error_handler: None,
+            trailing_slash: TrailingSlash::Strict,
+            index: None,
         }
     }
 }
@@ -60,18 +362,71 @@ impl<State> SubApp<State> {
 
     /// Adds a route and associated handler to the sub-app.
     /// Routes can include wildcards, for example `/blog/*`.
+    ///
+    /// If several routes match a path, the most specific one handles it, whatever order they were
+    ///   added in. Routes without wildcards or parameters are preferred, then routes with longer
+    ///   literal parts, so `/contact` is preferred to `/*`.
+    ///
+    /// The handler is called from whichever worker happens to receive the request, so it must be
+    ///   `Send + Sync`. Requiring this here means that a closure capturing an `Rc` or a reference is
+    ///   reported at the call site rather than somewhere inside the app.
     pub fn with_route<T>(mut self, route: &str, handler: T) -> Self
     where
-        T: RequestHandler<State> + 'static,
+        T: RequestHandler<State> + Send + Sync + 'static,
     {
         self.routes.push(RouteHandler {
             route: route.to_string(),
+            name: None,
             handler: Box::new(handler),
             cors: self.cors.as_ref().map_or(Cors::default(), |c| c.clone()),
+            methods: None,
         });
         self
     }
 
+    /// Adds a route and associated handler to the sub-app, giving it a name by which it can be
+    ///   identified when resolving requests with `resolve`.
+    /// Routes can include wildcards, for example `/blog/*`.
+    pub fn with_named_route<T>(mut self, name: &str, route: &str, handler: T) -> Self
+    where
+        T: RequestHandler<State> + Send + Sync + 'static,
+    {
+        self = self.with_route(route, handler);
+        self.routes.last_mut().unwrap().name = Some(name.to_string());
+        self
+    }
+
+    /// Adds a route and associated handler to the sub-app which only handles `HEAD` requests.
+    /// Routes can include wildcards, for example `/files/*`.
+    ///
+    /// Without one, `HEAD` requests are handled by the route which would handle them as `GET`
+    ///   requests, and the body of its response is not sent. A `HEAD` route is checked before the
+    ///   other routes of the sub-app, so it can answer without generating a body which would be
+    ///   discarded.
+    pub fn with_head_route<T>(self, route: &str, handler: T) -> Self
+    where
+        T: RequestHandler<State> + Send + Sync + 'static,
+    {
+        self.with_route_method(route, Method::Head, handler)
+    }
+
+    /// Adds a route and associated handler to the sub-app which only handles requests with the given
+    ///   method.
+    /// Routes can include wildcards, for example `/api/items/*`.
+    ///
+    /// Requests to the route's path with other methods fall through to other matching routes, and if
+    ///   no route handles them, are answered with `405 Method Not Allowed` and an `Allow` header
+    ///   listing the methods of the routes which match the path. A route for `GET` requests also
+    ///   handles `HEAD` requests, unless a route has been added for them specifically.
+    pub fn with_route_method<T>(mut self, route: &str, method: Method, handler: T) -> Self
+    where
+        T: RequestHandler<State> + Send + Sync + 'static,
+    {
+        self = self.with_route(route, handler);
+        self.routes.last_mut().unwrap().methods = Some(vec![method]);
+        self
+    }
+
     /// Adds a route and associated handler to the sub-app.
     /// Does not pass the state to the handler.
     /// Routes can include wildcards, for example `/blog/*`.
@@ -79,12 +434,14 @@ impl<State> SubApp<State> {
     /// If you want to access the app's state in the handler, consider using `with_route`.
     pub fn with_stateless_route<T>(mut self, route: &str, handler: T) -> Self
     where
-        T: StatelessRequestHandler<State> + 'static,
+        T: StatelessRequestHandler<State> + Send + Sync + 'static,
     {
         self.routes.push(RouteHandler {
             route: route.to_string(),
+            name: None,
             handler: Box::new(move |request, _| handler.serve(request)),
             cors: self.cors.as_ref().map_or(Cors::default(), |c| c.clone()),
+            methods: None,
         });
         self
     }
@@ -94,12 +451,32 @@ impl<State> SubApp<State> {
     /// Will also pass the route to the handler at runtime.
     pub fn with_path_aware_route<T>(mut self, route: &'static str, handler: T) -> Self
     where
-        T: PathAwareRequestHandler<State> + 'static,
+        T: PathAwareRequestHandler<State> + Send + Sync + 'static,
     {
         self.routes.push(RouteHandler {
             route: route.to_string(),
+            name: None,
             handler: Box::new(move |request, state| handler.serve(request, state, route)),
             cors: self.cors.as_ref().map_or(Cors::default(), |c| c.clone()),
+            methods: None,
+        });
+        self
+    }
+
+    /// Adds a route and associated handler to the sub-app whose request bodies are streamed to the
+    ///   handler as it reads them, rather than read into memory before it is called.
+    /// Routes can include wildcards, for example `/uploads/*`.
+    ///
+    /// Streaming routes are checked before the other routes of the sub-app, and are matched against
+    ///   the request before it is rewritten.
+    pub fn with_streaming_route<T>(mut self, route: &str, handler: T) -> Self
+    where
+        T: StreamingRequestHandler<State> + Send + Sync + 'static,
+    {
+        self.streaming_routes.push(StreamingRouteHandler {
+            route: route.to_string(),
+            handler: Box::new(handler),
+            cors: self.cors.as_ref().map_or(Cors::default(), |c| c.clone()),
         });
         self
     }
@@ -109,11 +486,48 @@ impl<State> SubApp<State> {
     /// The handler is passed the stream, state, and the request which triggered its calling.
     pub fn with_websocket_route<T>(mut self, route: &str, handler: T) -> Self
     where
-        T: WebsocketHandler<State> + 'static,
+        T: WebsocketHandler<State> + Send + Sync + 'static,
+    {
+        self.websocket_routes.push(WebsocketRouteHandler {
+            route: route.to_string(),
+            handler: Box::new(handler),
+            guard: None,
+        });
+        self
+    }
+
+    /// Adds a WebSocket route and associated handler to the sub-app, with a guard which decides
+    ///   whether each upgrade request is accepted before the handler is called.
+    /// Routes can include wildcards, for example `/ws/*`.
+    ///
+    /// If the guard rejects the request, its response is sent instead of upgrading the connection,
+    ///   and the handler is not called.
+    pub fn with_guarded_websocket_route<G, T>(mut self, route: &str, guard: G, handler: T) -> Self
+    where
+        G: WebsocketGuard<State> + 'static,
+        T: WebsocketHandler<State> + Send + Sync + 'static,
     {
         self.websocket_routes.push(WebsocketRouteHandler {
             route: route.to_string(),
             handler: Box::new(handler),
+            guard: Some(Box::new(guard)),
+        });
+        self
+    }
+
+    /// Adds a path-aware WebSocket route and associated handler to the sub-app.
+    /// Routes can include wildcards, for example `/ws/*`.
+    /// Will also pass the route to the handler at runtime.
+    pub fn with_path_aware_websocket_route<T>(mut self, route: &'static str, handler: T) -> Self
+    where
+        T: PathAwareWebsocketHandler<State> + Send + Sync + 'static,
+    {
+        self.websocket_routes.push(WebsocketRouteHandler {
+            route: route.to_string(),
+            handler: Box::new(move |request, stream, state| {
+                handler.serve(request, stream, state, route)
+            }),
+            guard: None,
         });
         self
     }
@@ -130,6 +544,10 @@ impl<State> SubApp<State> {
             route.cors = cors.clone();
         });
 
+        self.streaming_routes.iter_mut().for_each(|route| {
+            route.cors = cors.clone();
+        });
+
         self
     }
 
@@ -143,8 +561,498 @@ impl<State> SubApp<State> {
             }
         });
 
+        self.streaming_routes.iter_mut().for_each(|r| {
+            if r.route == route {
+                r.cors = cors.clone();
+            }
+        });
+
+        self
+    }
+
+    /// Adds a header to every response for the sub-app's host, including those generated by the
+    ///   error handler, unless the response already sets it.
+    ///
+    /// This can be called repeatedly to add several headers. `Set-Cookie` headers are always added,
+    ///   since a response can set many cookies, and informational (`1xx`) responses are left alone.
+    pub fn with_default_header(mut self, name: impl HeaderLike, value: impl AsRef<str>) -> Self {
+        self.default_headers.add(name, value);
+        self
+    }
+
+    /// Sets the headers to add to every response for the sub-app's host, replacing any added
+    ///   previously with `with_default_header`.
+    pub fn with_default_headers(mut self, headers: Headers) -> Self {
+        self.default_headers = headers;
         self
     }
+
+    /// Adds a middleware which runs before and after the handlers for every request to the
+    ///   sub-app's host, after any middleware of the app itself.
+    ///
+    /// Middleware runs in the order it was added before the request is handled, and in the reverse
+    ///   order afterwards. See `Middleware` for details.
+    pub fn with_middleware(mut self, middleware: Middleware<State>) -> Self {
+        self.middleware.push(middleware);
+        self
+    }
+
+    /// Sets the error handler for requests to the sub-app's host, which is used instead of the
+    ///   app's error handler.
+    pub fn with_error_handler<T>(self, handler: T) -> Self
+    where
+        T: Fn(StatusCode) -> Response + Send + Sync + 'static,
+    {
+        self.with_error_handler_with_state(move |status_code, _, _| handler(status_code))
+    }
+
+    /// Sets the error handler for requests to the sub-app's host, which is also given the request
+    ///   and the app's state, and is used instead of the app's error handler.
+    pub fn with_error_handler_with_state<T>(mut self, handler: T) -> Self
+    where
+        T: Fn(StatusCode, Option<&Request>, Arc<State>) -> Response + Send + Sync + 'static,
+    {
+        let handler = move |context: &ErrorContext, state| {
+            handler(context.status_code, context.request, state)
+        };
+
+        #[cfg(not(feature = "tokio"))]
+        let handler = Box::new(handler);
+        #[cfg(feature = "tokio")]
+        let handler = Arc::new(handler);
+
+        self.error_handler = Some(handler);
+        self
+    }
+
+    /// Sets how paths which differ from a route only by a trailing slash are handled for the
+    ///   sub-app's host.
+    ///
+    /// ## Example
+    /// ```
+    /// let subapp: SubApp<()> = SubApp::new()
+    ///     .with_trailing_slash_policy(TrailingSlash::Redirect)
+    ///     .with_route("/about", about_handler);
+    /// ```
+    pub fn with_trailing_slash_policy(mut self, policy: TrailingSlash) -> Self {
+        self.trailing_slash = policy;
+        self
+    }
+
+    /// Returns the path which a request with the given method and path is handled as under the
+    ///   sub-app's trailing slash policy, or `None` if it is handled as it is.
+    ///
+    /// This is the path with its trailing slash added or removed, if that matches a route without
+    ///   wildcards more specifically than the path itself matches any route. It is always `None`
+    ///   under `TrailingSlash::Strict`.
+    pub fn canonical_path(&self, method: &Method, path: &str) -> Option<String> {
+        if self.trailing_slash == TrailingSlash::Strict {
+            return None;
+        }
+
+        let alternate = toggle_trailing_slash(path)?;
+        let route = &self.routes[self.route_index(method, &alternate)?].route;
+
+        if segments(route).contains(&Segment::Wildcard) {
+            return None;
+        }
+
+        match self.route_index(method, path) {
+            Some(index) if specificity(&self.routes[index].route) >= specificity(route) => None,
+            _ => Some(alternate),
+        }
+    }
+
+    /// Returns the route of this sub-app which would handle a request with the given method and
+    ///   path, without calling its handler.
+    ///
+    /// The path should be percent-decoded and without the query string, as in `request.uri`. The most
+    ///   specific matching route is chosen exactly as when requests are handled, with routes which are
+    ///   equally specific checked in the order they were added.
+    pub fn resolve<'a>(&'a self, method: &Method, path: &'a str) -> Option<RouteMatch<'a>> {
+        let index = self.route_index(method, path)?;
+
+        Some(self.route_match(index, method, path))
+    }
+
+    /// Returns the index of the route which handles requests with the given method and path.
+    ///
+    /// `HEAD` requests are handled by a route added specifically for them if there is one, and by the
+    ///   route which would handle the equivalent `GET` request otherwise. `OPTIONS` requests are
+    ///   answered by the app itself, so they match the most specific route for the path whatever its
+    ///   methods. `TRACE` and `CONNECT` requests only match routes added specifically for them.
+    fn route_index(&self, method: &Method, path: &str) -> Option<usize> {
+        let routes = self.route_candidates(path);
+        let matching = |accepts: &dyn Fn(&RouteHandler<State>) -> bool| {
+            most_specific(
+                routes
+                    .iter()
+                    .filter(|(_, route)| accepts(route))
+                    .map(|&(index, route)| (index, route.route.as_str())),
+                path,
+            )
+        };
+
+        match method {
+            Method::Head => matching(&|route| route.declares(&Method::Head))
+                .or_else(|| matching(&|route| route.allows(&Method::Get))),
+            Method::Options => matching(&|_| true),
+            method if method.requires_declared_route() => matching(&|route| route.declares(method)),
+            method => matching(&|route| route.allows(method)),
+        }
+    }
+
+    /// Returns the most specific streaming route which matches the path.
+    pub(crate) fn streaming_route(&self, path: &str) -> Option<&StreamingRouteHandler<State>> {
+        let index = self.index.as_ref().map(|index| &index.streaming_routes);
+        let routes = candidates(&self.streaming_routes, index, path);
+
+        most_specific(
+            routes
+                .into_iter()
+                .map(|(index, route)| (index, route.route.as_str())),
+            path,
+        )
+        .map(|index| &self.streaming_routes[index])
+    }
+
+    /// Returns the most specific WebSocket route which matches the path.
+    pub(crate) fn websocket_route(&self, path: &str) -> Option<&WebsocketRouteHandler<State>> {
+        let index = self.index.as_ref().map(|index| &index.websocket_routes);
+        let routes = candidates(&self.websocket_routes, index, path);
+
+        most_specific(
+            routes
+                .into_iter()
+                .map(|(index, route)| (index, route.route.as_str())),
+            path,
+        )
+        .map(|index| &self.websocket_routes[index])
+    }
+
+    /// Returns the routes which could match the path and their indices, in the order they were added.
+    fn route_candidates(&self, path: &str) -> Vec<(usize, &RouteHandler<State>)> {
+        let index = self.index.as_ref().map(|index| &index.routes);
+
+        candidates(&self.routes, index, path)
+    }
+
+    /// Builds the indexes used to find the routes which could match a path, so that every route does
+    ///   not need to be checked.
+    ///
+    /// This is done when the app is run, after which its routes cannot change. If routes are added or
+    ///   removed afterwards, the indexes are ignored.
+    pub(crate) fn build_index(&mut self) {
+        self.index = Some(SubAppIndex {
+            routes: RouteIndex::new(self.routes.iter().map(|route| route.route.as_str())),
+            websocket_routes: RouteIndex::new(
+                self.websocket_routes
+                    .iter()
+                    .map(|route| route.route.as_str()),
+            ),
+            streaming_routes: RouteIndex::new(
+                self.streaming_routes
+                    .iter()
+                    .map(|route| route.route.as_str()),
+            ),
+        });
+    }
+
+    /// Describes the route at the given index as matching the method and path.
+    pub(crate) fn route_match<'a>(
+        &'a self,
+        index: usize,
+        method: &Method,
+        path: &'a str,
+    ) -> RouteMatch<'a> {
+        let route = &self.routes[index];
+        let (wildcards, params) = route_captures(&route.route, path).unwrap_or_default();
+
+        RouteMatch {
+            host: &self.host,
+            route: &route.route,
+            name: route.name.as_deref(),
+            index,
+            wildcards,
+            params,
+            cors: &route.cors,
+            preflight: *method == Method::Options,
+        }
+    }
+}
+
+/// Finds the first sub-app whose host matches the given `Host` header, ignoring its port unless the
+///   sub-app's host specifies one.
+pub(crate) fn find_subapp<'a, State>(
+    subapps: &'a [SubApp<State>],
+    host: &str,
+) -> Option<&'a SubApp<State>> {
+    subapps
+        .iter()
+        .find(|subapp| host_matches(&subapp.host, host))
+}
+
+/// Finds the sub-app and the index of its route which handle requests with the given method to the
+///   given host and path.
+///
+/// The first sub-app whose host matches is checked first, then the default sub-app if it has no
+///   matching route.
+pub(crate) fn find_route<'a, State>(
+    subapps: &'a [SubApp<State>],
+    default_subapp: &'a SubApp<State>,
+    method: &Method,
+    host: Option<&str>,
+    path: &str,
+) -> Option<(&'a SubApp<State>, usize)> {
+    let subapp = host.and_then(|host| find_subapp(subapps, host));
+
+    subapp
+        .into_iter()
+        .chain(std::iter::once(default_subapp))
+        .find_map(|subapp| {
+            subapp
+                .route_index(method, path)
+                .map(|index| (subapp, index))
+        })
+}
+
+/// Applies the trailing slash policy of the sub-app which handles the request, checking the first
+///   sub-app whose host matches and then the default sub-app.
+///
+/// Under `TrailingSlash::Merge`, the request's `uri` is changed to the form of the route which will
+///   handle it. Under `TrailingSlash::Redirect`, a redirect to that form is returned instead, keeping
+///   the path as the client sent it and the query string, unless the request is a CORS preflight
+///   request, which is handled as under `TrailingSlash::Merge`.
+pub(crate) fn apply_trailing_slash<State>(
+    request: &mut Request,
+    subapps: &[SubApp<State>],
+    default_subapp: &SubApp<State>,
+) -> Option<Response> {
+    let host = request.headers.get(HeaderType::Host);
+    let subapp = host.and_then(|host| find_subapp(subapps, host));
+
+    // The first sub-app which handles the path, in either form, decides how it is handled
+    let mut found = None;
+    for subapp in subapp.into_iter().chain(std::iter::once(default_subapp)) {
+        found = subapp
+            .canonical_path(&request.method, &request.uri)
+            .map(|canonical| (canonical, subapp.trailing_slash));
+
+        if found.is_some() || subapp.route_index(&request.method, &request.uri).is_some() {
+            break;
+        }
+    }
+
+    let (canonical, policy) = found?;
+
+    // Preflight requests are never redirected, since browsers do not follow redirects for them
+    match policy {
+        TrailingSlash::Redirect if request.method != Method::Options => {
+            let mut location = toggle_trailing_slash(&request.uri_raw)?;
+
+            if !request.query.is_empty() {
+                location.push('?');
+                location.push_str(&request.query);
+            }
+
+            Some(Response::permanent_redirect(location))
+        }
+        _ => {
+            request.uri = canonical;
+            None
+        }
+    }
+}
+
+/// Returns the path with its trailing slash removed if it has one, or added if it does not, or
+///   `None` for the root path.
+fn toggle_trailing_slash(path: &str) -> Option<String> {
+    if path.is_empty() || path == "/" {
+        return None;
+    }
+
+    match path.strip_suffix('/') {
+        Some(stripped) => Some(stripped.to_string()),
+        None => Some(format!("{}/", path)),
+    }
+}
+
+/// Returns the value of the `Allow` header for requests to the given host and path, listing the
+///   methods of the routes which match the path if they were all added for specific methods.
+///
+/// Returns `None` if no route matches the path, or if one of them handles every method. `HEAD` is
+///   allowed wherever `GET` is, and `OPTIONS` is always allowed since the app answers it itself.
+pub(crate) fn allow_header<State>(
+    subapps: &[SubApp<State>],
+    default_subapp: &SubApp<State>,
+    host: Option<&str>,
+    path: &str,
+) -> Option<String> {
+    let subapp = host.and_then(|host| find_subapp(subapps, host));
+
+    let routes = subapp
+        .into_iter()
+        .chain(std::iter::once(default_subapp))
+        .flat_map(|subapp| subapp.route_candidates(path))
+        .map(|(_, route)| route)
+        .filter(|route| route.route.route_matches(path));
+
+    let mut allowed: Vec<Method> = Vec::new();
+    for route in routes {
+        for method in route.methods.as_ref()? {
+            if !allowed.contains(method) {
+                allowed.push(method.clone());
+            }
+        }
+    }
+
+    if allowed.is_empty() {
+        return None;
+    }
+
+    if allowed.contains(&Method::Get) && !allowed.contains(&Method::Head) {
+        allowed.push(Method::Head);
+    }
+
+    if !allowed.contains(&Method::Options) {
+        allowed.push(Method::Options);
+    }
+
+    let allowed: Vec<String> = allowed.iter().map(|method| method.to_string()).collect();
+
+    Some(allowed.join(", "))
+}
+
+/// Finds the streaming route which handles requests to the given host and path, checking the first
+///   sub-app whose host matches and then the default sub-app.
+pub(crate) fn find_streaming_route<'a, State>(
+    subapps: &'a [SubApp<State>],
+    default_subapp: &'a SubApp<State>,
+    host: Option<&str>,
+    path: &str,
+) -> Option<&'a StreamingRouteHandler<State>> {
+    let subapp = host.and_then(|host| find_subapp(subapps, host));
+
+    subapp
+        .into_iter()
+        .chain(std::iter::once(default_subapp))
+        .find_map(|subapp| subapp.streaming_route(path))
+}
+
+/// Adds the default headers of the sub-app for the given host and of the default sub-app to the
+///   response.
+///
+/// Headers which the response already has are not replaced, except for `Set-Cookie`, and the
+///   host's defaults take precedence over those of the default sub-app with the same name.
+///   Informational (`1xx`) responses are left unchanged.
+pub(crate) fn apply_default_headers<State>(
+    response: &mut Response,
+    subapps: &[SubApp<State>],
+    default_subapp: &SubApp<State>,
+    host: Option<&str>,
+) {
+    if (100..200).contains(&u16::from(response.status_code)) {
+        return;
+    }
+
+    let host_defaults = host
+        .and_then(|host| find_subapp(subapps, host))
+        .map(|subapp| &subapp.default_headers);
+
+    let app_defaults = default_subapp
+        .default_headers
+        .iter()
+        .filter(|header| host_defaults.and_then(|h| h.get(&header.name)).is_none());
+
+    // Decide which defaults to add before adding any, so that a default can occur multiple times
+    let defaults: Vec<Header> = host_defaults
+        .into_iter()
+        .flat_map(|headers| headers.iter())
+        .chain(app_defaults)
+        .filter(|header| {
+            header.name == HeaderType::SetCookie || response.headers.get(&header.name).is_none()
+        })
+        .collect();
+
+    for header in defaults {
+        response.headers.push(header);
+    }
+}
+
+/// Generates the response for an error with the error handler of the sub-app for the request's host,
+///   or with the app's error handler if it does not have one.
+///
+/// If the request could not be parsed, the app's error handler is used.
+pub(crate) fn handle_error<State>(
+    context: &ErrorContext,
+    subapps: &[SubApp<State>],
+    error_handler: &ErrorHandler<State>,
+    state: &Arc<State>,
+) -> Response {
+    let host = context
+        .request
+        .and_then(|request| request.headers.get(HeaderType::Host));
+    let handler = host
+        .and_then(|host| find_subapp(subapps, host))
+        .and_then(|subapp| subapp.error_handler.as_ref())
+        .unwrap_or(error_handler);
+
+    handler(context, state.clone())
+}
+
+/// Handles the request as the method it asks for with the given method override, if any, keeping the
+///   method it was sent with in `overridden_method`.
+///
+/// A request from another origin is only overridden to a method which the CORS configuration of its
+///   route allows that origin to use, since otherwise any page could use a form to make requests with
+///   methods that browsers only send after a successful preflight request.
+pub(crate) fn apply_method_override<State>(
+    request: &mut Request,
+    method_override: &MethodOverride,
+    subapps: &[SubApp<State>],
+    default_subapp: &SubApp<State>,
+) {
+    let method = match method_override.requested(request) {
+        Some(method) => method,
+        None => return,
+    };
+
+    let own_origin = Origin::from_request(request, &[]).map(|origin| origin.to_string());
+
+    if let Some(origin) = request.headers.get(HeaderType::Origin) {
+        if own_origin.as_deref() != Some(origin.to_ascii_lowercase().as_str()) {
+            let host = request.headers.get(HeaderType::Host);
+            let cors = find_streaming_route(subapps, default_subapp, host, &request.uri)
+                .map(|route| &route.cors)
+                .or_else(|| {
+                    find_route(subapps, default_subapp, &method, host, &request.uri)
+                        .map(|(subapp, index)| &subapp.routes[index].cors)
+                });
+
+            if !cors.is_some_and(|cors| cors.allows(origin, &method)) {
+                return;
+            }
+        }
+    }
+
+    request.overridden_method = Some(std::mem::replace(&mut request.method, method));
+}
+
+/// Returns the request to pass to the handler of the given route, with the parameters of the route
+///   captured from its path, which for a `HEAD` request to a route which does not handle `HEAD`
+///   requests itself is the equivalent `GET` request.
+///
+/// The request is then handled as a `GET` request, but `original_method` still returns `HEAD` so
+///   that handlers can avoid generating a body which will not be sent.
+pub(crate) fn handler_request(request: &Request, route: &str, handles_head: bool) -> Request {
+    let mut request = request.clone();
+    request.params = RouteParams::from_route(route, &request.uri);
+
+    if request.method == Method::Head && !handles_head {
+        request.overridden_method = Some(std::mem::replace(&mut request.method, Method::Get));
+    }
+
+    request
 }
 
 /// An object that can represent a route, currently only `String`.
@@ -154,13 +1062,109 @@ pub trait Route {
 }
 
 impl Route for String {
-    /// Checks whether this route matches the given one, respecting its own wildcards only.
+    /// Checks whether this route matches the given one, respecting its own wildcards and parameters
+    ///   only.
     /// For example, `/blog/*` will match `/blog/my-first-post` but not the other way around.
     fn route_matches(&self, route: &str) -> bool {
-        krauss::wildcard_match(self, route)
+        route_matches(self, route)
     }
 }
 
+/// Returns the index of the most specific of the given routes which matches the path, choosing the
+///   route with the lowest index if several are equally specific.
+pub(crate) fn most_specific<'a>(
+    routes: impl Iterator<Item = (usize, &'a str)>,
+    path: &str,
+) -> Option<usize> {
+    routes
+        .filter(|(_, route)| route_matches(route, path))
+        .min_by_key(|&(index, route)| (Reverse(specificity(route)), index))
+        .map(|(index, _)| index)
+}
+
+/// Returns the routes which could match the path and their indices, using the index of the routes
+///   if it is given and up to date, or every route otherwise.
+fn candidates<'a, T>(
+    routes: &'a [T],
+    index: Option<&RouteIndex>,
+    path: &str,
+) -> Vec<(usize, &'a T)> {
+    match index {
+        Some(index) if index.len() == routes.len() => index
+            .candidates(path)
+            .into_iter()
+            .map(|i| (i, &routes[i]))
+            .collect(),
+        _ => routes.iter().enumerate().collect(),
+    }
+}
+
+/// Builds the route indexes of the sub-apps of an app which is about to be run.
+pub(crate) fn build_indexes<State>(
+    subapps: &mut [SubApp<State>],
+    default_subapp: &mut SubApp<State>,
+) {
+    for subapp in subapps {
+        subapp.build_index();
+    }
+
+    default_subapp.build_index();
+}
+
+/// Calculates how specifically the route matches paths.
+fn specificity(route: &str) -> Specificity {
+    let mut literal = 0;
+    let mut wildcards = 0;
+    let mut params = 0;
+
+    for segment in segments(route) {
+        match segment {
+            Segment::Literal(part) => literal += part.chars().count(),
+            Segment::Wildcard => wildcards += 1,
+            Segment::Param(_) => params += 1,
+        }
+    }
+
+    Specificity {
+        exact: wildcards == 0 && params == 0,
+        literal,
+        wildcards: Reverse(wildcards),
+    }
+}
+
+/// Returns whether the route matches the path, respecting its wildcards and parameters.
+fn route_matches(route: &str, path: &str) -> bool {
+    match has_params(route) {
+        true => route_captures(route, path).is_some(),
+        false => wildcard_match(route, path),
+    }
+}
+
+/// Options for serving files from the filesystem.
+///
+/// The file handlers, such as `serve_file`, use the default options, and `serve_dir_with_options`
+///   allows them to be changed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DirOptions {
+    /// Whether to add the `charset=utf-8` parameter to the `Content-Type` of textual files.
+    ///   Defaults to `true`.
+    pub charset: bool,
+    /// Whether to remove a UTF-8 byte order mark from the start of textual files before they are
+    ///   sent. Defaults to `false`.
+    pub strip_bom: bool,
+    /// The size in bytes above which files are streamed from disk while they are sent, rather than
+    ///   being read into memory first. `None` means files are never streamed. Defaults to 1 MiB.
+    pub stream_threshold: Option<u64>,
+    /// Whether to send `X-Content-Type-Options: nosniff` with files, so that browsers do not guess
+    ///   a different type from their contents. Defaults to `true`.
+    pub nosniff: bool,
+    /// Whether the files may have been uploaded by untrusted users. If so, files whose types could
+    ///   run scripts, such as HTML, SVG and XML, and files whose types are unknown are sent as
+    ///   `application/octet-stream` with `Content-Disposition: attachment`, so that browsers download
+    ///   them instead of displaying them. Defaults to `false`.
+    pub untrusted_uploads: bool,
+}
+
 /// A located file or directory path.
 pub enum LocatedPath {
     /// A directory was located.
@@ -169,15 +1173,228 @@ pub enum LocatedPath {
     File(PathBuf),
 }
 
+impl Default for DirOptions {
+    fn default() -> Self {
+        Self {
+            charset: true,
+            strip_bom: false,
+            stream_threshold: Some(1024 * 1024),
+            nosniff: true,
+            untrusted_uploads: false,
+        }
+    }
+}
+
+impl DirOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether to add the `charset=utf-8` parameter to the `Content-Type` of textual files.
+    pub fn with_charset(mut self, charset: bool) -> Self {
+        self.charset = charset;
+        self
+    }
+
+    /// Sets whether to remove a UTF-8 byte order mark from the start of textual files.
+    pub fn with_strip_bom(mut self, strip_bom: bool) -> Self {
+        self.strip_bom = strip_bom;
+        self
+    }
+
+    /// Sets the size in bytes above which files are streamed from disk, or `None` to never stream
+    ///   them.
+    pub fn with_stream_threshold(mut self, stream_threshold: Option<u64>) -> Self {
+        self.stream_threshold = stream_threshold;
+        self
+    }
+
+    /// Sets whether to send `X-Content-Type-Options: nosniff` with files.
+    pub fn with_nosniff(mut self, nosniff: bool) -> Self {
+        self.nosniff = nosniff;
+        self
+    }
+
+    /// Sets whether the files may have been uploaded by untrusted users, in which case files which
+    ///   could run scripts or whose types are unknown are sent as downloads.
+    pub fn with_untrusted_uploads(mut self, untrusted_uploads: bool) -> Self {
+        self.untrusted_uploads = untrusted_uploads;
+        self
+    }
+
+    /// Generates the response for the file at the given path, reading it into memory if it is no
+    ///   larger than the stream threshold and streaming it from disk otherwise.
+    #[cfg(not(feature = "tokio"))]
+    pub(crate) fn open_file(&self, path: &Path) -> std::io::Result<Response> {
+        self.open_file_versioned(path).map(|(response, _)| response)
+    }
+
+    /// Generates the response for the file at the given path in the same way as `open_file`, also
+    ///   returning the version of the file which was opened.
+    ///
+    /// The version is read from the open file, so it describes the contents of the response even if
+    ///   the file at the path is replaced while it is being read.
+    #[cfg(not(feature = "tokio"))]
+    pub(crate) fn open_file_versioned(
+        &self,
+        path: &Path,
+    ) -> std::io::Result<(Response, FileVersion)> {
+        use std::io::Read;
+
+        let mut file = std::fs::File::open(path)?;
+        let metadata = file.metadata()?;
+        let version = FileVersion::from_metadata(&metadata);
+        let length = metadata.len();
+
+        if !matches!(self.stream_threshold, Some(threshold) if length > threshold) {
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents)?;
+
+            return Ok((self.file_response(path, contents), version));
+        }
+
+        // Only the start of the file is read, in case it is a byte order mark to be removed
+        let mut start = Vec::new();
+        (&mut file)
+            .take(UTF8_BOM.len() as u64)
+            .read_to_end(&mut start)?;
+        let length = length - start.len() as u64;
+
+        let response = Response::from_reader(StatusCode::OK, file, length);
+
+        Ok((self.streamed_file_response(path, response, start), version))
+    }
+
+    /// Generates the response for the file at the given path, reading it into memory if it is no
+    ///   larger than the stream threshold and streaming it from disk otherwise.
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn open_file(&self, path: &Path) -> std::io::Result<Response> {
+        use tokio::io::AsyncReadExt;
+
+        let mut file = tokio::fs::File::open(path).await?;
+        let length = file.metadata().await?.len();
+
+        if !matches!(self.stream_threshold, Some(threshold) if length > threshold) {
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents).await?;
+
+            return Ok(self.file_response(path, contents));
+        }
+
+        // Only the start of the file is read, in case it is a byte order mark to be removed
+        let mut start = Vec::new();
+        (&mut file)
+            .take(UTF8_BOM.len() as u64)
+            .read_to_end(&mut start)
+            .await?;
+        let length = length - start.len() as u64;
+
+        let response = Response::from_reader(StatusCode::OK, file, length);
+
+        Ok(self.streamed_file_response(path, response, start))
+    }
+
+    /// Generates the response for a file with the given contents, inferring its MIME type from the
+    ///   extension of its path.
+    ///
+    /// The byte order mark is removed before the body is set, so the `Content-Length` of the
+    ///   response always matches the bytes which are sent. Files of other types are sent unchanged.
+    pub(crate) fn file_response(&self, path: &Path, mut contents: Vec<u8>) -> Response {
+        let textual = self.mime_type(path).is_some_and(|m| m.is_textual());
+
+        if self.strip_bom && textual && contents.starts_with(UTF8_BOM) {
+            contents.drain(..UTF8_BOM.len());
+        }
+
+        self.with_file_headers(path, Response::new(StatusCode::OK, contents))
+    }
+
+    /// Completes the response for a file whose body is streamed, given the bytes already read from
+    ///   the start of the file, in the same way as `file_response`.
+    fn streamed_file_response(&self, path: &Path, response: Response, start: Vec<u8>) -> Response {
+        let textual = self.mime_type(path).is_some_and(|m| m.is_textual());
+
+        let start = match self.strip_bom && textual && start == UTF8_BOM {
+            true => Vec::new(),
+            false => start,
+        };
+
+        self.with_file_headers(path, response.with_bytes(start))
+    }
+
+    /// Adds the headers which describe the file at the given path to its response, which are its
+    ///   `Content-Type`, inferred from the extension of the path, and the `X-Content-Type-Options` and
+    ///   `Content-Disposition` headers required by the options.
+    ///
+    /// This allows files which are not read by the file handlers, such as those served from a cache,
+    ///   to be sent with exactly the same headers.
+    pub fn with_file_headers(&self, path: &Path, mut response: Response) -> Response {
+        if let Some(mime_type) = self.mime_type(path) {
+            response = response.with_header(HeaderType::ContentType, self.content_type(mime_type));
+        }
+
+        if self.is_download(path) {
+            response = response.with_header(HeaderType::ContentDisposition, "attachment");
+        }
+
+        if self.nosniff {
+            response = response.with_header(HeaderType::from("X-Content-Type-Options"), "nosniff");
+        }
+
+        response
+    }
+
+    /// Returns the MIME type with which the file at the given path is sent, or `None` if it is sent
+    ///   without a `Content-Type` because it has no extension.
+    fn mime_type(&self, path: &Path) -> Option<MimeType> {
+        if self.is_download(path) {
+            return Some(MimeType::ApplicationOctetStream);
+        }
+
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .map(MimeType::from_extension)
+    }
+
+    /// Returns `true` if the file at the given path must be downloaded rather than displayed, because
+    ///   it may have been uploaded by an untrusted user and its type is either unknown or could run
+    ///   scripts.
+    fn is_download(&self, path: &Path) -> bool {
+        if !self.untrusted_uploads {
+            return false;
+        }
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) => {
+                let mime_type = MimeType::from_extension(extension);
+                mime_type == MimeType::ApplicationOctetStream || mime_type.is_executable()
+            }
+            None => true,
+        }
+    }
+
+    /// Returns the `Content-Type` header value for the MIME type.
+    fn content_type(&self, mime_type: MimeType) -> String {
+        match self.charset {
+            true => mime_type.to_content_type(),
+            false => mime_type.to_string(),
+        }
+    }
+}
+
 /// Attempts to find a given path.
 /// If the path itself is not found, attempts to find index files within it.
 /// If these are not found, returns `None`.
+///
+/// The path may be percent-encoded or decoded as `request.uri` is. Paths which contain an encoded
+///   slash (`%2F`) are never found, so that it cannot be used to escape the directory.
 pub fn try_find_path(
     directory: &str,
     request_path: &str,
     index_files: &[&str],
 ) -> Option<LocatedPath> {
-    let request_path = String::from_utf8(request_path.percent_decode()?).ok()?;
+    let request_path = decode_file_path(request_path)?;
 
     // Avoid path traversal exploits
     if request_path.contains("..") || request_path.contains(':') {
@@ -0,0 +1,155 @@
+//! Provides an index of routes by their literal prefixes, so that the routes which could match a path
+//!   are found without checking every route.
+//!
+//! Every route which matches a path must start with the literal part of the route before its first
+//!   wildcard or parameter, and a route without either must be equal to the path. The index is a
+//!   radix tree of these prefixes, so walking it along the path finds every route which could match
+//!   in time proportional to the length of the path rather than the number of routes. The candidates
+//!   are then checked and ranked exactly as they would be without the index.
+//!
+//! Apps build an index for each of their sub-apps when they are run, since their routes cannot change
+//!   afterwards. Sub-apps without one, such as those checked with `App::resolve`, check every route.
+
+use crate::http::params::{segments, Segment};
+
+/// An index of a list of routes by their literal prefixes.
+#[derive(Debug, Default)]
+pub(crate) struct RouteIndex {
+    root: Node,
+    len: usize,
+}
+
+/// A node of the radix tree, which is reached by following the labels of the nodes above it.
+#[derive(Debug, Default)]
+struct Node {
+    /// The part of the prefix matched by this node, which is only empty for the root.
+    label: Vec<u8>,
+    /// The child nodes, whose labels each start with a different byte, sorted by that byte.
+    children: Vec<Node>,
+    /// The indices of the routes without wildcards or parameters which are equal to the prefix.
+    exact: Vec<usize>,
+    /// The indices of the routes with wildcards or parameters whose literal prefix ends here.
+    prefixed: Vec<usize>,
+}
+
+impl RouteIndex {
+    /// Builds an index of the given routes, in the order they were added.
+    pub(crate) fn new<'a>(routes: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut index = Self::default();
+
+        for route in routes {
+            // Routes without wildcards or parameters are a single literal part, or empty
+            let (prefix, exact) = match segments(route).as_slice() {
+                [] => ("", true),
+                [Segment::Literal(literal)] => (*literal, true),
+                [Segment::Literal(literal), ..] => (*literal, false),
+                _ => ("", false),
+            };
+
+            let node = index.root.insert(prefix.as_bytes());
+
+            match exact {
+                true => node.exact.push(index.len),
+                false => node.prefixed.push(index.len),
+            }
+
+            index.len += 1;
+        }
+
+        index
+    }
+
+    /// Returns the number of routes in the index.
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the indices of the routes which could match the path, in ascending order.
+    ///
+    /// Every route which matches the path is included, but the routes still need to be checked
+    ///   against the path since a wildcard or parameter may not match the rest of it.
+    pub(crate) fn candidates(&self, path: &str) -> Vec<usize> {
+        let mut candidates = Vec::new();
+        let mut node = &self.root;
+        let mut rest = path.as_bytes();
+
+        loop {
+            candidates.extend_from_slice(&node.prefixed);
+
+            let first = match rest.first() {
+                Some(first) => *first,
+                None => {
+                    candidates.extend_from_slice(&node.exact);
+                    break;
+                }
+            };
+
+            match node.child(first) {
+                Some(child) if rest.starts_with(&child.label) => {
+                    rest = &rest[child.label.len()..];
+                    node = child;
+                }
+                _ => break,
+            }
+        }
+
+        candidates.sort_unstable();
+        candidates
+    }
+}
+
+impl Node {
+    /// Returns the child whose label starts with the given byte, if any.
+    fn child(&self, first: u8) -> Option<&Node> {
+        self.children
+            .binary_search_by_key(&first, |child| child.label[0])
+            .ok()
+            .map(|i| &self.children[i])
+    }
+
+    /// Returns the node for the given prefix below this one, adding and splitting nodes as needed.
+    fn insert(&mut self, prefix: &[u8]) -> &mut Node {
+        let first = match prefix.first() {
+            Some(first) => *first,
+            None => return self,
+        };
+
+        let i = match self
+            .children
+            .binary_search_by_key(&first, |child| child.label[0])
+        {
+            Ok(i) => i,
+            Err(i) => {
+                let child = Node {
+                    label: prefix.to_vec(),
+                    ..Node::default()
+                };
+
+                self.children.insert(i, child);
+                return &mut self.children[i];
+            }
+        };
+
+        let child = &mut self.children[i];
+        let common = child
+            .label
+            .iter()
+            .zip(prefix)
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        // Split the child so that its label ends where the prefix diverges from it
+        if common < child.label.len() {
+            let rest = child.label.split_off(common);
+            let split = std::mem::take(child);
+
+            child.label = split.label;
+            child.children.push(Node {
+                label: rest,
+                ..split
+            });
+        }
+
+        child.insert(&prefix[common..])
+    }
+}
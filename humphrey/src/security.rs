@@ -0,0 +1,102 @@
+//! Provides security-related defaults for apps, including a hardened preset.
+//!
+//! By default, apps only add a `Server: Humphrey` header to responses, and limit the headers of
+//!   requests to 16 KiB and their bodies to 10 MiB so that a single client cannot exhaust the
+//!   server's memory. `App::with_hardened_defaults` applies `SecurityOptions::hardened` instead,
+//!   after which each option can still be overridden individually.
+
+use crate::http::headers::HeaderType;
+use crate::http::request::{RequestLimits, DEFAULT_MAX_BODY_SIZE, DEFAULT_MAX_HEADER_SIZE};
+use crate::http::response::Response;
+
+use std::time::Duration;
+
+/// The value of the `Server` header which is sent by default.
+pub const DEFAULT_SERVER_HEADER: &str = "Humphrey";
+
+/// Represents the security-related headers added to responses and the limits applied to requests.
+///
+/// Headers are only added to responses which do not already set them, so handlers can override any
+///   of them for individual responses.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SecurityOptions {
+    /// The value of the `Server` header, or `None` to omit it.
+    pub server_header: Option<String>,
+    /// The value of the `X-Content-Type-Options` header, or `None` to omit it.
+    pub content_type_options: Option<String>,
+    /// The value of the `X-Frame-Options` header, or `None` to omit it.
+    pub frame_options: Option<String>,
+    /// The `max-age` of the `Strict-Transport-Security` header, or `None` to omit it.
+    ///
+    /// The header is only ever sent over TLS connections, as required by the specification.
+    pub hsts_max_age: Option<Duration>,
+    /// The limits on the size of requests.
+    pub request_limits: RequestLimits,
+}
+
+impl SecurityOptions {
+    /// Returns the hardened options applied by `App::with_hardened_defaults`.
+    ///
+    /// These omit the `Server` header, send `X-Content-Type-Options: nosniff` and
+    ///   `X-Frame-Options: DENY`, send `Strict-Transport-Security` with a `max-age` of one year over
+    ///   TLS, and apply `RequestLimits::conservative`.
+    pub fn hardened() -> Self {
+        Self {
+            server_header: None,
+            content_type_options: Some("nosniff".to_string()),
+            frame_options: Some("DENY".to_string()),
+            hsts_max_age: Some(Duration::from_secs(365 * 24 * 60 * 60)),
+            request_limits: RequestLimits::conservative(),
+        }
+    }
+
+    /// Adds the headers to the response, unless the response already sets them.
+    ///
+    /// `secure` indicates whether the response will be sent over a TLS connection.
+    pub fn apply(&self, response: &mut Response, secure: bool) {
+        let hsts = self
+            .hsts_max_age
+            .filter(|_| secure)
+            .map(|max_age| format!("max-age={}", max_age.as_secs()));
+
+        let headers = [
+            (HeaderType::Server, self.server_header.as_deref()),
+            (
+                HeaderType::from("X-Content-Type-Options"),
+                self.content_type_options.as_deref(),
+            ),
+            (
+                HeaderType::from("X-Frame-Options"),
+                self.frame_options.as_deref(),
+            ),
+            (
+                HeaderType::from("Strict-Transport-Security"),
+                hsts.as_deref(),
+            ),
+        ];
+
+        for (header, value) in headers {
+            if let Some(value) = value {
+                if response.headers.get(&header).is_none() {
+                    response.headers.add(header, value);
+                }
+            }
+        }
+    }
+}
+
+impl Default for SecurityOptions {
+    fn default() -> Self {
+        Self {
+            server_header: Some(DEFAULT_SERVER_HEADER.to_string()),
+            content_type_options: None,
+            frame_options: None,
+            hsts_max_age: None,
+            request_limits: RequestLimits {
+                max_uri_length: None,
+                max_header_size: Some(DEFAULT_MAX_HEADER_SIZE),
+                max_body_size: Some(DEFAULT_MAX_BODY_SIZE),
+            },
+        }
+    }
+}
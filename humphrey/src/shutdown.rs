@@ -0,0 +1,279 @@
+//! Provides the signal which gracefully shuts down an app.
+//!
+//! The same `ShutdownSignal` is accepted by both the synchronous and the tokio app, so that
+//!   shutdown works the same way whichever runtime is used. Clones of a signal share their state,
+//!   so one clone can be given to the app with `with_shutdown` and another kept to trigger it.
+//!
+//! Dropping a signal never triggers it, so an app whose signal is dropped without being triggered
+//!   keeps accepting connections as if it had no shutdown signal at all.
+//!
+//! Once an app stops running, `run` returns a `ShutdownSummary` describing what it did while it
+//!   was running and why it stopped.
+
+use crate::http::StatusCode;
+
+use std::fmt::Display;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[cfg(not(feature = "tokio"))]
+use std::sync::mpsc::Receiver;
+
+#[cfg(feature = "tokio")]
+use tokio_util::sync::CancellationToken;
+
+/// How often `wait` checks signals created from a flag, which cannot notify it when they are set.
+#[cfg(feature = "tokio")]
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A signal which gracefully shuts down the app it is given to once it is triggered.
+///
+/// ## Example
+/// ```
+/// let shutdown = ShutdownSignal::new();
+///
+/// let app: App<()> = App::new()
+///     .with_shutdown(shutdown.clone())
+///     .with_stateless_route("/", home);
+///
+/// spawn(move || {
+///     sleep(Duration::from_secs(5));
+///     shutdown.trigger();
+/// });
+///
+/// app.run("0.0.0.0:80")?;
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ShutdownSignal {
+    triggered: Arc<AtomicBool>,
+    /// Whether the flag may be set by code which does not know about the signal, in which case it
+    ///   has to be polled.
+    #[cfg(feature = "tokio")]
+    polled: bool,
+    #[cfg(feature = "tokio")]
+    token: CancellationToken,
+}
+
+/// Describes what an app did while it was running, returned by `run` once the app stops.
+///
+/// ## Example
+/// ```
+/// let summary = app.run("0.0.0.0:80")?;
+///
+/// println!("{}", summary);
+/// ```
+#[derive(Debug)]
+pub struct ShutdownSummary {
+    /// The number of connections accepted and handled by the app.
+    ///
+    /// Connections which were denied by the connection condition or rejected because the app was
+    ///   handling its maximum number of connections are not counted.
+    pub connections_accepted: u64,
+    /// The number of responses sent, by the class of their status code.
+    pub requests: RequestCounts,
+    /// The number of connections which were still open when the app stopped, such as connections
+    ///   whose requests were still being handled after the drain period.
+    ///
+    /// These connections are not waited for, so they are closed without being answered once their
+    ///   handlers next return or the process exits.
+    pub connections_abandoned: u64,
+    /// How long the app was running for.
+    pub uptime: Duration,
+    /// Why the app stopped running.
+    pub reason: ExitReason,
+}
+
+/// The number of responses sent by an app, by the class of their status code.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RequestCounts {
+    /// The number of `1xx` responses.
+    pub informational: u64,
+    /// The number of `2xx` responses.
+    pub success: u64,
+    /// The number of `3xx` responses.
+    pub redirection: u64,
+    /// The number of `4xx` responses.
+    pub client_error: u64,
+    /// The number of `5xx` responses.
+    pub server_error: u64,
+}
+
+/// The reason an app stopped running.
+#[derive(Debug)]
+pub enum ExitReason {
+    /// The shutdown signal was triggered.
+    Shutdown,
+    /// Every listener failed in a way which stopped it from accepting connections, with this being
+    ///   the last error encountered.
+    ListenerError(io::Error),
+}
+
+/// Counts the connections and responses handled by a running app, from which its
+///   `ShutdownSummary` is built.
+///
+/// Clones share their counts. Custom connection handlers should call `record_response` for every
+///   response they send.
+#[derive(Clone, Debug, Default)]
+pub struct AppCounters(Arc<Counts>);
+
+#[derive(Debug, Default)]
+struct Counts {
+    connections: AtomicU64,
+    responses: [AtomicU64; 5],
+}
+
+impl RequestCounts {
+    /// Returns the total number of responses sent.
+    pub fn total(&self) -> u64 {
+        self.informational + self.success + self.redirection + self.client_error + self.server_error
+    }
+}
+
+impl AppCounters {
+    /// Counts a response sent with the given status code.
+    pub fn record_response(&self, status_code: StatusCode) {
+        let class = (u16::from(status_code) / 100).clamp(1, 5) as usize;
+
+        self.0.responses[class - 1].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts an accepted connection.
+    pub(crate) fn record_connection(&self) {
+        self.0.connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Builds the summary of an app which started running at the given time.
+    pub(crate) fn summary(
+        &self,
+        started: Instant,
+        connections_abandoned: usize,
+        reason: ExitReason,
+    ) -> ShutdownSummary {
+        let [informational, success, redirection, client_error, server_error] = self
+            .0
+            .responses
+            .each_ref()
+            .map(|count| count.load(Ordering::Relaxed));
+
+        ShutdownSummary {
+            connections_accepted: self.0.connections.load(Ordering::Relaxed),
+            requests: RequestCounts {
+                informational,
+                success,
+                redirection,
+                client_error,
+                server_error,
+            },
+            connections_abandoned: connections_abandoned as u64,
+            uptime: started.elapsed(),
+            reason,
+        }
+    }
+}
+
+impl Display for ShutdownSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Served {} requests on {} connections in {}s ({} abandoned), ",
+            self.requests.total(),
+            self.connections_accepted,
+            self.uptime.as_secs(),
+            self.connections_abandoned
+        )?;
+
+        match &self.reason {
+            ExitReason::Shutdown => write!(f, "stopped by the shutdown signal"),
+            ExitReason::ListenerError(e) => write!(f, "stopped by a listener error: {}", e),
+        }
+    }
+}
+
+impl ShutdownSignal {
+    /// Creates a new signal which has not been triggered.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Triggers the signal, shutting down the app.
+    ///
+    /// Triggering a signal more than once has no further effect.
+    pub fn trigger(&self) {
+        self.triggered.store(true, Ordering::SeqCst);
+
+        #[cfg(feature = "tokio")]
+        self.token.cancel();
+    }
+
+    /// Returns `true` if the signal has been triggered.
+    pub fn is_triggered(&self) -> bool {
+        #[cfg(feature = "tokio")]
+        if self.token.is_cancelled() {
+            return true;
+        }
+
+        self.triggered.load(Ordering::SeqCst)
+    }
+
+    /// Waits until the signal is triggered.
+    #[cfg(feature = "tokio")]
+    pub async fn wait(&self) {
+        while !self.is_triggered() {
+            if self.polled {
+                tokio::select! {
+                    _ = self.token.cancelled() => (),
+                    _ = tokio::time::sleep(POLL_INTERVAL) => (),
+                }
+            } else {
+                self.token.cancelled().await;
+            }
+        }
+    }
+}
+
+impl From<Arc<AtomicBool>> for ShutdownSignal {
+    /// Creates a signal which is triggered when the flag is set, as well as by `trigger`, which sets
+    ///   the flag.
+    fn from(triggered: Arc<AtomicBool>) -> Self {
+        Self {
+            triggered,
+            #[cfg(feature = "tokio")]
+            polled: true,
+            #[cfg(feature = "tokio")]
+            token: CancellationToken::new(),
+        }
+    }
+}
+
+#[cfg(not(feature = "tokio"))]
+impl From<Receiver<()>> for ShutdownSignal {
+    /// Creates a signal which is triggered when a message is received or every sender has been
+    ///   dropped.
+    ///
+    /// A thread waits for the message in the background.
+    fn from(receiver: Receiver<()>) -> Self {
+        let signal = Self::new();
+        let cloned_signal = signal.clone();
+
+        std::thread::spawn(move || {
+            let _ = receiver.recv();
+            cloned_signal.trigger();
+        });
+
+        signal
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl From<CancellationToken> for ShutdownSignal {
+    /// Creates a signal which is triggered when the token is cancelled, and which cancels the token
+    ///   when it is triggered.
+    fn from(token: CancellationToken) -> Self {
+        Self {
+            token,
+            ..Self::default()
+        }
+    }
+}
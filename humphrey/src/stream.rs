@@ -61,6 +61,25 @@ impl Stream {
         }
     }
 
+    /// Returns the local socket address of this connection, which identifies the listener it was
+    ///   accepted by.
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        match self {
+            Stream::Tcp(stream) => stream.local_addr(),
+            #[cfg(feature = "tls")]
+            Stream::Tls(stream) => stream.sock.local_addr(),
+        }
+    }
+
+    /// Returns whether this connection is secured with TLS.
+    pub fn is_secure(&self) -> bool {
+        match self {
+            Stream::Tcp(_) => false,
+            #[cfg(feature = "tls")]
+            Stream::Tls(_) => true,
+        }
+    }
+
     /// Shuts down both the read and write halves of this connection.
     pub fn shutdown(&self) -> std::io::Result<()> {
         match self {
@@ -102,4 +121,23 @@ impl Stream {
             Stream::Tls(stream) => stream.sock.set_nonblocking(false),
         }
     }
+
+    /// Returns the underlying TCP socket, so that data can be read from it before the TLS handshake.
+    pub(crate) fn tcp_mut(&mut self) -> &mut TcpStream {
+        match self {
+            Stream::Tcp(stream) => stream,
+            #[cfg(feature = "tls")]
+            Stream::Tls(stream) => &mut stream.sock,
+        }
+    }
+
+    /// Creates another handle to the underlying TCP socket, so that the connection can be checked
+    ///   while the stream is in use elsewhere.
+    pub(crate) fn try_clone_socket(&self) -> std::io::Result<TcpStream> {
+        match self {
+            Stream::Tcp(stream) => stream.try_clone(),
+            #[cfg(feature = "tls")]
+            Stream::Tls(stream) => stream.sock.try_clone(),
+        }
+    }
 }
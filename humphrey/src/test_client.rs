@@ -0,0 +1,407 @@
+//! Provides a client which sends requests to an app without running it, for integration tests.
+//!
+//! An app can be converted into a `TestClient` with `App::into_test_client`. Each request is
+//!   written to an in-memory stream and parsed from it as if it had been received on a connection,
+//!   then handled in the same way as by `App::run`, including sub-apps matched by the `Host` header,
+//!   middleware, CORS, the request condition and rewriter, the rate limiter and the error handler.
+//!   The response is given the same default headers, but is returned directly instead of being
+//!   written to a connection.
+//!
+//! Since no connection is involved, WebSocket routes, hijacked responses, draining and keep-alive
+//!   limits are not supported, and no monitoring events are sent.
+
+use crate::app::{
+    get_allow_header, get_handler, get_streaming_handler, panic_response, ConnectionOptions,
+    ErrorHandler,
+};
+use crate::http::body::RequestBody;
+use crate::http::date::DateTime;
+use crate::http::headers::HeaderType;
+use crate::http::method::{Method, MethodOverride};
+use crate::http::request::{BodyDeferral, HeadCondition, Request, RequestError};
+use crate::http::response::Response;
+use crate::http::status::StatusCode;
+use crate::middleware::{find_middleware, run_request_middleware, run_response_middleware};
+use crate::rate_limit::rate_limited_response;
+use crate::route::{
+    apply_default_headers, apply_method_override, apply_trailing_slash, handle_error,
+    handler_request, ErrorCause, ErrorContext, SubApp,
+};
+
+use std::net::SocketAddr;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+
+/// Sends requests to an app's routes without binding a port, returning the responses which the app
+///   would send.
+///
+/// Clones share the same app and state.
+pub struct TestClient<State> {
+    subapps: Arc<Vec<SubApp<State>>>,
+    default_subapp: Arc<SubApp<State>>,
+    error_handler: Arc<ErrorHandler<State>>,
+    state: Arc<State>,
+    options: Arc<ConnectionOptions>,
+}
+
+impl<State> TestClient<State>
+where
+    State: Send + Sync + 'static,
+{
+    /// Creates a client for the given app, whose route indexes have already been built.
+    pub(crate) fn new(
+        subapps: Arc<Vec<SubApp<State>>>,
+        default_subapp: Arc<SubApp<State>>,
+        error_handler: Arc<ErrorHandler<State>>,
+        state: Arc<State>,
+        options: Arc<ConnectionOptions>,
+    ) -> Self {
+        Self {
+            subapps,
+            default_subapp,
+            error_handler,
+            state,
+            options,
+        }
+    }
+
+    /// Returns the state of the app, so that tests can check how requests changed it.
+    pub fn state(&self) -> &Arc<State> {
+        &self.state
+    }
+
+    /// Sends a `GET` request for the given URI, which may include a query string.
+    pub fn get(&self, uri: impl AsRef<str>) -> Response {
+        self.request(Request::builder().uri(uri).build())
+    }
+
+    /// Sends a `POST` request for the given URI with the given body.
+    pub fn post(&self, uri: impl AsRef<str>, body: impl AsRef<[u8]>) -> Response {
+        self.request(
+            Request::builder()
+                .method(Method::Post)
+                .uri(uri)
+                .body(body)
+                .build(),
+        )
+    }
+
+    /// Sends the given request, which appears to come from its address and is secure if it says so,
+    ///   and returns the response.
+    ///
+    /// Streamed response bodies are read into `body`, so the response has a `Content-Length` header
+    ///   as if its body had not been streamed.
+    pub fn request(&self, request: Request) -> Response {
+        let (subapps, default_subapp, state, options) = (
+            &self.subapps,
+            &self.default_subapp,
+            &self.state,
+            &self.options,
+        );
+        let error_handler =
+            |context: ErrorContext| handle_error(&context, subapps, &self.error_handler, state);
+
+        let addr = SocketAddr::new(request.address.origin_addr, request.address.port);
+        let secure = request.secure;
+        let bytes: Vec<u8> = request.into();
+        let mut stream = &bytes[..];
+
+        // Parses the request from the stream, checking the head against the request condition
+        let mut rejection: Option<(Request, Response)> = None;
+        let mut check_head = |head: &Request| {
+            let response = options
+                .request_condition
+                .as_ref()
+                .and_then(|condition| condition(head));
+            let accepted = response.is_none();
+            rejection = response.map(|response| (head.clone(), response));
+
+            accepted
+        };
+        let condition = options
+            .request_condition
+            .as_ref()
+            .map(|_| &mut check_head as HeadCondition);
+
+        // Leave the body unread if the request is for a streaming route
+        let mut is_streamed = |head: &Request| {
+            head.method != Method::Options
+                && get_streaming_handler(head, subapps, default_subapp).is_some()
+        };
+        let mut deferral = BodyDeferral {
+            condition: &mut is_streamed as HeadCondition,
+            buffered: None,
+        };
+
+        let request = Request::from_reader_checked(
+            &mut stream,
+            addr,
+            condition,
+            Some(&mut deferral),
+            &options.security.request_limits,
+        );
+        let buffered = deferral.buffered;
+
+        // If the request was rejected by the request condition, respond using its head
+        let (mut request, mut rejected) = match (request, rejection) {
+            (Err(RequestError::Rejected), Some((head, response))) => (head, Some(response)),
+            (Ok(request), _) => (request, None),
+            (Err(e), _) => return self.request_error(e, secure),
+        };
+
+        request.secure = secure;
+        request.set_deadline(options.timeout, &options.deadline_proxies);
+        request.set_trace(options.trace);
+
+        // Rewrite the request before it is routed, which may also reject it
+        if let (None, Some(rewriter)) = (&rejected, &options.request_rewriter) {
+            rejected = rewriter(&mut request);
+        }
+
+        // Handle the request as the method it asks for, if the method override is enabled
+        if let (None, Some(method_override)) = (&rejected, &options.method_override) {
+            apply_method_override(&mut request, method_override, subapps, default_subapp);
+        }
+
+        // Merge or redirect paths which only match a route with a different trailing slash
+        if rejected.is_none() {
+            rejected = apply_trailing_slash(&mut request, subapps, default_subapp);
+        }
+
+        // Refuse the request if its client has used up its allowance under the rate limit
+        if let (None, Some(limiter)) = (&rejected, &options.rate_limiter) {
+            if let Err(retry_after) = limiter.check(addr.ip()) {
+                rejected = Some(rate_limited_response(retry_after, &|status_code| {
+                    error_handler(
+                        ErrorContext::new(status_code)
+                            .with_request(&request)
+                            .with_cause(ErrorCause::LimitReached(limiter.describe())),
+                    )
+                }));
+            }
+        }
+
+        // Run the request side of the middleware for the request's host, which may answer it itself
+        let host = request.headers.get(&HeaderType::Host);
+        let middleware = find_middleware(subapps, default_subapp, host);
+        let mut middleware_ran = 0;
+        if rejected.is_none() && request.method != Method::Options {
+            (middleware_ran, rejected) = run_request_middleware(&middleware, &mut request, state);
+        }
+
+        let mut keep_alive = request
+            .headers
+            .get(&HeaderType::Connection)
+            .is_some_and(|connection| connection.eq_ignore_ascii_case("keep-alive"))
+            && request.body_complete()
+            && (rejected.is_none() || request.rejected_body_drained());
+
+        let mut response = match rejected {
+            None if request.method == Method::Options => self.preflight(&request, keep_alive),
+            rejected => {
+                let handler = get_handler(&request, subapps, default_subapp);
+                let streaming_handler = get_streaming_handler(&request, subapps, default_subapp);
+                let mut body = buffered.map(|buffered| {
+                    RequestBody::new(
+                        buffered,
+                        &mut stream,
+                        &request.headers,
+                        &options.security.request_limits,
+                    )
+                });
+
+                let mut response = match (rejected, streaming_handler, &mut body, handler) {
+                    (Some(mut response), ..) => {
+                        if !keep_alive {
+                            response.headers.add(HeaderType::Connection, "Close");
+                        }
+
+                        response
+                    }
+                    (None, Some(handler), Some(body), _) => {
+                        let served = catch_unwind(AssertUnwindSafe(|| {
+                            handler.handler.serve(
+                                handler_request(&request, &handler.route, false),
+                                body,
+                                state.clone(),
+                            )
+                        }));
+
+                        let mut response = served.unwrap_or_else(|payload| {
+                            keep_alive = false;
+                            panic_response(&request, &handler.route, payload, &error_handler)
+                        });
+
+                        handler.cors.set_headers(&mut response.headers);
+
+                        response
+                    }
+                    (None, _, _, Some(handler)) => {
+                        let served = catch_unwind(AssertUnwindSafe(|| {
+                            handler.handler.serve(
+                                handler_request(
+                                    &request,
+                                    &handler.route,
+                                    handler.declares(&Method::Head),
+                                ),
+                                state.clone(),
+                            )
+                        }));
+
+                        let mut response = served.unwrap_or_else(|payload| {
+                            keep_alive = false;
+                            panic_response(&request, &handler.route, payload, &error_handler)
+                        });
+
+                        handler.cors.set_headers(&mut response.headers);
+
+                        response
+                    }
+                    // Methods only routed to routes added for them are not implemented otherwise
+                    (None, ..) if request.method.requires_declared_route() => error_handler(
+                        ErrorContext::new(StatusCode::NotImplemented).with_request(&request),
+                    ),
+                    (None, ..) => match get_allow_header(&request, subapps, default_subapp) {
+                        Some(allow) => error_handler(
+                            ErrorContext::new(StatusCode::MethodNotAllowed).with_request(&request),
+                        )
+                        .with_header(HeaderType::Allow, allow),
+                        None => error_handler(
+                            ErrorContext::new(StatusCode::NotFound).with_request(&request),
+                        ),
+                    },
+                };
+
+                run_response_middleware(
+                    &middleware[..middleware_ran],
+                    &mut response,
+                    &request,
+                    state,
+                );
+
+                if let Some(mut body) = body {
+                    if !body.finish() {
+                        keep_alive = false;
+                        response.headers.remove(HeaderType::Connection);
+                        response.headers.add(HeaderType::Connection, "Close");
+                    }
+                }
+
+                self.finalise(response, &request)
+            }
+        };
+
+        // HTTP/1.0 clients do not understand chunked framing and only keep the connection open if
+        //   they asked to
+        if request.version == "HTTP/1.0" {
+            response.finalise_http_1_0(keep_alive);
+        }
+
+        let host = request.headers.get(&HeaderType::Host);
+        apply_default_headers(&mut response, subapps, default_subapp, host);
+        options.security.apply(&mut response, secure);
+
+        response
+    }
+
+    /// Generates the response to a CORS preflight or `OPTIONS` request.
+    fn preflight(&self, request: &Request, keep_alive: bool) -> Response {
+        let handler = get_handler(request, &self.subapps, &self.default_subapp)
+            .map(|handler| &handler.cors)
+            .or_else(|| {
+                get_streaming_handler(request, &self.subapps, &self.default_subapp)
+                    .map(|handler| &handler.cors)
+            });
+
+        let Some(cors) = handler else {
+            return self.error(ErrorContext::new(StatusCode::NotFound).with_request(request));
+        };
+
+        let mut response = Response::empty(StatusCode::NoContent)
+            .with_header(HeaderType::Date, DateTime::now().to_string())
+            .with_header(
+                HeaderType::Connection,
+                match keep_alive {
+                    true => "Keep-Alive",
+                    false => "Close",
+                },
+            );
+
+        cors.set_headers(&mut response.headers);
+
+        // Browsers only send the override header if the preflight response allows it
+        if self.options.method_override == Some(MethodOverride::Header) {
+            cors.allow_override_header(&mut response.headers);
+        }
+
+        if let Some(allow) = get_allow_header(request, &self.subapps, &self.default_subapp) {
+            response.headers.add(HeaderType::Allow, allow);
+        }
+
+        response
+    }
+
+    /// Reads any streamed body of the response and adds the headers which are generated for every
+    ///   response to a routed request.
+    fn finalise(&self, mut response: Response, request: &Request) -> Response {
+        if response.read_streamed_body().is_err() {
+            response =
+                self.error(ErrorContext::new(StatusCode::InternalError).with_request(request));
+        }
+
+        if response.headers.get(HeaderType::Connection).is_none() {
+            match request.headers.get(&HeaderType::Connection) {
+                Some(connection) if request.body_complete() => {
+                    response.headers.add(HeaderType::Connection, connection);
+                }
+                _ => response.headers.add(HeaderType::Connection, "Close"),
+            }
+        }
+
+        if response.headers.get(HeaderType::Date).is_none() {
+            response
+                .headers
+                .add(HeaderType::Date, DateTime::now().to_string());
+        }
+
+        // Remove the body and adjust the framing where the status code or method require it
+        response.finalise_framing(&request.method);
+        response.version = request.version.clone();
+
+        response
+    }
+
+    /// Generates the response to a request which could not be parsed.
+    fn request_error(&self, e: RequestError, secure: bool) -> Response {
+        let status_code = match &e {
+            RequestError::TooLarge(part) => part.status_code(),
+            _ => StatusCode::BadRequest,
+        };
+
+        let mut response = self
+            .error(ErrorContext::new(status_code).with_cause(ErrorCause::Request(e)))
+            .with_header(HeaderType::Connection, "Close");
+
+        apply_default_headers(&mut response, &self.subapps, &self.default_subapp, None);
+        self.options.security.apply(&mut response, secure);
+
+        response
+    }
+
+    /// Generates the response for an error with the error handler for the request's host.
+    fn error(&self, context: ErrorContext) -> Response {
+        handle_error(&context, &self.subapps, &self.error_handler, &self.state)
+    }
+}
+
+impl<State> Clone for TestClient<State> {
+    fn clone(&self) -> Self {
+        Self {
+            subapps: self.subapps.clone(),
+            default_subapp: self.default_subapp.clone(),
+            error_handler: self.error_handler.clone(),
+            state: self.state.clone(),
+            options: self.options.clone(),
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use crate::accept::{AcceptErrors, AcceptOptions, Listener};
+use crate::monitor::event::{Event, EventType};
+use crate::monitor::MonitorConfig;
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
+
+#[cfg(feature = "tokio")]
+use std::task::{Context, Poll};
+
+/// `EMFILE` on Unix, `WSAEMFILE` on Windows.
+#[cfg(unix)]
+const EMFILE: i32 = 24;
+#[cfg(windows)]
+const EMFILE: i32 = 10024;
+
+/// A listener which returns a scripted sequence of results, then signals shutdown.
+struct MockListener {
+    results: RefCell<VecDeque<Result<u8, Error>>>,
+    shutdown: AtomicBool,
+}
+
+impl MockListener {
+    fn new(results: Vec<Result<u8, Error>>) -> Self {
+        Self {
+            results: RefCell::new(results.into()),
+            shutdown: AtomicBool::new(false),
+        }
+    }
+
+    fn next(&self) -> Result<u8, Error> {
+        self.results.borrow_mut().pop_front().unwrap_or_else(|| {
+            self.shutdown.store(true, Ordering::SeqCst);
+            Err(ErrorKind::WouldBlock.into())
+        })
+    }
+}
+
+#[cfg(not(feature = "tokio"))]
+impl Listener for MockListener {
+    type Stream = u8;
+
+    fn accept(&self) -> Result<u8, Error> {
+        self.next()
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Listener for MockListener {
+    type Stream = u8;
+
+    fn poll_accept(&self, _: &mut Context<'_>) -> Poll<Result<u8, Error>> {
+        match self.next() {
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Poll::Pending,
+            result => Poll::Ready(result),
+        }
+    }
+}
+
+fn exhausted() -> Result<u8, Error> {
+    Err(Error::from_raw_os_error(EMFILE))
+}
+
+fn options(reserve_fd: bool) -> AcceptOptions {
+    AcceptOptions {
+        burst: 4,
+        min_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(4),
+        reserve_fd,
+        poll_interval: Duration::from_millis(1),
+    }
+}
+
+fn monitor() -> (MonitorConfig, Receiver<Event>) {
+    let (tx, rx) = channel();
+    let monitor = MonitorConfig::new(tx)
+        .with_subscription_to(EventType::AcceptBackoff)
+        .with_subscription_to(EventType::ConnectionError);
+
+    (monitor, rx)
+}
+
+/// Runs the accept loop until the listener runs out of results or fails, returning the accepted
+///   streams and the result of the loop.
+#[cfg(not(feature = "tokio"))]
+fn run(
+    listener: &MockListener,
+    options: &AcceptOptions,
+    monitor: &MonitorConfig,
+) -> (Vec<u8>, Result<(), Error>) {
+    let mut accepted = Vec::new();
+
+    let result =
+        crate::app::accept_loop(listener, options, monitor, &listener.shutdown, |stream| {
+            accepted.push(stream)
+        });
+
+    (accepted, result)
+}
+
+/// Runs the accept loop until the listener runs out of results or fails, returning the accepted
+///   streams and the result of the loop.
+#[cfg(feature = "tokio")]
+fn run(
+    listener: &MockListener,
+    options: &AcceptOptions,
+    monitor: &MonitorConfig,
+) -> (Vec<u8>, Result<(), Error>) {
+    let mut accepted = Vec::new();
+    let shutdown = crate::shutdown::ShutdownSignal::new();
+
+    let result = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap()
+        .block_on(async {
+            // The mock listener never wakes the task, so shutdown is checked on a timer instead
+            let cancel = shutdown.clone();
+            let shutdown_flag = &listener.shutdown;
+            let watcher = async move {
+                while !shutdown_flag.load(Ordering::SeqCst) {
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                }
+
+                cancel.trigger();
+            };
+
+            let accept_loop =
+                crate::app::accept_loop(listener, options, monitor, Some(&shutdown), |stream| {
+                    accepted.push(stream)
+                });
+
+            // The watcher is not waited for if the loop stops because the listener failed
+            tokio::select! {
+                () = watcher => Ok(()),
+                result = accept_loop => result,
+            }
+        });
+
+    (accepted, result)
+}
+
+#[test]
+fn test_backoff_increases_and_resets() {
+    let (monitor, _rx) = monitor();
+    let mut errors = AcceptErrors::new(&options(false));
+    let error = Error::from_raw_os_error(EMFILE);
+
+    let delays: Vec<Option<Duration>> = (0..4).map(|_| errors.record(&error, &monitor)).collect();
+    assert_eq!(
+        delays,
+        [1, 2, 4, 4]
+            .map(|ms| Some(Duration::from_millis(ms)))
+            .to_vec()
+    );
+
+    errors.reset();
+    assert_eq!(
+        errors.record(&error, &monitor),
+        Some(Duration::from_millis(1))
+    );
+
+    let error = Error::from(ErrorKind::ConnectionAborted);
+    assert_eq!(errors.record(&error, &monitor), None);
+}
+
+#[test]
+fn test_exhaustion_is_rate_limited() {
+    let listener = MockListener::new(vec![
+        exhausted(),
+        exhausted(),
+        exhausted(),
+        Ok(1),
+        Ok(2),
+        Err(ErrorKind::ConnectionAborted.into()),
+        Ok(3),
+    ]);
+    let (monitor, rx) = monitor();
+
+    let (accepted, result) = run(&listener, &options(false), &monitor);
+    assert_eq!(accepted, vec![1, 2, 3]);
+    assert!(result.is_ok());
+
+    let events: Vec<Event> = rx.try_iter().collect();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].kind, EventType::AcceptBackoff);
+    assert!(events[0].info.as_ref().unwrap().contains("retrying in 1ms"));
+    assert_eq!(events[1].kind, EventType::ConnectionError);
+}
+
+#[test]
+fn test_reserve_drains_one_connection() {
+    // Each exhaustion error is followed by an accept with the reserve descriptor closed
+    let listener = MockListener::new(vec![exhausted(), Ok(1), exhausted(), exhausted(), Ok(2)]);
+    let (monitor, _rx) = monitor();
+
+    let (accepted, _) = run(&listener, &options(true), &monitor);
+    assert_eq!(accepted, vec![2]);
+}
+
+#[test]
+fn test_listener_failure_stops_loop() {
+    let listener = MockListener::new(vec![Ok(1), Err(ErrorKind::InvalidInput.into()), Ok(2)]);
+    let (monitor, _rx) = monitor();
+
+    // Retrying would fail forever, so the error is returned instead of reported
+    let (accepted, result) = run(&listener, &options(false), &monitor);
+    assert_eq!(accepted, vec![1]);
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
+}
@@ -0,0 +1,220 @@
+use crate::cancellation::CancellationToken;
+use crate::http::{Request, Response, StatusCode};
+use crate::App;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::sleep;
+use std::time::Duration;
+
+/// How long the polling handler works for if it is not cancelled.
+const WORK: Duration = Duration::from_millis(500);
+
+/// Reports what the handlers observed.
+type Observations = Mutex<Sender<&'static str>>;
+
+/// Reports an observation from a handler.
+fn observe(observations: &Observations, observation: &'static str) {
+    observations.lock().unwrap().send(observation).unwrap();
+}
+
+/// Builds an app whose `/poll` route works until it is cancelled, whose `/callback` route waits
+///   after registering a callback, and whose `/stream` route streams an endless body.
+#[cfg(not(feature = "tokio"))]
+fn app(observations: Sender<&'static str>) -> App<Observations> {
+    App::new_with_config(4, Mutex::new(observations))
+        .with_route(
+            "/poll",
+            |request: Request, observations: Arc<Observations>| {
+                let cancellation = request.cancellation_token();
+
+                for _ in 0..WORK.as_millis() {
+                    if cancellation.is_cancelled() {
+                        observe(&observations, "cancelled");
+                        return Response::empty(StatusCode::ServiceUnavailable);
+                    }
+
+                    sleep(Duration::from_millis(1));
+                }
+
+                observe(&observations, "finished");
+                Response::new(StatusCode::OK, "finished")
+            },
+        )
+        .with_route(
+            "/callback",
+            |request: Request, observations: Arc<Observations>| {
+                request
+                    .cancellation_token()
+                    .on_cancel(move || observe(&observations, "cancelled"));
+
+                sleep(WORK);
+                Response::empty(StatusCode::OK)
+            },
+        )
+        .with_route(
+            "/stream",
+            |request: Request, observations: Arc<Observations>| {
+                request
+                    .cancellation_token()
+                    .on_cancel(move || observe(&observations, "cancelled"));
+
+                Response::chunked(StatusCode::OK, std::io::repeat(b'a'))
+            },
+        )
+}
+
+/// Builds an app whose `/poll` route works until it is cancelled, whose `/callback` route waits
+///   after registering a callback, and whose `/stream` route streams an endless body.
+#[cfg(feature = "tokio")]
+fn app(observations: Sender<&'static str>) -> App<Observations> {
+    App::new_with_config(Mutex::new(observations))
+        .with_route(
+            "/poll",
+            |request: Request, observations: Arc<Observations>| async move {
+                let cancellation = request.cancellation_token();
+
+                tokio::select! {
+                    _ = cancellation.cancelled() => {
+                        observe(&observations, "cancelled");
+                        Response::empty(StatusCode::ServiceUnavailable)
+                    }
+                    _ = tokio::time::sleep(WORK) => {
+                        observe(&observations, "finished");
+                        Response::new(StatusCode::OK, "finished")
+                    }
+                }
+            },
+        )
+        .with_route(
+            "/callback",
+            |request: Request, observations: Arc<Observations>| async move {
+                request
+                    .cancellation_token()
+                    .on_cancel(move || observe(&observations, "cancelled"));
+
+                tokio::time::sleep(WORK).await;
+                Response::empty(StatusCode::OK)
+            },
+        )
+        .with_route(
+            "/stream",
+            |request: Request, observations: Arc<Observations>| async move {
+                request
+                    .cancellation_token()
+                    .on_cancel(move || observe(&observations, "cancelled"));
+
+                Response::chunked(StatusCode::OK, tokio::io::repeat(b'a'))
+            },
+        )
+}
+
+/// Starts the app in the background, returning its address and the receiver of the observations of
+///   its handlers.
+fn start() -> (String, Receiver<&'static str>) {
+    let (tx, rx) = channel();
+    let addr = crate::tests::start_app(app(tx));
+
+    (addr, rx)
+}
+
+/// Sends a request for the given path, returning the stream.
+fn send(addr: &str, path: &str) -> TcpStream {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "GET {} HTTP/1.1\r\nConnection: Close\r\n\r\n", path).unwrap();
+
+    stream
+}
+
+#[test]
+fn test_token() {
+    let token = CancellationToken::new();
+    let clone = token.clone();
+    let calls = Arc::new(AtomicUsize::new(0));
+
+    let cloned_calls = calls.clone();
+    token.on_cancel(move || {
+        cloned_calls.fetch_add(1, Ordering::SeqCst);
+    });
+
+    assert!(!clone.is_cancelled());
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+    // Callbacks run once, however many times the token is cancelled
+    clone.cancel();
+    token.cancel();
+    assert!(token.is_cancelled());
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    // Callbacks registered afterwards run immediately
+    let cloned_calls = calls.clone();
+    token.on_cancel(move || {
+        cloned_calls.fetch_add(1, Ordering::SeqCst);
+    });
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    // Requests which were not received by an app are never cancelled by themselves
+    let request = Request::builder().build();
+    assert!(!request.cancellation_token().is_cancelled());
+}
+
+#[test]
+fn test_cancelled_on_disconnect() {
+    let (addr, observations) = start();
+
+    let stream = send(&addr, "/poll");
+    sleep(Duration::from_millis(50));
+    drop(stream);
+
+    // The handler notices well before it would have finished
+    assert_eq!(
+        observations.recv_timeout(WORK - Duration::from_millis(150)),
+        Ok("cancelled")
+    );
+}
+
+#[test]
+fn test_not_cancelled_while_connected() {
+    let (addr, observations) = start();
+
+    let mut stream = send(&addr, "/poll");
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("\r\n\r\nfinished"));
+    assert_eq!(observations.recv(), Ok("finished"));
+}
+
+#[test]
+fn test_callback_on_disconnect() {
+    let (addr, observations) = start();
+
+    let stream = send(&addr, "/callback");
+    sleep(Duration::from_millis(50));
+    drop(stream);
+
+    // The callback runs while the handler is still waiting
+    assert_eq!(
+        observations.recv_timeout(WORK - Duration::from_millis(150)),
+        Ok("cancelled")
+    );
+}
+
+#[test]
+fn test_cancelled_on_write_failure() {
+    let (addr, observations) = start();
+
+    let mut stream = send(&addr, "/stream");
+    let mut buf = [0; 1024];
+    stream.read_exact(&mut buf).unwrap();
+    drop(stream);
+
+    assert_eq!(
+        observations.recv_timeout(Duration::from_secs(2)),
+        Ok("cancelled")
+    );
+}
@@ -1,13 +1,14 @@
-use crate::client::{Client, ParsedUrl, Protocol};
+use crate::client::{connect, Client, ConnectError, ParsedUrl, Protocol};
 use crate::http::headers::{HeaderType, Headers};
 
-use std::net::ToSocketAddrs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::thread::spawn;
+use std::time::{Duration, Instant};
 
 #[test]
 fn test_url_parser() {
-    let google_ip_80 = "google.com:80".to_socket_addrs().unwrap().next().unwrap();
-    let google_ip_443 = "google.com:443".to_socket_addrs().unwrap().next().unwrap();
-
     let urls = [
         Client::parse_url("https://google.com").unwrap(),
         Client::parse_url("http://google.com").unwrap(),
@@ -21,28 +22,28 @@ fn test_url_parser() {
     let expected_urls = [
         ParsedUrl {
             protocol: Protocol::Https,
-            host: google_ip_443,
+            host: "google.com:443".to_string(),
             path: "/".to_string(),
             query: "".to_string(),
             host_headers: expected_host_headers.clone(),
         },
         ParsedUrl {
             protocol: Protocol::Http,
-            host: google_ip_80,
+            host: "google.com:80".to_string(),
             path: "/".to_string(),
             query: "".to_string(),
             host_headers: expected_host_headers.clone(),
         },
         ParsedUrl {
             protocol: Protocol::Https,
-            host: google_ip_443,
+            host: "google.com:443".to_string(),
             path: "/maps".to_string(),
             query: "".to_string(),
             host_headers: expected_host_headers.clone(),
         },
         ParsedUrl {
             protocol: Protocol::Https,
-            host: google_ip_443,
+            host: "google.com:443".to_string(),
             path: "/search".to_string(),
             query: "q=test".to_string(),
             host_headers: expected_host_headers,
@@ -78,3 +79,233 @@ fn test_content_length() {
     assert_eq!(put_request.headers.get("Content-Length"), Some("13"));
     assert_eq!(empty_request.headers.get("Content-Length"), Some("0"));
 }
+
+#[test]
+fn test_explicit_port() {
+    let url = Client::parse_url("http://127.0.0.1:8080/api?q=test").unwrap();
+
+    let mut expected_host_headers = Headers::new();
+    expected_host_headers.add(HeaderType::Host, "127.0.0.1:8080");
+
+    assert_eq!(
+        url,
+        ParsedUrl {
+            protocol: Protocol::Http,
+            host: "127.0.0.1:8080".to_string(),
+            path: "/api".to_string(),
+            query: "q=test".to_string(),
+            host_headers: expected_host_headers,
+        }
+    );
+
+    let url = Client::parse_url("https://[::1]/").unwrap();
+    assert_eq!(url.host, "[::1]:443");
+}
+
+/// The ports which the resolvers below point to, separate since the tests run in parallel.
+static BLACKHOLE_PORT: AtomicU16 = AtomicU16::new(0);
+static REFUSED_PORT: AtomicU16 = AtomicU16::new(0);
+
+/// Accepts a single connection, replies to its request and returns the client's address.
+fn serve_once(listener: TcpListener) -> std::thread::JoinHandle<SocketAddr> {
+    spawn(move || {
+        let (mut stream, peer) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        let mut line = String::new();
+        while line != "\r\n" {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+        }
+
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nOK")
+            .unwrap();
+
+        peer
+    })
+}
+
+/// Resolves every host to an address which never answers, followed by the test server.
+fn blackhole_resolver(_: &str) -> io::Result<Vec<SocketAddr>> {
+    let port = BLACKHOLE_PORT.load(Ordering::SeqCst);
+
+    Ok(vec![
+        "[100::1]:80".parse().unwrap(),
+        "192.0.2.1:80".parse().unwrap(),
+        SocketAddr::from(([127, 0, 0, 1], port)),
+    ])
+}
+
+/// Resolves every host to two ports on which nothing is listening.
+fn refused_resolver(_: &str) -> io::Result<Vec<SocketAddr>> {
+    let port = REFUSED_PORT.load(Ordering::SeqCst);
+
+    Ok(vec![
+        SocketAddr::from(([127, 0, 0, 1], port)),
+        SocketAddr::from(([127, 0, 0, 1], port + 1)),
+    ])
+}
+
+#[test]
+fn test_interleave() {
+    let addresses: Vec<SocketAddr> = vec![
+        "[::1]:80".parse().unwrap(),
+        "[::2]:80".parse().unwrap(),
+        "[::3]:80".parse().unwrap(),
+        "127.0.0.1:80".parse().unwrap(),
+        "127.0.0.2:80".parse().unwrap(),
+    ];
+
+    let expected: Vec<SocketAddr> = vec![
+        "[::1]:80".parse().unwrap(),
+        "127.0.0.1:80".parse().unwrap(),
+        "[::2]:80".parse().unwrap(),
+        "127.0.0.2:80".parse().unwrap(),
+        "[::3]:80".parse().unwrap(),
+    ];
+
+    assert_eq!(connect::interleave(&addresses), expected);
+    assert!(connect::interleave(&[]).is_empty());
+}
+
+#[test]
+fn test_resolver_skips_blackholed_addresses() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    BLACKHOLE_PORT.store(listener.local_addr().unwrap().port(), Ordering::SeqCst);
+    let server = serve_once(listener);
+
+    let mut client = Client::new()
+        .with_resolver(blackhole_resolver)
+        .with_connect_timeout(Duration::from_secs(30));
+
+    let start = Instant::now();
+    let response = client.get("http://example.com/").unwrap().send().unwrap();
+
+    assert_eq!(response.body, b"OK");
+    assert!(start.elapsed() < Duration::from_secs(10));
+
+    server.join().unwrap();
+}
+
+#[test]
+fn test_connect_error_lists_addresses() {
+    // Bind and immediately drop listeners so that the ports are very likely to be closed
+    let port = {
+        let first = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = first.local_addr().unwrap().port();
+        drop(TcpListener::bind(("127.0.0.1", port + 1)));
+        port
+    };
+    REFUSED_PORT.store(port, Ordering::SeqCst);
+
+    let mut client = Client::new().with_resolver(refused_resolver);
+    let error = client
+        .get("http://example.com/")
+        .unwrap()
+        .send()
+        .unwrap_err();
+
+    let error = error.downcast::<ConnectError>().unwrap();
+    let tried: Vec<SocketAddr> = error.attempts.iter().map(|(address, _)| *address).collect();
+
+    assert_eq!(
+        tried,
+        vec![
+            SocketAddr::from(([127, 0, 0, 1], port)),
+            SocketAddr::from(([127, 0, 0, 1], port + 1))
+        ]
+    );
+    assert!(error
+        .to_string()
+        .contains(&format!("127.0.0.1:{}", port + 1)));
+}
+
+#[test]
+fn test_resolver_error() {
+    fn failing_resolver(_: &str) -> io::Result<Vec<SocketAddr>> {
+        Err(io::Error::new(io::ErrorKind::NotFound, "Unknown host"))
+    }
+
+    let mut client = Client::new().with_resolver(failing_resolver);
+
+    assert!(client.get("http://example.com/").is_err());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_local_address() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = serve_once(listener);
+
+    let mut client = Client::new().with_local_address("127.0.0.2:0".parse().unwrap());
+    let response = client
+        .get(format!("http://{}/", address))
+        .unwrap()
+        .send()
+        .unwrap();
+
+    assert_eq!(response.body, b"OK");
+    assert_eq!(server.join().unwrap().ip().to_string(), "127.0.0.2");
+}
+
+#[test]
+fn test_deadline_passed() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let mut client = Client::new();
+    let error = client
+        .get(format!("http://{}/", address))
+        .unwrap()
+        .with_deadline(Instant::now())
+        .send()
+        .unwrap_err();
+
+    assert_eq!(
+        error.downcast::<io::Error>().unwrap().kind(),
+        io::ErrorKind::TimedOut
+    );
+}
+
+#[test]
+fn test_deadline_sent_and_enforced() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    // Reads the head of the request and never responds, returning the deadline header along with
+    //   the connection so that it stays open
+    let server = spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut header = None;
+
+        let mut line = String::new();
+        while line != "\r\n" {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+
+            if let Some(value) = line
+                .to_ascii_lowercase()
+                .strip_prefix("x-request-deadline: ")
+            {
+                header = Some(value.trim().parse::<u64>().unwrap());
+            }
+        }
+
+        (header, reader)
+    });
+
+    let mut client = Client::new();
+    let start = Instant::now();
+    let result = client
+        .get(format!("http://{}/", address))
+        .unwrap()
+        .with_deadline(Instant::now() + Duration::from_millis(300))
+        .send();
+
+    assert!(result.is_err());
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert!(server.join().unwrap().0.unwrap() <= 300);
+}
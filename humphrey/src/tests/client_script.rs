@@ -0,0 +1,295 @@
+use crate::client::Client;
+use crate::http::headers::HeaderType;
+use crate::http::method::Method;
+use crate::http::response::{ResponseError, MAX_RESPONSE_HEADERS, MAX_RESPONSE_LINE_LENGTH};
+use crate::http::{Response, StatusCode};
+
+use std::error::Error;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant};
+
+/// Starts a server which reads the head of one request, then writes each part of the script with a
+///   short pause before each, so that the client sees them in separate reads. The connection is
+///   then closed, or if `close` is false, kept open until the client closes it, so that a client
+///   waiting for more data hangs instead of failing.
+fn serve_script(parts: &[&'static [u8]], close: bool) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let parts = parts.to_vec();
+
+    spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        let mut line = String::new();
+        while line != "\r\n" {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+        }
+
+        for part in parts {
+            sleep(Duration::from_millis(20));
+            stream.write_all(part).unwrap();
+        }
+
+        if !close {
+            reader.read_to_end(&mut Vec::new()).ok();
+        }
+    });
+
+    format!("http://{}/", address)
+}
+
+/// Sends a request with the given method to the server, failing if it takes more than a second.
+fn fetch(url: String, method: Method) -> Result<Response, Box<dyn Error>> {
+    let start = Instant::now();
+    let response = Client::new()
+        .get(url)?
+        .with_method(method)
+        .with_deadline(Instant::now() + Duration::from_secs(5))
+        .send();
+
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    response
+}
+
+/// Runs the script and returns the response which the client read for a `GET` request.
+fn get(parts: &[&'static [u8]], close: bool) -> Response {
+    fetch(serve_script(parts, close), Method::Get).unwrap()
+}
+
+/// Runs the script and returns the error which the client failed with.
+fn get_err(parts: &[&'static [u8]]) -> ResponseError {
+    *fetch(serve_script(parts, true), Method::Get)
+        .unwrap_err()
+        .downcast::<ResponseError>()
+        .unwrap()
+}
+
+/// Runs the script and reads two responses from the same connection, as if two `GET` requests had
+///   been pipelined.
+fn get_pipelined(parts: &[&'static [u8]]) -> (Response, Response) {
+    let url = serve_script(parts, false);
+    let address = url.trim_start_matches("http://").trim_end_matches('/');
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+    let mut reader = BufReader::new(stream);
+
+    let first = Response::from_buffered_stream_for(&mut reader, &Method::Get).unwrap();
+    let second = Response::from_buffered_stream_for(&mut reader, &Method::Get).unwrap();
+
+    (first, second)
+}
+
+#[test]
+fn test_no_reason_phrase() {
+    let response = get(&[b"HTTP/1.1 200\r\nContent-Length: 2\r\n\r\nOK"], false);
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body, b"OK");
+
+    let response = get(&[b"HTTP/1.1 404 \r\nContent-Length: 0\r\n\r\n"], false);
+    assert_eq!(response.status_code, StatusCode::NotFound);
+}
+
+#[test]
+fn test_headers_without_space() {
+    let response = get(
+        &[b"HTTP/1.1 200 OK\r\nContent-Type:text/plain\r\nContent-Length:2  \r\n\r\nOK"],
+        false,
+    );
+
+    assert_eq!(
+        response.headers.get(HeaderType::ContentType),
+        Some("text/plain")
+    );
+    assert_eq!(response.body, b"OK");
+}
+
+#[test]
+fn test_bare_line_feeds() {
+    let response = get(&[b"HTTP/1.1 200 OK\nContent-Length: 2\n\nOK"], false);
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body, b"OK");
+}
+
+#[test]
+fn test_head_with_content_length() {
+    let url = serve_script(&[b"HTTP/1.1 200 OK\r\nContent-Length: 1000\r\n\r\n"], false);
+    let response = fetch(url, Method::Head).unwrap();
+
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(
+        response.headers.get(HeaderType::ContentLength),
+        Some("1000")
+    );
+    assert!(response.body.is_empty());
+}
+
+#[test]
+fn test_no_body_then_pipelined() {
+    let (response, next) = get_pipelined(&[
+        b"HTTP/1.1 204 No Content\r\n\r\nHTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nnext",
+    ]);
+    assert_eq!(response.status_code, StatusCode::NoContent);
+    assert!(response.body.is_empty());
+    assert_eq!(next.status_code, StatusCode::OK);
+    assert_eq!(next.body, b"next");
+
+    let (response, next) = get_pipelined(&[
+        b"HTTP/1.1 304 Not Modified\r\nContent-Length: 4\r\n\r\n",
+        b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nnext",
+    ]);
+    assert_eq!(response.status_code, StatusCode::NotModified);
+    assert_eq!(response.headers.get(HeaderType::ContentLength), Some("4"));
+    assert!(response.body.is_empty());
+    assert_eq!(next.status_code, StatusCode::OK);
+    assert_eq!(next.body, b"next");
+}
+
+#[test]
+fn test_close_delimited_body() {
+    let response = get(&[b"HTTP/1.0 200 OK\r\n\r\nHello, ", b"world"], true);
+    assert_eq!(response.version, "HTTP/1.0");
+    assert_eq!(response.body, b"Hello, world");
+
+    let response = get(&[b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n"], true);
+    assert!(response.body.is_empty());
+}
+
+#[test]
+fn test_interim_responses() {
+    let response = get(
+        &[
+            b"HTTP/1.1 100 Continue\r\n\r\n",
+            b"HTTP/1.1 103 Early Hints\r\nLink: </style.css>; rel=preload\r\n\r\n",
+            b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nOK",
+        ],
+        false,
+    );
+
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.headers.get(HeaderType::Link), None);
+    assert_eq!(response.body, b"OK");
+
+    // Switching protocols is the final response, after which the connection no longer uses HTTP
+    let response = get(
+        &[b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\n\x81\x00"],
+        false,
+    );
+    assert_eq!(response.status_code, StatusCode::SwitchingProtocols);
+    assert!(response.body.is_empty());
+}
+
+#[test]
+fn test_split_across_reads() {
+    let response = get(
+        &[
+            b"HTTP/1.1 200 OK\r\nTransfer-Enc",
+            b"oding: Chunked\r\n\r\n5\r\nHel",
+            b"lo\r\n7\r\n, world\r\n0\r\n\r\n",
+        ],
+        false,
+    );
+
+    assert_eq!(response.body, b"Hello, world");
+    assert_eq!(response.headers.get(HeaderType::ContentLength), Some("12"));
+}
+
+#[test]
+fn test_non_utf8() {
+    let response = get(
+        &[b"HTTP/1.1 200 OK\r\nX-Name: caf\xe9\r\nContent-Length: 2\r\n\r\n\xff\xfe"],
+        false,
+    );
+
+    assert_eq!(response.headers.get("X-Name"), Some("caf\u{fffd}"));
+    assert_eq!(response.body, b"\xff\xfe");
+    assert_eq!(response.text(), None);
+
+    assert_eq!(
+        get_err(&[b"HTTP/1.1 200 \xff\r\nContent-Length: 0\r\n\r\n"]),
+        ResponseError::Response
+    );
+}
+
+#[test]
+fn test_truncated() {
+    assert_eq!(get_err(&[b""]), ResponseError::Stream);
+    assert_eq!(get_err(&[b"HTTP/1.1 200 O"]), ResponseError::Stream);
+    assert_eq!(
+        get_err(&[b"HTTP/1.1 200 OK\r\nContent-Len"]),
+        ResponseError::Stream
+    );
+    assert_eq!(
+        get_err(&[b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n"]),
+        ResponseError::Stream
+    );
+    assert_eq!(
+        get_err(&[b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nHello"]),
+        ResponseError::Stream
+    );
+    assert_eq!(
+        get_err(&[b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nHel"]),
+        ResponseError::Stream
+    );
+    assert_eq!(
+        get_err(&[b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nHello\r\n"]),
+        ResponseError::Stream
+    );
+}
+
+#[test]
+fn test_invalid() {
+    assert_eq!(get_err(&[b"HTTP/1.1\r\n\r\n"]), ResponseError::Response);
+    assert_eq!(
+        get_err(&[b"HTTP/1.1 20 OK\r\n\r\n"]),
+        ResponseError::Response
+    );
+    assert_eq!(get_err(&[b"ICY 200 OK\r\n\r\n"]), ResponseError::Response);
+    assert_eq!(
+        get_err(&[b"HTTP/1.1 200 OK\r\nNo colon\r\n\r\n"]),
+        ResponseError::Response
+    );
+    assert_eq!(
+        get_err(&[b"HTTP/1.1 200 OK\r\nContent-Length: ten\r\n\r\n"]),
+        ResponseError::Response
+    );
+}
+
+#[test]
+fn test_head_limits() {
+    let line = format!(
+        "HTTP/1.1 200 OK\r\nX-Long: {}\r\n\r\n",
+        "a".repeat(MAX_RESPONSE_LINE_LENGTH)
+    );
+    let line: &'static [u8] = Box::leak(line.into_bytes().into_boxed_slice());
+    assert_eq!(get_err(&[line]), ResponseError::Response);
+
+    let headers = |count: usize| -> &'static [u8] {
+        let headers = "X-Header: value\r\n".repeat(count);
+        let head = format!("HTTP/1.1 200 OK\r\n{}Content-Length: 0\r\n\r\n", headers);
+        Box::leak(head.into_bytes().into_boxed_slice())
+    };
+
+    let response = get(&[headers(MAX_RESPONSE_HEADERS - 1)], false);
+    assert_eq!(response.headers.len(), MAX_RESPONSE_HEADERS);
+    assert_eq!(
+        get_err(&[headers(MAX_RESPONSE_HEADERS)]),
+        ResponseError::Response
+    );
+}
+
+#[test]
+fn test_trailer_limits() {
+    let trailers = "X-Trailer: value\r\n".repeat(MAX_RESPONSE_HEADERS + 1);
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nOK\r\n0\r\n{}\r\n",
+        trailers
+    );
+    let response: &'static [u8] = Box::leak(response.into_bytes().into_boxed_slice());
+    assert_eq!(get_err(&[response]), ResponseError::Response);
+}
@@ -0,0 +1,76 @@
+use crate::clock::{now_monotonic, now_unix, override_clock, MockClock};
+use crate::http::date::DateTime;
+
+use std::thread::spawn;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[test]
+fn test_mock_clock() {
+    let clock = MockClock::at(Duration::from_secs(1628437415));
+    let _guard = override_clock(clock.clone());
+
+    let start = now_monotonic();
+    assert_eq!(now_unix(), Duration::from_secs(1628437415));
+    assert_eq!(DateTime::now().to_string(), "Sun, 08 Aug 2021 15:43:35 GMT");
+
+    clock.advance(Duration::from_secs(90));
+
+    assert_eq!(now_monotonic() - start, Duration::from_secs(90));
+    assert_eq!(now_unix(), Duration::from_secs(1628437505));
+    assert_eq!(DateTime::now().to_string(), "Sun, 08 Aug 2021 15:45:05 GMT");
+}
+
+#[test]
+fn test_clock_override_scope() {
+    let outer = MockClock::at(Duration::from_secs(100));
+    let inner = MockClock::at(Duration::from_secs(200));
+
+    {
+        let _outer = override_clock(outer);
+        assert_eq!(now_unix(), Duration::from_secs(100));
+
+        {
+            let _inner = override_clock(inner);
+            assert_eq!(now_unix(), Duration::from_secs(200));
+        }
+
+        // Other threads still read the system clock
+        let other = spawn(now_unix).join().unwrap();
+        assert!(other > Duration::from_secs(200));
+
+        assert_eq!(now_unix(), Duration::from_secs(100));
+    }
+
+    let system = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    assert!(now_unix() >= system);
+}
+
+#[cfg(not(feature = "tokio"))]
+#[test]
+fn test_mock_clock_test_client() {
+    use crate::http::headers::HeaderType;
+    use crate::http::{Response, StatusCode};
+    use crate::rate_limit::RateLimitConfig;
+    use crate::App;
+
+    let clock = MockClock::at(Duration::from_secs(1628437415));
+    let _guard = override_clock(clock.clone());
+
+    let client = App::<()>::new()
+        .with_rate_limit(RateLimitConfig::new(1, Duration::from_secs(1)))
+        .with_stateless_route("/", |_| Response::new(StatusCode::OK, "index"))
+        .into_test_client();
+
+    let response = client.get("/");
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(
+        response.headers.get(HeaderType::Date),
+        Some("Sun, 08 Aug 2021 15:43:35 GMT")
+    );
+
+    assert_eq!(client.get("/").status_code, StatusCode::TooManyRequests);
+
+    // Time only passes when the clock is advanced
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(client.get("/").status_code, StatusCode::OK);
+}
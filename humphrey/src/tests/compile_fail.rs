@@ -0,0 +1,21 @@
+#[test]
+#[cfg(not(feature = "tokio"))]
+fn test_handler_compile_errors() {
+    let tests = trybuild::TestCases::new();
+    tests.compile_fail("src/tests/ui/*.rs");
+    tests.pass("src/tests/ui_pass/*.rs");
+}
+
+#[test]
+#[cfg(feature = "tokio")]
+fn test_async_handler_compile_errors() {
+    let tests = trybuild::TestCases::new();
+    tests.compile_fail("src/tests/ui_tokio/*.rs");
+    tests.pass("src/tests/ui_tokio_pass/*.rs");
+}
+
+#[test]
+fn test_feature_paths() {
+    let tests = trybuild::TestCases::new();
+    tests.pass("src/tests/ui_features/*.rs");
+}
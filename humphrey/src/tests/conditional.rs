@@ -0,0 +1,222 @@
+use crate::http::conditional::{
+    evaluate, respond_conditional, ConditionalResult, ETag, Validators,
+};
+use crate::http::date::DateTime;
+use crate::http::headers::HeaderType;
+use crate::http::method::Method;
+use crate::http::{Request, Response, StatusCode};
+
+use std::cell::Cell;
+
+const EARLIER: &str = "Sat, 05 Nov 1994 08:49:37 GMT";
+const MODIFIED: &str = "Sun, 06 Nov 1994 08:49:37 GMT";
+const LATER: &str = "Mon, 07 Nov 1994 08:49:37 GMT";
+
+/// The method and headers of a request, and the expected result of evaluating its preconditions.
+type Case<'a> = (Method, &'a [(HeaderType, &'a str)], ConditionalResult);
+
+fn conditional_request(method: Method, headers: &[(HeaderType, &str)]) -> Request {
+    headers
+        .iter()
+        .fold(
+            Request::builder().method(method),
+            |builder, (name, value)| builder.header(name.clone(), value),
+        )
+        .build()
+}
+
+fn validators() -> Validators {
+    Validators::new()
+        .with_etag(ETag::strong("abc"))
+        .with_last_modified(DateTime::parse(MODIFIED).unwrap())
+}
+
+#[test]
+fn test_etag_parse() {
+    assert_eq!(ETag::parse("\"abc\""), Some(ETag::strong("abc")));
+    assert_eq!(ETag::parse(" W/\"abc\" "), Some(ETag::weak("abc")));
+    assert_eq!(ETag::parse("\"\""), Some(ETag::strong("")));
+    assert_eq!(ETag::parse("\"a,b\""), Some(ETag::strong("a,b")));
+
+    for value in [
+        "",
+        "abc",
+        "\"abc",
+        "w/\"abc\"",
+        "\"a b\"",
+        "\"a\" \"b\"",
+        "\"a\", x",
+    ] {
+        assert_eq!(ETag::parse(value), None, "{}", value);
+    }
+
+    assert_eq!(ETag::weak("abc").to_string(), "W/\"abc\"");
+    assert_eq!(ETag::from_version(7).to_string(), "\"v7\"");
+    assert_eq!(ETag::from_bytes(b"hello"), ETag::from_bytes(b"hello"));
+    assert_ne!(ETag::from_bytes(b"hello"), ETag::from_bytes(b"world"));
+}
+
+#[test]
+fn test_etag_comparison() {
+    // The table from section 2.3.2 of RFC 7232
+    let cases = [
+        (ETag::weak("1"), ETag::weak("1"), false, true),
+        (ETag::weak("1"), ETag::weak("2"), false, false),
+        (ETag::weak("1"), ETag::strong("1"), false, true),
+        (ETag::strong("1"), ETag::strong("1"), true, true),
+    ];
+
+    for (a, b, strong, weak) in cases {
+        assert_eq!(a.strong_eq(&b), strong, "{} {}", a, b);
+        assert_eq!(a.weak_eq(&b), weak, "{} {}", a, b);
+        assert_eq!(b.strong_eq(&a), strong, "{} {}", b, a);
+        assert_eq!(b.weak_eq(&a), weak, "{} {}", b, a);
+    }
+}
+
+#[test]
+fn test_evaluate_precedence() {
+    use ConditionalResult::*;
+    use HeaderType::*;
+    use Method::*;
+
+    let cases: &[Case] = &[
+        (Get, &[], Proceed),
+        // Step 1: If-Match, with the strong comparison function
+        (Put, &[(IfMatch, "\"abc\"")], Proceed),
+        (Put, &[(IfMatch, "\"xyz\", \"abc\"")], Proceed),
+        (Put, &[(IfMatch, "*")], Proceed),
+        (Put, &[(IfMatch, "W/\"abc\"")], PreconditionFailed),
+        (Put, &[(IfMatch, "\"xyz\"")], PreconditionFailed),
+        (Get, &[(IfMatch, "\"xyz\"")], PreconditionFailed),
+        // Step 2: If-Unmodified-Since, only without If-Match
+        (Put, &[(IfUnmodifiedSince, MODIFIED)], Proceed),
+        (Put, &[(IfUnmodifiedSince, LATER)], Proceed),
+        (Put, &[(IfUnmodifiedSince, EARLIER)], PreconditionFailed),
+        (Put, &[(IfUnmodifiedSince, "yesterday")], Proceed),
+        (
+            Put,
+            &[(IfMatch, "\"abc\""), (IfUnmodifiedSince, EARLIER)],
+            Proceed,
+        ),
+        // Step 3: If-None-Match, with the weak comparison function
+        (Get, &[(IfNoneMatch, "\"abc\"")], NotModified),
+        (Head, &[(IfNoneMatch, "W/\"abc\"")], NotModified),
+        (Get, &[(IfNoneMatch, "*")], NotModified),
+        (Get, &[(IfNoneMatch, "\"xyz\"")], Proceed),
+        (Put, &[(IfNoneMatch, "\"abc\"")], PreconditionFailed),
+        (Delete, &[(IfNoneMatch, "*")], PreconditionFailed),
+        // Step 4: If-Modified-Since, only for GET and HEAD without If-None-Match
+        (Get, &[(IfModifiedSince, MODIFIED)], NotModified),
+        (Head, &[(IfModifiedSince, LATER)], NotModified),
+        (Get, &[(IfModifiedSince, EARLIER)], Proceed),
+        (Get, &[(IfModifiedSince, "not a date")], Proceed),
+        (Post, &[(IfModifiedSince, LATER)], Proceed),
+        (
+            Get,
+            &[(IfNoneMatch, "\"xyz\""), (IfModifiedSince, LATER)],
+            Proceed,
+        ),
+        // Earlier steps take precedence over later ones
+        (
+            Get,
+            &[(IfMatch, "\"xyz\""), (IfNoneMatch, "\"abc\"")],
+            PreconditionFailed,
+        ),
+        (
+            Get,
+            &[(IfUnmodifiedSince, EARLIER), (IfModifiedSince, LATER)],
+            PreconditionFailed,
+        ),
+        (
+            Get,
+            &[(IfMatch, "\"abc\""), (IfNoneMatch, "\"abc\"")],
+            NotModified,
+        ),
+    ];
+
+    for (method, headers, expected) in cases {
+        let request = conditional_request(method.clone(), headers);
+        assert_eq!(
+            evaluate(&request, &validators()),
+            *expected,
+            "{:?} {:?}",
+            method,
+            headers
+        );
+    }
+}
+
+#[test]
+fn test_evaluate_without_validators() {
+    // Tags never match a resource without one, and dates are ignored without a modification date
+    let validators = Validators::new();
+
+    let request = conditional_request(Method::Put, &[(HeaderType::IfMatch, "\"abc\"")]);
+    assert_eq!(
+        evaluate(&request, &validators),
+        ConditionalResult::PreconditionFailed
+    );
+
+    let request = conditional_request(Method::Put, &[(HeaderType::IfUnmodifiedSince, EARLIER)]);
+    assert_eq!(evaluate(&request, &validators), ConditionalResult::Proceed);
+
+    let request = conditional_request(Method::Get, &[(HeaderType::IfModifiedSince, LATER)]);
+    assert_eq!(evaluate(&request, &validators), ConditionalResult::Proceed);
+}
+
+#[test]
+fn test_evaluate_missing_resource() {
+    // `*` only matches a resource which exists, so `If-None-Match: *` allows creating one
+    let validators = Validators::missing();
+
+    let request = conditional_request(Method::Put, &[(HeaderType::IfNoneMatch, "*")]);
+    assert_eq!(evaluate(&request, &validators), ConditionalResult::Proceed);
+
+    let request = conditional_request(Method::Put, &[(HeaderType::IfMatch, "*")]);
+    assert_eq!(
+        evaluate(&request, &validators),
+        ConditionalResult::PreconditionFailed
+    );
+}
+
+#[test]
+fn test_respond_conditional() {
+    let built = Cell::new(0);
+    let build = || {
+        built.set(built.get() + 1);
+        Response::new(StatusCode::OK, "body")
+    };
+
+    let respond = |headers: &[(HeaderType, &str)]| {
+        respond_conditional(
+            &conditional_request(Method::Get, headers),
+            Some(ETag::from_version(3)),
+            DateTime::parse(MODIFIED),
+            build,
+        )
+    };
+
+    let response = respond(&[]);
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body, b"body");
+    assert_eq!(response.headers.get(HeaderType::ETag), Some("\"v3\""));
+    assert_eq!(
+        response.headers.get(HeaderType::LastModified),
+        Some(MODIFIED)
+    );
+
+    let response = respond(&[(HeaderType::IfNoneMatch, "\"v3\"")]);
+    assert_eq!(response.status_code, StatusCode::NotModified);
+    assert!(response.body.is_empty());
+    assert_eq!(response.headers.get(HeaderType::ETag), Some("\"v3\""));
+    assert_eq!(
+        response.headers.get(HeaderType::LastModified),
+        Some(MODIFIED)
+    );
+
+    let response = respond(&[(HeaderType::IfMatch, "\"v2\"")]);
+    assert_eq!(response.status_code, StatusCode::PreconditionFailed);
+
+    assert_eq!(built.get(), 1);
+}
@@ -0,0 +1,149 @@
+use crate::http::{Response, StatusCode};
+use crate::monitor::event::{Event, EventType};
+use crate::monitor::MonitorConfig;
+use crate::App;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{channel, Receiver};
+use std::thread::sleep;
+use std::time::Duration;
+
+const REJECTION: &[u8] =
+    b"HTTP/1.1 503 Service Unavailable\r\nConnection: close\r\nContent-Length: 0\r\n\r\n";
+
+fn connect(addr: &str) -> TcpStream {
+    for _ in 0..50 {
+        if let Ok(stream) = TcpStream::connect(addr) {
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+
+            return stream;
+        }
+
+        sleep(Duration::from_millis(10));
+    }
+
+    panic!("the app did not start");
+}
+
+/// Connects and reads until the app closes the connection without sending a request.
+fn rejected(addr: &str) -> Vec<u8> {
+    let mut received = Vec::new();
+    connect(addr).read_to_end(&mut received).unwrap();
+
+    received
+}
+
+/// Waits for the next event of the given kind.
+fn next_event(monitor: &Receiver<Event>, kind: EventType) -> Event {
+    loop {
+        let event = monitor.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        if event.kind == kind {
+            return event;
+        }
+    }
+}
+
+/// Starts an app which handles at most two connections at once, with one worker thread for the
+///   threaded app so that the second connection waits in the queue.
+#[cfg(not(feature = "tokio"))]
+fn start_app() -> (String, Receiver<Event>) {
+    let (monitor_tx, monitor_rx) = channel();
+
+    let app: App<()> = App::new_with_config(1, ())
+        .with_stateless_route("/", |_| Response::new(StatusCode::OK, "hello"))
+        .with_max_concurrent_connections(2)
+        .with_monitor(
+            MonitorConfig::new(monitor_tx)
+                .with_subscription_to(EventType::ConnectionSuccess)
+                .with_subscription_to(EventType::ConnectionRejectedOverCapacity),
+        );
+
+    (crate::tests::start_app(app), monitor_rx)
+}
+
+/// Starts an app which handles at most two connections at once.
+#[cfg(feature = "tokio")]
+fn start_app() -> (String, Receiver<Event>) {
+    let (monitor_tx, monitor_rx) = channel();
+
+    let app: App<()> = App::new_with_config(())
+        .with_stateless_route("/", |_| async { Response::new(StatusCode::OK, "hello") })
+        .with_max_concurrent_connections(2)
+        .with_monitor(
+            MonitorConfig::new(monitor_tx)
+                .with_subscription_to(EventType::ConnectionSuccess)
+                .with_subscription_to(EventType::ConnectionRejectedOverCapacity),
+        );
+
+    (crate::tests::start_app(app), monitor_rx)
+}
+
+#[test]
+fn test_max_concurrent_connections() {
+    let (addr, monitor) = start_app();
+
+    // The connection used to check that the app started is released before any are held
+    next_event(&monitor, EventType::ConnectionSuccess);
+    sleep(Duration::from_millis(100));
+
+    // Two idle connections are held open, leaving no room for more
+    let held = [connect(&addr), connect(&addr)];
+    next_event(&monitor, EventType::ConnectionSuccess);
+    next_event(&monitor, EventType::ConnectionSuccess);
+
+    for _ in 0..3 {
+        assert_eq!(rejected(&addr), REJECTION);
+
+        let event = next_event(&monitor, EventType::ConnectionRejectedOverCapacity);
+        assert_eq!(event.info.as_deref(), Some("2/2 connections"));
+        assert!(event.peer.is_some());
+    }
+
+    // Once the held connections are closed, their handlers return and new connections are served
+    drop(held);
+    sleep(Duration::from_millis(100));
+
+    let mut stream = connect(&addr);
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nConnection: Close\r\n\r\n")
+        .unwrap();
+    let response = Response::from_stream(&mut stream).unwrap();
+
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body, b"hello");
+}
+
+#[cfg(not(feature = "tokio"))]
+#[test]
+fn test_connection_released_on_panic() {
+    let app: App<()> = App::new_with_config(2, ())
+        .with_max_concurrent_connections(1)
+        .with_custom_connection_handler(|mut stream, _, _, _, _, _, _| {
+            let _ = stream.write_all(b"panicking");
+            panic!("connection handler failed");
+        });
+
+    let addr = crate::tests::start_app(app);
+
+    // Every connection is served, since each handler releases the connection as it panics, but the
+    //   previous handler may still be unwinding when the next connection arrives
+    for _ in 0..3 {
+        let served = (0..50).any(|_| {
+            let received = rejected(&addr);
+
+            if received == REJECTION {
+                sleep(Duration::from_millis(10));
+                return false;
+            }
+
+            assert_eq!(received, b"panicking");
+            true
+        });
+
+        assert!(served);
+    }
+}
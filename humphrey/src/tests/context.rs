@@ -0,0 +1,164 @@
+#![allow(unused_imports)]
+use crate::thread::context::RequestContext;
+
+use std::panic::catch_unwind;
+
+#[cfg(not(feature = "tokio"))]
+use crate::http::{Request, Response, StatusCode};
+#[cfg(not(feature = "tokio"))]
+use crate::monitor::event::EventType;
+#[cfg(not(feature = "tokio"))]
+use crate::monitor::MonitorConfig;
+#[cfg(not(feature = "tokio"))]
+use crate::App;
+#[cfg(not(feature = "tokio"))]
+use std::io::{BufRead, BufReader, Read, Write};
+#[cfg(not(feature = "tokio"))]
+use std::net::{TcpListener, TcpStream};
+#[cfg(not(feature = "tokio"))]
+use std::sync::mpsc::channel;
+#[cfg(not(feature = "tokio"))]
+use std::thread::{sleep, spawn};
+#[cfg(not(feature = "tokio"))]
+use std::time::Duration;
+
+#[test]
+fn test_context_guard() {
+    let context = RequestContext {
+        route: "/api/*".into(),
+        peer: "1.2.3.4:5678".parse().unwrap(),
+        connection_id: 7,
+    };
+
+    assert_eq!(RequestContext::current(), None);
+
+    let guard = context.clone().enter();
+    assert_eq!(RequestContext::current(), Some(context.clone()));
+    assert_eq!(
+        RequestContext::current().unwrap().to_string(),
+        "route /api/*, peer 1.2.3.4:5678, connection 7"
+    );
+
+    drop(guard);
+    assert_eq!(RequestContext::current(), None);
+
+    // The context is also cleared when the handler panics
+    let result = catch_unwind(|| {
+        let _guard = context.enter();
+        panic!("handler failed");
+    });
+
+    assert!(result.is_err());
+    assert_eq!(RequestContext::current(), None);
+}
+
+/// Sends a keep-alive request for the URI and returns the body of the response.
+#[cfg(not(feature = "tokio"))]
+fn get(reader: &mut impl BufRead, writer: &mut impl Write, uri: &str) -> String {
+    writer
+        .write_all(format!("GET {} HTTP/1.1\r\nConnection: keep-alive\r\n\r\n", uri).as_bytes())
+        .unwrap();
+
+    let mut length = 0;
+
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+
+        if line == "\r\n" {
+            break;
+        } else if let Some(value) = line.strip_prefix("Content-Length: ") {
+            length = value.trim().parse().unwrap();
+        }
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+
+    String::from_utf8(body).unwrap()
+}
+
+#[test]
+#[cfg(not(feature = "tokio"))]
+fn test_panic_context() {
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .to_string();
+    let (shutdown_tx, shutdown_rx) = channel();
+    let (monitor_tx, monitor_rx) = channel();
+
+    let app: App<()> = App::new_with_config(1, ())
+        .with_stateless_route("/context", |_: Request| {
+            let context = RequestContext::current().unwrap();
+            let thread = std::thread::current().name().unwrap().to_string();
+
+            Response::new(StatusCode::OK, format!("{} on {}", context, thread))
+        })
+        .with_stateless_route("/panic/*", |_: Request| -> Response {
+            panic!("handler failed")
+        })
+        .with_monitor(
+            MonitorConfig::new(monitor_tx).with_subscription_to(EventType::ThreadPoolPanic),
+        )
+        .with_shutdown(shutdown_rx);
+
+    let cloned_addr = addr.clone();
+    spawn(move || app.run(cloned_addr).unwrap());
+
+    let stream = loop {
+        if let Ok(stream) = TcpStream::connect(&addr) {
+            break stream;
+        }
+
+        sleep(Duration::from_millis(10));
+    };
+    let peer = stream.local_addr().unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+
+    // The handler sees the context of its own request
+    let body = get(&mut reader, &mut writer, "/context");
+    let connection_id: u64 = body
+        .split_once("connection ")
+        .and_then(|(_, rest)| rest.split(' ').next())
+        .unwrap()
+        .parse()
+        .unwrap();
+
+    assert_eq!(
+        body,
+        format!(
+            "route /context, peer {}, connection {} on humphrey-worker-0",
+            peer, connection_id
+        )
+    );
+
+    // The panic is reported with the context of the request which caused it
+    writer
+        .write_all(b"GET /panic/now HTTP/1.1\r\nConnection: keep-alive\r\n\r\n")
+        .unwrap();
+
+    let event = monitor_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    let info = event.info.unwrap();
+
+    assert_eq!(event.kind, EventType::ThreadPoolPanic);
+    assert!(info.starts_with("Thread humphrey-worker-0 panicked at "));
+    assert!(info.ends_with(&format!(
+        " while handling a request (route /panic/*, peer {}, connection {})",
+        peer, connection_id
+    )));
+
+    // The next request is handled with a fresh context on a new connection
+    let stream = TcpStream::connect(&addr).unwrap();
+    let next_peer = stream.local_addr().unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+
+    let body = get(&mut reader, &mut writer, "/context");
+    assert!(body.starts_with(&format!("route /context, peer {}, connection ", next_peer)));
+    assert!(!body.contains(&format!("connection {} ", connection_id)));
+
+    shutdown_tx.send(()).unwrap();
+}
@@ -1,5 +1,27 @@
 #[allow(unused_imports)]
-use crate::http::date::DateTime;
+use crate::http::date::{expand_year, DateTime};
+
+#[allow(unused_imports)]
+use std::time::{Duration, SystemTime};
+
+/// The timestamps of the first and last seconds which can be formatted with a four-digit year.
+const MIN_TIMESTAMP: i64 = -62135596800;
+const MAX_TIMESTAMP: i64 = 253402300799;
+
+/// A small deterministic random number generator, so that failures can be reproduced.
+struct Rng(u64);
+
+impl Rng {
+    /// Returns a random number in the given range.
+    fn between(&mut self, min: i64, max: i64) -> i64 {
+        // xorshift64
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+
+        min + (self.0 % (max - min + 1) as u64) as i64
+    }
+}
 
 #[test]
 fn test_date_from_timestamp() {
@@ -34,3 +56,269 @@ fn test_date_from_timestamp() {
     assert_eq!(output_4, expected_output_4);
     assert_eq!(output_5, expected_output_5);
 }
+
+#[test]
+fn test_date_parse_formats() {
+    let rfc_1123 = DateTime::parse("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+    let rfc_850 = DateTime::parse("Sunday, 06-Nov-94 08:49:37 GMT").unwrap();
+    let asctime = DateTime::parse("Sun Nov  6 08:49:37 1994").unwrap();
+
+    assert_eq!(rfc_1123.timestamp, 784111777);
+    assert_eq!(rfc_850, rfc_1123);
+    assert_eq!(asctime, rfc_1123);
+    assert_eq!(
+        DateTime::parse("Thu Nov 24 08:49:37 1994")
+            .unwrap()
+            .timestamp,
+        785666977
+    );
+}
+
+#[test]
+fn test_date_round_trip() {
+    // Every day from 1995 to 2005 at both the start and end of the day, which covers every month
+    //   boundary including the end of February in leap and non-leap years
+    let start = DateTime::parse("Sun, 01 Jan 1995 00:00:00 GMT")
+        .unwrap()
+        .timestamp;
+
+    for day in 0..(365 * 11 + 3) {
+        for offset in [0, 86399] {
+            let timestamp = start + day * 86400 + offset;
+            let formatted = DateTime::from(timestamp).to_string();
+            let parsed = DateTime::parse(&formatted).unwrap();
+
+            assert_eq!(parsed.timestamp, timestamp, "{}", formatted);
+            assert_eq!(parsed.to_string(), formatted);
+        }
+    }
+
+    for date in [
+        "Sat, 31 Jan 2004 23:59:59 GMT",
+        "Sun, 01 Feb 2004 00:00:00 GMT",
+        "Thu, 29 Feb 1968 12:00:00 GMT",
+        "Fri, 28 Sep 1066 10:12:51 GMT",
+        "Fri, 31 Dec 9999 23:59:59 GMT",
+        // Years before 1000 are padded to four digits
+        "Mon, 01 Mar 0100 00:00:00 GMT",
+    ] {
+        assert_eq!(DateTime::parse(date).unwrap().to_string(), date);
+    }
+}
+
+#[test]
+fn test_date_parse_leap_years() {
+    assert_eq!(
+        DateTime::parse("Sat, 29 Feb 2020 12:34:56 GMT")
+            .unwrap()
+            .timestamp,
+        1582979696
+    );
+    assert_eq!(
+        DateTime::parse("Tue, 29 Feb 2000 00:00:00 GMT")
+            .unwrap()
+            .timestamp,
+        951782400
+    );
+
+    // Centuries are only leap years if they are divisible by 400
+    assert!(DateTime::parse("Thu, 29 Feb 1900 00:00:00 GMT").is_none());
+    assert!(DateTime::parse("Mon, 29 Feb 2100 00:00:00 GMT").is_none());
+    assert!(DateTime::parse("Mon, 29 Feb 2021 00:00:00 GMT").is_none());
+    assert!(DateTime::parse("Sun, 30 Feb 2020 00:00:00 GMT").is_none());
+    assert!(DateTime::parse("Sunday, 29-Feb-21 00:00:00 GMT").is_none());
+    assert!(DateTime::parse("Mon Feb 29 00:00:00 2021").is_none());
+}
+
+#[test]
+fn test_date_two_digit_years() {
+    assert_eq!(expand_year(94, 2021), 1994);
+    assert_eq!(expand_year(21, 2021), 2021);
+    assert_eq!(expand_year(71, 2021), 2071);
+    assert_eq!(expand_year(72, 2021), 1972);
+    assert_eq!(expand_year(0, 2021), 2000);
+    assert_eq!(expand_year(99, 2049), 2099);
+    assert_eq!(expand_year(10, 2095), 2110);
+
+    let now = DateTime::now();
+    let year = now.year as i64 % 100;
+    let date = format!("Wednesday, 01-Jan-{:02} 00:00:00 GMT", year);
+
+    assert_eq!(DateTime::parse(&date).unwrap().year, now.year);
+}
+
+#[test]
+fn test_date_parse_invalid() {
+    for date in [
+        "",
+        "yesterday",
+        "Sun, 06 Nov 1994 08:49:37",
+        "Sun, 06 Nov 1994 08:49:37 UTC",
+        "Sun, 06 Nov 94 08:49:37 GMT",
+        "Sun, 6 Nov 1994 08:49:37 GMT",
+        "Sun, 06 November 1994 08:49:37 GMT",
+        "Sun, 06 Nov 1994 24:00:00 GMT",
+        "Sun, 06 Nov 1994 08:60:00 GMT",
+        "Sun, 06 Nov 1994 08:49 GMT",
+        "Sun, 06 Nov 1994 08:49:37:00 GMT",
+        "Sun, 00 Nov 1994 08:49:37 GMT",
+        "Sun, 31 Nov 1994 08:49:37 GMT",
+        "Sun, +6 Nov 1994 08:49:37 GMT",
+        "Sunday, 06-Nov-1994 08:49:37 GMT",
+        "Sunday, 06-Nov-94-01 08:49:37 GMT",
+        "Sun Nov 06 08:49:37 94",
+        "Sun Nov 106 08:49:37 1994",
+        "1, 06 Nov 1994 08:49:37 GMT",
+    ] {
+        assert!(DateTime::parse(date).is_none(), "{}", date);
+    }
+}
+
+#[test]
+fn test_date_comparison() {
+    let modified = DateTime::parse("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+    let earlier = DateTime::parse("Sun, 06 Nov 1994 08:49:36 GMT").unwrap();
+    let later = DateTime::parse("Mon Nov  7 00:00:00 1994").unwrap();
+
+    assert!(earlier < modified);
+    assert!(later > modified);
+    assert!(modified <= DateTime::from(784111777));
+    assert_eq!(modified.max(later), later);
+
+    // File modification times have sub-second precision which HTTP dates do not
+    let mtime = SystemTime::UNIX_EPOCH + Duration::from_millis(784111777900);
+    assert_eq!(DateTime::from(mtime), modified);
+    assert!(DateTime::from(mtime) <= modified);
+
+    let before_epoch = SystemTime::UNIX_EPOCH - Duration::from_millis(1500);
+    assert_eq!(DateTime::from(before_epoch).timestamp, -2);
+}
+
+#[test]
+fn test_date_arithmetic() {
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+
+    for _ in 0..100000 {
+        // Deltas range from a second to several centuries, so that some cross many leap years
+        let seconds = match rng.between(0, 2) {
+            0 => rng.between(0, 86400 * 2),
+            1 => rng.between(0, 86400 * 366 * 4),
+            _ => rng.between(0, 86400 * 366 * 400),
+        };
+        let timestamp = rng.between(MIN_TIMESTAMP, MAX_TIMESTAMP - seconds);
+
+        let date = DateTime::from(timestamp);
+        let system_time = SystemTime::from(date);
+        let later = date.add_seconds(seconds as u64);
+
+        assert_eq!(
+            later,
+            DateTime::from(system_time + Duration::from_secs(seconds as u64)),
+            "{} + {}",
+            date.to_string(),
+            seconds
+        );
+        assert_eq!(DateTime::from(system_time), date);
+        assert_eq!(later - date, seconds);
+        assert_eq!(date - later, -seconds);
+        assert_eq!(later > date, seconds > 0);
+        assert_eq!(later.cmp(&date), SystemTime::from(later).cmp(&system_time));
+
+        // 1 January 1970 was a Thursday
+        assert_eq!(
+            later.weekday as i64,
+            ((timestamp + seconds).div_euclid(86400) + 4).rem_euclid(7)
+        );
+
+        // Parsing calculates the timestamp from the fields independently
+        assert_eq!(DateTime::parse(&later.to_string()), Some(later));
+    }
+}
+
+#[test]
+fn test_date_arithmetic_boundaries() {
+    for (date, seconds, expected) in [
+        // 2100 is not a leap year, but 2000 is
+        (
+            "Sun, 28 Feb 2100 00:00:00 GMT",
+            86400,
+            "Mon, 01 Mar 2100 00:00:00 GMT",
+        ),
+        (
+            "Mon, 28 Feb 2000 12:00:00 GMT",
+            86400,
+            "Tue, 29 Feb 2000 12:00:00 GMT",
+        ),
+        (
+            "Thu, 31 Dec 2099 23:59:59 GMT",
+            1,
+            "Fri, 01 Jan 2100 00:00:00 GMT",
+        ),
+        // Leap seconds were inserted after both of these, but UNIX time ignores them
+        (
+            "Sat, 31 Dec 2016 23:59:59 GMT",
+            1,
+            "Sun, 01 Jan 2017 00:00:00 GMT",
+        ),
+        (
+            "Tue, 30 Jun 2015 23:59:59 GMT",
+            1,
+            "Wed, 01 Jul 2015 00:00:00 GMT",
+        ),
+        // The ends of months of every length
+        (
+            "Tue, 31 Jan 2023 23:59:59 GMT",
+            1,
+            "Wed, 01 Feb 2023 00:00:00 GMT",
+        ),
+        (
+            "Tue, 28 Feb 2023 23:59:59 GMT",
+            1,
+            "Wed, 01 Mar 2023 00:00:00 GMT",
+        ),
+        (
+            "Thu, 29 Feb 2024 23:59:59 GMT",
+            1,
+            "Fri, 01 Mar 2024 00:00:00 GMT",
+        ),
+        (
+            "Sun, 30 Apr 2023 23:59:59 GMT",
+            1,
+            "Mon, 01 May 2023 00:00:00 GMT",
+        ),
+        (
+            "Sun, 31 Dec 2023 23:59:59 GMT",
+            1,
+            "Mon, 01 Jan 2024 00:00:00 GMT",
+        ),
+        (
+            "Sun, 31 Dec 2023 23:59:59 GMT",
+            0,
+            "Sun, 31 Dec 2023 23:59:59 GMT",
+        ),
+    ] {
+        let date = DateTime::parse(date).unwrap();
+        let expected = DateTime::parse(expected).unwrap();
+
+        assert_eq!(date.add_seconds(seconds), expected);
+        assert_eq!(expected - date, seconds as i64);
+    }
+
+    // Adding too many seconds saturates instead of overflowing
+    assert_eq!(DateTime::from(0).add_seconds(u64::MAX).timestamp, i64::MAX);
+}
+
+#[test]
+fn test_date_into_system_time() {
+    let date = DateTime::parse("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+    assert_eq!(
+        SystemTime::from(date),
+        SystemTime::UNIX_EPOCH + Duration::from_secs(784111777)
+    );
+
+    let date = DateTime::parse("Sun, 30 Apr 1967 21:02:13 GMT").unwrap();
+    assert_eq!(
+        SystemTime::from(date),
+        SystemTime::UNIX_EPOCH - Duration::from_secs(84337067)
+    );
+}
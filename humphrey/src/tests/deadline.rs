@@ -0,0 +1,204 @@
+use crate::clock::{now_monotonic, override_clock, MockClock};
+use crate::http::address::Address;
+use crate::http::request::DEADLINE_HEADER;
+use crate::http::Request;
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// The address of the trusted proxy in these tests.
+const PROXY: [u8; 4] = [10, 0, 0, 1];
+
+/// Builds a request with the given `X-Request-Deadline` header, received directly from `peer`.
+fn request(deadline: &str, peer: &str) -> Request {
+    let mut request = Request::builder().header(DEADLINE_HEADER, deadline).build();
+    request.address = Address::from_headers(&request.headers, peer).unwrap();
+
+    request
+}
+
+#[test]
+fn test_deadline_from_timeout() {
+    let clock = MockClock::new();
+    let _guard = override_clock(clock.clone());
+
+    let mut request = Request::builder().build();
+    assert_eq!(request.deadline, None);
+    assert_eq!(request.time_remaining(), None);
+
+    request.set_deadline(Some(Duration::from_secs(5)), &[]);
+    assert_eq!(
+        request.deadline,
+        Some(now_monotonic() + Duration::from_secs(5))
+    );
+    assert_eq!(request.time_remaining(), Some(Duration::from_secs(5)));
+
+    clock.advance(Duration::from_secs(3));
+    assert_eq!(request.time_remaining(), Some(Duration::from_secs(2)));
+
+    clock.advance(Duration::from_secs(3));
+    assert_eq!(request.time_remaining(), Some(Duration::ZERO));
+}
+
+#[test]
+fn test_deadline_from_trusted_proxy() {
+    let _guard = override_clock(MockClock::new());
+    let trusted = [IpAddr::from(PROXY)];
+
+    let mut proxied = request("250", "10.0.0.1:1234");
+    proxied.set_deadline(None, &trusted);
+    assert_eq!(proxied.time_remaining(), Some(Duration::from_millis(250)));
+
+    // The earlier of the two deadlines is used
+    proxied.set_deadline(Some(Duration::from_secs(5)), &trusted);
+    assert_eq!(proxied.time_remaining(), Some(Duration::from_millis(250)));
+
+    let mut generous = request("60000", "10.0.0.1:1234");
+    generous.set_deadline(Some(Duration::from_secs(5)), &trusted);
+    assert_eq!(generous.time_remaining(), Some(Duration::from_secs(5)));
+}
+
+#[test]
+fn test_deadline_header_ignored() {
+    let trusted = [IpAddr::from(PROXY)];
+
+    // The header could be forged by any other client
+    let mut untrusted = request("250", "10.0.0.2:1234");
+    untrusted.set_deadline(None, &trusted);
+    assert_eq!(untrusted.deadline, None);
+
+    let mut invalid = request("soon", "10.0.0.1:1234");
+    invalid.set_deadline(None, &trusted);
+    assert_eq!(invalid.deadline, None);
+
+    // A deadline too far in the future to represent is ignored rather than panicking
+    let mut distant = request(&u64::MAX.to_string(), "10.0.0.1:1234");
+    distant.set_deadline(Some(Duration::from_secs(5)), &trusted);
+    assert!(distant.time_remaining().unwrap() <= Duration::from_secs(5));
+}
+
+#[cfg(not(feature = "tokio"))]
+mod app {
+    use crate::client::Client;
+    use crate::http::{Request, Response, StatusCode};
+    use crate::App;
+
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{IpAddr, Ipv4Addr, TcpListener, TcpStream};
+    use std::sync::mpsc::{channel, Sender};
+    use std::thread::spawn;
+    use std::time::{Duration, Instant};
+
+    /// Starts a server which reads requests but never responds to them, returning its address.
+    fn start_unresponsive() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        spawn(move || {
+            for stream in listener.incoming().flatten() {
+                spawn(move || {
+                    let mut reader = BufReader::new(stream);
+                    let mut line = String::new();
+
+                    while reader.read_line(&mut line).is_ok_and(|read| read > 0) {
+                        line.clear();
+                    }
+                });
+            }
+        });
+
+        addr
+    }
+
+    /// Starts an app whose handler calls the unresponsive server with the deadline of its request,
+    ///   returning its address and shutdown signal.
+    fn start_app(timeout: Option<Duration>, trusted: Vec<IpAddr>) -> (String, Sender<()>) {
+        let (shutdown_tx, shutdown_rx) = channel();
+        let downstream = start_unresponsive();
+
+        let app: App<()> = App::new_with_config(2, ())
+            .with_connection_timeout(timeout)
+            .with_deadline_propagation(trusted)
+            .with_stateless_route("/", move |request: Request| {
+                let deadline = match request.deadline {
+                    Some(deadline) => deadline,
+                    None => return Response::new(StatusCode::OK, "no deadline"),
+                };
+
+                let mut client = Client::new();
+                let result = client
+                    .get(format!("http://{}/", downstream))
+                    .unwrap()
+                    .with_deadline(deadline)
+                    .send();
+
+                match result {
+                    Ok(_) => Response::new(StatusCode::OK, "responded"),
+                    Err(_) => Response::empty(StatusCode::GatewayTimeout),
+                }
+            })
+            .with_shutdown(shutdown_rx);
+
+        let addr = crate::tests::start_app(app);
+
+        (addr, shutdown_tx)
+    }
+
+    /// Sends a request with the given headers, returning the status line and body of the response.
+    fn send(addr: &str, headers: &str) -> (String, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "GET / HTTP/1.1\r\nConnection: Close\r\n{}\r\n",
+            headers
+        )
+        .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split("\r\n").next().unwrap().to_string();
+
+        (status, body.trim_end().to_string())
+    }
+
+    #[test]
+    fn test_propagated_deadline() {
+        let (addr, shutdown) = start_app(None, vec![IpAddr::V4(Ipv4Addr::LOCALHOST)]);
+
+        // The downstream call gives up when the nearly expired deadline passes, rather than waiting
+        //   for the unresponsive server indefinitely
+        let start = Instant::now();
+        let (status, _) = send(&addr, "X-Request-Deadline: 100\r\n");
+
+        assert_eq!(status, "HTTP/1.1 504 Gateway Timeout");
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        shutdown.send(()).unwrap();
+    }
+
+    #[test]
+    fn test_timeout_deadline() {
+        let (addr, shutdown) = start_app(Some(Duration::from_millis(200)), Vec::new());
+
+        let start = Instant::now();
+        let (status, _) = send(&addr, "");
+
+        assert_eq!(status, "HTTP/1.1 504 Gateway Timeout");
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        shutdown.send(()).unwrap();
+    }
+
+    #[test]
+    fn test_untrusted_deadline_ignored() {
+        let (addr, shutdown) = start_app(None, Vec::new());
+
+        let (status, body) = send(&addr, "X-Request-Deadline: 100\r\n");
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(body, "no deadline");
+
+        shutdown.send(()).unwrap();
+    }
+}
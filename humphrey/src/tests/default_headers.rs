@@ -0,0 +1,194 @@
+use crate::http::headers::{HeaderType, Headers};
+use crate::http::{Response, StatusCode};
+use crate::route::{apply_default_headers, SubApp};
+
+/// Builds a default sub-app and a sub-app for `api.example.com`, each with default headers.
+fn subapps() -> (Vec<SubApp<()>>, SubApp<()>) {
+    let mut api = SubApp::new()
+        .with_default_header("X-Api-Version", "2")
+        .with_default_header(HeaderType::CacheControl, "no-store");
+    api.host = "api.example.com".to_string();
+
+    let mut defaults = Headers::new();
+    defaults.add("X-Api-Version", "1");
+    defaults.add(HeaderType::CacheControl, "max-age=60");
+    defaults.add("Vary", "Accept");
+    defaults.add("Vary", "Origin");
+    defaults.add(HeaderType::SetCookie, "session=abc");
+
+    let default_subapp = SubApp::new()
+        .with_default_header("X-Replaced", "true")
+        .with_default_headers(defaults);
+
+    (vec![api], default_subapp)
+}
+
+/// Returns the values of the response headers with the given name.
+fn values(response: &Response, name: &str) -> Vec<String> {
+    response
+        .headers
+        .get_all(name)
+        .into_iter()
+        .map(|value| value.to_string())
+        .collect()
+}
+
+#[test]
+fn test_defaults_added() {
+    let (subapps, default_subapp) = subapps();
+    let mut response = Response::new(StatusCode::OK, "hello");
+
+    apply_default_headers(&mut response, &subapps, &default_subapp, None);
+
+    assert_eq!(values(&response, "X-Api-Version"), vec!["1"]);
+    assert_eq!(values(&response, "Cache-Control"), vec!["max-age=60"]);
+    assert_eq!(values(&response, "Vary"), vec!["Accept", "Origin"]);
+    assert_eq!(values(&response, "Set-Cookie"), vec!["session=abc"]);
+
+    // `with_default_headers` replaces the headers added before it
+    assert!(values(&response, "X-Replaced").is_empty());
+}
+
+#[test]
+fn test_defaults_do_not_override() {
+    let (subapps, default_subapp) = subapps();
+    let mut response = Response::new(StatusCode::OK, "hello")
+        .with_header(HeaderType::CacheControl, "private")
+        .with_header("Vary", "Cookie")
+        .with_header(HeaderType::SetCookie, "theme=dark");
+
+    apply_default_headers(&mut response, &subapps, &default_subapp, None);
+
+    assert_eq!(values(&response, "Cache-Control"), vec!["private"]);
+    assert_eq!(values(&response, "Vary"), vec!["Cookie"]);
+    assert_eq!(values(&response, "X-Api-Version"), vec!["1"]);
+
+    // A response can set many cookies, so the default one is added alongside the handler's
+    assert_eq!(
+        values(&response, "Set-Cookie"),
+        vec!["theme=dark", "session=abc"]
+    );
+}
+
+#[test]
+fn test_host_defaults_take_precedence() {
+    let (subapps, default_subapp) = subapps();
+    let mut response = Response::new(StatusCode::OK, "hello");
+
+    apply_default_headers(
+        &mut response,
+        &subapps,
+        &default_subapp,
+        Some("api.example.com"),
+    );
+
+    assert_eq!(values(&response, "X-Api-Version"), vec!["2"]);
+    assert_eq!(values(&response, "Cache-Control"), vec!["no-store"]);
+
+    // Defaults which the host does not replace still apply
+    assert_eq!(values(&response, "Vary"), vec!["Accept", "Origin"]);
+
+    // Other hosts only get the app's defaults
+    let mut response = Response::new(StatusCode::OK, "hello");
+    apply_default_headers(
+        &mut response,
+        &subapps,
+        &default_subapp,
+        Some("www.example.com"),
+    );
+
+    assert_eq!(values(&response, "X-Api-Version"), vec!["1"]);
+}
+
+#[test]
+fn test_informational_responses_unchanged() {
+    let (subapps, default_subapp) = subapps();
+    let mut response = Response::empty(StatusCode::Continue);
+
+    apply_default_headers(&mut response, &subapps, &default_subapp, None);
+
+    assert!(response.headers.is_empty());
+}
+
+#[cfg(not(feature = "tokio"))]
+mod app {
+    use crate::http::headers::HeaderType;
+    use crate::http::{Response, StatusCode};
+    use crate::route::SubApp;
+    use crate::App;
+
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::sync::mpsc::channel;
+    use std::thread::spawn;
+
+    /// Sends a request for the path to the given host, returning the status line and headers of the
+    ///   response with their names in lowercase.
+    fn send(addr: &str, host: &str, path: &str) -> (String, Vec<String>) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, host).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let (head, _) = response.split_once("\r\n\r\n").unwrap();
+        let mut lines = head.split("\r\n");
+        let status = lines.next().unwrap().to_string();
+
+        let headers = lines
+            .filter_map(|line| line.split_once(": "))
+            .map(|(name, value)| format!("{}: {}", name.to_ascii_lowercase(), value))
+            .collect();
+
+        (status, headers)
+    }
+
+    #[test]
+    fn test_default_headers_served() {
+        let addr = crate::tests::free_addr();
+        let (shutdown_tx, shutdown_rx) = channel();
+
+        let api = SubApp::new()
+            .with_default_header("X-Api-Version", "2")
+            .with_stateless_route("/", |_| Response::new(StatusCode::OK, "api"));
+
+        let app: App<()> = App::new_with_config(2, ())
+            .with_host("api.example.com", api)
+            .with_default_header("X-Api-Version", "1")
+            .with_default_header(HeaderType::CacheControl, "no-cache")
+            .with_stateless_route("/", |_| Response::new(StatusCode::OK, "hello"))
+            .with_stateless_route("/private", |_| {
+                Response::new(StatusCode::OK, "secret")
+                    .with_header(HeaderType::CacheControl, "no-store")
+            })
+            .with_error_handler(|status| Response::new(status, "error"))
+            .with_shutdown(shutdown_rx);
+
+        let cloned_addr = addr.clone();
+        let handle = spawn(move || app.run(cloned_addr).unwrap());
+
+        crate::tests::wait_for(&addr);
+
+        let (status, headers) = send(&addr, "example.com", "/");
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert!(headers.contains(&"x-api-version: 1".to_string()));
+        assert!(headers.contains(&"cache-control: no-cache".to_string()));
+
+        let (_, headers) = send(&addr, "example.com", "/private");
+        assert!(headers.contains(&"cache-control: no-store".to_string()));
+        assert!(!headers.contains(&"cache-control: no-cache".to_string()));
+
+        // Responses from the error handler get the defaults too
+        let (status, headers) = send(&addr, "example.com", "/missing");
+        assert_eq!(status, "HTTP/1.1 404 Not Found");
+        assert!(headers.contains(&"x-api-version: 1".to_string()));
+
+        let (_, headers) = send(&addr, "api.example.com", "/");
+        assert!(headers.contains(&"x-api-version: 2".to_string()));
+        assert!(!headers.contains(&"x-api-version: 1".to_string()));
+        assert!(headers.contains(&"cache-control: no-cache".to_string()));
+
+        shutdown_tx.send(()).unwrap();
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,172 @@
+use crate::drain::{DrainOptions, DrainSignal};
+use crate::http::headers::HeaderType;
+use crate::http::{Response, StatusCode};
+use crate::route::ErrorContext;
+use crate::tests::{free_addr, wait_for};
+use crate::App;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::thread::sleep;
+use std::time::Duration;
+
+#[cfg(not(feature = "tokio"))]
+use std::sync::mpsc::channel;
+#[cfg(not(feature = "tokio"))]
+use std::thread::spawn;
+
+const PERIOD: Duration = Duration::from_millis(500);
+
+fn connect(addr: &str) -> TcpStream {
+    let stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream
+}
+
+/// Sends a keep-alive request for the given path on the stream and reads the response.
+fn get(stream: &mut TcpStream, path: &str) -> Response {
+    stream
+        .write_all(format!("GET {} HTTP/1.1\r\nConnection: Keep-Alive\r\n\r\n", path).as_bytes())
+        .unwrap();
+
+    Response::from_stream(stream).unwrap()
+}
+
+fn options() -> DrainOptions {
+    DrainOptions {
+        period: PERIOD,
+        retry_after: Duration::from_millis(2500),
+    }
+}
+
+/// Describes the cause of the error and the path of the request.
+fn draining_error(context: &ErrorContext) -> Response {
+    let path = context
+        .request
+        .map_or("none", |request| request.uri.as_str());
+
+    Response::new(context.status_code, format!("{:?} {}", context.cause, path))
+}
+
+/// Reports whether the app was draining when the request finished, after taking some time.
+fn slow_handler(drain: DrainSignal) -> impl Fn(crate::http::Request) -> Response {
+    move |_| {
+        sleep(Duration::from_millis(300));
+        Response::new(StatusCode::OK, format!("draining: {}", drain.is_draining()))
+    }
+}
+
+/// Checks the behaviour of an app which has just been signalled to shut down, given a keep-alive
+///   connection opened beforehand, a slow request started beforehand, and a function to wait for
+///   the app to stop.
+fn check_drain(
+    addr: &str,
+    mut keep_alive: TcpStream,
+    mut slow: TcpStream,
+    drain: DrainSignal,
+    wait: impl FnOnce(),
+) {
+    sleep(Duration::from_millis(50));
+    assert!(drain.is_draining());
+
+    // New connections are still accepted during the drain period, but refused with 503
+    let response = get(&mut connect(addr), "/");
+    assert_eq!(response.status_code, StatusCode::ServiceUnavailable);
+    assert_eq!(response.headers.get(HeaderType::RetryAfter), Some("3"));
+    assert_eq!(response.headers.get(HeaderType::Connection), Some("Close"));
+    assert_eq!(response.body, b"Some(Draining) /");
+
+    // New requests on existing keep-alive connections are also refused, and the connection closed
+    let response = get(&mut keep_alive, "/");
+    assert_eq!(response.status_code, StatusCode::ServiceUnavailable);
+    assert_eq!(response.headers.get(HeaderType::Connection), Some("Close"));
+    assert_eq!(keep_alive.read(&mut [0; 1]).unwrap(), 0);
+
+    // The request which was already being processed completes normally
+    let response = Response::from_stream(&mut slow).unwrap();
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body, b"draining: true");
+
+    // After the drain period, the app stops accepting connections
+    wait();
+    assert!(TcpStream::connect(addr).is_err());
+}
+
+#[cfg(not(feature = "tokio"))]
+#[test]
+fn test_drain() {
+    let addr = free_addr();
+    let (shutdown_tx, shutdown_rx) = channel();
+
+    let app: App<()> = App::new_with_config(4, ())
+        .with_stateless_route("/", |_| Response::new(StatusCode::OK, "hello"))
+        .with_draining(options())
+        .with_error_context_handler(draining_error)
+        .with_shutdown(shutdown_rx);
+    let drain = app.drain_signal();
+    let app = app.with_stateless_route("/slow", slow_handler(drain.clone()));
+
+    let cloned_addr = addr.clone();
+    let app_thread = spawn(move || app.run(cloned_addr).unwrap());
+    wait_for(&addr);
+
+    // Before the drain, requests are served normally
+    let mut keep_alive = connect(&addr);
+    let response = get(&mut keep_alive, "/");
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert!(!drain.is_draining());
+
+    let mut slow = connect(&addr);
+    slow.write_all(b"GET /slow HTTP/1.1\r\n\r\n").unwrap();
+    sleep(Duration::from_millis(50));
+
+    shutdown_tx.send(()).unwrap();
+
+    check_drain(&addr, keep_alive, slow, drain, || {
+        app_thread.join().unwrap();
+    });
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn test_drain() {
+    let addr = free_addr();
+    let shutdown = tokio_util::sync::CancellationToken::new();
+
+    let app: App<()> = App::new_with_config(())
+        .with_stateless_route("/", |_| async { Response::new(StatusCode::OK, "hello") })
+        .with_draining(options())
+        .with_error_context_handler(draining_error)
+        .with_shutdown(shutdown.clone());
+    let drain = app.drain_signal();
+    let slow = slow_handler(drain.clone());
+    let app = app.with_stateless_route("/slow", move |request| {
+        let response = tokio::task::block_in_place(|| slow(request));
+        async move { response }
+    });
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let cloned_addr = addr.clone();
+    let app_task = runtime.spawn(async move { app.run(cloned_addr).await.is_ok() });
+    wait_for(&addr);
+
+    let mut keep_alive = connect(&addr);
+    let response = get(&mut keep_alive, "/");
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert!(!drain.is_draining());
+
+    let mut slow = connect(&addr);
+    slow.write_all(b"GET /slow HTTP/1.1\r\n\r\n").unwrap();
+    sleep(Duration::from_millis(50));
+
+    shutdown.cancel();
+
+    check_drain(&addr, keep_alive, slow, drain, || {
+        assert!(runtime.block_on(app_task).unwrap());
+    });
+}
@@ -0,0 +1,159 @@
+use crate::cancellation::CancellationToken;
+use crate::embed::{EmbeddedDir, EmbeddedFile};
+use crate::http::address::Address;
+use crate::http::headers::{HeaderType, Headers};
+use crate::http::method::Method;
+use crate::http::request::BodyState;
+use crate::http::{Request, Response, StatusCode};
+use crate::route::RouteParams;
+
+fn assets() -> EmbeddedDir {
+    crate::embed!(
+        "./testcases/embed",
+        [
+            "index.html",
+            "docs/index.html",
+            "docs/guide/style.css",
+            "logo.png"
+        ]
+    )
+}
+
+fn request(uri: &str, etag: Option<&str>) -> Request {
+    let mut headers = Headers::new();
+
+    if let Some(etag) = etag {
+        headers.add("If-None-Match", etag);
+    }
+
+    Request {
+        method: Method::Get,
+        overridden_method: None,
+        uri: uri.into(),
+        uri_raw: uri.into(),
+        query: String::new(),
+        version: "HTTP/1.1".into(),
+        headers,
+        content: None,
+        body_state: BodyState::Complete,
+        trailers: Headers::new(),
+        address: Address::new("127.0.0.1:1234").unwrap(),
+        secure: false,
+        local_addr: None,
+        deadline: None,
+        trace: None,
+        cancellation: CancellationToken::default(),
+        params: RouteParams::default(),
+    }
+}
+
+fn serve(dir: &EmbeddedDir, uri: &str) -> Option<Response> {
+    dir.serve(&request(uri, None), uri)
+}
+
+#[test]
+fn test_nested_directories() {
+    let dir = assets();
+    assert_eq!(dir.len(), 4);
+
+    let response = serve(&dir, "/").unwrap();
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body, b"<h1>Home</h1>\n");
+    assert_eq!(
+        response.headers.get(HeaderType::ContentType),
+        Some("text/html")
+    );
+
+    let response = serve(&dir, "/docs/").unwrap();
+    assert_eq!(response.body, b"<h1>Docs</h1>\n");
+
+    let response = serve(&dir, "/docs").unwrap();
+    assert_eq!(response.status_code, StatusCode::MovedPermanently);
+    assert_eq!(response.headers.get(HeaderType::Location), Some("/docs/"));
+
+    let response = serve(&dir, "/docs/guide/style.css").unwrap();
+    assert_eq!(response.body, b"body { color: red; }\n");
+    assert_eq!(
+        response.headers.get(HeaderType::ContentType),
+        Some("text/css")
+    );
+
+    assert!(serve(&dir, "/docs/guide/").is_none());
+    assert!(serve(&dir, "/missing.html").is_none());
+    assert!(serve(&dir, "/docs/../index.html").is_none());
+}
+
+#[test]
+fn test_binary_contents() {
+    let dir = assets();
+    let expected = include_bytes!("./testcases/embed/logo.png");
+
+    let response = serve(&dir, "/logo.png").unwrap();
+    assert_eq!(response.body, expected);
+    assert_eq!(
+        response.headers.get(HeaderType::ContentType),
+        Some("image/png")
+    );
+}
+
+#[test]
+fn test_not_modified() {
+    let dir = assets();
+
+    let response = serve(&dir, "/logo.png").unwrap();
+    let etag = response.headers.get(HeaderType::ETag).unwrap().to_string();
+    assert_eq!(etag, dir.get("logo.png").unwrap().etag);
+
+    let response = dir
+        .serve(&request("/logo.png", Some(&etag)), "/logo.png")
+        .unwrap();
+    assert_eq!(response.status_code, StatusCode::NotModified);
+    assert!(response.body.is_empty());
+
+    let other_etag = dir.get("index.html").unwrap().etag.clone();
+    assert_ne!(etag, other_etag);
+
+    let response = dir
+        .serve(&request("/logo.png", Some(&other_etag)), "/logo.png")
+        .unwrap();
+    assert_eq!(response.status_code, StatusCode::OK);
+}
+
+#[test]
+fn test_favicon_type() {
+    let png = EmbeddedFile::favicon(include_bytes!("./testcases/embed/logo.png"));
+    assert_eq!(png.mime_type.to_string(), "image/png");
+
+    let svg = EmbeddedFile::favicon(b"<svg xmlns=\"http://www.w3.org/2000/svg\"></svg>");
+    assert_eq!(svg.mime_type.to_string(), "image/svg+xml");
+
+    let icon = EmbeddedFile::favicon(&[0, 0, 1, 0]);
+    assert_eq!(icon.mime_type.to_string(), "image/vnd.microsoft.icon");
+}
+
+#[cfg(not(feature = "tokio"))]
+#[test]
+fn test_handlers() {
+    use crate::handlers::{robots_txt, serve_embedded};
+
+    use std::sync::Arc;
+
+    let handler = serve_embedded::<()>(assets());
+
+    let response = handler(request("/static/docs/", None), Arc::new(()), "/static/*");
+    assert_eq!(response.body, b"<h1>Docs</h1>\n");
+
+    let response = handler(request("/static/nothing", None), Arc::new(()), "/static/*");
+    assert_eq!(response.status_code, StatusCode::NotFound);
+
+    let handler = robots_txt::<()>("User-agent: *\nDisallow: /admin\n");
+    let response = handler(request("/robots.txt", None), Arc::new(()));
+    assert_eq!(
+        response.headers.get(HeaderType::ContentType),
+        Some("text/plain")
+    );
+    assert_eq!(
+        response.headers.get(HeaderType::CacheControl),
+        Some("public, max-age=604800")
+    );
+}
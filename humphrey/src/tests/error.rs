@@ -0,0 +1,100 @@
+use crate::error::Error;
+use crate::http::request::RequestError;
+use crate::http::response::ResponseError;
+use crate::http::status::StatusCodeError;
+use crate::http::{Response, StatusCode};
+
+use std::error::Error as StdError;
+use std::io::ErrorKind;
+
+fn read_missing_file() -> Result<Vec<u8>, Error> {
+    let contents = std::fs::read("this/file/does/not/exist")?;
+
+    Ok(contents)
+}
+
+#[test]
+fn test_io_conversion() {
+    let error = read_missing_file().unwrap_err();
+
+    assert!(matches!(&error, Error::Io(e) if e.kind() == ErrorKind::NotFound));
+    assert!(error.to_string().starts_with("I/O error: "));
+    assert!(error.source().is_some());
+    assert_eq!(error.status_code(), StatusCode::InternalError);
+}
+
+#[test]
+fn test_core_conversions() {
+    let error = Error::from(RequestError::Timeout);
+    assert_eq!(error.status_code(), StatusCode::RequestTimeout);
+    assert_eq!(
+        error.to_string(),
+        "Request error: the request timed out".to_string()
+    );
+
+    let error = Error::from(RequestError::Request);
+    assert_eq!(error.status_code(), StatusCode::BadRequest);
+
+    let error = Error::from(ResponseError::Stream);
+    assert_eq!(error.status_code(), StatusCode::InternalError);
+    assert_eq!(
+        error.to_string(),
+        "Response error: the stream could not be read".to_string()
+    );
+
+    let error = Error::from(StatusCode::try_from(600).unwrap_err());
+    assert!(matches!(error, Error::Status(StatusCodeError)));
+}
+
+#[test]
+fn test_boxed_conversion() {
+    let boxed: Box<dyn StdError + Send + Sync> = "something went wrong".into();
+    let error = Error::from(boxed);
+
+    assert!(matches!(error, Error::Other(_)));
+    assert_eq!(error.to_string(), "Error: something went wrong".to_string());
+}
+
+#[test]
+fn test_response_conversion() {
+    let response = Response::from(Error::from(RequestError::Request));
+
+    assert_eq!(response.status_code, StatusCode::BadRequest);
+    assert_eq!(response.body, b"Bad Request");
+
+    let response = Response::from(read_missing_file().unwrap_err());
+
+    assert_eq!(response.status_code, StatusCode::InternalError);
+    assert_eq!(response.body, b"Internal Server Error");
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_json_traceback_conversion() {
+    fn parse(json: &str) -> Result<humphrey_json::Value, Error> {
+        let value = humphrey_json::Value::parse(json)?;
+
+        Ok(value)
+    }
+
+    let error = parse("{\n  \"a\": 1,\n}").unwrap_err();
+
+    assert!(matches!(error, Error::Json(_)));
+    assert_eq!(error.status_code(), StatusCode::BadRequest);
+    assert_eq!(error.to_string(), "JSON error: trailing comma at 2:9");
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_json_parse_error_conversion() {
+    let error = Error::from(humphrey_json::error::ParseError::ValidationError(
+        "email",
+        "must contain an @".into(),
+    ));
+
+    assert!(matches!(error, Error::Json(_)));
+    assert_eq!(
+        error.to_string(),
+        "JSON error: field `email` failed validation: must contain an @"
+    );
+}
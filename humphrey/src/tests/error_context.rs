@@ -0,0 +1,126 @@
+use crate::http::method::Method;
+use crate::http::{Request, Response, StatusCode};
+use crate::route::{ErrorCause, ErrorContext};
+use crate::trace::TraceContext;
+use crate::App;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::panic::catch_unwind;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Describes each field of the error context in the body of the response.
+fn context_error(context: &ErrorContext) -> Response {
+    let cause = context.cause.as_ref().map(|cause| format!("{:?}", cause));
+    let path = context.request.map(|request| request.uri.as_str());
+
+    Response::new(
+        context.status_code,
+        format!(
+            "{} | {} | {} | {}",
+            u16::from(context.status_code),
+            cause.as_deref().unwrap_or("none"),
+            context.route.unwrap_or("none"),
+            path.unwrap_or("none")
+        ),
+    )
+}
+
+/// Starts an app whose errors are described by `context_error`, with a route for `GET /items`, a
+///   WebSocket route which is never allowed a connection, and a short connection timeout.
+fn start_app() -> (String, Sender<()>) {
+    let (shutdown_tx, shutdown_rx) = channel();
+
+    let app: App<()> = App::new_with_config(4, ())
+        .with_route_method("/items", Method::Get, |_, _| {
+            Response::new(StatusCode::OK, "items")
+        })
+        .with_websocket_route("/ws", |_, _, _: Arc<()>| ())
+        .with_websocket_limit(0)
+        .with_connection_timeout(Some(Duration::from_millis(100)))
+        .with_error_context_handler(context_error)
+        .with_shutdown(shutdown_rx);
+
+    let addr = crate::tests::start_app(app);
+
+    (addr, shutdown_tx)
+}
+
+/// Sends the request on a new connection and returns everything received until it is closed.
+fn exchange(addr: &str, request: &[u8]) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream.write_all(request).unwrap();
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+
+    String::from_utf8(response).unwrap()
+}
+
+#[test]
+fn test_error_context_sites() {
+    let (addr, shutdown) = start_app();
+
+    let not_allowed = exchange(&addr, b"DELETE /items HTTP/1.1\r\n\r\n");
+    assert!(not_allowed.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+    assert!(not_allowed.contains("\r\n\r\n405 | none | none | /items"));
+
+    let preflight = exchange(&addr, b"OPTIONS /missing HTTP/1.1\r\n\r\n");
+    assert!(preflight.contains("\r\n\r\n404 | none | none | /missing"));
+
+    // The limit is given with the reason it was reached
+    let websocket = exchange(
+        &addr,
+        b"GET /ws HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n",
+    );
+    assert!(websocket.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+    assert!(websocket
+        .contains("\r\n\r\n503 | LimitReached(\"0/0 workers held by WebSockets\") | none | /ws"));
+
+    // Requests which never arrive time out without a request
+    let timeout = exchange(&addr, b"");
+    assert!(timeout.starts_with("HTTP/1.1 408 Request Timeout\r\n"));
+    assert!(timeout.contains("\r\n\r\n408 | Request(Timeout) | none | none"));
+
+    shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_error_context_trace_id() {
+    let mut request = Request::builder().build();
+    let context = ErrorContext::new(StatusCode::NotFound).with_request(&request);
+    assert_eq!(context.trace_id(), None);
+
+    let trace = TraceContext::new(true);
+    let trace_id = trace.trace_id;
+    request.trace = Some(trace);
+
+    let context = ErrorContext::new(StatusCode::NotFound).with_request(&request);
+    assert_eq!(context.trace_id(), Some(trace_id));
+}
+
+#[test]
+fn test_panic_cause() {
+    let message = String::from("formatted");
+    let payloads = [
+        catch_unwind(|| panic!("static")).unwrap_err(),
+        catch_unwind(|| panic!("{}", message)).unwrap_err(),
+        catch_unwind(|| std::panic::panic_any(42)).unwrap_err(),
+    ];
+
+    let causes: Vec<ErrorCause> = payloads.into_iter().map(ErrorCause::panic).collect();
+
+    assert_eq!(
+        causes,
+        [
+            ErrorCause::Panic("static".into()),
+            ErrorCause::Panic("formatted".into()),
+            ErrorCause::Panic("<unknown>".into())
+        ]
+    );
+}
@@ -0,0 +1,81 @@
+use crate::app::ErrorHandler;
+use crate::http::{Request, Response, StatusCode};
+use crate::route::{handle_error, ErrorContext, SubApp};
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Builds a sub-app for `api.example.com` with its own error handler, and one for `example.com`
+///   without.
+fn subapps() -> Vec<SubApp<AtomicUsize>> {
+    let mut api = SubApp::new().with_error_handler(|status| Response::new(status, "api"));
+    api.host = "api.example.com".to_string();
+
+    let mut site = SubApp::new();
+    site.host = "example.com".to_string();
+
+    vec![api, site]
+}
+
+/// The app's error handler, which mentions the path of the request and counts the errors in the
+///   app's state.
+fn app_handler() -> ErrorHandler<AtomicUsize> {
+    let handler = |context: &ErrorContext, errors: Arc<AtomicUsize>| {
+        errors.fetch_add(1, Ordering::SeqCst);
+
+        let path = context
+            .request
+            .map_or("unknown", |request| request.uri.as_str());
+        Response::new(context.status_code, format!("app {}", path))
+    };
+
+    #[cfg(not(feature = "tokio"))]
+    return Box::new(handler);
+
+    #[cfg(feature = "tokio")]
+    return Arc::new(handler);
+}
+
+/// Builds a request for the given host and path.
+fn request(host: &str, path: &str) -> Request {
+    let mut request = Request::builder().header("Host", host).build();
+    request.uri = path.to_string();
+
+    request
+}
+
+#[test]
+fn test_error_handler_given_request_and_state() {
+    let subapps = subapps();
+    let handler = app_handler();
+    let errors = Arc::new(AtomicUsize::new(0));
+
+    let request = request("example.com", "/missing");
+    let context = ErrorContext::new(StatusCode::NotFound).with_request(&request);
+    let response = handle_error(&context, &subapps, &handler, &errors);
+
+    assert_eq!(response.status_code, StatusCode::NotFound);
+    assert_eq!(response.body, b"app /missing");
+
+    // Requests which could not be parsed are handled without one
+    let context = ErrorContext::new(StatusCode::BadRequest);
+    let response = handle_error(&context, &subapps, &handler, &errors);
+    assert_eq!(response.body, b"app unknown");
+
+    assert_eq!(errors.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_host_error_handler() {
+    let subapps = subapps();
+    let handler = app_handler();
+    let errors = Arc::new(AtomicUsize::new(0));
+
+    let request = request("api.example.com", "/missing");
+    let context = ErrorContext::new(StatusCode::NotFound).with_request(&request);
+    let response = handle_error(&context, &subapps, &handler, &errors);
+
+    assert_eq!(response.status_code, StatusCode::NotFound);
+    assert_eq!(response.body, b"api");
+    assert_eq!(errors.load(Ordering::SeqCst), 0);
+}
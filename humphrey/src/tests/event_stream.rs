@@ -0,0 +1,81 @@
+use crate::http::event_stream::{Event, StreamClosed};
+use crate::http::headers::HeaderType;
+use crate::http::response::Response;
+
+use std::time::Duration;
+
+#[test]
+fn test_event_serialisation() {
+    let event = Event::new("{\"price\": 42}")
+        .with_id("17")
+        .with_event("price")
+        .with_retry(Duration::from_secs(3));
+
+    assert_eq!(
+        event.to_bytes(),
+        b"id: 17\nevent: price\nretry: 3000\ndata: {\"price\": 42}\n\n"
+    );
+}
+
+#[test]
+fn test_event_data_lines() {
+    let event = Event::new("first\nsecond\r\nthird\rfourth\n");
+
+    assert_eq!(
+        event.to_bytes(),
+        b"data: first\ndata: second\ndata: third\ndata: fourth\ndata: \n\n"
+    );
+}
+
+#[test]
+fn test_event_empty_data() {
+    assert_eq!(Event::new("").to_bytes(), b"data: \n\n");
+}
+
+#[test]
+fn test_event_fields_single_line() {
+    let event = Event::new("data").with_id("1\n2").with_event("a\r\nb");
+
+    assert_eq!(event.to_bytes(), b"id: 12\nevent: ab\ndata: data\n\n");
+}
+
+#[test]
+fn test_event_stream_response() {
+    let (response, sender) = Response::event_stream();
+
+    assert!(response.is_streamed());
+    assert_eq!(
+        response.headers.get(HeaderType::ContentType),
+        Some("text/event-stream")
+    );
+    assert_eq!(
+        response.headers.get(HeaderType::CacheControl),
+        Some("no-cache")
+    );
+    assert_eq!(response.headers.get(HeaderType::Connection), Some("Close"));
+
+    drop(response);
+
+    assert_eq!(sender.send(Event::new("lost")), Err(StreamClosed));
+    assert_eq!(sender.comment("ping"), Err(StreamClosed));
+}
+
+#[cfg(not(feature = "tokio"))]
+#[test]
+fn test_event_stream_body() {
+    let (response, sender) = Response::event_stream();
+
+    sender.send(Event::new("one")).unwrap();
+    sender.comment("ping").unwrap();
+    sender.clone().send(Event::new("two").with_id("2")).unwrap();
+    drop(sender);
+
+    let bytes: Vec<u8> = response.into();
+    let text = String::from_utf8(bytes).unwrap();
+
+    assert!(text.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(text.contains("Transfer-Encoding: chunked\r\n"));
+    assert!(text.ends_with(
+        "\r\n\r\nb\r\ndata: one\n\n\r\n8\r\n: ping\n\n\r\n11\r\nid: 2\ndata: two\n\n\r\n0\r\n\r\n"
+    ));
+}
@@ -0,0 +1,147 @@
+use crate::file::{FileVersion, HeldFile};
+
+use std::fs::{create_dir_all, read_to_string, remove_file, rename, write};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+#[cfg(not(feature = "tokio"))]
+use crate::handlers::{serve_file, serve_held_file};
+#[cfg(not(feature = "tokio"))]
+use crate::http::{Response, StatusCode};
+#[cfg(not(feature = "tokio"))]
+use crate::shutdown::ShutdownSignal;
+#[cfg(not(feature = "tokio"))]
+use crate::App;
+#[cfg(not(feature = "tokio"))]
+use std::io::Write;
+#[cfg(not(feature = "tokio"))]
+use std::net::TcpStream;
+#[cfg(not(feature = "tokio"))]
+use std::thread::spawn;
+
+/// Creates a file with the given contents in a temporary directory for the test.
+fn file(name: &str, contents: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("humphrey-file-{}", std::process::id()));
+    create_dir_all(&directory).unwrap();
+
+    let path = directory.join(name);
+    write(&path, contents).unwrap();
+
+    path
+}
+
+/// Replaces the file at the path as a deploy would, by writing the new contents next to it and
+///   renaming them over it.
+fn replace(path: &Path, contents: &str) {
+    let new = path.with_extension("new");
+    write(&new, contents).unwrap();
+    rename(&new, path).unwrap();
+}
+
+fn read(file: &HeldFile) -> String {
+    String::from_utf8(file.read().unwrap()).unwrap()
+}
+
+#[test]
+fn test_file_version() {
+    let path = file("version.txt", "one");
+    let version = FileVersion::of(&path).unwrap();
+    assert_eq!(FileVersion::of(&path).unwrap(), version);
+
+    // Replacing the file is detected even if the contents are the same length
+    replace(&path, "two");
+    let replaced = FileVersion::of(&path).unwrap();
+    assert_ne!(replaced, version);
+
+    write(&path, "three").unwrap();
+    assert_ne!(FileVersion::of(&path).unwrap(), replaced);
+
+    remove_file(&path).unwrap();
+    assert_eq!(
+        FileVersion::of(&path).unwrap_err().kind(),
+        ErrorKind::NotFound
+    );
+}
+
+#[test]
+fn test_held_file_replaced() {
+    let path = file("held.txt", "one");
+    let held = HeldFile::open(&path).unwrap();
+    let clone = held.clone();
+    assert_eq!(read(&held), "one");
+    assert!(!held.is_stale());
+
+    // The held file keeps being served until it is reopened
+    replace(&path, "two");
+    assert_eq!(read(&held), "one");
+    assert!(held.is_stale());
+
+    held.reopen().unwrap();
+    assert_eq!(read(&clone), "two");
+    assert!(!clone.is_stale());
+    assert_eq!(clone.version(), FileVersion::of(&path).unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_held_file_removed() {
+    let path = file("removed.txt", "one");
+    let held = HeldFile::open(&path).unwrap();
+
+    // If the file cannot be reopened, the held file is still served
+    remove_file(&path).unwrap();
+    assert!(held.is_stale());
+    assert_eq!(held.reopen().unwrap_err().kind(), ErrorKind::NotFound);
+    assert_eq!(read(&held), "one");
+
+    // Unlike on Windows, a new file can be created at the path while the old one is still open
+    write(&path, "two").unwrap();
+    held.reopen().unwrap();
+    assert_eq!(read(&held), "two");
+    assert_eq!(read_to_string(&path).unwrap(), "two");
+}
+
+#[cfg(not(feature = "tokio"))]
+#[test]
+fn test_files_swapped_under_running_app() {
+    let path = file("served.txt", "one");
+    let held_path = file("hot.txt", "one");
+    let held = HeldFile::open(&held_path).unwrap();
+
+    let static_path: &'static str = Box::leak(path.to_string_lossy().into());
+    let shutdown = ShutdownSignal::new();
+    let app: App = App::new_with_config(2, ())
+        .with_route("/file", serve_file(static_path))
+        .with_route("/held", serve_held_file(held.clone()))
+        .with_shutdown(shutdown.clone());
+
+    let addr = crate::tests::free_addr();
+    let cloned_addr = addr.clone();
+    let thread = spawn(move || app.run(cloned_addr).unwrap());
+    crate::tests::wait_for(&addr);
+
+    let get = |uri: &str| {
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nConnection: Close\r\n\r\n", uri).unwrap();
+
+        Response::from_stream(&mut stream).unwrap()
+    };
+
+    // Files are opened for every request, so the new file is served by the very next request
+    assert_eq!(get("/file").body, b"one");
+    replace(&path, "two");
+    assert_eq!(get("/file").body, b"two");
+
+    remove_file(&path).unwrap();
+    assert_eq!(get("/file").status_code, StatusCode::NotFound);
+
+    // Held files are only swapped when they are reopened, for example by a deploy hook
+    assert_eq!(get("/held").body, b"one");
+    replace(&held_path, "two");
+    assert_eq!(get("/held").body, b"one");
+    held.reopen().unwrap();
+    assert_eq!(get("/held").body, b"two");
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
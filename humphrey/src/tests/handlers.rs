@@ -0,0 +1,547 @@
+use crate::cancellation::CancellationToken;
+use crate::file::FileVersion;
+use crate::handlers::{
+    serve_dir, serve_dir_with_hooks, serve_dir_with_options, serve_file, serve_file_with_hooks,
+    DirOptions, FileHooks,
+};
+use crate::http::address::Address;
+use crate::http::headers::{HeaderType, Headers};
+use crate::http::method::Method;
+use crate::http::mime::MimeType;
+use crate::http::request::BodyState;
+use crate::http::{Request, Response, StatusCode};
+use crate::monitor::event::EventType;
+use crate::monitor::MonitorConfig;
+use crate::route::RouteParams;
+
+use crate::tests::mock_stream::MockStream;
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::iter::FromIterator;
+use std::path::Path;
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+
+const DIRECTORY: &str = "./src/tests/testcases/files";
+
+fn request(uri: &str) -> Request {
+    Request {
+        method: Method::Get,
+        overridden_method: None,
+        uri: uri.into(),
+        uri_raw: uri.into(),
+        query: String::new(),
+        version: "HTTP/1.1".into(),
+        headers: Headers::new(),
+        content: None,
+        body_state: BodyState::Complete,
+        trailers: Headers::new(),
+        address: Address::new("127.0.0.1:1234").unwrap(),
+        secure: false,
+        local_addr: None,
+        deadline: None,
+        trace: None,
+        cancellation: CancellationToken::default(),
+        params: RouteParams::default(),
+    }
+}
+
+/// Parses a request for the given target, so that its path is decoded as it would be by the app.
+fn parse(target: &str) -> Request {
+    let bytes = format!("GET {} HTTP/1.1\r\n\r\n", target).into_bytes();
+    let mut stream = MockStream::with_data(VecDeque::from_iter(bytes));
+
+    Request::from_stream(&mut stream, "127.0.0.1:1234".parse().unwrap()).unwrap()
+}
+
+fn serve(options: DirOptions, uri: &str) -> Response {
+    let handler = serve_dir_with_options::<()>(DIRECTORY, options);
+    let mut response = handler(request(uri), Arc::new(()), "/*");
+    response.finalise_framing(&Method::Get);
+
+    response
+}
+
+#[test]
+fn test_mime_is_textual() {
+    for extension in ["html", "css", "js", "txt", "json"] {
+        let mime_type = MimeType::from_extension(extension);
+
+        assert!(mime_type.is_textual(), "{}", extension);
+        assert!(mime_type.to_content_type().ends_with("; charset=utf-8"));
+    }
+
+    for extension in ["png", "svg", "pdf", "woff2", "unknown"] {
+        let mime_type = MimeType::from_extension(extension);
+
+        assert!(!mime_type.is_textual(), "{}", extension);
+        assert_eq!(mime_type.to_content_type(), mime_type.to_string());
+    }
+
+    assert_eq!(
+        MimeType::TextHtml.to_content_type(),
+        "text/html; charset=utf-8"
+    );
+}
+
+#[test]
+fn test_serve_dir_charset() {
+    let handler = serve_dir::<()>(DIRECTORY);
+
+    let response = handler(request("/data.json"), Arc::new(()), "/*");
+    assert_eq!(
+        response.headers.get(HeaderType::ContentType),
+        Some("application/json; charset=utf-8")
+    );
+
+    let response = handler(request("/binary.png"), Arc::new(()), "/*");
+    assert_eq!(
+        response.headers.get(HeaderType::ContentType),
+        Some("image/png")
+    );
+
+    let response = serve(DirOptions::new().with_charset(false), "/data.json");
+    assert_eq!(
+        response.headers.get(HeaderType::ContentType),
+        Some("application/json")
+    );
+}
+
+#[test]
+fn test_serve_dir_bom() {
+    // The byte order mark is kept by default
+    let response = serve(DirOptions::default(), "/bom.html");
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body, "\u{feff}<h1>Café</h1>\n".as_bytes());
+    assert_eq!(response.headers.get(HeaderType::ContentLength), Some("18"));
+    assert_eq!(
+        response.headers.get(HeaderType::ContentType),
+        Some("text/html; charset=utf-8")
+    );
+
+    let options = DirOptions::new().with_strip_bom(true);
+
+    let response = serve(options, "/bom.html");
+    assert_eq!(response.body, "<h1>Café</h1>\n".as_bytes());
+    assert_eq!(response.headers.get(HeaderType::ContentLength), Some("15"));
+    assert_eq!(
+        response.headers.get(HeaderType::ContentType),
+        Some("text/html; charset=utf-8")
+    );
+
+    // Binary files and files without an extension are never changed and never have a charset
+    let response = serve(options, "/binary.png");
+    assert_eq!(response.body, b"\xEF\xBB\xBF\x00\x01\x02\x89PNG");
+    assert_eq!(response.headers.get(HeaderType::ContentLength), Some("10"));
+    assert_eq!(
+        response.headers.get(HeaderType::ContentType),
+        Some("image/png")
+    );
+
+    let response = serve(options, "/README");
+    assert_eq!(response.body, b"\xEF\xBB\xBFno extension\n");
+    assert_eq!(response.headers.get(HeaderType::ContentType), None);
+}
+
+#[test]
+fn test_serve_dir_streamed() {
+    let options = DirOptions::new().with_stream_threshold(Some(12));
+
+    // Files no larger than the threshold are read into memory
+    let response = serve(options, "/hello world.txt");
+    assert!(!response.is_streamed());
+
+    let response = serve(options, "/bom.html");
+    assert!(response.is_streamed());
+    assert_eq!(response.headers.get(HeaderType::ContentLength), Some("18"));
+    assert_eq!(
+        response.headers.get(HeaderType::ContentType),
+        Some("text/html; charset=utf-8")
+    );
+
+    let bytes: Vec<u8> = response.into();
+    assert!(bytes.ends_with("\r\n\r\n\u{feff}<h1>Café</h1>\n".as_bytes()));
+
+    // The byte order mark is removed from streamed files in the same way
+    let response = serve(options.with_strip_bom(true), "/bom.html");
+    assert_eq!(response.headers.get(HeaderType::ContentLength), Some("15"));
+
+    let bytes: Vec<u8> = response.into();
+    assert!(bytes.ends_with("\r\n\r\n<h1>Café</h1>\n".as_bytes()));
+
+    let response = serve(options.with_stream_threshold(None), "/bom.html");
+    assert!(!response.is_streamed());
+}
+
+#[test]
+fn test_serve_file_charset() {
+    let handler = serve_file::<()>("./src/tests/testcases/files/bom.html");
+    let response = handler(request("/"), Arc::new(()));
+
+    assert_eq!(response.body, "\u{feff}<h1>Café</h1>\n".as_bytes());
+    assert_eq!(
+        response.headers.get(HeaderType::ContentType),
+        Some("text/html; charset=utf-8")
+    );
+}
+
+#[test]
+fn test_serve_dir_decoded_paths() {
+    let handler = serve_dir::<()>(DIRECTORY);
+
+    for (target, body) in [
+        ("/hello%20world.txt", &b"hello world"[..]),
+        ("/100%25.txt", b"one hundred percent"),
+        ("/nested/secret.txt", b"nested"),
+        ("/%6Eested/secret.txt", b"nested"),
+    ] {
+        let response = handler(parse(target), Arc::new(()), "/*");
+
+        assert_eq!(response.status_code, StatusCode::OK, "{}", target);
+        assert_eq!(response.body, body, "{}", target);
+    }
+}
+
+#[test]
+fn test_serve_dir_traversal() {
+    let handler = serve_dir::<()>(DIRECTORY);
+
+    for target in [
+        "/nested%2Fsecret.txt",
+        "/nested%2fsecret.txt",
+        "/..%2Fembed/index.html",
+        "/%2E%2E/embed/index.html",
+        "/%2e%2e%2fembed/index.html",
+        "/nested/..%5C..%5Cembed/index.html",
+    ] {
+        let response = handler(parse(target), Arc::new(()), "/*");
+
+        assert_eq!(response.status_code, StatusCode::NotFound, "{}", target);
+    }
+
+    // Encoded null characters are rejected before the request reaches the handler
+    let bytes = b"GET /data.json%00 HTTP/1.1\r\n\r\n";
+    let mut stream = MockStream::with_data(VecDeque::from_iter(bytes.iter().cloned()));
+    assert!(Request::from_stream(&mut stream, "127.0.0.1:1234".parse().unwrap()).is_err());
+}
+
+#[test]
+fn test_serve_dir_registered_mime_type() {
+    MimeType::register("usdz", "model/vnd.usdz+zip");
+
+    let response = serve(DirOptions::default(), "/scene.usdz");
+
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(
+        response.headers.get(HeaderType::ContentType),
+        Some("model/vnd.usdz+zip")
+    );
+}
+
+#[test]
+fn test_mime_is_executable() {
+    for extension in ["html", "htm", "svg", "xml", "js"] {
+        assert!(
+            MimeType::from_extension(extension).is_executable(),
+            "{}",
+            extension
+        );
+    }
+
+    for extension in ["css", "txt", "json", "png", "pdf", "unknown"] {
+        assert!(
+            !MimeType::from_extension(extension).is_executable(),
+            "{}",
+            extension
+        );
+    }
+}
+
+/// Returns the `Content-Type`, `Content-Disposition` and `X-Content-Type-Options` headers of the
+///   response for the file.
+fn file_headers(
+    options: DirOptions,
+    uri: &str,
+) -> (Option<String>, Option<String>, Option<String>) {
+    let response = serve(options, uri);
+    assert_eq!(response.status_code, StatusCode::OK, "{}", uri);
+
+    let header = |name: HeaderType| response.headers.get(name).map(|value| value.to_string());
+
+    (
+        header(HeaderType::ContentType),
+        header(HeaderType::ContentDisposition),
+        header(HeaderType::from("X-Content-Type-Options")),
+    )
+}
+
+#[test]
+fn test_serve_dir_trusted_headers() {
+    let cases = [
+        ("/bom.html", Some("text/html; charset=utf-8")),
+        ("/image.svg", Some("image/svg+xml")),
+        ("/feed.xml", Some("application/xml; charset=utf-8")),
+        ("/binary.png", Some("image/png")),
+        ("/data.json", Some("application/json; charset=utf-8")),
+        ("/upload.dat", Some("application/octet-stream")),
+        ("/README", None),
+    ];
+
+    for (uri, content_type) in cases {
+        assert_eq!(
+            file_headers(DirOptions::default(), uri),
+            (
+                content_type.map(|s| s.to_string()),
+                None,
+                Some("nosniff".to_string())
+            ),
+            "{}",
+            uri
+        );
+    }
+}
+
+#[test]
+fn test_serve_dir_untrusted_headers() {
+    let options = DirOptions::new().with_untrusted_uploads(true);
+
+    // Files which could run scripts, or whose types are unknown, are downloaded instead of displayed
+    for uri in [
+        "/bom.html",
+        "/image.svg",
+        "/feed.xml",
+        "/upload.dat",
+        "/README",
+    ] {
+        assert_eq!(
+            file_headers(options, uri),
+            (
+                Some("application/octet-stream".to_string()),
+                Some("attachment".to_string()),
+                Some("nosniff".to_string())
+            ),
+            "{}",
+            uri
+        );
+    }
+
+    for (uri, content_type) in [
+        ("/binary.png", "image/png"),
+        ("/data.json", "application/json; charset=utf-8"),
+        ("/hello%20world.txt", "text/plain; charset=utf-8"),
+    ] {
+        assert_eq!(
+            file_headers(options, uri),
+            (
+                Some(content_type.to_string()),
+                None,
+                Some("nosniff".to_string())
+            ),
+            "{}",
+            uri
+        );
+    }
+
+    // The byte order mark of a downloaded file is kept, since it is not sent as text
+    let response = serve(options.with_strip_bom(true), "/bom.html");
+    assert_eq!(response.body, "\u{feff}<h1>Café</h1>\n".as_bytes());
+}
+
+#[test]
+fn test_serve_dir_without_nosniff() {
+    assert_eq!(
+        file_headers(DirOptions::new().with_nosniff(false), "/binary.png"),
+        (Some("image/png".to_string()), None, None)
+    );
+
+    assert_eq!(
+        file_headers(
+            DirOptions::new()
+                .with_nosniff(false)
+                .with_untrusted_uploads(true),
+            "/image.svg"
+        ),
+        (
+            Some("application/octet-stream".to_string()),
+            Some("attachment".to_string()),
+            None
+        )
+    );
+}
+
+#[test]
+fn test_serve_dir_untrusted_streamed() {
+    let options = DirOptions::new()
+        .with_untrusted_uploads(true)
+        .with_stream_threshold(Some(0));
+
+    let response = serve(options, "/image.svg");
+
+    assert!(response.streamed_body.is_some());
+    assert_eq!(
+        response.headers.get(HeaderType::ContentType),
+        Some("application/octet-stream")
+    );
+    assert_eq!(
+        response.headers.get(HeaderType::ContentDisposition),
+        Some("attachment")
+    );
+}
+
+/// Records the hooks which are called, and serves requests for `/cached` without the filesystem.
+#[derive(Default)]
+struct RecordingHooks {
+    calls: RefCell<Vec<String>>,
+}
+
+impl FileHooks for RecordingHooks {
+    fn lookup(&self, request: &Request) -> Option<Response> {
+        self.calls
+            .borrow_mut()
+            .push(format!("lookup {}", request.uri));
+
+        (request.uri == "/cached").then(|| Response::new(StatusCode::OK, "from cache"))
+    }
+
+    fn loaded(&self, _: &Request, path: &Path, version: FileVersion, response: &Response) {
+        assert_eq!(FileVersion::of(path).unwrap(), version);
+
+        self.calls.borrow_mut().push(format!(
+            "loaded {} {}",
+            path.file_name().unwrap().to_string_lossy(),
+            response.body.len()
+        ));
+    }
+
+    fn missing(&self, _: &Request, path: &Path) {
+        self.calls.borrow_mut().push(format!(
+            "missing {}",
+            path.file_name().unwrap().to_string_lossy()
+        ));
+    }
+
+    fn served(&self, _: &Request, response: &Response) {
+        self.calls
+            .borrow_mut()
+            .push(format!("served {}", u16::from(response.status_code)));
+    }
+
+    fn error(&self, _: &Request, status_code: StatusCode) -> Response {
+        Response::new(status_code, "custom error")
+    }
+}
+
+impl RecordingHooks {
+    fn take(&self) -> Vec<String> {
+        self.calls.take()
+    }
+}
+
+#[test]
+fn test_serve_dir_hooks() {
+    let hooks = RecordingHooks::default();
+    let serve = |uri: &str, options| {
+        serve_dir_with_hooks(&request(uri), DIRECTORY, "/static/*", options, &hooks)
+    };
+
+    let response = serve("/static/data.json", DirOptions::default());
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(
+        hooks.take(),
+        [
+            "lookup /static/data.json",
+            "loaded data.json 8",
+            "served 200"
+        ]
+    );
+
+    // Responses from the lookup hook are returned without using the filesystem or other hooks
+    let response = serve("/cached", DirOptions::default());
+    assert_eq!(response.body, b"from cache");
+    assert_eq!(hooks.take(), ["lookup /cached"]);
+
+    let response = serve("/static/nested", DirOptions::default());
+    assert_eq!(response.status_code, StatusCode::MovedPermanently);
+    assert_eq!(hooks.take(), ["lookup /static/nested", "served 301"]);
+
+    let response = serve("/static/missing", DirOptions::default());
+    assert_eq!(response.status_code, StatusCode::NotFound);
+    assert_eq!(response.body, b"custom error");
+    assert_eq!(hooks.take(), ["lookup /static/missing", "served 404"]);
+
+    // Streamed files are not passed to the loaded hook, since their contents are not in memory
+    let response = serve(
+        "/static/bom.html",
+        DirOptions::new().with_stream_threshold(Some(12)),
+    );
+    assert!(response.is_streamed());
+    assert_eq!(hooks.take(), ["lookup /static/bom.html", "served 200"]);
+}
+
+#[test]
+fn test_serve_file_hooks() {
+    let hooks = RecordingHooks::default();
+
+    let path = Path::new("./src/tests/testcases/files/hello world.txt");
+    let response = serve_file_with_hooks(&request("/hello"), path, DirOptions::default(), &hooks);
+    assert_eq!(response.body, b"hello world");
+    assert_eq!(
+        hooks.take(),
+        ["lookup /hello", "loaded hello world.txt 11", "served 200"]
+    );
+
+    let path = Path::new("./src/tests/testcases/files/missing.txt");
+    let response = serve_file_with_hooks(&request("/hello"), path, DirOptions::default(), &hooks);
+    assert_eq!(response.status_code, StatusCode::NotFound);
+    assert_eq!(response.body, b"custom error");
+    assert_eq!(
+        hooks.take(),
+        ["lookup /hello", "missing missing.txt", "served 404"]
+    );
+}
+
+#[test]
+fn test_serve_file_removed() {
+    let path = std::env::temp_dir().join(format!("humphrey-removed-{}.txt", std::process::id()));
+    std::fs::write(&path, "here").unwrap();
+
+    let (tx, rx) = channel();
+    let monitor = MonitorConfig::new(tx).with_subscription_to(EventType::FileMissing);
+    let serve = || serve_file_with_hooks(&request("/file"), &path, DirOptions::default(), &monitor);
+
+    assert_eq!(serve().body, b"here");
+    assert!(rx.try_recv().is_err());
+
+    // A file removed while the app is running is reported as not found, with an event
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(serve().status_code, StatusCode::NotFound);
+
+    let event = rx.try_recv().unwrap();
+    assert_eq!(event.kind, EventType::FileMissing);
+    assert_eq!(event.peer, Some("127.0.0.1:1234".parse().unwrap()));
+    assert!(event.info.unwrap().starts_with("/file ("));
+}
+
+#[test]
+fn test_serve_dir_redirect_encoded() {
+    let directory = std::env::temp_dir().join(format!("humphrey-redirect-{}", std::process::id()));
+    std::fs::create_dir_all(directory.join("a b\">")).unwrap();
+
+    // The location is built from the re-encoded path, never from the decoded one
+    let response = serve_dir_with_hooks(
+        &parse("/static/a%20b%22%3E"),
+        directory.to_str().unwrap(),
+        "/static/*",
+        DirOptions::default(),
+        &(),
+    );
+
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    assert_eq!(response.status_code, StatusCode::MovedPermanently);
+    assert_eq!(
+        response.headers.get(HeaderType::Location),
+        Some("/static/a%20b%22%3E/")
+    );
+}
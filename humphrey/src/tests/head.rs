@@ -0,0 +1,155 @@
+use crate::http::method::Method;
+use crate::http::{Request, Response, StatusCode};
+use crate::route::{handler_request, SubApp};
+use crate::App;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+
+/// A handler which responds with the given name, so that the route which handled a request can be
+///   identified from the response.
+fn named(name: &'static str) -> impl Fn(Request, Arc<()>) -> Response {
+    move |_, _| Response::new(StatusCode::OK, name)
+}
+
+#[test]
+fn test_handler_request() {
+    let request = Request::builder().method(Method::Head).uri("/").build();
+
+    let converted = handler_request(&request, "/", false);
+    assert_eq!(converted.method, Method::Get);
+    assert_eq!(converted.original_method(), &Method::Head);
+
+    let unchanged = handler_request(&request, "/", true);
+    assert_eq!(unchanged.method, Method::Head);
+    assert_eq!(unchanged.overridden_method, None);
+
+    let get = Request::builder().method(Method::Get).uri("/").build();
+    assert_eq!(handler_request(&get, "/", false).overridden_method, None);
+}
+
+#[test]
+fn test_resolve_head() {
+    let app: App<()> = App::new_with_config(2, ())
+        .with_named_route("files", "/files/*", named("files"))
+        .with_head_route("/files/*", named("files_head"))
+        .with_named_route("index", "/", named("index"));
+
+    // `HEAD` routes are checked first, even if they were added later
+    let head = app.resolve(&Method::Head, "/files/a.txt", None).unwrap();
+    assert_eq!(head.index, 1);
+
+    let get = app.resolve(&Method::Get, "/files/a.txt", None).unwrap();
+    assert_eq!(get.name, Some("files"));
+
+    // Without a `HEAD` route, the route which handles `GET` requests is used
+    let index = app.resolve(&Method::Head, "/", None).unwrap();
+    assert_eq!(index.name, Some("index"));
+
+    // `HEAD` routes never handle other methods
+    let subapp: SubApp<()> = SubApp::new().with_head_route("/*", named("head"));
+    assert!(subapp.resolve(&Method::Get, "/").is_none());
+    assert!(subapp.resolve(&Method::Head, "/").is_some());
+}
+
+/// Starts an app with routes which only respond to `GET` requests, one of which streams its body,
+///   and a route with its own `HEAD` handler, returning its address and shutdown signal.
+fn start_app() -> (String, Sender<()>) {
+    let (shutdown_tx, shutdown_rx) = channel();
+
+    let app: App<()> = App::new_with_config(2, ())
+        .with_stateless_route("/", |request: Request| match request.method {
+            Method::Get => Response::new(StatusCode::OK, "hello world")
+                .with_header("X-Original-Method", request.original_method().to_string()),
+            _ => Response::empty(StatusCode::MethodNotAllowed),
+        })
+        .with_stateless_route("/stream", |_| {
+            Response::from_reader(StatusCode::OK, &b"streamed body"[..], 13)
+        })
+        .with_head_route("/custom", |_, _| {
+            Response::empty(StatusCode::OK).with_header("X-Head-Handler", "true")
+        })
+        .with_stateless_route("/custom", |_| Response::new(StatusCode::OK, "custom"))
+        .with_shutdown(shutdown_rx);
+
+    let addr = crate::tests::start_app(app);
+
+    (addr, shutdown_tx)
+}
+
+/// Sends a request with the given method and path, returning every byte of the response.
+fn send(addr: &str, method: &str, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nConnection: Close\r\n\r\n",
+        method, path
+    )
+    .unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    response
+}
+
+/// Returns the lines of the response head other than `Date`, which changes between requests.
+fn head_lines(response: &str) -> Vec<&str> {
+    let (head, _) = response.split_once("\r\n\r\n").unwrap();
+
+    head.split("\r\n")
+        .filter(|line| !line.starts_with("Date: "))
+        .collect()
+}
+
+#[test]
+fn test_head_derived_from_get() {
+    let (addr, shutdown) = start_app();
+
+    let get = send(&addr, "GET", "/");
+    let head = send(&addr, "HEAD", "/");
+
+    // Only the head of the response is sent, ending with the blank line after the headers
+    assert!(get.contains("\r\n\r\nhello world"));
+    assert!(head.ends_with("\r\n\r\n"));
+    assert_eq!(head.len(), head.split_once("\r\n\r\n").unwrap().0.len() + 4);
+
+    assert_eq!(head_lines(&head)[0], "HTTP/1.1 200 OK");
+    assert!(head_lines(&head).contains(&"Content-Length: 11"));
+    assert!(head_lines(&head).contains(&"x-original-method: HEAD"));
+    assert!(head_lines(&get).contains(&"x-original-method: GET"));
+
+    shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_head_streamed_body() {
+    let (addr, shutdown) = start_app();
+
+    let get = send(&addr, "GET", "/stream");
+    let head = send(&addr, "HEAD", "/stream");
+
+    assert!(get.contains("\r\n\r\nstreamed body"));
+    assert!(head.ends_with("\r\n\r\n"));
+    assert_eq!(head_lines(&head), head_lines(&get));
+    assert!(head_lines(&head).contains(&"Content-Length: 13"));
+
+    shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_head_route_wins() {
+    let (addr, shutdown) = start_app();
+
+    let head = send(&addr, "HEAD", "/custom");
+    assert!(head_lines(&head).contains(&"x-head-handler: true"));
+    assert!(head_lines(&head).contains(&"Content-Length: 0"));
+
+    let get = send(&addr, "GET", "/custom");
+    assert!(get.contains("\r\n\r\ncustom"));
+    assert!(!head_lines(&get).contains(&"x-head-handler: true"));
+
+    shutdown.send(()).unwrap();
+}
@@ -0,0 +1,117 @@
+#![allow(unused_imports)]
+use crate::http::headers::HeaderType;
+use crate::http::{Request, Response, StatusCode};
+use crate::tests::mock_stream::MockStream;
+
+use std::collections::VecDeque;
+use std::iter::FromIterator;
+
+#[test]
+#[cfg(not(feature = "tokio"))]
+fn test_http_1_0_request_line() {
+    let test_data = b"GET /index.html HTTP/1.0\r\nUser-Agent: legacy\r\n\r\n";
+    let mut stream = MockStream::with_data(VecDeque::from_iter(test_data.iter().cloned()));
+    let request = Request::from_stream(&mut stream, "1.2.3.4:5678".parse().unwrap()).unwrap();
+
+    assert_eq!(request.version, "HTTP/1.0");
+    assert_eq!(request.uri, "/index.html");
+    assert_eq!(request.headers.get(HeaderType::Connection), None);
+}
+
+#[test]
+fn test_finalise_http_1_0() {
+    let mut response = Response::new(StatusCode::OK, "data")
+        .with_header(HeaderType::Connection, "Keep-Alive")
+        .with_header(HeaderType::TransferEncoding, "chunked");
+    response.trailers.add("Server-Timing", "total;dur=12");
+
+    response.finalise_http_1_0(false);
+
+    let bytes: Vec<u8> = response.into();
+    assert_eq!(
+        bytes,
+        b"HTTP/1.0 200 OK\r\nConnection: Close\r\nContent-Length: 4\r\n\r\ndata"
+    );
+
+    // Clients which ask for the connection to be kept open keep the handler's header
+    let mut response =
+        Response::empty(StatusCode::NoContent).with_header(HeaderType::Connection, "keep-alive");
+    response.finalise_http_1_0(true);
+
+    assert_eq!(response.version, "HTTP/1.0");
+    assert_eq!(
+        response.headers.get(HeaderType::Connection),
+        Some("keep-alive")
+    );
+    assert_eq!(response.headers.get(HeaderType::ContentLength), None);
+}
+
+/// Answers the request the way the app does for HTTP/1.0 clients, with a body and with a trailer
+///   depending on the path, returning the bytes written back.
+#[cfg(not(feature = "tokio"))]
+fn respond(request: &[u8]) -> Vec<u8> {
+    let mut stream = MockStream::with_data(VecDeque::from_iter(request.iter().cloned()));
+    let request = Request::from_stream(&mut stream, "1.2.3.4:5678".parse().unwrap()).unwrap();
+    let keep_alive = request
+        .headers
+        .get(HeaderType::Connection)
+        .is_some_and(|connection| connection.eq_ignore_ascii_case("keep-alive"));
+
+    let mut response = match request.uri.as_str() {
+        "/trailers" => {
+            let mut response = Response::new(StatusCode::OK, "timed");
+            response.trailers.add("Server-Timing", "total;dur=12");
+            response
+        }
+        _ => Response::new(StatusCode::OK, "hello"),
+    };
+
+    if let Some(connection) = request.headers.get(HeaderType::Connection) {
+        response.headers.add(HeaderType::Connection, connection);
+    }
+
+    response.finalise_framing(&request.method);
+    response.version = request.version.clone();
+    response.finalise_http_1_0(keep_alive);
+
+    response.into()
+}
+
+#[test]
+#[cfg(not(feature = "tokio"))]
+fn test_http_1_0_connection_close() {
+    let bytes = respond(b"GET / HTTP/1.0\r\n\r\n");
+
+    assert_eq!(
+        bytes,
+        b"HTTP/1.0 200 OK\r\nConnection: Close\r\nContent-Length: 5\r\n\r\nhello"
+    );
+
+    // Trailers cannot be sent without chunked framing, so they are dropped
+    let bytes = respond(b"GET /trailers HTTP/1.0\r\n\r\n");
+
+    assert_eq!(
+        bytes,
+        b"HTTP/1.0 200 OK\r\nConnection: Close\r\nContent-Length: 5\r\n\r\ntimed"
+    );
+}
+
+#[test]
+#[cfg(not(feature = "tokio"))]
+fn test_http_1_0_keep_alive() {
+    // Both requests are answered with the version echoed back, and nothing follows the body so the
+    //   next response on the connection starts straight after it
+    for (uri, body) in [("/", "hello"), ("/trailers", "timed")] {
+        let request = format!("GET {} HTTP/1.0\r\nConnection: keep-alive\r\n\r\n", uri);
+        let bytes = respond(request.as_bytes());
+
+        assert_eq!(
+            bytes,
+            format!(
+                "HTTP/1.0 200 OK\r\nConnection: keep-alive\r\nContent-Length: 5\r\n\r\n{}",
+                body
+            )
+            .as_bytes()
+        );
+    }
+}
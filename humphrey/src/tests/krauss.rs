@@ -1,4 +1,4 @@
-use crate::krauss::wildcard_match;
+use crate::krauss::{wildcard_captures, wildcard_match};
 
 #[test]
 fn test_match() {
@@ -34,3 +34,25 @@ fn test_match() {
     assert!(wildcard_match("", ""));
     assert!(wildcard_match("*", ""));
 }
+
+#[test]
+fn test_captures() {
+    assert_eq!(wildcard_captures("/about", "/about"), Some(vec![]));
+    assert_eq!(wildcard_captures("/about", "/contact"), None);
+
+    assert_eq!(wildcard_captures("/blog/*", "/blog/"), Some(vec![""]));
+    assert_eq!(
+        wildcard_captures("/blog/*", "/blog/2022/post"),
+        Some(vec!["2022/post"])
+    );
+    assert_eq!(
+        wildcard_captures("/users/*/posts/*", "/users/w-henderson/posts/1"),
+        Some(vec!["w-henderson", "1"])
+    );
+    assert_eq!(wildcard_captures("/*/*", "/a/b/c"), Some(vec!["a", "b/c"]));
+    assert_eq!(wildcard_captures("*.png", "/é.png"), Some(vec!["/é"]));
+    assert_eq!(
+        wildcard_captures("/users/*/posts", "/users/w-henderson"),
+        None
+    );
+}
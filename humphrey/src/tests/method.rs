@@ -7,8 +7,32 @@ fn test_from_name() {
     assert_eq!(Method::from_name("GET"), Ok(Method::Get));
     assert_eq!(Method::from_name("POST"), Ok(Method::Post));
     assert_eq!(Method::from_name("PUT"), Ok(Method::Put));
+    assert_eq!(Method::from_name("PATCH"), Ok(Method::Patch));
     assert_eq!(Method::from_name("DELETE"), Ok(Method::Delete));
-    assert_eq!(Method::from_name("get"), Err(RequestError::Request));
-    assert_eq!(Method::from_name("method"), Err(RequestError::Request));
+    assert_eq!(Method::from_name("HEAD"), Ok(Method::Head));
+    assert_eq!(Method::from_name("TRACE"), Ok(Method::Trace));
+    assert_eq!(Method::from_name("CONNECT"), Ok(Method::Connect));
+
+    // Any other token is a custom method, and names are case-sensitive
+    assert_eq!(Method::from_name("get"), Ok(Method::Custom("get".into())));
+    assert_eq!(
+        Method::from_name("PROPFIND"),
+        Ok(Method::Custom("PROPFIND".into()))
+    );
+    assert_eq!(
+        Method::from_name("X-Purge_1.0~"),
+        Ok(Method::Custom("X-Purge_1.0~".into()))
+    );
+
     assert_eq!(Method::from_name(""), Err(RequestError::Request));
+    assert_eq!(Method::from_name("GET POST"), Err(RequestError::Request));
+    assert_eq!(Method::from_name("GET/1"), Err(RequestError::Request));
+    assert_eq!(Method::from_name("MÉTHODE"), Err(RequestError::Request));
+}
+
+#[test]
+fn test_display() {
+    for name in ["GET", "PATCH", "TRACE", "CONNECT", "PROPFIND", "get"] {
+        assert_eq!(Method::from_name(name).unwrap().to_string(), name);
+    }
 }
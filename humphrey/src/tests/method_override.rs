@@ -0,0 +1,375 @@
+use crate::http::cors::Cors;
+use crate::http::headers::{HeaderType, Headers};
+use crate::http::method::{Method, MethodOverride};
+use crate::http::{Request, Response, StatusCode};
+use crate::route::{apply_method_override, SubApp};
+
+/// Builds a `POST` request to `/posts/1` on `example.com` with the given headers and body.
+fn request(headers: &[(&str, &str)], body: &str) -> Request {
+    let mut builder = Request::builder()
+        .method(Method::Post)
+        .uri("/posts/1")
+        .header(HeaderType::Host, "example.com");
+
+    for (name, value) in headers {
+        builder = builder.header(*name, value);
+    }
+
+    match body.is_empty() {
+        true => builder.build(),
+        false => builder.body(body).build(),
+    }
+}
+
+/// Builds a form request with the given body.
+fn form(body: &str) -> Request {
+    request(
+        &[("Content-Type", "application/x-www-form-urlencoded")],
+        body,
+    )
+}
+
+/// Builds a sub-app whose `/posts/*` route allows `https://app.example.com` to use `PUT` and `DELETE`.
+fn subapp() -> SubApp<()> {
+    SubApp::new()
+        .with_stateless_route("/posts/*", |_| Response::empty(StatusCode::OK))
+        .with_cors_config(
+            "/posts/*",
+            Cors::new()
+                .with_origin("https://app.example.com")
+                .with_method(Method::Put)
+                .with_method(Method::Delete)
+                .with_header(HeaderType::ContentType),
+        )
+}
+
+#[test]
+fn test_header_override() {
+    let header = MethodOverride::Header;
+
+    let request = request(&[("X-HTTP-Method-Override", "DELETE")], "");
+    assert_eq!(header.requested(&request), Some(Method::Delete));
+
+    // The method name is not case-sensitive
+    let request = self::request(&[("X-HTTP-Method-Override", "patch")], "");
+    assert_eq!(header.requested(&request), Some(Method::Patch));
+
+    let request = self::request(&[], "");
+    assert_eq!(header.requested(&request), None);
+}
+
+#[test]
+fn test_form_field_override() {
+    let field = MethodOverride::FormField("_method".into());
+
+    assert_eq!(
+        field.requested(&form("title=Hello+world&_method=PUT")),
+        Some(Method::Put)
+    );
+    assert_eq!(
+        field.requested(&form("%5Fmethod=delete")),
+        Some(Method::Delete)
+    );
+    assert_eq!(field.requested(&form("title=Hello")), None);
+
+    // Only form bodies are searched for the field
+    let request = request(&[("Content-Type", "text/plain")], "_method=PUT");
+    assert_eq!(field.requested(&request), None);
+
+    // The header is ignored when the override is taken from a form field
+    let request = self::request(&[("X-HTTP-Method-Override", "PUT")], "");
+    assert_eq!(field.requested(&request), None);
+}
+
+#[test]
+fn test_only_post_overridden() {
+    let mut request = request(&[("X-HTTP-Method-Override", "DELETE")], "");
+    request.method = Method::Get;
+
+    assert_eq!(MethodOverride::Header.requested(&request), None);
+}
+
+#[test]
+fn test_only_some_methods_allowed() {
+    for method in ["GET", "HEAD", "OPTIONS", "POST", "TRACE", ""] {
+        let request = request(&[("X-HTTP-Method-Override", method)], "");
+
+        assert_eq!(MethodOverride::Header.requested(&request), None);
+    }
+}
+
+#[test]
+fn test_apply_method_override() {
+    let subapp = subapp();
+    let mut request = request(&[("X-HTTP-Method-Override", "PATCH")], "");
+
+    apply_method_override(&mut request, &MethodOverride::Header, &[], &subapp);
+
+    assert_eq!(request.method, Method::Patch);
+    assert_eq!(request.original_method(), &Method::Post);
+    assert_eq!(request.overridden_method, Some(Method::Post));
+
+    // Requests which are not overridden keep their method
+    let mut request = self::request(&[], "");
+
+    apply_method_override(&mut request, &MethodOverride::Header, &[], &subapp);
+
+    assert_eq!(request.method, Method::Post);
+    assert_eq!(request.original_method(), &Method::Post);
+    assert_eq!(request.overridden_method, None);
+}
+
+#[test]
+fn test_cross_origin_override() {
+    let subapp = subapp();
+    let apply = |origin: &str, method: &str| {
+        let mut request = request(
+            &[("Origin", origin), ("X-HTTP-Method-Override", method)],
+            "",
+        );
+        apply_method_override(&mut request, &MethodOverride::Header, &[], &subapp);
+
+        request.method
+    };
+
+    // Requests from the same origin can use any method
+    assert_eq!(apply("http://example.com", "PATCH"), Method::Patch);
+    assert_eq!(apply("http://EXAMPLE.com", "PATCH"), Method::Patch);
+
+    // Requests from other origins can only use the methods which CORS allows them to use
+    assert_eq!(apply("https://app.example.com", "DELETE"), Method::Delete);
+    assert_eq!(apply("https://app.example.com", "PATCH"), Method::Post);
+    assert_eq!(apply("https://example.com", "DELETE"), Method::Post);
+    assert_eq!(apply("https://evil.example.org", "DELETE"), Method::Post);
+    assert_eq!(apply("null", "DELETE"), Method::Post);
+}
+
+#[test]
+fn test_cross_origin_override_without_cors() {
+    let subapp: SubApp<()> =
+        SubApp::new().with_stateless_route("/posts/*", |_| Response::empty(StatusCode::OK));
+
+    let mut request = request(
+        &[
+            ("Origin", "https://app.example.com"),
+            ("X-HTTP-Method-Override", "DELETE"),
+        ],
+        "",
+    );
+    apply_method_override(&mut request, &MethodOverride::Header, &[], &subapp);
+
+    assert_eq!(request.method, Method::Post);
+
+    // Wildcard CORS allows every origin to use every method
+    let subapp = subapp.with_cors(Cors::wildcard());
+    apply_method_override(&mut request, &MethodOverride::Header, &[], &subapp);
+
+    assert_eq!(request.method, Method::Delete);
+}
+
+#[test]
+fn test_preflight_allows_override_header() {
+    let cors = Cors::new()
+        .with_origin("https://app.example.com")
+        .with_method(Method::Put)
+        .with_method(Method::Delete)
+        .with_header(HeaderType::ContentType);
+
+    let mut headers = Headers::new();
+    cors.set_headers(&mut headers);
+    cors.allow_override_header(&mut headers);
+
+    assert_eq!(
+        headers.get(HeaderType::AccessControlAllowMethods),
+        Some("PUT, DELETE")
+    );
+    assert_eq!(
+        headers.get(HeaderType::AccessControlAllowHeaders),
+        Some("Content-Type, X-HTTP-Method-Override")
+    );
+
+    // The header is only added once
+    cors.allow_override_header(&mut headers);
+    assert_eq!(
+        headers.get(HeaderType::AccessControlAllowHeaders),
+        Some("Content-Type, X-HTTP-Method-Override")
+    );
+
+    // Wildcard headers already include it
+    let mut headers = Headers::new();
+    Cors::wildcard().set_headers(&mut headers);
+    Cors::wildcard().allow_override_header(&mut headers);
+    assert_eq!(
+        headers.get(HeaderType::AccessControlAllowHeaders),
+        Some("*")
+    );
+}
+
+#[test]
+fn test_preflight_without_overridable_methods() {
+    // No method which can be overridden to is allowed, so the header is not needed
+    let cors = Cors::new()
+        .with_origin("https://app.example.com")
+        .with_method(Method::Get)
+        .with_method(Method::Post);
+
+    let mut headers = Headers::new();
+    cors.set_headers(&mut headers);
+    cors.allow_override_header(&mut headers);
+
+    assert_eq!(headers.get(HeaderType::AccessControlAllowHeaders), None);
+
+    // Neither is it without any allowed origins
+    let cors = Cors::new().with_method(Method::Delete);
+
+    let mut headers = Headers::new();
+    cors.set_headers(&mut headers);
+    cors.allow_override_header(&mut headers);
+
+    assert_eq!(headers.get(HeaderType::AccessControlAllowHeaders), None);
+}
+
+mod app {
+    use crate::http::cors::Cors;
+    use crate::http::method::{Method, MethodOverride};
+    use crate::http::{Request, Response, StatusCode};
+    use crate::App;
+
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::sync::mpsc::{channel, Sender};
+
+    /// Starts an app whose handler responds with the method of the request and the method it was
+    ///   sent with, returning its address and shutdown signal.
+    fn start_app(method_override: Option<MethodOverride>) -> (String, Sender<()>) {
+        let (shutdown_tx, shutdown_rx) = channel();
+
+        let mut app: App<()> = App::new_with_config(2, ())
+            .with_stateless_route("/posts/*", |request: Request| {
+                let methods = format!("{} {}", request.method, request.original_method());
+
+                Response::new(StatusCode::OK, methods)
+            })
+            .with_cors_config(
+                "/posts/*",
+                Cors::new()
+                    .with_origin("https://app.example.com")
+                    .with_method(Method::Put)
+                    .with_method(Method::Delete),
+            )
+            .with_shutdown(shutdown_rx);
+
+        if let Some(method_override) = method_override {
+            app = app.with_method_override(method_override);
+        }
+
+        let addr = crate::tests::start_app(app);
+
+        (addr, shutdown_tx)
+    }
+
+    /// Sends the request, returning the lines of the response head and the body.
+    fn send(addr: &str, request: &str) -> (Vec<String>, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+
+        let head = head.split("\r\n").map(|line| line.to_string()).collect();
+
+        (head, body.trim_end().to_string())
+    }
+
+    #[test]
+    fn test_header_override() {
+        let (addr, shutdown) = start_app(Some(MethodOverride::Header));
+
+        let (_, body) = send(
+            &addr,
+            "POST /posts/1 HTTP/1.1\r\nHost: example.com\r\nX-HTTP-Method-Override: DELETE\r\n\r\n",
+        );
+        assert_eq!(body, "DELETE POST");
+
+        let (_, body) = send(
+            &addr,
+            "GET /posts/1 HTTP/1.1\r\nHost: example.com\r\nX-HTTP-Method-Override: DELETE\r\n\r\n",
+        );
+        assert_eq!(body, "GET GET");
+
+        shutdown.send(()).unwrap();
+    }
+
+    #[test]
+    fn test_form_field_override() {
+        let (addr, shutdown) = start_app(Some(MethodOverride::FormField("_method".into())));
+
+        let (_, body) = send(
+            &addr,
+            "POST /posts/1 HTTP/1.1\r\nHost: example.com\r\nContent-Type: application/x-www-form-urlencoded\r\nContent-Length: 23\r\n\r\ntitle=Hello&_method=PUT",
+        );
+        assert_eq!(body, "PUT POST");
+
+        shutdown.send(()).unwrap();
+    }
+
+    #[test]
+    fn test_override_disabled() {
+        let (addr, shutdown) = start_app(None);
+
+        let (_, body) = send(
+            &addr,
+            "POST /posts/1 HTTP/1.1\r\nHost: example.com\r\nX-HTTP-Method-Override: DELETE\r\n\r\n",
+        );
+        assert_eq!(body, "POST POST");
+
+        // The override header is not allowed in preflight responses either
+        let (head, _) = send(
+            &addr,
+            "OPTIONS /posts/1 HTTP/1.1\r\nHost: example.com\r\nOrigin: https://app.example.com\r\nAccess-Control-Request-Method: POST\r\nAccess-Control-Request-Headers: x-http-method-override\r\n\r\n",
+        );
+        assert!(!head
+            .iter()
+            .any(|line| line.contains("X-HTTP-Method-Override")));
+
+        shutdown.send(()).unwrap();
+    }
+
+    #[test]
+    fn test_cross_origin_override() {
+        let (addr, shutdown) = start_app(Some(MethodOverride::Header));
+
+        let (head, _) = send(
+            &addr,
+            "OPTIONS /posts/1 HTTP/1.1\r\nHost: example.com\r\nOrigin: https://app.example.com\r\nAccess-Control-Request-Method: POST\r\nAccess-Control-Request-Headers: x-http-method-override\r\n\r\n",
+        );
+        assert_eq!(head[0], "HTTP/1.1 204 No Content");
+        assert!(head.contains(&"Access-Control-Allow-Origin: https://app.example.com".to_string()));
+        assert!(head.contains(&"Access-Control-Allow-Methods: PUT, DELETE".to_string()));
+        assert!(head.contains(&"Access-Control-Allow-Headers: X-HTTP-Method-Override".to_string()));
+
+        let (head, body) = send(
+            &addr,
+            "POST /posts/1 HTTP/1.1\r\nHost: example.com\r\nOrigin: https://app.example.com\r\nX-HTTP-Method-Override: DELETE\r\n\r\n",
+        );
+        assert!(head.contains(&"Access-Control-Allow-Methods: PUT, DELETE".to_string()));
+        assert_eq!(body, "DELETE POST");
+
+        // Methods which the route does not allow the origin to use are not overridden to
+        let (_, body) = send(
+            &addr,
+            "POST /posts/1 HTTP/1.1\r\nHost: example.com\r\nOrigin: https://app.example.com\r\nX-HTTP-Method-Override: PATCH\r\n\r\n",
+        );
+        assert_eq!(body, "POST POST");
+
+        let (_, body) = send(
+            &addr,
+            "POST /posts/1 HTTP/1.1\r\nHost: example.com\r\nOrigin: https://evil.example.org\r\nX-HTTP-Method-Override: DELETE\r\n\r\n",
+        );
+        assert_eq!(body, "POST POST");
+
+        shutdown.send(()).unwrap();
+    }
+}
@@ -0,0 +1,136 @@
+use crate::http::{Request, Response, StatusCode};
+use crate::middleware::{
+    find_middleware, run_request_middleware, run_response_middleware, Middleware,
+};
+use crate::route::SubApp;
+
+use std::sync::{Arc, Mutex};
+
+/// Records the order in which the sides of each middleware were run.
+type Log = Mutex<Vec<String>>;
+
+/// Builds a middleware which logs both of its sides under the given name.
+fn logged(name: &'static str) -> Middleware<Log> {
+    Middleware::new()
+        .with_request(move |_, log: Arc<Log>| {
+            log.lock().unwrap().push(format!("request {}", name));
+            None
+        })
+        .with_response(move |_, _, log: Arc<Log>| {
+            log.lock().unwrap().push(format!("response {}", name));
+        })
+}
+
+/// Builds a default sub-app and a sub-app for `api.example.com`, each with logged middleware.
+fn subapps() -> (Vec<SubApp<Log>>, SubApp<Log>) {
+    let mut api = SubApp::new().with_middleware(logged("api"));
+    api.host = "api.example.com".to_string();
+
+    let default_subapp = SubApp::new()
+        .with_middleware(logged("first"))
+        .with_middleware(logged("second"));
+
+    (vec![api], default_subapp)
+}
+
+/// Runs both sides of the middleware for a request to the given host, as the app does, and
+///   returns the log.
+fn run(subapps: &[SubApp<Log>], default_subapp: &SubApp<Log>, host: Option<&str>) -> Vec<String> {
+    let state: Arc<Log> = Arc::default();
+    let middleware = find_middleware(subapps, default_subapp, host);
+
+    let mut request = Request::builder().build();
+    let (ran, response) = run_request_middleware(&middleware, &mut request, &state);
+    let mut response = response.unwrap_or_else(|| Response::empty(StatusCode::OK));
+    run_response_middleware(&middleware[..ran], &mut response, &request, &state);
+
+    let log = state.lock().unwrap();
+    log.clone()
+}
+
+#[test]
+fn test_middleware_order() {
+    let (subapps, default_subapp) = subapps();
+
+    assert_eq!(
+        run(&subapps, &default_subapp, None),
+        vec![
+            "request first",
+            "request second",
+            "response second",
+            "response first"
+        ]
+    );
+}
+
+#[test]
+fn test_host_middleware() {
+    let (subapps, default_subapp) = subapps();
+
+    // The host's middleware runs inside that of the app
+    assert_eq!(
+        run(&subapps, &default_subapp, Some("api.example.com")),
+        vec![
+            "request first",
+            "request second",
+            "request api",
+            "response api",
+            "response second",
+            "response first"
+        ]
+    );
+
+    assert_eq!(run(&subapps, &default_subapp, Some("example.com")).len(), 4);
+}
+
+#[test]
+fn test_middleware_short_circuit() {
+    let (subapps, default_subapp) = subapps();
+    let default_subapp = default_subapp.with_middleware(
+        Middleware::new().with_request(|_, _| Some(Response::empty(StatusCode::Forbidden))),
+    );
+
+    // Only the middleware which ran before the request was answered see the response
+    assert_eq!(
+        run(&subapps, &default_subapp, Some("api.example.com")),
+        vec![
+            "request first",
+            "request second",
+            "response second",
+            "response first"
+        ]
+    );
+}
+
+#[test]
+fn test_middleware_modifies_request_and_response() {
+    let state: Arc<()> = Arc::new(());
+    let default_subapp: SubApp<()> = SubApp::new().with_middleware(
+        Middleware::new()
+            .with_request(|request, _| {
+                request.headers.add("X-User", "ferris");
+                None
+            })
+            .with_response(|response, request, _| {
+                let user = request
+                    .headers
+                    .get("X-User")
+                    .unwrap_or("nobody")
+                    .to_string();
+                response.headers.add("X-Served-To", user);
+            }),
+    );
+
+    let middleware = find_middleware(&[], &default_subapp, None);
+    let mut request = Request::builder().build();
+    let (ran, rejected) = run_request_middleware(&middleware, &mut request, &state);
+
+    assert_eq!(ran, 1);
+    assert!(rejected.is_none());
+    assert_eq!(request.headers.get("X-User"), Some("ferris"));
+
+    let mut response = Response::empty(StatusCode::OK);
+    run_response_middleware(&middleware[..ran], &mut response, &request, &state);
+
+    assert_eq!(response.headers.get("X-Served-To"), Some("ferris"));
+}
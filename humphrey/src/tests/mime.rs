@@ -0,0 +1,66 @@
+use crate::http::mime::MimeType;
+
+#[test]
+fn test_builtin_mime_types() {
+    for (extension, mime_type) in [
+        ("wasm", "application/wasm"),
+        ("webmanifest", "application/manifest+json"),
+        ("avif", "image/avif"),
+        ("gltf", "model/gltf+json"),
+        ("glb", "model/gltf-binary"),
+        ("mp3", "audio/mpeg"),
+        ("csv", "text/csv"),
+    ] {
+        assert_eq!(
+            MimeType::from_extension(extension).to_string(),
+            mime_type,
+            "{}",
+            extension
+        );
+    }
+
+    assert_eq!(
+        MimeType::ApplicationManifest.to_content_type(),
+        "application/manifest+json; charset=utf-8"
+    );
+    assert_eq!(
+        MimeType::ApplicationWasm.to_content_type(),
+        "application/wasm"
+    );
+}
+
+#[test]
+fn test_register_mime_type() {
+    assert_eq!(
+        MimeType::from_extension("x-test-scene"),
+        MimeType::ApplicationOctetStream
+    );
+
+    MimeType::register("x-test-scene", "model/vnd.test+json");
+
+    let mime_type = MimeType::from_extension("x-test-scene");
+    assert_eq!(mime_type, MimeType::Custom("model/vnd.test+json"));
+    assert_eq!(
+        mime_type.to_content_type(),
+        "model/vnd.test+json; charset=utf-8"
+    );
+
+    MimeType::register("x-test-scene", "model/vnd.test");
+
+    let mime_type = MimeType::from_extension("x-test-scene");
+    assert_eq!(mime_type.to_string(), "model/vnd.test");
+    assert_eq!(mime_type.to_content_type(), "model/vnd.test");
+}
+
+#[test]
+fn test_register_overrides_builtin() {
+    // `bmp` is not used by any other test, since the registry is shared between them
+    assert_eq!(MimeType::from_extension("bmp"), MimeType::ImageBmp);
+
+    MimeType::register("bmp", "image/x-ms-bmp");
+
+    assert_eq!(
+        MimeType::from_extension("bmp"),
+        MimeType::Custom("image/x-ms-bmp")
+    );
+}
@@ -1,26 +1,58 @@
 #![allow(dead_code)]
 
 use std::collections::VecDeque;
-use std::io::Read;
+use std::io::{ErrorKind, Read};
 
 #[cfg(feature = "tokio")]
 use tokio::io::AsyncRead;
 
 pub struct MockStream {
     data: VecDeque<u8>,
+    error: Option<ErrorKind>,
+    read_size: usize,
 }
 
 impl MockStream {
     pub fn with_data(data: VecDeque<u8>) -> Self {
-        Self { data }
+        Self {
+            data,
+            error: None,
+            read_size: usize::MAX,
+        }
+    }
+
+    /// Creates a stream which returns at most `read_size` bytes from each read, so that the data
+    ///   arrives split across many reads.
+    pub fn with_data_in_reads(data: VecDeque<u8>, read_size: usize) -> Self {
+        Self {
+            data,
+            error: None,
+            read_size,
+        }
+    }
+
+    /// Creates a stream which returns an error of the given kind once the data has been read,
+    ///   instead of reaching the end of the stream.
+    pub fn with_data_then_error(data: VecDeque<u8>, error: ErrorKind) -> Self {
+        Self {
+            data,
+            error: Some(error),
+            read_size: usize::MAX,
+        }
     }
 }
 
 impl Read for MockStream {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if let (true, Some(error)) = (self.data.is_empty(), self.error) {
+            return Err(error.into());
+        }
+
         let mut bytes_written: usize = 0;
 
-        for byte in buf {
+        let read_size = self.read_size;
+
+        for byte in buf.iter_mut().take(read_size) {
             if let Some(new_byte) = self.data.pop_front() {
                 *byte = new_byte;
                 bytes_written += 1;
@@ -40,9 +72,15 @@ impl AsyncRead for MockStream {
         _cx: &mut std::task::Context<'_>,
         buf: &mut tokio::io::ReadBuf<'_>,
     ) -> std::task::Poll<std::io::Result<()>> {
+        if let (true, Some(error)) = (self.data.is_empty(), self.error) {
+            return std::task::Poll::Ready(Err(error.into()));
+        }
+
         let mut bytes_written: usize = 0;
 
-        for byte in buf.initialize_unfilled() {
+        let read_size = self.read_size;
+
+        for byte in buf.initialize_unfilled().iter_mut().take(read_size) {
             if let Some(new_byte) = self.data.pop_front() {
                 *byte = new_byte;
                 bytes_written += 1;
@@ -4,11 +4,134 @@ pub mod request;
 #[cfg(feature = "tokio")]
 pub mod request_tokio;
 
+pub mod accept;
+pub mod cancellation;
 pub mod client;
+pub mod client_script;
+pub mod clock;
+pub mod compile_fail;
+pub mod conditional;
+pub mod connection_limit;
+pub mod context;
 pub mod date;
+pub mod deadline;
+pub mod default_headers;
+pub mod drain;
+pub mod embed;
+#[cfg(feature = "error")]
+pub mod error;
+#[cfg(not(feature = "tokio"))]
+pub mod error_context;
+pub mod error_handler;
+pub mod event_stream;
+pub mod file;
+#[cfg(not(feature = "tokio"))]
+pub mod handlers;
+#[cfg(not(feature = "tokio"))]
+pub mod head;
+pub mod http_1_0;
 pub mod krauss;
+pub mod middleware;
 pub mod method;
+#[cfg(not(feature = "tokio"))]
+pub mod method_override;
+pub mod mime;
 pub mod mock_stream;
+pub mod monitor;
+pub mod multipart;
+pub mod parity;
 pub mod percent;
+pub mod proxy;
+pub mod proxy_protocol;
+pub mod range;
+pub mod rate_limit;
+#[cfg(not(feature = "tokio"))]
+pub mod recording;
+#[cfg(not(feature = "tokio"))]
+pub mod request_condition;
+pub mod request_body;
+pub mod request_builder;
 pub mod response;
+#[cfg(not(feature = "tokio"))]
+pub mod route;
+pub mod route_index;
+#[cfg(not(feature = "tokio"))]
+pub mod route_method;
+pub mod route_params;
+#[cfg(not(feature = "tokio"))]
+pub mod security;
+pub mod shutdown;
+#[cfg(not(feature = "tokio"))]
+pub mod slow_request;
 pub mod status;
+#[cfg(not(feature = "tokio"))]
+pub mod streamed_response;
+#[cfg(feature = "tls")]
+pub mod tls;
+#[cfg(feature = "tower-compat")]
+pub mod tower;
+pub mod trace;
+pub mod url;
+#[cfg(not(feature = "tokio"))]
+pub mod websocket_limit;
+
+use crate::App;
+
+use std::net::{TcpListener, TcpStream};
+use std::thread::{sleep, spawn};
+use std::time::Duration;
+
+/// Returns an address on the loopback interface with a port which is not in use.
+pub fn free_addr() -> String {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .to_string()
+}
+
+/// Waits for up to half a second until something is listening at the address.
+pub fn wait_for(addr: &str) {
+    for _ in 0..50 {
+        if TcpStream::connect(addr).is_ok() {
+            break;
+        }
+
+        sleep(Duration::from_millis(10));
+    }
+}
+
+/// Runs the app on a free port in the background, returning its address once it is listening.
+#[cfg(not(feature = "tokio"))]
+pub fn start_app<State>(app: App<State>) -> String
+where
+    State: Send + Sync + 'static,
+{
+    let addr = free_addr();
+    let cloned_addr = addr.clone();
+    spawn(move || app.run(cloned_addr).unwrap());
+    wait_for(&addr);
+
+    addr
+}
+
+/// Runs the app on a free port in the background, returning its address once it is listening.
+#[cfg(feature = "tokio")]
+pub fn start_app<State>(app: App<State>) -> String
+where
+    State: Send + Sync + 'static,
+{
+    let addr = free_addr();
+    let cloned_addr = addr.clone();
+    spawn(move || {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(app.run(cloned_addr))
+            .unwrap()
+    });
+    wait_for(&addr);
+
+    addr
+}
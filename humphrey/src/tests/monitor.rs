@@ -0,0 +1,67 @@
+use crate::monitor::event::{Event, EventLevel, EventType};
+use crate::monitor::MonitorConfig;
+
+use std::cell::Cell;
+use std::sync::mpsc::channel;
+
+#[test]
+fn test_send_with() {
+    let (tx, rx) = channel();
+    let monitor = MonitorConfig::new(tx).with_subscription_to(EventType::RequestServedSuccess);
+
+    monitor.send_with(EventType::RequestServedSuccess, |event| {
+        event
+            .with_peer("127.0.0.1:8080")
+            .with_info(format!("200 OK {}", "/"))
+    });
+
+    let event = rx.try_recv().unwrap();
+    assert_eq!(event.kind, EventType::RequestServedSuccess);
+    assert_eq!(event.peer, Some("127.0.0.1:8080".parse().unwrap()));
+    assert_eq!(event.info.as_deref(), Some("200 OK /"));
+}
+
+#[test]
+fn test_send_with_unsubscribed() {
+    let built = Cell::new(false);
+    let build = |event: Event| {
+        built.set(true);
+        event
+    };
+
+    // Events are only built if they would be sent
+    let (tx, rx) = channel();
+    let monitor = MonitorConfig::new(tx).with_subscription_to(EventLevel::Error);
+    monitor.send_with(EventType::RequestServedSuccess, build);
+    assert!(!built.get());
+    assert!(rx.try_recv().is_err());
+
+    let unconfigured = MonitorConfig::default().with_subscription_to(EventLevel::Debug);
+    unconfigured.send_with(EventType::RequestServedSuccess, build);
+    assert!(!built.get());
+}
+
+#[test]
+fn test_is_subscribed_to() {
+    let (tx, _rx) = channel();
+    let monitor = MonitorConfig::new(tx).with_subscription_to(EventLevel::Warning);
+
+    assert!(monitor.is_subscribed_to(EventType::ThreadPoolOverload));
+    assert!(!monitor.is_subscribed_to(EventType::RequestServedSuccess));
+
+    // A monitor without a receiver is never subscribed
+    let unconfigured = MonitorConfig::default().with_subscription_to(EventLevel::Debug);
+    assert!(!unconfigured.is_subscribed_to(EventType::ThreadPoolOverload));
+}
+
+#[test]
+fn test_send() {
+    let (tx, rx) = channel();
+    let monitor = MonitorConfig::new(tx).with_subscription_to(EventType::ConnectionClosed);
+
+    monitor.send(Event::new(EventType::ConnectionClosed).with_info("closed"));
+    monitor.send(EventType::ConnectionSuccess);
+
+    assert_eq!(rx.try_recv().unwrap().info.as_deref(), Some("closed"));
+    assert!(rx.try_recv().is_err());
+}
@@ -0,0 +1,453 @@
+use crate::http::headers::HeaderType;
+use crate::http::method::Method;
+use crate::http::multipart::{MultipartError, MultipartLimits, MultipartReader};
+use crate::http::Request;
+use crate::tests::mock_stream::MockStream;
+
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read};
+use std::iter::FromIterator;
+
+const BOUNDARY: &str = "----humphrey-boundary";
+
+/// Builds a multipart body from `(name, filename, content)` parts.
+fn body(parts: &[(&str, Option<&str>, &[u8])]) -> Vec<u8> {
+    let mut body = b"preamble to be ignored\r\n".to_vec();
+
+    for (name, filename, content) in parts {
+        body.extend(format!("--{}\r\n", BOUNDARY).as_bytes());
+
+        match filename {
+            Some(filename) => body.extend(
+                format!(
+                    "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\nContent-Type: application/octet-stream\r\n\r\n",
+                    name, filename
+                )
+                .as_bytes(),
+            ),
+            None => body.extend(
+                format!("Content-Disposition: form-data; name=\"{}\"\r\n\r\n", name).as_bytes(),
+            ),
+        }
+
+        body.extend(*content);
+        body.extend(b"\r\n");
+    }
+
+    body.extend(format!("--{}--\r\nepilogue to be ignored", BOUNDARY).as_bytes());
+    body
+}
+
+fn reader(body: Vec<u8>, read_size: usize, limits: MultipartLimits) -> MultipartReader<MockStream> {
+    let stream = MockStream::with_data_in_reads(VecDeque::from_iter(body), read_size);
+    MultipartReader::new(stream, BOUNDARY, limits)
+}
+
+/// A file whose content contains partial delimiters, so that they can be split across reads.
+fn file_content() -> Vec<u8> {
+    let mut content = Vec::new();
+
+    for i in 0..500 {
+        content.extend(format!("{} \r\n--{} \r\n-", i, &BOUNDARY[..i % BOUNDARY.len()]).as_bytes());
+    }
+
+    content
+}
+
+#[test]
+fn test_multipart_parts() {
+    let file = file_content();
+    let body = body(&[
+        ("title", None, b"Holiday photos"),
+        ("empty", None, b""),
+        ("upload", Some("photos.zip"), &file),
+    ]);
+
+    for read_size in [1, 2, 3, 7, 64, 4096, usize::MAX] {
+        let mut multipart = reader(body.clone(), read_size, MultipartLimits::default());
+
+        let mut part = multipart.next_part().unwrap().unwrap();
+        let mut title = String::new();
+        part.read_to_string(&mut title).unwrap();
+        assert_eq!(part.name, "title");
+        assert_eq!(part.filename, None);
+        assert_eq!(part.content_type, None);
+        assert_eq!(title, "Holiday photos");
+
+        let mut part = multipart.next_part().unwrap().unwrap();
+        let mut empty = Vec::new();
+        part.read_to_end(&mut empty).unwrap();
+        assert_eq!(part.name, "empty");
+        assert!(empty.is_empty());
+
+        let mut part = multipart.next_part().unwrap().unwrap();
+        let mut upload = Vec::new();
+        part.read_to_end(&mut upload).unwrap();
+        assert_eq!(part.name, "upload");
+        assert_eq!(part.filename.as_deref(), Some("photos.zip"));
+        assert_eq!(
+            part.content_type.as_deref(),
+            Some("application/octet-stream")
+        );
+        assert_eq!(
+            part.headers.get("Content-Disposition"),
+            Some("form-data; name=\"upload\"; filename=\"photos.zip\"")
+        );
+        assert_eq!(upload, file);
+
+        assert!(multipart.next_part().unwrap().is_none());
+        assert!(multipart.next_part().unwrap().is_none());
+    }
+}
+
+#[test]
+fn test_multipart_skip() {
+    let file = file_content();
+    let body = body(&[
+        ("upload", Some("photos.zip"), &file),
+        ("ignored", None, b"value"),
+        ("title", None, b"Holiday photos"),
+    ]);
+
+    let mut multipart = reader(body, 5, MultipartLimits::default());
+
+    let mut part = multipart.next_part().unwrap().unwrap();
+    let mut start = [0; 10];
+    part.read_exact(&mut start).unwrap();
+    assert_eq!(part.skip(), Ok(file.len() as u64 - 10));
+
+    let part = multipart.next_part().unwrap().unwrap();
+    assert_eq!(part.name, "ignored");
+
+    // The part was neither read nor skipped
+    assert_eq!(
+        multipart.next_part().err(),
+        Some(MultipartError::PartNotFinished)
+    );
+    assert_eq!(multipart.skip_part(), Ok(5));
+
+    let mut part = multipart.next_part().unwrap().unwrap();
+    let mut title = String::new();
+    part.read_to_string(&mut title).unwrap();
+    assert_eq!(title, "Holiday photos");
+
+    assert!(multipart.next_part().unwrap().is_none());
+}
+
+#[test]
+fn test_multipart_limits() {
+    let limits = MultipartLimits {
+        max_field_size: Some(4),
+        max_file_size: Some(8),
+        max_total_size: Some(14),
+    };
+
+    // Limits which are reached exactly are not exceeded
+    let body_at_limits = body(&[
+        ("field", None, b"1234"),
+        ("file", Some("a.txt"), b"12345678"),
+        ("other", None, b"12"),
+    ]);
+    let mut multipart = reader(body_at_limits, 3, limits);
+    while let Some(part) = multipart.next_part().unwrap() {
+        part.skip().unwrap();
+    }
+
+    let field_too_large = body(&[("field", None, b"12345")]);
+    let mut multipart = reader(field_too_large, 3, limits);
+    let mut part = multipart.next_part().unwrap().unwrap();
+    let error = part.read_to_end(&mut Vec::new()).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+    assert_eq!(
+        error.into_inner().unwrap().downcast_ref::<MultipartError>(),
+        Some(&MultipartError::PartTooLarge {
+            name: "field".into(),
+            limit: 4
+        })
+    );
+
+    // The error is returned again rather than continuing with the next part
+    assert_eq!(
+        multipart.next_part().err(),
+        Some(MultipartError::PartTooLarge {
+            name: "field".into(),
+            limit: 4
+        })
+    );
+
+    let file_too_large = body(&[("file", Some("a.txt"), b"123456789")]);
+    let mut multipart = reader(file_too_large, 3, limits);
+    let part = multipart.next_part().unwrap().unwrap();
+    assert_eq!(
+        part.skip(),
+        Err(MultipartError::PartTooLarge {
+            name: "file".into(),
+            limit: 8
+        })
+    );
+
+    let total_too_large = body(&[
+        ("file", Some("a.txt"), b"12345678"),
+        ("one", None, b"123"),
+        ("two", None, b"123"),
+        ("three", None, b"123"),
+    ]);
+    let mut multipart = reader(total_too_large, 3, limits);
+    multipart.next_part().unwrap().unwrap().skip().unwrap();
+    multipart.next_part().unwrap().unwrap().skip().unwrap();
+    multipart.next_part().unwrap().unwrap().skip().unwrap();
+    let part = multipart.next_part().unwrap().unwrap();
+    assert_eq!(
+        part.skip(),
+        Err(MultipartError::TotalTooLarge { limit: 14 })
+    );
+}
+
+#[test]
+fn test_multipart_malformed() {
+    let limits = MultipartLimits::default();
+
+    let no_boundary = b"no boundary here".to_vec();
+    let mut multipart = reader(no_boundary, 4, limits);
+    assert_eq!(multipart.next_part().err(), Some(MultipartError::Malformed));
+
+    let no_name = format!(
+        "--{0}\r\nContent-Disposition: form-data\r\n\r\nvalue\r\n--{0}--",
+        BOUNDARY
+    );
+    let mut multipart = reader(no_name.into_bytes(), 4, limits);
+    assert_eq!(multipart.next_part().err(), Some(MultipartError::Malformed));
+
+    let mut truncated = body(&[("field", None, b"value")]);
+    truncated.truncate(truncated.len() - 40);
+    let mut multipart = reader(truncated, 4, limits);
+    let part = multipart.next_part().unwrap().unwrap();
+    assert_eq!(part.skip(), Err(MultipartError::Malformed));
+
+    let stream = MockStream::with_data_then_error(
+        VecDeque::from_iter(format!("--{}\r\n", BOUNDARY).into_bytes()),
+        ErrorKind::ConnectionReset,
+    );
+    let mut multipart = MultipartReader::new(stream, BOUNDARY, limits);
+    assert_eq!(
+        multipart.next_part().err(),
+        Some(MultipartError::Io(ErrorKind::ConnectionReset))
+    );
+}
+
+#[test]
+fn test_multipart_from_request() {
+    let body = body(&[("field", None, b"value")]);
+
+    let request = Request::builder()
+        .method(Method::Post)
+        .uri("/upload")
+        .header(
+            HeaderType::ContentType,
+            format!("multipart/form-data; boundary=\"{}\"", BOUNDARY),
+        )
+        .body(body)
+        .build();
+
+    let mut multipart =
+        MultipartReader::from_request(&request, MultipartLimits::default()).unwrap();
+    let mut part = multipart.next_part().unwrap().unwrap();
+    let mut value = String::new();
+    part.read_to_string(&mut value).unwrap();
+    assert_eq!(value, "value");
+    assert!(multipart.next_part().unwrap().is_none());
+
+    let request = Request::builder()
+        .method(Method::Post)
+        .header(HeaderType::ContentType, "application/x-www-form-urlencoded")
+        .body("field=value")
+        .build();
+    assert_eq!(
+        MultipartReader::from_request(&request, MultipartLimits::default()).err(),
+        Some(MultipartError::NotMultipart)
+    );
+}
+
+#[cfg(not(feature = "tokio"))]
+mod app {
+    use super::{body, file_content, BOUNDARY};
+    use crate::http::multipart::{MultipartError, MultipartLimits, MultipartReader};
+    use crate::http::{Request, Response, StatusCode};
+    use crate::App;
+
+    use std::fs::File;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpStream;
+    use std::path::PathBuf;
+    use std::sync::mpsc::channel;
+    use std::thread::spawn;
+
+    /// Saves file parts to the upload directory in the app state, reporting the progress of each
+    ///   upload, and returns the text fields and uploads in the response.
+    fn upload_handler(request: Request, directory: std::sync::Arc<PathBuf>) -> Response {
+        let limits = MultipartLimits {
+            max_field_size: Some(4 * 1024),
+            max_file_size: Some(2 * 1024 * 1024 * 1024),
+            max_total_size: None,
+        };
+
+        let mut multipart = match MultipartReader::from_request(&request, limits) {
+            Ok(multipart) => multipart,
+            Err(_) => return Response::empty(StatusCode::BadRequest),
+        };
+
+        let mut summary = String::new();
+
+        loop {
+            let mut part = match multipart.next_part() {
+                Ok(Some(part)) => part,
+                Ok(None) => break,
+                Err(e) => return Response::new(StatusCode::BadRequest, e.to_string()),
+            };
+
+            // Returning early leaves the remaining parts unread
+            if part.name == "stop" {
+                return Response::new(StatusCode::OK, summary);
+            }
+
+            let result = match part.filename.clone() {
+                Some(filename) => {
+                    let mut file = File::create(directory.join(&filename)).unwrap();
+                    let mut buf = [0; 4096];
+                    let mut progress = Vec::new();
+                    let mut written = 0;
+
+                    loop {
+                        match part.read(&mut buf) {
+                            Ok(0) => break Ok(()),
+                            Ok(n) => {
+                                file.write_all(&buf[..n]).unwrap();
+                                written += n;
+                                progress.push(written);
+                            }
+                            Err(e) => break Err(e),
+                        }
+                    }
+                    .map(|_| {
+                        assert!(progress.windows(2).all(|w| w[0] < w[1]));
+                        summary.push_str(&format!("{}: {} bytes\n", filename, written));
+                    })
+                }
+                None => {
+                    let mut value = String::new();
+                    part.read_to_string(&mut value)
+                        .map(|_| summary.push_str(&format!("{}={}\n", part.name, value)))
+                }
+            };
+
+            if let Err(e) = result {
+                let error = e
+                    .into_inner()
+                    .and_then(|e| e.downcast::<MultipartError>().ok())
+                    .map(|e| e.to_string())
+                    .unwrap_or_default();
+
+                return Response::new(StatusCode::RequestEntityTooLarge, error);
+            }
+        }
+
+        Response::new(StatusCode::OK, summary)
+    }
+
+    /// Sends a multipart request on the stream and returns the status line and body of the response.
+    fn upload(stream: &mut TcpStream, body: &[u8]) -> (String, String) {
+        write!(
+            stream,
+            "POST /upload HTTP/1.1\r\nHost: localhost\r\nConnection: Keep-Alive\r\nContent-Type: multipart/form-data; boundary={}\r\nContent-Length: {}\r\n\r\n",
+            BOUNDARY,
+            body.len()
+        )
+        .unwrap();
+        stream.write_all(body).unwrap();
+
+        let mut reader = BufReader::new(stream);
+        let mut status = String::new();
+        reader.read_line(&mut status).unwrap();
+
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+
+            if line == "\r\n" {
+                break;
+            } else if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+
+        let mut content = vec![0; length];
+        reader.read_exact(&mut content).unwrap();
+
+        (
+            status.trim_end().to_string(),
+            String::from_utf8(content).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_multipart_upload() {
+        let directory =
+            std::env::temp_dir().join(format!("humphrey-multipart-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let addr = crate::tests::free_addr();
+        let (shutdown_tx, shutdown_rx) = channel();
+
+        let app: App<PathBuf> = App::new_with_config(2, directory.clone())
+            .with_route("/upload", upload_handler)
+            .with_shutdown(shutdown_rx);
+
+        let cloned_addr = addr.clone();
+        let handle = spawn(move || app.run(cloned_addr).unwrap());
+
+        crate::tests::wait_for(&addr);
+
+        let file = file_content();
+        let mut stream = TcpStream::connect(&addr).unwrap();
+
+        let (status, content) = upload(
+            &mut stream,
+            &body(&[
+                ("title", None, b"Holiday photos"),
+                ("upload", Some("photos.zip"), &file),
+            ]),
+        );
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(
+            content,
+            format!("title=Holiday photos\nphotos.zip: {} bytes\n", file.len())
+        );
+        assert_eq!(std::fs::read(directory.join("photos.zip")).unwrap(), file);
+
+        // The connection is kept alive after the handler returns without reading every part
+        let (status, content) = upload(
+            &mut stream,
+            &body(&[
+                ("title", None, b"Stopped"),
+                ("stop", None, b""),
+                ("ignored", Some("ignored.zip"), &file),
+            ]),
+        );
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(content, "title=Stopped\n");
+        assert!(!directory.join("ignored.zip").exists());
+
+        let long_field = vec![b'a'; 4 * 1024 + 1];
+        let (status, content) = upload(&mut stream, &body(&[("title", None, &long_field)]));
+        assert_eq!(status, "HTTP/1.1 413 Request Entity Too Large");
+        assert_eq!(
+            content,
+            "Multipart error: the part `title` was larger than 4096 bytes"
+        );
+
+        shutdown_tx.send(()).unwrap();
+        handle.join().unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,1763 @@
+//! Scenarios which are run against whichever `App` the crate was compiled with, so that the
+//!   threaded and tokio implementations are held to the same behaviour.
+//!
+//! The scenarios only use the `Harness` trait, which hides the differences in how the two apps are
+//!   built and run. Where the implementations genuinely differ, the harness describes the
+//!   difference and the scenario asserts it explicitly.
+
+use crate::endpoint::{Endpoint, EndpointKind};
+use crate::http::cors::Cors;
+use crate::http::event_stream::Event;
+use crate::http::headers::HeaderType;
+use crate::http::method::Method;
+use crate::http::request::RequestLimits;
+use crate::http::{Request, Response, StatusCode};
+use crate::keep_alive::KeepAliveConfig;
+use crate::middleware::Middleware;
+use crate::monitor::event::EventType;
+use crate::monitor::MonitorConfig;
+use crate::rate_limit::RateLimitConfig;
+use crate::route::{ErrorContext, SubApp, TrailingSlash, UpgradeDecision};
+use crate::shutdown::{ShutdownSignal, ShutdownSummary};
+use crate::stream::Stream;
+use crate::tests::{free_addr, wait_for};
+use crate::App;
+
+use std::io::{BufReader, ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use std::thread::{sleep, spawn, JoinHandle};
+use std::time::Duration;
+
+#[cfg(feature = "tls")]
+use crate::app::HumphreyError;
+#[cfg(feature = "tls")]
+use std::net::{SocketAddr, TcpListener};
+
+/// A handler used by the scenarios, which the harness adapts to the app's handler traits.
+type Handler = fn(Request) -> Response;
+
+/// An error handler used by the scenarios which is given the request and the app's state.
+type StateErrorHandler = fn(StatusCode, Option<&Request>, Arc<()>) -> Response;
+
+/// A guard used by the scenarios to decide whether WebSocket upgrade requests are accepted.
+type Guard = fn(&Request, Arc<()>) -> UpgradeDecision;
+
+/// The certificate and private key used to run apps with TLS, issued for `localhost` by `CA`.
+#[cfg(feature = "tls")]
+const CERT: &str = "./src/tests/testcases/tls/cert.pem";
+#[cfg(feature = "tls")]
+const KEY: &str = "./src/tests/testcases/tls/key.pem";
+#[cfg(feature = "tls")]
+const CA: &str = "./src/tests/testcases/tls/ca.pem";
+/// The certificate followed by the certificate of `CA`, and the same key as `KEY` in SEC1 format.
+#[cfg(feature = "tls")]
+const FULLCHAIN: &str = "./src/tests/testcases/tls/fullchain.pem";
+#[cfg(feature = "tls")]
+const EC_KEY: &str = "./src/tests/testcases/tls/key-ec.pem";
+
+/// Represents an app which the scenarios can be run against.
+///
+/// This is implemented for the threaded `App` without the `tokio` feature, and for the tokio `App`
+///   with it.
+trait Harness: Sized {
+    /// Whether idle keep-alive connections are closed after the connection timeout, following a
+    ///   `408 Request Timeout` response.
+    ///
+    /// The tokio app has no connection timeout, so idle connections stay open until the client
+    ///   closes them.
+    const CLOSES_IDLE_CONNECTIONS: bool;
+
+    /// Creates an app with no state. The threaded app is given four threads.
+    fn build() -> Self;
+
+    /// Adds a route which does not use the app's state.
+    fn route(self, route: &str, handler: Handler) -> Self;
+
+    /// Adds a sub-app for the given host with a single route.
+    fn host(self, host: &str, route: &str, handler: Handler) -> Self;
+
+    /// Sets the CORS configuration of an existing route.
+    fn cors(self, route: &str, cors: Cors) -> Self;
+
+    /// Adds a middleware to the app.
+    fn middleware(self, middleware: Middleware<()>) -> Self;
+
+    /// Sets the error handler.
+    fn error_handler(self, handler: fn(StatusCode) -> Response) -> Self;
+
+    /// Sets the error handler, which is also given the request and the app's state.
+    fn error_handler_with_state(self, handler: StateErrorHandler) -> Self;
+
+    /// Sets the error handler which is given the context of the error.
+    fn error_context_handler(self, handler: fn(&ErrorContext) -> Response) -> Self;
+
+    /// Sets the limits on the size of requests.
+    fn request_limits(self, limits: RequestLimits) -> Self;
+
+    /// Sets how long to wait between keep-alive requests, where supported.
+    fn connection_timeout(self, timeout: Duration) -> Self;
+
+    /// Limits the rate at which each client can make requests.
+    fn rate_limit(self, config: RateLimitConfig) -> Self;
+
+    /// Limits how long keep-alive connections stay idle and how many requests are sent on each.
+    fn keep_alive(self, config: KeepAliveConfig) -> Self;
+
+    /// Expects every connection to start with a PROXY protocol header.
+    fn proxy_protocol(self) -> Self;
+
+    /// Sets how paths which differ from a route only by a trailing slash are handled.
+    fn trailing_slash(self, policy: TrailingSlash) -> Self;
+
+    /// Adds a sub-app for the given host with a single route, which handles paths which differ from
+    ///   its routes only by a trailing slash with the given policy.
+    fn host_trailing_slash(
+        self,
+        host: &str,
+        policy: TrailingSlash,
+        route: &str,
+        handler: Handler,
+    ) -> Self;
+
+    /// Adds a WebSocket route with the given guard, whose handler writes `connected` to the stream
+    ///   and closes it.
+    fn guarded_websocket(self, route: &str, guard: Guard) -> Self;
+
+    /// Adds a WebSocket route whose handler writes `connected` to the stream and waits for the
+    ///   client to close it.
+    fn held_websocket(self, route: &str) -> Self;
+
+    /// Limits the number of WebSocket connections which can be open from a single IP address at
+    ///   once.
+    fn websocket_limit_per_ip(self, limit: usize) -> Self;
+
+    /// Adds a route whose response hands the connection to a function which writes `hijacked` to
+    ///   the stream, then echoes everything it reads until the client closes its side.
+    fn hijacked(self, route: &str) -> Self;
+
+    /// Sends the app's monitor events to the given configuration.
+    fn monitor(self, config: MonitorConfig) -> Self;
+
+    /// Sets the certificate and key to run the app with TLS.
+    #[cfg(feature = "tls")]
+    fn cert(self) -> Self;
+
+    /// Sets the certificate and key to run the app with TLS from PEM data.
+    #[cfg(feature = "tls")]
+    fn cert_bytes(self, cert_pem: &[u8], key_pem: &[u8]) -> Self;
+
+    /// Forces HTTPS, redirecting insecure requests received on the given address.
+    #[cfg(feature = "tls")]
+    fn forced_https(self, addr: &str) -> Self;
+
+    /// Runs the app with TLS on a free port, expecting it to fail to start, and returns the error.
+    #[cfg(feature = "tls")]
+    fn run_tls_error(self) -> HumphreyError;
+
+    /// Starts the app on a free port in the background, with TLS if `tls` is true.
+    fn start(self, tls: bool) -> Running;
+
+    /// Starts the app in the background with `run_multi`, with an endpoint of each given kind on a
+    ///   free port.
+    fn start_endpoints(self, kinds: &[EndpointKind]) -> Running;
+
+    /// Converts the app into a test client, returning a function which sends it a request and
+    ///   waits for the response.
+    fn test_client(self) -> TestClient;
+}
+
+/// Sends a request to an app's test client and returns the response.
+type TestClient = Box<dyn Fn(Request) -> Response>;
+
+/// Represents an app running in the background, which is shut down when dropped.
+struct Running {
+    addr: String,
+    addrs: Vec<String>,
+    signal: Option<Box<dyn FnOnce() + Send>>,
+    thread: Option<JoinHandle<ShutdownSummary>>,
+}
+
+#[cfg(not(feature = "tokio"))]
+impl Harness for App<()> {
+    const CLOSES_IDLE_CONNECTIONS: bool = true;
+
+    fn build() -> Self {
+        App::new_with_config(4, ())
+    }
+
+    fn route(self, route: &str, handler: Handler) -> Self {
+        self.with_stateless_route(route, handler)
+    }
+
+    fn host(self, host: &str, route: &str, handler: Handler) -> Self {
+        self.with_host(host, SubApp::new().with_stateless_route(route, handler))
+    }
+
+    fn cors(self, route: &str, cors: Cors) -> Self {
+        self.with_cors_config(route, cors)
+    }
+
+    fn middleware(self, middleware: Middleware<()>) -> Self {
+        self.with_middleware(middleware)
+    }
+
+    fn error_handler(self, handler: fn(StatusCode) -> Response) -> Self {
+        self.with_error_handler(handler)
+    }
+
+    fn error_handler_with_state(self, handler: StateErrorHandler) -> Self {
+        self.with_error_handler_with_state(handler)
+    }
+
+    fn error_context_handler(self, handler: fn(&ErrorContext) -> Response) -> Self {
+        self.with_error_context_handler(handler)
+    }
+
+    fn request_limits(self, limits: RequestLimits) -> Self {
+        self.with_request_limits(limits)
+    }
+
+    fn connection_timeout(self, timeout: Duration) -> Self {
+        self.with_connection_timeout(Some(timeout))
+    }
+
+    fn rate_limit(self, config: RateLimitConfig) -> Self {
+        self.with_rate_limit(config)
+    }
+
+    fn keep_alive(self, config: KeepAliveConfig) -> Self {
+        self.with_keep_alive(config)
+    }
+
+    fn proxy_protocol(self) -> Self {
+        self.with_proxy_protocol(true)
+    }
+
+    fn trailing_slash(self, policy: TrailingSlash) -> Self {
+        self.with_trailing_slash_policy(policy)
+    }
+
+    fn host_trailing_slash(
+        self,
+        host: &str,
+        policy: TrailingSlash,
+        route: &str,
+        handler: Handler,
+    ) -> Self {
+        self.with_host(
+            host,
+            SubApp::new()
+                .with_trailing_slash_policy(policy)
+                .with_stateless_route(route, handler),
+        )
+    }
+
+    fn guarded_websocket(self, route: &str, guard: Guard) -> Self {
+        self.with_guarded_websocket_route(route, guard, |_, mut stream: Stream, _| {
+            let _ = stream.write_all(b"connected");
+        })
+    }
+
+    fn held_websocket(self, route: &str) -> Self {
+        self.with_websocket_route(route, |_, mut stream: Stream, _| {
+            let _ = stream.write_all(b"connected");
+            let _ = stream.read_to_end(&mut Vec::new());
+        })
+    }
+
+    fn websocket_limit_per_ip(self, limit: usize) -> Self {
+        self.with_websocket_limits(None, Some(limit))
+    }
+
+    fn hijacked(self, route: &str) -> Self {
+        self.with_stateless_route(route, |_| {
+            Response::new(StatusCode::OK, "ready").with_hijack(|mut stream| {
+                let mut received = Vec::new();
+                let _ = stream.write_all(b"hijacked");
+                let _ = stream.read_to_end(&mut received);
+                let _ = stream.write_all(&received);
+            })
+        })
+    }
+
+    fn monitor(self, config: MonitorConfig) -> Self {
+        self.with_monitor(config)
+    }
+
+    #[cfg(feature = "tls")]
+    fn cert(self) -> Self {
+        self.with_cert(CERT, KEY)
+    }
+
+    #[cfg(feature = "tls")]
+    fn cert_bytes(self, cert_pem: &[u8], key_pem: &[u8]) -> Self {
+        self.with_cert_bytes(cert_pem, key_pem)
+    }
+
+    #[cfg(feature = "tls")]
+    fn forced_https(self, addr: &str) -> Self {
+        let addr: SocketAddr = addr.parse().unwrap();
+
+        self.with_forced_https_address(addr.ip())
+            .with_forced_https_port(addr.port())
+    }
+
+    #[cfg(feature = "tls")]
+    fn run_tls_error(self) -> HumphreyError {
+        self.run_tls(free_addr()).unwrap_err()
+    }
+
+    fn start(self, tls: bool) -> Running {
+        let shutdown = ShutdownSignal::new();
+        let app = self.with_shutdown(shutdown.clone());
+
+        let addr = free_addr();
+        let cloned_addr = addr.clone();
+        let thread = spawn(move || match tls {
+            #[cfg(feature = "tls")]
+            true => app.run_tls(cloned_addr).unwrap(),
+            _ => app.run(cloned_addr).unwrap(),
+        });
+
+        Running::new(vec![addr], thread, move || shutdown.trigger())
+    }
+
+    fn start_endpoints(self, kinds: &[EndpointKind]) -> Running {
+        let shutdown = ShutdownSignal::new();
+        let app = self.with_shutdown(shutdown.clone());
+
+        let (addrs, endpoints) = endpoints(kinds);
+        let thread = spawn(move || app.run_multi(endpoints).unwrap());
+
+        Running::new(addrs, thread, move || shutdown.trigger())
+    }
+
+    fn test_client(self) -> TestClient {
+        let client = self.into_test_client();
+
+        Box::new(move |request| client.request(request))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Harness for App<()> {
+    const CLOSES_IDLE_CONNECTIONS: bool = false;
+
+    fn build() -> Self {
+        App::new_with_config(())
+    }
+
+    fn route(self, route: &str, handler: Handler) -> Self {
+        self.with_stateless_route(route, move |request| async move { handler(request) })
+    }
+
+    fn host(self, host: &str, route: &str, handler: Handler) -> Self {
+        self.with_host(
+            host,
+            SubApp::new()
+                .with_stateless_route(route, move |request| async move { handler(request) }),
+        )
+    }
+
+    fn cors(self, route: &str, cors: Cors) -> Self {
+        self.with_cors_config(route, cors)
+    }
+
+    fn middleware(self, middleware: Middleware<()>) -> Self {
+        self.with_middleware(middleware)
+    }
+
+    fn error_handler(self, handler: fn(StatusCode) -> Response) -> Self {
+        self.with_error_handler(handler)
+    }
+
+    fn error_handler_with_state(self, handler: StateErrorHandler) -> Self {
+        self.with_error_handler_with_state(handler)
+    }
+
+    fn error_context_handler(self, handler: fn(&ErrorContext) -> Response) -> Self {
+        self.with_error_context_handler(handler)
+    }
+
+    fn request_limits(self, limits: RequestLimits) -> Self {
+        self.with_request_limits(limits)
+    }
+
+    fn connection_timeout(self, _: Duration) -> Self {
+        self
+    }
+
+    fn rate_limit(self, config: RateLimitConfig) -> Self {
+        self.with_rate_limit(config)
+    }
+
+    fn keep_alive(self, config: KeepAliveConfig) -> Self {
+        self.with_keep_alive(config)
+    }
+
+    fn proxy_protocol(self) -> Self {
+        self.with_proxy_protocol(true)
+    }
+
+    fn trailing_slash(self, policy: TrailingSlash) -> Self {
+        self.with_trailing_slash_policy(policy)
+    }
+
+    fn host_trailing_slash(
+        self,
+        host: &str,
+        policy: TrailingSlash,
+        route: &str,
+        handler: Handler,
+    ) -> Self {
+        self.with_host(
+            host,
+            SubApp::new()
+                .with_trailing_slash_policy(policy)
+                .with_stateless_route(route, move |request| async move { handler(request) }),
+        )
+    }
+
+    fn guarded_websocket(self, route: &str, guard: Guard) -> Self {
+        self.with_guarded_websocket_route(route, guard, |_, mut stream: Stream, _| async move {
+            let _ = tokio::io::AsyncWriteExt::write_all(&mut stream, b"connected").await;
+        })
+    }
+
+    fn held_websocket(self, route: &str) -> Self {
+        self.with_websocket_route(route, |_, mut stream: Stream, _| async move {
+            let _ = tokio::io::AsyncWriteExt::write_all(&mut stream, b"connected").await;
+            let _ = tokio::io::AsyncReadExt::read_to_end(&mut stream, &mut Vec::new()).await;
+        })
+    }
+
+    fn websocket_limit_per_ip(self, limit: usize) -> Self {
+        self.with_websocket_limits(None, Some(limit))
+    }
+
+    fn hijacked(self, route: &str) -> Self {
+        self.with_stateless_route(route, |_| async {
+            Response::new(StatusCode::OK, "ready").with_hijack(|mut stream| async move {
+                use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+                let mut received = Vec::new();
+                let _ = stream.write_all(b"hijacked").await;
+                let _ = stream.read_to_end(&mut received).await;
+                let _ = stream.write_all(&received).await;
+            })
+        })
+    }
+
+    fn monitor(self, config: MonitorConfig) -> Self {
+        self.with_monitor(config)
+    }
+
+    #[cfg(feature = "tls")]
+    fn cert(self) -> Self {
+        self.with_cert(CERT, KEY)
+    }
+
+    #[cfg(feature = "tls")]
+    fn cert_bytes(self, cert_pem: &[u8], key_pem: &[u8]) -> Self {
+        self.with_cert_bytes(cert_pem, key_pem)
+    }
+
+    #[cfg(feature = "tls")]
+    fn forced_https(self, addr: &str) -> Self {
+        let addr: SocketAddr = addr.parse().unwrap();
+
+        self.with_forced_https_address(addr.ip())
+            .with_forced_https_port(addr.port())
+    }
+
+    #[cfg(feature = "tls")]
+    fn run_tls_error(self) -> HumphreyError {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(self.run_tls(free_addr()))
+            .unwrap_err()
+    }
+
+    fn start(self, tls: bool) -> Running {
+        let shutdown = ShutdownSignal::new();
+        let app = self.with_shutdown(shutdown.clone());
+
+        let addr = free_addr();
+        let cloned_addr = addr.clone();
+        let thread = spawn(move || {
+            let runtime = tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+
+            match tls {
+                #[cfg(feature = "tls")]
+                true => runtime.block_on(app.run_tls(cloned_addr)).unwrap(),
+                _ => runtime.block_on(app.run(cloned_addr)).unwrap(),
+            }
+        });
+
+        Running::new(vec![addr], thread, move || shutdown.trigger())
+    }
+
+    fn start_endpoints(self, kinds: &[EndpointKind]) -> Running {
+        let shutdown = ShutdownSignal::new();
+        let app = self.with_shutdown(shutdown.clone());
+
+        let (addrs, endpoints) = endpoints(kinds);
+        let thread = spawn(move || {
+            tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(app.run_multi(endpoints))
+                .unwrap()
+        });
+
+        Running::new(addrs, thread, move || shutdown.trigger())
+    }
+
+    fn test_client(self) -> TestClient {
+        let client = self.into_test_client();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        Box::new(move |request| runtime.block_on(client.request(request)))
+    }
+}
+
+impl Running {
+    /// Waits for the app to start listening on every given address.
+    fn new(
+        addrs: Vec<String>,
+        thread: JoinHandle<ShutdownSummary>,
+        signal: impl FnOnce() + Send + 'static,
+    ) -> Self {
+        for addr in &addrs {
+            wait_for(addr);
+        }
+
+        Self {
+            addr: addrs[0].clone(),
+            addrs,
+            signal: Some(Box::new(signal)),
+            thread: Some(thread),
+        }
+    }
+
+    /// Signals the app to shut down and waits for it to stop running, returning its summary.
+    fn shutdown(mut self) -> ShutdownSummary {
+        (self.signal.take().unwrap())();
+        self.thread.take().unwrap().join().unwrap()
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        if let Some(signal) = self.signal.take() {
+            signal();
+        }
+    }
+}
+
+/// Creates an endpoint of each given kind on a free port, returning their addresses.
+fn endpoints(kinds: &[EndpointKind]) -> (Vec<String>, Vec<Endpoint>) {
+    kinds
+        .iter()
+        .map(|kind| {
+            let addr = free_addr();
+            let endpoint = match kind {
+                EndpointKind::Plain => Endpoint::plain(&addr),
+                #[cfg(feature = "tls")]
+                EndpointKind::Tls => Endpoint::tls(&addr),
+                #[cfg(feature = "tls")]
+                EndpointKind::Redirect => Endpoint::redirect(&addr),
+            };
+
+            (addr, endpoint)
+        })
+        .unzip()
+}
+
+fn connect(addr: &str) -> TcpStream {
+    let stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream
+}
+
+/// Connects to the given address over TLS, trusting only the test certificate authority.
+#[cfg(feature = "tls")]
+fn connect_tls(addr: &str) -> rustls::StreamOwned<rustls::ClientConnection, TcpStream> {
+    use rustls::{Certificate, ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+    use rustls_pemfile::certs;
+
+    use std::convert::TryInto;
+    use std::fs::File;
+    use std::io::BufReader;
+    use std::sync::Arc;
+
+    let mut roots = RootCertStore::empty();
+    for cert in certs(&mut BufReader::new(File::open(CA).unwrap())).unwrap() {
+        roots.add(&Certificate(cert)).unwrap();
+    }
+
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let connection =
+        ClientConnection::new(Arc::new(config), "localhost".try_into().unwrap()).unwrap();
+
+    StreamOwned::new(connection, connect(addr))
+}
+
+/// Sends a request with the given method, path and extra header lines on the stream, and reads the
+///   response.
+fn send<S>(stream: &mut S, method: &str, path: &str, headers: &str) -> Response
+where
+    S: Read + Write,
+{
+    write!(stream, "{} {} HTTP/1.1\r\n{}\r\n", method, path, headers).unwrap();
+
+    Response::from_stream(stream).unwrap()
+}
+
+/// Sends a request which closes the connection and reads the response.
+fn get(addr: &str, path: &str, headers: &str) -> Response {
+    send(
+        &mut connect(addr),
+        "GET",
+        path,
+        &format!("Connection: Close\r\n{}", headers),
+    )
+}
+
+/// Reads from the stream until the server closes the connection, returning what was received, or
+///   `None` if the connection is still open after waiting briefly.
+fn read_until_closed(stream: &mut TcpStream) -> Option<Vec<u8>> {
+    stream
+        .set_read_timeout(Some(Duration::from_millis(200)))
+        .unwrap();
+
+    let mut received = Vec::new();
+
+    match stream.read_to_end(&mut received) {
+        Ok(_) => Some(received),
+        Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => None,
+        Err(_) => Some(received),
+    }
+}
+
+fn index(_: Request) -> Response {
+    Response::new(StatusCode::OK, "index")
+}
+
+fn about(_: Request) -> Response {
+    Response::new(StatusCode::OK, "about")
+}
+
+fn special(_: Request) -> Response {
+    Response::new(StatusCode::OK, "special")
+}
+
+fn api(request: Request) -> Response {
+    Response::new(StatusCode::OK, format!("api {}", request.uri))
+}
+
+fn fallback(_: Request) -> Response {
+    Response::new(StatusCode::OK, "fallback")
+}
+
+fn host(_: Request) -> Response {
+    Response::new(StatusCode::OK, "host")
+}
+
+fn echo(request: Request) -> Response {
+    Response::new(StatusCode::OK, request.uri)
+}
+
+fn body(request: Request) -> Response {
+    Response::new(StatusCode::OK, request.content.unwrap_or_default())
+}
+
+/// Sends one event straight away and another after a delay, then ends the stream.
+fn events(_: Request) -> Response {
+    let (response, sender) = Response::event_stream();
+
+    spawn(move || {
+        sender.send(Event::new("first")).unwrap();
+        sleep(Duration::from_millis(200));
+        sender
+            .send(Event::new("second\nline").with_event("update"))
+            .unwrap();
+    });
+
+    response
+}
+
+fn address(request: Request) -> Response {
+    let address = request.address;
+
+    Response::new(
+        StatusCode::OK,
+        format!("{} {}", address.origin_addr, address.port),
+    )
+}
+
+fn user(request: Request) -> Response {
+    let id: u64 = match request.params.parse("id") {
+        Some(id) => id,
+        None => return Response::empty(StatusCode::BadRequest),
+    };
+
+    Response::new(StatusCode::OK, format!("user {}", id))
+}
+
+fn listener(request: Request) -> Response {
+    let port = request.local_addr.unwrap().port();
+
+    Response::new(StatusCode::OK, format!("{} {}", request.secure, port))
+}
+
+fn room_guard(request: &Request, _: Arc<()>) -> UpgradeDecision {
+    if request.query != "token=secret" {
+        UpgradeDecision::Reject(Response::new(StatusCode::Unauthorized, "token required"))
+    } else if request.params.get("room") == Some("private") {
+        UpgradeDecision::Reject(Response::empty(StatusCode::Forbidden))
+    } else {
+        UpgradeDecision::Accept
+    }
+}
+
+fn custom_error(status: StatusCode) -> Response {
+    Response::new(status, format!("custom {}", u16::from(status)))
+}
+
+fn request_error(status: StatusCode, request: Option<&Request>, _: Arc<()>) -> Response {
+    let path = request.map_or("unknown", |request| request.uri.as_str());
+
+    Response::new(status, format!("{} {}", u16::from(status), path))
+}
+
+fn panicking(_: Request) -> Response {
+    panic!("handler failed")
+}
+
+/// Describes each field of the error context in the body of the response.
+fn context_error(context: &ErrorContext) -> Response {
+    let cause = context.cause.as_ref().map(|cause| cause.to_string());
+    let path = context.request.map(|request| request.uri.as_str());
+
+    Response::new(
+        context.status_code,
+        format!(
+            "{} | {} | {} | {}",
+            u16::from(context.status_code),
+            cause.as_deref().unwrap_or("none"),
+            context.route.unwrap_or("none"),
+            path.unwrap_or("none")
+        ),
+    )
+}
+
+#[test]
+fn test_routing() {
+    let app = App::build()
+        .route("/", index)
+        .route("/about", about)
+        .host("example.com", "/", host)
+        .start(false);
+
+    assert_eq!(get(&app.addr, "/", "").body, b"index");
+    assert_eq!(get(&app.addr, "/about", "").body, b"about");
+
+    let missing = get(&app.addr, "/missing", "");
+    assert_eq!(missing.status_code, StatusCode::NotFound);
+
+    // Requests for a host with its own sub-app only use that sub-app's routes
+    let response = send(
+        &mut connect(&app.addr),
+        "GET",
+        "/",
+        "Host: example.com\r\nConnection: Close\r\n",
+    );
+    assert_eq!(response.body, b"host");
+
+    app.shutdown();
+}
+
+#[test]
+fn test_host_ports() {
+    let client = App::build()
+        .route("/", index)
+        .host("localhost", "/", host)
+        .host("[::1]", "/", special)
+        .host("example.com:8080", "/", about)
+        .test_client();
+    let get = |host: &str| {
+        client(
+            Request::builder()
+                .uri("/")
+                .header(HeaderType::Host, host)
+                .build(),
+        )
+        .body
+    };
+
+    // The port is ignored unless the sub-app's host specifies one
+    assert_eq!(get("localhost"), b"host");
+    assert_eq!(get("localhost:8080"), b"host");
+    assert_eq!(get("[::1]"), b"special");
+    assert_eq!(get("[::1]:8080"), b"special");
+    assert_eq!(get("example.com:8080"), b"about");
+    assert_eq!(get("example.com:9090"), b"index");
+    assert_eq!(get("example.com"), b"index");
+}
+
+#[test]
+fn test_trailing_slash() {
+    let build = |policy| {
+        App::build()
+            .trailing_slash(policy)
+            .route("/", index)
+            .route("/about", echo)
+            .route("/docs/", echo)
+            .route("/users/:id", user)
+            .route("/blog/*", echo)
+            .route("/*", fallback)
+            .test_client()
+    };
+    let get = |client: &TestClient, uri: &str| {
+        let (path, query) = uri.split_once('?').unwrap_or((uri, ""));
+
+        client(Request::builder().uri(path).query(query).build())
+    };
+
+    // By default, only the exact path matches
+    let strict = build(TrailingSlash::Strict);
+    assert_eq!(get(&strict, "/about").body, b"/about");
+    assert_eq!(get(&strict, "/about/").body, b"fallback");
+
+    // Both forms are handled by the route, which is given its own form of the path
+    let merge = build(TrailingSlash::Merge);
+    assert_eq!(get(&merge, "/about").body, b"/about");
+    assert_eq!(get(&merge, "/about/").body, b"/about");
+    assert_eq!(get(&merge, "/docs").body, b"/docs/");
+    assert_eq!(get(&merge, "/users/5/").body, b"user 5");
+    assert_eq!(get(&merge, "/").body, b"index");
+    assert_eq!(get(&merge, "/missing/").body, b"fallback");
+
+    // Wildcard routes are left alone
+    assert_eq!(get(&merge, "/blog/").body, b"/blog/");
+    assert_eq!(get(&merge, "/blog").body, b"fallback");
+
+    // The other form is redirected to the route's form, keeping the query string
+    let redirect = build(TrailingSlash::Redirect);
+    let response = get(&redirect, "/about/?lang=en");
+    assert_eq!(response.status_code, StatusCode::MovedPermanently);
+    assert_eq!(
+        response.headers.get(HeaderType::Location),
+        Some("/about?lang=en")
+    );
+
+    let response = get(&redirect, "/docs");
+    assert_eq!(response.status_code, StatusCode::MovedPermanently);
+    assert_eq!(response.headers.get(HeaderType::Location), Some("/docs/"));
+
+    assert_eq!(get(&redirect, "/about").body, b"/about");
+    assert_eq!(get(&redirect, "/").body, b"index");
+    assert_eq!(get(&redirect, "/blog").body, b"fallback");
+    assert_eq!(get(&redirect, "/missing/").body, b"fallback");
+
+    // Sub-apps have their own policy, which applies to requests for their host
+    let client = App::build()
+        .route("/about", about)
+        .host_trailing_slash("example.com", TrailingSlash::Merge, "/contact", special)
+        .test_client();
+    let get = |host: &str, uri: &str| {
+        client(
+            Request::builder()
+                .uri(uri)
+                .header(HeaderType::Host, host)
+                .build(),
+        )
+    };
+
+    assert_eq!(get("example.com", "/contact/").body, b"special");
+    assert_eq!(get("example.com", "/about").body, b"about");
+    assert_eq!(
+        get("example.com", "/about/").status_code,
+        StatusCode::NotFound
+    );
+    assert_eq!(
+        get("localhost", "/contact/").status_code,
+        StatusCode::NotFound
+    );
+}
+
+#[test]
+fn test_trailing_slash_redirect() {
+    let app = App::build()
+        .trailing_slash(TrailingSlash::Redirect)
+        .route("/about", about)
+        .start(false);
+
+    let response = get(&app.addr, "/about/?lang=en", "");
+    assert_eq!(response.status_code, StatusCode::MovedPermanently);
+    assert_eq!(
+        response.headers.get(HeaderType::Location),
+        Some("/about?lang=en")
+    );
+
+    assert_eq!(get(&app.addr, "/about", "").body, b"about");
+}
+
+#[test]
+fn test_extension_methods() {
+    let app = App::build().route("/*", fallback).start(false);
+    let request = |method| {
+        send(
+            &mut connect(&app.addr),
+            method,
+            "/",
+            "Connection: Close\r\n",
+        )
+    };
+
+    // Custom methods are routed like any other method
+    let response = request("PURGE");
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body, b"fallback");
+
+    // Routes which handle every method are not given `TRACE` or `CONNECT` requests
+    assert_eq!(request("TRACE").status_code, StatusCode::NotImplemented);
+    assert_eq!(request("CONNECT").status_code, StatusCode::NotImplemented);
+
+    app.shutdown();
+}
+
+#[test]
+fn test_wildcard_precedence() {
+    let routes: [(&str, Handler); 4] = [
+        ("/api/special", special),
+        ("/api/*", api),
+        ("/*", fallback),
+        ("/index", index),
+    ];
+
+    // The most specific route handles each path, whatever order the routes were added in
+    for reversed in [false, true] {
+        let mut ordered = routes.to_vec();
+        if reversed {
+            ordered.reverse();
+        }
+
+        let app = ordered
+            .into_iter()
+            .fold(App::build(), |app, (route, handler)| {
+                app.route(route, handler)
+            })
+            .start(false);
+
+        assert_eq!(get(&app.addr, "/api/special", "").body, b"special");
+        assert_eq!(get(&app.addr, "/api/users/1", "").body, b"api /api/users/1");
+        assert_eq!(get(&app.addr, "/other", "").body, b"fallback");
+        assert_eq!(get(&app.addr, "/index", "").body, b"index");
+
+        app.shutdown();
+    }
+}
+
+#[test]
+fn test_route_params() {
+    let app = App::build()
+        .route("/user/me", about)
+        .route("/user/:id", user)
+        .start(false);
+
+    assert_eq!(get(&app.addr, "/user/me", "").body, b"about");
+    assert_eq!(get(&app.addr, "/user/42", "").body, b"user 42");
+
+    let invalid = get(&app.addr, "/user/ferris", "");
+    assert_eq!(invalid.status_code, StatusCode::BadRequest);
+
+    let missing = get(&app.addr, "/user/42/posts", "");
+    assert_eq!(missing.status_code, StatusCode::NotFound);
+
+    app.shutdown();
+}
+
+#[test]
+fn test_cors_preflight() {
+    let cors = Cors::new()
+        .with_origin("https://example.com")
+        .with_method(Method::Put);
+
+    let app = App::build()
+        .route("/cors", index)
+        .cors("/cors", cors)
+        .start(false);
+
+    let preflight = send(
+        &mut connect(&app.addr),
+        "OPTIONS",
+        "/cors",
+        "Origin: https://example.com\r\nAccess-Control-Request-Method: PUT\r\nConnection: Close\r\n",
+    );
+    assert_eq!(preflight.status_code, StatusCode::NoContent);
+    assert_eq!(
+        preflight.headers.get(HeaderType::AccessControlAllowOrigin),
+        Some("https://example.com")
+    );
+    assert!(preflight
+        .headers
+        .get(HeaderType::AccessControlAllowMethods)
+        .is_some_and(|methods| methods.contains("PUT")));
+
+    let response = get(&app.addr, "/cors", "Origin: https://example.com\r\n");
+    assert_eq!(response.body, b"index");
+    assert_eq!(
+        response.headers.get(HeaderType::AccessControlAllowOrigin),
+        Some("https://example.com")
+    );
+
+    app.shutdown();
+}
+
+#[test]
+fn test_middleware() {
+    let app = App::build()
+        .route("/*", echo)
+        .middleware(Middleware::new().with_response(|response, _, _| {
+            response.headers.add("X-Middleware", "true");
+        }))
+        .middleware(Middleware::new().with_request(|request, _| {
+            if request.uri.starts_with("/private") {
+                return Some(Response::empty(StatusCode::Forbidden));
+            }
+
+            request.uri = request.uri.replace("/old/", "/new/");
+            None
+        }))
+        .start(false);
+
+    // Requests are modified before they are routed
+    let response = get(&app.addr, "/old/page", "");
+    assert_eq!(response.body, b"/new/page");
+    assert_eq!(response.headers.get("X-Middleware"), Some("true"));
+
+    // Responses from middleware still pass through the middleware added before it
+    let forbidden = get(&app.addr, "/private", "");
+    assert_eq!(forbidden.status_code, StatusCode::Forbidden);
+    assert_eq!(forbidden.headers.get("X-Middleware"), Some("true"));
+
+    // `OPTIONS` requests are answered by the app without running middleware
+    let options = send(
+        &mut connect(&app.addr),
+        "OPTIONS",
+        "/private",
+        "Connection: Close\r\n",
+    );
+    assert_eq!(options.status_code, StatusCode::NoContent);
+    assert_eq!(options.headers.get("X-Middleware"), None);
+
+    app.shutdown();
+}
+
+#[test]
+fn test_test_client() {
+    let cors = Cors::new().with_origin("https://example.com");
+
+    let client = App::build()
+        .route("/", index)
+        .route("/echo", body)
+        .route("/panic", panicking)
+        .cors("/", cors)
+        .host("example.com", "/", host)
+        .middleware(Middleware::new().with_response(|response, _, _| {
+            response.headers.add("X-Middleware", "true");
+        }))
+        .error_handler(custom_error)
+        .test_client();
+
+    // Responses are given the same headers as they would be on a connection
+    let response = client(Request::builder().uri("/").build());
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body, b"index");
+    assert_eq!(response.headers.get(HeaderType::ContentLength), Some("5"));
+    assert_eq!(response.headers.get(HeaderType::Connection), Some("Close"));
+    assert_eq!(response.headers.get("X-Middleware"), Some("true"));
+    assert!(response.headers.get(HeaderType::Date).is_some());
+    assert!(response.headers.get(HeaderType::Server).is_some());
+
+    // Requests with a `Host` header are routed to the sub-app for that host
+    let response = client(
+        Request::builder()
+            .uri("/")
+            .header(HeaderType::Host, "example.com")
+            .build(),
+    );
+    assert_eq!(response.body, b"host");
+
+    let response = client(
+        Request::builder()
+            .uri("/echo")
+            .method(Method::Post)
+            .body("posted")
+            .build(),
+    );
+    assert_eq!(response.body, b"posted");
+
+    let missing = client(Request::builder().uri("/missing").build());
+    assert_eq!(missing.status_code, StatusCode::NotFound);
+    assert_eq!(missing.body, b"custom 404");
+
+    let panicked = client(Request::builder().uri("/panic").build());
+    assert_eq!(panicked.status_code, StatusCode::InternalError);
+    assert_eq!(panicked.body, b"custom 500");
+
+    let preflight = client(
+        Request::builder()
+            .uri("/")
+            .method(Method::Options)
+            .header(HeaderType::Origin, "https://example.com")
+            .build(),
+    );
+    assert_eq!(preflight.status_code, StatusCode::NoContent);
+    assert_eq!(
+        preflight.headers.get(HeaderType::AccessControlAllowOrigin),
+        Some("https://example.com")
+    );
+}
+
+#[test]
+fn test_keep_alive_sequencing() {
+    let app = App::build().route("/*", echo).start(false);
+    let mut stream = connect(&app.addr);
+
+    // Responses on a keep-alive connection arrive in the order the requests were sent
+    for path in ["/first", "/second", "/third"] {
+        let response = send(&mut stream, "GET", path, "Connection: Keep-Alive\r\n");
+        assert_eq!(response.body, path.as_bytes());
+        assert!(read_until_closed(&mut stream).is_none());
+    }
+
+    let response = send(&mut stream, "GET", "/last", "Connection: Close\r\n");
+    assert_eq!(response.body, b"/last");
+    assert!(read_until_closed(&mut stream).is_some());
+
+    // Without a `Connection` header, the connection is closed after the response
+    let mut stream = connect(&app.addr);
+    let response = send(&mut stream, "GET", "/only", "");
+    assert_eq!(response.body, b"/only");
+    assert!(read_until_closed(&mut stream).is_some());
+
+    app.shutdown();
+}
+
+#[test]
+fn test_event_stream() {
+    let app = App::build().route("/events", events).start(false);
+    let mut stream = connect(&app.addr);
+
+    write!(
+        stream,
+        "GET /events HTTP/1.1\r\nConnection: Keep-Alive\r\n\r\n"
+    )
+    .unwrap();
+
+    // The first event arrives while the handler is still waiting to send the second
+    let mut received = Vec::new();
+    let mut buf = [0; 1024];
+
+    while !String::from_utf8_lossy(&received).contains("data: first\n\n") {
+        let length = stream.read(&mut buf).unwrap();
+        assert_ne!(length, 0);
+        received.extend(&buf[..length]);
+    }
+
+    let head = String::from_utf8_lossy(&received).to_string();
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(head.contains("Content-Type: text/event-stream\r\n"));
+    assert!(head.contains("Transfer-Encoding: chunked\r\n"));
+    assert!(head.contains("Connection: Close\r\n"));
+    assert!(!head.contains("update"));
+
+    // Once the sender is dropped, the stream ends and the connection is closed despite keep-alive
+    sleep(Duration::from_millis(300));
+    received.extend(read_until_closed(&mut stream).unwrap());
+
+    let response = Response::from_stream(&mut received.as_slice()).unwrap();
+    assert_eq!(
+        response.body,
+        b"data: first\n\nevent: update\ndata: second\ndata: line\n\n"
+    );
+
+    app.shutdown();
+}
+
+#[test]
+fn test_error_handler() {
+    let app = App::build()
+        .route("/", index)
+        .error_handler(custom_error)
+        .start(false);
+
+    let missing = get(&app.addr, "/missing", "");
+    assert_eq!(missing.status_code, StatusCode::NotFound);
+    assert_eq!(missing.body, b"custom 404");
+
+    let mut stream = connect(&app.addr);
+    stream.write_all(b"NOT A REQUEST\r\n\r\n").unwrap();
+    let invalid = String::from_utf8(read_until_closed(&mut stream).unwrap()).unwrap();
+    assert!(invalid.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    assert!(invalid.contains("\r\n\r\ncustom 400"));
+
+    app.shutdown();
+}
+
+#[test]
+fn test_error_handler_with_state() {
+    let app = App::build()
+        .route("/", index)
+        .error_handler_with_state(request_error)
+        .start(false);
+
+    let missing = get(&app.addr, "/missing", "");
+    assert_eq!(missing.status_code, StatusCode::NotFound);
+    assert_eq!(missing.body, b"404 /missing");
+
+    // Requests which could not be parsed are handled without a request
+    let mut stream = connect(&app.addr);
+    stream.write_all(b"NOT A REQUEST\r\n\r\n").unwrap();
+    let invalid = String::from_utf8(read_until_closed(&mut stream).unwrap()).unwrap();
+    assert!(invalid.contains("\r\n\r\n400 unknown"));
+
+    app.shutdown();
+}
+
+#[test]
+fn test_error_context() {
+    // The context handler is preferred to the legacy handler whichever is set first
+    let app = App::build()
+        .route("/", index)
+        .route("/panic/*", panicking)
+        .error_context_handler(context_error)
+        .error_handler(custom_error)
+        .request_limits(RequestLimits {
+            max_uri_length: Some(64),
+            ..RequestLimits::default()
+        })
+        .start(false);
+
+    let missing = get(&app.addr, "/missing", "");
+    assert_eq!(missing.status_code, StatusCode::NotFound);
+    assert_eq!(missing.body, b"404 | none | none | /missing");
+
+    // Requests which could not be parsed are described by why they could not be parsed
+    let mut stream = connect(&app.addr);
+    stream.write_all(b"NOT A REQUEST\r\n\r\n").unwrap();
+    let invalid = String::from_utf8(read_until_closed(&mut stream).unwrap()).unwrap();
+    assert!(invalid.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    assert!(
+        invalid.contains("\r\n\r\n400 | Request error: the request was malformed | none | none")
+    );
+
+    let mut stream = connect(&app.addr);
+    write!(stream, "GET /{} HTTP/1.1\r\n\r\n", "a".repeat(100)).unwrap();
+    let long = String::from_utf8(read_until_closed(&mut stream).unwrap()).unwrap();
+    assert!(long.starts_with("HTTP/1.1 414 Request-URI Too Long\r\n"));
+    assert!(
+        long.contains("\r\n\r\n414 | Request error: the request URI was too large | none | none")
+    );
+
+    // Panics are described by their message and the route whose handler panicked, and close the
+    //   connection without stopping the app
+    let mut stream = connect(&app.addr);
+    let panicked = send(
+        &mut stream,
+        "GET",
+        "/panic/now",
+        "Connection: Keep-Alive\r\n",
+    );
+    assert_eq!(panicked.status_code, StatusCode::InternalError);
+    assert_eq!(
+        panicked.body,
+        b"500 | Handler panicked: handler failed | /panic/* | /panic/now"
+    );
+    assert_eq!(panicked.headers.get(HeaderType::Connection), Some("Close"));
+    assert_eq!(read_until_closed(&mut stream), Some(Vec::new()));
+
+    assert_eq!(get(&app.addr, "/", "").body, b"index");
+
+    app.shutdown();
+}
+
+#[test]
+fn test_rate_limit() {
+    // Two requests are allowed per minute, so once both are used the client must wait 30 seconds
+    let app = App::build()
+        .route("/", index)
+        .error_context_handler(context_error)
+        .rate_limit(RateLimitConfig::new(2, Duration::from_secs(60)))
+        .start(false);
+
+    // Each request on a keep-alive connection is counted, and the connection stays open once limited
+    let mut stream = connect(&app.addr);
+    for _ in 0..2 {
+        let response = send(&mut stream, "GET", "/", "Connection: Keep-Alive\r\n");
+        assert_eq!(response.status_code, StatusCode::OK);
+    }
+
+    let limited = send(&mut stream, "GET", "/", "Connection: Keep-Alive\r\n");
+    assert_eq!(limited.status_code, StatusCode::TooManyRequests);
+    assert_eq!(limited.headers.get(HeaderType::RetryAfter), Some("30"));
+    assert_eq!(
+        limited.body,
+        b"429 | Limit reached: 2 requests per 60s | none | /"
+    );
+    assert!(read_until_closed(&mut stream).is_none());
+
+    // The allowance belongs to the client rather than the connection
+    let limited = get(&app.addr, "/", "");
+    assert_eq!(limited.status_code, StatusCode::TooManyRequests);
+
+    app.shutdown();
+}
+
+#[test]
+fn test_proxy_protocol() {
+    let app = App::build()
+        .route("/", address)
+        .rate_limit(RateLimitConfig::new(1, Duration::from_secs(60)))
+        .proxy_protocol()
+        .start(false);
+
+    let mut stream = connect(&app.addr);
+    stream
+        .write_all(b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\n")
+        .unwrap();
+    let response = send(&mut stream, "GET", "/", "Connection: Close\r\n");
+    assert_eq!(response.body, b"192.0.2.1 56324");
+
+    let mut header = b"\r\n\r\n\0\r\nQUIT\n\x21\x21\x00\x24".to_vec();
+    header.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+    header.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2]);
+    header.extend_from_slice(&[0xdc, 0x04, 0x01, 0xbb]);
+
+    let mut stream = connect(&app.addr);
+    stream.write_all(&header).unwrap();
+    let response = send(&mut stream, "GET", "/", "Connection: Close\r\n");
+    assert_eq!(response.body, b"2001:db8::1 56324");
+
+    // Requests are rate limited by the address of the client rather than that of the proxy
+    let mut stream = connect(&app.addr);
+    stream
+        .write_all(b"PROXY TCP4 192.0.2.1 198.51.100.1 56325 443\r\n")
+        .unwrap();
+    let response = send(&mut stream, "GET", "/", "Connection: Close\r\n");
+    assert_eq!(response.status_code, StatusCode::TooManyRequests);
+
+    // Connections without a valid header are closed without a response
+    let mut stream = connect(&app.addr);
+    stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+    assert_eq!(read_until_closed(&mut stream).unwrap(), b"");
+
+    app.shutdown();
+}
+
+#[test]
+fn test_websocket_guard() {
+    let app = App::build()
+        .route("/", index)
+        .guarded_websocket("/ws/:room", room_guard)
+        .start(false);
+    let upgrade = "Upgrade: websocket\r\n";
+
+    let mut stream = connect(&app.addr);
+    write!(
+        stream,
+        "GET /ws/lobby?token=secret HTTP/1.1\r\n{}\r\n",
+        upgrade
+    )
+    .unwrap();
+    assert_eq!(read_until_closed(&mut stream).unwrap(), b"connected");
+
+    // A rejection is sent as an ordinary response, so the connection can be kept alive
+    let mut stream = connect(&app.addr);
+    let keep_alive = format!("{}Connection: Keep-Alive\r\n", upgrade);
+    let response = send(&mut stream, "GET", "/ws/lobby", &keep_alive);
+    assert_eq!(response.status_code, StatusCode::Unauthorized);
+    assert_eq!(response.body, b"token required");
+
+    let response = send(&mut stream, "GET", "/", "Connection: Close\r\n");
+    assert_eq!(response.body, b"index");
+
+    // The guard is given the route parameters
+    let response = get(&app.addr, "/ws/private?token=secret", upgrade);
+    assert_eq!(response.status_code, StatusCode::Forbidden);
+
+    app.shutdown();
+}
+
+/// Upgrade requests beyond the WebSocket connection limits are refused until an open connection
+///   closes.
+#[test]
+fn test_websocket_limits() {
+    let app = App::build()
+        .held_websocket("/ws")
+        .websocket_limit_per_ip(1)
+        .start(false);
+    let upgrade = "GET /ws HTTP/1.1\r\nUpgrade: websocket\r\n\r\n";
+    let mut connected = [0; 9];
+
+    let mut first = connect(&app.addr);
+    first.write_all(upgrade.as_bytes()).unwrap();
+    first.read_exact(&mut connected).unwrap();
+    assert_eq!(&connected, b"connected");
+
+    let mut second = connect(&app.addr);
+    second.write_all(upgrade.as_bytes()).unwrap();
+
+    let response = Response::from_stream(&mut second).unwrap();
+    assert_eq!(response.status_code, StatusCode::ServiceUnavailable);
+
+    // The connection stops being counted once its handler returns, which may take a moment
+    drop(first);
+    let third = (0..50).find_map(|_| {
+        let mut third = connect(&app.addr);
+        third.write_all(upgrade.as_bytes()).unwrap();
+
+        match third.read_exact(&mut connected) {
+            Ok(()) if &connected == b"connected" => Some(third),
+            _ => {
+                sleep(Duration::from_millis(10));
+                None
+            }
+        }
+    });
+    assert!(third.is_some());
+
+    drop(third);
+    app.shutdown();
+}
+
+#[test]
+fn test_hijacked_response() {
+    let (monitor_tx, monitor_rx) = channel();
+    let app = App::build()
+        .hijacked("/hijack")
+        .monitor(MonitorConfig::new(monitor_tx).with_subscription_to(EventType::ConnectionHijacked))
+        .start(false);
+    let mut stream = connect(&app.addr);
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+    // The response is written in full before the function takes over the connection
+    stream
+        .write_all(b"GET /hijack HTTP/1.1\r\nConnection: Keep-Alive\r\n\r\n")
+        .unwrap();
+    let response = Response::from_buffered_stream_for(&mut reader, &Method::Get).unwrap();
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body, b"ready");
+
+    let mut hijacked = [0; 8];
+    reader.read_exact(&mut hijacked).unwrap();
+    assert_eq!(&hijacked, b"hijacked");
+
+    // Keep-alive no longer applies, so a further request reaches the function instead of the app
+    stream.write_all(b"GET /hijack HTTP/1.1\r\n\r\n").unwrap();
+    stream.shutdown(Shutdown::Write).unwrap();
+
+    let mut echoed = Vec::new();
+    reader.read_to_end(&mut echoed).unwrap();
+    assert_eq!(echoed, b"GET /hijack HTTP/1.1\r\n\r\n");
+
+    let event = monitor_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(event.kind, EventType::ConnectionHijacked);
+    assert_eq!(event.peer, Some(stream.local_addr().unwrap()));
+
+    app.shutdown();
+}
+
+#[test]
+fn test_shutdown() {
+    let app = App::build().route("/", index).start(false);
+    let addr = app.addr.clone();
+
+    assert_eq!(get(&addr, "/", "").body, b"index");
+
+    // Shutting down waits for the app to stop running, after which connections are refused
+    app.shutdown();
+    assert!(TcpStream::connect(&addr).is_err());
+}
+
+/// The threaded app responds with `408 Request Timeout` and closes idle keep-alive connections after
+///   the connection timeout, but the tokio app has no connection timeout, so the connection stays open.
+#[test]
+fn test_idle_connection() {
+    let app = App::build()
+        .route("/", index)
+        .connection_timeout(Duration::from_millis(100))
+        .start(false);
+    let mut stream = connect(&app.addr);
+
+    let response = send(&mut stream, "GET", "/", "Connection: Keep-Alive\r\n");
+    assert_eq!(response.body, b"index");
+
+    sleep(Duration::from_millis(300));
+    let received = read_until_closed(&mut stream);
+    assert_eq!(
+        received.is_some(),
+        <App<()> as Harness>::CLOSES_IDLE_CONNECTIONS
+    );
+
+    if let Some(received) = received {
+        assert!(String::from_utf8_lossy(&received).contains("HTTP/1.1 408 Request Timeout\r\n"));
+    }
+
+    drop(stream);
+    app.shutdown();
+}
+
+#[test]
+fn test_keep_alive_limits() {
+    let app = App::build()
+        .route("/", index)
+        .keep_alive(KeepAliveConfig::new(Duration::from_secs(5), 3))
+        .start(false);
+    let mut stream = connect(&app.addr);
+
+    // Each response advertises the limits and how many more requests can be sent
+    for remaining in ["2", "1"] {
+        let response = send(&mut stream, "GET", "/", "Connection: Keep-Alive\r\n");
+        assert_eq!(response.body, b"index");
+        assert_eq!(
+            response.headers.get(HeaderType::KeepAlive),
+            Some(format!("timeout=5, max={}", remaining).as_str())
+        );
+    }
+
+    // The last request allowed closes the connection
+    let response = send(&mut stream, "GET", "/", "Connection: Keep-Alive\r\n");
+    assert_eq!(response.body, b"index");
+    assert_eq!(response.headers.get(HeaderType::Connection), Some("Close"));
+    assert_eq!(response.headers.get(HeaderType::KeepAlive), None);
+    assert_eq!(read_until_closed(&mut stream), Some(Vec::new()));
+
+    // Connections which are not kept open are not told about the limits
+    let response = get(&app.addr, "/", "");
+    assert_eq!(response.headers.get(HeaderType::KeepAlive), None);
+
+    app.shutdown();
+}
+
+#[test]
+fn test_keep_alive_idle_timeout() {
+    let app = App::build()
+        .route("/", index)
+        .keep_alive(KeepAliveConfig::new(Duration::from_millis(100), 100))
+        .start(false);
+    let mut stream = connect(&app.addr);
+
+    let response = send(&mut stream, "GET", "/", "Connection: Keep-Alive\r\n");
+    assert_eq!(
+        response.headers.get(HeaderType::KeepAlive),
+        Some("timeout=0, max=99")
+    );
+
+    // Idle connections are closed without a `408 Request Timeout` response
+    sleep(Duration::from_millis(300));
+    assert_eq!(read_until_closed(&mut stream), Some(Vec::new()));
+
+    app.shutdown();
+}
+
+/// Connections waiting for their next request are closed as soon as the app shuts down, rather than
+///   holding on to their workers until the client closes them.
+#[test]
+fn test_shutdown_closes_idle_connections() {
+    let app = App::build().route("/", index).start(false);
+    let mut stream = connect(&app.addr);
+
+    let response = send(&mut stream, "GET", "/", "Connection: Keep-Alive\r\n");
+    assert_eq!(response.body, b"index");
+    assert_eq!(response.headers.get(HeaderType::KeepAlive), None);
+
+    app.shutdown();
+    assert_eq!(read_until_closed(&mut stream), Some(Vec::new()));
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn test_tls_handshake() {
+    let app = App::build().route("/", index).cert().start(true);
+    let mut stream = connect_tls(&app.addr);
+
+    let response = send(&mut stream, "GET", "/", "Connection: Close\r\n");
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body, b"index");
+
+    app.shutdown();
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn test_tls_cert_chain() {
+    let cert_pem = std::fs::read(FULLCHAIN).unwrap();
+    let key_pem = std::fs::read(EC_KEY).unwrap();
+
+    let app = App::build()
+        .route("/", index)
+        .cert_bytes(&cert_pem, &key_pem)
+        .start(true);
+    let mut stream = connect_tls(&app.addr);
+
+    let response = send(&mut stream, "GET", "/", "Connection: Close\r\n");
+    assert_eq!(response.status_code, StatusCode::OK);
+
+    // The whole chain is sent, rather than only the first certificate
+    assert_eq!(stream.conn.peer_certificates().unwrap().len(), 2);
+
+    app.shutdown();
+}
+
+#[test]
+fn test_multiple_endpoints() {
+    let app = App::build()
+        .route("/", listener)
+        .start_endpoints(&[EndpointKind::Plain, EndpointKind::Plain]);
+    let addrs = app.addrs.clone();
+
+    // Handlers can tell which endpoint accepted the request from its local address
+    for addr in &addrs {
+        let port = addr.rsplit(':').next().unwrap();
+        let response = get(addr, "/", "");
+        assert_eq!(response.body, format!("false {}", port).as_bytes());
+    }
+
+    // The shutdown signal stops every endpoint
+    app.shutdown();
+    for addr in &addrs {
+        assert!(TcpStream::connect(addr).is_err());
+    }
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn test_tls_and_plain_endpoints() {
+    let app = App::build().route("/*", listener).cert().start_endpoints(&[
+        EndpointKind::Tls,
+        EndpointKind::Plain,
+        EndpointKind::Redirect,
+    ]);
+    let port = |addr: &str| addr.rsplit(':').next().unwrap().to_string();
+
+    let mut stream = connect_tls(&app.addrs[0]);
+    let secure = send(&mut stream, "GET", "/", "Connection: Close\r\n");
+    assert_eq!(
+        secure.body,
+        format!("true {}", port(&app.addrs[0])).as_bytes()
+    );
+
+    // Plaintext endpoints are only redirected to HTTPS if they were created to redirect
+    let plain = get(&app.addrs[1], "/health", "");
+    assert_eq!(
+        plain.body,
+        format!("false {}", port(&app.addrs[1])).as_bytes()
+    );
+
+    // Requests are redirected to the port of the TLS endpoint
+    let redirect = get(&app.addrs[2], "/login", "Host: localhost\r\n");
+    assert_eq!(redirect.status_code, StatusCode::MovedPermanently);
+    assert_eq!(
+        redirect.headers.get(HeaderType::Location),
+        Some(format!("https://localhost:{}/login", port(&app.addrs[0])).as_str())
+    );
+
+    app.shutdown();
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn test_forced_https_port() {
+    let redirect_addr = free_addr();
+    let app = App::build()
+        .route("/*", listener)
+        .cert()
+        .forced_https(&redirect_addr)
+        .start(true);
+    let https_port = app.addr.rsplit(':').next().unwrap();
+
+    // The port the request was sent to is replaced by the port the app is served over HTTPS on
+    let redirect = get(&redirect_addr, "/login?a=b", "Host: localhost:8080\r\n");
+    assert_eq!(redirect.status_code, StatusCode::MovedPermanently);
+    assert_eq!(
+        redirect.headers.get(HeaderType::Location),
+        Some(format!("https://localhost:{}/login?a=b", https_port).as_str())
+    );
+
+    // The redirect listener is stopped along with the app
+    app.shutdown();
+    assert!(TcpStream::connect(&redirect_addr).is_err());
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn test_forced_https_errors() {
+    let redirect_addr = free_addr();
+    let app = App::build()
+        .route("/*", listener)
+        .cert()
+        .forced_https(&redirect_addr)
+        .connection_timeout(Duration::from_millis(200))
+        .start(true);
+
+    // Requests which cannot be parsed are answered with an error instead of stopping the listener
+    let mut stream = connect(&redirect_addr);
+    stream.write_all(b"NOT A REQUEST\r\n\r\n").unwrap();
+    let invalid = String::from_utf8(read_until_closed(&mut stream).unwrap()).unwrap();
+    assert!(invalid.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    assert!(invalid.contains("Content-Length: 0\r\n"));
+    assert!(invalid.contains("Connection: Close\r\n"));
+
+    // Clients which never finish their requests do not hold up the redirects of other clients,
+    //   even when there are more of them than workers
+    let slow: Vec<TcpStream> = (0..6)
+        .map(|_| {
+            let mut stream = connect(&redirect_addr);
+            stream.write_all(b"GET / HTTP/1.1\r\nHost: local").unwrap();
+            stream
+        })
+        .collect();
+
+    let redirect = get(&redirect_addr, "/login", "Host: localhost\r\n");
+    assert_eq!(redirect.status_code, StatusCode::MovedPermanently);
+    assert_eq!(redirect.headers.get(HeaderType::ContentLength), Some("0"));
+    assert_eq!(redirect.headers.get(HeaderType::Connection), Some("Close"));
+
+    drop(slow);
+    app.shutdown();
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn test_forced_https_bind_error() {
+    let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+    let error = App::build()
+        .cert()
+        .forced_https(&taken.local_addr().unwrap().to_string())
+        .run_tls_error();
+
+    assert_eq!(
+        error.downcast_ref::<std::io::Error>().map(|e| e.kind()),
+        Some(ErrorKind::AddrInUse)
+    );
+}
@@ -1,4 +1,4 @@
-use crate::percent::{PercentDecode, PercentEncode};
+use crate::percent::{decode_file_path, decode_path, PercentDecode, PercentEncode};
 
 #[test]
 fn encode_unreserved_chars() {
@@ -47,3 +47,47 @@ fn decode_bytes() {
 
     assert_eq!(decoded, Some(b"this is a \0null character".to_vec()));
 }
+
+#[test]
+fn decode_invalid_escapes() {
+    assert_eq!("%+1".percent_decode(), None);
+    assert_eq!("%-F".percent_decode(), None);
+    assert_eq!("%2".percent_decode(), None);
+}
+
+#[test]
+fn decode_path_keeps_slashes_encoded() {
+    assert_eq!(
+        decode_path("/files/hello%20world.txt"),
+        Some("/files/hello world.txt".into())
+    );
+    assert_eq!(decode_path("/caf%C3%A9"), Some("/café".into()));
+    assert_eq!(decode_path("/a%2fb%2Fc"), Some("/a%2Fb%2Fc".into()));
+    assert_eq!(decode_path("/100%25.txt"), Some("/100%25.txt".into()));
+    assert_eq!(decode_path("/%252F"), Some("/%252F".into()));
+
+    // Decoding is idempotent
+    assert_eq!(decode_path("/a%2Fb c%25"), Some("/a%2Fb c%25".into()));
+
+    assert_eq!(decode_path("/invalid%2"), None);
+    assert_eq!(decode_path("/invalid%zz"), None);
+    assert_eq!(decode_path("/invalid%FF"), None);
+    assert_eq!(decode_path("/invalid%+1"), None);
+    assert_eq!(decode_path("/invalid%-F"), None);
+
+    // Control characters are rejected whether or not they are encoded
+    assert_eq!(decode_path("/header%0D%0AX-Injected:%20true"), None);
+    assert_eq!(decode_path("/null%00"), None);
+    assert_eq!(decode_path("/delete%7F"), None);
+    assert_eq!(decode_path("/tab\t"), None);
+}
+
+#[test]
+fn decode_file_path_rejects_separators() {
+    assert_eq!(decode_file_path("/100%25.txt"), Some("/100%.txt".into()));
+    assert_eq!(decode_file_path("/%252F"), Some("/%2F".into()));
+
+    assert_eq!(decode_file_path("/a%2Fb"), None);
+    assert_eq!(decode_file_path("/..%5Csecret"), None);
+    assert_eq!(decode_file_path("/secret%00.txt"), None);
+}
@@ -0,0 +1,240 @@
+#![allow(unused_imports)]
+use crate::cancellation::CancellationToken;
+use crate::http::address::Address;
+use crate::http::headers::{HeaderType, Headers};
+use crate::http::method::Method;
+use crate::http::proxy::{proxy_request, try_proxy_request, ProxyError};
+use crate::http::request::BodyState;
+use crate::http::{Request, Response, StatusCode};
+use crate::route::RouteParams;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread::{sleep, spawn};
+use std::time::Duration;
+
+#[cfg(not(feature = "tokio"))]
+use crate::{App, Client};
+#[cfg(not(feature = "tokio"))]
+use std::sync::mpsc::channel;
+
+/// Creates a request for the given URI from a fixed address.
+fn get_request(uri: &str) -> Request {
+    Request {
+        method: Method::Get,
+        overridden_method: None,
+        uri: uri.into(),
+        uri_raw: uri.into(),
+        query: String::new(),
+        version: "HTTP/1.1".into(),
+        headers: Default::default(),
+        content: None,
+        body_state: BodyState::Complete,
+        trailers: Headers::new(),
+        address: Address::new("127.0.0.1:1234").unwrap(),
+        secure: false,
+        local_addr: None,
+        deadline: None,
+        trace: None,
+        cancellation: CancellationToken::default(),
+        params: RouteParams::default(),
+    }
+}
+
+#[test]
+fn test_proxy_preserves_trailers() {
+    let upstream = TcpListener::bind("127.0.0.1:0").unwrap();
+    let upstream_addr = upstream.local_addr().unwrap();
+
+    let upstream_thread = spawn(move || {
+        let (mut stream, _) = upstream.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        // Read the request head before replying
+        let mut line = String::new();
+        while line != "\r\n" {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+        }
+
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nTrailer: Server-Timing\r\n\r\n4\r\ndata\r\n0\r\nServer-Timing: total;dur=12\r\nTransfer-Encoding: gzip\r\n\r\n")
+            .unwrap();
+    });
+
+    let request = get_request("/");
+
+    let response = proxy_request(&request, upstream_addr, Duration::from_secs(5));
+    upstream_thread.join().unwrap();
+
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.trailers.get("Server-Timing"), Some("total;dur=12"));
+    assert_eq!(response.trailers.get(HeaderType::TransferEncoding), None);
+
+    let bytes: Vec<u8> = response.into();
+    let expected_bytes = b"HTTP/1.1 200 OK\r\nTrailer: Server-Timing\r\nTransfer-Encoding: chunked\r\n\r\n4\r\ndata\r\n0\r\nserver-timing: total;dur=12\r\n\r\n";
+
+    assert_eq!(bytes, expected_bytes.to_vec());
+}
+
+#[test]
+fn test_proxy_preserves_unknown_status() {
+    let upstream = TcpListener::bind("127.0.0.1:0").unwrap();
+    let upstream_addr = upstream.local_addr().unwrap();
+
+    let upstream_thread = spawn(move || {
+        let (mut stream, _) = upstream.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        let mut line = String::new();
+        while line != "\r\n" {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+        }
+
+        stream
+            .write_all(b"HTTP/1.1 499 Client Closed Request\r\nContent-Length: 0\r\n\r\n")
+            .unwrap();
+    });
+
+    let response = proxy_request(&get_request("/"), upstream_addr, Duration::from_secs(5));
+    upstream_thread.join().unwrap();
+
+    assert_eq!(response.status_code, StatusCode::Custom(499));
+
+    let bytes: Vec<u8> = response.into();
+    assert!(bytes.starts_with(b"HTTP/1.1 499 Client Error\r\n"));
+}
+
+#[test]
+fn test_proxy_errors() {
+    // Nothing is listening on the address once the listener is dropped
+    let closed_addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    assert_eq!(
+        try_proxy_request(&get_request("/"), closed_addr, Duration::from_secs(5)).err(),
+        Some(ProxyError::Connect)
+    );
+
+    // The first upstream connection is closed after the request is read, and the second after
+    //   part of the response is sent
+    let upstream = TcpListener::bind("127.0.0.1:0").unwrap();
+    let upstream_addr = upstream.local_addr().unwrap();
+
+    let upstream_thread = spawn(move || {
+        for partial_response in [&b""[..], &b"HTTP/1.1 200 OK\r\nContent-Le"[..]] {
+            let (mut stream, _) = upstream.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+            let mut line = String::new();
+            while line != "\r\n" {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+            }
+
+            stream.write_all(partial_response).unwrap();
+        }
+    });
+
+    assert_eq!(
+        try_proxy_request(&get_request("/"), upstream_addr, Duration::from_secs(5)).err(),
+        Some(ProxyError::Reset)
+    );
+    assert_eq!(
+        try_proxy_request(&get_request("/"), upstream_addr, Duration::from_secs(5)).err(),
+        Some(ProxyError::Response)
+    );
+
+    upstream_thread.join().unwrap();
+
+    let response = proxy_request(&get_request("/"), closed_addr, Duration::from_secs(5));
+    assert_eq!(response.status_code, StatusCode::BadGateway);
+    assert_eq!(
+        Response::from(ProxyError::Timeout).status_code,
+        StatusCode::GatewayTimeout
+    );
+}
+
+#[test]
+#[cfg(not(feature = "tokio"))]
+fn test_proxy_preserves_set_cookie() {
+    let upstream = TcpListener::bind("127.0.0.1:0").unwrap();
+    let upstream_addr = upstream.local_addr().unwrap();
+
+    // The upstream sets three cookies on every request
+    spawn(move || {
+        for stream in upstream.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+            let mut line = String::new();
+            while line != "\r\n" {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+            }
+
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nSet-Cookie: session=abc; Path=/; HttpOnly\r\nContent-Length: 2\r\nSet-Cookie: theme=dark\r\nSet-Cookie: lang=en; Max-Age=3600\r\n\r\nOK")
+                .unwrap();
+        }
+    });
+
+    let proxy_addr: SocketAddr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let (shutdown_tx, shutdown_rx) = channel();
+
+    let app: App<()> = App::new_with_config(2, ())
+        .with_stateless_route("/*", move |request: Request| {
+            proxy_request(&request, upstream_addr, Duration::from_secs(5))
+        })
+        .with_shutdown(shutdown_rx);
+
+    spawn(move || app.run(proxy_addr).unwrap());
+
+    let mut stream = loop {
+        if let Ok(stream) = TcpStream::connect(proxy_addr) {
+            break stream;
+        }
+
+        sleep(Duration::from_millis(10));
+    };
+
+    // Every cookie is sent on its own line through the proxy
+    stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+    let mut bytes = String::new();
+    stream.read_to_string(&mut bytes).unwrap();
+
+    let cookie_lines: Vec<&str> = bytes
+        .lines()
+        .filter(|line| line.starts_with("Set-Cookie: "))
+        .collect();
+
+    assert_eq!(
+        cookie_lines,
+        vec![
+            "Set-Cookie: session=abc; Path=/; HttpOnly",
+            "Set-Cookie: theme=dark",
+            "Set-Cookie: lang=en; Max-Age=3600"
+        ]
+    );
+
+    // The client parses each one separately
+    let response = Client::new().request(proxy_addr, get_request("/")).unwrap();
+
+    assert_eq!(response.headers.get_all(HeaderType::SetCookie).len(), 3);
+    assert_eq!(
+        response
+            .get_cookies()
+            .iter()
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect::<Vec<_>>(),
+        vec!["session=abc", "theme=dark", "lang=en"]
+    );
+
+    shutdown_tx.send(()).unwrap();
+}
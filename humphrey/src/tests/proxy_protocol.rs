@@ -0,0 +1,224 @@
+use crate::proxy_protocol::{read_header, ProxyHeader, ProxyHeaderError};
+
+use std::io::Read;
+use std::net::SocketAddr;
+
+#[cfg(not(feature = "tokio"))]
+use crate::http::{Response, StatusCode};
+#[cfg(not(feature = "tokio"))]
+use crate::monitor::event::{Event, EventType};
+#[cfg(not(feature = "tokio"))]
+use crate::monitor::MonitorConfig;
+#[cfg(not(feature = "tokio"))]
+use crate::shutdown::ShutdownSignal;
+#[cfg(not(feature = "tokio"))]
+use crate::App;
+#[cfg(not(feature = "tokio"))]
+use std::io::Write;
+#[cfg(not(feature = "tokio"))]
+use std::net::TcpStream;
+#[cfg(not(feature = "tokio"))]
+use std::sync::mpsc::{channel, Receiver};
+#[cfg(not(feature = "tokio"))]
+use std::thread::spawn;
+#[cfg(not(feature = "tokio"))]
+use std::time::Duration;
+
+/// Builds a version 2 header with the given command and family bytes followed by the addresses.
+fn v2(command: u8, family: u8, addresses: &[u8]) -> Vec<u8> {
+    let mut header = b"\r\n\r\n\0\r\nQUIT\n".to_vec();
+    header.extend_from_slice(&[command, family]);
+    header.extend_from_slice(&(addresses.len() as u16).to_be_bytes());
+    header.extend_from_slice(addresses);
+    header
+}
+
+fn proxied(source: &str, destination: &str) -> ProxyHeader {
+    ProxyHeader::Proxied {
+        source: source.parse::<SocketAddr>().unwrap(),
+        destination: destination.parse::<SocketAddr>().unwrap(),
+    }
+}
+
+fn invalid(bytes: &[u8]) -> &'static str {
+    match read_header(&mut &*bytes) {
+        Err(ProxyHeaderError::Invalid(reason)) => reason,
+        result => panic!("expected an invalid header, got {:?}", result),
+    }
+}
+
+#[test]
+fn test_v1_ipv4() {
+    let mut stream: &[u8] = b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\nGET / HTTP/1.1\r\n";
+
+    let header = read_header(&mut stream).unwrap();
+    assert_eq!(header, proxied("192.0.2.1:56324", "198.51.100.1:443"));
+    assert_eq!(header.source(), Some("192.0.2.1:56324".parse().unwrap()));
+
+    // Nothing after the header is read
+    let mut rest = String::new();
+    stream.read_to_string(&mut rest).unwrap();
+    assert_eq!(rest, "GET / HTTP/1.1\r\n");
+}
+
+#[test]
+fn test_v1_ipv6() {
+    let mut stream: &[u8] = b"PROXY TCP6 2001:db8::1 2001:db8::2 56324 443\r\n";
+
+    let header = read_header(&mut stream).unwrap();
+    assert_eq!(header, proxied("[2001:db8::1]:56324", "[2001:db8::2]:443"));
+}
+
+#[test]
+fn test_v1_unknown() {
+    let mut stream: &[u8] = b"PROXY UNKNOWN ffff::1 ffff::2 1 2\r\n";
+
+    let header = read_header(&mut stream).unwrap();
+    assert_eq!(header, ProxyHeader::Local);
+    assert_eq!(header.source(), None);
+}
+
+#[test]
+fn test_v1_invalid() {
+    assert_eq!(
+        invalid(b"PROXY TCP4 192.0.2.1 198.51.100.1 56324\r\n"),
+        "wrong number of fields"
+    );
+    assert_eq!(
+        invalid(b"PROXY UDP4 192.0.2.1 198.51.100.1 1 2\r\n"),
+        "unknown protocol"
+    );
+    assert_eq!(
+        invalid(b"PROXY TCP4 2001:db8::1 198.51.100.1 1 2\r\n"),
+        "invalid address"
+    );
+    assert_eq!(
+        invalid(b"PROXY TCP4 192.0.2.1 198.51.100.1 1 65536\r\n"),
+        "invalid port"
+    );
+
+    // The header must end within the maximum length rather than being read indefinitely
+    let long = format!("PROXY TCP4 {}\r\n", "1".repeat(128));
+    assert_eq!(invalid(long.as_bytes()), "too long");
+}
+
+#[test]
+fn test_v2_ipv4() {
+    let addresses = [192, 0, 2, 1, 198, 51, 100, 1, 0xdc, 0x04, 0x01, 0xbb];
+    let mut bytes = v2(0x21, 0x11, &addresses);
+    bytes.extend_from_slice(b"GET");
+    let mut stream = &*bytes;
+
+    let header = read_header(&mut stream).unwrap();
+    assert_eq!(header, proxied("192.0.2.1:56324", "198.51.100.1:443"));
+    assert_eq!(stream, b"GET");
+}
+
+#[test]
+fn test_v2_ipv6() {
+    let mut addresses = vec![0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+    addresses.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2]);
+    addresses.extend_from_slice(&[0xdc, 0x04, 0x01, 0xbb]);
+    // Extra type-length-value fields after the addresses are skipped
+    addresses.extend_from_slice(&[0x04, 0x00, 0x01, 0xff]);
+    let bytes = v2(0x21, 0x21, &addresses);
+
+    let header = read_header(&mut &*bytes).unwrap();
+    assert_eq!(header, proxied("[2001:db8::1]:56324", "[2001:db8::2]:443"));
+}
+
+#[test]
+fn test_v2_local() {
+    let bytes = v2(0x20, 0x00, &[]);
+    assert_eq!(read_header(&mut &*bytes).unwrap(), ProxyHeader::Local);
+
+    // Unix socket addresses do not describe the client either
+    let bytes = v2(0x21, 0x31, &[0; 216]);
+    assert_eq!(read_header(&mut &*bytes).unwrap(), ProxyHeader::Local);
+}
+
+#[test]
+fn test_v2_invalid() {
+    assert_eq!(invalid(&v2(0x11, 0x11, &[0; 12])), "unsupported version");
+    assert_eq!(invalid(&v2(0x22, 0x11, &[0; 12])), "unknown command");
+    assert_eq!(invalid(&v2(0x21, 0x41, &[0; 12])), "unknown address family");
+    assert_eq!(invalid(&v2(0x21, 0x21, &[0; 12])), "addresses too short");
+}
+
+#[test]
+fn test_missing_header() {
+    assert_eq!(invalid(b"GET / HTTP/1.1\r\n\r\n"), "missing header");
+
+    // A stream which ends part of the way through a header is an IO error
+    let bytes = v2(0x21, 0x11, &[0; 12]);
+    assert!(matches!(
+        read_header(&mut &bytes[..20]),
+        Err(ProxyHeaderError::Io(_))
+    ));
+}
+
+/// Waits for the next event which satisfies the predicate.
+#[cfg(not(feature = "tokio"))]
+fn next_event(rx: &Receiver<Event>, predicate: impl Fn(&Event) -> bool) -> Event {
+    loop {
+        let event = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        if predicate(&event) {
+            return event;
+        }
+    }
+}
+
+#[cfg(not(feature = "tokio"))]
+#[test]
+fn test_monitor_events() {
+    let (tx, rx) = channel();
+    let shutdown = ShutdownSignal::new();
+    let app: App = App::new_with_config(1, ())
+        .with_stateless_route("/", |_| Response::new(StatusCode::OK, "hello"))
+        .with_proxy_protocol(true)
+        .with_monitor(
+            MonitorConfig::new(tx)
+                .with_subscription_to(EventType::ConnectionError)
+                .with_subscription_to(EventType::RequestServedSuccess),
+        )
+        .with_shutdown(shutdown.clone());
+
+    let addr = crate::tests::free_addr();
+    let cloned_addr = addr.clone();
+    let thread = spawn(move || app.run(cloned_addr).unwrap());
+    crate::tests::wait_for(&addr);
+
+    // Events about requests are given the address of the client
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    stream
+        .write_all(b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\n")
+        .unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nConnection: Close\r\n\r\n")
+        .unwrap();
+    assert_eq!(Response::from_stream(&mut stream).unwrap().body, b"hello");
+
+    let event = next_event(&rx, |event| event.kind == EventType::RequestServedSuccess);
+    assert_eq!(event.peer, Some("192.0.2.1:56324".parse().unwrap()));
+
+    // Malformed headers are reported with the address of the load balancer
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    stream.write_all(b"PROXY TCP4 192.0.2.1\r\n").unwrap();
+    let mut received = Vec::new();
+    stream.read_to_end(&mut received).unwrap();
+    assert!(received.is_empty());
+
+    // Connections which close before sending a header, such as the ones made while waiting for the
+    //   app to start, are reported too
+    let local_addr = stream.local_addr().ok();
+    let event = next_event(&rx, |event| event.peer == local_addr);
+    assert_eq!(event.kind, EventType::ConnectionError);
+    assert_eq!(
+        event.info.as_deref(),
+        Some("Invalid PROXY protocol header: wrong number of fields")
+    );
+
+    shutdown.trigger();
+    thread.join().unwrap();
+}
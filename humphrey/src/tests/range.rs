@@ -0,0 +1,188 @@
+#![allow(unused_imports)]
+use crate::http::headers::HeaderType;
+use crate::http::range::{ByteRange, ContentRange, Range, RangeNotSatisfiable};
+use crate::http::response::Response;
+use crate::http::status::StatusCode;
+
+#[test]
+fn test_range_parse() {
+    assert_eq!(
+        Range::parse("bytes=0-1023"),
+        Some(Range::new([ByteRange::Bounded(0, 1023)]))
+    );
+    assert_eq!(
+        Range::parse("bytes=500-"),
+        Some(Range::new([ByteRange::From(500)]))
+    );
+    assert_eq!(
+        Range::parse("bytes=-500"),
+        Some(Range::new([ByteRange::Suffix(500)]))
+    );
+    assert_eq!(
+        Range::parse("Bytes = 0-0, 10-19 ,, -1"),
+        Some(Range::new([
+            ByteRange::Bounded(0, 0),
+            ByteRange::Bounded(10, 19),
+            ByteRange::Suffix(1)
+        ]))
+    );
+
+    let range = Range::parse("bytes=0-99,200-,-50").unwrap();
+    assert_eq!(range.to_string(), "bytes=0-99,200-,-50");
+    assert_eq!(Range::parse(&range.to_string()), Some(range));
+}
+
+#[test]
+fn test_range_parse_invalid() {
+    for value in [
+        "",
+        "bytes",
+        "bytes=",
+        "bytes=,",
+        "bytes=-",
+        "bytes=abc-",
+        "bytes=10-5",
+        "bytes=+5-10",
+        "bytes=0-1023;",
+        "bytes=0-100,x",
+        "bytes=99999999999999999999-",
+        "items=0-10",
+        "0-10",
+    ] {
+        assert_eq!(Range::parse(value), None, "{}", value);
+    }
+}
+
+#[test]
+fn test_range_resolve() {
+    let resolve = |value: &str, length: u64| Range::parse(value).unwrap().resolve(length);
+
+    assert_eq!(
+        resolve("bytes=0-1023", 10000),
+        Ok(vec![ContentRange {
+            start: 0,
+            end: 1023,
+            length: 10000
+        }])
+    );
+    assert_eq!(
+        resolve("bytes=9500-", 10000),
+        Ok(vec![ContentRange {
+            start: 9500,
+            end: 9999,
+            length: 10000
+        }])
+    );
+    assert_eq!(
+        resolve("bytes=-500", 10000),
+        Ok(vec![ContentRange {
+            start: 9500,
+            end: 9999,
+            length: 10000
+        }])
+    );
+
+    // Ranges which extend beyond the end of the resource are shortened
+    assert_eq!(
+        resolve("bytes=5-100", 10),
+        Ok(vec![ContentRange {
+            start: 5,
+            end: 9,
+            length: 10
+        }])
+    );
+    assert_eq!(
+        resolve("bytes=-100", 10),
+        Ok(vec![ContentRange {
+            start: 0,
+            end: 9,
+            length: 10
+        }])
+    );
+
+    // Ranges beyond the end are dropped, and overlapping or adjacent ranges are merged
+    assert_eq!(
+        resolve("bytes=50-60,20-29,0-9,5-14,100-", 80),
+        Ok(vec![
+            ContentRange {
+                start: 0,
+                end: 14,
+                length: 80
+            },
+            ContentRange {
+                start: 20,
+                end: 29,
+                length: 80
+            },
+            ContentRange {
+                start: 50,
+                end: 60,
+                length: 80
+            },
+        ])
+    );
+    assert_eq!(
+        resolve("bytes=0-9,10-19,-60", 80),
+        Ok(vec![ContentRange {
+            start: 0,
+            end: 79,
+            length: 80
+        }])
+    );
+}
+
+#[test]
+fn test_range_not_satisfiable() {
+    let resolve = |value: &str, length: u64| Range::parse(value).unwrap().resolve(length);
+
+    assert_eq!(
+        resolve("bytes=10-20", 10),
+        Err(RangeNotSatisfiable { length: 10 })
+    );
+    assert_eq!(
+        resolve("bytes=10-", 10),
+        Err(RangeNotSatisfiable { length: 10 })
+    );
+    assert_eq!(
+        resolve("bytes=-0", 10),
+        Err(RangeNotSatisfiable { length: 10 })
+    );
+    assert_eq!(
+        resolve("bytes=0-0,-5", 0),
+        Err(RangeNotSatisfiable { length: 0 })
+    );
+
+    let response: Response = resolve("bytes=100-,200-300", 50).unwrap_err().into();
+    assert_eq!(
+        response.status_code,
+        StatusCode::RequestedRangeNotSatisfiable
+    );
+    assert_eq!(
+        response.headers.get(HeaderType::ContentRange),
+        Some("bytes */50")
+    );
+    assert!(response.body.is_empty());
+}
+
+#[test]
+fn test_partial_content() {
+    let resource = b"Hello, world!";
+    let range = Range::parse("bytes=-6")
+        .unwrap()
+        .resolve(resource.len() as u64)
+        .unwrap()[0];
+
+    assert_eq!(range.size(), 6);
+    assert_eq!(range.slice(resource), b"world!");
+
+    let response = Response::partial_content(range, range.slice(resource));
+    assert_eq!(response.status_code, StatusCode::PartialContent);
+    assert_eq!(response.body, b"world!");
+
+    let bytes: Vec<u8> = response.into();
+    let expected_bytes: Vec<u8> =
+        b"HTTP/1.1 206 Partial Content\r\nContent-Length: 6\r\nContent-Range: bytes 7-12/13\r\n\r\nworld!"
+            .to_vec();
+
+    assert_eq!(bytes, expected_bytes);
+}
@@ -0,0 +1,80 @@
+use crate::rate_limit::{RateLimitConfig, RateLimiter};
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::{Duration, Instant};
+
+fn ip(last: u8) -> IpAddr {
+    IpAddr::V4(Ipv4Addr::new(10, 0, 0, last))
+}
+
+fn secs(seconds: u64) -> Duration {
+    Duration::from_secs(seconds)
+}
+
+#[test]
+fn test_rate_limit_burst() {
+    // Ten requests a minute, so one more is allowed every six seconds once the burst is used
+    let limiter = RateLimiter::new(RateLimitConfig::new(10, secs(60)).with_burst(3));
+    let start = Instant::now();
+
+    for _ in 0..3 {
+        assert_eq!(limiter.check_at(ip(1), start), Ok(()));
+    }
+
+    assert_eq!(limiter.check_at(ip(1), start), Err(secs(6)));
+    assert_eq!(limiter.check_at(ip(1), start + secs(4)), Err(secs(2)));
+
+    // Refused requests are not counted, so the client is allowed one request as soon as it is due
+    assert_eq!(limiter.check_at(ip(1), start + secs(6)), Ok(()));
+    assert_eq!(limiter.check_at(ip(1), start + secs(6)), Err(secs(6)));
+
+    // Other clients have their own allowance
+    assert_eq!(limiter.check_at(ip(2), start), Ok(()));
+}
+
+#[test]
+fn test_rate_limit_replenished() {
+    let limiter = RateLimiter::new(RateLimitConfig::new(2, secs(60)));
+    let start = Instant::now();
+
+    assert_eq!(limiter.check_at(ip(1), start), Ok(()));
+    assert_eq!(limiter.check_at(ip(1), start), Ok(()));
+    assert_eq!(limiter.check_at(ip(1), start), Err(secs(30)));
+
+    // After a whole window, the full burst is available again rather than twice as many requests
+    let later = start + secs(60);
+    assert_eq!(limiter.check_at(ip(1), later), Ok(()));
+    assert_eq!(limiter.check_at(ip(1), later), Ok(()));
+    assert_eq!(limiter.check_at(ip(1), later), Err(secs(30)));
+}
+
+#[test]
+fn test_rate_limit_sweep() {
+    let limiter = RateLimiter::new(RateLimitConfig::new(2, secs(60)));
+    let start = Instant::now();
+
+    for last in 0..100 {
+        limiter.check_at(ip(last), start).unwrap();
+    }
+    assert_eq!(limiter.clients(), 100);
+
+    // Clients are forgotten once their allowance has been replenished
+    limiter.check_at(ip(200), start + secs(60)).unwrap();
+    assert_eq!(limiter.clients(), 1);
+}
+
+#[test]
+fn test_rate_limit_max_clients() {
+    let limiter = RateLimiter::new(RateLimitConfig::new(2, secs(60)).with_max_clients(2));
+    let start = Instant::now();
+
+    limiter.check_at(ip(1), start).unwrap();
+    limiter.check_at(ip(1), start).unwrap();
+    limiter.check_at(ip(2), start + secs(1)).unwrap();
+
+    // The client closest to having its allowance replenished is forgotten to make room
+    limiter.check_at(ip(3), start + secs(2)).unwrap();
+    assert_eq!(limiter.clients(), 2);
+    assert!(limiter.check_at(ip(1), start + secs(2)).is_err());
+    assert_eq!(limiter.clients(), 2);
+}
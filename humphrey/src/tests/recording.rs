@@ -0,0 +1,174 @@
+use crate::http::request::{RequestError, RequestLimits};
+use crate::http::{Request, StatusCode};
+use crate::recording::{Recorder, RecordingConfig, RecordingSink};
+use crate::stream::Stream;
+
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use std::thread::spawn;
+use std::time::Duration;
+
+/// Sends the given bytes to a new connection and reads them back with recording enabled, capturing
+///   the body as well as the head if `body` is true.
+fn read_recorded(bytes: &'static [u8], body: bool) -> (Result<Request, RequestError>, Vec<u8>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (server, addr) = listener.accept().unwrap();
+
+    client.write_all(bytes).unwrap();
+    drop(client);
+
+    let mut stream = Stream::Tcp(server);
+    let mut raw = Vec::new();
+    let request = Request::from_stream_with_options(
+        &mut stream,
+        addr,
+        None,
+        Some(&mut raw),
+        body,
+        None,
+        None,
+        None,
+        &RequestLimits::default(),
+    );
+
+    (request, raw)
+}
+
+#[test]
+fn test_recording_redacts_headers() {
+    let (request, raw) = read_recorded(
+        b"POST /api/login HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer secret\r\nContent-Length: 5\r\n\r\nhello",
+        true,
+    );
+    let request = request.unwrap();
+    assert!(raw.ends_with(b"\r\n\r\nhello"));
+
+    let (tx, rx) = channel();
+    let recorder = Recorder::new(RecordingConfig::new(RecordingSink::Channel(tx)).with_body(true));
+    recorder.record(
+        "127.0.0.1:80".parse().unwrap(),
+        &raw,
+        Some(&request),
+        StatusCode::OK,
+        Duration::from_millis(3),
+    );
+
+    let recording = rx.try_recv().unwrap();
+    assert_eq!(
+        recording.head,
+        b"POST /api/login HTTP/1.1\r\nHost: localhost\r\nAuthorization: [REDACTED]\r\nContent-Length: 5\r\n\r\n"
+    );
+    assert_eq!(recording.body, Some(b"hello".to_vec()));
+    assert!(recording.parsed);
+    assert_eq!(recording.status, StatusCode::OK);
+}
+
+#[test]
+fn test_recording_malformed_request() {
+    let (request, raw) = read_recorded(
+        b"GET /broken HTTP/1.1\r\nCookie: a=b\r\nNot A Header\r\n\r\n",
+        false,
+    );
+    assert_eq!(request.unwrap_err(), RequestError::Request);
+
+    let (tx, rx) = channel();
+    let recorder = Recorder::new(
+        RecordingConfig::new(RecordingSink::Channel(tx))
+            .with_route("/broken")
+            .with_status_class(4),
+    );
+
+    let peer = "127.0.0.1:80".parse().unwrap();
+    recorder.record(peer, &raw, None, StatusCode::BadRequest, Duration::ZERO);
+    recorder.record(peer, &raw, None, StatusCode::OK, Duration::ZERO);
+
+    let recording = rx.try_recv().unwrap();
+    assert_eq!(
+        recording.head,
+        b"GET /broken HTTP/1.1\r\nCookie: [REDACTED]\r\nNot A Header\r\n\r\n"
+    );
+    assert!(!recording.parsed);
+    assert!(rx.try_recv().is_err());
+}
+
+#[test]
+fn test_recording_sampling_and_limit() {
+    let raw = b"GET / HTTP/1.1\r\n\r\n";
+    let peer = "127.0.0.1:80".parse().unwrap();
+
+    let (tx, rx) = channel();
+    let recorder = Recorder::new(
+        RecordingConfig::new(RecordingSink::Channel(tx))
+            .with_sampling(2)
+            .with_byte_limit(raw.len() * 2),
+    );
+
+    for _ in 0..8 {
+        recorder.record(peer, raw, None, StatusCode::OK, Duration::ZERO);
+    }
+
+    assert_eq!(rx.try_iter().count(), 2);
+}
+
+#[test]
+fn test_recording_head_only() {
+    // The body is read with the head, but only the head is captured
+    let (request, raw) = read_recorded(
+        b"POST /upload HTTP/1.1\r\nContent-Length: 11\r\n\r\nhello world",
+        false,
+    );
+    assert_eq!(request.unwrap().content, Some(b"hello world".to_vec()));
+    assert_eq!(raw, b"POST /upload HTTP/1.1\r\nContent-Length: 11\r\n\r\n");
+}
+
+#[test]
+fn test_recording_concurrent_names() {
+    let directory = std::env::temp_dir().join(format!("humphrey-recording-{}", std::process::id()));
+    std::fs::remove_dir_all(&directory).ok();
+    std::fs::create_dir_all(&directory).unwrap();
+
+    let recorder = Arc::new(Recorder::new(RecordingConfig::new(
+        RecordingSink::Directory(directory.clone()),
+    )));
+
+    // Each recording gets its own files, even when they are recorded at the same time
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            let recorder = recorder.clone();
+
+            spawn(move || {
+                for _ in 0..10 {
+                    let peer = "127.0.0.1:80".parse().unwrap();
+                    let raw = b"GET / HTTP/1.1\r\n\r\n";
+                    recorder.record(peer, raw, None, StatusCode::OK, Duration::ZERO);
+                }
+            })
+        })
+        .collect();
+
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    // Files are named by the timestamp and the index of the recording among those which matched
+    let mut indices: Vec<usize> = std::fs::read_dir(&directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "http")
+        })
+        .map(|path| {
+            let name = path.file_stem().unwrap().to_str().unwrap().to_string();
+            name.rsplit('-').next().unwrap().parse().unwrap()
+        })
+        .collect();
+    indices.sort_unstable();
+
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    assert_eq!(indices, (0..80).collect::<Vec<_>>());
+}
@@ -1,13 +1,16 @@
 #![allow(dead_code, unused_imports)]
+use crate::cancellation::CancellationToken;
 use crate::http::address::Address;
 use crate::http::cookie::Cookie;
 use crate::http::headers::{Header, HeaderType, Headers};
 use crate::http::method::Method;
+use crate::http::request::{BodyState, RequestError};
 use crate::http::Request;
+use crate::route::RouteParams;
 use crate::tests::mock_stream::MockStream;
 
 use std::collections::{BTreeMap, VecDeque};
-use std::io::Read;
+use std::io::{ErrorKind, Read};
 use std::iter::FromIterator;
 use std::net::{SocketAddr, ToSocketAddrs};
 
@@ -32,6 +35,27 @@ fn test_request_from_stream() {
     assert_eq!(request.headers, expected_headers);
 }
 
+#[test]
+fn test_request_uri_decoding() {
+    let test_data = b"GET /files/hello%20world%2Ftxt?name=a%20b HTTP/1.1\r\n\r\n";
+    let mut stream = MockStream::with_data(VecDeque::from_iter(test_data.iter().cloned()));
+    let request = Request::from_stream(&mut stream, "1.2.3.4:5678".parse().unwrap()).unwrap();
+
+    assert_eq!(request.uri, "/files/hello world%2Ftxt");
+    assert_eq!(request.uri_raw, "/files/hello%20world%2Ftxt");
+    assert_eq!(request.query, "name=a%20b");
+
+    // Serialising the request encodes the path again
+    let bytes: Vec<u8> = request.into();
+    assert!(bytes.starts_with(b"GET /files/hello%20world%2Ftxt?name=a%20b HTTP/1.1\r\n"));
+
+    let test_data = b"GET /files/%zz HTTP/1.1\r\n\r\n";
+    let mut stream = MockStream::with_data(VecDeque::from_iter(test_data.iter().cloned()));
+    let request = Request::from_stream(&mut stream, "1.2.3.4:5678".parse().unwrap());
+
+    assert_eq!(request.unwrap_err(), RequestError::Request);
+}
+
 #[test]
 fn test_cookie_request() {
     let test_data = b"GET / HTTP/1.1\r\nHost: localhost\r\nCookie: foo=bar; baz=qux\r\n\r\n";
@@ -47,16 +71,46 @@ fn test_cookie_request() {
     assert_eq!(request.get_cookie("sus"), None);
 }
 
+#[test]
+fn test_quoted_cookie_request() {
+    let test_data = b"GET / HTTP/1.1\r\nCookie: foo = \"quoted value\" ;bar=baz;;invalid\r\nCookie: qux=\"\"\r\n\r\n";
+    let mut stream = MockStream::with_data(VecDeque::from_iter(test_data.iter().cloned()));
+    let request = Request::from_stream(&mut stream, "1.2.3.4:5678".parse().unwrap()).unwrap();
+
+    assert_eq!(
+        request.get_cookies(),
+        vec![
+            Cookie::new("foo", "quoted value"),
+            Cookie::new("bar", "baz"),
+            Cookie::new("qux", "")
+        ]
+    );
+    assert_eq!(
+        request.get_cookie("foo"),
+        Some(Cookie::new("foo", "quoted value"))
+    );
+}
+
 #[test]
 fn test_bytes_from_request() {
     let mut test_data = Request {
         method: Method::Get,
+        overridden_method: None,
         uri: "/test".into(),
+        uri_raw: "/test".into(),
         query: "foo=bar".into(),
         version: "HTTP/1.1".into(),
         headers: Headers::new(),
         content: Some(b"this is a test".to_vec()),
+        body_state: BodyState::Complete,
+        trailers: Headers::new(),
         address: Address::new("1.2.3.4:5678").unwrap(),
+        secure: false,
+        local_addr: None,
+        deadline: None,
+        trace: None,
+        cancellation: CancellationToken::default(),
+        params: RouteParams::default(),
     };
 
     test_data.headers.add(HeaderType::ContentLength, "14");
@@ -69,6 +123,67 @@ fn test_bytes_from_request() {
     assert_eq!(bytes, expected_bytes);
 }
 
+#[test]
+fn test_bytes_from_request_with_trailers() {
+    // The body is re-chunked so the trailers parsed from a chunked request can be relayed
+    let mut stream = MockStream::with_data(VecDeque::from_iter(CHUNKED_REQUEST.iter().cloned()));
+    let request = Request::from_stream(&mut stream, "1.2.3.4:5678".parse().unwrap()).unwrap();
+
+    let expected_bytes = b"POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nb\r\nhello world\r\n0\r\nserver-timing: total;dur=12\r\n\r\n".to_vec();
+
+    let bytes: Vec<u8> = request.into();
+
+    assert_eq!(bytes, expected_bytes);
+}
+
+#[test]
+fn test_bytes_from_request_with_forbidden_trailers() {
+    let mut stream = MockStream::with_data(VecDeque::from_iter(CHUNKED_REQUEST.iter().cloned()));
+    let mut request = Request::from_stream(&mut stream, "1.2.3.4:5678".parse().unwrap()).unwrap();
+
+    // Trailers set after parsing are filtered when the request is serialised
+    request.trailers.add(HeaderType::ContentLength, "0");
+    request.trailers.add(HeaderType::Host, "evil.example");
+
+    let expected_bytes = b"POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nb\r\nhello world\r\n0\r\nserver-timing: total;dur=12\r\n\r\n".to_vec();
+
+    let bytes: Vec<u8> = request.into();
+    assert_eq!(bytes, expected_bytes);
+
+    // With only forbidden trailers, the body is not re-chunked
+    let test_data = b"POST /upload HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello";
+    let mut stream = MockStream::with_data(VecDeque::from_iter(test_data.iter().cloned()));
+    let mut request = Request::from_stream(&mut stream, "1.2.3.4:5678".parse().unwrap()).unwrap();
+    request
+        .trailers
+        .add(HeaderType::TransferEncoding, "chunked");
+
+    let bytes: Vec<u8> = request.into();
+    assert_eq!(bytes, test_data.to_vec());
+}
+
+#[test]
+fn test_repeated_headers() {
+    let test_data =
+        b"GET / HTTP/1.1\r\nVia: 1.1 first\r\nHost: localhost\r\nVia: 1.1 second\r\n\r\n";
+    let mut stream = MockStream::with_data(VecDeque::from_iter(test_data.iter().cloned()));
+    let request = Request::from_stream(&mut stream, "1.2.3.4:5678".parse().unwrap()).unwrap();
+
+    assert_eq!(request.headers.len(), 3);
+    assert_eq!(request.headers.get("Via"), Some("1.1 first"));
+    assert_eq!(
+        request.headers.get_all("Via"),
+        vec!["1.1 first", "1.1 second"]
+    );
+
+    // Each instance is serialised on its own line, in the order it was received
+    let bytes: Vec<u8> = request.into();
+    assert_eq!(
+        bytes,
+        b"GET / HTTP/1.1\r\nHost: localhost\r\nVia: 1.1 first\r\nVia: 1.1 second\r\n\r\n".to_vec()
+    );
+}
+
 #[test]
 fn test_proxied_request_from_stream() {
     let test_data =
@@ -97,3 +212,249 @@ fn test_proxied_request_from_stream() {
 
     assert_eq!(request.headers, expected_headers);
 }
+
+/// Parses a request declaring a 100-byte body, of which only the given number of bytes are sent.
+fn truncated_request(sent: usize) -> Request {
+    let mut test_data = b"POST /upload HTTP/1.1\r\nContent-Length: 100\r\n\r\n".to_vec();
+    test_data.extend(vec![b'a'; sent]);
+    let mut stream = MockStream::with_data(VecDeque::from(test_data));
+
+    Request::from_stream(&mut stream, "1.2.3.4:5678".parse().unwrap()).unwrap()
+}
+
+#[test]
+fn test_truncated_body() {
+    for sent in [0, 50, 99] {
+        let request = truncated_request(sent);
+
+        assert_eq!(request.content, Some(vec![b'a'; sent]));
+        assert_eq!(
+            request.body_state,
+            BodyState::Truncated {
+                received: sent,
+                expected: 100
+            }
+        );
+        assert!(!request.body_complete());
+    }
+
+    let request = truncated_request(100);
+    assert_eq!(request.content, Some(vec![b'a'; 100]));
+    assert_eq!(request.body_state, BodyState::Complete);
+    assert!(request.body_complete());
+}
+
+#[test]
+fn test_body_timeout() {
+    let test_data = b"POST /upload HTTP/1.1\r\nContent-Length: 10\r\n\r\nhello";
+    let mut stream = MockStream::with_data_then_error(
+        VecDeque::from_iter(test_data.iter().cloned()),
+        ErrorKind::TimedOut,
+    );
+    let request = Request::from_stream(&mut stream, "1.2.3.4:5678".parse().unwrap()).unwrap();
+
+    assert_eq!(request.content, Some(b"hello".to_vec()));
+    assert_eq!(
+        request.body_state,
+        BodyState::Truncated {
+            received: 5,
+            expected: 10
+        }
+    );
+}
+
+#[test]
+fn test_undeclared_body_length() {
+    let test_data = b"POST /upload HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\nhello";
+    let mut stream = MockStream::with_data(VecDeque::from_iter(test_data.iter().cloned()));
+    let request = Request::from_stream(&mut stream, "1.2.3.4:5678".parse().unwrap()).unwrap();
+
+    assert_eq!(request.content, None);
+    assert_eq!(request.body_state, BodyState::Unknown);
+    assert!(!request.body_complete());
+}
+
+/// A chunked request with a chunk extension and a trailer.
+const CHUNKED_REQUEST: &[u8] = b"POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5;name=value\r\nhello\r\n6\r\n world\r\n0\r\nServer-Timing: total;dur=12\r\n\r\n";
+
+#[test]
+fn test_chunked_body() {
+    // Reading a few bytes at a time splits the size lines, data and CRLFs across reads
+    for read_size in [1, 2, 3, 5, 8, 13, usize::MAX] {
+        let mut stream = MockStream::with_data_in_reads(
+            VecDeque::from_iter(CHUNKED_REQUEST.iter().cloned()),
+            read_size,
+        );
+        let request = Request::from_stream(&mut stream, "1.2.3.4:5678".parse().unwrap()).unwrap();
+
+        assert_eq!(request.content, Some(b"hello world".to_vec()));
+        assert_eq!(request.body_state, BodyState::Complete);
+        assert_eq!(request.headers.get(HeaderType::ContentLength), Some("11"));
+        assert_eq!(request.headers.get(HeaderType::TransferEncoding), None);
+        assert_eq!(request.headers.get("Server-Timing"), None);
+        assert_eq!(request.trailers.get("Server-Timing"), Some("total;dur=12"));
+    }
+}
+
+#[test]
+fn test_chunked_body_trailers_kept_apart() {
+    let test_data = b"POST /upload HTTP/1.1\r\nHost: example.com\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\nHost: admin.internal\r\nAuthorization: Bearer token\r\nX-Checksum: 5d41\r\n\r\n";
+    let mut stream = MockStream::with_data(VecDeque::from_iter(test_data.iter().cloned()));
+    let request = Request::from_stream(&mut stream, "1.2.3.4:5678".parse().unwrap()).unwrap();
+
+    assert_eq!(request.headers.get(HeaderType::Host), Some("example.com"));
+    assert_eq!(request.headers.get(HeaderType::Authorization), None);
+    assert_eq!(request.headers.get("X-Checksum"), None);
+
+    // Trailers which could change routing or authorisation are dropped altogether
+    assert_eq!(request.trailers.get(HeaderType::Host), None);
+    assert_eq!(request.trailers.get(HeaderType::Authorization), None);
+    assert_eq!(request.trailers.get("X-Checksum"), Some("5d41"));
+}
+
+#[test]
+fn test_bare_line_feed() {
+    // A multibyte character right before a bare LF must not be sliced through
+    for test_data in [
+        "GET / HTTP/1.1\r\nX-Name: caf\u{e9}\n\r\n",
+        "POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n0\r\nX-Name: caf\u{e9}\n\r\n",
+    ] {
+        let mut stream = MockStream::with_data(VecDeque::from_iter(test_data.bytes()));
+        let request = Request::from_stream(&mut stream, "1.2.3.4:5678".parse().unwrap());
+
+        assert_eq!(
+            request.unwrap_err(),
+            RequestError::Request,
+            "{:?}",
+            test_data
+        );
+    }
+}
+
+#[test]
+fn test_chunked_body_codings() {
+    let test_data =
+        b"POST /upload HTTP/1.1\r\nTransfer-Encoding: gzip, Chunked\r\n\r\n2\r\n\x1f\x8b\r\n0\r\n\r\n";
+    let mut stream = MockStream::with_data(VecDeque::from_iter(test_data.iter().cloned()));
+    let request = Request::from_stream(&mut stream, "1.2.3.4:5678".parse().unwrap()).unwrap();
+
+    // The remaining codings are kept so the body can still be decoded
+    assert_eq!(request.content, Some(b"\x1f\x8b".to_vec()));
+    assert_eq!(
+        request.headers.get(HeaderType::TransferEncoding),
+        Some("gzip")
+    );
+    assert_eq!(request.headers.get(HeaderType::ContentLength), Some("2"));
+}
+
+#[test]
+fn test_malformed_chunked_body() {
+    let head = "POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n";
+
+    for body in [
+        "zz\r\nhello\r\n0\r\n\r\n",
+        "\r\nhello\r\n0\r\n\r\n",
+        "-5\r\nhello\r\n0\r\n\r\n",
+        "0x5\r\nhello\r\n0\r\n\r\n",
+        "ffffffffffffffffffff\r\nhello\r\n0\r\n\r\n",
+        "5\r\nhelloXX\r\n0\r\n\r\n",
+        "5\r\nhello\r\n0\r\nbroken trailer\r\n\r\n",
+    ] {
+        let test_data = format!("{}{}", head, body);
+        let mut stream = MockStream::with_data(VecDeque::from_iter(test_data.bytes()));
+        let request = Request::from_stream(&mut stream, "1.2.3.4:5678".parse().unwrap());
+
+        assert_eq!(request.unwrap_err(), RequestError::Request, "{:?}", body);
+    }
+
+    // Both ways of framing the body cannot be used at once
+    let test_data = b"POST /upload HTTP/1.1\r\nContent-Length: 5\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n";
+    let mut stream = MockStream::with_data(VecDeque::from_iter(test_data.iter().cloned()));
+    let request = Request::from_stream(&mut stream, "1.2.3.4:5678".parse().unwrap());
+
+    assert_eq!(request.unwrap_err(), RequestError::Request);
+}
+
+#[test]
+fn test_truncated_chunked_body() {
+    let test_data =
+        b"POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6\r\n wo";
+    let mut stream = MockStream::with_data_then_error(
+        VecDeque::from_iter(test_data.iter().cloned()),
+        ErrorKind::TimedOut,
+    );
+    let request = Request::from_stream(&mut stream, "1.2.3.4:5678".parse().unwrap()).unwrap();
+
+    assert_eq!(request.content, Some(b"hello wo".to_vec()));
+    assert_eq!(
+        request.body_state,
+        BodyState::Truncated {
+            received: 8,
+            expected: 11
+        }
+    );
+
+    // The stream ending before the final chunk also truncates the body
+    let test_data = b"POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n";
+    let mut stream = MockStream::with_data(VecDeque::from_iter(test_data.iter().cloned()));
+    let request = Request::from_stream(&mut stream, "1.2.3.4:5678".parse().unwrap()).unwrap();
+
+    assert_eq!(request.content, Some(b"hello".to_vec()));
+    assert!(!request.body_complete());
+}
+
+#[test]
+fn test_truncated_body_closes_connection() {
+    use crate::http::{Response, StatusCode};
+    use crate::monitor::event::{Event, EventType};
+    use crate::monitor::MonitorConfig;
+    use crate::App;
+
+    use std::io::Write;
+    use std::net::{Shutdown, TcpListener, TcpStream};
+    use std::sync::mpsc::channel;
+    use std::thread::{sleep, spawn};
+    use std::time::Duration;
+
+    let (shutdown_tx, shutdown_rx) = channel();
+    let (monitor_tx, monitor_rx) = channel::<Event>();
+
+    let app: App<()> = App::new_with_config(2, ())
+        .with_stateless_route("/upload", |request: Request| {
+            Response::new(
+                StatusCode::OK,
+                format!("complete: {}", request.body_complete()),
+            )
+        })
+        .with_monitor(
+            MonitorConfig::new(monitor_tx).with_subscription_to(EventType::RequestBodyIncomplete),
+        )
+        .with_shutdown(shutdown_rx);
+
+    let addr = crate::tests::start_app(app);
+
+    // Send half of the declared body, then stop sending
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream
+        .write_all(
+            b"POST /upload HTTP/1.1\r\nConnection: Keep-Alive\r\nContent-Length: 10\r\n\r\nhello",
+        )
+        .unwrap();
+    stream.shutdown(Shutdown::Write).unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("Connection: Close\r\n"));
+    assert!(response.contains("\r\n\r\ncomplete: false"));
+
+    let event = monitor_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(event.kind, EventType::RequestBodyIncomplete);
+    assert_eq!(event.info.as_deref(), Some("5 of 10 bytes received"));
+
+    shutdown_tx.send(()).unwrap();
+}
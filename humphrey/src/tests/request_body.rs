@@ -0,0 +1,416 @@
+use crate::http::headers::{HeaderType, Headers};
+use crate::http::request::RequestLimits;
+use crate::tests::mock_stream::MockStream;
+
+use std::collections::VecDeque;
+use std::io::ErrorKind;
+use std::iter::FromIterator;
+
+#[cfg(not(feature = "tokio"))]
+use crate::http::body::RequestBody;
+#[cfg(not(feature = "tokio"))]
+use std::io::Read;
+
+#[cfg(feature = "tokio")]
+use crate::http::body::DeferredBody;
+#[cfg(feature = "tokio")]
+use crate::http::{Response, StatusCode};
+#[cfg(feature = "tokio")]
+use futures::executor::block_on;
+#[cfg(feature = "tokio")]
+use tokio::io::AsyncReadExt;
+
+/// Builds headers from `(name, value)` pairs.
+fn headers(pairs: &[(HeaderType, &str)]) -> Headers {
+    let mut headers = Headers::new();
+
+    for (name, value) in pairs {
+        headers.add(name.clone(), value);
+    }
+
+    headers
+}
+
+/// Creates a stream which returns the data a few bytes at a time.
+fn mock(data: &[u8]) -> MockStream {
+    MockStream::with_data_in_reads(VecDeque::from_iter(data.iter().copied()), 3)
+}
+
+/// Returns what remains on the stream, which should be the start of the next request.
+#[cfg(not(feature = "tokio"))]
+fn remaining(mut stream: MockStream) -> Vec<u8> {
+    let mut rest = Vec::new();
+    stream.read_to_end(&mut rest).unwrap();
+    rest
+}
+
+#[test]
+#[cfg(not(feature = "tokio"))]
+fn test_length_body() {
+    let headers = headers(&[(HeaderType::ContentLength, "11")]);
+    let mut stream = mock(b"lo worldGET / HTTP/1.1\r\n\r\n");
+
+    let mut body = RequestBody::new(
+        b"hel".to_vec(),
+        &mut stream,
+        &headers,
+        &RequestLimits::default(),
+    );
+    assert_eq!(body.content_length(), Some(11));
+
+    let mut content = Vec::new();
+    body.read_to_end(&mut content).unwrap();
+
+    assert_eq!(content, b"hello world");
+    assert!(body.is_complete());
+
+    // Nothing after the end of the body is read
+    drop(body);
+    assert_eq!(remaining(stream), b"GET / HTTP/1.1\r\n\r\n");
+}
+
+#[test]
+#[cfg(not(feature = "tokio"))]
+fn test_chunked_body() {
+    let headers = headers(&[(HeaderType::TransferEncoding, "chunked")]);
+    let mut stream =
+        mock(b"\r\nhello\r\n6;ext=1\r\n world\r\n0\r\nExpires: never\r\n\r\nGET / HTTP/1.1");
+
+    let mut body = RequestBody::new(
+        b"5".to_vec(),
+        &mut stream,
+        &headers,
+        &RequestLimits::default(),
+    );
+    assert_eq!(body.content_length(), None);
+
+    let mut content = Vec::new();
+    body.read_to_end(&mut content).unwrap();
+
+    assert_eq!(content, b"hello world");
+    assert!(body.is_complete());
+
+    drop(body);
+    assert_eq!(remaining(stream), b"GET / HTTP/1.1");
+}
+
+#[test]
+#[cfg(not(feature = "tokio"))]
+fn test_truncated_body() {
+    let headers = headers(&[(HeaderType::ContentLength, "20")]);
+    let mut stream = mock(b"hello");
+
+    let mut body = RequestBody::new(Vec::new(), &mut stream, &headers, &RequestLimits::default());
+
+    let mut content = Vec::new();
+    let error = body.read_to_end(&mut content).unwrap_err();
+
+    assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+    assert_eq!(content, b"hello");
+    assert!(!body.is_complete());
+
+    // The body cannot be read any further once it has failed
+    assert!(body.read(&mut [0; 8]).is_err());
+}
+
+#[test]
+#[cfg(not(feature = "tokio"))]
+fn test_invalid_bodies() {
+    let chunked = headers(&[(HeaderType::TransferEncoding, "chunked")]);
+    let limits = RequestLimits {
+        max_body_size: Some(8),
+        ..Default::default()
+    };
+
+    let mut stream = mock(b"5\r\nhello\r\n5\r\nworld\r\n0\r\n\r\n");
+    let mut body = RequestBody::new(Vec::new(), &mut stream, &chunked, &limits);
+    let error = body.read_to_end(&mut Vec::new()).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+    let mut stream = mock(b"zz\r\nhello\r\n0\r\n\r\n");
+    let mut body = RequestBody::new(Vec::new(), &mut stream, &chunked, &limits);
+    let error = body.read_to_end(&mut Vec::new()).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+    let mut stream = mock(b"5\r\nhelloXX0\r\n\r\n");
+    let mut body = RequestBody::new(Vec::new(), &mut stream, &chunked, &limits);
+    let error = body.read_to_end(&mut Vec::new()).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+    // A body which is neither chunked nor of a declared length cannot be read
+    let unknown = headers(&[(HeaderType::TransferEncoding, "gzip")]);
+    let mut stream = mock(b"hello");
+    let mut body = RequestBody::new(Vec::new(), &mut stream, &unknown, &limits);
+    let error = body.read_to_end(&mut Vec::new()).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    assert!(!body.finish());
+}
+
+#[test]
+#[cfg(not(feature = "tokio"))]
+fn test_finish() {
+    let headers = headers(&[(HeaderType::ContentLength, "100")]);
+    let data = [b'a'; 100];
+    let mut stream = mock(&data);
+
+    let mut body = RequestBody::new(Vec::new(), &mut stream, &headers, &RequestLimits::default());
+    body.read_exact(&mut [0; 10]).unwrap();
+
+    // Small remainders are discarded so the connection can be reused
+    assert!(body.finish());
+    assert!(body.is_complete());
+
+    let headers = self::headers(&[(HeaderType::ContentLength, "100000")]);
+    let data = vec![b'a'; 100000];
+    let mut stream = mock(&data);
+
+    let mut body = RequestBody::new(Vec::new(), &mut stream, &headers, &RequestLimits::default());
+    assert!(!body.finish());
+}
+
+#[test]
+#[cfg(feature = "tokio")]
+fn test_chunked_body() {
+    let headers = headers(&[(HeaderType::TransferEncoding, "chunked")]);
+    let mut stream = mock(b"\r\nhello\r\n6\r\n world\r\n0\r\n\r\nGET / HTTP/1.1");
+
+    let mut body = DeferredBody::new(
+        b"5".to_vec(),
+        &mut stream,
+        &headers,
+        &RequestLimits::default(),
+    );
+
+    let response = block_on(body.stream(|mut body| async move {
+        let mut content = Vec::new();
+        body.read_to_end(&mut content).await.unwrap();
+
+        assert!(body.is_complete());
+        Response::new(StatusCode::OK, content)
+    }));
+
+    assert_eq!(response.body, b"hello world");
+    assert!(block_on(body.finish()));
+
+    drop(body);
+    let mut rest = Vec::new();
+    block_on(stream.read_to_end(&mut rest)).unwrap();
+    assert_eq!(rest, b"GET / HTTP/1.1");
+}
+
+#[test]
+#[cfg(feature = "tokio")]
+fn test_truncated_body() {
+    let headers = headers(&[(HeaderType::ContentLength, "20")]);
+    let mut stream = mock(b"hello");
+
+    let mut body = DeferredBody::new(Vec::new(), &mut stream, &headers, &RequestLimits::default());
+
+    let response = block_on(body.stream(|mut body| async move {
+        let mut content = Vec::new();
+        let error = body.read_to_end(&mut content).await.unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+        assert!(!body.is_complete());
+        Response::new(StatusCode::BadRequest, content)
+    }));
+
+    assert_eq!(response.body, b"hello");
+    assert!(!block_on(body.finish()));
+}
+
+#[test]
+#[cfg(feature = "tokio")]
+fn test_finish() {
+    // The handler returns without reading the body, so it is discarded afterwards
+    let headers = headers(&[(HeaderType::ContentLength, "100")]);
+    let data = [b'a'; 100];
+    let mut stream = mock(&data);
+
+    let mut body = DeferredBody::new(Vec::new(), &mut stream, &headers, &RequestLimits::default());
+    block_on(body.stream(|_| async { Response::empty(StatusCode::OK) }));
+    assert!(block_on(body.finish()));
+
+    let headers = self::headers(&[(HeaderType::ContentLength, "100000")]);
+    let data = vec![b'a'; 100000];
+    let mut stream = mock(&data);
+
+    let mut body = DeferredBody::new(Vec::new(), &mut stream, &headers, &RequestLimits::default());
+    block_on(body.stream(|_| async { Response::empty(StatusCode::OK) }));
+    assert!(!block_on(body.finish()));
+}
+
+#[cfg(not(feature = "tokio"))]
+mod app {
+    use crate::http::body::RequestBody;
+    use crate::http::headers::HeaderType;
+    use crate::http::{Request, Response, StatusCode};
+    use crate::App;
+
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpStream;
+    use std::sync::mpsc::{channel, Sender};
+    use std::sync::Arc;
+
+    use std::time::Duration;
+
+    /// Counts the bytes of the upload without keeping them, as a handler copying to disk would.
+    fn upload(request: Request, body: &mut RequestBody, _: Arc<()>) -> Response {
+        assert!(request.content.is_none());
+
+        match std::io::copy(body, &mut std::io::sink()) {
+            Ok(length) => Response::new(StatusCode::OK, length.to_string()),
+            Err(_) => Response::empty(StatusCode::BadRequest),
+        }
+    }
+
+    /// Rejects the upload without reading any of it.
+    fn refuse(_: Request, _: &mut RequestBody, _: Arc<()>) -> Response {
+        Response::empty(StatusCode::Forbidden)
+    }
+
+    fn start_app() -> (String, Sender<()>) {
+        let (shutdown_tx, shutdown_rx) = channel();
+
+        let app: App<()> = App::new_with_config(2, ())
+            .with_max_body_size(usize::MAX)
+            .with_streaming_route("/upload", upload)
+            .with_streaming_route("/refuse", refuse)
+            .with_route("/*", |request: Request, _| {
+                let length = request.content.map_or(0, |content| content.len());
+                Response::new(StatusCode::OK, format!("buffered {}", length))
+            })
+            .with_shutdown(shutdown_rx);
+
+        let addr = crate::tests::start_app(app);
+
+        (addr, shutdown_tx)
+    }
+
+    /// Reads one response from the stream, returning its status line and body.
+    fn read_response(reader: &mut impl BufRead) -> (String, String) {
+        let mut status = String::new();
+        reader.read_line(&mut status).unwrap();
+
+        let mut length = 0;
+
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+
+            if line == "\r\n" {
+                break;
+            }
+
+            if let Some(value) = line.strip_prefix("Content-Length: ") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+
+        (
+            status.trim_end().to_string(),
+            String::from_utf8(body).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_streaming_route_keep_alive() {
+        let (addr, shutdown) = start_app();
+
+        let stream = TcpStream::connect(&addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+
+        let upload = vec![b'a'; 200_000];
+        write!(
+            writer,
+            "POST /upload HTTP/1.1\r\nConnection: keep-alive\r\nContent-Length: {}\r\n\r\n",
+            upload.len()
+        )
+        .unwrap();
+        writer.write_all(&upload).unwrap();
+
+        assert_eq!(
+            read_response(&mut reader),
+            ("HTTP/1.1 200 OK".to_string(), "200000".to_string())
+        );
+
+        writer
+            .write_all(b"POST /upload HTTP/1.1\r\nConnection: keep-alive\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n")
+            .unwrap();
+
+        assert_eq!(
+            read_response(&mut reader),
+            ("HTTP/1.1 200 OK".to_string(), "11".to_string())
+        );
+
+        // A small body which the handler did not read is discarded
+        writer
+            .write_all(b"POST /refuse HTTP/1.1\r\nConnection: keep-alive\r\nContent-Length: 5\r\n\r\nhello")
+            .unwrap();
+
+        assert_eq!(read_response(&mut reader).0, "HTTP/1.1 403 Forbidden");
+
+        // Other routes still have their bodies buffered
+        writer
+            .write_all(
+                b"POST /other HTTP/1.1\r\nConnection: keep-alive\r\nContent-Length: 5\r\n\r\nhello",
+            )
+            .unwrap();
+
+        assert_eq!(
+            read_response(&mut reader),
+            ("HTTP/1.1 200 OK".to_string(), "buffered 5".to_string())
+        );
+
+        shutdown.send(()).unwrap();
+    }
+
+    #[test]
+    fn test_unread_body_closes_connection() {
+        let (addr, shutdown) = start_app();
+
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        // The body is too large to discard, so the connection is closed instead of reading it all
+        stream
+            .write_all(b"POST /refuse HTTP/1.1\r\nConnection: keep-alive\r\nContent-Length: 1000000000\r\n\r\n")
+            .unwrap();
+        stream.write_all(&[b'a'; 100_000]).unwrap();
+
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response);
+
+        assert!(response.starts_with("HTTP/1.1 403 Forbidden"));
+        assert!(response.contains("Connection: Close"));
+
+        shutdown.send(()).unwrap();
+    }
+    #[test]
+    fn test_streaming_route_test_client() {
+        let client = App::<()>::new_with_config(1, ())
+            .with_streaming_route("/upload", upload)
+            .with_stateless_route("/download", |_| {
+                Response::from_reader(StatusCode::OK, &b"streamed"[..], 8)
+            })
+            .into_test_client();
+
+        // Uploads are streamed to the handler from the in-memory request
+        let response = client.post("/upload", vec![b'a'; 200_000]);
+        assert_eq!(response.body, b"200000");
+
+        // Streamed responses are read in full
+        let response = client.get("/download");
+        assert_eq!(response.body, b"streamed");
+        assert_eq!(response.headers.get(HeaderType::ContentLength), Some("8"));
+    }
+}
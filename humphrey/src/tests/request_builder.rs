@@ -0,0 +1,105 @@
+use crate::http::headers::HeaderType;
+use crate::http::method::Method;
+use crate::http::request::BodyState;
+use crate::http::{Request, Response, StatusCode};
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::{Arc, Mutex};
+
+#[derive(Default)]
+struct AppState {
+    notes: Mutex<Vec<String>>,
+}
+
+fn add_note(request: Request, state: Arc<AppState>) -> Response {
+    if request.method != Method::Post {
+        return Response::empty(StatusCode::MethodNotAllowed);
+    }
+
+    match request.content.map(String::from_utf8) {
+        Some(Ok(note)) if !note.is_empty() => {
+            state.notes.lock().unwrap().push(note);
+            Response::empty(StatusCode::Created)
+        }
+        _ => Response::empty(StatusCode::BadRequest),
+    }
+}
+
+#[test]
+fn test_builder_defaults() {
+    let request = Request::builder().build();
+
+    assert_eq!(request.method, Method::Get);
+    assert_eq!(request.uri, "/");
+    assert_eq!(request.uri_raw, "/");
+    assert_eq!(request.query, "");
+    assert_eq!(request.version, "HTTP/1.1");
+    assert!(request.headers.is_empty());
+    assert_eq!(request.content, None);
+    assert_eq!(request.body_state, BodyState::Complete);
+    assert_eq!(request.address.origin_addr, IpAddr::V4(Ipv4Addr::LOCALHOST));
+    assert!(request.address.proxies.is_empty());
+    assert!(!request.secure);
+}
+
+#[test]
+fn test_builder() {
+    let request = Request::builder()
+        .method(Method::Post)
+        .uri("/api/notes?draft=true")
+        .header(HeaderType::ContentType, "text/plain")
+        .header("X-Request-Id", "42")
+        .body("Hello, world!")
+        .address("1.2.3.4:5678".parse().unwrap())
+        .secure(true)
+        .build();
+
+    assert_eq!(request.method, Method::Post);
+    assert_eq!(request.uri, "/api/notes");
+    assert_eq!(request.query, "draft=true");
+    assert_eq!(
+        request.headers.get(HeaderType::ContentType),
+        Some("text/plain")
+    );
+    assert_eq!(request.headers.get("X-Request-Id"), Some("42"));
+    assert_eq!(request.headers.get(HeaderType::ContentLength), Some("13"));
+    assert_eq!(request.content, Some(b"Hello, world!".to_vec()));
+    assert_eq!(request.address.origin_addr.to_string(), "1.2.3.4");
+    assert_eq!(request.address.port, 5678);
+    assert!(request.secure);
+
+    let request = Request::builder()
+        .uri("/search?q=old")
+        .query("q=new")
+        .body("first")
+        .body("second")
+        .build();
+
+    assert_eq!(request.uri, "/search");
+    assert_eq!(request.query, "q=new");
+    assert_eq!(
+        request.headers.get_all(HeaderType::ContentLength),
+        vec!["6"]
+    );
+}
+
+#[test]
+fn test_builder_handler() {
+    let state = Arc::new(AppState::default());
+
+    let request = Request::builder()
+        .method(Method::Post)
+        .uri("/api/notes")
+        .body("Hello, world!")
+        .build();
+
+    assert_eq!(
+        add_note(request, state.clone()).status_code,
+        StatusCode::Created
+    );
+    assert_eq!(
+        add_note(Request::builder().uri("/api/notes").build(), state.clone()).status_code,
+        StatusCode::MethodNotAllowed
+    );
+    assert_eq!(*state.notes.lock().unwrap(), vec!["Hello, world!"]);
+}
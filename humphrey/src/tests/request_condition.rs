@@ -0,0 +1,135 @@
+use crate::http::headers::HeaderType;
+use crate::http::{Request, Response, StatusCode};
+use crate::{App, SubApp};
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Rejects requests from bots, and any request which would upload to `/forbidden`.
+fn reject_bots(request: &Request, _: Arc<()>) -> Option<Response> {
+    assert!(request.content.is_none());
+
+    let is_bot = request
+        .headers
+        .get(HeaderType::UserAgent)
+        .is_some_and(|agent| agent.contains("bot"));
+
+    (is_bot || request.uri == "/forbidden").then(|| Response::new(StatusCode::Forbidden, "go away"))
+}
+
+/// Starts an app with the bot-rejecting request condition, returning its address and shutdown signal.
+fn start_app() -> (String, Sender<()>) {
+    let (shutdown_tx, shutdown_rx) = channel();
+
+    let app: App<()> = App::new_with_config(2, ())
+        .with_max_body_size(usize::MAX)
+        .with_request_condition(reject_bots)
+        .with_stateless_route("/*", |request: Request| {
+            let length = request.content.map(|content| content.len()).unwrap_or(0);
+            Response::new(StatusCode::OK, format!("read {} bytes", length))
+        })
+        .with_shutdown(shutdown_rx);
+
+    let addr = crate::tests::start_app(app);
+
+    (addr, shutdown_tx)
+}
+
+#[test]
+fn test_rejected_before_body() {
+    let (addr, shutdown) = start_app();
+
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    // The declared body is never sent, so the server would hang if it tried to read it
+    stream
+        .write_all(b"POST /upload?file=huge HTTP/1.1\r\nUser-Agent: evilbot/1.0\r\nConnection: Keep-Alive\r\nContent-Length: 1073741824\r\n\r\n")
+        .unwrap();
+
+    let start = Instant::now();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+    let response = String::from_utf8(response).unwrap();
+
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert!(response.starts_with("HTTP/1.1 403 Forbidden\r\n"));
+    assert!(response.contains("Connection: Close\r\n"));
+    assert!(response.contains("\r\n\r\ngo away"));
+
+    shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_rejected_small_body_drained() {
+    let (addr, shutdown) = start_app();
+
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    stream
+        .write_all(
+            b"POST /forbidden HTTP/1.1\r\nConnection: Keep-Alive\r\nContent-Length: 5\r\n\r\nhello",
+        )
+        .unwrap();
+
+    let response = Response::from_stream(&mut stream).unwrap();
+    assert_eq!(response.status_code, StatusCode::Forbidden);
+    assert_eq!(
+        response.headers.get(HeaderType::Connection),
+        Some("Keep-Alive")
+    );
+
+    // The connection is reused, and the next request's body is read as normal
+    stream
+        .write_all(b"POST /upload HTTP/1.1\r\nConnection: Close\r\nContent-Length: 5\r\n\r\nhello")
+        .unwrap();
+
+    let response = Response::from_stream(&mut stream).unwrap();
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body, b"read 5 bytes");
+
+    shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_trailers_not_trusted() {
+    let (shutdown_tx, shutdown_rx) = channel();
+
+    let admin =
+        SubApp::new().with_stateless_route("/*", |_| Response::new(StatusCode::OK, "admin"));
+
+    let app: App<()> = App::new_with_config(2, ())
+        .with_host("admin.internal", admin)
+        .with_request_condition(reject_bots)
+        .with_stateless_route("/*", |request: Request| {
+            let authorised = request.headers.get(HeaderType::Authorization).is_some();
+            Response::new(StatusCode::OK, format!("authorised: {}", authorised))
+        })
+        .with_shutdown(shutdown_rx);
+
+    let addr = crate::tests::start_app(app);
+
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    // Trailers arrive after the head has been routed and approved, so they must not be merged into it
+    stream
+        .write_all(b"POST /upload HTTP/1.1\r\nHost: example.com\r\nConnection: Close\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\nHost: admin.internal\r\nAuthorization: Bearer token\r\nUser-Agent: evilbot/1.0\r\n\r\n")
+        .unwrap();
+
+    let response = Response::from_stream(&mut stream).unwrap();
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body, b"authorised: false");
+
+    shutdown_tx.send(()).unwrap();
+}
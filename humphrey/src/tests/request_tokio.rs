@@ -1,14 +1,21 @@
 #![allow(dead_code, unused_imports)]
 
+use crate::cancellation::CancellationToken;
 use crate::http::address::Address;
 use crate::http::cookie::Cookie;
 use crate::http::headers::{Header, HeaderType, Headers};
 use crate::http::method::Method;
+use crate::http::request::{
+    BodyState, RequestError, RequestLimits, RequestPart, DEFAULT_MAX_BODY_SIZE,
+    DEFAULT_MAX_HEADER_SIZE,
+};
 use crate::http::Request;
+use crate::route::RouteParams;
+use crate::security::SecurityOptions;
 use crate::tests::mock_stream::MockStream;
 
 use std::collections::{BTreeMap, VecDeque};
-use std::io::Read;
+use std::io::{ErrorKind, Read};
 use std::iter::FromIterator;
 use std::net::{SocketAddr, ToSocketAddrs};
 
@@ -61,12 +68,22 @@ fn test_cookie_request() {
 fn test_bytes_from_request() {
     let mut test_data = Request {
         method: Method::Get,
+        overridden_method: None,
         uri: "/test".into(),
+        uri_raw: "/test".into(),
         query: "foo=bar".into(),
         version: "HTTP/1.1".into(),
         headers: Headers::new(),
         content: Some(b"this is a test".to_vec()),
+        body_state: BodyState::Complete,
+        trailers: Headers::new(),
         address: Address::new("1.2.3.4:5678").unwrap(),
+        secure: false,
+        local_addr: None,
+        deadline: None,
+        trace: None,
+        cancellation: CancellationToken::default(),
+        params: RouteParams::default(),
     };
 
     test_data.headers.add(HeaderType::ContentLength, "14");
@@ -110,3 +127,211 @@ fn test_proxied_request_from_stream() {
 
     assert_eq!(request.headers, expected_headers);
 }
+
+/// Parses a request declaring a 100-byte body, of which only the given number of bytes are sent.
+fn truncated_request(sent: usize) -> Request {
+    let mut test_data = b"POST /upload HTTP/1.1\r\nContent-Length: 100\r\n\r\n".to_vec();
+    test_data.extend(vec![b'a'; sent]);
+    let mut stream = MockStream::with_data(VecDeque::from(test_data));
+
+    block_on(Request::from_stream(
+        &mut stream,
+        "1.2.3.4:5678".parse().unwrap(),
+    ))
+    .unwrap()
+}
+
+#[test]
+fn test_truncated_body() {
+    for sent in [0, 50, 99] {
+        let request = truncated_request(sent);
+
+        assert_eq!(request.content, Some(vec![b'a'; sent]));
+        assert_eq!(
+            request.body_state,
+            BodyState::Truncated {
+                received: sent,
+                expected: 100
+            }
+        );
+        assert!(!request.body_complete());
+    }
+
+    let request = truncated_request(100);
+    assert_eq!(request.content, Some(vec![b'a'; 100]));
+    assert_eq!(request.body_state, BodyState::Complete);
+    assert!(request.body_complete());
+}
+
+#[test]
+fn test_body_timeout() {
+    let test_data = b"POST /upload HTTP/1.1\r\nContent-Length: 10\r\n\r\nhello";
+    let mut stream = MockStream::with_data_then_error(
+        VecDeque::from_iter(test_data.iter().cloned()),
+        ErrorKind::TimedOut,
+    );
+    let request = block_on(Request::from_stream(
+        &mut stream,
+        "1.2.3.4:5678".parse().unwrap(),
+    ))
+    .unwrap();
+
+    assert_eq!(request.content, Some(b"hello".to_vec()));
+    assert_eq!(
+        request.body_state,
+        BodyState::Truncated {
+            received: 5,
+            expected: 10
+        }
+    );
+}
+
+#[test]
+fn test_undeclared_body_length() {
+    let test_data = b"POST /upload HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\nhello";
+    let mut stream = MockStream::with_data(VecDeque::from_iter(test_data.iter().cloned()));
+    let request = block_on(Request::from_stream(
+        &mut stream,
+        "1.2.3.4:5678".parse().unwrap(),
+    ))
+    .unwrap();
+
+    assert_eq!(request.content, None);
+    assert_eq!(request.body_state, BodyState::Unknown);
+    assert!(!request.body_complete());
+}
+
+#[test]
+fn test_chunked_body() {
+    let test_data = b"POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5;name=value\r\nhello\r\n6\r\n world\r\n0\r\nServer-Timing: total;dur=12\r\n\r\n";
+
+    // Reading a few bytes at a time splits the size lines, data and CRLFs across reads
+    for read_size in [1, 2, 3, 5, 8, 13, usize::MAX] {
+        let mut stream = MockStream::with_data_in_reads(
+            VecDeque::from_iter(test_data.iter().cloned()),
+            read_size,
+        );
+        let request = block_on(Request::from_stream(
+            &mut stream,
+            "1.2.3.4:5678".parse().unwrap(),
+        ))
+        .unwrap();
+
+        assert_eq!(request.content, Some(b"hello world".to_vec()));
+        assert_eq!(request.body_state, BodyState::Complete);
+        assert_eq!(request.headers.get(HeaderType::ContentLength), Some("11"));
+        assert_eq!(request.headers.get(HeaderType::TransferEncoding), None);
+        assert_eq!(request.headers.get("Server-Timing"), None);
+        assert_eq!(request.trailers.get("Server-Timing"), Some("total;dur=12"));
+    }
+}
+
+#[test]
+fn test_malformed_chunked_body() {
+    let test_data =
+        b"POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5x\r\nhello\r\n0\r\n\r\n";
+    let mut stream = MockStream::with_data(VecDeque::from_iter(test_data.iter().cloned()));
+    let request = block_on(Request::from_stream(
+        &mut stream,
+        "1.2.3.4:5678".parse().unwrap(),
+    ));
+
+    assert_eq!(request.unwrap_err(), RequestError::Request);
+}
+
+#[test]
+fn test_chunked_body_limits() {
+    let limits = RequestLimits {
+        max_uri_length: None,
+        max_header_size: Some(64),
+        max_body_size: Some(8),
+    };
+    let head = "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n";
+
+    // The limit applies to the decoded body, since there is no declared length to check
+    assert_eq!(
+        limited_request(
+            format!("{}5\r\nhello\r\n5\r\nworld\r\n0\r\n\r\n", head).as_bytes(),
+            limits
+        )
+        .unwrap_err(),
+        RequestError::TooLarge(RequestPart::Body)
+    );
+
+    // Trailers count towards the size of the headers
+    let trailer = format!("X-Checksum: {}\r\n", "0".repeat(32));
+    assert_eq!(
+        limited_request(
+            format!("{}5\r\nhello\r\n0\r\n{}\r\n", head, trailer).as_bytes(),
+            limits
+        )
+        .unwrap_err(),
+        RequestError::TooLarge(RequestPart::Headers)
+    );
+}
+
+/// Parses the request with the given limits.
+fn limited_request(data: &[u8], limits: RequestLimits) -> Result<Request, RequestError> {
+    let mut stream = MockStream::with_data(VecDeque::from_iter(data.iter().cloned()));
+
+    block_on(Request::from_stream_checked(
+        &mut stream,
+        "1.2.3.4:5678".parse().unwrap(),
+        None,
+        None,
+        None,
+        None,
+        &limits,
+    ))
+}
+
+#[test]
+fn test_request_limits() {
+    let limits = RequestLimits {
+        max_uri_length: Some(8),
+        max_header_size: Some(32),
+        max_body_size: Some(4),
+    };
+
+    assert!(limited_request(b"GET /1234567 HTTP/1.1\r\n\r\n", limits).is_ok());
+    assert_eq!(
+        limited_request(b"GET /1234?567 HTTP/1.1\r\n\r\n", limits).unwrap_err(),
+        RequestError::TooLarge(RequestPart::Uri)
+    );
+    assert_eq!(
+        limited_request(
+            b"GET / HTTP/1.1\r\nHost: localhost\r\nX-Padding: padding\r\n\r\n",
+            limits
+        )
+        .unwrap_err(),
+        RequestError::TooLarge(RequestPart::Headers)
+    );
+    assert_eq!(
+        limited_request(b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\n", limits).unwrap_err(),
+        RequestError::TooLarge(RequestPart::Body)
+    );
+}
+
+#[test]
+fn test_default_request_limits() {
+    let limits = SecurityOptions::default().request_limits;
+
+    // The body is never sent, so the request must be rejected from the declared length alone
+    let request = format!(
+        "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+        DEFAULT_MAX_BODY_SIZE + 1
+    );
+    assert_eq!(
+        limited_request(request.as_bytes(), limits).unwrap_err(),
+        RequestError::TooLarge(RequestPart::Body)
+    );
+
+    let request = format!(
+        "GET / HTTP/1.1\r\nX-Padding: {}\r\n\r\n",
+        "a".repeat(DEFAULT_MAX_HEADER_SIZE)
+    );
+    assert_eq!(
+        limited_request(request.as_bytes(), limits).unwrap_err(),
+        RequestError::TooLarge(RequestPart::Headers)
+    );
+}
@@ -1,11 +1,13 @@
 #![allow(unused_imports)]
-use crate::http::cookie::{SameSite, SetCookie};
+use crate::http::cookie::{Cookie, SameSite, SetCookie};
 use crate::http::headers::{HeaderType, Headers};
-use crate::http::response::Response;
+use crate::http::method::Method;
+use crate::http::response::{Response, ResponseError, MAX_RESPONSE_BODY_SIZE};
 use crate::http::status::StatusCode;
 use crate::tests::mock_stream::MockStream;
 
 use std::collections::{BTreeMap, VecDeque};
+use std::io::Read;
 use std::iter::FromIterator;
 use std::time::Duration;
 
@@ -22,7 +24,7 @@ fn test_response() {
         Some("text/html")
     );
 
-    let expected_bytes: Vec<u8> = b"HTTP/1.1 200 OK\r\nDate: Thu, 1 Jan 1970 00:00:00 GMT\r\nContent-Language: en-GB\r\nContent-Type: text/html\r\n\r\n<body>test</body>\r\n".to_vec();
+    let expected_bytes: Vec<u8> = b"HTTP/1.1 200 OK\r\nDate: Thu, 1 Jan 1970 00:00:00 GMT\r\nContent-Language: en-GB\r\nContent-Type: text/html\r\n\r\n<body>test</body>".to_vec();
     let bytes: Vec<u8> = response.into();
 
     assert_eq!(bytes, expected_bytes);
@@ -54,13 +56,48 @@ fn test_cookie_response() {
     );
 
     let expected_bytes: Vec<u8> =
-        b"HTTP/1.1 200 OK\r\nSet-Cookie: X-Example-Cookie=example-value; Max-Age=3600; Path=/; Secure\r\nSet-Cookie: X-Example-Token=example-token; Domain=example.com; SameSite=Strict; Secure\r\n\r\nHello, world!\r\n"
+        b"HTTP/1.1 200 OK\r\nSet-Cookie: X-Example-Cookie=example-value; Max-Age=3600; Path=/; Secure\r\nSet-Cookie: X-Example-Token=example-token; Domain=example.com; SameSite=Strict; Secure\r\n\r\nHello, world!"
             .to_vec();
     let bytes: Vec<u8> = response.into();
 
     assert_eq!(bytes, expected_bytes);
 }
 
+#[test]
+fn test_cookie_response_round_trip() {
+    let response = Response::empty(StatusCode::OK)
+        .with_cookie(
+            SetCookie::new("HumphreyToken", "abc123")
+                .with_path("/")
+                .with_max_age(Duration::from_secs(3600))
+                .with_http_only(true)
+                .with_secure(true)
+                .with_same_site(SameSite::Strict),
+        )
+        .with_cookie(SetCookie::new("Theme", "dark").with_same_site(SameSite::None))
+        .with_header(HeaderType::ContentLength, "0");
+
+    let bytes: Vec<u8> = response.into();
+    let mut stream = MockStream::with_data(VecDeque::from(bytes));
+    let response = Response::from_stream(&mut stream).unwrap();
+
+    // Each cookie survives as a separate header, and `SameSite=None` is always secure
+    assert_eq!(
+        response.get_headers().get_all(HeaderType::SetCookie),
+        vec![
+            "HumphreyToken=abc123; Max-Age=3600; Path=/; SameSite=Strict; Secure; HttpOnly",
+            "Theme=dark; SameSite=None; Secure"
+        ]
+    );
+    assert_eq!(
+        response.get_cookies(),
+        vec![
+            Cookie::new("HumphreyToken", "abc123"),
+            Cookie::new("Theme", "dark")
+        ]
+    );
+}
+
 #[test]
 fn test_response_from_stream() {
     let test_data = b"HTTP/1.1 404 Not Found\r\nContent-Length: 51\r\n\r\nThe requested resource was not found on the server.\r\n";
@@ -79,3 +116,409 @@ fn test_response_from_stream() {
     expected_headers.add(HeaderType::ContentLength, "51");
     assert_eq!(response.headers, expected_headers);
 }
+
+#[test]
+fn test_response_after_body() {
+    let test_data = b"HelloHTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nworld";
+    let mut stream = MockStream::with_data(VecDeque::from_iter(test_data.iter().cloned()));
+    let mut byte_by_byte = ByteByByte(&mut stream);
+
+    // A body of the length declared by the previous response, which nothing follows
+    let mut body = [0; 5];
+    byte_by_byte.read_exact(&mut body).unwrap();
+
+    let response = Response::from_stream(&mut byte_by_byte).unwrap();
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body, b"world".to_vec());
+
+    // Empty lines before the status line are not skipped
+    let test_data = b"\r\nHTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n";
+    let mut stream = MockStream::with_data(VecDeque::from_iter(test_data.iter().cloned()));
+    assert_eq!(
+        Response::from_stream(&mut stream).unwrap_err(),
+        ResponseError::Response
+    );
+}
+
+#[test]
+fn test_response_body_too_large() {
+    // The declared length is rejected before anything is allocated for it
+    let test_data = format!(
+        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\nHello",
+        MAX_RESPONSE_BODY_SIZE + 1
+    );
+    let mut stream = MockStream::with_data(VecDeque::from_iter(test_data.bytes()));
+    assert_eq!(
+        Response::from_stream(&mut stream).unwrap_err(),
+        ResponseError::Response
+    );
+
+    let test_data =
+        b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nffffffffffff\r\nHello\r\n0\r\n\r\n";
+    let mut stream = MockStream::with_data(VecDeque::from_iter(test_data.iter().cloned()));
+    assert_eq!(
+        Response::from_stream(&mut stream).unwrap_err(),
+        ResponseError::Response
+    );
+
+    // A length which is within the limit but never sent is a stream error
+    let test_data = b"HTTP/1.1 200 OK\r\nContent-Length: 100000000\r\n\r\nHello";
+    let mut stream = MockStream::with_data(VecDeque::from_iter(test_data.iter().cloned()));
+    assert_eq!(
+        Response::from_stream(&mut stream).unwrap_err(),
+        ResponseError::Stream
+    );
+}
+
+#[test]
+fn test_chunked_response_with_trailers() {
+    let test_data = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nTrailer: Server-Timing\r\n\r\n5\r\nHello\r\n7;ext=1\r\n, world\r\n0\r\nServer-Timing: db;dur=53\r\nContent-Length: 1000\r\n\r\n";
+    let mut stream = MockStream::with_data(VecDeque::from_iter(test_data.iter().cloned()));
+    let response = Response::from_stream(&mut stream).unwrap();
+
+    assert_eq!(response.body, b"Hello, world".to_vec());
+    assert_eq!(response.headers.get(HeaderType::ContentLength), Some("12"));
+    assert_eq!(
+        response.headers.get(HeaderType::Trailer),
+        Some("Server-Timing")
+    );
+
+    let mut expected_trailers = Headers::new();
+    expected_trailers.add("Server-Timing", "db;dur=53");
+    assert_eq!(response.trailers, expected_trailers);
+
+    let expected_bytes: Vec<u8> = b"HTTP/1.1 200 OK\r\nTrailer: Server-Timing\r\nTransfer-Encoding: chunked\r\n\r\nc\r\nHello, world\r\n0\r\nserver-timing: db;dur=53\r\n\r\n".to_vec();
+    let bytes: Vec<u8> = response.into();
+
+    assert_eq!(bytes, expected_bytes);
+}
+
+#[test]
+#[cfg(not(feature = "tokio"))]
+fn test_hijacked_response() {
+    let response = Response::new(StatusCode::OK, b"Switching to raw mode")
+        .with_header(HeaderType::Date, "Thu, 1 Jan 1970 00:00:00 GMT")
+        .with_hijack(|_| ());
+
+    assert!(response.is_hijacked());
+    assert!(!Response::empty(StatusCode::OK).is_hijacked());
+
+    let expected_bytes: Vec<u8> =
+        b"HTTP/1.1 200 OK\r\nDate: Thu, 1 Jan 1970 00:00:00 GMT\r\n\r\nSwitching to raw mode"
+            .to_vec();
+    let bytes: Vec<u8> = response.into();
+
+    assert_eq!(bytes, expected_bytes);
+}
+
+/// Reads one byte at a time, so that parsing a response does not buffer any of the next one.
+struct ByteByByte<'a>(&'a mut MockStream);
+
+impl Read for ByteByByte<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = buf.len().min(1);
+        self.0.read(&mut buf[..len])
+    }
+}
+
+/// Finalises the response as the app would for a request with the given method, then writes it
+///   followed by another response to check that the second can still be read on the same connection.
+fn finalise_and_pipeline(mut response: Response, method: Method) -> Vec<u8> {
+    response.finalise_framing(&method);
+    let bytes: Vec<u8> = response.into();
+
+    let mut pipelined = bytes.clone();
+    pipelined.extend(b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nnext");
+
+    let mut stream = MockStream::with_data(VecDeque::from_iter(pipelined));
+    Response::from_stream(&mut ByteByByte(&mut stream)).unwrap();
+    let next = Response::from_stream(&mut stream).unwrap();
+    assert_eq!(next.body, b"next".to_vec());
+
+    bytes
+}
+
+#[test]
+fn test_informational_response_framing() {
+    let mut response =
+        Response::new(StatusCode::Continue, b"ignored").with_header(HeaderType::ContentLength, "7");
+    response.finalise_framing(&Method::Get);
+    let bytes: Vec<u8> = response.into();
+
+    assert_eq!(bytes, b"HTTP/1.1 100 Continue\r\n\r\n".to_vec());
+
+    // Interim responses are skipped when reading, so the response which follows is read instead
+    let mut pipelined = bytes;
+    pipelined.extend(b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nnext");
+
+    let mut stream = MockStream::with_data(VecDeque::from_iter(pipelined));
+    let next = Response::from_stream(&mut stream).unwrap();
+    assert_eq!(next.status_code, StatusCode::OK);
+    assert_eq!(next.body, b"next".to_vec());
+}
+
+#[test]
+fn test_no_content_response_framing() {
+    let mut response = Response::new(StatusCode::NoContent, b"ignored")
+        .with_header(HeaderType::TransferEncoding, "chunked");
+    response.trailers.add("Server-Timing", "total;dur=3");
+    let bytes = finalise_and_pipeline(response, Method::Delete);
+
+    assert_eq!(bytes, b"HTTP/1.1 204 No Content\r\n\r\n".to_vec());
+}
+
+#[test]
+fn test_not_modified_response_framing() {
+    let response = Response::new(StatusCode::NotModified, b"ignored");
+    let bytes = finalise_and_pipeline(response, Method::Get);
+
+    assert_eq!(bytes, b"HTTP/1.1 304 Not Modified\r\n\r\n".to_vec());
+
+    let response =
+        Response::empty(StatusCode::NotModified).with_header(HeaderType::ContentLength, "1024");
+    let bytes = finalise_and_pipeline(response, Method::Get);
+
+    assert_eq!(
+        bytes,
+        b"HTTP/1.1 304 Not Modified\r\nContent-Length: 1024\r\n\r\n".to_vec()
+    );
+}
+
+#[test]
+fn test_head_response_framing() {
+    let mut response = Response::new(StatusCode::OK, b"Hello, world");
+    response.trailers.add("Server-Timing", "total;dur=3");
+    response.finalise_framing(&Method::Head);
+    let bytes: Vec<u8> = response.into();
+
+    // The body is not sent, so the response cannot be read back with its declared length
+    assert_eq!(
+        bytes,
+        b"HTTP/1.1 200 OK\r\nContent-Length: 12\r\n\r\n".to_vec()
+    );
+
+    let mut response = Response::new(StatusCode::OK, b"Hello, world");
+    response.finalise_framing(&Method::Get);
+    let bytes: Vec<u8> = response.into();
+
+    assert_eq!(
+        bytes,
+        b"HTTP/1.1 200 OK\r\nContent-Length: 12\r\n\r\nHello, world".to_vec()
+    );
+}
+
+/// Reads the given data in pieces of at most three bytes, so a streamed body is split into chunks.
+#[cfg(not(feature = "tokio"))]
+fn slow_reader(data: &'static [u8]) -> impl Read + Send {
+    MockStream::with_data_in_reads(VecDeque::from_iter(data.iter().cloned()), 3)
+}
+
+#[test]
+#[cfg(not(feature = "tokio"))]
+fn test_chunked_response() {
+    let mut response = Response::chunked(StatusCode::OK, slow_reader(b"Hello, world"))
+        .with_header(HeaderType::ContentLength, "1000")
+        .with_bytes(b">");
+    response.trailers.add("Server-Timing", "total;dur=3");
+
+    assert!(response.is_streamed());
+    assert!(!Response::empty(StatusCode::OK).is_streamed());
+
+    let bytes = finalise_and_pipeline(response, Method::Get);
+
+    assert_eq!(
+        bytes,
+        b"HTTP/1.1 200 OK\r\nTrailer: server-timing\r\nTransfer-Encoding: chunked\r\n\r\n1\r\n>\r\n3\r\nHel\r\n3\r\nlo,\r\n3\r\n wo\r\n3\r\nrld\r\n0\r\nserver-timing: total;dur=3\r\n\r\n"
+            .to_vec()
+    );
+
+    // An empty reader still produces a valid chunked body
+    let response = Response::chunked(StatusCode::OK, slow_reader(b""));
+    let bytes = finalise_and_pipeline(response, Method::Get);
+
+    assert_eq!(
+        bytes,
+        b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n".to_vec()
+    );
+}
+
+#[test]
+#[cfg(not(feature = "tokio"))]
+fn test_chunked_response_framing() {
+    // The framing of the body is still declared in reply to `HEAD` requests
+    let mut response = Response::chunked(StatusCode::OK, slow_reader(b"Hello, world"));
+    response.finalise_framing(&Method::Head);
+    let bytes: Vec<u8> = response.into();
+
+    assert_eq!(
+        bytes,
+        b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec()
+    );
+
+    let response = Response::chunked(StatusCode::NoContent, slow_reader(b"ignored"));
+    let bytes = finalise_and_pipeline(response, Method::Get);
+
+    assert_eq!(bytes, b"HTTP/1.1 204 No Content\r\n\r\n".to_vec());
+
+    // HTTP/1.0 clients receive the body as it is, followed by the connection closing
+    let mut response = Response::chunked(StatusCode::OK, slow_reader(b"Hello, world"));
+    response.trailers.add("Server-Timing", "total;dur=3");
+    response.finalise_framing(&Method::Get);
+    response.finalise_http_1_0(true);
+
+    assert!(response.is_close_delimited());
+
+    let bytes: Vec<u8> = response.into();
+
+    assert_eq!(
+        bytes,
+        b"HTTP/1.0 200 OK\r\nConnection: Close\r\n\r\nHello, world".to_vec()
+    );
+}
+
+#[test]
+#[cfg(not(feature = "tokio"))]
+fn test_sized_streamed_response() {
+    let response = Response::from_reader(StatusCode::OK, slow_reader(b"Hello, world"), 12)
+        .with_header(HeaderType::TransferEncoding, "chunked")
+        .with_bytes(b">");
+
+    assert!(response.is_streamed());
+
+    let bytes = finalise_and_pipeline(response, Method::Get);
+
+    assert_eq!(
+        bytes,
+        b"HTTP/1.1 200 OK\r\nContent-Length: 13\r\n\r\n>Hello, world".to_vec()
+    );
+
+    // Only the declared length is read
+    let response = Response::from_reader(StatusCode::OK, slow_reader(b"Hello, world"), 5);
+    let bytes = finalise_and_pipeline(response, Method::Get);
+
+    assert_eq!(
+        bytes,
+        b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nHello".to_vec()
+    );
+
+    // The length is still declared in reply to `HEAD` requests
+    let mut response = Response::from_reader(StatusCode::OK, slow_reader(b"Hello, world"), 12);
+    response.finalise_framing(&Method::Head);
+    let bytes: Vec<u8> = response.into();
+
+    assert_eq!(
+        bytes,
+        b"HTTP/1.1 200 OK\r\nContent-Length: 12\r\n\r\n".to_vec()
+    );
+
+    // HTTP/1.0 clients can keep the connection alive since the length is known
+    let mut response = Response::from_reader(StatusCode::OK, slow_reader(b"Hello, world"), 12);
+    response.finalise_framing(&Method::Get);
+    response.finalise_http_1_0(true);
+
+    assert!(!response.is_close_delimited());
+
+    let bytes: Vec<u8> = response.into();
+
+    assert_eq!(
+        bytes,
+        b"HTTP/1.0 200 OK\r\nContent-Length: 12\r\n\r\nHello, world".to_vec()
+    );
+}
+
+#[test]
+#[cfg(not(feature = "tokio"))]
+fn test_truncated_streamed_response() {
+    // The reader ends before the declared length, so the response cannot be completed
+    let mut response = Response::from_reader(StatusCode::OK, slow_reader(b"Hello"), 12);
+    response.finalise_framing(&Method::Get);
+
+    let mut bytes = Vec::new();
+    let error = response.write_to(&mut bytes).unwrap_err();
+
+    assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+    assert_eq!(
+        bytes,
+        b"HTTP/1.1 200 OK\r\nContent-Length: 12\r\n\r\nHello".to_vec()
+    );
+}
+
+#[test]
+#[cfg(feature = "tokio")]
+fn test_sized_streamed_response() {
+    use futures::executor::block_on;
+
+    let data = VecDeque::from_iter(b"Hello, world".iter().cloned());
+    let mut response =
+        Response::from_reader(StatusCode::OK, MockStream::with_data_in_reads(data, 3), 12)
+            .with_bytes(b">");
+    response.finalise_framing(&Method::Get);
+
+    let mut bytes = Vec::new();
+    block_on(response.write_to(&mut bytes)).unwrap();
+
+    assert_eq!(
+        bytes,
+        b"HTTP/1.1 200 OK\r\nContent-Length: 13\r\n\r\n>Hello, world".to_vec()
+    );
+
+    let data = VecDeque::from_iter(b"Hello, world".iter().cloned());
+    let mut response = Response::chunked(StatusCode::OK, MockStream::with_data_in_reads(data, 5));
+    response.finalise_framing(&Method::Get);
+
+    let mut bytes = Vec::new();
+    block_on(response.write_to(&mut bytes)).unwrap();
+
+    assert_eq!(
+        bytes,
+        b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nHello\r\n5\r\n, wor\r\n2\r\nld\r\n0\r\n\r\n"
+            .to_vec()
+    );
+
+    let data = VecDeque::from_iter(b"Hello".iter().cloned());
+    let mut response = Response::from_reader(StatusCode::OK, MockStream::with_data(data), 12);
+    response.finalise_framing(&Method::Get);
+
+    let error = block_on(response.write_to(&mut Vec::new())).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn test_redirect_response() {
+    let bytes: Vec<u8> = Response::redirect("/login").into();
+    assert_eq!(bytes, b"HTTP/1.1 302 Found\r\nLocation: /login\r\n\r\n");
+
+    let bytes: Vec<u8> = Response::permanent_redirect("https://example.com/").into();
+    assert_eq!(
+        bytes,
+        b"HTTP/1.1 301 Moved Permanently\r\nLocation: https://example.com/\r\n\r\n"
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_json_response() {
+    use humphrey_json::prelude::*;
+
+    struct User {
+        id: u64,
+        name: String,
+    }
+
+    json_map! {
+        User,
+        id => "id",
+        name => "name"
+    }
+
+    let user = User {
+        id: 1,
+        name: "Ferris".to_string(),
+    };
+
+    let bytes: Vec<u8> = Response::json(&user).into();
+    assert_eq!(
+        bytes,
+        b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{\"id\":1,\"name\":\"Ferris\"}"
+    );
+}
@@ -0,0 +1,290 @@
+use crate::http::cors::Cors;
+use crate::http::method::Method;
+use crate::http::{Request, Response, StatusCode};
+use crate::route::{SubApp, TrailingSlash};
+use crate::App;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use std::thread::spawn;
+
+/// A handler which responds with the given name, so that the route which handled a request can be
+///   identified from the response.
+fn named(name: &'static str) -> impl Fn(Request, Arc<()>) -> Response {
+    move |_, _| Response::new(StatusCode::OK, name)
+}
+
+/// Builds an app with overlapping routes across host sub-apps and the default sub-app.
+fn table() -> App<()> {
+    let api = SubApp::new()
+        .with_named_route("api_users", "/users/*", named("api_users"))
+        .with_named_route("api_any", "/*", named("api_any"));
+
+    let subdomains = SubApp::new().with_named_route("sub_blog", "/blog/*", named("sub_blog"));
+
+    App::new_with_config(2, ())
+        .with_host("api.example.com", api)
+        .with_host("*.example.com", subdomains)
+        .with_named_route("users", "/users", named("users"))
+        .with_named_route("user", "/users/*", named("user"))
+        .with_named_route("comments", "/blog/*/comments/*", named("comments"))
+        .with_named_route("blog", "/blog/*", named("blog"))
+        .with_route("/static/*", named("/static/*"))
+        .with_cors_config("/blog/*", Cors::wildcard())
+}
+
+/// Requests which exercise the precedence of hosts and routes, as `(host, path)` pairs.
+const CASES: &[(Option<&str>, &str)] = &[
+    (Some("api.example.com"), "/users/42"),
+    (Some("api.example.com"), "/anything"),
+    (Some("www.example.com"), "/blog/post"),
+    (Some("www.example.com"), "/users"),
+    (Some("www.example.com"), "/nowhere"),
+    (Some("example.com"), "/blog/post"),
+    (Some("example.com"), "/blog/post/comments/3"),
+    (None, "/users"),
+    (None, "/users/1/posts"),
+    (None, "/static/css/main.css"),
+    (None, "/nowhere"),
+];
+
+#[test]
+fn test_resolve() {
+    let app = table();
+
+    let api_user = app
+        .resolve(&Method::Get, "/users/42", Some("api.example.com"))
+        .unwrap();
+    assert_eq!(api_user.host, "api.example.com");
+    assert_eq!(api_user.route, "/users/*");
+    assert_eq!(api_user.name, Some("api_users"));
+    assert_eq!(api_user.index, 0);
+    assert_eq!(api_user.wildcards, vec!["42"]);
+    assert!(!api_user.preflight);
+
+    // The host's sub-app has no matching route, so the default sub-app is used
+    let users = app
+        .resolve(&Method::Get, "/users", Some("www.example.com"))
+        .unwrap();
+    assert_eq!(users.host, "*");
+    assert_eq!(users.name, Some("users"));
+    assert!(users.wildcards.is_empty());
+
+    // The port of the host is ignored, since the sub-apps' hosts do not specify one
+    let api_port = app
+        .resolve(&Method::Get, "/users/42", Some("api.example.com:8080"))
+        .unwrap();
+    assert_eq!(api_port.host, "api.example.com");
+    assert_eq!(api_port.name, Some("api_users"));
+
+    let sub_port = app
+        .resolve(&Method::Get, "/blog/post", Some("www.example.com:8443"))
+        .unwrap();
+    assert_eq!(sub_port.host, "*.example.com");
+
+    let comments = app
+        .resolve(&Method::Post, "/blog/post/comments/3", None)
+        .unwrap();
+    assert_eq!(comments.name, Some("comments"));
+    assert_eq!(comments.index, 2);
+    assert_eq!(comments.wildcards, vec!["post", "3"]);
+
+    let file = app
+        .resolve(&Method::Get, "/static/css/main.css", None)
+        .unwrap();
+    assert_eq!(file.name, None);
+    assert_eq!(file.route, "/static/*");
+    assert_eq!(file.wildcards, vec!["css/main.css"]);
+
+    let preflight = app.resolve(&Method::Options, "/blog/post", None).unwrap();
+    assert!(preflight.preflight);
+    assert_eq!(preflight.name, Some("blog"));
+
+    assert!(app.resolve(&Method::Get, "/nowhere", None).is_none());
+}
+
+#[test]
+fn test_subapp_resolve() {
+    let subapp: SubApp<()> = SubApp::new()
+        .with_named_route("first", "/a/*", named("first"))
+        .with_named_route("second", "/a/b", named("second"))
+        .with_stateless_route("/c", |_: Request| Response::empty(StatusCode::OK));
+
+    // The most specific route is chosen, even if a wildcard route was added first
+    let second = subapp.resolve(&Method::Get, "/a/b").unwrap();
+    assert_eq!(second.name, Some("second"));
+    assert_eq!(second.index, 1);
+
+    let first = subapp.resolve(&Method::Get, "/a/c").unwrap();
+    assert_eq!(first.name, Some("first"));
+    assert_eq!(first.wildcards, vec!["c"]);
+
+    let unnamed = subapp.resolve(&Method::Get, "/c").unwrap();
+    assert_eq!(unnamed.name, None);
+    assert_eq!(unnamed.index, 2);
+
+    assert!(subapp.resolve(&Method::Get, "/d").is_none());
+}
+
+#[test]
+fn test_canonical_path() {
+    let routes = |policy| {
+        SubApp::<()>::new()
+            .with_trailing_slash_policy(policy)
+            .with_route("/", named("index"))
+            .with_route("/about", named("about"))
+            .with_route("/docs/", named("docs"))
+            .with_route("/users/:id", named("user"))
+            .with_route_method("/form", Method::Post, named("form"))
+            .with_route("/blog/*", named("blog"))
+            .with_route("/*", named("any"))
+    };
+
+    let strict = routes(TrailingSlash::Strict);
+    assert_eq!(strict.canonical_path(&Method::Get, "/about/"), None);
+
+    for policy in [TrailingSlash::Merge, TrailingSlash::Redirect] {
+        let subapp = routes(policy);
+        let canonical = |method, path| subapp.canonical_path(&method, path);
+
+        assert_eq!(canonical(Method::Get, "/about/"), Some("/about".into()));
+        assert_eq!(canonical(Method::Get, "/docs"), Some("/docs/".into()));
+        assert_eq!(canonical(Method::Get, "/users/5/"), Some("/users/5".into()));
+        assert_eq!(canonical(Method::Head, "/about/"), Some("/about".into()));
+
+        // Paths which already match a route exactly, or only match wildcard routes, are unchanged
+        assert_eq!(canonical(Method::Get, "/about"), None);
+        assert_eq!(canonical(Method::Get, "/"), None);
+        assert_eq!(canonical(Method::Get, "/blog"), None);
+        assert_eq!(canonical(Method::Get, "/blog/"), None);
+        assert_eq!(canonical(Method::Get, "/missing/"), None);
+
+        // The route must handle the method of the request
+        assert_eq!(canonical(Method::Post, "/form/"), Some("/form".into()));
+        assert_eq!(canonical(Method::Get, "/form/"), None);
+    }
+}
+
+#[test]
+fn test_registration_order() {
+    let routes = [
+        ("any", "/*"),
+        ("contact", "/contact"),
+        ("blog", "/blog/*"),
+        ("post", "/blog/:slug"),
+        ("comments", "/blog/*/comments"),
+        ("archive", "/blog/archive"),
+    ];
+    let expected = [
+        ("/contact", "contact"),
+        ("/about", "any"),
+        ("/blog/archive", "archive"),
+        ("/blog/hello", "post"),
+        ("/blog/2023/hello", "blog"),
+        ("/blog/hello/comments", "comments"),
+    ];
+
+    // Adding the routes in any of these orders gives the same result for each path
+    for rotation in 0..routes.len() {
+        for reversed in [false, true] {
+            let mut order = routes.to_vec();
+            order.rotate_left(rotation);
+            if reversed {
+                order.reverse();
+            }
+
+            let subapp = order
+                .iter()
+                .fold(SubApp::<()>::new(), |subapp, &(name, route)| {
+                    subapp.with_named_route(name, route, named(name))
+                });
+
+            for (path, name) in expected {
+                let route = subapp.resolve(&Method::Get, path).unwrap();
+                assert_eq!(route.name, Some(name), "{} with {:?}", path, order);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_equally_specific_routes() {
+    let subapp: SubApp<()> = SubApp::new()
+        .with_named_route("first", "/files/*", named("first"))
+        .with_named_route("second", "/files/*", named("second"))
+        .with_named_route("suffix", "/*/files", named("suffix"));
+
+    // Routes which are equally specific are checked in the order they were added
+    let route = subapp.resolve(&Method::Get, "/files/a").unwrap();
+    assert_eq!(route.name, Some("first"));
+
+    let route = subapp.resolve(&Method::Get, "/files/files").unwrap();
+    assert_eq!(route.name, Some("first"));
+}
+
+#[test]
+fn test_resolve_matches_dispatch() {
+    let addr = crate::tests::free_addr();
+    let (shutdown_tx, shutdown_rx) = channel();
+
+    let resolver = table();
+    let app = table().with_shutdown(shutdown_rx);
+
+    let cloned_addr = addr.clone();
+    let handle = spawn(move || app.run(cloned_addr).unwrap());
+
+    crate::tests::wait_for(&addr);
+
+    for (host, path) in CASES {
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        let host_header = host
+            .map(|host| format!("Host: {}\r\n", host))
+            .unwrap_or_default();
+
+        write!(stream, "GET {} HTTP/1.1\r\n{}\r\n", path, host_header).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let dispatched = match response.starts_with("HTTP/1.1 200 OK") {
+            true => Some(
+                response
+                    .split("\r\n\r\n")
+                    .nth(1)
+                    .unwrap()
+                    .trim_end()
+                    .to_string(),
+            ),
+            false => None,
+        };
+
+        let resolved = resolver
+            .resolve(&Method::Get, path, *host)
+            .map(|route| route.name.unwrap_or(route.route).to_string());
+
+        assert_eq!(resolved, dispatched, "host {:?}, path {}", host, path);
+    }
+
+    // Preflight requests are answered by the app rather than the handler
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    stream
+        .write_all(b"OPTIONS /blog/post HTTP/1.1\r\nOrigin: https://example.org\r\n\r\n")
+        .unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    let resolved = resolver
+        .resolve(&Method::Options, "/blog/post", None)
+        .unwrap();
+    assert!(resolved.preflight);
+    assert!(response.starts_with("HTTP/1.1 204 No Content"));
+    assert!(response
+        .to_ascii_lowercase()
+        .contains("access-control-allow-origin: *"));
+
+    shutdown_tx.send(()).unwrap();
+    handle.join().unwrap();
+}
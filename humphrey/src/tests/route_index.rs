@@ -0,0 +1,210 @@
+use crate::http::method::Method;
+use crate::http::{Request, Response};
+use crate::route::{allow_header, SubApp};
+use crate::route_index::RouteIndex;
+
+use std::sync::Arc;
+use std::time::Instant;
+
+/// A handler for routes which are only resolved and never called.
+#[cfg(not(feature = "tokio"))]
+fn unused(_: Request, _: Arc<()>) -> Response {
+    unreachable!()
+}
+
+/// A handler for routes which are only resolved and never called.
+#[cfg(feature = "tokio")]
+async fn unused(_: Request, _: Arc<()>) -> Response {
+    unreachable!()
+}
+
+/// The parts which generated routes and paths are made of, chosen to share prefixes and to make
+///   wildcards and parameters overlap with literal routes.
+const PARTS: [&str; 10] = ["/", "a", "ab", "api", "b", "é", ".", ":id", "*", "/*"];
+
+/// The methods which generated routes are added for.
+const METHODS: [Method; 4] = [Method::Get, Method::Post, Method::Head, Method::Delete];
+
+/// A small deterministic random number generator, so that failures can be reproduced.
+struct Rng(u64);
+
+impl Rng {
+    /// Returns a random number below the given bound.
+    fn below(&mut self, bound: usize) -> usize {
+        // xorshift64
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+
+        (self.0 % bound as u64) as usize
+    }
+
+    /// Returns a string of up to `max` random parts, optionally leaving out wildcards and parameters.
+    fn string(&mut self, max: usize, patterns: bool) -> String {
+        let parts = match patterns {
+            true => &PARTS[..],
+            false => &PARTS[..7],
+        };
+
+        (0..self.below(max + 1))
+            .map(|_| parts[self.below(parts.len())])
+            .collect()
+    }
+}
+
+/// Builds a sub-app with the given routes, some of which are only for specific methods.
+fn subapp(rng: &mut Rng, routes: &[String]) -> SubApp<()> {
+    routes
+        .iter()
+        .fold(SubApp::new(), |subapp, route| match rng.below(4) {
+            0 => subapp.with_route_method(route, METHODS[rng.below(METHODS.len())].clone(), unused),
+            _ => subapp.with_route(route, unused),
+        })
+}
+
+#[test]
+fn test_index_candidates() {
+    let routes = [
+        "/",
+        "/api",
+        "/api/*",
+        "/api/users",
+        "/apple",
+        "/a:b",
+        "/user/:id",
+        "*",
+    ];
+    let index = RouteIndex::new(routes);
+
+    assert_eq!(index.len(), 8);
+    assert_eq!(index.candidates("/"), vec![0, 7]);
+    assert_eq!(index.candidates("/api"), vec![1, 7]);
+    assert_eq!(index.candidates("/api/users"), vec![2, 3, 7]);
+    assert_eq!(index.candidates("/apple"), vec![4, 7]);
+    assert_eq!(index.candidates("/ap"), vec![7]);
+
+    // Colons are only parameters at the start of a segment
+    assert_eq!(index.candidates("/a:b"), vec![5, 7]);
+    assert_eq!(index.candidates("/user/42"), vec![6, 7]);
+    assert_eq!(index.candidates("/user"), vec![7]);
+}
+
+#[test]
+fn test_index_splits_characters() {
+    // The prefixes diverge within the encoding of a single character
+    let routes = ["/é", "/è/*", "/e"];
+    let index = RouteIndex::new(routes);
+
+    assert_eq!(index.candidates("/é"), vec![0]);
+    assert_eq!(index.candidates("/è/x"), vec![1]);
+    assert_eq!(index.candidates("/e"), vec![2]);
+    assert!(index.candidates("/ê").is_empty());
+}
+
+#[test]
+fn test_index_matches_scan() {
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+
+    for _ in 0..200 {
+        let routes: Vec<String> = (0..1 + rng.below(40))
+            .map(|_| rng.string(5, true))
+            .collect();
+
+        let seed = rng.0;
+        let scanned = subapp(&mut Rng(seed), &routes);
+        let mut indexed = subapp(&mut Rng(seed), &routes);
+        indexed.build_index();
+
+        for _ in 0..50 {
+            // Paths are mostly made of the same parts as the routes so that they often match
+            let path = match rng.below(4) {
+                0 => routes[rng.below(routes.len())].clone(),
+                _ => rng.string(6, false),
+            };
+
+            for method in METHODS.iter().chain([&Method::Options, &Method::Put]) {
+                let expected = scanned.resolve(method, &path).map(|route| route.index);
+                let actual = indexed.resolve(method, &path).map(|route| route.index);
+
+                assert_eq!(
+                    actual, expected,
+                    "{} {:?} with routes {:?}",
+                    method, path, routes
+                );
+            }
+
+            assert_eq!(
+                allow_header(&[], &indexed, None, &path),
+                allow_header(&[], &scanned, None, &path)
+            );
+        }
+    }
+}
+
+#[test]
+fn test_index_ignored_when_outdated() {
+    let mut subapp: SubApp<()> = SubApp::new().with_route("/*", unused);
+    subapp.build_index();
+
+    // Routes added after the index was built are still found
+    let subapp = subapp.with_route("/about", unused);
+    let route = subapp.resolve(&Method::Get, "/about").unwrap();
+
+    assert_eq!(route.route, "/about");
+}
+
+/// Compares how long it takes to find the route for each path with and without an index, for apps
+///   with different numbers of routes.
+///
+/// It is ignored by default since the result depends on the machine. Run it in release mode with
+///   `cargo test -p humphrey --release -- --ignored routing_benchmark --nocapture`.
+#[test]
+#[ignore]
+fn routing_benchmark() {
+    const LOOKUPS: usize = 20_000;
+
+    println!("{:>8} {:>14} {:>14}", "routes", "scan (ns)", "index (ns)");
+
+    for count in [10, 100, 1000, 5000] {
+        // Routes resembling a generated API, with a few catch-all routes
+        let routes: Vec<String> = (0..count)
+            .map(|i| match i % 4 {
+                0 => format!("/api/v{}/resource{}", i % 3, i),
+                1 => format!("/api/v{}/resource{}/:id", i % 3, i),
+                2 => format!("/api/v{}/resource{}/:id/items/*", i % 3, i),
+                _ => format!("/static/{}/*", i),
+            })
+            .chain(["/api/*".to_string(), "/*".to_string()])
+            .collect();
+
+        let paths: Vec<String> = (0..LOOKUPS)
+            .map(|i| {
+                let i = (i * 7919) % count;
+                let resource = i - i % 4 + 1;
+                format!("/api/v{}/resource{}/{}", resource % 3, resource, i)
+            })
+            .collect();
+
+        let build = || {
+            routes.iter().fold(SubApp::new(), |subapp, route| {
+                subapp.with_route(route, unused)
+            })
+        };
+
+        let scanned = build();
+        let mut indexed = build();
+        indexed.build_index();
+
+        let time = |subapp: &SubApp<()>| {
+            let start = Instant::now();
+
+            for path in &paths {
+                assert!(subapp.resolve(&Method::Get, path).is_some());
+            }
+
+            start.elapsed().as_nanos() / LOOKUPS as u128
+        };
+
+        println!("{:>8} {:>14} {:>14}", count, time(&scanned), time(&indexed));
+    }
+}
@@ -0,0 +1,272 @@
+use crate::client::Client;
+use crate::http::method::Method;
+use crate::http::{Request, Response, StatusCode};
+use crate::route::{allow_header, SubApp};
+use crate::tests::mock_stream::MockStream;
+use crate::App;
+
+use std::collections::VecDeque;
+use std::iter::FromIterator;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+
+/// A handler which responds with the given name, so that the route which handled a request can be
+///   identified from the response.
+fn named(name: &'static str) -> impl Fn(Request, Arc<()>) -> Response {
+    move |_, _| Response::new(StatusCode::OK, name)
+}
+
+/// Builds a sub-app with routes for specific methods on `/items`, and a route for every method on
+///   `/items/*`.
+fn subapp() -> SubApp<()> {
+    SubApp::new()
+        .with_route_method("/items", Method::Post, named("create"))
+        .with_route_method("/items", Method::Get, named("list"))
+        .with_named_route("item", "/items/*", named("item"))
+}
+
+#[test]
+fn test_resolve_method() {
+    let subapp = subapp();
+
+    assert_eq!(subapp.resolve(&Method::Post, "/items").unwrap().index, 0);
+    assert_eq!(subapp.resolve(&Method::Get, "/items").unwrap().index, 1);
+    assert!(subapp.resolve(&Method::Delete, "/items").is_none());
+
+    // `GET` routes handle `HEAD` requests, and the app answers `OPTIONS` requests for any route
+    assert_eq!(subapp.resolve(&Method::Head, "/items").unwrap().index, 1);
+    assert_eq!(subapp.resolve(&Method::Options, "/items").unwrap().index, 0);
+
+    let item = subapp.resolve(&Method::Delete, "/items/1").unwrap();
+    assert_eq!(item.name, Some("item"));
+
+    // Custom methods are routed like any other, but `TRACE` and `CONNECT` are only routed to routes
+    //   added specifically for them
+    let purge = Method::Custom("PURGE".into());
+    assert_eq!(
+        subapp.resolve(&purge, "/items/1").unwrap().name,
+        Some("item")
+    );
+    assert!(subapp.resolve(&Method::Trace, "/items/1").is_none());
+    assert!(subapp.resolve(&Method::Connect, "/items/1").is_none());
+
+    let subapp = subapp.with_route_method("/items/*", Method::Trace, named("trace"));
+    assert_eq!(subapp.resolve(&Method::Trace, "/items/1").unwrap().index, 3);
+}
+
+#[test]
+fn test_allow_header() {
+    let mut api = subapp();
+    api.host = "api.example.com".into();
+
+    let subapps = vec![api];
+    let default_subapp = SubApp::new()
+        .with_route_method("/health", Method::Get, named("health"))
+        .with_route("/", named("index"));
+
+    assert_eq!(
+        allow_header(&subapps, &default_subapp, Some("api.example.com"), "/items").as_deref(),
+        Some("POST, GET, HEAD, OPTIONS")
+    );
+    assert_eq!(
+        allow_header(&subapps, &default_subapp, None, "/health").as_deref(),
+        Some("GET, HEAD, OPTIONS")
+    );
+
+    // Routes which handle every method, and paths without routes, have no `Allow` header
+    assert_eq!(
+        allow_header(
+            &subapps,
+            &default_subapp,
+            Some("api.example.com"),
+            "/items/1"
+        ),
+        None
+    );
+    assert_eq!(
+        allow_header(&subapps, &default_subapp, None, "/missing"),
+        None
+    );
+}
+
+/// Starts an app with routes for specific methods, returning its address and shutdown signal.
+fn start_app() -> (String, Sender<()>) {
+    let (shutdown_tx, shutdown_rx) = channel();
+
+    let app: App<()> = App::new_with_config(2, ())
+        .with_route_method("/api/items", Method::Post, named("created"))
+        .with_route_method("/api/items", Method::Get, named("items"))
+        .with_stateless_route("/api/*", |_| Response::new(StatusCode::OK, "api"))
+        .with_route_method("/admin", Method::Delete, named("deleted"))
+        .with_route_method("/cache", Method::Custom("PURGE".into()), named("purged"))
+        .with_route_method("/trace", Method::Trace, |request: Request, _| {
+            Response::new(StatusCode::OK, request.method.to_string())
+        })
+        .with_shutdown(shutdown_rx);
+
+    let addr = crate::tests::start_app(app);
+
+    (addr, shutdown_tx)
+}
+
+/// Sends a request with the given method and path, returning the status line, the `Allow` header
+///   and the body of the response.
+fn send(addr: &str, method: &str, path: &str) -> (String, Option<String>, String) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n",
+        method, path
+    )
+    .unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let mut lines = head.split("\r\n");
+    let status = lines.next().unwrap().to_string();
+    let allow = lines
+        .filter_map(|line| line.split_once(": "))
+        .find(|(name, _)| name.eq_ignore_ascii_case("Allow"))
+        .map(|(_, value)| value.to_string());
+
+    (status, allow, body.trim_end().to_string())
+}
+
+#[test]
+fn test_method_routes() {
+    let (addr, shutdown) = start_app();
+
+    let (status, _, body) = send(&addr, "POST", "/api/items");
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert_eq!(body, "created");
+
+    let (_, _, body) = send(&addr, "GET", "/api/items");
+    assert_eq!(body, "items");
+
+    // Other methods fall through to later routes which match the path
+    let (status, allow, body) = send(&addr, "DELETE", "/api/items");
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert_eq!(allow, None);
+    assert_eq!(body, "api");
+
+    shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_method_not_allowed() {
+    let (addr, shutdown) = start_app();
+
+    let (status, allow, _) = send(&addr, "GET", "/admin");
+    assert_eq!(status, "HTTP/1.1 405 Method Not Allowed");
+    assert_eq!(allow.as_deref(), Some("DELETE, OPTIONS"));
+
+    let (status, _, body) = send(&addr, "DELETE", "/admin");
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert_eq!(body, "deleted");
+
+    // Paths with no routes at all are still not found
+    let (status, allow, _) = send(&addr, "GET", "/missing");
+    assert_eq!(status, "HTTP/1.1 404 Not Found");
+    assert_eq!(allow, None);
+
+    shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_options_allow() {
+    let (addr, shutdown) = start_app();
+
+    let (status, allow, _) = send(&addr, "OPTIONS", "/admin");
+    assert_eq!(status, "HTTP/1.1 204 No Content");
+    assert_eq!(allow.as_deref(), Some("DELETE, OPTIONS"));
+
+    // A route for every method matches the path, so the allowed methods cannot be listed
+    let (status, allow, _) = send(&addr, "OPTIONS", "/api/items");
+    assert_eq!(status, "HTTP/1.1 204 No Content");
+    assert_eq!(allow, None);
+
+    shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_custom_methods() {
+    let (addr, shutdown) = start_app();
+
+    let (status, _, body) = send(&addr, "PURGE", "/cache");
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert_eq!(body, "purged");
+
+    // Custom methods are listed in the `Allow` header, and method names are case-sensitive
+    let (status, allow, _) = send(&addr, "purge", "/cache");
+    assert_eq!(status, "HTTP/1.1 405 Method Not Allowed");
+    assert_eq!(allow.as_deref(), Some("PURGE, OPTIONS"));
+
+    let (_, _, body) = send(&addr, "PROPFIND", "/api/items");
+    assert_eq!(body, "api");
+
+    // Method names which are not valid tokens are still refused
+    let (status, _, _) = send(&addr, "PUR\"GE", "/cache");
+    assert_eq!(status, "HTTP/1.1 400 Bad Request");
+
+    shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_trace_connect() {
+    let (addr, shutdown) = start_app();
+
+    // Routes for every method never receive `TRACE` or `CONNECT` requests
+    let (status, allow, _) = send(&addr, "TRACE", "/api/items");
+    assert_eq!(status, "HTTP/1.1 501 Not Implemented");
+    assert_eq!(allow, None);
+
+    let (status, _, _) = send(&addr, "CONNECT", "example.com:443");
+    assert_eq!(status, "HTTP/1.1 501 Not Implemented");
+
+    let (status, _, body) = send(&addr, "TRACE", "/trace");
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert_eq!(body, "TRACE");
+
+    shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_custom_method_round_trip() {
+    let (addr, shutdown) = start_app();
+    let mut client = Client::new();
+
+    let request = client
+        .get(format!("http://{}/cache", addr))
+        .unwrap()
+        .with_method(Method::Custom("PURGE".into()));
+
+    // The method is serialised exactly as it was given, and parsed back into the same method
+    let bytes: Vec<u8> = request.into_inner().into();
+    assert!(bytes.starts_with(b"PURGE /cache HTTP/1.1\r\n"));
+
+    let mut stream = MockStream::with_data(VecDeque::from_iter(bytes));
+    let parsed = Request::from_stream(&mut stream, "127.0.0.1:1234".parse().unwrap()).unwrap();
+    assert_eq!(parsed.method, Method::Custom("PURGE".into()));
+
+    let response = client
+        .get(format!("http://{}/cache", addr))
+        .unwrap()
+        .with_method(Method::Custom("PURGE".into()))
+        .send()
+        .unwrap();
+    assert_eq!(response.body, b"purged");
+
+    let response = client
+        .patch(format!("http://{}/api/items", addr), b"{}".to_vec())
+        .unwrap()
+        .send()
+        .unwrap();
+    assert_eq!(response.body, b"api");
+
+    shutdown.send(()).unwrap();
+}
@@ -0,0 +1,160 @@
+use crate::http::method::Method;
+use crate::http::{Request, Response};
+use crate::route::{handler_request, Route, RouteParams, SubApp};
+
+use std::sync::Arc;
+
+/// A handler for routes which are only resolved and never called.
+#[cfg(not(feature = "tokio"))]
+fn unused(_: Request, _: Arc<()>) -> Response {
+    unreachable!()
+}
+
+/// A handler for routes which are only resolved and never called.
+#[cfg(feature = "tokio")]
+async fn unused(_: Request, _: Arc<()>) -> Response {
+    unreachable!()
+}
+
+#[test]
+fn test_param_matching() {
+    let route = String::from("/user/:id");
+
+    assert!(route.route_matches("/user/42"));
+    assert!(route.route_matches("/user/ferris"));
+
+    // A parameter matches exactly one non-empty segment
+    assert!(!route.route_matches("/user/42/posts"));
+    assert!(!route.route_matches("/user/"));
+    assert!(!route.route_matches("/user"));
+    assert!(!route.route_matches("/users/42"));
+}
+
+#[test]
+fn test_param_values() {
+    let params = RouteParams::from_route("/user/:id/posts/:post", "/user/42/posts/hello-world");
+
+    assert_eq!(params.get("id"), Some("42"));
+    assert_eq!(params.get("post"), Some("hello-world"));
+    assert_eq!(params.get("missing"), None);
+    assert_eq!(
+        params.iter().collect::<Vec<_>>(),
+        vec![("id", "42"), ("post", "hello-world")]
+    );
+
+    assert_eq!(params.parse::<u64>("id"), Some(42));
+    assert_eq!(params.parse::<u64>("post"), None);
+    assert_eq!(params.parse::<u64>("missing"), None);
+
+    assert!(RouteParams::from_route("/user/:id", "/other").is_empty());
+    assert!(RouteParams::from_route("/user/*", "/user/42").is_empty());
+}
+
+#[test]
+fn test_params_with_wildcards() {
+    let subapp: SubApp<()> = SubApp::new().with_route("/files/:owner/*", unused);
+    let route = subapp
+        .resolve(&Method::Get, "/files/alice/docs/a.txt")
+        .unwrap();
+
+    assert_eq!(route.params, vec![("owner", "alice")]);
+    assert_eq!(route.wildcards, vec!["docs/a.txt"]);
+}
+
+#[test]
+fn test_literal_colons() {
+    // Only colons at the start of a segment begin a parameter
+    let route = String::from("/time/12:30");
+    assert!(route.route_matches("/time/12:30"));
+    assert!(!route.route_matches("/time/12:45"));
+
+    assert!(RouteParams::from_route("/a/b:c/:d", "/a/b:c/e")
+        .iter()
+        .eq([("d", "e")]));
+}
+
+#[test]
+fn test_param_precedence() {
+    let subapp: SubApp<()> = SubApp::new()
+        .with_named_route("me", "/user/me", unused)
+        .with_named_route("user", "/user/:id", unused)
+        .with_named_route("any", "/user/*", unused);
+
+    let resolve = |path| subapp.resolve(&Method::Get, path).unwrap().name;
+
+    assert_eq!(resolve("/user/me"), Some("me"));
+    assert_eq!(resolve("/user/42"), Some("user"));
+    assert_eq!(resolve("/user/42/posts"), Some("any"));
+    assert_eq!(resolve("/user/"), Some("any"));
+
+    // Literal routes are preferred to parameters, and parameters to wildcards, whatever the order
+    let reversed: SubApp<()> = SubApp::new()
+        .with_named_route("any", "/user/*", unused)
+        .with_named_route("user", "/user/:id", unused)
+        .with_named_route("me", "/user/me", unused);
+
+    for path in ["/user/me", "/user/42", "/user/42/posts", "/user/"] {
+        let route = reversed.resolve(&Method::Get, path).unwrap();
+        assert_eq!(route.name, resolve(path));
+    }
+}
+
+#[test]
+fn test_handler_request_params() {
+    let request = Request::builder().uri("/user/42").build();
+    assert!(request.params.is_empty());
+
+    let request = handler_request(&request, "/user/:id", false);
+    assert_eq!(request.params.get("id"), Some("42"));
+}
+
+#[cfg(not(feature = "tokio"))]
+mod app {
+    use super::*;
+
+    use crate::http::StatusCode;
+    use crate::App;
+
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::sync::mpsc::channel;
+
+    fn path_aware(request: Request, _: Arc<()>, route: &'static str) -> Response {
+        Response::new(
+            StatusCode::OK,
+            format!("{} {}", route, request.params.get("id").unwrap_or("none")),
+        )
+    }
+
+    #[test]
+    fn test_path_aware_route_params() {
+        let (shutdown_tx, shutdown_rx) = channel();
+
+        let app: App<()> = App::new_with_config(2, ())
+            .with_path_aware_route("/user/:id", path_aware)
+            .with_path_aware_route("/static/*", path_aware)
+            .with_shutdown(shutdown_rx);
+
+        let addr = crate::tests::start_app(app);
+
+        let get = |path: &str| {
+            let mut stream = TcpStream::connect(&addr).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nConnection: Close\r\n\r\n", path).unwrap();
+
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+                .split_once("\r\n\r\n")
+                .unwrap()
+                .1
+                .trim_end()
+                .to_string()
+        };
+
+        // Path-aware handlers are still given the route, alongside the parameters in the request
+        assert_eq!(get("/user/42"), "/user/:id 42");
+        assert_eq!(get("/static/a.css"), "/static/* none");
+
+        shutdown_tx.send(()).unwrap();
+    }
+}
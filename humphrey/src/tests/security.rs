@@ -0,0 +1,253 @@
+use crate::http::headers::HeaderType;
+use crate::http::request::{RequestLimits, DEFAULT_MAX_BODY_SIZE, DEFAULT_MAX_HEADER_SIZE};
+use crate::http::{Response, StatusCode};
+use crate::security::SecurityOptions;
+use crate::App;
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{channel, Sender};
+use std::time::Duration;
+
+/// Starts the app with a route at `/` and a route at `/framed` which allows framing by the same
+///   origin, returning its address and shutdown signal.
+fn start_app(configure: impl FnOnce(App<()>) -> App<()>) -> (String, Sender<()>) {
+    let (shutdown_tx, shutdown_rx) = channel();
+
+    let app: App<()> = configure(App::new_with_config(2, ()))
+        .with_stateless_route("/", |_| Response::new(StatusCode::OK, "hello"))
+        .with_stateless_route("/framed", |_| {
+            Response::new(StatusCode::OK, "hello").with_header("X-Frame-Options", "SAMEORIGIN")
+        })
+        .with_shutdown(shutdown_rx);
+
+    let addr = crate::tests::start_app(app);
+
+    (addr, shutdown_tx)
+}
+
+/// Sends the raw request, returning the status line and the headers of the response other than
+///   `Date`, which changes between requests.
+fn send(addr: &str, request: &[u8]) -> (String, BTreeMap<String, String>) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(request).unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    let (head, _) = response.split_once("\r\n\r\n").unwrap();
+    let mut lines = head.split("\r\n");
+    let status = lines.next().unwrap().to_string();
+
+    let headers = lines
+        .filter_map(|line| line.split_once(": "))
+        .filter(|(name, _)| *name != "Date")
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+
+    (status, headers)
+}
+
+fn get(addr: &str, uri: &str) -> (String, BTreeMap<String, String>) {
+    send(addr, format!("GET {} HTTP/1.1\r\n\r\n", uri).as_bytes())
+}
+
+#[test]
+fn test_hardened_defaults_diff() {
+    let (default_addr, default_shutdown) = start_app(|app| app);
+    let (hardened_addr, hardened_shutdown) = start_app(|app| app.with_hardened_defaults());
+
+    let (default_status, default_headers) = get(&default_addr, "/");
+    let (hardened_status, hardened_headers) = get(&hardened_addr, "/");
+
+    assert_eq!(default_status, hardened_status);
+
+    let removed: Vec<(&String, &String)> = default_headers
+        .iter()
+        .filter(|(name, value)| hardened_headers.get(*name) != Some(value))
+        .collect();
+    let added: Vec<(&String, &String)> = hardened_headers
+        .iter()
+        .filter(|(name, value)| default_headers.get(*name) != Some(value))
+        .collect();
+
+    assert_eq!(
+        removed,
+        vec![(&"Server".to_string(), &"Humphrey".to_string())]
+    );
+    assert_eq!(
+        added,
+        vec![
+            (
+                &"x-content-type-options".to_string(),
+                &"nosniff".to_string()
+            ),
+            (&"x-frame-options".to_string(), &"DENY".to_string())
+        ]
+    );
+
+    // HSTS is only sent over TLS, and handlers can override headers for individual responses
+    assert!(!hardened_headers.contains_key("strict-transport-security"));
+
+    let (_, framed_headers) = get(&hardened_addr, "/framed");
+    assert_eq!(
+        framed_headers.get("x-frame-options"),
+        Some(&"SAMEORIGIN".to_string())
+    );
+
+    // Only the hardened app rejects oversized requests
+    let long_uri = format!("/{}", "a".repeat(10 * 1024));
+    let (default_status, _) = get(&default_addr, &long_uri);
+    let (hardened_status, _) = get(&hardened_addr, &long_uri);
+
+    assert_eq!(default_status, "HTTP/1.1 404 Not Found");
+    assert_eq!(hardened_status, "HTTP/1.1 414 Request-URI Too Long");
+
+    default_shutdown.send(()).unwrap();
+    hardened_shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_hardened_defaults_options() {
+    let default_app: App<()> = App::new_with_config(1, ());
+    let hardened_app: App<()> = App::new_with_config(1, ()).with_hardened_defaults();
+
+    assert_eq!(default_app.security_options(), &SecurityOptions::default());
+    assert_eq!(
+        default_app.security_options().request_limits,
+        RequestLimits {
+            max_uri_length: None,
+            max_header_size: Some(DEFAULT_MAX_HEADER_SIZE),
+            max_body_size: Some(DEFAULT_MAX_BODY_SIZE),
+        }
+    );
+
+    assert_eq!(
+        hardened_app.security_options(),
+        &SecurityOptions::hardened()
+    );
+    assert_eq!(
+        hardened_app.security_options().request_limits,
+        RequestLimits::conservative()
+    );
+}
+
+#[test]
+fn test_hardened_defaults_overrides() {
+    let explicit_limits = RequestLimits {
+        max_body_size: Some(1024),
+        ..Default::default()
+    };
+
+    let app: App<()> = App::new_with_config(1, ())
+        .with_request_limits(explicit_limits)
+        .with_hardened_defaults()
+        .with_server_header(Some("Example"))
+        .with_frame_options(None)
+        .with_hsts(Some(Duration::from_secs(60)));
+
+    let options = app.security_options();
+
+    assert_eq!(options.server_header.as_deref(), Some("Example"));
+    assert_eq!(options.content_type_options.as_deref(), Some("nosniff"));
+    assert_eq!(options.frame_options, None);
+    assert_eq!(options.hsts_max_age, Some(Duration::from_secs(60)));
+
+    // Explicit limits are kept, and the rest are filled in
+    assert_eq!(options.request_limits.max_body_size, Some(1024));
+    assert_eq!(
+        options.request_limits.max_uri_length,
+        RequestLimits::conservative().max_uri_length
+    );
+}
+
+#[test]
+fn test_request_limits() {
+    let (addr, shutdown) = start_app(|app| {
+        app.with_request_limits(RequestLimits {
+            max_uri_length: Some(64),
+            max_header_size: Some(128),
+            max_body_size: Some(16),
+        })
+    });
+
+    let (status, headers) = get(&addr, &format!("/{}", "a".repeat(64)));
+    assert_eq!(status, "HTTP/1.1 414 Request-URI Too Long");
+    assert_eq!(headers.get("Connection"), Some(&"Close".to_string()));
+
+    let request = format!("GET / HTTP/1.1\r\nX-Padding: {}\r\n\r\n", "a".repeat(128));
+    let (status, _) = send(&addr, request.as_bytes());
+    assert_eq!(status, "HTTP/1.1 431 Request Header Fields Too Large");
+
+    // The body is never sent, so the response must come from the declared length alone
+    let (status, _) = send(&addr, b"POST / HTTP/1.1\r\nContent-Length: 17\r\n\r\n");
+    assert_eq!(status, "HTTP/1.1 413 Request Entity Too Large");
+
+    let (status, _) = send(
+        &addr,
+        b"POST / HTTP/1.1\r\nContent-Length: 16\r\n\r\n0123456789abcdef",
+    );
+    assert_eq!(status, "HTTP/1.1 200 OK");
+
+    shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_max_sizes() {
+    let (addr, shutdown) = start_app(|app| app.with_max_body_size(16).with_max_header_size(128));
+
+    // A request one byte over each limit is rejected without its body being sent
+    let request = format!("POST / HTTP/1.1\r\nX-Padding: {}\r\n\r\n", "a".repeat(128));
+    let (status, _) = send(&addr, request.as_bytes());
+    assert_eq!(status, "HTTP/1.1 431 Request Header Fields Too Large");
+
+    let (status, headers) = send(&addr, b"POST / HTTP/1.1\r\nContent-Length: 17\r\n\r\n");
+    assert_eq!(status, "HTTP/1.1 413 Request Entity Too Large");
+    assert_eq!(headers.get("Connection"), Some(&"Close".to_string()));
+
+    let (status, _) = send(
+        &addr,
+        b"POST / HTTP/1.1\r\nContent-Length: 16\r\n\r\n0123456789abcdef",
+    );
+    assert_eq!(status, "HTTP/1.1 200 OK");
+
+    shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_default_limits() {
+    let (addr, shutdown) = start_app(|app| app);
+
+    let request = format!(
+        "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+        DEFAULT_MAX_BODY_SIZE + 1
+    );
+    let (status, _) = send(&addr, request.as_bytes());
+    assert_eq!(status, "HTTP/1.1 413 Request Entity Too Large");
+
+    let request = format!(
+        "GET / HTTP/1.1\r\nX-Padding: {}\r\n\r\n",
+        "a".repeat(DEFAULT_MAX_HEADER_SIZE)
+    );
+    let (status, _) = send(&addr, request.as_bytes());
+    assert_eq!(status, "HTTP/1.1 431 Request Header Fields Too Large");
+
+    // Long URIs are still allowed unless the limit is set explicitly
+    let (status, _) = get(&addr, &format!("/{}", "a".repeat(10 * 1024)));
+    assert_eq!(status, "HTTP/1.1 404 Not Found");
+
+    shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_security_headers_apply() {
+    let mut response = Response::empty(StatusCode::OK).with_header(HeaderType::Server, "Custom");
+    SecurityOptions::hardened().apply(&mut response, true);
+
+    assert_eq!(response.headers.get(HeaderType::Server), Some("Custom"));
+    assert_eq!(
+        response.headers.get("Strict-Transport-Security"),
+        Some("max-age=31536000")
+    );
+}
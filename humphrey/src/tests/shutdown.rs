@@ -0,0 +1,227 @@
+use crate::http::{Response, StatusCode};
+use crate::shutdown::{ExitReason, RequestCounts, ShutdownSignal, ShutdownSummary};
+use crate::App;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, TryRecvError};
+use std::sync::Arc;
+use std::thread::{sleep, spawn};
+use std::time::Duration;
+
+/// How long to wait for the app to stop after its signal is triggered.
+const STOP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Builds an app with a single route which is shut down by the given signal.
+#[cfg(not(feature = "tokio"))]
+fn app(shutdown: ShutdownSignal) -> App<()> {
+    App::new_with_config(4, ())
+        .with_stateless_route("/", |_| Response::new(StatusCode::OK, "hello"))
+        .with_stateless_route("/error", |_| {
+            Response::new(StatusCode::InternalError, "error")
+        })
+        .with_shutdown(shutdown)
+}
+
+/// Builds an app with a single route which is shut down by the given signal.
+#[cfg(feature = "tokio")]
+fn app(shutdown: ShutdownSignal) -> App<()> {
+    App::new_with_config(())
+        .with_stateless_route("/", |_| async { Response::new(StatusCode::OK, "hello") })
+        .with_stateless_route("/error", |_| async {
+            Response::new(StatusCode::InternalError, "error")
+        })
+        .with_shutdown(shutdown)
+}
+
+/// Runs the app in the background, returning its address and a receiver which is sent the app's
+///   summary once it has stopped running.
+fn start(app: App<()>) -> (String, Receiver<ShutdownSummary>) {
+    let addr = crate::tests::free_addr();
+    let (tx, rx) = channel();
+
+    let cloned_addr = addr.clone();
+    spawn(move || {
+        #[cfg(not(feature = "tokio"))]
+        let summary = app.run(cloned_addr).unwrap();
+
+        #[cfg(feature = "tokio")]
+        let summary = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(app.run(cloned_addr))
+            .unwrap();
+
+        tx.send(summary).unwrap();
+    });
+
+    crate::tests::wait_for(&addr);
+
+    (addr, rx)
+}
+
+/// Sends a request to the given path and returns the response.
+fn request(addr: &str, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "GET {} HTTP/1.1\r\nConnection: Close\r\n\r\n", path).unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    response
+}
+
+/// Sends a request to the app and returns whether it was served.
+fn served(addr: &str) -> bool {
+    request(addr, "/").starts_with("HTTP/1.1 200 OK\r\n")
+}
+
+#[test]
+fn test_signal() {
+    let signal = ShutdownSignal::new();
+    let clone = signal.clone();
+
+    assert!(!signal.is_triggered());
+
+    // Clones share their state, and triggering more than once has no further effect
+    clone.trigger();
+    clone.trigger();
+    assert!(signal.is_triggered());
+    assert!(clone.is_triggered());
+}
+
+#[test]
+fn test_signal_from_flag() {
+    let flag = Arc::new(AtomicBool::new(false));
+    let signal = ShutdownSignal::from(flag.clone());
+
+    flag.store(true, Ordering::SeqCst);
+    assert!(signal.is_triggered());
+
+    // Triggering the signal also sets the flag
+    let flag = Arc::new(AtomicBool::new(false));
+    let signal = ShutdownSignal::from(flag.clone());
+
+    signal.trigger();
+    assert!(flag.load(Ordering::SeqCst));
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn test_wait() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    // Signals created from a flag are noticed even though setting the flag cannot wake the waiter
+    let flag = Arc::new(AtomicBool::new(false));
+    let from_flag = ShutdownSignal::from(flag.clone());
+    let signal = ShutdownSignal::new();
+    let cloned_signal = signal.clone();
+
+    runtime.block_on(async move {
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            flag.store(true, Ordering::SeqCst);
+            cloned_signal.trigger();
+        });
+
+        tokio::time::timeout(STOP_TIMEOUT, async {
+            from_flag.wait().await;
+            signal.wait().await;
+        })
+        .await
+        .unwrap();
+    });
+}
+
+#[test]
+fn test_shutdown() {
+    let shutdown = ShutdownSignal::new();
+    let (addr, stopped) = start(app(shutdown.clone()));
+
+    assert!(served(&addr));
+    assert!(matches!(stopped.try_recv(), Err(TryRecvError::Empty)));
+
+    shutdown.trigger();
+    assert!(stopped.recv_timeout(STOP_TIMEOUT).is_ok());
+    assert!(TcpStream::connect(&addr).is_err());
+}
+
+#[test]
+fn test_shutdown_from_flag() {
+    let flag = Arc::new(AtomicBool::new(false));
+    let (addr, stopped) = start(app(flag.clone().into()));
+
+    assert!(served(&addr));
+
+    flag.store(true, Ordering::SeqCst);
+    assert!(stopped.recv_timeout(STOP_TIMEOUT).is_ok());
+}
+
+#[test]
+fn test_dropped_signal() {
+    let shutdown = ShutdownSignal::new();
+    let (addr, stopped) = start(app(shutdown.clone()));
+
+    // Dropping the signal neither shuts down the app nor stops it from accepting connections
+    drop(shutdown);
+
+    for _ in 0..5 {
+        assert!(served(&addr));
+        sleep(Duration::from_millis(20));
+    }
+
+    assert!(matches!(
+        stopped.recv_timeout(Duration::from_millis(100)),
+        Err(RecvTimeoutError::Timeout)
+    ));
+    assert!(served(&addr));
+}
+
+#[test]
+fn test_summary() {
+    let shutdown = ShutdownSignal::new();
+    let (addr, stopped) = start(app(shutdown.clone()));
+
+    for _ in 0..3 {
+        assert!(served(&addr));
+    }
+    assert!(request(&addr, "/error").starts_with("HTTP/1.1 500 Internal Server Error\r\n"));
+    assert!(request(&addr, "/missing").starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+    // Two requests on one keep-alive connection, which is waiting for another request when the app
+    //   stops, so it is closed rather than abandoned
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    let mut response = [0; 1024];
+    for _ in 0..2 {
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nConnection: Keep-Alive\r\n\r\n")
+            .unwrap();
+        assert!(stream.read(&mut response).unwrap() > 0);
+    }
+
+    sleep(Duration::from_millis(50));
+    shutdown.trigger();
+    let summary = stopped.recv_timeout(STOP_TIMEOUT).unwrap();
+
+    assert!(matches!(summary.reason, ExitReason::Shutdown));
+    // The connection made by `start` to wait for the app is counted, but sends no request
+    assert_eq!(summary.connections_accepted, 7);
+    assert_eq!(summary.connections_abandoned, 0);
+    assert_eq!(stream.read(&mut response).unwrap(), 0);
+    assert_eq!(
+        summary.requests,
+        RequestCounts {
+            success: 5,
+            client_error: 1,
+            server_error: 1,
+            ..Default::default()
+        }
+    );
+    assert_eq!(summary.requests.total(), 7);
+    assert!(summary.uptime >= Duration::from_millis(50));
+}
@@ -0,0 +1,107 @@
+use crate::http::method::Method;
+use crate::http::{Request, Response, StatusCode};
+use crate::monitor::event::{Event, EventType};
+use crate::monitor::MonitorConfig;
+use crate::App;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::sleep;
+use std::time::Duration;
+
+/// How long the slow handler takes.
+const HANDLER_DELAY: Duration = Duration::from_millis(250);
+
+/// Starts an app with a slow route and a fast route, returning its address, the monitor receiver
+///   and the shutdown signal.
+fn start_app() -> (String, Receiver<Event>, Sender<()>) {
+    let (shutdown_tx, shutdown_rx) = channel();
+    let (monitor_tx, monitor_rx) = channel();
+
+    let app: App<()> = App::new_with_config(2, ())
+        .with_stateless_route("/slow/*", |_: Request| {
+            sleep(HANDLER_DELAY);
+            Response::new(StatusCode::OK, "slow")
+        })
+        .with_stateless_route("/fast", |_: Request| Response::new(StatusCode::OK, "fast"))
+        .with_monitor(
+            MonitorConfig::new(monitor_tx).with_slow_request_threshold(Duration::from_millis(100)),
+        )
+        .with_shutdown(shutdown_rx);
+
+    let addr = crate::tests::start_app(app);
+
+    (addr, monitor_rx, shutdown_tx)
+}
+
+/// Reads one response from the stream, which must have a body of `slow` or `fast`.
+fn read_response(stream: &mut TcpStream) -> String {
+    let mut response: Vec<u8> = Vec::new();
+    let mut buf = [0; 1024];
+
+    while !response
+        .windows(8)
+        .any(|w| w == b"\r\n\r\nslow" || w == b"\r\n\r\nfast")
+    {
+        let n = stream.read(&mut buf).unwrap();
+        assert_ne!(n, 0, "connection closed before the response was received");
+        response.extend_from_slice(&buf[..n]);
+    }
+
+    String::from_utf8(response).unwrap()
+}
+
+#[test]
+fn test_slow_request_event() {
+    let (addr, monitor_rx, shutdown) = start_app();
+
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    // Fast requests do not cause events
+    stream
+        .write_all(b"GET /fast HTTP/1.1\r\nConnection: Keep-Alive\r\n\r\n")
+        .unwrap();
+    assert!(read_response(&mut stream).starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(monitor_rx.recv_timeout(Duration::from_millis(200)).is_err());
+
+    // Time spent idle between requests is not parse time, but time spent receiving the request is
+    sleep(Duration::from_millis(150));
+    stream.write_all(b"GET /slow/page HTTP/1.1\r\n").unwrap();
+    sleep(Duration::from_millis(50));
+    stream.write_all(b"Connection: Keep-Alive\r\n\r\n").unwrap();
+    assert!(read_response(&mut stream).starts_with("HTTP/1.1 200 OK\r\n"));
+
+    let event = monitor_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(event.kind, EventType::SlowRequest);
+    assert_eq!(event.peer, Some(stream.local_addr().unwrap()));
+
+    let request = event.request.unwrap();
+    assert_eq!(request.method, Method::Get);
+    assert_eq!(request.uri, "/slow/page");
+    assert_eq!(request.route.as_deref(), Some("/slow/*"));
+    assert_eq!(request.status, StatusCode::OK);
+
+    let timing = request.timing;
+    assert!(timing.handler >= HANDLER_DELAY, "{:?}", timing);
+    assert!(timing.handler < Duration::from_secs(2), "{:?}", timing);
+    assert!(timing.parse >= Duration::from_millis(40), "{:?}", timing);
+    assert!(timing.parse < Duration::from_millis(150), "{:?}", timing);
+    assert!(timing.write < HANDLER_DELAY, "{:?}", timing);
+    assert_eq!(timing.total(), timing.parse + timing.handler + timing.write);
+
+    let info = event.info.unwrap();
+    assert!(
+        info.starts_with("GET /slow/page 200 OK (route /slow/*) took parse "),
+        "{}",
+        info
+    );
+
+    // Other events are not sent, since the monitor is only subscribed to slow requests
+    assert!(monitor_rx.recv_timeout(Duration::from_millis(200)).is_err());
+
+    shutdown.send(()).unwrap();
+}
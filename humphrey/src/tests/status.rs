@@ -4,18 +4,22 @@ use std::convert::{TryFrom, TryInto};
 
 #[test]
 fn test_from_code() {
-    let valid_codes: [u16; 39] = [
-        100, 101, 200, 201, 202, 203, 204, 205, 206, 300, 301, 302, 303, 304, 305, 307, 400, 401,
-        403, 404, 405, 406, 407, 408, 409, 410, 411, 412, 413, 414, 415, 416, 417, 500, 501, 502,
-        503, 504, 505,
+    let valid_codes: [u16; 62] = [
+        100, 101, 102, 103, 200, 201, 202, 203, 204, 205, 206, 207, 208, 226, 300, 301, 302, 303,
+        304, 305, 307, 308, 400, 401, 402, 403, 404, 405, 406, 407, 408, 409, 410, 411, 412, 413,
+        414, 415, 416, 417, 418, 421, 422, 423, 424, 425, 426, 428, 429, 431, 451, 500, 501, 502,
+        503, 504, 505, 506, 507, 508, 510, 511,
     ];
 
     for code in valid_codes {
-        assert!(StatusCode::try_from(code).is_ok());
+        let status = StatusCode::try_from(code).unwrap();
+
+        assert!(!matches!(status, StatusCode::Custom(_)));
+        assert_eq!(u16::from(status), code);
     }
 
     assert!(StatusCode::try_from(69).is_err());
-    assert!(StatusCode::try_from(420).is_err());
+    assert!(StatusCode::try_from(600).is_err());
     assert!(StatusCode::try_from(1337).is_err());
 }
 
@@ -23,12 +27,41 @@ fn test_from_code() {
 fn test_into_code() {
     assert!(TryInto::<StatusCode>::try_into(200u16).is_ok());
     assert!(TryInto::<StatusCode>::try_into(404u16).is_ok());
+    assert!(TryInto::<StatusCode>::try_into(499u16).is_ok());
     assert!(TryInto::<StatusCode>::try_into(1337u16).is_err());
 }
 
+#[test]
+fn test_custom_code() {
+    for code in [299, 420, 499, 599] {
+        let status = StatusCode::try_from(code).unwrap();
+
+        assert_eq!(status, StatusCode::Custom(code));
+        assert_eq!(u16::from(status), code);
+    }
+
+    assert_eq!(Into::<&str>::into(StatusCode::Custom(199)), "Informational");
+    assert_eq!(Into::<&str>::into(StatusCode::Custom(299)), "Success");
+    assert_eq!(Into::<&str>::into(StatusCode::Custom(399)), "Redirection");
+    assert_eq!(Into::<&str>::into(StatusCode::Custom(499)), "Client Error");
+    assert_eq!(Into::<&str>::into(StatusCode::Custom(599)), "Server Error");
+}
+
 #[test]
 fn test_into_string() {
     assert_eq!(Into::<&str>::into(StatusCode::OK), "OK");
     assert_eq!(Into::<&str>::into(StatusCode::NotFound), "Not Found");
     assert_eq!(Into::<&str>::into(StatusCode::BadGateway), "Bad Gateway");
+    assert_eq!(Into::<&str>::into(StatusCode::ImATeapot), "I'm a teapot");
+}
+
+#[test]
+fn test_has_no_body() {
+    assert!(StatusCode::Continue.has_no_body());
+    assert!(StatusCode::SwitchingProtocols.has_no_body());
+    assert!(StatusCode::NoContent.has_no_body());
+    assert!(StatusCode::NotModified.has_no_body());
+    assert!(!StatusCode::OK.has_no_body());
+    assert!(!StatusCode::ResetContent.has_no_body());
+    assert!(!StatusCode::NotFound.has_no_body());
 }
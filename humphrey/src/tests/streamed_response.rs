@@ -0,0 +1,221 @@
+use crate::http::{Request, Response, StatusCode};
+use crate::App;
+
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{channel, Sender};
+use std::time::Duration;
+
+/// Produces numbered lines one at a time, like a log tail or database export, then fails if asked to.
+struct Lines {
+    next: usize,
+    count: usize,
+    fail: bool,
+}
+
+impl Read for Lines {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.next == self.count {
+            return match self.fail {
+                true => Err(ErrorKind::BrokenPipe.into()),
+                false => Ok(0),
+            };
+        }
+
+        let line = format!("line {}\n", self.next);
+        self.next += 1;
+        buf[..line.len()].copy_from_slice(line.as_bytes());
+
+        Ok(line.len())
+    }
+}
+
+/// Starts an app which streams three lines from `/lines`, the same lines with a known length from
+///   `/sized`, and fails after one line on `/broken`, returning its address and shutdown signal.
+fn start_app() -> (String, Sender<()>) {
+    let (shutdown_tx, shutdown_rx) = channel();
+
+    let app: App<()> = App::new_with_config(2, ())
+        .with_stateless_route("/lines", |_: Request| {
+            let lines = Lines {
+                next: 0,
+                count: 3,
+                fail: false,
+            };
+
+            Response::chunked(StatusCode::OK, lines)
+        })
+        .with_stateless_route("/broken", |_: Request| {
+            let lines = Lines {
+                next: 0,
+                count: 1,
+                fail: true,
+            };
+
+            Response::chunked(StatusCode::OK, lines)
+        })
+        .with_stateless_route("/sized", |_: Request| {
+            let lines = Lines {
+                next: 0,
+                count: 3,
+                fail: false,
+            };
+
+            Response::from_reader(StatusCode::OK, lines, 21)
+        })
+        .with_stateless_route("/*", |_: Request| Response::new(StatusCode::OK, "buffered"))
+        .with_shutdown(shutdown_rx);
+
+    let addr = crate::tests::start_app(app);
+
+    (addr, shutdown_tx)
+}
+
+/// Reads one response head from the stream, returning its status line and headers.
+fn read_head(reader: &mut impl BufRead) -> (String, Vec<String>) {
+    let mut lines = Vec::new();
+
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+
+        if line == "\r\n" || line.is_empty() {
+            break;
+        }
+
+        lines.push(line.trim_end().to_string());
+    }
+
+    let status = lines.remove(0);
+    let headers = lines
+        .into_iter()
+        .filter(|line| !line.starts_with("Date: ") && !line.starts_with("Server: "))
+        .collect();
+
+    (status, headers)
+}
+
+#[test]
+fn test_chunked_response_keep_alive() {
+    let (addr, shutdown) = start_app();
+
+    let stream = TcpStream::connect(&addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+
+    writer
+        .write_all(b"GET /lines HTTP/1.1\r\nConnection: keep-alive\r\n\r\n")
+        .unwrap();
+
+    let (status, headers) = read_head(&mut reader);
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert_eq!(
+        headers,
+        vec!["Connection: keep-alive", "Transfer-Encoding: chunked"]
+    );
+
+    // Each line is sent as its own chunk as soon as it is read
+    let mut body = [0; 41];
+    reader.read_exact(&mut body).unwrap();
+
+    assert_eq!(
+        &body,
+        b"7\r\nline 0\n\r\n7\r\nline 1\n\r\n7\r\nline 2\n\r\n0\r\n\r\n"
+    );
+
+    // The connection is still usable afterwards
+    writer
+        .write_all(b"GET / HTTP/1.1\r\nConnection: keep-alive\r\n\r\n")
+        .unwrap();
+
+    let (status, headers) = read_head(&mut reader);
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert_eq!(headers, vec!["Connection: keep-alive", "Content-Length: 8"]);
+
+    shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_sized_response_keep_alive() {
+    let (addr, shutdown) = start_app();
+
+    let stream = TcpStream::connect(&addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+
+    for version in ["HTTP/1.1", "HTTP/1.0"] {
+        write!(
+            writer,
+            "GET /sized {}\r\nConnection: keep-alive\r\n\r\n",
+            version
+        )
+        .unwrap();
+
+        let (status, headers) = read_head(&mut reader);
+        assert_eq!(status, format!("{} 200 OK", version));
+        assert_eq!(
+            headers,
+            vec!["Connection: keep-alive", "Content-Length: 21"]
+        );
+
+        let mut body = [0; 21];
+        reader.read_exact(&mut body).unwrap();
+
+        assert_eq!(&body, b"line 0\nline 1\nline 2\n");
+    }
+
+    shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_chunked_response_http_1_0() {
+    let (addr, shutdown) = start_app();
+
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    // The client asks for keep-alive, but the end of the body can only be marked by closing
+    stream
+        .write_all(b"GET /lines HTTP/1.0\r\nConnection: keep-alive\r\n\r\n")
+        .unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    let (status, headers) = read_head(&mut response.as_bytes());
+    assert_eq!(status, "HTTP/1.0 200 OK");
+    assert_eq!(headers, vec!["Connection: Close"]);
+    assert!(response.ends_with("\r\n\r\nline 0\nline 1\nline 2\n"));
+
+    shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_broken_chunked_response() {
+    let (addr, shutdown) = start_app();
+
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    stream
+        .write_all(b"GET /broken HTTP/1.1\r\nConnection: keep-alive\r\n\r\n")
+        .unwrap();
+
+    // The connection is closed without the final chunk, so the client can tell the body is incomplete
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    assert!(response.ends_with("\r\n\r\n7\r\nline 0\n\r\n"));
+
+    shutdown.send(()).unwrap();
+}
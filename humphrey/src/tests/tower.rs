@@ -0,0 +1,262 @@
+use crate::cancellation::CancellationToken;
+use crate::http::address::Address;
+use crate::http::cors::Cors;
+use crate::http::headers::{Header, HeaderType, Headers};
+use crate::http::method::Method;
+use crate::http::request::BodyState;
+use crate::http::{Request, Response, StatusCode};
+use crate::route::RouteParams;
+use crate::tower::{ConversionError, Trailers};
+use crate::App;
+
+use bytes::Bytes;
+use futures::executor::block_on;
+use tower_service::Service;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+fn sorted(headers: &Headers) -> Vec<Header> {
+    headers.iter().collect()
+}
+
+#[test]
+fn test_request_round_trip() {
+    let mut headers = Headers::new();
+    headers.add(HeaderType::Host, "localhost");
+    headers.add(HeaderType::Cookie, "session=abc");
+    headers.add("X-Custom", "caf\u{e9}");
+    headers.add("X-Custom", "second value");
+    headers.add(HeaderType::ContentLength, "5");
+
+    let request = Request {
+        method: Method::Post,
+        overridden_method: None,
+        uri: "/api/upload".into(),
+        uri_raw: "/api/upload".into(),
+        query: "name=test&empty".into(),
+        version: "HTTP/1.0".into(),
+        headers,
+        content: Some(b"hello".to_vec()),
+        body_state: BodyState::Complete,
+        trailers: Headers::new(),
+        address: Address::new("1.2.3.4:5678").unwrap(),
+        secure: false,
+        local_addr: None,
+        deadline: None,
+        trace: None,
+        cancellation: CancellationToken::default(),
+        params: RouteParams::default(),
+    };
+
+    let converted = http::Request::<Bytes>::try_from(request.clone()).unwrap();
+    assert_eq!(converted.method(), http::Method::POST);
+    assert_eq!(converted.uri(), "/api/upload?name=test&empty");
+    assert_eq!(converted.version(), http::Version::HTTP_10);
+    assert_eq!(converted.headers().get_all("x-custom").iter().count(), 2);
+    assert_eq!(converted.body().as_ref(), b"hello");
+
+    let round_trip = Request::try_from(converted).unwrap();
+    assert_eq!(round_trip.method, request.method);
+    assert_eq!(round_trip.uri, request.uri);
+    assert_eq!(round_trip.query, request.query);
+    assert_eq!(round_trip.version, request.version);
+    assert_eq!(sorted(&round_trip.headers), sorted(&request.headers));
+    assert_eq!(round_trip.content, request.content);
+    assert_eq!(round_trip.address, request.address);
+}
+
+fn test_response() -> Response {
+    let mut response = Response::empty(StatusCode::NotFound)
+        .with_header(HeaderType::ContentType, "text/plain")
+        .with_header(HeaderType::SetCookie, "a=1; Path=/")
+        .with_header(HeaderType::SetCookie, "b=2")
+        .with_bytes(b"not here");
+    response.trailers.add("Server-Timing", "total;dur=3");
+
+    response
+}
+
+#[test]
+fn test_response_round_trip() {
+    let response = test_response();
+
+    let converted = http::Response::<Bytes>::try_from(test_response()).unwrap();
+    assert_eq!(converted.status(), http::StatusCode::NOT_FOUND);
+    assert_eq!(
+        converted
+            .headers()
+            .get_all(http::header::SET_COOKIE)
+            .iter()
+            .collect::<Vec<_>>(),
+        vec!["a=1; Path=/", "b=2"]
+    );
+
+    let round_trip = Response::try_from(converted).unwrap();
+    assert_eq!(round_trip.status_code, response.status_code);
+    assert_eq!(round_trip.version, response.version);
+    assert_eq!(sorted(&round_trip.headers), sorted(&response.headers));
+    assert_eq!(round_trip.body, response.body);
+    assert_eq!(round_trip.trailers, response.trailers);
+}
+
+#[test]
+fn test_unsupported_conversions() {
+    // Every method which `http` accepts is a valid token, so extension methods are converted too
+    for (name, method) in [
+        ("TRACE", Method::Trace),
+        ("PROPFIND", Method::Custom("PROPFIND".into())),
+    ] {
+        let request = http::Request::builder()
+            .method(name)
+            .uri("/")
+            .body(Bytes::new())
+            .unwrap();
+        let converted = Request::try_from(request).unwrap();
+        assert_eq!(converted.method, method);
+
+        let round_trip = http::Request::<Bytes>::try_from(converted).unwrap();
+        assert_eq!(round_trip.method(), name);
+    }
+
+    // Unregistered codes within the valid range are converted, but codes above 599 are not
+    let response = http::Response::builder()
+        .status(299)
+        .body(Bytes::new())
+        .unwrap();
+    assert_eq!(
+        Response::try_from(response).unwrap().status_code,
+        StatusCode::Custom(299)
+    );
+
+    let response = http::Response::builder()
+        .status(600)
+        .body(Bytes::new())
+        .unwrap();
+    assert_eq!(
+        Response::try_from(response).unwrap_err(),
+        ConversionError::Status
+    );
+
+    let mut response = Response::empty(StatusCode::OK);
+    response.version = "HTTP/4".into();
+    assert_eq!(
+        http::Response::<Bytes>::try_from(response).unwrap_err(),
+        ConversionError::Version
+    );
+}
+
+#[test]
+fn test_request_address_from_extensions() {
+    let peer: SocketAddr = "10.0.0.1:4000".parse().unwrap();
+    let request = http::Request::builder()
+        .uri("http://example.com/path?x=1")
+        .header("X-Forwarded-For", "1.2.3.4")
+        .extension(peer)
+        .body(Bytes::new())
+        .unwrap();
+
+    let request = Request::try_from(request).unwrap();
+    assert_eq!(request.uri, "/path");
+    assert_eq!(request.query, "x=1");
+    assert_eq!(request.content, None);
+    assert_eq!(request.address.origin_addr.to_string(), "1.2.3.4");
+    assert_eq!(request.address.proxies, vec![peer.ip()]);
+}
+
+#[test]
+fn test_service_routing() {
+    async fn hello(request: Request, state: Arc<usize>) -> Response {
+        Response::new(StatusCode::OK, format!("{} {}", request.query, state))
+    }
+
+    let mut service = App::new_with_config(42_usize)
+        .with_route("/hello", hello)
+        .with_cors_config("/hello", Cors::wildcard())
+        .into_service();
+
+    let request = http::Request::builder()
+        .uri("/hello?name=tower")
+        .body(Bytes::new())
+        .unwrap();
+    let response = block_on(service.call(request)).unwrap();
+    assert_eq!(response.status(), http::StatusCode::OK);
+    assert_eq!(response.body().as_ref(), b"name=tower 42");
+    assert_eq!(
+        response
+            .headers()
+            .get("access-control-allow-origin")
+            .unwrap(),
+        "*"
+    );
+
+    let request = http::Request::builder()
+        .uri("/missing")
+        .body(Bytes::new())
+        .unwrap();
+    let response = block_on(service.call(request)).unwrap();
+    assert_eq!(response.status(), http::StatusCode::NOT_FOUND);
+    assert!(response.extensions().get::<Trailers>().is_none());
+}
+
+#[test]
+fn test_service_head() {
+    async fn method(request: Request, _: Arc<()>) -> Response {
+        let methods = format!("{} {}", request.method, request.original_method());
+        Response::new(StatusCode::OK, methods)
+    }
+
+    async fn head(_: Request, _: Arc<()>) -> Response {
+        Response::empty(StatusCode::OK).with_header("X-Head-Handler", "true")
+    }
+
+    let mut service = App::new_with_config(())
+        .with_route("/", method)
+        .with_route("/custom", method)
+        .with_head_route("/custom", head)
+        .into_service();
+
+    // `HEAD` requests are handled as `GET` requests unless the route has a `HEAD` handler
+    let request = http::Request::builder()
+        .method("HEAD")
+        .uri("/")
+        .body(Bytes::new())
+        .unwrap();
+    let response = block_on(service.call(request)).unwrap();
+    assert_eq!(response.body().as_ref(), b"GET HEAD");
+
+    let request = http::Request::builder()
+        .method("HEAD")
+        .uri("/custom")
+        .body(Bytes::new())
+        .unwrap();
+    let response = block_on(service.call(request)).unwrap();
+    assert_eq!(response.headers().get("x-head-handler").unwrap(), "true");
+}
+
+#[test]
+fn test_service_method_not_allowed() {
+    async fn create(_: Request, _: Arc<()>) -> Response {
+        Response::empty(StatusCode::Created)
+    }
+
+    let mut service = App::new_with_config(())
+        .with_route_method("/items", Method::Post, create)
+        .into_service();
+
+    let request = http::Request::builder()
+        .uri("/items")
+        .body(Bytes::new())
+        .unwrap();
+    let response = block_on(service.call(request)).unwrap();
+    assert_eq!(response.status(), http::StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.headers().get("allow").unwrap(), "POST, OPTIONS");
+
+    let request = http::Request::builder()
+        .method("POST")
+        .uri("/items")
+        .body(Bytes::new())
+        .unwrap();
+    let response = block_on(service.call(request)).unwrap();
+    assert_eq!(response.status(), http::StatusCode::CREATED);
+}
@@ -0,0 +1,459 @@
+use crate::http::headers::Headers;
+use crate::trace::{
+    parse_tracestate, SpanId, TraceContext, TraceId, TraceParent, TRACEPARENT_HEADER,
+    TRACESTATE_HEADER,
+};
+
+/// The trace ID used in the examples of the W3C Trace Context specification.
+const TRACE_ID: TraceId = TraceId([
+    0x0a, 0xf7, 0x65, 0x19, 0x16, 0xcd, 0x43, 0xdd, 0x84, 0x48, 0xeb, 0x21, 0x1c, 0x80, 0x31, 0x9c,
+]);
+
+/// The parent ID used in the examples of the W3C Trace Context specification.
+const PARENT_ID: SpanId = SpanId([0xb7, 0xad, 0x6b, 0x71, 0x69, 0x20, 0x33, 0x31]);
+
+/// Builds headers with the given `traceparent` and `tracestate` headers.
+fn headers(traceparents: &[&str], tracestates: &[&str]) -> Headers {
+    let mut headers = Headers::new();
+
+    for traceparent in traceparents {
+        headers.add(TRACEPARENT_HEADER, traceparent);
+    }
+
+    for tracestate in tracestates {
+        headers.add(TRACESTATE_HEADER, tracestate);
+    }
+
+    headers
+}
+
+#[test]
+fn test_traceparent_valid() {
+    let traceparent =
+        TraceParent::parse("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01").unwrap();
+
+    assert_eq!(
+        traceparent,
+        TraceParent {
+            version: 0,
+            trace_id: TRACE_ID,
+            parent_id: PARENT_ID,
+            flags: 1
+        }
+    );
+    assert!(traceparent.sampled());
+
+    let unsampled =
+        TraceParent::parse("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-00").unwrap();
+    assert!(!unsampled.sampled());
+
+    // Whitespace around the header is allowed
+    assert_eq!(
+        TraceParent::parse(" \t00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01\t "),
+        Some(traceparent)
+    );
+}
+
+#[test]
+fn test_traceparent_future_version() {
+    // Later versions may add fields after a dash, and are parsed as far as version 00 goes
+    let traceparent = TraceParent::parse(
+        "cc-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01-what-the-future-will-be-like",
+    )
+    .unwrap();
+
+    assert_eq!(traceparent.version, 0xcc);
+    assert_eq!(traceparent.trace_id, TRACE_ID);
+    assert_eq!(traceparent.parent_id, PARENT_ID);
+    assert!(traceparent.sampled());
+
+    // Unknown flags are kept, but only the sampled flag is interpreted
+    let traceparent =
+        TraceParent::parse("01-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-08").unwrap();
+    assert_eq!(traceparent.flags, 0x08);
+    assert!(!traceparent.sampled());
+
+    assert_eq!(
+        TraceParent::parse("cc-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01.what"),
+        None
+    );
+}
+
+#[test]
+fn test_traceparent_invalid() {
+    let invalid = [
+        // Empty or malformed
+        "",
+        "00",
+        "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331",
+        // Version
+        "ff-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+        "0-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+        "000-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+        "0g-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+        "FF-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+        // Version 00 cannot have further fields
+        "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01-",
+        "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01-what",
+        // Trace ID
+        "00-00000000000000000000000000000000-b7ad6b7169203331-01",
+        "00-0AF7651916CD43DD8448EB211C80319C-b7ad6b7169203331-01",
+        "00-0af7651916cd43dd8448eb211c8031gg-b7ad6b7169203331-01",
+        "00-0af7651916cd43dd8448eb211c80319-b7ad6b7169203331-01",
+        "00-0af7651916cd43dd8448eb211c80319c0-b7ad6b7169203331-01",
+        // Parent ID
+        "00-0af7651916cd43dd8448eb211c80319c-0000000000000000-01",
+        "00-0af7651916cd43dd8448eb211c80319c-B7AD6B7169203331-01",
+        "00-0af7651916cd43dd8448eb211c80319c-b7ad6b716920333g-01",
+        "00-0af7651916cd43dd8448eb211c80319c-b7ad6b716920333-01",
+        "00-0af7651916cd43dd8448eb211c80319c-b7ad6b71692033310-01",
+        // Flags
+        "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-0",
+        "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-001",
+        "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-0g",
+        "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-0A",
+        // Delimiters
+        "00_0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+        "00-0af7651916cd43dd8448eb211c80319c_b7ad6b7169203331-01",
+        "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331_01",
+        "00-0af7651916cd43dd8448eb211c80319c--b7ad6b716920333-01",
+        // Non-ASCII characters in place of a field
+        "00-0af7651916cd43dd8448eb211c8031é-b7ad6b7169203331-01",
+    ];
+
+    for traceparent in invalid {
+        assert_eq!(TraceParent::parse(traceparent), None, "{:?}", traceparent);
+    }
+}
+
+#[test]
+fn test_traceparent_serialise() {
+    let header = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+    assert_eq!(TraceParent::parse(header).unwrap().to_string(), header);
+
+    let traceparent = TraceParent {
+        version: 0,
+        trace_id: TraceId([0; 16]),
+        parent_id: SpanId([0xff; 8]),
+        flags: 0,
+    };
+    assert_eq!(
+        traceparent.to_string(),
+        "00-00000000000000000000000000000000-ffffffffffffffff-00"
+    );
+}
+
+#[test]
+fn test_tracestate() {
+    assert_eq!(
+        parse_tracestate("rojo=00f067aa0ba902b7,congo=t61rcWkgMzE").as_deref(),
+        Some("rojo=00f067aa0ba902b7,congo=t61rcWkgMzE")
+    );
+
+    // Whitespace and empty members are removed
+    assert_eq!(
+        parse_tracestate(" foo=1 ,\t, bar=2,").as_deref(),
+        Some("foo=1,bar=2")
+    );
+
+    // Values may contain spaces, but not end with one
+    assert_eq!(parse_tracestate("foo= a b").as_deref(), Some("foo= a b"));
+
+    // Multi-tenant keys
+    assert_eq!(
+        parse_tracestate("1tenant@system=1,t_-*/@s_-*/9=2").as_deref(),
+        Some("1tenant@system=1,t_-*/@s_-*/9=2")
+    );
+
+    assert_eq!(parse_tracestate(""), None);
+    assert_eq!(parse_tracestate(" , "), None);
+}
+
+#[test]
+fn test_tracestate_invalid() {
+    let long_key = "k".repeat(257);
+    let long_value = "v".repeat(257);
+    let long_tenant = "t".repeat(242);
+    let long_system = "s".repeat(15);
+
+    let invalid = [
+        "foo",
+        "foo=",
+        "=1",
+        "FOO=1",
+        "1foo=1",
+        "foo bar=1",
+        "foo=1=2",
+        "foo=\u{7f}",
+        "foo=é",
+        "foo=1,foo=2",
+        "foo=1,bar",
+        "@system=1",
+        "tenant@=1",
+        "tenant@1system=1",
+        "tenant@system@other=1",
+        &format!("{}=1", long_key),
+        &format!("foo={}", long_value),
+        &format!("{}@system=1", long_tenant),
+        &format!("tenant@{}=1", long_system),
+    ];
+
+    for tracestate in invalid {
+        assert_eq!(parse_tracestate(tracestate), None, "{:?}", tracestate);
+    }
+
+    // The longest keys and values are allowed
+    let longest = format!(
+        "{}=1,{}@{}=2,foo={}",
+        &long_key[1..],
+        &long_tenant[1..],
+        &long_system[1..],
+        &long_value[1..]
+    );
+    assert_eq!(parse_tracestate(&longest), Some(longest.clone()));
+}
+
+#[test]
+fn test_tracestate_member_limit() {
+    let members = |count: usize| -> String {
+        (0..count)
+            .map(|i| format!("k{}=v", i))
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+
+    assert_eq!(parse_tracestate(&members(32)), Some(members(32)));
+    assert_eq!(parse_tracestate(&members(33)), None);
+}
+
+#[test]
+fn test_context_continues_trace() {
+    let sampled = headers(
+        &["00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"],
+        &["rojo=00f067aa0ba902b7", "congo=t61rcWkgMzE"],
+    );
+    let context = TraceContext::from_headers(&sampled, false);
+
+    assert_eq!(context.trace_id, TRACE_ID);
+    assert_eq!(context.parent_id, Some(PARENT_ID));
+    assert_ne!(context.span_id, PARENT_ID);
+    assert!(context.span_id.is_valid());
+    assert!(context.sampled);
+    assert_eq!(
+        context.state.as_deref(),
+        Some("rojo=00f067aa0ba902b7,congo=t61rcWkgMzE")
+    );
+
+    // Outgoing requests are children of the new span, and always use version 00
+    let future = headers(
+        &["cc-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-00-future"],
+        &[],
+    );
+    let context = TraceContext::from_headers(&future, true);
+
+    assert!(!context.sampled);
+    assert_eq!(
+        context.traceparent().to_string(),
+        format!("00-{}-{}-00", TRACE_ID, context.span_id)
+    );
+}
+
+#[test]
+fn test_context_starts_trace() {
+    let context = TraceContext::from_headers(&Headers::new(), true);
+    assert_eq!(context.parent_id, None);
+    assert!(context.sampled);
+    assert!(context.trace_id.is_valid());
+
+    let other = TraceContext::from_headers(&Headers::new(), false);
+    assert!(!other.sampled);
+    assert_ne!(other.trace_id, context.trace_id);
+    assert_ne!(other.span_id, context.span_id);
+
+    // The trace state is ignored when the trace parent is invalid
+    let invalid = headers(
+        &["00-00000000000000000000000000000000-b7ad6b7169203331-01"],
+        &["foo=1"],
+    );
+    let context = TraceContext::from_headers(&invalid, false);
+    assert_ne!(context.trace_id, TraceId([0; 16]));
+    assert!(!context.sampled);
+    assert_eq!(context.state, None);
+
+    // A request with several trace parents is ambiguous
+    let ambiguous = headers(
+        &[
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+        ],
+        &[],
+    );
+    assert_ne!(
+        TraceContext::from_headers(&ambiguous, false).trace_id,
+        TRACE_ID
+    );
+}
+
+#[test]
+fn test_context_drops_invalid_state() {
+    let headers = headers(
+        &["00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"],
+        &["foo=1", "foo=2"],
+    );
+    let context = TraceContext::from_headers(&headers, false);
+
+    // The trace is still continued
+    assert_eq!(context.trace_id, TRACE_ID);
+    assert_eq!(context.state, None);
+}
+
+#[cfg(not(feature = "tokio"))]
+mod app {
+    use crate::client::Client;
+    use crate::http::{Request, Response, StatusCode};
+    use crate::monitor::event::{Event, EventType};
+    use crate::monitor::MonitorConfig;
+    use crate::trace::{TraceContext, TRACEPARENT_HEADER, TRACESTATE_HEADER};
+    use crate::App;
+
+    use std::sync::mpsc::{channel, Receiver, Sender};
+    use std::thread::spawn;
+    use std::time::Duration;
+
+    /// Describes a trace context as the trace ID, parent ID, span ID, sampling decision and state.
+    fn describe(trace: &TraceContext) -> String {
+        format!(
+            "{} {} {} {} {}",
+            trace.trace_id,
+            trace
+                .parent_id
+                .map_or_else(|| "none".to_string(), |id| id.to_string()),
+            trace.span_id,
+            trace.sampled,
+            trace.state.as_deref().unwrap_or("none")
+        )
+    }
+
+    /// Starts an app with trace propagation whose `/` route calls its `/downstream` route with the
+    ///   trace context of its request, returning its address, the monitor receiver and the shutdown
+    ///   signal.
+    fn start_app() -> (String, Receiver<Event>, Sender<()>) {
+        let addr = crate::tests::free_addr();
+        let (shutdown_tx, shutdown_rx) = channel();
+        let (monitor_tx, monitor_rx) = channel();
+        let downstream = format!("http://{}/downstream", addr);
+
+        let app: App<()> = App::new_with_config(4, ())
+            .with_trace_propagation(true)
+            .with_stateless_route("/", move |request: Request| {
+                let trace = request.trace.unwrap();
+                let response = Client::new()
+                    .get(&downstream)
+                    .unwrap()
+                    .with_trace_context(&trace)
+                    .send()
+                    .unwrap();
+
+                Response::new(
+                    StatusCode::OK,
+                    format!("{}\n{}", describe(&trace), response.text().unwrap()),
+                )
+            })
+            .with_stateless_route("/downstream", |request: Request| {
+                Response::new(StatusCode::OK, describe(&request.trace.unwrap()))
+            })
+            .with_monitor(
+                MonitorConfig::new(monitor_tx)
+                    .with_subscription_to(EventType::RequestServedSuccess),
+            )
+            .with_shutdown(shutdown_rx);
+
+        let cloned_addr = addr.clone();
+        spawn(move || app.run(cloned_addr).unwrap());
+        crate::tests::wait_for(&addr);
+
+        (addr, monitor_rx, shutdown_tx)
+    }
+
+    /// Sends a request to the app with the given `traceparent` header, returning the descriptions of
+    ///   the trace contexts of the `/` and `/downstream` routes.
+    fn send(addr: &str, traceparent: Option<&str>) -> Vec<Vec<String>> {
+        let mut client = Client::new();
+        let mut request = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .with_header(TRACESTATE_HEADER, "foo=1");
+
+        if let Some(traceparent) = traceparent {
+            request = request.with_header(TRACEPARENT_HEADER, traceparent);
+        }
+
+        let response = request.send().unwrap();
+
+        response
+            .text()
+            .unwrap()
+            .lines()
+            .map(|line| line.split(' ').map(|part| part.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_trace_propagation() {
+        let (addr, monitor, shutdown) = start_app();
+
+        let traces = send(
+            &addr,
+            Some("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-00"),
+        );
+        let (upstream, downstream) = (&traces[0], &traces[1]);
+
+        // The trace and its sampling decision are followed, and the downstream call is a child of
+        //   the span of the upstream request
+        assert_eq!(upstream[0], "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(upstream[1], "b7ad6b7169203331");
+        assert_eq!(upstream[3], "false");
+        assert_eq!(upstream[4], "foo=1");
+        assert_eq!(downstream[0], upstream[0]);
+        assert_eq!(downstream[1], upstream[2]);
+        assert_ne!(downstream[2], upstream[2]);
+        assert_eq!(downstream[3], "false");
+        assert_eq!(downstream[4], "foo=1");
+
+        // Both requests are reported with the IDs of their own spans
+        let mut spans: Vec<String> = (0..2)
+            .map(|_| monitor.recv_timeout(Duration::from_secs(1)).unwrap())
+            .inspect(|event| assert_eq!(event.trace_id.unwrap().to_string(), upstream[0]))
+            .map(|event| event.span_id.unwrap().to_string())
+            .collect();
+        spans.sort();
+
+        let mut expected = vec![upstream[2].clone(), downstream[2].clone()];
+        expected.sort();
+
+        assert_eq!(spans, expected);
+
+        shutdown.send(()).unwrap();
+    }
+
+    #[test]
+    fn test_new_trace() {
+        let (addr, _monitor, shutdown) = start_app();
+
+        // New traces use the default sampling decision, and an invalid trace parent is ignored
+        //   along with the trace state
+        for traceparent in [None, Some("00-invalid")] {
+            let traces = send(&addr, traceparent);
+            let (upstream, downstream) = (&traces[0], &traces[1]);
+
+            assert_eq!(upstream[1], "none");
+            assert_eq!(upstream[3], "true");
+            assert_eq!(upstream[4], "none");
+            assert_eq!(downstream[0], upstream[0]);
+            assert_eq!(downstream[1], upstream[2]);
+            assert_eq!(downstream[3], "true");
+        }
+
+        shutdown.send(()).unwrap();
+    }
+}
@@ -0,0 +1,11 @@
+use humphrey::http::{Response, StatusCode};
+use humphrey::App;
+
+fn main() {
+    let mut hits = 0;
+
+    App::<()>::new().with_stateless_route("/", |_| {
+        hits += 1;
+        Response::empty(StatusCode::OK)
+    });
+}
@@ -0,0 +1,12 @@
+use humphrey::http::{Response, StatusCode};
+use humphrey::App;
+
+use std::rc::Rc;
+
+fn main() {
+    let counter = Rc::new(0);
+
+    App::<()>::new().with_route("/", move |_, _| {
+        Response::new(StatusCode::OK, counter.to_string())
+    });
+}
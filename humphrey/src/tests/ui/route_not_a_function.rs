@@ -0,0 +1,5 @@
+use humphrey::App;
+
+fn main() {
+    App::<()>::new().with_route("/", "Hello, world!");
+}
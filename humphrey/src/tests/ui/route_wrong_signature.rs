@@ -0,0 +1,10 @@
+use humphrey::http::{Request, Response, StatusCode};
+use humphrey::App;
+
+fn handler(_: Request, _: u8) -> Response {
+    Response::empty(StatusCode::OK)
+}
+
+fn main() {
+    App::<()>::new().with_route("/", handler);
+}
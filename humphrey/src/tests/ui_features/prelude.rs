@@ -0,0 +1,43 @@
+//! Builds an app using only the prelude, which must compile unchanged with every combination of
+//!   features.
+
+#![allow(dead_code)]
+
+use humphrey::handlers::{redirect, serve_dir};
+use humphrey::prelude::*;
+
+fn with_handlers<R, P>(app: App, handler: R, path_aware_handler: P) -> App
+where
+    R: RequestHandler<()> + 'static,
+    P: PathAwareRequestHandler<()> + 'static,
+{
+    app.with_route("/old", handler)
+        .with_path_aware_route("/static/*", path_aware_handler)
+}
+
+fn takes_stateless<T: StatelessRequestHandler<()>>(_: T) {}
+
+fn takes_websocket<T: WebsocketHandler<()>>(_: T) {}
+
+fn takes_stream(_: Stream) {}
+
+fn main() {
+    let subapp: SubApp<()> = SubApp::new().with_route("/", redirect("/docs"));
+    let app: App = with_handlers(App::new(), redirect("/new"), serve_dir("./static"))
+        .with_route_method("/submit", Method::Post, redirect("/"))
+        .with_host("docs.example.com", subapp);
+
+    let request = Request::builder()
+        .method(Method::Get)
+        .uri("/old")
+        .header(HeaderType::Host, "example.com")
+        .build();
+
+    let mut headers = Headers::new();
+    headers.add(HeaderType::ContentType, "text/plain");
+
+    let mut response = Response::new(StatusCode::OK, "Hello, Humphrey!");
+    response.headers = headers;
+
+    let _ = (app, request, response, Client::new());
+}
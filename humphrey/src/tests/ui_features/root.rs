@@ -0,0 +1,20 @@
+//! Uses the types re-exported at the crate root, which must be reachable with every combination of
+//!   features.
+
+use humphrey::handlers::redirect;
+use humphrey::{App, Client, HeaderType, Method, Request, Response, StatusCode, SubApp};
+
+fn main() {
+    let subapp: SubApp<()> = SubApp::new().with_route("/", redirect("/docs"));
+    let app: App = App::new()
+        .with_route_method("/submit", Method::Post, redirect("/"))
+        .with_host("docs.example.com", subapp);
+
+    let request = Request::builder()
+        .uri("/submit")
+        .header(HeaderType::Host, "example.com")
+        .build();
+    let response = Response::empty(StatusCode::NoContent);
+
+    let _ = (app, request, response, Client::new());
+}
@@ -0,0 +1,10 @@
+use humphrey::http::{Response, StatusCode};
+use humphrey::App;
+
+fn main() {
+    let greeting = String::from("Hello");
+
+    App::<()>::new().with_stateless_route("/", move |_| async {
+        Response::new(StatusCode::OK, greeting.as_str())
+    });
+}
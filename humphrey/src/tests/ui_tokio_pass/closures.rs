@@ -0,0 +1,108 @@
+use humphrey::http::headers::HeaderType;
+use humphrey::http::{Request, Response, StatusCode};
+use humphrey::{App, SubApp};
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Per-route configuration, captured by value in each closure.
+///
+/// Path-aware routes are passed their pattern at runtime, so those patterns must be `'static`.
+#[derive(Clone)]
+struct Resource {
+    name: String,
+    limit: usize,
+    wildcard: &'static str,
+    websocket_wildcard: &'static str,
+}
+
+fn main() {
+    let resources = vec![
+        Resource {
+            name: "users".into(),
+            limit: 10,
+            wildcard: "/users/*",
+            websocket_wildcard: "/users/ws/*",
+        },
+        Resource {
+            name: "posts".into(),
+            limit: 50,
+            wildcard: "/posts/*",
+            websocket_wildcard: "/posts/ws/*",
+        },
+    ];
+
+    let blocked = vec!["10.0.0.1".parse::<std::net::IpAddr>().unwrap()];
+    let prefix = String::from("/v1");
+    let signature = String::from("closures");
+    let hits = Arc::new(AtomicUsize::new(0));
+
+    let mut app: App<()> = App::new()
+        .with_error_handler(move |status: StatusCode| {
+            Response::new(status, format!("{} error", signature))
+        })
+        .with_connection_condition(move |stream, _| {
+            stream
+                .peer_addr()
+                .map(|addr| !blocked.contains(&addr.ip()))
+                .unwrap_or(false)
+        })
+        .with_request_condition(move |_, _| {
+            hits.fetch_add(1, Ordering::SeqCst);
+            None
+        })
+        .with_request_rewriter(move |request, _| {
+            if let Some(uri) = request.uri.strip_prefix(prefix.as_str()) {
+                request.uri = uri.to_string();
+            }
+
+            None
+        });
+
+    for resource in resources {
+        let stateless = resource.clone();
+        let path_aware = resource.clone();
+        let websocket = resource.clone();
+        let path_aware_websocket = resource.clone();
+
+        app = app
+            .with_route(&format!("/{}", resource.name), move |_, _| {
+                let body = format!("{} (max {})", resource.name, resource.limit);
+                async move { Response::new(StatusCode::OK, body) }
+            })
+            .with_stateless_route(&format!("/{}/count", stateless.name), move |_| {
+                let limit = stateless.limit;
+                async move { Response::new(StatusCode::OK, limit.to_string()) }
+            })
+            .with_path_aware_route(path_aware.wildcard, move |request: Request, _, route| {
+                let name = path_aware.name.clone();
+                async move {
+                    Response::new(StatusCode::OK, format!("{} {}", name, route))
+                        .with_header(HeaderType::ContentLocation, request.uri)
+                }
+            })
+            .with_websocket_route(&format!("/{}/ws", websocket.name), move |_, _, _| {
+                let limit = websocket.limit;
+                async move {
+                    let _ = limit;
+                }
+            })
+            .with_path_aware_websocket_route(
+                path_aware_websocket.websocket_wildcard,
+                move |_, _, _, route| {
+                    let name = path_aware_websocket.name.clone();
+                    async move {
+                        let _ = (route, name);
+                    }
+                },
+            );
+    }
+
+    let suffix = String::from("!");
+    let host = SubApp::<()>::new().with_route("/", move |_, _| {
+        let body = format!("host{}", suffix);
+        async move { Response::new(StatusCode::OK, body) }
+    });
+
+    let _ = app.with_host("example.com", host);
+}
@@ -0,0 +1,242 @@
+use crate::cancellation::CancellationToken;
+use crate::http::address::Address;
+use crate::http::headers::{HeaderType, Headers};
+use crate::http::method::Method;
+use crate::http::request::BodyState;
+use crate::http::url::{host_matches, Origin};
+use crate::http::Request;
+use crate::route::RouteParams;
+
+use std::net::IpAddr;
+
+fn request(host: &str, secure: bool) -> Request {
+    let mut headers = Headers::new();
+    headers.add(HeaderType::Host, host);
+
+    Request {
+        method: Method::Get,
+        overridden_method: None,
+        uri: "/login".into(),
+        uri_raw: "/login".into(),
+        query: "next=/profile".into(),
+        version: "HTTP/1.1".into(),
+        headers,
+        content: None,
+        body_state: BodyState::Complete,
+        trailers: Headers::new(),
+        address: Address::new("127.0.0.1:1234").unwrap(),
+        secure,
+        local_addr: None,
+        deadline: None,
+        trace: None,
+        cancellation: CancellationToken::default(),
+        params: RouteParams::default(),
+    }
+}
+
+/// Creates a request which was forwarded by a proxy at `10.0.0.1` on behalf of `1.2.3.4`.
+fn proxied_request(host: &str) -> Request {
+    let mut request = request(host, false);
+    request.headers.add("X-Forwarded-For", "1.2.3.4");
+    request.headers.add("X-Forwarded-Proto", "https");
+    request.headers.add("X-Forwarded-Host", "www.example.com");
+    request.address = Address::from_headers(&request.headers, "10.0.0.1:1234").unwrap();
+
+    request
+}
+
+#[test]
+fn test_default_ports() {
+    assert_eq!(
+        request("example.com", false).base_url(&[]),
+        Some("http://example.com".into())
+    );
+    assert_eq!(
+        request("Example.COM:80", false).base_url(&[]),
+        Some("http://example.com".into())
+    );
+    assert_eq!(
+        request("example.com:443", true).base_url(&[]),
+        Some("https://example.com".into())
+    );
+}
+
+#[test]
+fn test_non_default_ports() {
+    assert_eq!(
+        request("example.com:8080", false).base_url(&[]),
+        Some("http://example.com:8080".into())
+    );
+    assert_eq!(
+        request("example.com:80", true).base_url(&[]),
+        Some("https://example.com:80".into())
+    );
+    assert_eq!(
+        request("localhost:8443", true).base_url(&[]),
+        Some("https://localhost:8443".into())
+    );
+}
+
+#[test]
+fn test_ipv6_hosts() {
+    assert_eq!(
+        request("[::1]", false).base_url(&[]),
+        Some("http://[::1]".into())
+    );
+    assert_eq!(
+        request("[2001:db8::1]:8080", true).base_url(&[]),
+        Some("https://[2001:db8::1]:8080".into())
+    );
+    assert_eq!(
+        request("[::1]:443", true).base_url(&[]),
+        Some("https://[::1]".into())
+    );
+
+    assert_eq!(request("[::1", false).base_url(&[]), None);
+    assert_eq!(request("[not-an-ip]", false).base_url(&[]), None);
+    assert_eq!(request("[::1]8080", false).base_url(&[]), None);
+}
+
+#[test]
+fn test_invalid_hosts() {
+    assert_eq!(request("", false).base_url(&[]), None);
+    assert_eq!(request("evil.com/path", false).base_url(&[]), None);
+    assert_eq!(request("example.com:http", false).base_url(&[]), None);
+    assert_eq!(request("example.com:99999", false).base_url(&[]), None);
+    assert_eq!(request("user@example.com", false).base_url(&[]), None);
+
+    let mut request = request("example.com", false);
+    request.headers.remove(HeaderType::Host);
+    assert_eq!(request.base_url(&[]), None);
+}
+
+#[test]
+fn test_trusted_proxy() {
+    let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+    let request = proxied_request("internal:8080");
+
+    assert_eq!(
+        request.base_url(&[proxy]),
+        Some("https://www.example.com".into())
+    );
+    assert_eq!(
+        request.absolute_url("/profile", None, &[proxy]),
+        Some("https://www.example.com/profile".into())
+    );
+}
+
+#[test]
+fn test_untrusted_proxy() {
+    let other_proxy: IpAddr = "10.0.0.2".parse().unwrap();
+    let request = proxied_request("internal:8080");
+
+    assert_eq!(request.base_url(&[]), Some("http://internal:8080".into()));
+    assert_eq!(
+        request.base_url(&[other_proxy]),
+        Some("http://internal:8080".into())
+    );
+
+    // The origin address is not the peer, so trusting it must not trust the forwarded headers
+    let client: IpAddr = "1.2.3.4".parse().unwrap();
+    assert_eq!(
+        request.base_url(&[client]),
+        Some("http://internal:8080".into())
+    );
+}
+
+#[test]
+fn test_trusted_proxy_invalid_headers() {
+    let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+
+    let mut request = proxied_request("internal");
+    request.headers.remove("X-Forwarded-Proto");
+    request.headers.add("X-Forwarded-Proto", "gopher");
+    assert_eq!(request.base_url(&[proxy]), None);
+
+    let mut request = proxied_request("internal");
+    request.headers.remove("X-Forwarded-Host");
+    request
+        .headers
+        .add("X-Forwarded-Host", "www.example.com:8443, internal");
+    assert_eq!(
+        request.base_url(&[proxy]),
+        Some("https://www.example.com:8443".into())
+    );
+}
+
+#[test]
+fn test_absolute_url() {
+    let request = request("example.com:8080", false);
+
+    assert_eq!(
+        request.absolute_url("/login", Some(&request.query), &[]),
+        Some("http://example.com:8080/login?next=/profile".into())
+    );
+    assert_eq!(
+        request.absolute_url("/login", Some("error=bad login"), &[]),
+        Some("http://example.com:8080/login?error=bad%20login".into())
+    );
+    assert_eq!(
+        request.absolute_url("files/my file%20.txt", Some(""), &[]),
+        Some("http://example.com:8080/files/my%20file%20.txt".into())
+    );
+    assert_eq!(
+        request.absolute_url("/caf\u{e9}", None, &[]),
+        Some("http://example.com:8080/caf%C3%A9".into())
+    );
+}
+
+#[test]
+fn test_into_secure() {
+    let origin = Origin::from_request(&request("example.com:8080", false), &[]).unwrap();
+
+    assert_eq!(
+        origin.into_secure().url("/login", Some("a=b")),
+        "https://example.com/login?a=b"
+    );
+}
+
+#[test]
+fn test_into_secure_on() {
+    let origin = Origin::from_request(&request("example.com:8080", false), &[]).unwrap();
+
+    assert_eq!(
+        origin.clone().into_secure_on(8443).url("/login", None),
+        "https://example.com:8443/login"
+    );
+    assert_eq!(
+        origin.into_secure_on(443).url("/login", None),
+        "https://example.com/login"
+    );
+}
+
+#[test]
+fn test_host_matches() {
+    assert!(host_matches("localhost", "localhost"));
+    assert!(host_matches("localhost", "localhost:8080"));
+    assert!(host_matches("*.example.com", "www.example.com:8443"));
+    assert!(!host_matches("localhost", "localhost.example.com:8080"));
+    assert!(!host_matches("example.com", "example.com:"));
+    assert!(!host_matches("example.com", "example.com:http"));
+}
+
+#[test]
+fn test_host_matches_port() {
+    assert!(host_matches("localhost:8080", "localhost:8080"));
+    assert!(!host_matches("localhost:8080", "localhost:8081"));
+    assert!(!host_matches("localhost:8080", "localhost"));
+    assert!(host_matches("localhost:*", "localhost:3000"));
+    assert!(!host_matches("localhost:*", "localhost"));
+}
+
+#[test]
+fn test_host_matches_ipv6() {
+    assert!(host_matches("[::1]", "[::1]"));
+    assert!(host_matches("[::1]", "[::1]:8080"));
+    assert!(host_matches("[::1]:8080", "[::1]:8080"));
+    assert!(!host_matches("[::1]:8080", "[::1]:9090"));
+
+    // Without brackets, the last part of the address is not a port
+    assert!(!host_matches("::", "::1"));
+    assert!(!host_matches("[::1]", "[::1]:port"));
+}
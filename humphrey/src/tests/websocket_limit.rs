@@ -0,0 +1,251 @@
+use crate::app::WebsocketConnections;
+use crate::http::headers::HeaderType;
+use crate::http::{Request, Response, StatusCode};
+use crate::monitor::event::{Event, EventType};
+use crate::monitor::MonitorConfig;
+use crate::route::UpgradeDecision;
+use crate::stream::Stream;
+use crate::App;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::Duration;
+
+const UPGRADE_REQUEST: &[u8] =
+    b"GET /ws HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n";
+
+const PANIC_UPGRADE_REQUEST: &[u8] =
+    b"GET /panic HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n";
+
+const GUARDED_UPGRADE_REQUEST: &[u8] =
+    b"GET /guarded HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n";
+
+const AUTHORIZED_UPGRADE_REQUEST: &[u8] = b"GET /guarded HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nAuthorization: Bearer secret\r\n\r\n";
+
+/// Only accepts upgrade requests with the right token.
+fn token_guard(request: &Request, _: Arc<()>) -> UpgradeDecision {
+    match request.headers.get(HeaderType::Authorization) {
+        Some("Bearer secret") => UpgradeDecision::Accept,
+        _ => UpgradeDecision::Reject(Response::empty(StatusCode::Unauthorized)),
+    }
+}
+
+/// Holds the connection until the client closes it, like a real WebSocket handler would.
+fn websocket_handler(_: Request, mut stream: Stream, _: Arc<()>) {
+    stream.write_all(b"connected").unwrap();
+
+    let mut buf = [0u8; 16];
+    while let Ok(1..) = stream.read(&mut buf) {}
+}
+
+/// Accepts the connection, then panics as soon as the client sends anything.
+fn panicking_handler(_: Request, mut stream: Stream, _: Arc<()>) {
+    stream.write_all(b"connected").unwrap();
+
+    let mut buf = [0u8; 16];
+    let _ = stream.read(&mut buf);
+
+    panic!("WebSocket handler panicked");
+}
+
+/// Starts an app with four workers, with the WebSocket limits set by the given function.
+fn start_app(
+    limits: impl FnOnce(App<()>) -> App<()>,
+) -> (String, Sender<()>, Receiver<Event>, WebsocketConnections) {
+    let (shutdown_tx, shutdown_rx) = channel();
+    let (monitor_tx, monitor_rx) = channel();
+
+    let app: App<()> = limits(App::new_with_config(4, ()))
+        .with_websocket_route("/ws", websocket_handler)
+        .with_websocket_route("/panic", panicking_handler)
+        .with_guarded_websocket_route("/guarded", token_guard, websocket_handler)
+        .with_stateless_route("/", |_| Response::new(StatusCode::OK, "hello"))
+        .with_monitor(
+            MonitorConfig::new(monitor_tx)
+                .with_subscription_to(EventType::WebsocketConnectionRefused),
+        )
+        .with_shutdown(shutdown_rx);
+    let connections = app.websocket_connections();
+
+    let addr = crate::tests::start_app(app);
+
+    (addr, shutdown_tx, monitor_rx, connections)
+}
+
+/// Opens a WebSocket connection, returning the stream once it is held by a worker.
+fn connect_websocket(addr: &str) -> TcpStream {
+    connect_websocket_with(addr, UPGRADE_REQUEST)
+}
+
+fn connect_websocket_with(addr: &str, request: &[u8]) -> TcpStream {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream.write_all(request).unwrap();
+
+    let mut buf = [0u8; 9];
+    stream.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"connected");
+
+    stream
+}
+
+fn http_request(addr: &str, request: &[u8]) -> Response {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream.write_all(request).unwrap();
+
+    Response::from_stream(&mut stream).unwrap()
+}
+
+#[test]
+fn test_http_served_while_websockets_connected() {
+    let (addr, shutdown, monitor, _) = start_app(|app| app.with_websocket_limit(2));
+
+    let websockets = [connect_websocket(&addr), connect_websocket(&addr)];
+
+    // The limit has been reached, so further upgrades are refused rather than taking the last workers
+    let response = http_request(&addr, UPGRADE_REQUEST);
+    assert_eq!(response.status_code, StatusCode::ServiceUnavailable);
+
+    let event = monitor.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(event.kind, EventType::WebsocketConnectionRefused);
+    assert_eq!(
+        event.info.as_deref(),
+        Some("2/2 workers held by WebSockets")
+    );
+
+    for _ in 0..8 {
+        let response = http_request(&addr, b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert_eq!(response.status_code, StatusCode::OK);
+        assert_eq!(response.body, b"hello");
+    }
+
+    // Once a WebSocket client disconnects, its worker is released for another upgrade
+    drop(websockets);
+    sleep(Duration::from_millis(100));
+
+    let _websocket = connect_websocket(&addr);
+
+    shutdown.send(()).unwrap();
+}
+
+/// Waits for the connection counts to settle at the given total, since connections are only
+///   released once their handler has noticed the client leave.
+fn wait_for_total(connections: &WebsocketConnections, total: usize) {
+    for _ in 0..100 {
+        if connections.total() == total {
+            return;
+        }
+
+        sleep(Duration::from_millis(10));
+    }
+
+    assert_eq!(connections.total(), total);
+}
+
+#[test]
+fn test_websocket_limit_per_ip() {
+    let (addr, shutdown, monitor, connections) =
+        start_app(|app| app.with_websocket_limits(Some(3), Some(2)));
+    let localhost = "127.0.0.1".parse().unwrap();
+
+    let first = connect_websocket(&addr);
+    let _second = connect_websocket(&addr);
+
+    assert_eq!(connections.total(), 2);
+    assert_eq!(connections.from_ip(localhost), 2);
+    assert_eq!(connections.clients(), 1);
+
+    // The global limit has not been reached, but this address has used its share
+    let response = http_request(&addr, UPGRADE_REQUEST);
+    assert_eq!(response.status_code, StatusCode::ServiceUnavailable);
+
+    let event = monitor.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(event.kind, EventType::WebsocketConnectionRefused);
+    assert_eq!(
+        event.info.as_deref(),
+        Some("2/2 WebSocket connections from 127.0.0.1")
+    );
+    assert_eq!(connections.total(), 2);
+
+    drop(first);
+    wait_for_total(&connections, 1);
+    assert_eq!(connections.from_ip(localhost), 1);
+
+    let _third = connect_websocket(&addr);
+    assert_eq!(connections.from_ip(localhost), 2);
+
+    shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_websocket_counts_released() {
+    let (addr, shutdown, _, connections) =
+        start_app(|app| app.with_websocket_limits(Some(2), Some(2)));
+    let localhost = "127.0.0.1".parse().unwrap();
+
+    // Connections closed cleanly and abruptly are both released
+    let clean = connect_websocket(&addr);
+    let abrupt = connect_websocket(&addr);
+    assert_eq!(connections.total(), 2);
+
+    clean.shutdown(std::net::Shutdown::Both).unwrap();
+    drop(clean);
+    drop(abrupt);
+    wait_for_total(&connections, 0);
+
+    // Connections whose handler panics are released too, and leave nothing behind for their address
+    let mut panicking = [
+        connect_websocket_with(&addr, PANIC_UPGRADE_REQUEST),
+        connect_websocket_with(&addr, PANIC_UPGRADE_REQUEST),
+    ];
+    assert_eq!(connections.total(), 2);
+    assert_eq!(connections.from_ip(localhost), 2);
+
+    for stream in &mut panicking {
+        stream.write_all(b"crash").unwrap();
+    }
+
+    wait_for_total(&connections, 0);
+    assert_eq!(connections.from_ip(localhost), 0);
+    assert_eq!(connections.clients(), 0);
+
+    // The limits apply from a clean slate afterwards
+    let _websockets = [connect_websocket(&addr), connect_websocket(&addr)];
+    assert_eq!(connections.total(), 2);
+
+    shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_guard_checked_before_limit() {
+    let (addr, shutdown, monitor, connections) = start_app(|app| app.with_websocket_limit(1));
+
+    // Requests refused by the guard never take a place under the limit
+    let response = http_request(&addr, GUARDED_UPGRADE_REQUEST);
+    assert_eq!(response.status_code, StatusCode::Unauthorized);
+    assert_eq!(connections.total(), 0);
+
+    let event = monitor.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(event.kind, EventType::WebsocketConnectionRefused);
+    assert_eq!(
+        event.info.as_deref(),
+        Some("refused by the guard of route /guarded")
+    );
+
+    let _websocket = connect_websocket_with(&addr, AUTHORIZED_UPGRADE_REQUEST);
+    assert_eq!(connections.total(), 1);
+
+    // Requests accepted by the guard are still subject to the limit
+    let response = http_request(&addr, AUTHORIZED_UPGRADE_REQUEST);
+    assert_eq!(response.status_code, StatusCode::ServiceUnavailable);
+
+    shutdown.send(()).unwrap();
+}
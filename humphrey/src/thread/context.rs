@@ -0,0 +1,110 @@
+//! Provides a thread-local context describing the request which a worker thread is handling.
+//!
+//! The context is set while a handler is running, so panic messages, monitor events and error
+//!   handling code can report which route and connection were being processed. It is cleared as
+//!   soon as the handler returns or panics, so it never describes an earlier request.
+//!
+//! The context is only set by the synchronous app, since asynchronous handlers can move between
+//!   threads while they run.
+//!
+//! ## Example
+//! ```
+//! fn handler(request: Request) -> Response {
+//!     match std::fs::read_to_string("data.txt") {
+//!         Ok(data) => Response::new(StatusCode::OK, data),
+//!         Err(e) => {
+//!             if let Some(context) = RequestContext::current() {
+//!                 eprintln!("Could not read data ({}): {}", context, e);
+//!             }
+//!
+//!             Response::empty(StatusCode::InternalError)
+//!         }
+//!     }
+//! }
+//! ```
+
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::net::SocketAddr;
+use std::panic;
+use std::sync::Once;
+
+thread_local! {
+    static CONTEXT: RefCell<Option<RequestContext>> = const { RefCell::new(None) };
+}
+
+/// Ensures the panic hook is only installed once.
+static PANIC_HOOK: Once = Once::new();
+
+/// Describes the request which the current thread is handling.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestContext {
+    /// The route pattern which matched the request, such as `/api/*`.
+    pub route: String,
+    /// The address of the client which sent the request.
+    pub peer: SocketAddr,
+    /// The ID of the connection the request was received on, which is unique within the process.
+    pub connection_id: u64,
+}
+
+/// Clears the context of the current thread when dropped, including while unwinding from a panic.
+#[cfg_attr(feature = "tokio", allow(dead_code))]
+pub(crate) struct ContextGuard(());
+
+impl RequestContext {
+    /// Returns the context of the request which the current thread is handling, if any.
+    pub fn current() -> Option<RequestContext> {
+        CONTEXT.with(|context| context.borrow().clone())
+    }
+
+    /// Sets the context of the current thread until the returned guard is dropped.
+    #[cfg_attr(feature = "tokio", allow(dead_code))]
+    pub(crate) fn enter(self) -> ContextGuard {
+        CONTEXT.with(|context| *context.borrow_mut() = Some(self));
+
+        ContextGuard(())
+    }
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        // This may run while unwinding, after which the thread-local could already be destroyed
+        let _ = CONTEXT.try_with(|context| context.borrow_mut().take());
+    }
+}
+
+impl Display for RequestContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "route {}, peer {}, connection {}",
+            self.route, self.peer, self.connection_id
+        )
+    }
+}
+
+/// Installs a panic hook which reports the context of the panicking thread, if it has one, before
+///   calling the previously installed hook.
+///
+/// The hook is only installed the first time this is called.
+pub fn install_panic_hook() {
+    PANIC_HOOK.call_once(|| {
+        let previous_hook = panic::take_hook();
+
+        panic::set_hook(Box::new(move |info| {
+            if let Some(context) = CONTEXT
+                .try_with(|context| context.borrow().clone())
+                .ok()
+                .flatten()
+            {
+                eprintln!(
+                    "Thread {} panicked while handling a request ({})",
+                    std::thread::current().name().unwrap_or("<unknown>"),
+                    context
+                );
+            }
+
+            previous_hook(info);
+        }));
+    });
+}
@@ -1,4 +1,5 @@
 //! Provides a basic thread pool implementation.
 
+pub mod context;
 pub mod pool;
 pub mod recovery;
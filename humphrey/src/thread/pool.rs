@@ -2,6 +2,7 @@
 
 use crate::monitor::event::EventType;
 use crate::monitor::MonitorConfig;
+use crate::thread::context::install_panic_hook;
 use crate::thread::recovery::{PanicMarker, RecoveryThread};
 
 use std::sync::mpsc::{channel, Receiver, Sender};
@@ -70,8 +71,11 @@ impl ThreadPool {
         }
     }
 
-    /// Starts the thread pool.
+    /// Starts the thread pool, installing the panic hook which reports the context of panicking
+    ///   threads.
     pub fn start(&mut self) {
+        install_panic_hook();
+
         let (tx, rx): (Sender<Message>, Receiver<Message>) = channel();
         let rx = Arc::new(Mutex::new(rx));
         let mut threads = Vec::with_capacity(self.thread_count);
@@ -147,7 +151,7 @@ impl Thread {
         monitor: Option<MonitorConfig>,
     ) -> Self {
         let thread = Builder::new()
-            .name(format!("{}", id))
+            .name(format!("humphrey-worker-{}", id))
             .spawn(move || {
                 let panic_marker = PanicMarker(id, panic_tx);
 
@@ -1,13 +1,14 @@
 //! Provides functionality for recovering from thread panics.
 
-use crate::monitor::event::{Event, EventType};
+use crate::monitor::event::EventType;
 use crate::monitor::MonitorConfig;
+use crate::thread::context::RequestContext;
 use crate::thread::pool::{Message, Thread};
 
 use std::panic;
 use std::sync::mpsc::{Receiver, Sender};
 use std::sync::{Arc, Mutex};
-use std::thread::{panicking, spawn, JoinHandle};
+use std::thread::{panicking, Builder, JoinHandle};
 
 /// Marker struct to detect thread panics.
 pub struct PanicMarker(pub usize, pub Sender<usize>);
@@ -37,58 +38,63 @@ impl RecoveryThread {
                 // Override the default panic handler to get more information about panics.
                 panic::set_hook(Box::new(move |info| {
                     if let Ok(monitor) = sync_monitor.lock() {
-                        if let Some(info) = info.location() {
-                            monitor.send(Event::new(EventType::ThreadPoolPanic).with_info(
-                                format!(
-                                    "Thread {} panicked at {}:{}:{}",
-                                    std::thread::current().name().unwrap_or("<unknown>"),
-                                    info.file(),
-                                    info.line(),
-                                    info.column()
-                                ),
-                            ));
-                        } else {
-                            monitor.send(Event::new(EventType::ThreadPoolPanic).with_info(
-                                format!(
-                                    "Thread {} panicked, no location available",
-                                    std::thread::current().name().unwrap_or("<unknown>"),
-                                ),
-                            ));
+                        let thread = std::thread::current();
+                        let thread = thread.name().unwrap_or("<unknown>");
+
+                        let mut message = match info.location() {
+                            Some(location) => format!(
+                                "Thread {} panicked at {}:{}:{}",
+                                thread,
+                                location.file(),
+                                location.line(),
+                                location.column()
+                            ),
+                            None => format!("Thread {} panicked, no location available", thread),
+                        };
+
+                        if let Some(context) = RequestContext::current() {
+                            message.push_str(&format!(" while handling a request ({})", context));
                         }
+
+                        monitor.send_with(EventType::ThreadPoolPanic, |event| {
+                            event.with_info(message)
+                        });
                     }
                 }))
             };
         }
 
-        let thread = spawn(move || loop {
-            for panicking_thread in &rx {
-                let mut threads = threads.lock().unwrap();
+        let thread = Builder::new()
+            .name("humphrey-recovery".into())
+            .spawn(move || loop {
+                for panicking_thread in &rx {
+                    let mut threads = threads.lock().unwrap();
 
-                // End the OS thread that panicked.
-                if let Some(thread) = threads[panicking_thread].os_thread.take() {
-                    thread.join().ok();
-                }
+                    // End the OS thread that panicked.
+                    if let Some(thread) = threads[panicking_thread].os_thread.take() {
+                        thread.join().ok();
+                    }
 
-                // Start a new thread with the same ID.
-                let restarted_thread = Thread::new(
-                    panicking_thread,
-                    task_rx.clone(),
-                    tx.clone(),
-                    monitor.clone(),
-                );
-
-                // Put the new thread in the old thread's place.
-                threads[panicking_thread] = restarted_thread;
-
-                // Log that the thread restarted.
-                if let Some(monitor) = &monitor {
-                    monitor.send(
-                        Event::new(EventType::ThreadRestarted)
-                            .with_info(format!("Thread {} was restarted", panicking_thread)),
+                    // Start a new thread with the same ID.
+                    let restarted_thread = Thread::new(
+                        panicking_thread,
+                        task_rx.clone(),
+                        tx.clone(),
+                        monitor.clone(),
                     );
+
+                    // Put the new thread in the old thread's place.
+                    threads[panicking_thread] = restarted_thread;
+
+                    // Log that the thread restarted.
+                    if let Some(monitor) = &monitor {
+                        monitor.send_with(EventType::ThreadRestarted, |event| {
+                            event.with_info(format!("Thread {} was restarted", panicking_thread))
+                        });
+                    }
                 }
-            }
-        });
+            })
+            .expect("Thread could not be spawned");
 
         Self(Some(thread))
     }
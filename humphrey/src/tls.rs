@@ -0,0 +1,98 @@
+//! Provides loading of TLS certificates and private keys for apps served over HTTPS.
+//!
+//! Certificates are read from PEM data, which may contain a whole chain, such as the
+//!   `fullchain.pem` issued by Let's Encrypt, in which case every certificate is sent to clients in
+//!   the order in which they appear. Private keys can be PKCS#8, PKCS#1 (RSA) or SEC1 (EC) keys.
+
+use rustls::{Certificate, PrivateKey, ServerConfig};
+use rustls_pemfile::{read_all, Item};
+
+use std::error::Error;
+use std::fmt::Display;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// An error encountered when loading a certificate and private key.
+#[derive(Debug)]
+pub enum CertError {
+    /// The file at the given path could not be read.
+    Read(PathBuf, io::Error),
+    /// The PEM data could not be parsed.
+    Pem(io::Error),
+    /// The certificate PEM data did not contain any certificates.
+    NoCertificates,
+    /// The key PEM data did not contain a private key in a supported format.
+    NoPrivateKey,
+    /// The certificates and private key could not be used, for example because the key does not
+    ///   belong to the certificate.
+    Invalid(rustls::Error),
+}
+
+/// Reads the certificate chain and private key from the PEM files at the given paths, and creates
+///   the TLS configuration for an app.
+pub(crate) fn config_from_files(
+    cert_path: &Path,
+    key_path: &Path,
+) -> Result<Arc<ServerConfig>, CertError> {
+    let read = |path: &Path| std::fs::read(path).map_err(|e| CertError::Read(path.into(), e));
+
+    config_from_pem(&read(cert_path)?, &read(key_path)?)
+}
+
+/// Creates the TLS configuration for an app from the given PEM data.
+pub(crate) fn config_from_pem(
+    cert_pem: &[u8],
+    key_pem: &[u8],
+) -> Result<Arc<ServerConfig>, CertError> {
+    let config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs(cert_pem)?, private_key(key_pem)?)
+        .map_err(CertError::Invalid)?;
+
+    Ok(Arc::new(config))
+}
+
+/// Reads every certificate from the PEM data, in the order in which they appear.
+pub(crate) fn certs(pem: &[u8]) -> Result<Vec<Certificate>, CertError> {
+    let certs: Vec<Certificate> = read_all(&mut &*pem)
+        .map_err(CertError::Pem)?
+        .into_iter()
+        .filter_map(|item| match item {
+            Item::X509Certificate(cert) => Some(Certificate(cert)),
+            _ => None,
+        })
+        .collect();
+
+    match certs.is_empty() {
+        true => Err(CertError::NoCertificates),
+        false => Ok(certs),
+    }
+}
+
+/// Reads the first private key from the PEM data.
+pub(crate) fn private_key(pem: &[u8]) -> Result<PrivateKey, CertError> {
+    read_all(&mut &*pem)
+        .map_err(CertError::Pem)?
+        .into_iter()
+        .find_map(|item| match item {
+            Item::PKCS8Key(key) | Item::RSAKey(key) | Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or(CertError::NoPrivateKey)
+}
+
+impl Display for CertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CertError::Read(path, e) => write!(f, "Could not read {}: {}", path.display(), e),
+            CertError::Pem(e) => write!(f, "Could not parse PEM data: {}", e),
+            CertError::NoCertificates => write!(f, "No certificates were found"),
+            CertError::NoPrivateKey => write!(f, "No PKCS#8, PKCS#1 or SEC1 private key was found"),
+            CertError::Invalid(e) => write!(f, "Invalid certificate or private key: {}", e),
+        }
+    }
+}
+
+impl Error for CertError {}
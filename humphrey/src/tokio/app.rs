@@ -2,27 +2,66 @@
 
 #![allow(clippy::new_without_default)]
 
+use crate::accept::{
+    is_listener_failure, AcceptErrors, AcceptOptions, ConnectionLimit, Listener,
+    OVER_CAPACITY_RESPONSE,
+};
+use crate::cancellation::watch;
+use crate::drain::{draining_response, DrainOptions, DrainSignal};
+use crate::endpoint::{Endpoint, EndpointKind};
+#[cfg(feature = "tls")]
+use crate::endpoint::{redirect_error, redirect_response, REDIRECT_TIMEOUT};
+use crate::http::body::DeferredBody;
 use crate::http::cors::Cors;
 use crate::http::date::DateTime;
-use crate::http::headers::HeaderType;
-use crate::http::method::Method;
-use crate::http::request::{Request, RequestError};
+use crate::http::headers::{HeaderLike, HeaderType, Headers};
+use crate::http::method::{Method, MethodOverride};
+use crate::http::request::{
+    BodyDeferral, BodyState, HeadCondition, Request, RequestError, RequestLimits,
+};
 use crate::http::response::Response;
 use crate::http::status::StatusCode;
-use crate::krauss::wildcard_match;
+use crate::keep_alive::{IdleConnections, KeepAliveConfig};
+use crate::middleware::{
+    find_middleware, run_request_middleware, run_response_middleware, Middleware,
+};
 use crate::monitor::event::{Event, EventType};
+use crate::monitor::timing::{RequestDetails, RequestTimer};
 use crate::monitor::MonitorConfig;
-use crate::route::{Route, RouteHandler, SubApp};
+use crate::proxy_protocol;
+use crate::rate_limit::{rate_limited_response, RateLimitConfig, RateLimiter};
+use crate::route::{
+    allow_header, apply_default_headers, apply_method_override, apply_trailing_slash,
+    build_indexes, find_route, find_streaming_route, find_subapp, handle_error, handler_request,
+    ErrorCause, ErrorContext, RouteHandler, RouteMatch, StreamingRouteHandler, SubApp,
+    TrailingSlash, UpgradeDecision, WebsocketGuard, WebsocketRouteHandler,
+};
+use crate::security::SecurityOptions;
+use crate::shutdown::{AppCounters, ExitReason, ShutdownSignal, ShutdownSummary};
 use crate::stream::Stream;
-
-use std::sync::Arc;
-
-use tokio::io::AsyncWriteExt;
+use crate::test_client::TestClient;
+use crate::trace::TraceOptions;
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::poll_fn;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::panic::AssertUnwindSafe;
+use std::pin::pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::FutureExt;
 use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
-use tokio_util::sync::CancellationToken;
 
+#[cfg(feature = "tls")]
+use crate::tls::{self, CertError};
 #[cfg(feature = "tls")]
 use rustls::ServerConfig;
+#[cfg(feature = "tls")]
+use std::path::Path;
 
 /// Represents the Humphrey app.
 ///
@@ -35,25 +74,108 @@ where
 {
     subapps: Vec<SubApp<State>>,
     default_subapp: SubApp<State>,
-    error_handler: ErrorHandler,
+    error_handler: ErrorHandler<State>,
+    error_context_handler: Option<ErrorHandler<State>>,
     state: Arc<State>,
     monitor: MonitorConfig,
     connection_condition: ConnectionCondition<State>,
+    request_condition: Option<RequestCondition<State>>,
+    request_rewriter: Option<RequestRewriter<State>>,
     #[cfg(feature = "tls")]
     tls_config: Option<Arc<ServerConfig>>,
     #[cfg(feature = "tls")]
     force_https: bool,
-    shutdown: Option<CancellationToken>,
+    #[cfg(feature = "tls")]
+    force_https_addr: SocketAddr,
+    accept_options: AcceptOptions,
+    connection_limit: ConnectionLimit,
+    shutdown: Option<ShutdownSignal>,
+    drain_signal: DrainSignal,
+    drain_options: Option<DrainOptions>,
+    security: Arc<SecurityOptions>,
+    method_override: Option<MethodOverride>,
+    deadline_proxies: Arc<Vec<IpAddr>>,
+    trace_options: Option<TraceOptions>,
+    rate_limiter: Option<RateLimiter>,
+    proxy_protocol: bool,
+    keep_alive: Option<KeepAliveConfig>,
+    websocket_limits: WebsocketLimits,
+}
+
+/// Reports the number of WebSocket connections currently open, both in total and from each IP
+///   address.
+///
+/// This is returned by `App::websocket_connections`, and shares its counts with the running app.
+#[derive(Clone, Default)]
+pub struct WebsocketConnections {
+    total: Arc<AtomicUsize>,
+    clients: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+/// The limits on the number of WebSocket connections which can be open at once, along with the
+///   connections which are currently open.
+#[derive(Clone, Default)]
+struct WebsocketLimits {
+    global: Option<usize>,
+    per_ip: Option<usize>,
+    connections: WebsocketConnections,
+}
+
+/// Represents an open WebSocket connection, which stops being counted when dropped so that the
+///   counts stay accurate even if the handler panics.
+struct WebsocketPermit {
+    connections: WebsocketConnections,
+    ip: IpAddr,
 }
 
 /// Represents a function able to calculate whether a connection will be accepted.
-pub type ConnectionCondition<State> = fn(&mut TcpStream, Arc<State>) -> bool;
+pub type ConnectionCondition<State> = Box<dyn Fn(&mut TcpStream, Arc<State>) -> bool + Send + Sync>;
+
+/// Represents a function pointer able to calculate whether a connection will be accepted.
+///
+/// ## Deprecated
+/// `with_connection_condition` now accepts closures, which are stored as a `ConnectionCondition`.
+#[deprecated(since = "0.8.0", note = "Please use `ConnectionCondition` instead")]
+pub type ConnectionConditionFn<State> = fn(&mut TcpStream, Arc<State>) -> bool;
+
+/// Represents a function able to reject a request once its head has been parsed, but before its
+///   body has been read.
+///
+/// The request passed to the function has no content. Returning `Some` rejects the request with the
+///   given response.
+pub type RequestCondition<State> =
+    Arc<dyn Fn(&Request, Arc<State>) -> Option<Response> + Send + Sync>;
+
+/// Represents a function pointer able to reject a request once its head has been parsed.
+///
+/// ## Deprecated
+/// `with_request_condition` now accepts closures, which are stored as a `RequestCondition`.
+#[deprecated(since = "0.8.0", note = "Please use `RequestCondition` instead")]
+pub type RequestConditionFn<State> = fn(&Request, Arc<State>) -> Option<Response>;
+
+/// Represents a function able to modify a request after it has been parsed but before it is routed,
+///   for example to normalise its path.
+///
+/// Returning `Some` rejects the request with the given response instead of routing it.
+pub type RequestRewriter<State> =
+    Arc<dyn Fn(&mut Request, Arc<State>) -> Option<Response> + Send + Sync>;
+
+/// Represents a function pointer able to modify a request before it is routed.
+///
+/// ## Deprecated
+/// `with_request_rewriter` now accepts closures, which are stored as a `RequestRewriter`.
+#[deprecated(since = "0.8.0", note = "Please use `RequestRewriter` instead")]
+pub type RequestRewriterFn<State> = fn(&mut Request, Arc<State>) -> Option<Response>;
 
 pub use crate::handler_traits::*;
 
 /// Represents a function able to handle an error.
-/// The first parameter of type `Option<Request>` will be `Some` if the request could be parsed.
-/// Otherwise, it will be `None` and the status code will be `StatusCode::BadRequest`.
+/// The first parameter is an `ErrorContext` describing the error, including the request if it could
+///   be parsed, and the second is the app's state.
+///
+/// Error handlers which only need the status code can be set with `with_error_handler`, those which
+///   also use the request and state with `with_error_handler_with_state`, and those which use the
+///   whole context with `with_error_context_handler`.
 ///
 /// Every app has a default error handler, which simply displays the status code.
 /// The source code for this default error handler is copied below since it is a good example.
@@ -70,7 +192,18 @@ pub use crate::handler_traits::*;
 ///     Response::new(status_code, body.as_bytes())
 /// }
 /// ```
-pub type ErrorHandler = fn(StatusCode) -> Response;
+///
+/// Closures are also accepted, so the error handler can capture configuration such as a branded
+///   error page.
+pub type ErrorHandler<State = ()> =
+    Arc<dyn Fn(&ErrorContext, Arc<State>) -> Response + Send + Sync>;
+
+/// Represents a function pointer able to handle an error.
+///
+/// ## Deprecated
+/// `with_error_handler` now accepts closures, which are stored as an `ErrorHandler`.
+#[deprecated(since = "0.8.0", note = "Please use `ErrorHandler` instead")]
+pub type ErrorHandlerFn = fn(StatusCode) -> Response;
 
 /// Represents a generic error with the program.
 pub type HumphreyError = Box<dyn std::error::Error>;
@@ -92,15 +225,32 @@ where
         Self {
             subapps: Vec::new(),
             default_subapp: SubApp::default(),
-            error_handler,
+            error_handler: Arc::new(|context, _| error_handler(context.status_code)),
+            error_context_handler: None,
             state: Arc::new(State::default()),
             monitor: MonitorConfig::default(),
-            connection_condition: |_, _| true,
+            connection_condition: Box::new(|_, _| true),
+            request_condition: None,
+            request_rewriter: None,
             #[cfg(feature = "tls")]
             tls_config: None,
             #[cfg(feature = "tls")]
             force_https: false,
+            #[cfg(feature = "tls")]
+            force_https_addr: SocketAddr::from(([0, 0, 0, 0], 80)),
+            accept_options: AcceptOptions::default(),
+            connection_limit: ConnectionLimit::default(),
             shutdown: None,
+            drain_signal: DrainSignal::new(),
+            drain_options: None,
+            security: Arc::new(SecurityOptions::default()),
+            method_override: None,
+            deadline_proxies: Arc::new(Vec::new()),
+            trace_options: None,
+            rate_limiter: None,
+            proxy_protocol: false,
+            keep_alive: None,
+            websocket_limits: WebsocketLimits::default(),
         }
     }
 
@@ -109,185 +259,294 @@ where
         Self {
             subapps: Vec::new(),
             default_subapp: SubApp::default(),
-            error_handler,
+            error_handler: Arc::new(|context, _| error_handler(context.status_code)),
+            error_context_handler: None,
             state: Arc::new(state),
             monitor: MonitorConfig::default(),
-            connection_condition: |_, _| true,
+            connection_condition: Box::new(|_, _| true),
+            request_condition: None,
+            request_rewriter: None,
             #[cfg(feature = "tls")]
             tls_config: None,
             #[cfg(feature = "tls")]
             force_https: false,
+            #[cfg(feature = "tls")]
+            force_https_addr: SocketAddr::from(([0, 0, 0, 0], 80)),
+            accept_options: AcceptOptions::default(),
+            connection_limit: ConnectionLimit::default(),
             shutdown: None,
+            drain_signal: DrainSignal::new(),
+            drain_options: None,
+            security: Arc::new(SecurityOptions::default()),
+            method_override: None,
+            deadline_proxies: Arc::new(Vec::new()),
+            trace_options: None,
+            rate_limiter: None,
+            proxy_protocol: false,
+            keep_alive: None,
+            websocket_limits: WebsocketLimits::default(),
         }
     }
 
     /// Runs the Humphrey app on the given socket address.
-    /// This function will only return if a fatal error is thrown such as the port being in use.
-    pub async fn run<A>(self, addr: A) -> Result<(), HumphreyError>
+    /// This function will only return once the app has been shut down, in which case a summary of
+    ///   what it did is returned, or if a fatal error is thrown such as the port being in use.
+    pub async fn run<A>(self, addr: A) -> Result<ShutdownSummary, HumphreyError>
     where
         A: ToSocketAddrs,
     {
         let socket = TcpListener::bind(addr).await?;
-        let subapps = Arc::new(self.subapps);
-        let default_subapp = Arc::new(self.default_subapp);
-        let error_handler = Arc::new(self.error_handler);
-
-        loop {
-            let shutdown = async {
-                if let Some(sd) = self.shutdown.as_ref() {
-                    sd.cancelled().await
-                } else {
-                    futures::future::pending().await
-                }
-            };
-            tokio::select! {
-                () = shutdown => { break Ok(()); }
-                s = socket.accept() => {
-                    match s {
-                        Ok((mut stream, _)) => {
-                            let cloned_state = self.state.clone();
-
-                            // Check that the client is allowed to connect
-                            if (self.connection_condition)(&mut stream, cloned_state) {
-                                let cloned_state = self.state.clone();
-                                let cloned_monitor = self.monitor.clone();
-                                let cloned_subapps = subapps.clone();
-                                let cloned_default_subapp = default_subapp.clone();
-                                let cloned_error_handler = error_handler.clone();
-
-                                cloned_monitor.send(
-                                    Event::new(EventType::ConnectionSuccess)
-                                        .with_peer_result(stream.peer_addr()),
-                                );
-
-                                // Spawn a new thread to handle the connection
-                                tokio::spawn(async move {
-                                    cloned_monitor.send(
-                                        Event::new(EventType::ThreadPoolProcessStarted)
-                                            .with_peer_result(stream.peer_addr()),
-                                    );
 
-                                    client_handler(
-                                        Stream::Tcp(stream),
-                                        cloned_subapps,
-                                        cloned_default_subapp,
-                                        cloned_error_handler,
-                                        cloned_state,
-                                        cloned_monitor,
-                                    )
-                                        .await
-                                });
-                            } else {
-                                self.monitor.send(
-                                    Event::new(EventType::ConnectionDenied)
-                                        .with_peer_result(stream.peer_addr()),
-                                );
-                            }
-                        }
-                        Err(e) => self
-                            .monitor
-                            .send(Event::new(EventType::ConnectionError).with_info(e.to_string())),
-                    }
-                }
-            };
-        }
+        self.serve(vec![(socket, EndpointKind::Plain)]).await
     }
 
     /// Securely runs the Humphrey app on the given socket address.
-    /// This function will only return if a fatal error is thrown such as the port being in use or the TLS certificate being invalid.
+    /// This function will only return once the app has been shut down, in which case a summary of
+    ///   what it did is returned, or if a fatal error is thrown such as the port being in use or the
+    ///   TLS certificate being invalid.
     #[cfg(feature = "tls")]
-    pub async fn run_tls<A>(self, addr: A) -> Result<(), HumphreyError>
+    pub async fn run_tls<A>(self, addr: A) -> Result<ShutdownSummary, HumphreyError>
     where
         A: ToSocketAddrs,
     {
-        use tokio_rustls::TlsAcceptor;
-
-        let socket = TcpListener::bind(addr).await?;
-        let subapps = Arc::new(self.subapps);
-        let default_subapp = Arc::new(self.default_subapp);
-        let error_handler = Arc::new(self.error_handler);
-        let tls_config = self.tls_config.expect("TLS certificate not supplied");
+        let mut listeners = vec![(TcpListener::bind(addr).await?, EndpointKind::Tls)];
 
         if self.force_https {
-            let cloned_monitor = self.monitor.clone();
+            let socket = TcpListener::bind(self.force_https_addr).await?;
+            listeners.push((socket, EndpointKind::Redirect));
+        }
 
-            tokio::spawn(async move {
-                force_https_thread(cloned_monitor).await.unwrap_or(());
-            });
+        self.serve(listeners).await
+    }
+
+    /// Runs the Humphrey app on every given endpoint at once, sharing its routes and state between
+    ///   them.
+    ///
+    /// The shutdown signal stops every endpoint. `with_forced_https` has no effect on apps run with
+    ///   this function, so plaintext requests are only redirected to HTTPS on endpoints created with
+    ///   `Endpoint::redirect`.
+    /// This function will only return once the app has been shut down, in which case a summary of
+    ///   what it did is returned, or if a fatal error is thrown such as a port being in use.
+    pub async fn run_multi(
+        self,
+        endpoints: Vec<Endpoint>,
+    ) -> Result<ShutdownSummary, HumphreyError> {
+        let mut listeners = Vec::with_capacity(endpoints.len());
+
+        for endpoint in endpoints {
+            let socket = TcpListener::bind(&endpoint.addrs?[..]).await?;
+            listeners.push((socket, endpoint.kind));
         }
 
-        let acceptor = TlsAcceptor::from(tls_config);
+        self.serve(listeners).await
+    }
 
-        loop {
-            let shutdown = async {
-                if let Some(sd) = self.shutdown.as_ref() {
-                    sd.cancelled().await
-                } else {
-                    futures::future::pending().await
-                }
-            };
+    /// Accepts connections on every listener until the shutdown signal is received or every
+    ///   listener has failed, handling them according to the kind of endpoint they were accepted on.
+    async fn serve(
+        mut self,
+        listeners: Vec<(TcpListener, EndpointKind)>,
+    ) -> Result<ShutdownSummary, HumphreyError> {
+        let started = Instant::now();
+
+        #[cfg(feature = "tls")]
+        let acceptor = match &self.tls_config {
+            Some(tls_config) => Some(tokio_rustls::TlsAcceptor::from(tls_config.clone())),
+            None if listeners.iter().any(|(_, kind)| *kind == EndpointKind::Tls) => {
+                return Err("TLS certificate not supplied".into())
+            }
+            None => None,
+        };
 
-            tokio::select! {
-                () = shutdown => { break Ok(()); }
-                 s = socket.accept() => {
-                    match s {
-                        Ok((mut sock, _)) => {
-                            let cloned_state = self.state.clone();
-
-                            // Check that the client is allowed to connect
-                            if (self.connection_condition)(&mut sock, cloned_state) {
-                                let cloned_state = self.state.clone();
-                                let cloned_subapps = subapps.clone();
-                                let cloned_default_subapp = default_subapp.clone();
-                                let cloned_error_handler = error_handler.clone();
-                                let cloned_monitor = self.monitor.clone();
-                                let cloned_acceptor = acceptor.clone();
-
-                                cloned_monitor.send(
-                                    Event::new(EventType::ConnectionSuccess)
-                                        .with_peer_result(sock.peer_addr()),
-                                );
-
-                                // Spawn a new thread to handle the connection
-                                tokio::spawn(async move {
-                                    cloned_monitor.send(
-                                        Event::new(EventType::ThreadPoolProcessStarted)
-                                            .with_peer_result(sock.peer_addr()),
+        let shutdown = self.shutdown_after_drain();
+        let shutdown = shutdown.as_ref();
+        build_indexes(&mut self.subapps, &mut self.default_subapp);
+        let subapps = Arc::new(std::mem::take(&mut self.subapps));
+        let default_subapp = Arc::new(std::mem::take(&mut self.default_subapp));
+        let error_handler = self
+            .error_context_handler
+            .clone()
+            .unwrap_or_else(|| self.error_handler.clone());
+        let counters = AppCounters::default();
+        let idle_connections = IdleConnections::default();
+        #[cfg(feature = "tls")]
+        let https_port = https_port(&listeners);
+
+        // The accept loops borrow the app, so that each of them can share its state and handlers
+        let app = &self;
+        let subapps = &subapps;
+        let default_subapp = &default_subapp;
+        let error_handler = &error_handler;
+        let counters = &counters;
+        let idle_connections = &idle_connections;
+        #[cfg(feature = "tls")]
+        let acceptor = &acceptor;
+
+        let accept_loops = listeners.iter().map(move |(socket, kind)| {
+            let kind = *kind;
+
+            accept_loop(
+                socket,
+                &app.accept_options,
+                &app.monitor,
+                shutdown,
+                move |mut stream| {
+                    let cloned_state = app.state.clone();
+
+                    // Check that the client is allowed to connect
+                    if !(app.connection_condition)(&mut stream, cloned_state) {
+                        app.monitor.send_with(EventType::ConnectionDenied, |event| {
+                            event.with_peer_result(stream.peer_addr())
+                        });
+
+                        return;
+                    }
+
+                    // Count the connection until it has been handled, unless the app is already
+                    //   handling as many as it can
+                    let permit = match app.connection_limit.acquire() {
+                        Some(permit) => permit,
+                        None => {
+                            app.monitor.send_with(
+                                EventType::ConnectionRejectedOverCapacity,
+                                |event| {
+                                    event
+                                        .with_peer_result(stream.peer_addr())
+                                        .with_info(app.connection_limit.describe())
+                                },
+                            );
+
+                            // The response fits in the send buffer of the new connection, so it is
+                            //   written without waiting for the socket to be reported as writable
+                            if !kind.is_tls() {
+                                if let Ok(mut stream) = stream.into_std() {
+                                    let _ = std::io::Write::write_all(
+                                        &mut stream,
+                                        OVER_CAPACITY_RESPONSE,
                                     );
+                                }
+                            }
 
-                                    match cloned_acceptor.accept(sock).await {
-                                        Ok(tls_stream) => {
-                                            let stream = Stream::Tls(tls_stream);
-
-                                            client_handler(
-                                                stream,
-                                                cloned_subapps,
-                                                cloned_default_subapp,
-                                                cloned_error_handler,
-                                                cloned_state,
-                                                cloned_monitor,
-                                            )
-                                                .await
-                                        }
-                                        Err(e) => cloned_monitor.send(
-                                            Event::new(EventType::ConnectionError).with_info(e.to_string()),
-                                        ),
+                            return;
+                        }
+                    };
+
+                    let cloned_state = app.state.clone();
+                    let cloned_monitor = app.monitor.clone();
+                    let cloned_subapps = subapps.clone();
+                    let cloned_default_subapp = default_subapp.clone();
+                    let cloned_error_handler = error_handler.clone();
+                    let request_condition = app.request_condition.clone();
+                    let request_rewriter = app.request_rewriter.clone();
+                    let drain_signal = app.drain_signal.clone();
+                    let drain_options = app.drain_options.clone();
+                    let security = app.security.clone();
+                    let method_override = app.method_override.clone();
+                    let deadline_proxies = app.deadline_proxies.clone();
+                    let trace_options = app.trace_options;
+                    let rate_limiter = app.rate_limiter.clone();
+                    let proxy_protocol = app.proxy_protocol;
+                    let keep_alive = app.keep_alive;
+                    let websocket_limits = app.websocket_limits.clone();
+                    let cloned_idle_connections = idle_connections.clone();
+                    let cloned_counters = counters.clone();
+                    #[cfg(feature = "tls")]
+                    let cloned_acceptor = acceptor.clone();
+
+                    cloned_monitor.send_with(EventType::ConnectionSuccess, |event| {
+                        event.with_peer_result(stream.peer_addr())
+                    });
+                    counters.record_connection();
+
+                    // Spawn a new thread to handle the connection
+                    tokio::spawn(async move {
+                        let _permit = permit;
+
+                        cloned_monitor.send_with(EventType::ThreadPoolProcessStarted, |event| {
+                            event.with_peer_result(stream.peer_addr())
+                        });
+
+                        // Use the address of the client from the header sent by the load balancer,
+                        //   which comes before the TLS handshake if there is one
+                        let proxied_addr = match proxy_protocol {
+                            true => match proxy_protocol::client_addr(&mut stream, &cloned_monitor)
+                                .await
+                            {
+                                Some(addr) => Some(addr),
+                                None => return,
+                            },
+                            false => None,
+                        };
+
+                        let stream = match kind {
+                            EndpointKind::Plain => Stream::Tcp(stream),
+                            #[cfg(feature = "tls")]
+                            EndpointKind::Tls => {
+                                match cloned_acceptor.unwrap().accept(stream).await {
+                                    Ok(tls_stream) => Stream::Tls(tls_stream),
+                                    Err(e) => {
+                                        return cloned_monitor
+                                            .send_with(EventType::ConnectionError, |event| {
+                                                event.with_info(e.to_string())
+                                            })
                                     }
-                                });
-                            } else {
-                                self.monitor.send(
-                                    Event::new(EventType::ConnectionDenied)
-                                        .with_peer_result(sock.peer_addr()),
-                                );
+                                }
                             }
-                        }
-                        Err(e) => self
-                            .monitor
-                            .send(Event::new(EventType::ConnectionError).with_info(e.to_string())),
-                    }
-                }
-            }
-        }
+                            #[cfg(feature = "tls")]
+                            EndpointKind::Redirect => {
+                                return redirect_to_https(
+                                    stream,
+                                    proxied_addr,
+                                    https_port,
+                                    &security,
+                                    &cloned_monitor,
+                                )
+                                .await;
+                            }
+                        };
+
+                        client_handler(
+                            stream,
+                            cloned_subapps,
+                            cloned_default_subapp,
+                            cloned_error_handler,
+                            cloned_state,
+                            cloned_monitor,
+                            request_condition,
+                            request_rewriter,
+                            drain_signal,
+                            drain_options,
+                            security,
+                            method_override,
+                            deadline_proxies,
+                            trace_options,
+                            rate_limiter,
+                            cloned_counters,
+                            proxied_addr,
+                            keep_alive,
+                            cloned_idle_connections,
+                            websocket_limits,
+                        )
+                        .await
+                    });
+                },
+            )
+        });
+
+        let results = futures::future::join_all(accept_loops).await;
+
+        // Connections waiting for their next request would otherwise stay open until the client
+        //   closes them, which browsers can leave for minutes
+        idle_connections.close(&self.connection_limit).await;
+
+        // Without a shutdown signal, the accept loops only stop once their listeners have failed
+        let triggered = self.shutdown.as_ref().is_some_and(|s| s.is_triggered());
+        let reason = match results.into_iter().filter_map(Result::err).last() {
+            Some(e) if !triggered => ExitReason::ListenerError(e),
+            _ => ExitReason::Shutdown,
+        };
+
+        Ok(counters.summary(started, self.connection_limit.count(), reason))
     }
 
     /// Sets the default state for the server.
@@ -301,6 +560,10 @@ where
     /// Adds a new host sub-app to the server.
     /// The host can contain wildcards, for example `*.example.com`.
     ///
+    /// The port of the `Host` header is ignored unless the host specifies one, so `localhost` also
+    ///   handles requests for `localhost:8080`, while `localhost:8080` only handles those for that
+    ///   port. IPv6 addresses are written in square brackets, for example `[::1]`.
+    ///
     /// ## Panics
     /// This function will panic if the host is equal to `*`, since this is the default host.
     /// If you want to add a route to every host, simply add it directly to the main app.
@@ -317,14 +580,63 @@ where
 
     /// Adds a route and associated handler to the server.
     /// Routes can include wildcards, for example `/blog/*`.
+    ///
+    /// If several routes match a path, the most specific one handles it, whatever order they were
+    ///   added in. Routes without wildcards or parameters are preferred, then routes with longer
+    ///   literal parts, so `/contact` is preferred to `/*`.
+    ///
+    /// The handler is called from whichever worker happens to receive the request, so it must be
+    ///   `Send + Sync`. Requiring this here means that a closure capturing an `Rc` or a reference is
+    ///   reported at the call site rather than somewhere inside the app.
     pub fn with_route<T>(mut self, route: &str, handler: T) -> Self
     where
-        T: RequestHandler<State> + 'static,
+        T: RequestHandler<State> + Send + Sync + 'static,
     {
         self.default_subapp = self.default_subapp.with_route(route, handler);
         self
     }
 
+    /// Adds a route and associated handler to the server, giving it a name by which it can be
+    ///   identified when resolving requests with `resolve`.
+    /// Routes can include wildcards, for example `/blog/*`.
+    pub fn with_named_route<T>(mut self, name: &str, route: &str, handler: T) -> Self
+    where
+        T: RequestHandler<State> + Send + Sync + 'static,
+    {
+        self.default_subapp = self.default_subapp.with_named_route(name, route, handler);
+        self
+    }
+
+    /// Adds a route and associated handler to the server which only handles requests with the given
+    ///   method.
+    /// Routes can include wildcards, for example `/api/items/*`.
+    ///
+    /// Requests to the route's path with other methods fall through to other matching routes, and if
+    ///   no route handles them, are answered with `405 Method Not Allowed` and an `Allow` header
+    ///   listing the methods of the routes which match the path.
+    pub fn with_route_method<T>(mut self, route: &str, method: Method, handler: T) -> Self
+    where
+        T: RequestHandler<State> + Send + Sync + 'static,
+    {
+        self.default_subapp = self
+            .default_subapp
+            .with_route_method(route, method, handler);
+        self
+    }
+
+    /// Adds a route and associated handler to the server which only handles `HEAD` requests.
+    /// Routes can include wildcards, for example `/files/*`.
+    ///
+    /// Without one, `HEAD` requests are handled by the route which would handle them as `GET`
+    ///   requests, and the body of its response is not sent.
+    pub fn with_head_route<T>(mut self, route: &str, handler: T) -> Self
+    where
+        T: RequestHandler<State> + Send + Sync + 'static,
+    {
+        self.default_subapp = self.default_subapp.with_head_route(route, handler);
+        self
+    }
+
     /// Adds a route and associated handler to the server.
     /// Does not pass the state to the handler.
     /// Routes can include wildcards, for example `/blog/*`.
@@ -332,7 +644,7 @@ where
     /// If you want to access the app's state in the handler, consider using `with_route`.
     pub fn with_stateless_route<T>(mut self, route: &str, handler: T) -> Self
     where
-        T: StatelessRequestHandler<State> + 'static,
+        T: StatelessRequestHandler<State> + Send + Sync + 'static,
     {
         self.default_subapp = self.default_subapp.with_stateless_route(route, handler);
         self
@@ -343,23 +655,78 @@ where
     /// Will also pass the route to the handler at runtime.
     pub fn with_path_aware_route<T>(mut self, route: &'static str, handler: T) -> Self
     where
-        T: PathAwareRequestHandler<State> + 'static,
+        T: PathAwareRequestHandler<State> + Send + Sync + 'static,
     {
         self.default_subapp = self.default_subapp.with_path_aware_route(route, handler);
         self
     }
 
+    /// Adds a route and associated handler to the server whose request bodies are streamed to the
+    ///   handler as it reads them, rather than read into memory before it is called. This allows
+    ///   uploads which are too large to buffer to be copied to disk incrementally.
+    /// Routes can include wildcards, for example `/uploads/*`.
+    ///
+    /// The handler is given a `RequestBody` to read the body from, which stops at the end of the
+    ///   body so the connection can be reused afterwards. The body must be read before the handler's
+    ///   future completes. The rest of the body is discarded if the handler returns before reading
+    ///   it all, or the connection is closed if too much remains.
+    ///
+    /// Bodies are still limited by the app's maximum body size, which defaults to 10 MiB and can be
+    ///   raised with `with_max_body_size`.
+    ///
+    /// Streaming routes are checked before other routes, and are matched against the request before
+    ///   it is rewritten.
+    pub fn with_streaming_route<T>(mut self, route: &str, handler: T) -> Self
+    where
+        T: StreamingRequestHandler<State> + Send + Sync + 'static,
+    {
+        self.default_subapp = self.default_subapp.with_streaming_route(route, handler);
+        self
+    }
+
     /// Adds a WebSocket route and associated handler to the server.
     /// Routes can include wildcards, for example `/ws/*`.
     /// The handler is passed the stream, state, and the request which triggered its calling.
     pub fn with_websocket_route<T>(mut self, route: &str, handler: T) -> Self
     where
-        T: WebsocketHandler<State> + 'static,
+        T: WebsocketHandler<State> + Send + Sync + 'static,
     {
         self.default_subapp = self.default_subapp.with_websocket_route(route, handler);
         self
     }
 
+    /// Adds a WebSocket route and associated handler to the server, with a guard which decides
+    ///   whether each upgrade request is accepted before the handler is called.
+    /// Routes can include wildcards, for example `/ws/*`.
+    ///
+    /// If the guard rejects the request, its response is sent instead of upgrading the connection,
+    ///   and the handler is not called. The guard is passed the request with its route parameters,
+    ///   so it can check them as well as headers such as `Authorization` or
+    ///   `Sec-WebSocket-Protocol`.
+    pub fn with_guarded_websocket_route<G, T>(mut self, route: &str, guard: G, handler: T) -> Self
+    where
+        G: WebsocketGuard<State> + 'static,
+        T: WebsocketHandler<State> + Send + Sync + 'static,
+    {
+        self.default_subapp = self
+            .default_subapp
+            .with_guarded_websocket_route(route, guard, handler);
+        self
+    }
+
+    /// Adds a path-aware WebSocket route and associated handler to the server.
+    /// Routes can include wildcards, for example `/ws/*`.
+    /// Will also pass the route to the handler at runtime.
+    pub fn with_path_aware_websocket_route<T>(mut self, route: &'static str, handler: T) -> Self
+    where
+        T: PathAwareWebsocketHandler<State> + Send + Sync + 'static,
+    {
+        self.default_subapp = self
+            .default_subapp
+            .with_path_aware_websocket_route(route, handler);
+        self
+    }
+
     /// Registers a monitor for the server.
     pub fn with_monitor(mut self, monitor: MonitorConfig) -> Self {
         self.monitor = monitor;
@@ -367,18 +734,223 @@ where
     }
 
     /// Sets the error handler for the server.
-    pub fn with_error_handler(mut self, handler: ErrorHandler) -> Self {
-        self.error_handler = handler;
+    pub fn with_error_handler<T>(self, handler: T) -> Self
+    where
+        T: Fn(StatusCode) -> Response + Send + Sync + 'static,
+    {
+        self.with_error_handler_with_state(move |status_code, _, _| handler(status_code))
+    }
+
+    /// Sets the error handler for the server, which is also given the request if it could be parsed
+    ///   and the app's state, so that it can mention the requested path or log the error.
+    ///
+    /// Sub-apps can override the error handler for their host with `SubApp::with_error_handler`.
+    pub fn with_error_handler_with_state<T>(mut self, handler: T) -> Self
+    where
+        T: Fn(StatusCode, Option<&Request>, Arc<State>) -> Response + Send + Sync + 'static,
+    {
+        self.error_handler =
+            Arc::new(move |context, state| handler(context.status_code, context.request, state));
+        self
+    }
+
+    /// Sets the error handler for the server, which is given an `ErrorContext` describing what went
+    ///   wrong, such as why the request could not be read or the message of a panicking handler.
+    ///
+    /// This is used instead of the handler set with `with_error_handler` or
+    ///   `with_error_handler_with_state` if both are set. Sub-apps with their own error handler still
+    ///   handle the errors for their host.
+    pub fn with_error_context_handler<T>(mut self, handler: T) -> Self
+    where
+        T: Fn(&ErrorContext) -> Response + Send + Sync + 'static,
+    {
+        self.error_context_handler = Some(Arc::new(move |context, _| handler(context)));
         self
     }
 
     /// Sets the connection condition, a function which decides whether to accept the connection.
     /// For example, this could be used for implementing whitelists and blacklists.
-    pub fn with_connection_condition(mut self, condition: ConnectionCondition<State>) -> Self {
-        self.connection_condition = condition;
+    pub fn with_connection_condition<T>(mut self, condition: T) -> Self
+    where
+        T: Fn(&mut TcpStream, Arc<State>) -> bool + Send + Sync + 'static,
+    {
+        self.connection_condition = Box::new(condition);
+        self
+    }
+
+    /// Sets the request condition, a function which decides whether to reject a request based on its
+    ///   head, before the body is read.
+    ///
+    /// Unlike the connection condition, this has access to the request line and headers, but since the
+    ///   body of a rejected request is never buffered, large rejected uploads cost almost nothing.
+    pub fn with_request_condition<T>(mut self, condition: T) -> Self
+    where
+        T: Fn(&Request, Arc<State>) -> Option<Response> + Send + Sync + 'static,
+    {
+        self.request_condition = Some(Arc::new(condition));
+        self
+    }
+
+    /// Sets the request rewriter, a function which can modify every request before it is routed.
+    ///
+    /// This runs after the request condition, so the condition sees the request as it was received,
+    ///   but route matching, WebSocket handlers and request handlers all see the rewritten request.
+    pub fn with_request_rewriter<T>(mut self, rewriter: T) -> Self
+    where
+        T: Fn(&mut Request, Arc<State>) -> Option<Response> + Send + Sync + 'static,
+    {
+        self.request_rewriter = Some(Arc::new(rewriter));
+        self
+    }
+
+    /// Enables the method override, which lets a `POST` request be handled as a `PUT`, `PATCH` or
+    ///   `DELETE` request, for clients such as HTML forms which can only send `GET` and `POST`.
+    ///
+    /// The method is taken from the `X-HTTP-Method-Override` header or a form field, as determined by
+    ///   the given `MethodOverride`, once the request rewriter has run. Handlers see the overridden
+    ///   method in `request.method`, and the method the request was sent with is available from
+    ///   `request.original_method()`. Requests from other origins are only overridden to methods
+    ///   which the CORS configuration of their route allows them to use.
+    pub fn with_method_override(mut self, method_override: MethodOverride) -> Self {
+        self.method_override = Some(method_override);
+        self
+    }
+
+    /// Trusts the `X-Request-Deadline` header of requests received directly from the given proxies,
+    ///   which gives the time remaining until the deadline of the request in milliseconds.
+    ///
+    /// Handlers can pass the deadline of a request, available in `request.deadline`, on to the
+    ///   services they call with `ClientRequest::with_deadline`, which sends the header. Apps which
+    ///   call each other can therefore share the deadline of the original request.
+    pub fn with_deadline_propagation(mut self, trusted_proxies: Vec<IpAddr>) -> Self {
+        self.deadline_proxies = Arc::new(trusted_proxies);
+        self
+    }
+
+    /// Enables trace propagation, which gives every request a trace context in `request.trace`.
+    ///
+    /// Requests with a valid `traceparent` header continue the caller's trace and follow its sampling
+    ///   decision, and other requests start a new trace which is sampled if `sample_new_traces` is
+    ///   `true`. The trace and span IDs are included in the monitoring events about each request.
+    pub fn with_trace_propagation(mut self, sample_new_traces: bool) -> Self {
+        self.trace_options = Some(TraceOptions { sample_new_traces });
+        self
+    }
+
+    /// Converts the app into a `tower::Service`, so it can be mounted inside another server such as hyper.
+    ///
+    /// The service routes requests exactly as `run` would, but connection management, WebSocket
+    ///   routes and the connection and request conditions are left to the server driving it.
+    #[cfg(feature = "tower-compat")]
+    pub fn into_service(self) -> crate::tokio::tower::AppService<State> {
+        crate::tokio::tower::AppService::new(
+            self.subapps,
+            self.default_subapp,
+            self.error_context_handler.unwrap_or(self.error_handler),
+            self.state,
+        )
+    }
+
+    /// Converts the app into a `TestClient`, which sends requests to the app's routes without
+    ///   binding a port, for integration tests.
+    ///
+    /// Requests are handled exactly as `run` would handle them, including sub-apps, middleware, CORS
+    ///   and the error handler, but WebSocket routes and the connection condition are not used.
+    pub fn into_test_client(mut self) -> TestClient<State> {
+        build_indexes(&mut self.subapps, &mut self.default_subapp);
+
+        TestClient::new(
+            Arc::new(self.subapps),
+            Arc::new(self.default_subapp),
+            self.error_context_handler.unwrap_or(self.error_handler),
+            self.state,
+            self.request_condition,
+            self.request_rewriter,
+            self.security,
+            self.method_override,
+            self.deadline_proxies,
+            self.trace_options,
+            self.rate_limiter,
+        )
+    }
+
+    /// Applies the hardened security preset described by `SecurityOptions::hardened`.
+    ///
+    /// Request limits which were already set explicitly are kept. Every other option is replaced, but
+    ///   can be overridden individually by calling the corresponding method afterwards.
+    pub fn with_hardened_defaults(mut self) -> Self {
+        let limits = self.security.request_limits;
+        let security = Arc::make_mut(&mut self.security);
+        *security = SecurityOptions::hardened();
+        security.request_limits = limits.or(RequestLimits::conservative());
+        self
+    }
+
+    /// Sets the value of the `Server` header sent with every response, or `None` to omit it.
+    /// Defaults to `Humphrey`.
+    pub fn with_server_header(mut self, value: Option<&str>) -> Self {
+        Arc::make_mut(&mut self.security).server_header = value.map(|v| v.to_string());
+        self
+    }
+
+    /// Sets the value of the `X-Content-Type-Options` header sent with every response, or `None` to
+    ///   omit it. Defaults to `None`.
+    pub fn with_content_type_options(mut self, value: Option<&str>) -> Self {
+        Arc::make_mut(&mut self.security).content_type_options = value.map(|v| v.to_string());
+        self
+    }
+
+    /// Sets the value of the `X-Frame-Options` header sent with every response, or `None` to omit it.
+    /// Defaults to `None`.
+    pub fn with_frame_options(mut self, value: Option<&str>) -> Self {
+        Arc::make_mut(&mut self.security).frame_options = value.map(|v| v.to_string());
+        self
+    }
+
+    /// Sets the `max-age` of the `Strict-Transport-Security` header sent with every response over
+    ///   TLS, or `None` to omit it. Defaults to `None`.
+    pub fn with_hsts(mut self, max_age: Option<Duration>) -> Self {
+        Arc::make_mut(&mut self.security).hsts_max_age = max_age;
+        self
+    }
+
+    /// Sets the limits on the size of requests.
+    ///
+    /// Requests which exceed a limit are rejected with `414 URI Too Long`,
+    ///   `431 Request Header Fields Too Large` or `413 Payload Too Large` as appropriate, and the
+    ///   connection is closed. By default, the headers are limited to `DEFAULT_MAX_HEADER_SIZE` and
+    ///   the body to `DEFAULT_MAX_BODY_SIZE`, but the URI is not limited.
+    pub fn with_request_limits(mut self, limits: RequestLimits) -> Self {
+        Arc::make_mut(&mut self.security).request_limits = limits;
         self
     }
 
+    /// Sets the maximum size of a request body in bytes, beyond which requests are rejected with
+    ///   `413 Payload Too Large` before the body is read. Defaults to `DEFAULT_MAX_BODY_SIZE`.
+    ///
+    /// This also limits the bodies of streaming routes, so must be raised for apps which accept
+    ///   large uploads.
+    pub fn with_max_body_size(mut self, size: usize) -> Self {
+        Arc::make_mut(&mut self.security)
+            .request_limits
+            .max_body_size = Some(size);
+        self
+    }
+
+    /// Sets the maximum total size of the headers of a request in bytes, beyond which requests are
+    ///   rejected with `431 Request Header Fields Too Large`. Defaults to `DEFAULT_MAX_HEADER_SIZE`.
+    pub fn with_max_header_size(mut self, size: usize) -> Self {
+        Arc::make_mut(&mut self.security)
+            .request_limits
+            .max_header_size = Some(size);
+        self
+    }
+
+    /// Returns the security-related headers and request limits which the app will apply.
+    pub fn security_options(&self) -> &SecurityOptions {
+        &self.security
+    }
+
     /// Sets the CORS configuration for the app.
     ///
     /// This overrides the CORS configuration for existing and future individual routes.
@@ -397,58 +969,251 @@ where
         self
     }
 
+    /// Adds a header to every response from the app, including those generated by the error
+    ///   handler, unless the handler already set it.
+    ///
+    /// This can be called repeatedly to add several headers. `Set-Cookie` headers are always added,
+    ///   since a response can set many cookies, and informational (`1xx`) responses are left alone.
+    ///   Default headers of a sub-app added with `with_host` take precedence for requests to its
+    ///   host.
+    pub fn with_default_header(mut self, name: impl HeaderLike, value: impl AsRef<str>) -> Self {
+        self.default_subapp = self.default_subapp.with_default_header(name, value);
+        self
+    }
+
+    /// Adds a middleware which runs before and after the handlers for every request to the app,
+    ///   including requests for the hosts of sub-apps, whose own middleware runs after it.
+    ///
+    /// Middleware runs in the order it was added before the request is handled, and in the reverse
+    ///   order afterwards. See `Middleware` for details.
+    pub fn with_middleware(mut self, middleware: Middleware<State>) -> Self {
+        self.default_subapp = self.default_subapp.with_middleware(middleware);
+        self
+    }
+
+    /// Sets how paths which differ from a route only by a trailing slash are handled, for example
+    ///   whether `/about/` is handled by the route `/about`.
+    ///
+    /// This applies to the app's own routes, while sub-apps added with `with_host` have their own
+    ///   policy, set with `SubApp::with_trailing_slash_policy`. See `TrailingSlash` for details.
+    ///
+    /// ## Example
+    /// ```
+    /// let app: App<()> = App::new()
+    ///     .with_trailing_slash_policy(TrailingSlash::Redirect)
+    ///     .with_route("/about", about_handler);
+    /// ```
+    pub fn with_trailing_slash_policy(mut self, policy: TrailingSlash) -> Self {
+        self.default_subapp = self.default_subapp.with_trailing_slash_policy(policy);
+        self
+    }
+
+    /// Sets the headers to add to every response from the app, replacing any added previously with
+    ///   `with_default_header`.
+    pub fn with_default_headers(mut self, headers: Headers) -> Self {
+        self.default_subapp = self.default_subapp.with_default_headers(headers);
+        self
+    }
+
     /// Sets whether HTTPS should be forced on all connections. Defaults to false.
     ///
-    /// If this is set to true, a background thread will be spawned when `run_tls` is called to send
-    ///   redirect responses to all insecure requests on port 80.
+    /// If this is set to true, `run_tls` also listens on port 80, or the port set with
+    ///   `with_forced_https_port`, and redirects every insecure request to the same URL on the port
+    ///   the app is served over HTTPS on. Apps run with `run_multi` ignore this setting, and instead
+    ///   redirect requests on the endpoints created with `Endpoint::redirect`.
     #[cfg(feature = "tls")]
     pub fn with_forced_https(mut self, forced: bool) -> Self {
         self.force_https = forced;
         self
     }
 
-    /// Sets the TLS configuration for the server.
+    /// Forces HTTPS on all connections, redirecting insecure requests received on the given port
+    ///   instead of port 80.
+    #[cfg(feature = "tls")]
+    pub fn with_forced_https_port(mut self, port: u16) -> Self {
+        self.force_https = true;
+        self.force_https_addr.set_port(port);
+        self
+    }
+
+    /// Sets the address on which insecure requests are received when HTTPS is forced. Defaults to
+    ///   `0.0.0.0`, so requests are received on every interface.
+    #[cfg(feature = "tls")]
+    pub fn with_forced_https_address(mut self, addr: IpAddr) -> Self {
+        self.force_https_addr.set_ip(addr);
+        self
+    }
+
+    /// Sets the TLS configuration for the server, reading the certificate chain and private key from
+    ///   the PEM files at the given paths.
+    ///
+    /// This **must** be called before `run_tls` is called, or before `run_multi` is called with a TLS
+    ///   endpoint. Every certificate in the certificate file is used, so it can contain a full chain,
+    ///   and the private key can be a PKCS#8, PKCS#1 (RSA) or SEC1 (EC) key.
     ///
-    /// This **must** be called before `run_tls` is called.
+    /// ## Panics
+    /// This function panics if the certificate or key cannot be loaded. Use `try_with_cert` to handle
+    ///   the error instead.
     #[cfg(feature = "tls")]
-    pub fn with_cert(mut self, cert_path: impl AsRef<str>, key_path: impl AsRef<str>) -> Self {
-        use rustls::{Certificate, PrivateKey};
-        use rustls_pemfile::{read_one, Item};
+    pub fn with_cert(self, cert_path: impl AsRef<str>, key_path: impl AsRef<str>) -> Self {
+        self.try_with_cert(cert_path, key_path)
+            .unwrap_or_else(|e| panic!("failed to load certificate: {}", e))
+    }
 
-        use std::fs::File;
-        use std::io::BufReader;
+    /// Sets the TLS configuration for the server in the same way as `with_cert`, returning an error
+    ///   if the certificate or key cannot be loaded.
+    #[cfg(feature = "tls")]
+    pub fn try_with_cert(
+        mut self,
+        cert_path: impl AsRef<str>,
+        key_path: impl AsRef<str>,
+    ) -> Result<Self, CertError> {
+        self.tls_config = Some(tls::config_from_files(
+            Path::new(cert_path.as_ref()),
+            Path::new(key_path.as_ref()),
+        )?);
+
+        Ok(self)
+    }
 
-        let mut cert_file =
-            BufReader::new(File::open(cert_path.as_ref()).expect("failed to open cert file"));
-        let mut key_file =
-            BufReader::new(File::open(key_path.as_ref()).expect("failed to open key file"));
+    /// Sets the TLS configuration for the server in the same way as `with_cert`, but from PEM data
+    ///   which has already been loaded, for example from a secret store or with `include_bytes!`.
+    ///
+    /// ## Panics
+    /// This function panics if the certificate or key cannot be loaded. Use `try_with_cert_bytes` to
+    ///   handle the error instead.
+    #[cfg(feature = "tls")]
+    pub fn with_cert_bytes(self, cert_pem: &[u8], key_pem: &[u8]) -> Self {
+        self.try_with_cert_bytes(cert_pem, key_pem)
+            .unwrap_or_else(|e| panic!("failed to load certificate: {}", e))
+    }
 
-        let certs: Vec<Certificate> = match read_one(&mut cert_file).unwrap().unwrap() {
-            Item::X509Certificate(cert) => vec![Certificate(cert)],
-            _ => panic!("failed to parse cert file"),
-        };
+    /// Sets the TLS configuration for the server in the same way as `with_cert_bytes`, returning an
+    ///   error if the certificate or key cannot be loaded.
+    #[cfg(feature = "tls")]
+    pub fn try_with_cert_bytes(
+        mut self,
+        cert_pem: &[u8],
+        key_pem: &[u8],
+    ) -> Result<Self, CertError> {
+        self.tls_config = Some(tls::config_from_pem(cert_pem, key_pem)?);
+
+        Ok(self)
+    }
 
-        let key: PrivateKey = match read_one(&mut key_file).unwrap().unwrap() {
-            Item::PKCS8Key(key) => PrivateKey(key),
-            _ => panic!("failed to parse key file"),
-        };
+    /// Registers a shutdown signal to gracefully shutdown the app, ending the run/run_tls loop.
+    ///
+    /// As well as a `ShutdownSignal`, this accepts a `tokio_util` `CancellationToken` which shuts
+    ///   down the app when it is cancelled, or an `Arc<AtomicBool>` which shuts down the app when it
+    ///   is set.
+    pub fn with_shutdown(mut self, shutdown: impl Into<ShutdownSignal>) -> Self {
+        self.shutdown = Some(shutdown.into());
+        self
+    }
 
-        let config = Arc::new(
-            ServerConfig::builder()
-                .with_safe_defaults()
-                .with_no_client_auth()
-                .with_single_cert(certs, key)
-                .expect("failed to create server config"),
-        );
+    /// Enables connection draining during graceful shutdown with the given options.
+    ///
+    /// Once the shutdown signal is triggered, the app keeps accepting connections for the configured
+    ///   period, but new requests, including those on existing keep-alive connections, receive
+    ///   `503 Service Unavailable` with a `Retry-After` header and `Connection: Close`. Requests
+    ///   which are already being processed complete normally.
+    pub fn with_draining(mut self, options: DrainOptions) -> Self {
+        self.drain_options = Some(options);
+        self
+    }
 
-        self.tls_config = Some(config);
+    /// Returns a handle which reports whether the app has started draining connections.
+    ///
+    /// This can be stored in the app's state or captured by a handler, for example to make a
+    ///   readiness check fail as soon as draining starts.
+    pub fn drain_signal(&self) -> DrainSignal {
+        self.drain_signal.clone()
+    }
 
+    /// Sets the maximum number of connections which can be handled at once.
+    ///
+    /// Without a limit, every accepted connection is given a task, however overloaded the app is.
+    ///   Once the limit is reached, further connections are answered with `503 Service Unavailable`
+    ///   and closed as soon as they are accepted, or closed straight away on TLS endpoints, and a
+    ///   `ConnectionRejectedOverCapacity` monitor event is sent. A connection is counted until its
+    ///   handler returns, including when it returns by panicking.
+    pub fn with_max_concurrent_connections(mut self, limit: usize) -> Self {
+        self.connection_limit = ConnectionLimit::new(Some(limit));
         self
     }
 
-    /// Registers a shutdown signal to gracefully shutdown the app, ending the run/run_tls loop.
-    pub fn with_shutdown(mut self, cancel_token: CancellationToken) -> Self {
-        self.shutdown = Some(cancel_token);
+    /// Limits the rate at which each client, identified by its IP address, can make requests.
+    ///
+    /// Every request is counted, including each request on a keep-alive connection. Requests from a
+    ///   client which has used up its allowance are answered with `429 Too Many Requests` and a
+    ///   `Retry-After` header before they are routed, and a `RequestRateLimited` monitor event is sent.
+    pub fn with_rate_limit(mut self, config: RateLimitConfig) -> Self {
+        self.rate_limiter = Some(RateLimiter::new(config));
+        self
+    }
+
+    /// Sets whether every connection starts with a PROXY protocol header, as sent by load balancers
+    ///   such as HAProxy and AWS Network Load Balancers. Defaults to false.
+    ///
+    /// If this is set to true, the address of the client given by the header is used in place of
+    ///   the address of the load balancer, both in `request.address` and in monitor events, and so
+    ///   for rate limiting. Connections which do not start with a valid header are closed, and the
+    ///   `ConnectionError` event is sent. The connection condition is still given the connection
+    ///   from the load balancer.
+    pub fn with_proxy_protocol(mut self, enabled: bool) -> Self {
+        self.proxy_protocol = enabled;
+        self
+    }
+
+    /// Limits how long keep-alive connections are kept open while idle and how many requests can be
+    ///   sent on each of them.
+    ///
+    /// Responses on connections which will be kept open have a `Keep-Alive` header advertising the
+    ///   limits. Connections which are idle for longer than the idle timeout are closed without a
+    ///   response, and the connection is closed after the last request allowed on it.
+    pub fn with_keep_alive(mut self, config: KeepAliveConfig) -> Self {
+        self.keep_alive = Some(config);
+        self
+    }
+
+    /// Sets the maximum number of WebSocket connections which can be open at once.
+    ///
+    /// Each WebSocket connection keeps its task, socket and buffers for its entire lifetime, so
+    ///   without a limit, enough connected clients will exhaust the resources of the server. Once the
+    ///   limit is reached, further upgrade requests are refused with `503 Service Unavailable`. The
+    ///   number of open WebSocket connections is included in the information of the WebSocket
+    ///   monitor events.
+    pub fn with_websocket_limit(mut self, limit: usize) -> Self {
+        self.websocket_limits.global = Some(limit);
+        self
+    }
+
+    /// Sets the maximum number of WebSocket connections which can be open at once, both in total and
+    ///   from a single IP address. `None` leaves the corresponding limit unbounded.
+    ///
+    /// The global limit is the same as that set by `with_websocket_limit`. The per-IP limit stops a
+    ///   single client from using up the global limit by itself, and is based on the address of the
+    ///   client, so clients behind the same proxy share it. Upgrade requests beyond either limit are
+    ///   refused with `503 Service Unavailable` before the handshake, and a
+    ///   `WebsocketConnectionRefused` monitor event is sent describing which limit was reached.
+    pub fn with_websocket_limits(mut self, global: Option<usize>, per_ip: Option<usize>) -> Self {
+        self.websocket_limits.global = global;
+        self.websocket_limits.per_ip = per_ip;
+        self
+    }
+
+    /// Returns a handle which reports the number of WebSocket connections currently open.
+    ///
+    /// Connections are counted from when their upgrade request is accepted until their handler
+    ///   returns, including when it returns by panicking.
+    pub fn websocket_connections(&self) -> WebsocketConnections {
+        self.websocket_limits.connections.clone()
+    }
+
+    /// Sets the options which control how connections are accepted, such as the backoff when the
+    ///   process runs out of file descriptors and the number of connections accepted at once.
+    pub fn with_accept_options(mut self, options: AcceptOptions) -> Self {
+        self.accept_options = options;
         self
     }
 
@@ -457,6 +1222,111 @@ where
     pub fn get_state(&self) -> Arc<State> {
         self.state.clone()
     }
+
+    /// Returns the route which would handle a request with the given method, path and `Host`
+    ///   header, without calling its handler.
+    ///
+    /// The path should be percent-decoded and without the query string, as in `request.uri`. Host
+    ///   sub-apps and routes are checked in the same order as when requests are handled, but the
+    ///   request rewriter, request condition and trailing slash policy are not applied.
+    pub fn resolve<'a>(
+        &'a self,
+        method: &Method,
+        path: &'a str,
+        host: Option<&str>,
+    ) -> Option<RouteMatch<'a>> {
+        find_route(&self.subapps, &self.default_subapp, method, host, path)
+            .map(|(subapp, index)| subapp.route_match(index, method, path))
+    }
+
+    /// Returns the signal which stops the accept loop.
+    ///
+    /// If draining is enabled, this is triggered once the drain period has elapsed after the
+    ///   shutdown signal is triggered, with the app draining in the meantime.
+    fn shutdown_after_drain(&self) -> Option<ShutdownSignal> {
+        match (&self.shutdown, &self.drain_options) {
+            (Some(shutdown), Some(drain_options)) => {
+                let stop = ShutdownSignal::new();
+                let cloned_stop = stop.clone();
+                let shutdown = shutdown.clone();
+                let drain_signal = self.drain_signal.clone();
+                let period = drain_options.period;
+
+                tokio::spawn(async move {
+                    shutdown.wait().await;
+                    drain_signal.start();
+                    tokio::time::sleep(period).await;
+                    cloned_stop.trigger();
+                });
+
+                Some(stop)
+            }
+            (shutdown, _) => shutdown.clone(),
+        }
+    }
+}
+
+/// Accepts connections from the listener and passes them to the given function until shutdown,
+///   or until the listener fails, in which case the error is returned.
+///
+/// Once a connection is accepted, up to `options.burst` connections which are already waiting are
+///   accepted before checking for shutdown again. If the process runs out of file descriptors, the
+///   loop backs off instead of retrying immediately.
+pub(crate) async fn accept_loop<L>(
+    listener: &L,
+    options: &AcceptOptions,
+    monitor: &MonitorConfig,
+    shutdown: Option<&ShutdownSignal>,
+    mut handle: impl FnMut(L::Stream),
+) -> io::Result<()>
+where
+    L: Listener,
+{
+    let mut errors = AcceptErrors::new(options);
+    let shutdown = async {
+        match shutdown {
+            Some(shutdown) => shutdown.wait().await,
+            None => futures::future::pending().await,
+        }
+    };
+    tokio::pin!(shutdown);
+
+    loop {
+        let mut result = tokio::select! {
+            () = &mut shutdown => return Ok(()),
+            result = poll_fn(|cx| listener.poll_accept(cx)) => Some(result),
+        };
+        let mut accepted: usize = 0;
+
+        while let Some(next) = result.take() {
+            match next {
+                Ok(stream) => {
+                    errors.reset();
+                    handle(stream);
+                    accepted += 1;
+
+                    if accepted < options.burst {
+                        result = poll_fn(|cx| listener.poll_accept(cx)).now_or_never();
+                    }
+                }
+                Err(e) if is_listener_failure(&e) => return Err(e),
+                Err(e) => {
+                    if let Some(delay) = errors.record(&e, monitor) {
+                        // Free a descriptor to accept and close one waiting connection
+                        if errors.release_reserve() {
+                            drop(poll_fn(|cx| listener.poll_accept(cx)).now_or_never());
+                            errors.restore_reserve();
+                        }
+
+                        tokio::select! {
+                            () = &mut shutdown => return Ok(()),
+                            () = tokio::time::sleep(delay) => (),
+                        }
+                    }
+                }
+            }
+        }
+    }
 }
 
 /// Handles a connection with a client.
@@ -467,40 +1337,285 @@ async fn client_handler<State>(
     mut stream: Stream,
     subapps: Arc<Vec<SubApp<State>>>,
     default_subapp: Arc<SubApp<State>>,
-    error_handler: Arc<ErrorHandler>,
+    error_handler: ErrorHandler<State>,
     state: Arc<State>,
     monitor: MonitorConfig,
-) {
-    let addr = if let Ok(addr) = stream.peer_addr() {
+    request_condition: Option<RequestCondition<State>>,
+    request_rewriter: Option<RequestRewriter<State>>,
+    drain_signal: DrainSignal,
+    drain_options: Option<DrainOptions>,
+    security: Arc<SecurityOptions>,
+    method_override: Option<MethodOverride>,
+    deadline_proxies: Arc<Vec<IpAddr>>,
+    trace_options: Option<TraceOptions>,
+    rate_limiter: Option<RateLimiter>,
+    counters: AppCounters,
+    proxied_addr: Option<SocketAddr>,
+    keep_alive_config: Option<KeepAliveConfig>,
+    idle_connections: IdleConnections,
+    websocket_limits: WebsocketLimits,
+) where
+    State: Send + Sync + 'static,
+{
+    let addr = if let Some(addr) = proxied_addr {
+        addr
+    } else if let Ok(addr) = stream.peer_addr() {
         addr
     } else {
         monitor.send(EventType::StreamDisconnectedWhileWaiting);
 
         return;
     };
+    let local_addr = stream.local_addr().ok();
+
+    // Another handle to the connection, so that it can be watched for disconnection while handlers
+    //   run
+    let socket = stream.try_clone_socket().ok();
+
+    let slow_request_threshold = monitor.slow_request_threshold();
+    let timed = slow_request_threshold.is_some();
+
+    // Bodies only need to be left unread if there are streaming routes to read them
+    let streaming = !default_subapp.streaming_routes.is_empty()
+        || subapps
+            .iter()
+            .any(|subapp| !subapp.streaming_routes.is_empty());
+
+    // Errors are handled by the error handler for the request's host
+    let error_handler =
+        |context: ErrorContext| handle_error(&context, &subapps, &error_handler, &state);
+
+    // The number of requests received on the connection so far
+    let mut requests: u32 = 0;
 
     loop {
-        // Parses the request from the stream
-        let request = Request::from_stream(&mut stream, addr).await;
+        // Parses the request from the stream, checking the head against the request condition
+        let mut timer = RequestTimer::new();
+        let mut rejection: Option<(Request, Response)> = None;
+        let mut check_head = |head: &Request| {
+            let response = request_condition
+                .as_ref()
+                .and_then(|condition| condition(head, state.clone()));
+            let accepted = response.is_none();
+            rejection = response.map(|response| (head.clone(), response));
+
+            accepted
+        };
+        let condition = request_condition
+            .as_ref()
+            .map(|_| &mut check_head as HeadCondition);
+
+        // Leave the body unread if the request is for a streaming route
+        let mut is_streamed = |head: &Request| {
+            head.method != Method::Options
+                && get_streaming_handler(head, &subapps, &default_subapp).is_some()
+        };
+        let mut deferral = streaming.then_some(BodyDeferral {
+            condition: &mut is_streamed as HeadCondition,
+            buffered: None,
+        });
+
+        // Between requests, the connection is closed when the app shuts down, and after the idle
+        //   timeout if keep-alive limits are set
+        let idle = requests > 0;
+        if idle && !idle_connections.enter() {
+            break;
+        }
+        let mut idle_wait = pin!(async {
+            let timeout = async {
+                match &keep_alive_config {
+                    Some(config) => tokio::time::sleep(config.idle_timeout).await,
+                    None => std::future::pending().await,
+                }
+            };
+
+            tokio::select! {
+                () = timeout => (),
+                () = idle_connections.closed() => (),
+            }
+        });
+
+        let mut request = Request::from_stream_checked(
+            &mut stream,
+            addr,
+            condition,
+            deferral.as_mut(),
+            Some(&mut timer.received),
+            idle.then_some(idle_wait.as_mut()),
+            &security.request_limits,
+        )
+        .await;
+        timer.parsed();
+
+        if idle {
+            idle_connections.leave();
+
+            // Idle connections are closed without a response once they time out or the app shuts
+            //   down
+            if let Err(RequestError::Timeout) = &request {
+                break;
+            }
+        }
+
+        requests += 1;
+
+        let buffered = deferral.and_then(|deferral| deferral.buffered);
+
+        if let Ok(request) = &mut request {
+            request.secure = stream.is_secure();
+            request.local_addr = local_addr;
+            request.set_deadline(None, &deadline_proxies);
+            request.set_trace(trace_options);
+        }
+
+        // If the request was rejected by the request condition, respond using its head
+        let (mut request, mut rejected) = match (request, rejection) {
+            (Err(RequestError::Rejected), Some((head, response))) => (Ok(head), Some(response)),
+            (request, _) => (request, None),
+        };
+
+        // Rewrite the request before it is routed, which may also reject it
+        if let (Ok(request), None, Some(rewriter)) = (&mut request, &rejected, &request_rewriter) {
+            rejected = rewriter(request, state.clone());
+        }
+
+        // Handle the request as the method it asks for, if the method override is enabled
+        if let (Ok(request), None, Some(method_override)) =
+            (&mut request, &rejected, &method_override)
+        {
+            apply_method_override(request, method_override, &subapps, &default_subapp);
+        }
+
+        // Merge or redirect paths which only match a route with a different trailing slash
+        if let (Ok(request), None) = (&mut request, &rejected) {
+            rejected = apply_trailing_slash(request, &subapps, &default_subapp);
+        }
+
+        // If the app is draining, refuse new requests and close the connection
+        let mut draining = false;
+        if let (Ok(request), None) = (&request, &rejected) {
+            rejected = draining_response(&drain_signal, drain_options.as_ref(), &|status_code| {
+                error_handler(
+                    ErrorContext::new(status_code)
+                        .with_request(request)
+                        .with_cause(ErrorCause::Draining),
+                )
+            });
+            draining = rejected.is_some();
+        }
+
+        // Refuse the request if its client has used up its allowance under the rate limit
+        if let (Ok(request), None, Some(limiter)) = (&request, &rejected, &rate_limiter) {
+            if let Err(retry_after) = limiter.check(addr.ip()) {
+                monitor.send_with(EventType::RequestRateLimited, |event| {
+                    event
+                        .with_peer(addr)
+                        .with_info(limiter.describe())
+                        .with_trace(request.trace.as_ref())
+                });
+
+                rejected = Some(rate_limited_response(retry_after, &|status_code| {
+                    error_handler(
+                        ErrorContext::new(status_code)
+                            .with_request(request)
+                            .with_cause(ErrorCause::LimitReached(limiter.describe())),
+                    )
+                }));
+            }
+        }
+
+        // Run the request side of the middleware for the request's host, which may answer it itself
+        let host = request
+            .as_ref()
+            .ok()
+            .and_then(|request| request.headers.get(&HeaderType::Host));
+        let middleware = find_middleware(&subapps, &default_subapp, host);
+        let mut middleware_ran = 0;
+        if let (Ok(request), None) = (&mut request, &rejected) {
+            if request.method != Method::Options {
+                (middleware_ran, rejected) = run_request_middleware(&middleware, request, &state);
+            }
+        }
 
         let cloned_state = state.clone();
 
         // If the request is valid an is a WebSocket request, call the corresponding handler
-        if let Ok(req) = &request {
+        if let (Ok(req), None) = (&request, &rejected) {
             if req.headers.get(&HeaderType::Upgrade) == Some("websocket") {
-                monitor.send(Event::new(EventType::WebsocketConnectionRequested).with_peer(addr));
+                let handler = get_websocket_handler(req, &subapps, &default_subapp);
+
+                // The route's guard decides first, then the connection must fit within the limits
+                let decision = handler.map(|handler| (handler, handler.check_upgrade(req, &state)));
+                let upgrade = match decision {
+                    Some((handler, UpgradeDecision::Reject(response))) => Err((
+                        format!("refused by the guard of route {}", handler.route),
+                        Some(response),
+                    )),
+                    _ => websocket_limits.acquire(addr.ip()).map_err(|e| (e, None)),
+                };
 
-                call_websocket_handler(req, &subapps, &default_subapp, cloned_state, stream).await;
+                match upgrade {
+                    Ok(permit) => {
+                        monitor.send_with(EventType::WebsocketConnectionRequested, |event| {
+                            event.with_peer(addr).with_info(websocket_limits.describe())
+                        });
+
+                        if let Some(handler) = handler {
+                            handler
+                                .handler
+                                .serve(
+                                    handler_request(req, &handler.route, false),
+                                    stream,
+                                    cloned_state,
+                                )
+                                .await;
+                        }
 
-                monitor.send(Event::new(EventType::WebsocketConnectionClosed).with_peer(addr));
-                break;
+                        drop(permit);
+                        monitor.send_with(EventType::WebsocketConnectionClosed, |event| {
+                            event
+                                .with_peer(addr)
+                                .with_info(websocket_limits.describe())
+                        });
+                        break;
+                    }
+                    Err((reason, response)) => {
+                        monitor.send_with(EventType::WebsocketConnectionRefused, |event| {
+                            event.with_peer(addr).with_info(reason.clone())
+                        });
+
+                        rejected = Some(response.unwrap_or_else(|| {
+                            error_handler(
+                                ErrorContext::new(StatusCode::ServiceUnavailable)
+                                    .with_request(req)
+                                    .with_cause(ErrorCause::LimitReached(reason)),
+                            )
+                        }));
+                    }
+                }
+            }
+        }
+
+        // A connection whose request body was cut short cannot be reused, since the rest of the body
+        //   may still arrive and be mistaken for the next request
+        if let Ok(request) = &request {
+            if let BodyState::Truncated { received, expected } = request.body_state {
+                monitor.send_with(EventType::RequestBodyIncomplete, |event| {
+                    event
+                        .with_peer(addr)
+                        .with_info(format!("{} of {} bytes received", received, expected))
+                        .with_trace(request.trace.as_ref())
+                });
             }
         }
 
         // Get the keep alive information from the request before it is consumed by the handler
-        let keep_alive = if let Ok(request) = &request {
+        let mut keep_alive = if let Ok(request) = &request {
             if let Some(connection) = request.headers.get(&HeaderType::Connection) {
                 connection.to_ascii_lowercase() == "keep-alive"
+                    && !draining
+                    && request.body_complete()
+                    && (rejected.is_none() || request.rejected_body_drained())
             } else {
                 false
             }
@@ -509,15 +1624,22 @@ async fn client_handler<State>(
         };
 
         // Generate the response based on the handlers
-        let response = match &request {
-            Ok(request) if request.method == Method::Options => {
-                let handler = get_handler(request, &subapps, &default_subapp);
+        let mut route: Option<String> = None;
+        let mut response = match &request {
+            Ok(request) if request.method == Method::Options && rejected.is_none() => {
+                let handler = get_handler(request, &subapps, &default_subapp)
+                    .map(|handler| (&handler.route, &handler.cors))
+                    .or_else(|| {
+                        get_streaming_handler(request, &subapps, &default_subapp)
+                            .map(|handler| (&handler.route, &handler.cors))
+                    });
 
                 match handler {
-                    Some(handler) => {
+                    Some((handler_route, cors)) => {
+                        route = timed.then(|| handler_route.clone());
+
                         let mut response = Response::empty(StatusCode::NoContent)
                             .with_header(HeaderType::Date, DateTime::now().to_string())
-                            .with_header(HeaderType::Server, "Humphrey")
                             .with_header(
                                 HeaderType::Connection,
                                 match keep_alive {
@@ -526,45 +1648,141 @@ async fn client_handler<State>(
                                 },
                             );
 
-                        handler.cors.set_headers(&mut response.headers);
+                        cors.set_headers(&mut response.headers);
+
+                        // Browsers only send the override header if the preflight response allows it
+                        if method_override == Some(MethodOverride::Header) {
+                            cors.allow_override_header(&mut response.headers);
+                        }
+
+                        if let Some(allow) = get_allow_header(request, &subapps, &default_subapp) {
+                            response.headers.add(HeaderType::Allow, allow);
+                        }
 
                         response
                     }
-                    None => error_handler(StatusCode::NotFound),
+                    None => {
+                        error_handler(ErrorContext::new(StatusCode::NotFound).with_request(request))
+                    }
                 }
             }
             Ok(request) => {
                 let handler = get_handler(request, &subapps, &default_subapp);
+                let streaming_handler = get_streaming_handler(request, &subapps, &default_subapp);
+                let mut body = buffered.map(|buffered| {
+                    DeferredBody::new(
+                        buffered,
+                        &mut stream,
+                        &request.headers,
+                        &security.request_limits,
+                    )
+                });
+
+                let mut response = match (rejected, streaming_handler, &mut body, handler) {
+                    (Some(mut response), ..) => {
+                        if !keep_alive {
+                            response.headers.add(HeaderType::Connection, "Close");
+                        }
+
+                        response
+                    }
+                    (None, Some(handler), Some(body), _) => {
+                        route = timed.then(|| handler.route.clone());
+
+                        let served = body
+                            .stream(|body| {
+                                AssertUnwindSafe(handler.handler.serve(
+                                    handler_request(request, &handler.route, false),
+                                    body,
+                                    state.clone(),
+                                ))
+                                .catch_unwind()
+                            })
+                            .await;
+
+                        let mut response = served.unwrap_or_else(|payload| {
+                            keep_alive = false;
+                            panic_response(request, &handler.route, payload, &error_handler)
+                        });
 
-                let mut response = match handler {
-                    Some(handler) => {
-                        let mut response: Response =
-                            handler.handler.serve(request.clone(), state.clone()).await;
+                        handler.cors.set_headers(&mut response.headers);
+
+                        response
+                    }
+                    (None, _, _, Some(handler)) => {
+                        route = timed.then(|| handler.route.clone());
+
+                        let serve = AssertUnwindSafe(handler.handler.serve(
+                            handler_request(
+                                request,
+                                &handler.route,
+                                handler.declares(&Method::Head),
+                            ),
+                            state.clone(),
+                        ))
+                        .catch_unwind();
+
+                        let served = match &socket {
+                            Some(socket) => watch(serve, socket, &request.cancellation).await,
+                            None => serve.await,
+                        };
+
+                        let mut response = served.unwrap_or_else(|payload| {
+                            keep_alive = false;
+                            panic_response(request, &handler.route, payload, &error_handler)
+                        });
 
                         handler.cors.set_headers(&mut response.headers);
 
                         response
                     }
-                    None => error_handler(StatusCode::NotFound),
+                    // Methods only routed to routes added for them are not implemented otherwise
+                    (None, ..) if request.method.requires_declared_route() => error_handler(
+                        ErrorContext::new(StatusCode::NotImplemented).with_request(request),
+                    ),
+                    (None, ..) => match get_allow_header(request, &subapps, &default_subapp) {
+                        Some(allow) => error_handler(
+                            ErrorContext::new(StatusCode::MethodNotAllowed).with_request(request),
+                        )
+                        .with_header(HeaderType::Allow, allow),
+                        None => error_handler(
+                            ErrorContext::new(StatusCode::NotFound).with_request(request),
+                        ),
+                    },
                 };
 
-                // Automatically generate required headers
+                run_response_middleware(
+                    &middleware[..middleware_ran],
+                    &mut response,
+                    request,
+                    &state,
+                );
+
+                // Whatever remains of an unread body must be discarded before the connection can be
+                //   reused, which is only done if little enough remains
+                if let Some(mut body) = body {
+                    if !body.finish().await {
+                        keep_alive = false;
+                        response.headers.remove(HeaderType::Connection);
+                        response.headers.add(HeaderType::Connection, "Close");
+                    }
+                }
+
+                // Automatically generate required headers, and close the connection if the
+                //   response says it will be, as event streams do
                 match response.headers.get_mut(HeaderType::Connection) {
+                    Some(connection) if connection.eq_ignore_ascii_case("close") => {
+                        keep_alive = false;
+                    }
                     Some(_) => (),
-                    None => {
-                        if let Some(connection) = &request.headers.get(&HeaderType::Connection) {
+                    None => match &request.headers.get(&HeaderType::Connection) {
+                        Some(connection) if request.body_complete() => {
                             response.headers.add(HeaderType::Connection, connection);
-                        } else {
+                        }
+                        _ => {
                             response.headers.add(HeaderType::Connection, "Close");
                         }
-                    }
-                }
-
-                match response.headers.get_mut(HeaderType::Server) {
-                    Some(_) => (),
-                    None => {
-                        response.headers.add(HeaderType::Server, "Humphrey");
-                    }
+                    },
                 }
 
                 match response.headers.get_mut(HeaderType::Date) {
@@ -576,14 +1794,8 @@ async fn client_handler<State>(
                     }
                 }
 
-                match response.headers.get_mut(HeaderType::ContentLength) {
-                    Some(_) => (),
-                    None => {
-                        response
-                            .headers
-                            .add(HeaderType::ContentLength, response.body.len().to_string());
-                    }
-                }
+                // Remove the body and adjust the framing where the status code or method require it
+                response.finalise_framing(&request.method);
 
                 // Set HTTP version
                 response.version = request.version.clone();
@@ -591,68 +1803,220 @@ async fn client_handler<State>(
                 response
             }
             Err(e) => match e {
-                RequestError::Request => error_handler(StatusCode::BadRequest),
-                RequestError::Timeout => error_handler(StatusCode::RequestTimeout),
+                RequestError::Request => error_handler(
+                    ErrorContext::new(StatusCode::BadRequest)
+                        .with_cause(ErrorCause::Request(e.clone())),
+                ),
+                RequestError::Timeout => error_handler(
+                    ErrorContext::new(StatusCode::RequestTimeout)
+                        .with_cause(ErrorCause::Request(e.clone())),
+                ),
+                RequestError::TooLarge(part) => error_handler(
+                    ErrorContext::new(part.status_code())
+                        .with_cause(ErrorCause::Request(e.clone())),
+                )
+                .with_header(HeaderType::Connection, "Close"),
                 RequestError::Disconnected => return,
-                RequestError::Stream => {
+                RequestError::Stream | RequestError::Rejected => {
                     return monitor.send(Event::new(EventType::RequestServedError))
                 }
             },
         };
 
+        // HTTP/1.0 clients do not understand chunked framing and only keep the connection open if
+        //   they asked to
+        if let Ok(request) = &request {
+            if request.version == "HTTP/1.0" {
+                response.finalise_http_1_0(keep_alive);
+            }
+        }
+
+        // Advertise the keep-alive limits, closing the connection after the last request allowed
+        if let Some(keep_alive_config) = &keep_alive_config {
+            keep_alive_config.limit(&mut response, &mut keep_alive, requests);
+        }
+
+        let host = request
+            .as_ref()
+            .ok()
+            .and_then(|request| request.headers.get(&HeaderType::Host));
+        apply_default_headers(&mut response, &subapps, &default_subapp, host);
+        security.apply(&mut response, stream.is_secure());
+
         // Write the response to the stream
+        timer.handled();
         let status = response.status_code;
-        let response_bytes: Vec<u8> = response.into();
+        let hijack = response.hijack.take();
+        let close_delimited = response.is_close_delimited();
+
+        // Streamed bodies are written as they are read, so reading them can also fail here, and
+        //   anything still working on the request is told to stop
+        if let Err(e) = response.write_to(&mut stream).await {
+            if let Ok(request) = &request {
+                request.cancellation.cancel();
+            }
 
-        if let Err(e) = stream.write_all(&response_bytes).await {
-            monitor.send(
-                Event::new(EventType::RequestServedError)
-                    .with_peer(addr)
-                    .with_info(e.to_string()),
-            );
+            monitor.send_with(EventType::RequestServedError, |event| {
+                event.with_peer(addr).with_info(e.to_string())
+            });
 
             break;
         };
 
+        counters.record_response(status);
+
+        // Only the handler counts towards the threshold, since a slow client can delay the write
+        if let (Some(threshold), Ok(request)) = (slow_request_threshold, &request) {
+            let timing = timer.written();
+
+            if timing.handler > threshold {
+                monitor.send_with(EventType::SlowRequest, |event| {
+                    event
+                        .with_peer(addr)
+                        .with_request(RequestDetails {
+                            method: request.original_method().clone(),
+                            uri: request.uri.clone(),
+                            route,
+                            status,
+                            timing,
+                        })
+                        .with_trace(request.trace.as_ref())
+                });
+            }
+        }
+
         let status_str: &str = status.into();
 
         match status {
-            StatusCode::OK => monitor.send(
-                Event::new(EventType::RequestServedSuccess)
-                    .with_peer(addr)
-                    .with_info(format!("200 OK {}", request.unwrap().uri)),
-            ),
-            StatusCode::RequestTimeout => monitor.send(
-                Event::new(EventType::RequestTimeout)
+            StatusCode::OK => monitor.send_with(EventType::RequestServedSuccess, |event| {
+                let request = request.unwrap();
+
+                event
                     .with_peer(addr)
-                    .with_info("408 Request Timeout"),
-            ),
+                    .with_info(format!("200 OK {}", request.uri))
+                    .with_trace(request.trace.as_ref())
+            }),
+            StatusCode::RequestTimeout => monitor.send_with(EventType::RequestTimeout, |event| {
+                event.with_peer(addr).with_info("408 Request Timeout")
+            }),
             e => {
                 if let Ok(request) = request {
-                    monitor.send(
-                        Event::new(EventType::RequestServedError)
+                    monitor.send_with(EventType::RequestServedError, |event| {
+                        event
                             .with_peer(addr)
-                            .with_info(format!("{} {} {}", u16::from(e), status_str, request.uri)),
-                    )
+                            .with_info(format!("{} {} {}", u16::from(e), status_str, request.uri))
+                            .with_trace(request.trace.as_ref())
+                    })
                 } else {
-                    monitor.send(
-                        Event::new(EventType::RequestServedError)
+                    monitor.send_with(EventType::RequestServedError, |event| {
+                        event
                             .with_peer(addr)
-                            .with_info(format!("{} {}", u16::from(e), status_str)),
-                    )
+                            .with_info(format!("{} {}", u16::from(e), status_str))
+                    })
                 }
             }
         }
 
-        // If the request specified to keep the connection open, respect this
-        if !keep_alive {
+        // If the handler asked for the connection, hand it over and forget about it
+        if let Some(hijack) = hijack {
+            monitor.send_with(EventType::ConnectionHijacked, |event| event.with_peer(addr));
+            hijack.run(stream).await;
+
+            return;
+        }
+
+        // If the request specified to keep the connection open, respect this, unless the end of the
+        //   body was marked by closing the connection
+        if !keep_alive || close_delimited {
             break;
         }
 
-        monitor.send(Event::new(EventType::KeepAliveRespected).with_peer(addr));
+        monitor.send_with(EventType::KeepAliveRespected, |event| event.with_peer(addr));
+    }
+
+    monitor.send_with(EventType::ConnectionClosed, |event| event.with_peer(addr));
+}
+
+impl WebsocketLimits {
+    /// Attempts to count a WebSocket connection from the given address, returning a description of
+    ///   the limit which was reached if it cannot.
+    fn acquire(&self, ip: IpAddr) -> Result<WebsocketPermit, String> {
+        let limit = self.global.unwrap_or(usize::MAX);
+        let limit_per_ip = self.per_ip.unwrap_or(usize::MAX);
+
+        // The lock is held until the connection is counted, so that concurrent upgrades from the same
+        //   address cannot both take its last place
+        let mut clients = self.connections.clients.lock().unwrap();
+        let from_ip = clients.get(&ip).copied().unwrap_or(0);
+
+        if from_ip >= limit_per_ip {
+            return Err(format!(
+                "{}/{} WebSocket connections from {}",
+                from_ip, limit_per_ip, ip
+            ));
+        }
+
+        self.connections
+            .total
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                (count < limit).then_some(count + 1)
+            })
+            .map_err(|_| self.describe())?;
+
+        clients.insert(ip, from_ip + 1);
+
+        Ok(WebsocketPermit {
+            connections: self.connections.clone(),
+            ip,
+        })
+    }
+
+    /// Describes the number of WebSocket connections currently open.
+    fn describe(&self) -> String {
+        let count = self.connections.total();
+
+        match self.global {
+            Some(limit) => format!("{}/{} WebSocket connections open", count, limit),
+            None => format!("{} WebSocket connections open", count),
+        }
+    }
+}
+
+impl Drop for WebsocketPermit {
+    fn drop(&mut self) {
+        self.connections.total.fetch_sub(1, Ordering::SeqCst);
+
+        // This runs while unwinding from a panicking handler, so it must not panic itself
+        let mut clients = match self.connections.clients.lock() {
+            Ok(clients) => clients,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        if let Some(count) = clients.get_mut(&self.ip) {
+            *count -= 1;
+
+            if *count == 0 {
+                clients.remove(&self.ip);
+            }
+        }
+    }
+}
+
+impl WebsocketConnections {
+    /// Returns the total number of WebSocket connections currently open.
+    pub fn total(&self) -> usize {
+        self.total.load(Ordering::SeqCst)
     }
 
-    monitor.send(Event::new(EventType::ConnectionClosed).with_peer(addr));
+    /// Returns the number of WebSocket connections currently open from the given IP address.
+    pub fn from_ip(&self, ip: IpAddr) -> usize {
+        self.clients.lock().unwrap().get(&ip).copied().unwrap_or(0)
+    }
+
+    /// Returns the number of IP addresses with at least one WebSocket connection currently open.
+    pub fn clients(&self) -> usize {
+        self.clients.lock().unwrap().len()
+    }
 }
 
 /// Gets the correct handler for the given request.
@@ -661,100 +2025,130 @@ pub(crate) fn get_handler<'a, State>(
     subapps: &'a [SubApp<State>],
     default_subapp: &'a SubApp<State>,
 ) -> Option<&'a RouteHandler<State>> {
-    // Iterate over the sub-apps and find the one which matches the host
-    if let Some(host) = request.headers.get(&HeaderType::Host) {
-        if let Some(subapp) = subapps
-            .iter()
-            .find(|subapp| wildcard_match(&subapp.host, host))
-        {
-            // If the sub-app has a handler for this route, call it
-            if let Some(handler) = subapp
-                .routes // Get the routes of the sub-app
-                .iter() // Iterate over the routes
-                .find(|route| route.route.route_matches(&request.uri))
-            // Find the route that matches
-            {
-                return Some(handler);
-            }
-        }
-    }
-
-    // If no sub-app was found, try to use the handler on the default sub-app
-    if let Some(handler) = default_subapp
-        .routes
-        .iter()
-        .find(|route| route.route.route_matches(&request.uri))
-    {
-        return Some(handler);
-    }
+    let host = request.headers.get(&HeaderType::Host);
 
-    None
+    find_route(subapps, default_subapp, &request.method, host, &request.uri)
+        .map(|(subapp, index)| &subapp.routes[index])
 }
 
-/// Calls the correct WebSocket handler for the given request.
-async fn call_websocket_handler<State>(
+/// Gets the `Allow` header for the path of the given request, if every route for the path only
+///   handles specific methods.
+pub(crate) fn get_allow_header<State>(
     request: &Request,
     subapps: &[SubApp<State>],
     default_subapp: &SubApp<State>,
-    state: Arc<State>,
-    stream: Stream,
-) {
-    // Iterate over the sub-apps and find the one which matches the host
-    if let Some(host) = request.headers.get(&HeaderType::Host) {
-        if let Some(subapp) = subapps
-            .iter()
-            .find(|subapp| wildcard_match(&subapp.host, host))
-        {
-            // If the sub-app has a handler for this route, call it
-            if let Some(handler) = subapp
-                .websocket_routes // Get the WebSocket routes of the sub-app
-                .iter() // Iterate over the routes
-                .find(|route| route.route.route_matches(&request.uri))
-            {
-                handler.handler.serve(request.clone(), stream, state).await;
-                return;
-            }
-        }
-    }
+) -> Option<String> {
+    let host = request.headers.get(&HeaderType::Host);
 
-    // If no sub-app was found, try to use the handler on the default sub-app
-    if let Some(handler) = default_subapp
-        .websocket_routes
-        .iter()
-        .find(|route| route.route.route_matches(&request.uri))
-    {
-        handler.handler.serve(request.clone(), stream, state).await
-    }
+    allow_header(subapps, default_subapp, host, &request.uri)
 }
 
+/// Gets the correct streaming handler for the given request.
+pub(crate) fn get_streaming_handler<'a, State>(
+    request: &Request,
+    subapps: &'a [SubApp<State>],
+    default_subapp: &'a SubApp<State>,
+) -> Option<&'a StreamingRouteHandler<State>> {
+    let host = request.headers.get(&HeaderType::Host);
+
+    find_streaming_route(subapps, default_subapp, host, &request.uri)
+}
+
+/// Gets the correct WebSocket handler for the given request.
+fn get_websocket_handler<'a, State>(
+    request: &Request,
+    subapps: &'a [SubApp<State>],
+    default_subapp: &'a SubApp<State>,
+) -> Option<&'a WebsocketRouteHandler<State>> {
+    // Use the sub-app which matches the host if it has a handler for this route, otherwise try to
+    //   use the handler on the default sub-app
+    request
+        .headers
+        .get(&HeaderType::Host)
+        .and_then(|host| find_subapp(subapps, host))
+        .and_then(|subapp| subapp.websocket_route(&request.uri))
+        .or_else(|| default_subapp.websocket_route(&request.uri))
+}
+
+/// Returns the port that insecure requests are redirected to, which is that of the first TLS
+///   listener so that, for example, requests on port 8080 are redirected to port 8443.
 #[cfg(feature = "tls")]
-async fn force_https_thread(monitor: MonitorConfig) -> Result<(), Box<dyn std::error::Error>> {
-    let socket = TcpListener::bind("0.0.0.0:80").await?;
+fn https_port(listeners: &[(TcpListener, EndpointKind)]) -> u16 {
+    listeners
+        .iter()
+        .filter(|(_, kind)| *kind == EndpointKind::Tls)
+        .find_map(|(socket, _)| socket.local_addr().ok())
+        .map_or(443, |addr| addr.port())
+}
 
-    while let Ok((mut stream, addr)) = socket.accept().await {
-        let request = Request::from_stream(&mut stream, addr).await?;
+/// Reads a request from the stream and redirects it to the same URL over HTTPS on the given port.
+///
+/// The address of the client is the address of the peer, unless it was given by a PROXY protocol
+///   header. The request must arrive within `REDIRECT_TIMEOUT`, so that slow clients cannot hold
+///   their connections open indefinitely. Requests which cannot be parsed are answered with an error
+///   instead of being redirected.
+#[cfg(feature = "tls")]
+async fn redirect_to_https(
+    mut stream: TcpStream,
+    proxied_addr: Option<SocketAddr>,
+    https_port: u16,
+    security: &SecurityOptions,
+    monitor: &MonitorConfig,
+) {
+    let Some(addr) = proxied_addr.or_else(|| stream.peer_addr().ok()) else {
+        return;
+    };
 
-        let response = if let Some(host) = request.headers.get(&HeaderType::Host) {
-            Response::empty(StatusCode::MovedPermanently)
-                .with_header(
-                    HeaderType::Location,
-                    format!("https://{}{}", host, request.uri),
-                )
-                .with_header(HeaderType::Connection, "Close")
-        } else {
-            Response::empty(StatusCode::OK)
-                .with_bytes(b"<h1>Please access over HTTPS</h1>")
-                .with_header(HeaderType::ContentLength, "33")
-                .with_header(HeaderType::Connection, "Close")
-        };
+    let read = Request::from_stream_checked(
+        &mut stream,
+        addr,
+        None,
+        None,
+        None,
+        None,
+        &security.request_limits,
+    );
+    let request = tokio::time::timeout(REDIRECT_TIMEOUT, read)
+        .await
+        .unwrap_or(Err(RequestError::Timeout));
+
+    let response = match &request {
+        Ok(request) => redirect_response(request, https_port),
+        Err(e) => match redirect_error(e.clone(), addr, monitor) {
+            Some(response) => response,
+            None => return,
+        },
+    };
 
-        let response_bytes: Vec<u8> = response.into();
-        stream.write_all(&response_bytes).await?;
+    let written = tokio::time::timeout(REDIRECT_TIMEOUT, response.write_to(&mut stream)).await;
 
-        monitor.send(Event::new(EventType::HTTPSRedirect).with_peer(addr));
+    match written {
+        Ok(Ok(())) if request.is_ok() => {
+            monitor.send_with(EventType::HTTPSRedirect, |event| event.with_peer(addr))
+        }
+        Ok(Ok(())) => (),
+        Ok(Err(e)) => monitor.send_with(EventType::RequestServedError, |event| {
+            event.with_peer(addr).with_info(e.to_string())
+        }),
+        Err(_) => monitor.send_with(EventType::RequestTimeout, |event| event.with_peer(addr)),
     }
+}
 
-    Ok(())
+/// Generates the response for a request whose handler panicked, which closes the connection since
+///   the handler may have left it part of the way through reading the body.
+pub(crate) fn panic_response(
+    request: &Request,
+    route: &str,
+    payload: Box<dyn Any + Send>,
+    error_handler: &dyn Fn(ErrorContext) -> Response,
+) -> Response {
+    error_handler(
+        ErrorContext::new(StatusCode::InternalError)
+            .with_request(request)
+            .with_route(route)
+            .with_cause(ErrorCause::panic(payload)),
+    )
+    .with_header(HeaderType::Connection, "Close")
 }
 
 /// The default error handler for every Humphrey app.
@@ -1,3 +1,11 @@
+//! Defines traits for asynchronous handler functions.
+//!
+//! As well as the handlers themselves being `Send + Sync`, the futures they return must be
+//!   `Send + 'static`, since they are spawned onto the tokio runtime. This means an `async`
+//!   block returned from a handler cannot borrow from anything outside of it, so it should
+//!   take ownership of what it needs with `async move`.
+
+use crate::http::body::RequestBody;
 use crate::http::{Request, Response};
 use crate::stream::Stream;
 
@@ -6,6 +14,12 @@ use std::pin::Pin;
 use std::sync::Arc;
 
 /// Represents a function able to handle a WebSocket handshake and consequent data frames.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a valid WebSocket handler",
+    label = "expected a thread-safe `Fn(Request, Stream, Arc<State>) -> impl Future<Output = ()>`",
+    note = "handlers must be `Send + Sync`, so capture shared data with `Arc` instead of `Rc` or references",
+    note = "the returned future must be `Send + 'static`, so use `async move` rather than borrowing from the closure"
+)]
 pub trait WebsocketHandler<State>: Send + Sync {
     #[allow(missing_docs)]
     fn serve(
@@ -30,6 +44,40 @@ where
     }
 }
 
+/// Represents a function able to handle a WebSocket handshake and consequent data frames, which is
+///   also passed the route pattern it was registered with.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a valid path-aware WebSocket handler",
+    label = "expected a thread-safe `Fn(Request, Stream, Arc<State>, &'static str) -> impl Future<Output = ()>`",
+    note = "handlers must be `Send + Sync`, so capture shared data with `Arc` instead of `Rc` or references",
+    note = "the returned future must be `Send + 'static`, so use `async move` rather than borrowing from the closure"
+)]
+pub trait PathAwareWebsocketHandler<State>: Send + Sync {
+    #[allow(missing_docs)]
+    fn serve(
+        &self,
+        request: Request,
+        stream: Stream,
+        state: Arc<State>,
+        route: &'static str,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+}
+impl<F, Fut, State> PathAwareWebsocketHandler<State> for F
+where
+    F: Fn(Request, Stream, Arc<State>, &'static str) -> Fut + Send + Sync,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    fn serve(
+        &self,
+        request: Request,
+        stream: Stream,
+        state: Arc<State>,
+        route: &'static str,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(self(request, stream, state, route))
+    }
+}
+
 /// Represents a function able to handle a request.
 /// It is passed the request as well as the app's state, and must return a response.
 ///
@@ -40,6 +88,12 @@ where
 ///     Response::new(StatusCode::OK, b"Success")
 /// }
 /// ```
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a valid request handler",
+    label = "expected a thread-safe `Fn(Request, Arc<State>) -> impl Future<Output = Response>`",
+    note = "handlers must be `Send + Sync`, so capture shared data with `Arc` instead of `Rc` or references",
+    note = "the returned future must be `Send + 'static`, so use `async move` rather than borrowing from the closure"
+)]
 pub trait RequestHandler<State>: Send + Sync {
     #[allow(missing_docs)]
     fn serve(
@@ -62,6 +116,53 @@ where
     }
 }
 
+/// Represents a function able to handle a request whose body is streamed from the connection
+///   rather than buffered.
+/// It is passed the request, whose `content` is always `None`, a reader for its body, and the app's
+///   state, and must return a response.
+///
+/// ## Example
+/// A handler which saves an upload to disk without holding it in memory would be as follows:
+/// ```
+/// async fn handler(_: Request, mut body: RequestBody, _: Arc<()>) -> Response {
+///     let mut file = File::create("upload.bin").await.unwrap();
+///
+///     match tokio::io::copy(&mut body, &mut file).await {
+///         Ok(_) => Response::empty(StatusCode::Created),
+///         Err(_) => Response::empty(StatusCode::BadRequest),
+///     }
+/// }
+/// ```
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a valid streaming request handler",
+    label = "expected a thread-safe `Fn(Request, RequestBody, Arc<State>) -> impl Future<Output = Response>`",
+    note = "handlers must be `Send + Sync`, so capture shared data with `Arc` instead of `Rc` or references",
+    note = "the returned future must be `Send + 'static`, so use `async move` rather than borrowing from the closure"
+)]
+pub trait StreamingRequestHandler<State>: Send + Sync {
+    #[allow(missing_docs)]
+    fn serve(
+        &self,
+        request: Request,
+        body: RequestBody,
+        state: Arc<State>,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send>>;
+}
+impl<F, Fut, State> StreamingRequestHandler<State> for F
+where
+    F: Fn(Request, RequestBody, Arc<State>) -> Fut + Send + Sync,
+    Fut: Future<Output = Response> + Send + 'static,
+{
+    fn serve(
+        &self,
+        request: Request,
+        body: RequestBody,
+        state: Arc<State>,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        Box::pin(self(request, body, state))
+    }
+}
+
 /// Represents a function able to handle a request.
 /// It is passed only the request, and must return a response.
 /// If you want access to the app's state, consider using the `RequestHandler` trait instead.
@@ -73,6 +174,12 @@ where
 ///     Response::new(StatusCode::OK, b"Success")
 /// }
 /// ```
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a valid stateless request handler",
+    label = "expected a thread-safe `Fn(Request) -> impl Future<Output = Response>`",
+    note = "handlers must be `Send + Sync`, so capture shared data with `Arc` instead of `Rc` or references",
+    note = "the returned future must be `Send + 'static`, so use `async move` rather than borrowing from the closure"
+)]
 pub trait StatelessRequestHandler<State>: Send + Sync {
     #[allow(missing_docs)]
     fn serve(&self, request: Request) -> Pin<Box<dyn Future<Output = Response> + Send>>;
@@ -97,6 +204,12 @@ where
 ///     Response::new(StatusCode::OK, format!("Success matching route {}", route))
 /// }
 /// ```
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a valid path-aware request handler",
+    label = "expected a thread-safe `Fn(Request, Arc<State>, &'static str) -> impl Future<Output = Response>`",
+    note = "handlers must be `Send + Sync`, so capture shared data with `Arc` instead of `Rc` or references",
+    note = "the returned future must be `Send + 'static`, so use `async move` rather than borrowing from the closure"
+)]
 pub trait PathAwareRequestHandler<State>: Send + Sync {
     #[allow(missing_docs)]
     fn serve(
@@ -4,13 +4,15 @@
 // When they are stabilised, this code will look a lot nicer.
 
 use crate::app::{error_handler, PathAwareRequestHandler, RequestHandler};
+use crate::embed::{EmbeddedDir, EmbeddedFile, LONG_CACHE_CONTROL};
+use crate::file::{is_missing, HeldFile};
 use crate::http::headers::HeaderType;
-use crate::http::mime::MimeType;
+use crate::http::url::encode_path;
 use crate::http::{Request, Response, StatusCode};
+use crate::percent::decode_file_path;
 use crate::route::{try_find_path, LocatedPath};
 
-use tokio::fs::File;
-use tokio::io::AsyncReadExt;
+pub use crate::route::DirOptions;
 
 use futures::Future;
 
@@ -21,6 +23,9 @@ use std::sync::Arc;
 const INDEX_FILES: [&str; 2] = ["index.html", "index.htm"];
 
 /// Serve the specified file, or a default error 404 if not found.
+///
+/// The file is opened for every request, so changes to it are served straight away. If it cannot be
+///   read, a default error 500 is served.
 pub fn serve_file<S>(file_path: &'static str) -> impl RequestHandler<S> {
     let path_buf = PathBuf::from(file_path);
 
@@ -36,21 +41,42 @@ impl<S> RequestHandler<S> for FileServer {
         let path_buf = self.path_buf.clone();
 
         Box::pin(async move {
-            if let Ok(mut file) = File::open(&path_buf).await {
-                let mut buf = Vec::new();
-                if file.read_to_end(&mut buf).await.is_ok() {
-                    return if let Some(extension) = &path_buf.extension() {
-                        Response::new(StatusCode::OK, buf).with_header(
-                            HeaderType::ContentType,
-                            MimeType::from_extension(extension.to_str().unwrap()).to_string(),
-                        )
-                    } else {
-                        Response::new(StatusCode::OK, buf)
-                    };
-                }
+            match DirOptions::default().open_file(&path_buf).await {
+                Ok(response) => response,
+                Err(e) if is_missing(&e) => error_handler(StatusCode::NotFound),
+                Err(_) => error_handler(StatusCode::InternalError),
             }
+        })
+    }
+}
+
+/// Serves the given held file, without opening it for every request.
+///
+/// This is intended for very frequently requested files. The file is served as it was when it was
+///   opened until `HeldFile::reopen` is called, for example from a deploy hook, so keep a clone of the
+///   held file to reopen it.
+pub fn serve_held_file<S>(file: HeldFile) -> impl RequestHandler<S> {
+    HeldFileServer { file }
+}
 
-            error_handler(StatusCode::NotFound)
+struct HeldFileServer {
+    file: HeldFile,
+}
+
+impl<S> RequestHandler<S> for HeldFileServer {
+    fn serve(&self, _: Request, _: Arc<S>) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        let file = self.file.clone();
+
+        Box::pin(async move {
+            let read = {
+                let file = file.clone();
+                tokio::task::spawn_blocking(move || file.read()).await
+            };
+
+            match read {
+                Ok(Ok(contents)) => DirOptions::default().file_response(file.path(), contents),
+                _ => error_handler(StatusCode::InternalError),
+            }
         })
     }
 }
@@ -78,26 +104,19 @@ impl<S> RequestHandler<S> for FilePathServer {
         let directory_path = self.directory_path.clone();
 
         Box::pin(async move {
-            let file_path = request.uri.strip_prefix('/').unwrap_or(&request.uri);
+            let file_path = match decode_file_path(&request.uri) {
+                Some(file_path) => file_path,
+                None => return error_handler(StatusCode::NotFound),
+            };
+            let file_path = file_path.strip_prefix('/').unwrap_or(&file_path);
             let path = format!("{}/{}", directory_path.to_str().unwrap(), file_path);
 
             let path_buf = PathBuf::from(path);
 
-            if let Ok(mut file) = File::open(&path_buf).await {
-                let mut buf = Vec::new();
-                if file.read_to_end(&mut buf).await.is_ok() {
-                    return if let Some(extension) = path_buf.extension() {
-                        Response::new(StatusCode::OK, buf).with_header(
-                            HeaderType::ContentType,
-                            MimeType::from_extension(extension.to_str().unwrap()).to_string(),
-                        )
-                    } else {
-                        Response::new(StatusCode::OK, buf)
-                    };
-                }
-            }
-
-            error_handler(StatusCode::NotFound)
+            DirOptions::default()
+                .open_file(&path_buf)
+                .await
+                .unwrap_or_else(|_| error_handler(StatusCode::NotFound))
         })
     }
 }
@@ -108,11 +127,28 @@ impl<S> RequestHandler<S> for FilePathServer {
 ///   - requests to `/directory` will return either the file `directory`, 301 redirect to `/directory/` if it is a directory, or return 404
 ///   - requests to `/directory/` will return either the file `/directory/index.html` or `/directory/index.htm`, or return 404
 pub fn serve_dir<S>(directory_path: &'static str) -> impl PathAwareRequestHandler<S> {
-    DirServer { directory_path }
+    serve_dir_with_options(directory_path, DirOptions::default())
+}
+
+/// Serves a directory of files in the same way as `serve_dir`, but with the given options.
+///
+/// ## Example
+/// ```
+/// serve_dir_with_options("./static", DirOptions::new().with_strip_bom(true))
+/// ```
+pub fn serve_dir_with_options<S>(
+    directory_path: &'static str,
+    options: DirOptions,
+) -> impl PathAwareRequestHandler<S> {
+    DirServer {
+        directory_path,
+        options,
+    }
 }
 
 struct DirServer {
     directory_path: &'static str,
+    options: DirOptions,
 }
 
 impl<S> PathAwareRequestHandler<S> for DirServer {
@@ -123,6 +159,7 @@ impl<S> PathAwareRequestHandler<S> for DirServer {
         route: &'static str,
     ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
         let directory_path = self.directory_path;
+        let options = self.options;
 
         Box::pin(async move {
             let route_without_wildcard = route.strip_suffix('*').unwrap_or(route);
@@ -136,25 +173,14 @@ impl<S> PathAwareRequestHandler<S> for DirServer {
             if let Some(located) = located {
                 match located {
                     LocatedPath::Directory => Response::empty(StatusCode::MovedPermanently)
-                        .with_header(HeaderType::Location, format!("{}/", &request.uri)),
-                    LocatedPath::File(path) => {
-                        if let Ok(mut file) = File::open(&path).await {
-                            let mut buf = Vec::new();
-                            if file.read_to_end(&mut buf).await.is_ok() {
-                                return if let Some(extension) = path.extension() {
-                                    Response::new(StatusCode::OK, buf).with_header(
-                                        HeaderType::ContentType,
-                                        MimeType::from_extension(extension.to_str().unwrap())
-                                            .to_string(),
-                                    )
-                                } else {
-                                    Response::new(StatusCode::OK, buf)
-                                };
-                            }
-                        }
-
-                        error_handler(StatusCode::InternalError)
-                    }
+                        .with_header(
+                            HeaderType::Location,
+                            format!("{}/", encode_path(&request.uri)),
+                        ),
+                    LocatedPath::File(path) => options
+                        .open_file(&path)
+                        .await
+                        .unwrap_or_else(|_| error_handler(StatusCode::InternalError)),
                 }
             } else {
                 error_handler(StatusCode::NotFound)
@@ -176,6 +202,72 @@ impl<S> RequestHandler<S> for RedirectServer {
     fn serve(&self, _: Request, _: Arc<S>) -> Pin<Box<dyn Future<Output = Response> + Send>> {
         let location = self.location;
 
-        Box::pin(async move { Response::redirect(location) })
+        Box::pin(async move { Response::permanent_redirect(location) })
+    }
+}
+
+/// Serves files which have been embedded into the executable with the `embed!` macro.
+///
+/// The request URI is resolved relative to the route, so this should be used with a wildcard route
+///   such as `/static/*`. Index files and directory redirects behave in the same way as `serve_dir`.
+pub fn serve_embedded<S>(dir: EmbeddedDir) -> impl PathAwareRequestHandler<S> {
+    EmbeddedDirServer { dir: Arc::new(dir) }
+}
+
+struct EmbeddedDirServer {
+    dir: Arc<EmbeddedDir>,
+}
+
+impl<S> PathAwareRequestHandler<S> for EmbeddedDirServer {
+    fn serve(
+        &self,
+        request: Request,
+        _: Arc<S>,
+        route: &'static str,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        let dir = self.dir.clone();
+
+        Box::pin(async move {
+            let route_without_wildcard = route.strip_suffix('*').unwrap_or(route);
+            let uri_without_route = request
+                .uri
+                .strip_prefix(route_without_wildcard)
+                .unwrap_or(&request.uri);
+
+            dir.serve(&request, uri_without_route)
+                .unwrap_or_else(|| error_handler(StatusCode::NotFound))
+        })
+    }
+}
+
+/// Serves the given bytes as a favicon, allowing clients to cache it for a week.
+///
+/// The image type is detected from the contents, so PNG, GIF and SVG favicons are supported as well
+///   as icon files. This is intended to be used with `include_bytes!`.
+pub fn favicon<S>(bytes: &'static [u8]) -> impl RequestHandler<S> {
+    EmbeddedFileServer {
+        file: Arc::new(EmbeddedFile::favicon(bytes)),
+    }
+}
+
+/// Serves the given rules as `robots.txt`, allowing clients to cache them for a week.
+pub fn robots_txt<S>(rules: &'static str) -> impl RequestHandler<S> {
+    EmbeddedFileServer {
+        file: Arc::new(EmbeddedFile::new("robots.txt", rules.as_bytes())),
+    }
+}
+
+struct EmbeddedFileServer {
+    file: Arc<EmbeddedFile>,
+}
+
+impl<S> RequestHandler<S> for EmbeddedFileServer {
+    fn serve(&self, request: Request, _: Arc<S>) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        let file = self.file.clone();
+
+        Box::pin(async move {
+            file.serve(&request)
+                .with_header(HeaderType::CacheControl, LONG_CACHE_CONTROL)
+        })
     }
 }
@@ -2,3 +2,7 @@ pub mod app;
 pub mod handler_traits;
 pub mod handlers;
 pub mod stream;
+pub mod test_client;
+
+#[cfg(feature = "tower-compat")]
+pub mod tower;
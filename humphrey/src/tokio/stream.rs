@@ -78,6 +78,35 @@ impl Stream {
         }
     }
 
+    /// Returns the local socket address of this connection, which identifies the listener it was
+    ///   accepted by.
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        match self {
+            Stream::Tcp(stream) => stream.local_addr(),
+            #[cfg(feature = "tls")]
+            Stream::Tls(stream) => stream.get_ref().0.local_addr(),
+        }
+    }
+
+    /// Returns whether this connection is secured with TLS.
+    pub fn is_secure(&self) -> bool {
+        match self {
+            Stream::Tcp(_) => false,
+            #[cfg(feature = "tls")]
+            Stream::Tls(_) => true,
+        }
+    }
+
+    /// Creates another handle to the underlying TCP socket, so that the connection can be watched
+    ///   while the stream is in use elsewhere.
+    pub(crate) fn try_clone_socket(&self) -> std::io::Result<TcpStream> {
+        match self {
+            Stream::Tcp(stream) => clone_socket(stream),
+            #[cfg(feature = "tls")]
+            Stream::Tls(stream) => clone_socket(stream.get_ref().0),
+        }
+    }
+
     /// Shuts down both the read and write halves of this connection.
     pub async fn shutdown(&mut self) -> std::io::Result<()> {
         match self {
@@ -87,3 +116,13 @@ impl Stream {
         }
     }
 }
+
+/// Duplicates the handle to the given socket.
+fn clone_socket(socket: &TcpStream) -> std::io::Result<TcpStream> {
+    #[cfg(unix)]
+    let owned = std::os::fd::AsFd::as_fd(socket).try_clone_to_owned()?;
+    #[cfg(windows)]
+    let owned = std::os::windows::io::AsSocket::as_socket(socket).try_clone_to_owned()?;
+
+    TcpStream::from_std(std::net::TcpStream::from(owned))
+}
@@ -0,0 +1,411 @@
+//! Provides interoperability with the `tower` and `http` ecosystem.
+//!
+//! An app can be converted into a `tower::Service` with `App::into_service`, which allows it to be
+//!   mounted inside an existing hyper server and wrapped in tower middleware. Requests and responses
+//!   are converted between Humphrey's types and the `http` crate's types in both directions.
+//!
+//! WebSocket routes and hijacked responses are not supported through the service, since they need
+//!   direct access to the connection.
+
+use crate::cancellation::CancellationToken;
+use crate::http::address::Address;
+use crate::http::headers::{HeaderType, Headers};
+use crate::http::method::Method;
+use crate::http::request::{BodyState, RequestError};
+use crate::http::status::StatusCode;
+use crate::http::url::encode_path;
+use crate::http::{Request, Response};
+use crate::percent::decode_path;
+use crate::route::{
+    build_indexes, handle_error, handler_request, ErrorCause, ErrorContext, RouteParams, SubApp,
+};
+use crate::tokio::app::{get_allow_header, get_handler, ErrorHandler};
+
+use bytes::Bytes;
+use tower_service::Service;
+
+use std::convert::Infallible;
+use std::error::Error;
+use std::fmt::Display;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// Represents a Humphrey app's routing as a `tower::Service`.
+///
+/// Requests are routed exactly as they would be by `App::run`, including sub-apps, CORS and the
+///   error handler, but connection management is left to the server which drives the service.
+pub struct AppService<State> {
+    subapps: Arc<Vec<SubApp<State>>>,
+    default_subapp: Arc<SubApp<State>>,
+    error_handler: ErrorHandler<State>,
+    state: Arc<State>,
+}
+
+/// The trailers of a response, stored in the extensions of the `http` response since it has no
+///   other way of representing them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Trailers(pub Headers);
+
+/// An error which occurred while converting between Humphrey's types and the `http` crate's types.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ConversionError {
+    /// The method is not supported by Humphrey.
+    Method,
+    /// The HTTP version is not supported.
+    Version,
+    /// The status code is not supported by Humphrey.
+    Status,
+    /// A header name or value could not be represented.
+    Header,
+    /// The URI could not be represented.
+    Uri,
+}
+
+impl<State> AppService<State>
+where
+    State: Send + Sync + 'static,
+{
+    /// Creates a new service from the routing of an app.
+    pub(crate) fn new(
+        mut subapps: Vec<SubApp<State>>,
+        mut default_subapp: SubApp<State>,
+        error_handler: ErrorHandler<State>,
+        state: Arc<State>,
+    ) -> Self {
+        build_indexes(&mut subapps, &mut default_subapp);
+
+        Self {
+            subapps: Arc::new(subapps),
+            default_subapp: Arc::new(default_subapp),
+            error_handler,
+            state,
+        }
+    }
+
+    /// Routes the request to the appropriate handler and generates the response.
+    async fn handle(self, request: http::Request<Bytes>) -> http::Response<Bytes> {
+        let request = match Request::try_from(request) {
+            Ok(request) => request,
+            Err(ConversionError::Method) => {
+                return into_http_response(
+                    self.error(ErrorContext::new(StatusCode::NotImplemented)),
+                )
+            }
+            Err(ConversionError::Version) => {
+                return into_http_response(
+                    self.error(ErrorContext::new(StatusCode::VersionNotSupported)),
+                )
+            }
+            Err(_) => {
+                let context = ErrorContext::new(StatusCode::BadRequest)
+                    .with_cause(ErrorCause::Request(RequestError::Request));
+
+                return into_http_response(self.error(context));
+            }
+        };
+
+        let handler = get_handler(&request, &self.subapps, &self.default_subapp);
+
+        let mut response = match handler {
+            Some(handler) if request.method == Method::Options => {
+                let mut response = Response::empty(StatusCode::NoContent);
+                handler.cors.set_headers(&mut response.headers);
+
+                if let Some(allow) = get_allow_header(&request, &self.subapps, &self.default_subapp)
+                {
+                    response.headers.add(HeaderType::Allow, allow);
+                }
+
+                response
+            }
+            Some(handler) => {
+                let mut response = handler
+                    .handler
+                    .serve(
+                        handler_request(&request, &handler.route, handler.declares(&Method::Head)),
+                        self.state.clone(),
+                    )
+                    .await;
+                handler.cors.set_headers(&mut response.headers);
+
+                response
+            }
+            None if request.method.requires_declared_route() => {
+                self.error(ErrorContext::new(StatusCode::NotImplemented).with_request(&request))
+            }
+            None => match get_allow_header(&request, &self.subapps, &self.default_subapp) {
+                Some(allow) => self
+                    .error(ErrorContext::new(StatusCode::MethodNotAllowed).with_request(&request))
+                    .with_header(HeaderType::Allow, allow),
+                None => self.error(ErrorContext::new(StatusCode::NotFound).with_request(&request)),
+            },
+        };
+
+        response.version = request.version.clone();
+
+        // The body of the `http` response is a single buffer, so a streamed body is read in full
+        if response.read_streamed_body().await.is_err() {
+            let context = ErrorContext::new(StatusCode::InternalError).with_request(&request);
+
+            return into_http_response(self.error(context));
+        }
+
+        into_http_response(response)
+    }
+
+    /// Generates the response for an error with the error handler for the request's host.
+    fn error(&self, context: ErrorContext) -> Response {
+        handle_error(&context, &self.subapps, &self.error_handler, &self.state)
+    }
+}
+
+impl<State> Clone for AppService<State> {
+    fn clone(&self) -> Self {
+        Self {
+            subapps: self.subapps.clone(),
+            default_subapp: self.default_subapp.clone(),
+            error_handler: self.error_handler.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<State> Service<http::Request<Bytes>> for AppService<State>
+where
+    State: Send + Sync + 'static,
+{
+    type Response = http::Response<Bytes>;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<Bytes>) -> Self::Future {
+        let service = self.clone();
+
+        Box::pin(async move { Ok(service.handle(request).await) })
+    }
+}
+
+impl TryFrom<http::Request<Bytes>> for Request {
+    type Error = ConversionError;
+
+    /// Converts an `http` request into a Humphrey request.
+    ///
+    /// The address is taken from an `Address` or `SocketAddr` in the request's extensions if present,
+    ///   otherwise the unspecified address is used. The request is considered secure if its URI has
+    ///   the `https` scheme.
+    fn try_from(request: http::Request<Bytes>) -> Result<Self, Self::Error> {
+        let (parts, body) = request.into_parts();
+
+        let method =
+            Method::from_name(parts.method.as_str()).map_err(|_| ConversionError::Method)?;
+        let version = version_to_string(parts.version)?.to_string();
+        let headers = headers_from_http(&parts.headers)?;
+
+        let (uri_raw, query) = match parts.uri.path_and_query() {
+            Some(path_and_query) => (
+                path_and_query.path().to_string(),
+                path_and_query.query().unwrap_or("").to_string(),
+            ),
+            None => ("/".to_string(), String::new()),
+        };
+        let uri = decode_path(&uri_raw).ok_or(ConversionError::Uri)?;
+
+        let address = match parts.extensions.get::<Address>() {
+            Some(address) => address.clone(),
+            None => {
+                let socket_addr = parts
+                    .extensions
+                    .get::<SocketAddr>()
+                    .copied()
+                    .unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], 0)));
+
+                Address::from_headers(&headers, socket_addr).map_err(|_| ConversionError::Header)?
+            }
+        };
+
+        // Humphrey only has content when the length was declared, matching its own parser
+        let content = (!body.is_empty() || headers.get(HeaderType::ContentLength).is_some())
+            .then(|| body.to_vec());
+
+        Ok(Self {
+            method,
+            overridden_method: None,
+            uri,
+            uri_raw,
+            query,
+            version,
+            headers,
+            content,
+            body_state: BodyState::Complete,
+            trailers: Headers::new(),
+            address,
+            secure: parts.uri.scheme_str() == Some("https"),
+            local_addr: None,
+            deadline: None,
+            trace: None,
+            cancellation: CancellationToken::default(),
+            params: RouteParams::default(),
+        })
+    }
+}
+
+impl TryFrom<Request> for http::Request<Bytes> {
+    type Error = ConversionError;
+
+    /// Converts a Humphrey request into an `http` request.
+    ///
+    /// The request's address is stored in its extensions.
+    fn try_from(request: Request) -> Result<Self, Self::Error> {
+        let path = encode_path(&request.uri);
+        let uri = match request.query.is_empty() {
+            true => path,
+            false => format!("{}?{}", path, request.query),
+        };
+
+        let mut builder = http::Request::builder()
+            .method(request.method.to_string().as_str())
+            .uri(uri)
+            .version(version_from_string(&request.version)?)
+            .extension(request.address);
+
+        *builder.headers_mut().ok_or(ConversionError::Uri)? = headers_to_http(&request.headers)?;
+
+        builder
+            .body(Bytes::from(request.content.unwrap_or_default()))
+            .map_err(|_| ConversionError::Uri)
+    }
+}
+
+impl TryFrom<http::Response<Bytes>> for Response {
+    type Error = ConversionError;
+
+    /// Converts an `http` response into a Humphrey response.
+    ///
+    /// Trailers are taken from the `Trailers` extension if present.
+    fn try_from(response: http::Response<Bytes>) -> Result<Self, Self::Error> {
+        let (parts, body) = response.into_parts();
+
+        let mut converted = Response::empty(
+            StatusCode::try_from(parts.status.as_u16()).map_err(|_| ConversionError::Status)?,
+        )
+        .with_bytes(body);
+
+        converted.version = version_to_string(parts.version)?.to_string();
+        converted.headers = headers_from_http(&parts.headers)?;
+
+        if let Some(trailers) = parts.extensions.get::<Trailers>() {
+            converted.trailers = trailers.0.clone();
+        }
+
+        Ok(converted)
+    }
+}
+
+impl TryFrom<Response> for http::Response<Bytes> {
+    type Error = ConversionError;
+
+    /// Converts a Humphrey response into an `http` response.
+    ///
+    /// Trailers are stored in the `Trailers` extension, and any hijack function is discarded.
+    fn try_from(response: Response) -> Result<Self, Self::Error> {
+        let mut builder = http::Response::builder()
+            .status(u16::from(response.status_code))
+            .version(version_from_string(&response.version)?);
+
+        if !response.trailers.is_empty() {
+            builder = builder.extension(Trailers(response.trailers));
+        }
+
+        *builder.headers_mut().ok_or(ConversionError::Status)? =
+            headers_to_http(&response.headers)?;
+
+        builder
+            .body(Bytes::from(response.body))
+            .map_err(|_| ConversionError::Status)
+    }
+}
+
+impl Display for ConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            ConversionError::Method => "the method is not supported",
+            ConversionError::Version => "the HTTP version is not supported",
+            ConversionError::Status => "the status code is not supported",
+            ConversionError::Header => "a header could not be represented",
+            ConversionError::Uri => "the URI could not be represented",
+        };
+
+        write!(f, "Conversion error: {}", reason)
+    }
+}
+
+impl Error for ConversionError {}
+
+/// Converts a response generated by a handler, falling back to an empty 500 response if it cannot be
+///   represented.
+fn into_http_response(response: Response) -> http::Response<Bytes> {
+    http::Response::try_from(response).unwrap_or_else(|_| {
+        let mut response = http::Response::new(Bytes::new());
+        *response.status_mut() = http::StatusCode::INTERNAL_SERVER_ERROR;
+        response
+    })
+}
+
+/// Converts Humphrey headers into an `http` header map, keeping every value of repeated headers.
+fn headers_to_http(headers: &Headers) -> Result<http::HeaderMap, ConversionError> {
+    let mut map = http::HeaderMap::with_capacity(headers.len());
+
+    for header in headers.iter() {
+        let name = http::HeaderName::from_bytes(header.name.to_string().as_bytes())
+            .map_err(|_| ConversionError::Header)?;
+        let value = http::HeaderValue::from_bytes(header.value.as_bytes())
+            .map_err(|_| ConversionError::Header)?;
+
+        map.append(name, value);
+    }
+
+    Ok(map)
+}
+
+/// Converts an `http` header map into Humphrey headers.
+fn headers_from_http(map: &http::HeaderMap) -> Result<Headers, ConversionError> {
+    let mut headers = Headers::new();
+
+    for (name, value) in map {
+        let value = std::str::from_utf8(value.as_bytes()).map_err(|_| ConversionError::Header)?;
+        headers.add(HeaderType::from(name.as_str()), value);
+    }
+
+    Ok(headers)
+}
+
+/// Converts an `http` version into the string used by Humphrey.
+fn version_to_string(version: http::Version) -> Result<&'static str, ConversionError> {
+    match version {
+        http::Version::HTTP_09 => Ok("HTTP/0.9"),
+        http::Version::HTTP_10 => Ok("HTTP/1.0"),
+        http::Version::HTTP_11 => Ok("HTTP/1.1"),
+        http::Version::HTTP_2 => Ok("HTTP/2.0"),
+        http::Version::HTTP_3 => Ok("HTTP/3.0"),
+        _ => Err(ConversionError::Version),
+    }
+}
+
+/// Converts the version string used by Humphrey into an `http` version.
+fn version_from_string(version: &str) -> Result<http::Version, ConversionError> {
+    match version {
+        "HTTP/0.9" => Ok(http::Version::HTTP_09),
+        "HTTP/1.0" => Ok(http::Version::HTTP_10),
+        "HTTP/1.1" => Ok(http::Version::HTTP_11),
+        "HTTP/2" | "HTTP/2.0" => Ok(http::Version::HTTP_2),
+        "HTTP/3" | "HTTP/3.0" => Ok(http::Version::HTTP_3),
+        _ => Err(ConversionError::Version),
+    }
+}
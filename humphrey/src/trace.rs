@@ -0,0 +1,382 @@
+//! Provides propagation of trace context between services, following the
+//!   [W3C Trace Context](https://www.w3.org/TR/trace-context/) specification.
+//!
+//! When trace propagation is enabled with `App::with_trace_propagation`, every request is given a
+//!   `TraceContext` in `request.trace`. Requests with a valid `traceparent` header continue the
+//!   caller's trace, and other requests start a new one. Either way, the handling of the request is
+//!   given a new span ID, which is included in monitoring events and recordings so that they can be
+//!   matched up with the traces of other services.
+//!
+//! Handlers which call other services pass the context on with `ClientRequest::with_trace_context`,
+//!   which makes their span the parent of the span of the service being called.
+//!
+//! This module only propagates identifiers. Spans are not timed or exported, which is left to a
+//!   tracing library if one is needed.
+
+use std::fmt::Display;
+
+#[cfg(feature = "runtime")]
+use crate::http::headers::Headers;
+#[cfg(feature = "runtime")]
+use std::collections::hash_map::RandomState;
+#[cfg(feature = "runtime")]
+use std::hash::{BuildHasher, Hasher};
+#[cfg(feature = "runtime")]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "runtime")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The header which identifies the trace and the span of the caller.
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// The header which carries vendor-specific trace information alongside the `traceparent` header.
+pub const TRACESTATE_HEADER: &str = "tracestate";
+
+/// The flag of the `traceparent` header which indicates that the caller may have recorded the trace.
+pub const SAMPLED_FLAG: u8 = 0x01;
+
+/// The maximum number of members of the `tracestate` header.
+///
+/// Headers with more members are discarded rather than truncated, since it is not known which
+///   members the vendors who added them consider the most important.
+pub const MAX_TRACESTATE_MEMBERS: usize = 32;
+
+/// The length of a version 00 `traceparent` header, which is also the shortest valid length for any
+///   version.
+const TRACEPARENT_LENGTH: usize = 55;
+
+/// Counts the IDs which have been generated, so that IDs generated at the same instant still differ.
+#[cfg(feature = "runtime")]
+static GENERATED_IDS: AtomicU64 = AtomicU64::new(0);
+
+/// Identifies a trace, which is the same for every service handling a request.
+///
+/// A valid trace ID is never all zeroes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TraceId(pub [u8; 16]);
+
+/// Identifies a span, which is a single operation within a trace such as the handling of a request.
+///
+/// A valid span ID is never all zeroes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SpanId(pub [u8; 8]);
+
+/// Represents the fields of a `traceparent` header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceParent {
+    /// The version of the header format.
+    pub version: u8,
+    /// The ID of the trace.
+    pub trace_id: TraceId,
+    /// The ID of the span of the sender, which is the parent of the span of the receiver.
+    pub parent_id: SpanId,
+    /// The trace flags, of which only `SAMPLED_FLAG` is currently defined.
+    pub flags: u8,
+}
+
+/// Represents the trace context of a request, including the span of its handling by this app.
+///
+/// ## Example
+/// ```
+/// fn handler(request: Request, _: Arc<()>) -> Response {
+///     let mut client = Client::new();
+///     let mut upstream = client.get("http://127.0.0.1/api").unwrap();
+///
+///     if let Some(trace) = &request.trace {
+///         upstream = upstream.with_trace_context(trace);
+///     }
+///
+///     upstream.send().unwrap()
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceContext {
+    /// The ID of the trace.
+    pub trace_id: TraceId,
+    /// The ID of the span of the caller, or `None` if the trace was started by this app.
+    pub parent_id: Option<SpanId>,
+    /// The ID of the span of the handling of the request, which is new for every request.
+    pub span_id: SpanId,
+    /// Whether the trace is sampled, which is inherited from the caller or decided by the app's
+    ///   `TraceOptions` for new traces.
+    pub sampled: bool,
+    /// The `tracestate` header of the request, which is passed on unchanged apart from the removal of
+    ///   whitespace, or `None` if there was none or it was invalid.
+    pub state: Option<String>,
+}
+
+/// Represents the options for trace propagation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TraceOptions {
+    /// Whether traces started by the app, for requests without a valid `traceparent` header, are
+    ///   sampled.
+    pub sample_new_traces: bool,
+}
+
+impl TraceId {
+    /// Generates a new random trace ID.
+    ///
+    /// The ID is unpredictable enough to avoid collisions between services, but it is not
+    ///   cryptographically secure, so it should not be used as a secret.
+    #[cfg(feature = "runtime")]
+    pub fn generate() -> Self {
+        loop {
+            let mut id = [0; 16];
+            id[..8].copy_from_slice(&random_u64().to_be_bytes());
+            id[8..].copy_from_slice(&random_u64().to_be_bytes());
+
+            let id = Self(id);
+            if id.is_valid() {
+                return id;
+            }
+        }
+    }
+
+    /// Returns whether the ID is valid, which is the case unless it is all zeroes.
+    pub fn is_valid(&self) -> bool {
+        self.0 != [0; 16]
+    }
+}
+
+impl SpanId {
+    /// Generates a new random span ID.
+    ///
+    /// The ID is unpredictable enough to avoid collisions between services, but it is not
+    ///   cryptographically secure, so it should not be used as a secret.
+    #[cfg(feature = "runtime")]
+    pub fn generate() -> Self {
+        loop {
+            let id = Self(random_u64().to_be_bytes());
+            if id.is_valid() {
+                return id;
+            }
+        }
+    }
+
+    /// Returns whether the ID is valid, which is the case unless it is all zeroes.
+    pub fn is_valid(&self) -> bool {
+        self.0 != [0; 8]
+    }
+}
+
+impl TraceParent {
+    /// Parses a `traceparent` header, returning `None` if it is invalid.
+    ///
+    /// Headers of versions later than 00 are parsed as far as the fields of version 00, and may have
+    ///   further fields after a dash. Version `ff`, uppercase hexadecimal and IDs of all zeroes are
+    ///   invalid.
+    pub fn parse(header: &str) -> Option<Self> {
+        let header = header.trim_matches([' ', '\t']).as_bytes();
+
+        if header.len() < TRACEPARENT_LENGTH {
+            return None;
+        }
+
+        let version = parse_hex::<1>(&header[0..2])?[0];
+
+        match version {
+            0xff => return None,
+            0x00 if header.len() != TRACEPARENT_LENGTH => return None,
+            _ if header.len() > TRACEPARENT_LENGTH && header[TRACEPARENT_LENGTH] != b'-' => {
+                return None
+            }
+            _ => (),
+        }
+
+        if header[2] != b'-' || header[35] != b'-' || header[52] != b'-' {
+            return None;
+        }
+
+        let traceparent = Self {
+            version,
+            trace_id: TraceId(parse_hex(&header[3..35])?),
+            parent_id: SpanId(parse_hex(&header[36..52])?),
+            flags: parse_hex::<1>(&header[53..55])?[0],
+        };
+
+        (traceparent.trace_id.is_valid() && traceparent.parent_id.is_valid()).then_some(traceparent)
+    }
+
+    /// Returns whether the sampled flag is set.
+    pub fn sampled(&self) -> bool {
+        self.flags & SAMPLED_FLAG != 0
+    }
+}
+
+impl TraceContext {
+    /// Starts a new trace, with a new trace ID and span ID.
+    #[cfg(feature = "runtime")]
+    pub fn new(sampled: bool) -> Self {
+        Self {
+            trace_id: TraceId::generate(),
+            parent_id: None,
+            span_id: SpanId::generate(),
+            sampled,
+            state: None,
+        }
+    }
+
+    /// Creates the trace context for handling a request with the given headers, with a new span ID.
+    ///
+    /// If the request has exactly one valid `traceparent` header, the trace is continued and its
+    ///   sampling decision is followed. Otherwise, a new trace is started which is sampled if
+    ///   `sample_new_traces` is `true`, and any `tracestate` header is ignored.
+    #[cfg(feature = "runtime")]
+    pub fn from_headers(headers: &Headers, sample_new_traces: bool) -> Self {
+        let traceparent = match headers.get_all(TRACEPARENT_HEADER).as_slice() {
+            [traceparent] => TraceParent::parse(traceparent),
+            _ => None,
+        };
+
+        match traceparent {
+            Some(traceparent) => Self {
+                trace_id: traceparent.trace_id,
+                parent_id: Some(traceparent.parent_id),
+                span_id: SpanId::generate(),
+                sampled: traceparent.sampled(),
+                state: parse_tracestate(&headers.get_all(TRACESTATE_HEADER).join(",")),
+            },
+            None => Self::new(sample_new_traces),
+        }
+    }
+
+    /// Returns the `traceparent` header to send with requests made while handling this one, which
+    ///   makes the span of this app their parent.
+    pub fn traceparent(&self) -> TraceParent {
+        TraceParent {
+            version: 0,
+            trace_id: self.trace_id,
+            parent_id: self.span_id,
+            flags: match self.sampled {
+                true => SAMPLED_FLAG,
+                false => 0,
+            },
+        }
+    }
+}
+
+impl Display for TraceId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+impl Display for SpanId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+impl Display for TraceParent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:02x}-{}-{}-{:02x}",
+            self.version, self.trace_id, self.parent_id, self.flags
+        )
+    }
+}
+
+/// Parses a `tracestate` header, returning it without whitespace or empty members, or `None` if it
+///   is empty or invalid.
+///
+/// The header is invalid if any member is malformed, if a key appears twice, or if it has more than
+///   `MAX_TRACESTATE_MEMBERS` members. Several `tracestate` headers should be joined with commas
+///   before being parsed.
+pub fn parse_tracestate(header: &str) -> Option<String> {
+    let mut members: Vec<(&str, &str)> = Vec::new();
+
+    for member in header.split(',') {
+        let member = member.trim_matches([' ', '\t']);
+        if member.is_empty() {
+            continue;
+        }
+
+        let (key, value) = member.split_once('=')?;
+
+        if !valid_tracestate_key(key)
+            || !valid_tracestate_value(value)
+            || members.iter().any(|(existing, _)| *existing == key)
+        {
+            return None;
+        }
+
+        members.push((key, value));
+    }
+
+    if members.is_empty() || members.len() > MAX_TRACESTATE_MEMBERS {
+        return None;
+    }
+
+    let members: Vec<String> = members
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect();
+
+    Some(members.join(","))
+}
+
+/// Checks whether the key of a `tracestate` member is either a simple key, or a tenant ID and a
+///   system ID separated by `@`.
+fn valid_tracestate_key(key: &str) -> bool {
+    let valid_char = |c: u8| matches!(c, b'a'..=b'z' | b'0'..=b'9' | b'_' | b'-' | b'*' | b'/');
+    let valid_part = |part: &[u8], max: usize, first: fn(&u8) -> bool| {
+        part.first().is_some_and(first) && part.len() <= max && part.iter().all(|&c| valid_char(c))
+    };
+
+    match key.split_once('@') {
+        Some((tenant, system)) => {
+            valid_part(tenant.as_bytes(), 241, |c| {
+                c.is_ascii_lowercase() || c.is_ascii_digit()
+            }) && valid_part(system.as_bytes(), 14, u8::is_ascii_lowercase)
+        }
+        None => valid_part(key.as_bytes(), 256, u8::is_ascii_lowercase),
+    }
+}
+
+/// Checks whether the value of a `tracestate` member is printable ASCII without commas or equals
+///   signs, and does not end with a space.
+fn valid_tracestate_value(value: &str) -> bool {
+    let value = value.as_bytes();
+
+    !value.is_empty()
+        && value.len() <= 256
+        && value.last() != Some(&b' ')
+        && value
+            .iter()
+            .all(|&c| (0x20..=0x7e).contains(&c) && c != b',' && c != b'=')
+}
+
+/// Parses lowercase hexadecimal into exactly `N` bytes.
+fn parse_hex<const N: usize>(hex: &[u8]) -> Option<[u8; N]> {
+    let digit = |c: u8| match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        _ => None,
+    };
+
+    if hex.len() != N * 2 {
+        return None;
+    }
+
+    let mut bytes = [0; N];
+    for (byte, pair) in bytes.iter_mut().zip(hex.chunks(2)) {
+        *byte = digit(pair[0])? << 4 | digit(pair[1])?;
+    }
+
+    Some(bytes)
+}
+
+/// Returns a random number, using the randomly-keyed hasher from the standard library so that no
+///   dependency is needed.
+#[cfg(feature = "runtime")]
+fn random_u64() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos());
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(GENERATED_IDS.fetch_add(1, Ordering::Relaxed));
+    hasher.write_u128(nanos);
+    hasher.finish()
+}
@@ -65,10 +65,10 @@ impl Plugin for HotReloadPlugin {
                 state.clone(),
             );
 
-            state.logger.info(format!(
-                "{}: Hot Reload WebSocket connection opened",
-                request.address
-            ));
+            state
+                .logger
+                .scoped("hot-reload")
+                .info(format!("{}: WebSocket connection opened", request.address));
 
             None
         } else {
@@ -1,4 +1,5 @@
 use humphrey_server::config::RouteType;
+use humphrey_server::logger::LogLevel;
 use humphrey_server::AppState;
 use humphrey_ws::{Message, WebsocketStream};
 
@@ -8,7 +9,7 @@ use std::error::Error;
 use std::path::PathBuf;
 use std::sync::mpsc::channel;
 use std::sync::{Arc, Mutex};
-use std::thread::spawn;
+use std::thread::Builder;
 
 struct WatchedRoute {
     path: PathBuf,
@@ -29,10 +30,12 @@ pub fn init(
                 let path = PathBuf::from(route.path.as_ref().unwrap()).canonicalize()?;
                 watcher.watch(&path, RecursiveMode::Recursive)?;
 
-                state.logger.debug(format!(
-                    "Hot Reload: Watching for changes on {}",
-                    path.display()
-                ));
+                state
+                    .logger
+                    .scoped("hot-reload")
+                    .log_at(LogLevel::Debug, || {
+                        format!("Watching for changes on {}", path.display())
+                    });
 
                 watched_routes.push(WatchedRoute {
                     path,
@@ -43,46 +46,51 @@ pub fn init(
         }
     }
 
-    spawn(move || {
-        // Watcher must be moved onto the thread so it doesn't get dropped.
-        // This is because `Drop` disconnects the channel.
-        let _watcher_on_thread = watcher;
+    Builder::new()
+        .name("humphrey-hot-reload".into())
+        .spawn(move || {
+            // Watcher must be moved onto the thread so it doesn't get dropped.
+            // This is because `Drop` disconnects the channel.
+            let _watcher_on_thread = watcher;
 
-        loop {
-            let event = rx.recv().unwrap();
+            loop {
+                let event = rx.recv().unwrap();
 
-            if event.path.is_none() || event.op.is_err() || event.op.unwrap() != Op::WRITE {
-                continue;
-            }
+                if event.path.is_none() || event.op.is_err() || event.op.unwrap() != Op::WRITE {
+                    continue;
+                }
 
-            let path = event.path.unwrap();
+                let path = event.path.unwrap();
 
-            let mut streams = streams.lock().unwrap();
+                let mut streams = streams.lock().unwrap();
 
-            for route in &watched_routes {
-                if path.starts_with(&route.path) {
-                    let url = (route.url_prefix.clone()
-                        + path.strip_prefix(&route.path).unwrap().to_str().unwrap())
-                    .replace('\\', "/");
+                for route in &watched_routes {
+                    if path.starts_with(&route.path) {
+                        let url = (route.url_prefix.clone()
+                            + path.strip_prefix(&route.path).unwrap().to_str().unwrap())
+                        .replace('\\', "/");
 
-                    state.logger.debug(format!("Hot Reload: Reloading {}", url));
+                        state
+                            .logger
+                            .scoped("hot-reload")
+                            .log_at(LogLevel::Debug, || format!("Reloading {}", url));
 
-                    let mut to_remove = Vec::with_capacity(streams.len());
+                        let mut to_remove = Vec::with_capacity(streams.len());
 
-                    #[allow(clippy::significant_drop_in_scrutinee)]
-                    for (i, stream) in streams.iter_mut().enumerate() {
-                        if stream.send(Message::new(url.clone())).is_err() {
-                            to_remove.push(i);
+                        #[allow(clippy::significant_drop_in_scrutinee)]
+                        for (i, stream) in streams.iter_mut().enumerate() {
+                            if stream.send(Message::new(url.clone())).is_err() {
+                                to_remove.push(i);
+                            }
                         }
-                    }
 
-                    for i in to_remove.iter().rev() {
-                        streams.swap_remove(*i);
+                        for i in to_remove.iter().rev() {
+                            streams.swap_remove(*i);
+                        }
                     }
                 }
             }
-        }
-    });
+        })?;
 
     Ok(())
 }
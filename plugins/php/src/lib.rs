@@ -5,29 +5,32 @@ use humphrey::route::{try_find_path, LocatedPath};
 use humphrey_server::config::extended_hashmap::ExtendedMap;
 use humphrey_server::config::{RouteConfig, RouteType};
 use humphrey_server::declare_plugin;
+use humphrey_server::logger::LogLevel;
 use humphrey_server::plugins::plugin::{Plugin, PluginLoadResult};
 use humphrey_server::server::server::AppState;
 
 use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::io::Write;
-use std::net::{Shutdown, TcpStream};
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+use std::time::Duration;
 
 use crate::fcgi::record::FcgiRecord;
 use crate::fcgi::request::FcgiRequest;
 use crate::fcgi::types::FcgiType;
+use crate::pool::{PoolError, PoolStats, StreamPool};
 
 mod fcgi;
+mod pool;
+
+#[cfg(test)]
+mod tests;
 
 #[derive(Debug, Default)]
 pub struct PhpPlugin {
-    /// Acts as a thread pool of open streams to the interpreter.
-    streams: Vec<Mutex<TcpStream>>,
-    /// Keeps track of which streams are available.
-    next_stream: AtomicUsize,
+    /// Acts as a thread pool of open streams to the interpreter, once the plugin is loaded.
+    pool: Option<StreamPool>,
 }
 
 impl Plugin for PhpPlugin {
@@ -44,34 +47,37 @@ impl Plugin for PhpPlugin {
         let php_address = config.get_optional("address", "127.0.0.1".into());
         let php_port = config.get_optional("port", "9000".into());
         let php_target = format!("{}:{}", php_address, php_port);
-        let stream_count = config.get_optional_parsed("threads", 8_usize, "");
-
-        if let Ok(threads) = stream_count {
-            // If the thread count could be parsed, start that many streams
-
-            for _ in 0..threads {
-                if let Ok(stream) = TcpStream::connect(&php_target) {
-                    // If the stream successfully connected, add it to the list
-
-                    self.streams.push(Mutex::new(stream));
-                } else {
-                    // Otherwise return a fatal error
-
-                    return PluginLoadResult::Fatal("Could not connect to the PHP CGI server");
-                }
+        let threads = match config.get_optional_parsed("threads", 8_usize, "") {
+            Ok(threads) => threads,
+            Err(_) => {
+                return PluginLoadResult::Fatal("Could not parse the PHP plugin threads count")
             }
+        };
+        let overflow = match config.get_optional_parsed("overflow", 0_usize, "") {
+            Ok(overflow) => overflow,
+            Err(_) => {
+                return PluginLoadResult::Fatal("Could not parse the PHP plugin overflow limit")
+            }
+        };
+        let timeout = match config.get_duration("wait_timeout", Duration::from_secs(1), "") {
+            Ok(timeout) => timeout.unwrap_or(Duration::from_secs(5)),
+            Err(_) => {
+                return PluginLoadResult::Fatal("Could not parse the PHP plugin wait timeout")
+            }
+        };
 
-            state.logger.info(&format!(
-                "PHP Plugin connected to FCGI server at {} with {} threads",
-                php_target, threads
-            ));
+        // Start the streams, returning a fatal error if any of them could not connect
+        match StreamPool::connect(&php_target, threads, timeout, overflow) {
+            Ok(pool) => self.pool = Some(pool),
+            Err(_) => return PluginLoadResult::Fatal("Could not connect to the PHP CGI server"),
+        }
 
-            PluginLoadResult::Ok(())
-        } else {
-            // If the configuration could not be parsed, return an error
+        state.logger.scoped("php").info(format!(
+            "Connected to FCGI server at {} with {} threads and up to {} temporary connections",
+            php_target, threads, overflow
+        ));
 
-            PluginLoadResult::Fatal("Could not parse the PHP plugin threads count")
-        }
+        PluginLoadResult::Ok(())
     }
 
     fn on_request(
@@ -121,10 +127,9 @@ impl Plugin for PhpPlugin {
     }
 
     fn on_unload(&mut self) {
-        // Drain the streams iterator, shutting down every stream.
-        for stream in self.streams.drain(..) {
-            let stream = stream.lock().unwrap();
-            stream.shutdown(Shutdown::Both).unwrap();
+        // Shut down every stream in the pool.
+        if let Some(pool) = self.pool.take() {
+            pool.shutdown();
         }
     }
 }
@@ -183,20 +188,45 @@ impl PhpPlugin {
                 }
             }
 
-            // Generate the FCGI request
+            // Check out a stream, responding with an error if none became available in time
+            let pool = self.pool.as_ref()?;
+            let mut stream = match pool.checkout() {
+                Ok(stream) => stream,
+                Err(e) => {
+                    let (level, message) = match e {
+                        PoolError::Timeout => (
+                            LogLevel::Warn,
+                            "Timed out waiting for a connection to the PHP server".to_string(),
+                        ),
+                        PoolError::Connect(e) => (
+                            LogLevel::Error,
+                            format!(
+                                "Could not open a temporary connection to the PHP server: {}",
+                                e
+                            ),
+                        ),
+                    };
+
+                    log_stats(&state, level, message, pool.stats());
+
+                    return Some(Response::empty(StatusCode::ServiceUnavailable));
+                }
+            };
+
+            // Generate the FCGI request, asking the server to close temporary connections
             let empty_vec = Vec::new();
-            let fcgi_request =
-                FcgiRequest::new(params, request.content.as_ref().unwrap_or(&empty_vec), true);
+            let fcgi_request = FcgiRequest::new(
+                params,
+                request.content.as_ref().unwrap_or(&empty_vec),
+                !stream.is_temporary(),
+            );
 
             // Send the request to the PHP interpreter
-            let stream_index = self.next_stream.fetch_add(1, Ordering::SeqCst) % self.streams.len();
-            let mut stream = self.streams[stream_index].lock().unwrap();
 
             if let Err(e) = stream.write(&fcgi_request.encode()) {
-                state
-                    .logger
-                    .error("PHP Plugin lost connection with the PHP server");
-                state.logger.error(&format!("Error: {}", e));
+                let logger = state.logger.scoped("php");
+                logger.error("Lost connection with the PHP server");
+                logger.error(format!("Error: {}", e));
                 std::process::exit(0);
             }
 
@@ -214,10 +244,9 @@ impl PhpPlugin {
                         records.push(record);
                     }
                     Err(e) => {
-                        state
-                            .logger
-                            .error("PHP Plugin lost connection with the PHP server");
-                        state.logger.error(&format!("Error: {}", e));
+                        let logger = state.logger.scoped("php");
+                        logger.error("Lost connection with the PHP server");
+                        logger.error(format!("Error: {}", e));
                         std::process::exit(0);
                     }
                 }
@@ -264,10 +293,14 @@ impl PhpPlugin {
             let status_code_number: u16 = status.into();
             let status_code_string: &str = status.into();
 
-            state.logger.info(&format!(
-                "{}: {} {} (PHP Plugin) {}",
-                request.address, status_code_number, status_code_string, request.uri
-            ));
+            if state.logger.scoped("php").enabled(LogLevel::Info) {
+                let message = format!(
+                    "{}: {} {} {}",
+                    request.address, status_code_number, status_code_string, request.uri
+                );
+
+                log_stats(&state, LogLevel::Info, message, pool.stats());
+            }
 
             // Return the response
             Some(response)
@@ -279,5 +312,24 @@ impl PhpPlugin {
     }
 }
 
+/// Logs the message with the gauges of the stream pool attached as fields.
+fn log_stats(state: &AppState, level: LogLevel, message: impl AsRef<str>, stats: PoolStats) {
+    let busy = stats.busy.to_string();
+    let temporary = stats.temporary.to_string();
+    let waiters = stats.waiters.to_string();
+    let timeouts = stats.timeouts.to_string();
+
+    state.logger.scoped("php").log_with_fields(
+        level,
+        message,
+        &[
+            ("busy", &busy),
+            ("temporary", &temporary),
+            ("waiters", &waiters),
+            ("timeouts", &timeouts),
+        ],
+    );
+}
+
 // Declare the plugin
 declare_plugin!(PhpPlugin, PhpPlugin::default);
@@ -0,0 +1,205 @@
+//! Provides a pool of open streams to the FastCGI server.
+
+use std::collections::VecDeque;
+use std::io;
+use std::net::{Shutdown, TcpStream};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// A pool of open streams to the FastCGI server, which requests check out for as long as they need.
+///
+/// When every stream is busy, a request waits up to the timeout for one to be returned. If overflow
+///   is allowed, a temporary stream is opened instead of waiting, up to the given number at once,
+///   which is closed as soon as the request is complete.
+#[derive(Debug)]
+pub struct StreamPool {
+    /// The address of the FastCGI server.
+    target: String,
+    /// How long a request waits for a stream before giving up.
+    timeout: Duration,
+    /// The maximum number of temporary streams which can be open at once.
+    overflow: usize,
+    /// The free streams and the gauges of the pool.
+    state: Mutex<PoolState>,
+    /// Notified whenever a stream is returned to the pool.
+    returned: Condvar,
+}
+
+/// The mutable state of the pool.
+#[derive(Debug, Default)]
+struct PoolState {
+    free: VecDeque<TcpStream>,
+    busy: usize,
+    temporary: usize,
+    waiters: usize,
+    timeouts: usize,
+}
+
+/// A snapshot of the gauges of the pool.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// The number of streams which are checked out, including temporary ones.
+    pub busy: usize,
+    /// The number of temporary streams which are open.
+    pub temporary: usize,
+    /// The number of requests which are waiting for a stream.
+    pub waiters: usize,
+    /// The number of requests which have timed out waiting for a stream.
+    pub timeouts: usize,
+}
+
+/// An error which prevented a stream from being checked out.
+#[derive(Debug)]
+pub enum PoolError {
+    /// No stream was returned to the pool before the timeout.
+    Timeout,
+    /// A temporary stream could not be opened.
+    Connect(io::Error),
+}
+
+/// A stream checked out from the pool, which is returned to it when dropped.
+#[derive(Debug)]
+pub struct PooledStream<'a> {
+    pool: &'a StreamPool,
+    stream: Option<TcpStream>,
+    temporary: bool,
+}
+
+impl StreamPool {
+    /// Opens the given number of streams to the FastCGI server at the target address.
+    pub fn connect(
+        target: impl Into<String>,
+        streams: usize,
+        timeout: Duration,
+        overflow: usize,
+    ) -> io::Result<Self> {
+        let target = target.into();
+        let free = (0..streams)
+            .map(|_| TcpStream::connect(&target))
+            .collect::<io::Result<VecDeque<_>>>()?;
+
+        Ok(Self {
+            target,
+            timeout,
+            overflow,
+            state: Mutex::new(PoolState {
+                free,
+                ..Default::default()
+            }),
+            returned: Condvar::new(),
+        })
+    }
+
+    /// Checks out a free stream, opening a temporary one if they are all busy and overflow is allowed,
+    ///   or otherwise waiting until one is returned or the timeout elapses.
+    pub fn checkout(&self) -> Result<PooledStream<'_>, PoolError> {
+        let deadline = Instant::now() + self.timeout;
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            if let Some(stream) = state.free.pop_front() {
+                state.busy += 1;
+
+                return Ok(PooledStream {
+                    pool: self,
+                    stream: Some(stream),
+                    temporary: false,
+                });
+            }
+
+            if state.temporary < self.overflow {
+                state.busy += 1;
+                state.temporary += 1;
+                drop(state);
+
+                return match TcpStream::connect(&self.target) {
+                    Ok(stream) => Ok(PooledStream {
+                        pool: self,
+                        stream: Some(stream),
+                        temporary: true,
+                    }),
+                    Err(e) => {
+                        self.release(None, true);
+                        Err(PoolError::Connect(e))
+                    }
+                };
+            }
+
+            let now = Instant::now();
+
+            if now >= deadline {
+                state.timeouts += 1;
+                return Err(PoolError::Timeout);
+            }
+
+            state.waiters += 1;
+            state = self.returned.wait_timeout(state, deadline - now).unwrap().0;
+            state.waiters -= 1;
+        }
+    }
+
+    /// Returns a snapshot of the gauges of the pool.
+    pub fn stats(&self) -> PoolStats {
+        let state = self.state.lock().unwrap();
+
+        PoolStats {
+            busy: state.busy,
+            temporary: state.temporary,
+            waiters: state.waiters,
+            timeouts: state.timeouts,
+        }
+    }
+
+    /// Shuts down every free stream.
+    pub fn shutdown(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        for stream in state.free.drain(..) {
+            stream.shutdown(Shutdown::Both).unwrap();
+        }
+    }
+
+    /// Returns a checked out stream to the pool, or closes it if it was temporary, and wakes a request
+    ///   which is waiting for one.
+    fn release(&self, stream: Option<TcpStream>, temporary: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.busy -= 1;
+
+        if temporary {
+            state.temporary -= 1;
+        } else if let Some(stream) = stream {
+            state.free.push_back(stream);
+        }
+
+        drop(state);
+        self.returned.notify_one();
+    }
+}
+
+impl PooledStream<'_> {
+    /// Returns `true` if the stream is a temporary one, which will be closed when it is dropped.
+    pub fn is_temporary(&self) -> bool {
+        self.temporary
+    }
+}
+
+impl Deref for PooledStream<'_> {
+    type Target = TcpStream;
+
+    fn deref(&self) -> &Self::Target {
+        self.stream.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledStream<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.stream.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledStream<'_> {
+    fn drop(&mut self) {
+        self.pool.release(self.stream.take(), self.temporary);
+    }
+}
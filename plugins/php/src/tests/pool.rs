@@ -0,0 +1,281 @@
+use humphrey::http::{Request, Response, StatusCode};
+
+use humphrey_server::config::config::Config;
+use humphrey_server::config::{RouteConfig, RouteType};
+use humphrey_server::logger::LogLevel;
+use humphrey_server::plugins::plugin::{Plugin, PluginLoadResult};
+use humphrey_server::server::server::AppState;
+
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{sleep, spawn};
+use std::time::Duration;
+
+use crate::fcgi::record::FcgiRecord;
+use crate::fcgi::types::FcgiType;
+use crate::pool::{PoolError, PoolStats, StreamPool};
+use crate::PhpPlugin;
+
+/// Starts a FastCGI server which responds to each request with `Hello` after the given delay,
+///   returning its address and the number of connections it has accepted.
+fn start_server(delay: Duration) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let connections = Arc::new(AtomicUsize::new(0));
+    let accepted = connections.clone();
+
+    spawn(move || {
+        for stream in listener.incoming() {
+            accepted.fetch_add(1, Ordering::SeqCst);
+            spawn(move || serve(stream.unwrap(), delay));
+        }
+    });
+
+    (addr, connections)
+}
+
+/// Responds to requests on the stream until it is closed, or until a request which did not ask for
+///   the connection to be kept open has been responded to.
+fn serve(mut stream: TcpStream, delay: Duration) {
+    loop {
+        let mut keep_alive = true;
+
+        loop {
+            let record = match FcgiRecord::read_from(&stream) {
+                Ok(record) => record,
+                Err(_) => return,
+            };
+
+            if record.fcgi_type == FcgiType::Begin {
+                keep_alive = record.content_data[2] == 1;
+            } else if record.fcgi_type == FcgiType::Stdin && record.content_length == 0 {
+                break;
+            }
+        }
+
+        sleep(delay);
+
+        let stdout: Vec<u8> = FcgiRecord::new(
+            FcgiType::Stdout,
+            b"Content-Type: text/plain\r\n\r\nHello",
+            0,
+        )
+        .into();
+        let end: Vec<u8> = FcgiRecord::new(FcgiType::End, &[0; 8], 0).into();
+        stream.write_all(&stdout).unwrap();
+        stream.write_all(&end).unwrap();
+
+        if !keep_alive {
+            return;
+        }
+    }
+}
+
+/// Loads the plugin with the given configuration, connected to the server at the given address, and
+///   returns it with the state of an app which logs to a file, and the path of the file.
+fn load(name: &str, addr: &str, config: &[(&str, &str)]) -> (PhpPlugin, Arc<AppState>, String) {
+    let path = std::env::temp_dir()
+        .join(format!("humphrey-php-{}-{}.log", name, std::process::id()))
+        .to_string_lossy()
+        .to_string();
+
+    let mut conf = Config::default();
+    conf.logging.level = LogLevel::Info;
+    conf.logging.console = false;
+    conf.logging.file = Some(path.clone());
+    let state = Arc::new(AppState::from(conf));
+
+    let (address, port) = addr.split_once(':').unwrap();
+    let mut map: HashMap<String, String> = HashMap::new();
+    map.insert("address".into(), address.into());
+    map.insert("port".into(), port.into());
+
+    for (key, value) in config {
+        map.insert(key.to_string(), value.to_string());
+    }
+
+    let mut plugin = PhpPlugin::default();
+    assert!(matches!(
+        plugin.on_load(&map, state.clone()),
+        PluginLoadResult::Ok(())
+    ));
+
+    (plugin, state, path)
+}
+
+/// Requests `/index.php` from the plugin.
+fn request(plugin: &PhpPlugin, state: &Arc<AppState>) -> Response {
+    let route = RouteConfig {
+        route_type: RouteType::File,
+        matches: "/index.php".into(),
+        path: Some("/var/www/index.php".into()),
+        ..Default::default()
+    };
+
+    let mut request = Request::builder().uri("/index.php").build();
+    plugin
+        .on_request(&mut request, state.clone(), &route)
+        .unwrap()
+}
+
+fn pool(plugin: &PhpPlugin) -> &StreamPool {
+    plugin.pool.as_ref().unwrap()
+}
+
+#[test]
+fn test_request() {
+    let (addr, _) = start_server(Duration::ZERO);
+    let (plugin, state, path) = load("request", &addr, &[("threads", "1")]);
+
+    let response = request(&plugin, &state);
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body, b"Hello");
+
+    let log = read_to_string(&path).unwrap();
+    assert!(log.contains("busy=1 temporary=0 waiters=0 timeouts=0"));
+    assert_eq!(pool(&plugin).stats(), PoolStats::default());
+}
+
+#[test]
+fn test_waits_for_stream() {
+    let (addr, connections) = start_server(Duration::from_millis(100));
+    let (plugin, state, _) = load("wait", &addr, &[("threads", "1"), ("wait_timeout", "5s")]);
+    let plugin = Arc::new(plugin);
+
+    let stream = pool(&plugin).checkout().unwrap();
+
+    let waiting = {
+        let plugin = plugin.clone();
+        spawn(move || request(&plugin, &state))
+    };
+
+    sleep(Duration::from_millis(100));
+    assert_eq!(
+        pool(&plugin).stats(),
+        PoolStats {
+            busy: 1,
+            waiters: 1,
+            ..Default::default()
+        }
+    );
+
+    drop(stream);
+    let response = waiting.join().unwrap();
+
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body, b"Hello");
+    assert_eq!(pool(&plugin).stats(), PoolStats::default());
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_concurrent_requests() {
+    let (addr, _) = start_server(Duration::from_millis(50));
+    let (plugin, state, _) = load(
+        "concurrent",
+        &addr,
+        &[("threads", "2"), ("wait_timeout", "5s")],
+    );
+    let plugin = Arc::new(plugin);
+
+    let threads: Vec<_> = (0..6)
+        .map(|_| {
+            let plugin = plugin.clone();
+            let state = state.clone();
+            spawn(move || request(&plugin, &state))
+        })
+        .collect();
+
+    for thread in threads {
+        assert_eq!(thread.join().unwrap().body, b"Hello");
+    }
+
+    assert_eq!(pool(&plugin).stats(), PoolStats::default());
+}
+
+#[test]
+fn test_timeout() {
+    let (addr, _) = start_server(Duration::ZERO);
+    let (plugin, state, path) = load(
+        "timeout",
+        &addr,
+        &[("threads", "1"), ("wait_timeout", "100ms")],
+    );
+
+    let stream = pool(&plugin).checkout().unwrap();
+    assert!(matches!(pool(&plugin).checkout(), Err(PoolError::Timeout)));
+
+    let response = request(&plugin, &state);
+    assert_eq!(response.status_code, StatusCode::ServiceUnavailable);
+    assert_eq!(pool(&plugin).stats().timeouts, 2);
+
+    let log = read_to_string(&path).unwrap();
+    assert!(log.contains(
+        "[WARN]  [php] Timed out waiting for a connection to the PHP server busy=1 temporary=0 waiters=0 timeouts=2"
+    ));
+
+    drop(stream);
+    assert_eq!(request(&plugin, &state).status_code, StatusCode::OK);
+}
+
+#[test]
+fn test_overflow() {
+    let (addr, connections) = start_server(Duration::from_millis(300));
+    let (plugin, state, _) = load(
+        "overflow",
+        &addr,
+        &[
+            ("threads", "1"),
+            ("overflow", "1"),
+            ("wait_timeout", "100ms"),
+        ],
+    );
+    let plugin = Arc::new(plugin);
+
+    let stream = pool(&plugin).checkout().unwrap();
+    assert!(!stream.is_temporary());
+
+    let overflowing = {
+        let plugin = plugin.clone();
+        let state = state.clone();
+        spawn(move || request(&plugin, &state))
+    };
+
+    sleep(Duration::from_millis(50));
+    assert_eq!(
+        pool(&plugin).stats(),
+        PoolStats {
+            busy: 2,
+            temporary: 1,
+            ..Default::default()
+        }
+    );
+
+    // The temporary connection is in use, so the hard cap has been reached
+    assert!(matches!(pool(&plugin).checkout(), Err(PoolError::Timeout)));
+
+    let response = overflowing.join().unwrap();
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.body, b"Hello");
+
+    // The temporary connection was closed, so another can be opened
+    let temporary = pool(&plugin).checkout().unwrap();
+    assert!(temporary.is_temporary());
+
+    sleep(Duration::from_millis(50));
+    assert_eq!(connections.load(Ordering::SeqCst), 3);
+
+    drop(temporary);
+    drop(stream);
+    assert_eq!(
+        pool(&plugin).stats(),
+        PoolStats {
+            timeouts: 1,
+            ..Default::default()
+        }
+    );
+}
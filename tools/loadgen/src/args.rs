@@ -0,0 +1,132 @@
+//! Provides command-line argument parsing.
+
+use crate::load::LoadConfig;
+
+use std::time::Duration;
+
+/// The usage information printed with `--help` or when the arguments are invalid.
+pub const USAGE: &str = "Usage: loadgen <target> [options]
+
+Generates load against an HTTP target such as `http://127.0.0.1:8080`.
+
+Options:
+  -c, --connections <n>      Number of concurrent connections (default 8)
+  -d, --duration <seconds>   How long to generate load for (default 10)
+  -k, --no-keep-alive        Open a new connection for every request
+  -p, --path <path>[@weight] Path to request, with an optional relative weight; may be repeated (default /)
+  -b, --payload <size>       Request body size such as 512, 4K or 1M, sent with POST; may be repeated,
+                             in which case one is chosen at random for each request (default 0)
+  -t, --timeout <seconds>    Timeout for connecting, sending and receiving (default 5)
+      --json                 Print the report as JSON
+  -h, --help                 Print this message";
+
+/// Represents the parsed command-line arguments.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Arguments {
+    /// The configuration of the load test.
+    pub config: LoadConfig,
+    /// Whether to print the report as JSON.
+    pub json: bool,
+    /// Whether to print usage information instead of running.
+    pub help: bool,
+}
+
+/// Parses the command-line arguments, excluding the program name.
+pub fn parse_args<I>(args: I) -> Result<Arguments, String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter();
+    let mut config = LoadConfig::default();
+    let mut target: Option<String> = None;
+    let mut paths: Vec<(String, u32)> = Vec::new();
+    let mut payload_sizes: Vec<usize> = Vec::new();
+    let mut json = false;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("Missing value for `{}`", name))
+        };
+
+        match arg.as_str() {
+            "-h" | "--help" => {
+                return Ok(Arguments {
+                    config,
+                    json,
+                    help: true,
+                })
+            }
+            "-c" | "--connections" => {
+                config.connections = parse_number(&arg, &value(&arg)?)?;
+            }
+            "-d" | "--duration" => {
+                config.duration = Duration::from_secs(parse_number(&arg, &value(&arg)?)?);
+            }
+            "-t" | "--timeout" => {
+                config.timeout = Duration::from_secs(parse_number(&arg, &value(&arg)?)?);
+            }
+            "-k" | "--no-keep-alive" => config.keep_alive = false,
+            "-p" | "--path" => paths.push(parse_path(&value(&arg)?)?),
+            "-b" | "--payload" => payload_sizes.push(parse_size(&value(&arg)?)?),
+            "--json" => json = true,
+            _ if arg.starts_with('-') => return Err(format!("Unknown option `{}`", arg)),
+            _ if target.is_none() => target = Some(arg),
+            _ => return Err(format!("Unexpected argument `{}`", arg)),
+        }
+    }
+
+    config.target = target.ok_or("No target specified")?;
+
+    if !paths.is_empty() {
+        config.paths = paths;
+    }
+
+    if !payload_sizes.is_empty() {
+        config.payload_sizes = payload_sizes;
+    }
+
+    Ok(Arguments {
+        config,
+        json,
+        help: false,
+    })
+}
+
+/// Parses a number given as the value of an option.
+fn parse_number<T>(option: &str, value: &str) -> Result<T, String>
+where
+    T: std::str::FromStr,
+{
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value `{}` for `{}`", value, option))
+}
+
+/// Parses a path with an optional weight, such as `/api@3`.
+fn parse_path(value: &str) -> Result<(String, u32), String> {
+    let (path, weight) = match value.rsplit_once('@') {
+        Some((path, weight)) => (path, parse_number("--path", weight)?),
+        None => (value, 1),
+    };
+
+    if !path.starts_with('/') {
+        return Err(format!("Path `{}` must start with a slash", path));
+    }
+
+    Ok((path.to_string(), weight))
+}
+
+/// Parses a size in bytes with an optional `K` or `M` suffix.
+pub fn parse_size(value: &str) -> Result<usize, String> {
+    let (number, multiplier) = match value.to_ascii_uppercase().chars().last() {
+        Some('K') => (&value[..value.len() - 1], 1024),
+        Some('M') => (&value[..value.len() - 1], 1024 * 1024),
+        _ => (value, 1),
+    };
+
+    number
+        .parse::<usize>()
+        .map(|size| size * multiplier)
+        .map_err(|_| format!("Invalid payload size `{}`", value))
+}
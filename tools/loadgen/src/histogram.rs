@@ -0,0 +1,148 @@
+//! Provides a fixed-size latency histogram.
+
+use std::time::Duration;
+
+/// The number of buckets each power of two is divided into, which bounds the relative error of a
+///   recorded value to one in this number.
+const SUB_BUCKETS: usize = 64;
+
+/// The number of rows of buckets, where each row after the first covers a power of two. Together they
+///   cover latencies of up to 2^37 microseconds, about 38 hours.
+const MAGNITUDES: usize = 32;
+
+/// Represents a histogram of latencies with microsecond resolution.
+///
+/// Values are recorded into logarithmically-sized buckets, so the memory used is constant no matter
+///   how many values are recorded, and percentiles are accurate to within about 1.5%.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    total: u64,
+    min: u64,
+    max: u64,
+    sum: u64,
+}
+
+impl Histogram {
+    /// Creates a new, empty histogram.
+    pub fn new() -> Self {
+        Self {
+            counts: vec![0; MAGNITUDES * SUB_BUCKETS],
+            total: 0,
+            min: u64::MAX,
+            max: 0,
+            sum: 0,
+        }
+    }
+
+    /// Records a latency.
+    pub fn record(&mut self, latency: Duration) {
+        let micros = latency.as_micros().min(u64::MAX as u128) as u64;
+        let index = bucket_index(micros).min(self.counts.len() - 1);
+
+        self.counts[index] += 1;
+        self.total += 1;
+        self.min = self.min.min(micros);
+        self.max = self.max.max(micros);
+        self.sum = self.sum.saturating_add(micros);
+    }
+
+    /// Adds every value recorded in the other histogram to this one.
+    pub fn merge(&mut self, other: &Histogram) {
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+
+        self.total += other.total;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum = self.sum.saturating_add(other.sum);
+    }
+
+    /// Returns the number of values recorded.
+    pub fn len(&self) -> u64 {
+        self.total
+    }
+
+    /// Returns `true` if no values have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.total == 0
+    }
+
+    /// Returns the smallest value recorded, or zero if the histogram is empty.
+    pub fn min(&self) -> Duration {
+        Duration::from_micros(if self.is_empty() { 0 } else { self.min })
+    }
+
+    /// Returns the largest value recorded, or zero if the histogram is empty.
+    pub fn max(&self) -> Duration {
+        Duration::from_micros(self.max)
+    }
+
+    /// Returns the mean of the values recorded, or zero if the histogram is empty.
+    pub fn mean(&self) -> Duration {
+        Duration::from_micros(self.sum.checked_div(self.total).unwrap_or(0))
+    }
+
+    /// Returns the value below which the given percentage of the recorded values fall, or zero if
+    ///   the histogram is empty.
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        if self.is_empty() {
+            return Duration::ZERO;
+        }
+
+        let rank = ((percentile / 100.0) * self.total as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+
+            if seen >= rank {
+                // The last bucket also holds every value beyond the range of the histogram
+                let value = if index == self.counts.len() - 1 {
+                    self.max
+                } else {
+                    bucket_value(index).clamp(self.min, self.max)
+                };
+
+                return Duration::from_micros(value);
+            }
+        }
+
+        self.max()
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Gets the index of the bucket containing the value.
+///
+/// Values below `SUB_BUCKETS` have a bucket each, and every following power of two is divided into
+///   `SUB_BUCKETS` equally-sized buckets.
+fn bucket_index(value: u64) -> usize {
+    if value < SUB_BUCKETS as u64 {
+        return value as usize;
+    }
+
+    let magnitude = (63 - value.leading_zeros()) as usize;
+    let shift = magnitude - SUB_BUCKETS.trailing_zeros() as usize;
+    let sub_bucket = ((value >> shift) as usize) - SUB_BUCKETS;
+
+    (shift + 1) * SUB_BUCKETS + sub_bucket
+}
+
+/// Gets the largest value in the bucket with the given index.
+fn bucket_value(index: usize) -> u64 {
+    if index < SUB_BUCKETS {
+        return index as u64;
+    }
+
+    let shift = index / SUB_BUCKETS - 1;
+    let sub_bucket = (index % SUB_BUCKETS + SUB_BUCKETS) as u64;
+
+    ((sub_bucket + 1) << shift) - 1
+}
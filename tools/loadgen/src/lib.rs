@@ -0,0 +1,17 @@
+//! A load generator for reproducing performance and memory reports against Humphrey applications.
+//!
+//! The load is generated by a number of concurrent connections, each sending requests as fast as the
+//!   target responds for a fixed duration. Requests are chosen from a weighted mix of paths and a
+//!   set of payload sizes, and can either reuse their connection with keep-alive or open a new one for
+//!   every request. Latencies are recorded in a fixed-size histogram so that long runs do not grow
+//!   the load generator's own memory usage.
+
+#![warn(missing_docs)]
+
+pub mod args;
+pub mod histogram;
+pub mod load;
+pub mod report;
+
+pub use load::{run, LoadConfig};
+pub use report::Report;
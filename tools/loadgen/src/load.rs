@@ -0,0 +1,268 @@
+//! Provides the load generation itself.
+
+use crate::histogram::Histogram;
+use crate::report::{Errors, Report};
+
+use humphrey::http::headers::HeaderType;
+use humphrey::http::Response;
+use humphrey::Client;
+
+use std::error::Error;
+use std::io::Write;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Represents the configuration of a load test.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoadConfig {
+    /// The origin to send requests to, for example `http://127.0.0.1:8080`.
+    pub target: String,
+    /// The number of concurrent connections.
+    pub connections: usize,
+    /// How long to generate load for.
+    pub duration: Duration,
+    /// Whether to reuse connections for subsequent requests.
+    pub keep_alive: bool,
+    /// The paths to request and their relative weights.
+    pub paths: Vec<(String, u32)>,
+    /// The sizes of the request bodies, in bytes, one of which is chosen at random for each request.
+    ///   Requests with a body are sent with `POST`, and those without are sent with `GET`.
+    pub payload_sizes: Vec<usize>,
+    /// How long to wait to connect, send a request or receive a response before giving up.
+    pub timeout: Duration,
+}
+
+/// The statistics recorded by a single connection.
+#[derive(Default)]
+struct WorkerStats {
+    latencies: Histogram,
+    connections: u64,
+    bytes_received: u64,
+    errors: Errors,
+}
+
+/// A simple xorshift generator, used to choose requests without depending on a random crate.
+struct Xorshift(u64);
+
+impl Default for LoadConfig {
+    fn default() -> Self {
+        Self {
+            target: "http://127.0.0.1:80".into(),
+            connections: 8,
+            duration: Duration::from_secs(10),
+            keep_alive: true,
+            paths: vec![("/".into(), 1)],
+            payload_sizes: vec![0],
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl Xorshift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// Runs the load test, blocking until it has finished.
+///
+/// Returns an error if the configuration is invalid or the target cannot be resolved, but errors
+///   encountered while generating load are counted in the report instead.
+pub fn run(config: &LoadConfig) -> Result<Report, Box<dyn Error>> {
+    if config.connections == 0 {
+        return Err("At least one connection is required".into());
+    }
+
+    if config.paths.is_empty() || config.paths.iter().all(|(_, weight)| *weight == 0) {
+        return Err("At least one path with a non-zero weight is required".into());
+    }
+
+    if config.payload_sizes.is_empty() {
+        return Err("At least one payload size is required".into());
+    }
+
+    let address = resolve(&config.target)?;
+    let requests = build_requests(config)?;
+
+    let start = Instant::now();
+    let deadline = start + config.duration;
+
+    let workers: Vec<_> = (0..config.connections)
+        .map(|index| {
+            let config = config.clone();
+            let requests = requests.clone();
+            spawn(move || worker(&config, address, &requests, deadline, index as u64))
+        })
+        .collect();
+
+    let mut total = WorkerStats::default();
+
+    for worker in workers {
+        let stats = worker.join().map_err(|_| "A connection thread panicked")?;
+
+        total.latencies.merge(&stats.latencies);
+        total.connections += stats.connections;
+        total.bytes_received += stats.bytes_received;
+        total.errors.merge(&stats.errors);
+    }
+
+    Ok(Report {
+        target: config.target.clone(),
+        concurrency: config.connections,
+        keep_alive: config.keep_alive,
+        elapsed: start.elapsed(),
+        connections: total.connections,
+        bytes_received: total.bytes_received,
+        latencies: total.latencies,
+        errors: total.errors,
+    })
+}
+
+/// Resolves the address of the target, which must be an `http://` origin.
+fn resolve(target: &str) -> Result<SocketAddr, Box<dyn Error>> {
+    let host = target
+        .strip_prefix("http://")
+        .ok_or("Only `http://` targets are supported")?
+        .trim_end_matches('/');
+
+    let address = if host.contains(':') {
+        host.to_socket_addrs()
+    } else {
+        (host, 80).to_socket_addrs()
+    };
+
+    Ok(address?.next().ok_or("Target could not be resolved")?)
+}
+
+/// Serialises a request for every combination of path and payload size, indexed by the path then
+///   the payload size, so that no time is spent building requests during the test.
+fn build_requests(config: &LoadConfig) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+    let mut client = Client::new();
+    let connection = if config.keep_alive {
+        "Keep-Alive"
+    } else {
+        "Close"
+    };
+
+    let mut requests = Vec::with_capacity(config.paths.len() * config.payload_sizes.len());
+
+    for (path, _) in &config.paths {
+        let url = format!("{}{}", config.target.trim_end_matches('/'), path);
+
+        for size in &config.payload_sizes {
+            let request = match size {
+                0 => client.get(&url)?,
+                size => client.post(&url, vec![b'x'; *size])?,
+            };
+
+            requests.push(
+                request
+                    .with_header(HeaderType::Connection, connection)
+                    .into_inner()
+                    .into(),
+            );
+        }
+    }
+
+    Ok(requests)
+}
+
+/// Sends requests on a single connection until the deadline, reconnecting if the connection is
+///   closed or fails.
+fn worker(
+    config: &LoadConfig,
+    address: SocketAddr,
+    requests: &[Vec<u8>],
+    deadline: Instant,
+    index: u64,
+) -> WorkerStats {
+    let seed = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|time| time.as_nanos() as u64)
+        .unwrap_or_default();
+    let mut rng = Xorshift((seed ^ (index + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15)) | 1);
+
+    let total_weight: u64 = config.paths.iter().map(|(_, weight)| *weight as u64).sum();
+    let mut stats = WorkerStats::default();
+    let mut stream: Option<TcpStream> = None;
+
+    while Instant::now() < deadline {
+        let mut connection = match stream.take() {
+            Some(connection) => connection,
+            None => match connect(address, config.timeout) {
+                Ok(connection) => {
+                    stats.connections += 1;
+                    connection
+                }
+                Err(_) => {
+                    stats.errors.connect += 1;
+
+                    // Avoid spinning while the target is refusing connections
+                    sleep(Duration::from_millis(10));
+                    continue;
+                }
+            },
+        };
+
+        // Choose a path by weight, then a payload size uniformly
+        let mut choice = rng.next() % total_weight;
+        let path = config
+            .paths
+            .iter()
+            .position(|(_, weight)| match choice.checked_sub(*weight as u64) {
+                Some(remaining) => {
+                    choice = remaining;
+                    false
+                }
+                None => true,
+            })
+            .unwrap_or(0);
+        let size = (rng.next() % config.payload_sizes.len() as u64) as usize;
+        let request = &requests[path * config.payload_sizes.len() + size];
+
+        let start = Instant::now();
+        let response = connection
+            .write_all(request)
+            .ok()
+            .and_then(|_| Response::from_stream(&mut connection).ok());
+
+        match response {
+            Some(response) => {
+                stats.latencies.record(start.elapsed());
+                stats.bytes_received += response.body.len() as u64;
+
+                match u16::from(response.status_code) {
+                    400..=499 => stats.errors.client += 1,
+                    500..=599 => stats.errors.server += 1,
+                    _ => (),
+                }
+
+                let closed = response
+                    .headers
+                    .get(HeaderType::Connection)
+                    .is_some_and(|value| value.eq_ignore_ascii_case("close"));
+
+                if config.keep_alive && !closed {
+                    stream = Some(connection);
+                }
+            }
+            None => stats.errors.io += 1,
+        }
+    }
+
+    stats
+}
+
+/// Connects to the target with the given timeout applied to every operation.
+fn connect(address: SocketAddr, timeout: Duration) -> Result<TcpStream, std::io::Error> {
+    let stream = TcpStream::connect_timeout(&address, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    stream.set_nodelay(true)?;
+
+    Ok(stream)
+}
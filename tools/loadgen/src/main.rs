@@ -0,0 +1,32 @@
+use loadgen::args::{parse_args, USAGE};
+use loadgen::run;
+
+use std::env::args;
+use std::process::exit;
+
+#[cfg(test)]
+mod tests;
+
+fn main() {
+    let arguments = match parse_args(args().skip(1)) {
+        Ok(arguments) => arguments,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, USAGE);
+            exit(2);
+        }
+    };
+
+    if arguments.help {
+        println!("{}", USAGE);
+        return;
+    }
+
+    match run(&arguments.config) {
+        Ok(report) if arguments.json => println!("{}", report.to_json().serialize()),
+        Ok(report) => println!("{}", report),
+        Err(error) => {
+            eprintln!("{}", error);
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Provides the results of a load test, in both human-readable and JSON form.
+
+use crate::histogram::Histogram;
+
+use humphrey_json::prelude::*;
+use humphrey_json::Value;
+
+use std::fmt::Display;
+use std::time::Duration;
+
+/// The percentiles of the latency which are reported.
+const PERCENTILES: [(&str, f64); 5] = [
+    ("p50", 50.0),
+    ("p90", 90.0),
+    ("p99", 99.0),
+    ("p99.9", 99.9),
+    ("p100", 100.0),
+];
+
+/// Represents the results of a load test.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    /// The origin which the requests were sent to.
+    pub target: String,
+    /// The number of concurrent connections.
+    pub concurrency: usize,
+    /// Whether connections were reused for subsequent requests.
+    pub keep_alive: bool,
+    /// How long the test took.
+    pub elapsed: Duration,
+    /// The number of connections established, including reconnections.
+    pub connections: u64,
+    /// The number of bytes received in response bodies.
+    pub bytes_received: u64,
+    /// The latencies of every request which received a response.
+    pub latencies: Histogram,
+    /// The errors which occurred.
+    pub errors: Errors,
+}
+
+/// Represents the number of each kind of error which occurred during a load test.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Errors {
+    /// Connections which could not be established.
+    pub connect: u64,
+    /// Requests which could not be sent, or whose response could not be read.
+    pub io: u64,
+    /// Responses with a 4xx status code.
+    pub client: u64,
+    /// Responses with a 5xx status code.
+    pub server: u64,
+}
+
+impl Report {
+    /// Returns the number of requests which received a response, including error responses.
+    pub fn requests(&self) -> u64 {
+        self.latencies.len()
+    }
+
+    /// Returns the average number of requests which received a response per second.
+    pub fn requests_per_second(&self) -> f64 {
+        self.requests() as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Serialises the report as JSON, with durations in milliseconds.
+    pub fn to_json(&self) -> Value {
+        let latency = Value::Object(
+            [
+                ("min", self.latencies.min()),
+                ("mean", self.latencies.mean()),
+            ]
+            .into_iter()
+            .chain(
+                PERCENTILES
+                    .iter()
+                    .map(|(name, p)| (*name, self.latencies.percentile(*p))),
+            )
+            .map(|(name, latency)| (name.to_string(), json!(millis(latency))))
+            .collect(),
+        );
+
+        json!({
+            "target": (self.target.as_str()),
+            "concurrency": (self.concurrency),
+            "keepAlive": (self.keep_alive),
+            "elapsed": (millis(self.elapsed)),
+            "requests": (self.requests()),
+            "requestsPerSecond": (self.requests_per_second()),
+            "connections": (self.connections),
+            "bytesReceived": (self.bytes_received),
+            "latency": latency,
+            "errors": {
+                "connect": (self.errors.connect),
+                "io": (self.errors.io),
+                "client": (self.errors.client),
+                "server": (self.errors.server)
+            }
+        })
+    }
+}
+
+impl Errors {
+    /// Returns the total number of errors.
+    pub fn total(&self) -> u64 {
+        self.connect + self.io + self.client + self.server
+    }
+
+    /// Adds the errors from another set of errors to this one.
+    pub fn merge(&mut self, other: &Errors) {
+        self.connect += other.connect;
+        self.io += other.io;
+        self.client += other.client;
+        self.server += other.server;
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Target:       {}", self.target)?;
+        writeln!(
+            f,
+            "Connections:  {} concurrent, {} established{}",
+            self.concurrency,
+            self.connections,
+            if self.keep_alive { " (keep-alive)" } else { "" }
+        )?;
+        writeln!(f, "Duration:     {:.2}s", self.elapsed.as_secs_f64())?;
+        writeln!(
+            f,
+            "Requests:     {} ({:.1}/s, {} bytes received)",
+            self.requests(),
+            self.requests_per_second(),
+            self.bytes_received
+        )?;
+        writeln!(
+            f,
+            "Errors:       {} connect, {} I/O, {} 4xx, {} 5xx",
+            self.errors.connect, self.errors.io, self.errors.client, self.errors.server
+        )?;
+        write!(
+            f,
+            "Latency:      min {:.2}ms, mean {:.2}ms",
+            millis(self.latencies.min()),
+            millis(self.latencies.mean())
+        )?;
+
+        for (name, percentile) in PERCENTILES {
+            write!(
+                f,
+                ", {} {:.2}ms",
+                name,
+                millis(self.latencies.percentile(percentile))
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Converts a duration to fractional milliseconds.
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
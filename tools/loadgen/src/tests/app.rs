@@ -0,0 +1,44 @@
+use humphrey::http::{Request, Response, StatusCode};
+use humphrey::App;
+
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Sender};
+use std::thread::{sleep, spawn};
+use std::time::Duration;
+
+/// Starts a sample app with a mix of small, large and echoed responses, returning its origin and
+///   shutdown signal.
+///
+/// Each keep-alive connection occupies one of the app's threads, so there must be more threads than
+///   concurrent connections.
+pub fn start_app() -> (String, Sender<()>) {
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .to_string();
+    let (shutdown_tx, shutdown_rx) = channel();
+
+    let app: App<()> = App::new_with_config(32, ())
+        .with_stateless_route("/", |_| Response::new(StatusCode::OK, "Hello, world!"))
+        .with_stateless_route("/large", |_| {
+            Response::new(StatusCode::OK, vec![b'a'; 64 * 1024])
+        })
+        .with_stateless_route("/echo", |request: Request| {
+            Response::new(StatusCode::OK, request.content.unwrap_or_default())
+        })
+        .with_shutdown(shutdown_rx);
+
+    let cloned_addr = addr.clone();
+    spawn(move || app.run(cloned_addr).unwrap());
+
+    for _ in 0..50 {
+        if TcpStream::connect(&addr).is_ok() {
+            break;
+        }
+
+        sleep(Duration::from_millis(10));
+    }
+
+    (format!("http://{}", addr), shutdown_tx)
+}
@@ -0,0 +1,79 @@
+use loadgen::args::{parse_args, parse_size};
+use loadgen::LoadConfig;
+
+use std::time::Duration;
+
+fn args(args: &[&str]) -> Result<loadgen::args::Arguments, String> {
+    parse_args(args.iter().map(|arg| arg.to_string()))
+}
+
+#[test]
+fn test_defaults() {
+    let arguments = args(&["http://127.0.0.1:8080"]).unwrap();
+
+    assert_eq!(
+        arguments.config,
+        LoadConfig {
+            target: "http://127.0.0.1:8080".into(),
+            ..Default::default()
+        }
+    );
+    assert!(!arguments.json);
+    assert!(!arguments.help);
+}
+
+#[test]
+fn test_options() {
+    let arguments = args(&[
+        "-c",
+        "64",
+        "http://localhost",
+        "--duration",
+        "120",
+        "-k",
+        "-p",
+        "/",
+        "--path",
+        "/api/users?sort=name@3",
+        "-b",
+        "0",
+        "-b",
+        "4K",
+        "--json",
+    ])
+    .unwrap();
+
+    assert_eq!(
+        arguments.config,
+        LoadConfig {
+            target: "http://localhost".into(),
+            connections: 64,
+            duration: Duration::from_secs(120),
+            keep_alive: false,
+            paths: vec![("/".into(), 1), ("/api/users?sort=name".into(), 3)],
+            payload_sizes: vec![0, 4096],
+            timeout: Duration::from_secs(5),
+        }
+    );
+    assert!(arguments.json);
+}
+
+#[test]
+fn test_invalid() {
+    assert!(args(&[]).is_err());
+    assert!(args(&["http://localhost", "-c"]).is_err());
+    assert!(args(&["http://localhost", "-c", "many"]).is_err());
+    assert!(args(&["http://localhost", "--path", "api"]).is_err());
+    assert!(args(&["http://localhost", "--unknown"]).is_err());
+    assert!(args(&["http://localhost", "http://example.com"]).is_err());
+    assert!(args(&["--help"]).unwrap().help);
+}
+
+#[test]
+fn test_sizes() {
+    assert_eq!(parse_size("512"), Ok(512));
+    assert_eq!(parse_size("2k"), Ok(2048));
+    assert_eq!(parse_size("1M"), Ok(1048576));
+    assert!(parse_size("1G").is_err());
+    assert!(parse_size("").is_err());
+}
@@ -0,0 +1,53 @@
+use loadgen::histogram::Histogram;
+
+use std::time::Duration;
+
+#[test]
+fn test_empty() {
+    let histogram = Histogram::new();
+
+    assert!(histogram.is_empty());
+    assert_eq!(histogram.min(), Duration::ZERO);
+    assert_eq!(histogram.percentile(99.0), Duration::ZERO);
+    assert_eq!(histogram.mean(), Duration::ZERO);
+}
+
+#[test]
+fn test_percentiles() {
+    let mut histogram = Histogram::new();
+
+    for micros in 1..=10000 {
+        histogram.record(Duration::from_micros(micros));
+    }
+
+    assert_eq!(histogram.len(), 10000);
+    assert_eq!(histogram.min(), Duration::from_micros(1));
+    assert_eq!(histogram.max(), Duration::from_micros(10000));
+    assert_eq!(histogram.mean(), Duration::from_micros(5000));
+    assert_eq!(histogram.percentile(100.0), Duration::from_micros(10000));
+
+    // Percentiles are accurate to within the width of a bucket
+    for (percentile, expected) in [(50.0, 5000.0), (90.0, 9000.0), (99.0, 9900.0)] {
+        let actual = histogram.percentile(percentile).as_micros() as f64;
+        assert!((actual - expected).abs() / expected < 0.02, "{}", actual);
+    }
+
+    // Small values are exact
+    assert_eq!(histogram.percentile(0.5), Duration::from_micros(50));
+}
+
+#[test]
+fn test_merge() {
+    let mut fast = Histogram::new();
+    let mut slow = Histogram::new();
+
+    fast.record(Duration::from_micros(10));
+    slow.record(Duration::from_secs(2));
+    slow.record(Duration::from_secs(3600 * 24 * 365));
+    fast.merge(&slow);
+
+    assert_eq!(fast.len(), 3);
+    assert_eq!(fast.min(), Duration::from_micros(10));
+    assert_eq!(fast.max(), Duration::from_secs(3600 * 24 * 365));
+    assert_eq!(fast.percentile(100.0), fast.max());
+}
@@ -0,0 +1,88 @@
+use super::app::start_app;
+
+use loadgen::{run, LoadConfig};
+
+use std::time::Duration;
+
+fn config(target: String, keep_alive: bool) -> LoadConfig {
+    LoadConfig {
+        target,
+        connections: 4,
+        duration: Duration::from_millis(500),
+        keep_alive,
+        paths: vec![
+            ("/".into(), 3),
+            ("/large".into(), 1),
+            ("/missing".into(), 1),
+        ],
+        payload_sizes: vec![0, 1024],
+        timeout: Duration::from_secs(5),
+    }
+}
+
+#[test]
+fn test_keep_alive() {
+    let (target, shutdown) = start_app();
+    let report = run(&config(target, true)).unwrap();
+
+    assert!(report.requests() > 0);
+    assert_eq!(report.connections, 4);
+    assert_eq!(report.errors.connect, 0);
+    assert_eq!(report.errors.io, 0);
+    assert!(report.errors.client > 0);
+    assert_eq!(report.errors.server, 0);
+
+    shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_without_keep_alive() {
+    let (target, shutdown) = start_app();
+    let report = run(&config(target, false)).unwrap();
+
+    assert!(report.requests() > 0);
+    assert_eq!(report.connections, report.requests());
+    assert_eq!(report.errors.io, 0);
+
+    shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_report() {
+    let (target, shutdown) = start_app();
+    let report = run(&config(target.clone(), true)).unwrap();
+
+    let json = report.to_json();
+    assert_eq!(json["target"].as_str(), Some(target.as_str()));
+    assert_eq!(json["requests"].as_number(), Some(report.requests() as f64));
+    assert!(json["latency"]["p99"].as_number().is_some());
+    assert_eq!(json["errors"]["io"].as_number(), Some(0.0));
+
+    let human = report.to_string();
+    assert!(human.contains("4 concurrent, 4 established (keep-alive)"));
+    assert!(human.contains("p99.9"));
+
+    shutdown.send(()).unwrap();
+}
+
+#[test]
+fn test_invalid_config() {
+    let invalid = [
+        LoadConfig {
+            connections: 0,
+            ..Default::default()
+        },
+        LoadConfig {
+            paths: vec![("/".into(), 0)],
+            ..Default::default()
+        },
+        LoadConfig {
+            target: "https://127.0.0.1".into(),
+            ..Default::default()
+        },
+    ];
+
+    for config in invalid {
+        assert!(run(&config).is_err());
+    }
+}
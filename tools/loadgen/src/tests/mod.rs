@@ -0,0 +1,6 @@
+pub mod app;
+pub mod args;
+pub mod histogram;
+pub mod load;
+pub mod soak;
+pub mod throughput;
@@ -0,0 +1,118 @@
+//! A soak test which runs the sample app under sustained load while sampling the memory usage of the
+//!   process, so that leaks are caught before release.
+//!
+//! This takes a few minutes, so it is ignored by default. Run it with
+//!   `cargo test -p loadgen --release -- --ignored soak`, optionally setting `LOADGEN_SOAK_SECONDS`
+//!   to change how long the load is applied for. It is only supported on Linux, where the resident
+//!   set size can be read from `/proc`.
+
+use super::app::start_app;
+
+use loadgen::{run, LoadConfig};
+
+use std::env::var;
+use std::fs::read_to_string;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{sleep, spawn};
+use std::time::Duration;
+
+/// The default length of the soak test, in seconds.
+const DEFAULT_SECONDS: u64 = 180;
+
+/// The interval between samples of the resident set size.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The fraction of the samples taken while the app warms up, which are excluded from the band.
+const WARM_UP_FRACTION: usize = 5;
+
+/// The minimum growth permitted beyond the baseline, which absorbs allocator noise.
+const MIN_GROWTH: u64 = 16 * 1024 * 1024;
+
+/// Reads the resident set size of the current process in bytes, if supported by the platform.
+fn rss() -> Option<u64> {
+    let status = read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+
+    Some(kilobytes * 1024)
+}
+
+#[test]
+#[ignore]
+fn soak() {
+    if rss().is_none() {
+        eprintln!("Skipping soak test, the resident set size cannot be read on this platform");
+        return;
+    }
+
+    let seconds = var("LOADGEN_SOAK_SECONDS")
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .unwrap_or(DEFAULT_SECONDS);
+
+    let (target, shutdown) = start_app();
+
+    let running = Arc::new(AtomicBool::new(true));
+    let cloned_running = running.clone();
+    let sampler = spawn(move || {
+        let mut samples: Vec<u64> = Vec::new();
+
+        while cloned_running.load(Ordering::SeqCst) {
+            samples.extend(rss());
+            sleep(SAMPLE_INTERVAL);
+        }
+
+        samples
+    });
+
+    let report = run(&LoadConfig {
+        target,
+        connections: 16,
+        duration: Duration::from_secs(seconds),
+        keep_alive: true,
+        paths: vec![
+            ("/".into(), 8),
+            ("/large".into(), 1),
+            ("/echo".into(), 2),
+            ("/missing".into(), 1),
+        ],
+        payload_sizes: vec![0, 512, 16 * 1024],
+        timeout: Duration::from_secs(5),
+    })
+    .unwrap();
+
+    running.store(false, Ordering::SeqCst);
+    let samples = sampler.join().unwrap();
+    shutdown.send(()).unwrap();
+
+    println!("{}", report);
+
+    assert!(report.requests() > 0, "No requests were completed");
+    assert_eq!(report.errors.connect, 0, "Connections were refused");
+    assert_eq!(report.errors.io, 0, "Requests failed");
+    assert_eq!(report.errors.server, 0, "The app returned server errors");
+
+    // The baseline is the largest sample once the app has warmed up, and every later sample must
+    //   stay within a band above it
+    let warm_up = (samples.len() / WARM_UP_FRACTION).max(1);
+    let baseline = samples.iter().take(warm_up).copied().max().unwrap();
+    let limit = baseline + (baseline / 4).max(MIN_GROWTH);
+    let peak = samples.iter().copied().max().unwrap();
+
+    println!(
+        "Memory:       baseline {} KiB, peak {} KiB, limit {} KiB over {} samples",
+        baseline / 1024,
+        peak / 1024,
+        limit / 1024,
+        samples.len()
+    );
+
+    assert!(
+        peak <= limit,
+        "Memory grew from {} KiB to {} KiB, beyond the limit of {} KiB",
+        baseline / 1024,
+        peak / 1024,
+        limit / 1024
+    );
+}
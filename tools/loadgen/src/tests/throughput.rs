@@ -0,0 +1,45 @@
+//! A benchmark of the requests per second the sample app serves with monitoring disabled, which
+//!   is the path every request takes in most deployments.
+//!
+//! It is ignored by default since the result depends on the machine. Run it in release mode with
+//!   `cargo test -p loadgen --release -- --ignored throughput --nocapture` on two commits to compare
+//!   them, optionally setting `LOADGEN_THROUGHPUT_SECONDS` to change how long each run lasts.
+
+use super::app::start_app;
+
+use loadgen::{run, LoadConfig};
+
+use std::env::var;
+use std::time::Duration;
+
+/// The default length of the benchmark, in seconds.
+const DEFAULT_SECONDS: u64 = 10;
+
+#[test]
+#[ignore]
+fn throughput() {
+    let seconds = var("LOADGEN_THROUGHPUT_SECONDS")
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .unwrap_or(DEFAULT_SECONDS);
+
+    let (target, shutdown) = start_app();
+
+    let report = run(&LoadConfig {
+        target,
+        connections: 16,
+        duration: Duration::from_secs(seconds),
+        keep_alive: true,
+        paths: vec![("/".into(), 1)],
+        payload_sizes: vec![0],
+        timeout: Duration::from_secs(5),
+    })
+    .unwrap();
+
+    shutdown.send(()).unwrap();
+
+    println!("{}", report);
+
+    assert!(report.requests() > 0, "No requests were completed");
+    assert_eq!(report.errors.total(), 0, "Requests failed");
+}